@@ -12,6 +12,7 @@ use std::path::PathBuf;
 
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::FileAnalyzer;
@@ -109,7 +110,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 pkg_guard_pop,
                 "pkg_name_pop",
             );
-            add_edge(graph, pkg_name_pop, module_guard, 0);
+            add_edge(graph, pkg_name_pop, module_guard, Precedence::DEFAULT);
 
             // Common main
             let main = Some(npm_pkg.main)
@@ -125,7 +126,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
             //
             //     [pop pkg_name] -1-> [push main]* -> [push pkg_internal_name]
             //
-            add_edge(graph, pkg_name_pop, main_push, 0);
+            add_edge(graph, pkg_name_pop, main_push, Precedence::DEFAULT);
         }
 
         // reach dependencies via package internal name
@@ -158,7 +159,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 dep_guard_push,
                 &format!("dep_name_push[{}", i),
             );
-            add_edge(graph, dep_name_pop, dep_name_push, 0);
+            add_edge(graph, dep_name_pop, dep_name_push, Precedence::DEFAULT);
         }
 
         Ok(())
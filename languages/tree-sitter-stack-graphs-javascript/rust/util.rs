@@ -12,6 +12,7 @@ use std::path::PathBuf;
 
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 
 pub const MODULE_GUARD: &str = "GUARD:MODULE";
@@ -37,7 +38,7 @@ pub fn add_pop(
     let id = graph.new_node_id(file);
     let sym = graph.add_symbol(name);
     let node = graph.add_pop_symbol_node(id, sym, false).unwrap();
-    graph.add_edge(from, node, 0);
+    graph.add_edge(from, node, Precedence::DEFAULT);
     add_debug_name(graph, node, debug_name);
     node
 }
@@ -52,12 +53,17 @@ pub fn add_push(
     let id = graph.new_node_id(file);
     let sym = graph.add_symbol(name);
     let node = graph.add_push_symbol_node(id, sym, false).unwrap();
-    graph.add_edge(node, to, 0);
+    graph.add_edge(node, to, Precedence::DEFAULT);
     add_debug_name(graph, node, debug_name);
     node
 }
 
-pub fn add_edge(graph: &mut StackGraph, from: Handle<Node>, to: Handle<Node>, precedence: i32) {
+pub fn add_edge(
+    graph: &mut StackGraph,
+    from: Handle<Node>,
+    to: Handle<Node>,
+    precedence: Precedence,
+) {
     if from == to {
         return;
     }
@@ -12,6 +12,7 @@ use std::path::PathBuf;
 
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::FileAnalyzer;
@@ -57,7 +58,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 proj_name,
                 "npm_package.proj_def",
             );
-            add_edge(graph, proj_def, proj_scope, 0);
+            add_edge(graph, proj_def, proj_scope, Precedence::DEFAULT);
 
             // project reference
             let proj_ref = add_ns_push(
@@ -68,7 +69,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 proj_name,
                 "npm_package.proj_ref",
             );
-            add_edge(graph, proj_scope, proj_ref, 0);
+            add_edge(graph, proj_scope, proj_ref, Precedence::DEFAULT);
 
             proj_scope
         } else {
@@ -86,7 +87,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 "npm_package.pkg_def",
             );
             let pkg_ref = add_push(graph, file, proj_scope, PKG_M_NS, "npm_package.pkg_ref");
-            add_edge(graph, pkg_def, pkg_ref, 0);
+            add_edge(graph, pkg_def, pkg_ref, Precedence::DEFAULT);
 
             let main = Some(npm_pkg.main)
                 .filter(|main| !main.is_empty())
@@ -96,7 +97,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 .with_extension("");
             let main_ref =
                 add_module_pushes(graph, file, M_NS, &main, proj_scope, "npm_package.main_ref");
-            add_edge(graph, pkg_def, main_ref, 0);
+            add_edge(graph, pkg_def, main_ref, Precedence::DEFAULT);
         }
 
         // dependencies (package references)
@@ -117,7 +118,7 @@ impl FileAnalyzer for NpmPackageAnalyzer {
                 root,
                 &format!("npm_package.dep[{}]", i),
             );
-            add_edge(graph, pkg_def, pkg_ref, 0);
+            add_edge(graph, pkg_def, pkg_ref, Precedence::DEFAULT);
         }
 
         Ok(())
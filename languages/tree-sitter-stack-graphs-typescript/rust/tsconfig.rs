@@ -13,6 +13,7 @@ use std::path::PathBuf;
 
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::FileAnalyzer;
@@ -51,11 +52,11 @@ impl FileAnalyzer for TsConfigAnalyzer {
 
             // project definition
             let proj_def = add_ns_pop(graph, file, root, PROJ_NS, proj_name, "tsconfig.proj_def");
-            add_edge(graph, proj_def, proj_scope, 0);
+            add_edge(graph, proj_def, proj_scope, Precedence::DEFAULT);
 
             // project reference
             let proj_ref = add_ns_push(graph, file, root, PROJ_NS, proj_name, "tsconfig.proj_ref");
-            add_edge(graph, proj_scope, proj_ref, 0);
+            add_edge(graph, proj_scope, proj_ref, Precedence::DEFAULT);
 
             proj_scope
         } else {
@@ -72,7 +73,7 @@ impl FileAnalyzer for TsConfigAnalyzer {
             proj_scope,
             "tsconfig.root_dir.ref",
         );
-        add_edge(graph, pkg_def, root_dir_ref, 0);
+        add_edge(graph, pkg_def, root_dir_ref, Precedence::DEFAULT);
 
         // auxiliary root directories, map relative imports to module paths
         for (idx, root_dir) in tsc.root_dirs().iter().enumerate() {
@@ -91,7 +92,7 @@ impl FileAnalyzer for TsConfigAnalyzer {
                 proj_scope,
                 &format!("tsconfig.root_dirs[{}].ref", idx),
             );
-            add_edge(graph, root_dir_def, root_dir_ref, 0);
+            add_edge(graph, root_dir_def, root_dir_ref, Precedence::DEFAULT);
         }
 
         // base URL
@@ -111,7 +112,7 @@ impl FileAnalyzer for TsConfigAnalyzer {
             proj_scope,
             "tsconfig.base_url.ref",
         );
-        add_edge(graph, base_url_def, base_url_ref, 0);
+        add_edge(graph, base_url_def, base_url_ref, Precedence::DEFAULT);
 
         // path mappings
         for (from_idx, (from, tos)) in tsc.paths().iter().enumerate() {
@@ -152,7 +153,7 @@ impl FileAnalyzer for TsConfigAnalyzer {
                     proj_scope,
                     &format!("tsconfig.paths[{}][{}].to_ref", from_idx, to_idx),
                 );
-                add_edge(graph, from_def, to_ref, 0);
+                add_edge(graph, from_def, to_ref, Precedence::DEFAULT);
             }
         }
 
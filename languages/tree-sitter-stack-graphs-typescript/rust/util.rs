@@ -11,6 +11,7 @@ use std::path::Path;
 
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 
 pub const M_NS: &str = "%M";
@@ -35,7 +36,7 @@ pub fn add_pop(
     let id = graph.new_node_id(file);
     let sym = graph.add_symbol(name);
     let node = graph.add_pop_symbol_node(id, sym, false).unwrap();
-    graph.add_edge(from, node, 0);
+    graph.add_edge(from, node, Precedence::DEFAULT);
     add_debug_name(graph, node, debug_name);
     node
 }
@@ -50,7 +51,7 @@ pub fn add_push(
     let id = graph.new_node_id(file);
     let sym = graph.add_symbol(name);
     let node = graph.add_push_symbol_node(id, sym, false).unwrap();
-    graph.add_edge(node, to, 0);
+    graph.add_edge(node, to, Precedence::DEFAULT);
     add_debug_name(graph, node, debug_name);
     node
 }
@@ -81,7 +82,12 @@ pub fn add_ns_push(
     push_node
 }
 
-pub fn add_edge(graph: &mut StackGraph, from: Handle<Node>, to: Handle<Node>, precedence: i32) {
+pub fn add_edge(
+    graph: &mut StackGraph,
+    from: Handle<Node>,
+    to: Handle<Node>,
+    precedence: Precedence,
+) {
     if from == to {
         return;
     }
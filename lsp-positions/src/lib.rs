@@ -24,12 +24,17 @@
 //! and the UTF-16 code unit position within the line, which can be used to generate `Position`
 //! values for LSP.
 //!
+//! Use a [`LineIndex`][] to convert between UTF-8 byte offsets, UTF-16 code unit offsets, and
+//! grapheme offsets, instead of hand-rolling these conversions.
+//!
 //! [lsp]: https://microsoft.github.io/language-server-protocol/
 //! [lsp-utf16]: https://microsoft.github.io/language-server-protocol/specifications/specification-current/#textDocuments
 
+use std::collections::HashMap;
 use std::ops::Range;
 
 use memchr::memchr;
+use memchr::memchr_iter;
 
 use unicode_segmentation::UnicodeSegmentation as _;
 
@@ -46,6 +51,11 @@ fn utf16_len(string: &str) -> usize {
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Position {
     /// The 0-indexed line number containing the character
     pub line: usize,
@@ -61,6 +71,11 @@ pub struct Position {
 }
 
 impl Position {
+    /// Returns the UTF-8 byte offset of this position within the file.
+    pub fn utf8_offset(&self) -> usize {
+        self.containing_line.start + self.column.utf8_offset
+    }
+
     /// Returns a tree-sitter [`Point`][Point] for this position.
     ///
     /// [Point]: https://docs.rs/tree-sitter/*/tree_sitter/struct.Point.html
@@ -110,12 +125,22 @@ impl PartialOrd<tree_sitter::Point> for Position {
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
 }
 
 impl Span {
+    /// Returns the UTF-8 byte range of this span within the file.
+    pub fn utf8_range(&self) -> Range<usize> {
+        self.start.utf8_offset()..self.end.utf8_offset()
+    }
+
     pub fn contains(&self, position: &Position) -> bool {
         &self.start <= position && &self.end > position
     }
@@ -147,6 +172,11 @@ impl PartialOrd for Span {
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Offset {
     /// The number of UTF-8-encoded bytes appearing before this character in the string
     pub utf8_offset: usize,
@@ -442,3 +472,137 @@ impl<'a> SpanCalculator<'a> {
         offset
     }
 }
+
+/// An index of the lines in a string, which converts between UTF-8 byte offsets, UTF-16 code unit
+/// offsets (used by LSP), and grapheme offsets.
+///
+/// The line boundaries are calculated when the index is created.  The column offsets of a line are
+/// calculated the first time a position on that line is requested, and cached for later requests.
+/// It is therefore efficient to reuse a single index for all positions in a file.
+pub struct LineIndex<'a> {
+    string: &'a str,
+    line_starts: Vec<usize>,
+    columns: HashMap<usize, Vec<Offset>>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Creates a new line index for the given string.
+    pub fn new(string: &'a str) -> LineIndex<'a> {
+        let line_starts = std::iter::once(0)
+            .chain(memchr_iter(b'\n', string.as_bytes()).map(|offset| offset + 1))
+            .collect();
+        LineIndex {
+            string,
+            line_starts,
+            columns: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of lines in the string.  A string ending in a newline has an empty last
+    /// line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the UTF-8 byte offsets (within the string) of the start and end of the given
+    /// 0-indexed line, excluding the newline.
+    pub fn line_bounds(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(line)?;
+        let end = match self.line_starts.get(line + 1) {
+            Some(next_start) => next_start - 1,
+            None => self.string.len(),
+        };
+        Some(start..end)
+    }
+
+    /// Returns the 0-indexed line containing the given UTF-8 byte offset.
+    pub fn line_for_utf8_offset(&self, utf8_offset: usize) -> Option<usize> {
+        if utf8_offset > self.string.len() {
+            return None;
+        }
+        match self.line_starts.binary_search(&utf8_offset) {
+            Ok(line) => Some(line),
+            Err(next_line) => Some(next_line - 1),
+        }
+    }
+
+    /// Returns the position of the character at the given UTF-8 byte offset within the string.
+    /// Returns `None` if the offset is out of bounds, or not at a character boundary.
+    pub fn position_for_utf8_offset(&mut self, utf8_offset: usize) -> Option<Position> {
+        let line = self.line_for_utf8_offset(utf8_offset)?;
+        let column_utf8_offset = utf8_offset - self.line_starts[line];
+        self.position(line, |columns| {
+            columns
+                .binary_search_by_key(&column_utf8_offset, |offset| offset.utf8_offset)
+                .ok()
+        })
+    }
+
+    /// Returns the position of the character at the given UTF-16 code unit offset within the
+    /// given 0-indexed line.  This is the conversion needed for LSP positions.  Returns `None` if
+    /// the line or offset are out of bounds, or if the offset is in the middle of a character.
+    pub fn position_for_line_and_utf16(
+        &mut self,
+        line: usize,
+        utf16_offset: usize,
+    ) -> Option<Position> {
+        self.position(line, |columns| {
+            columns
+                .binary_search_by_key(&utf16_offset, |offset| offset.utf16_offset)
+                .ok()
+        })
+    }
+
+    /// Returns the position of the first character of the grapheme at the given grapheme offset
+    /// within the given 0-indexed line.  Returns `None` if the line or offset are out of bounds.
+    pub fn position_for_line_and_grapheme(
+        &mut self,
+        line: usize,
+        grapheme_offset: usize,
+    ) -> Option<Position> {
+        self.position(line, |columns| {
+            // The characters following the first character of a multi-character grapheme have the
+            // offset of the next grapheme, so the first character of a grapheme is the last one
+            // with its offset.
+            let index = columns
+                .partition_point(|offset| offset.grapheme_offset <= grapheme_offset)
+                .checked_sub(1)?;
+            if columns[index].grapheme_offset == grapheme_offset {
+                Some(index)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the span for the given UTF-8 byte range within the string.  Returns `None` if the
+    /// range is out of bounds, or not at character boundaries.
+    pub fn span_for_utf8_range(&mut self, utf8_range: Range<usize>) -> Option<Span> {
+        let start = self.position_for_utf8_offset(utf8_range.start)?;
+        let end = self.position_for_utf8_offset(utf8_range.end)?;
+        Some(Span { start, end })
+    }
+
+    /// Returns the position on the given line for the column offset selected by `find`, which
+    /// returns an index into the cached column offsets of the line.
+    fn position<F>(&mut self, line: usize, find: F) -> Option<Position>
+    where
+        F: FnOnce(&[Offset]) -> Option<usize>,
+    {
+        let bounds = self.line_bounds(line)?;
+        let content = &self.string[bounds.clone()];
+        let columns: &[Offset] = self
+            .columns
+            .entry(line)
+            .or_insert_with(|| Offset::all_chars(content).collect());
+        let column = columns[find(columns)?];
+        let mut trimmed_line = PositionedSubstring::from_range(self.string, bounds.clone());
+        trimmed_line.trim_whitespace();
+        Some(Position {
+            line,
+            column,
+            containing_line: bounds,
+            trimmed_line: trimmed_line.utf8_bounds,
+        })
+    }
+}
@@ -7,6 +7,7 @@
 
 use unicode_segmentation::UnicodeSegmentation as _;
 
+use lsp_positions::LineIndex;
 use lsp_positions::Offset;
 
 fn check_offsets(line: &str) {
@@ -47,3 +48,46 @@ fn can_calculate_column_offsets() {
     check_offsets("print '❤️', b, '👨‍👨‍👧', c");
     check_offsets("print '✨✨✨', d");
 }
+
+#[test]
+fn can_convert_offsets_with_line_index() {
+    let source = "a = 1\n  b = '👨‍👨‍👧' + c\n";
+    let mut index = LineIndex::new(source);
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line_bounds(1), Some(6..36));
+
+    // `c` is at byte 29, UTF-16 code unit 19, and grapheme 12 of the second line
+    let c_utf8_offset = source.find('c').unwrap();
+    let position = index.position_for_utf8_offset(c_utf8_offset).unwrap();
+    assert_eq!(position.line, 1);
+    assert_eq!(position.column.utf8_offset, 29);
+    assert_eq!(position.column.utf16_offset, 19);
+    assert_eq!(position.column.grapheme_offset, 12);
+    assert_eq!(position.containing_line, 6..36);
+    assert_eq!(position.trimmed_line, 8..36);
+    assert_eq!(position.utf8_offset(), c_utf8_offset);
+
+    assert_eq!(
+        index.position_for_line_and_utf16(1, 19),
+        Some(position.clone())
+    );
+    assert_eq!(index.position_for_line_and_grapheme(1, 12), Some(position));
+
+    // positions inside the emoji are not valid
+    assert_eq!(index.position_for_line_and_utf16(1, 8), None);
+    assert_eq!(
+        index.position_for_utf8_offset(source.find('👨').unwrap() + 1),
+        None
+    );
+    // the emoji is a single grapheme
+    let emoji = index.position_for_line_and_grapheme(1, 7).unwrap();
+    assert_eq!(emoji.utf8_offset(), source.find('👨').unwrap());
+
+    let span = index.span_for_utf8_range(0..5).unwrap();
+    assert_eq!(span.utf8_range(), 0..5);
+    assert_eq!(&source[span.utf8_range()], "a = 1");
+
+    assert_eq!(index.line_for_utf8_offset(source.len()), Some(2));
+    assert_eq!(index.line_for_utf8_offset(source.len() + 1), None);
+    assert_eq!(index.position_for_line_and_utf16(3, 0), None);
+}
@@ -59,6 +59,19 @@ enum sg_result {
     SG_RESULT_CANCELLED,
 };
 
+#if defined(SG_FEATURE_VISUALIZATION)
+// How a visualization reports clicks on nodes to the page or application that hosts it, so that
+// it can jump to the source of the node.
+enum sg_source_navigation {
+    // Clicks are not reported.
+    SG_SOURCE_NAVIGATION_NONE,
+    // A navigation message is posted to the parent window.
+    SG_SOURCE_NAVIGATION_POST_MESSAGE,
+    // A URL built from the `url_template` of the visualization options is opened.
+    SG_SOURCE_NAVIGATION_URL,
+};
+#endif
+
 // Manages the state of a collection of partial paths to be used in the path-stitching algorithm.
 struct sg_partial_path_arena;
 
@@ -500,6 +513,22 @@ struct sg_forward_partial_path_stitcher {
     bool is_complete;
 };
 
+#if defined(SG_FEATURE_VISUALIZATION)
+// Options for the visualizations that are created by `sg_stack_graph_to_html`.
+struct sg_visualization_options {
+    // The files whose nodes, edges, and paths are included in the visualization.  If
+    // `file_count` is zero, all files are included.
+    const sg_file_handle *files;
+    size_t file_count;
+    // How the visualization reports clicks on nodes to its host.
+    enum sg_source_navigation source_navigation;
+    // The URL template that is used with `SG_SOURCE_NAVIGATION_URL`, as UTF-8.  It does not
+    // need to be NUL-terminated.
+    const char *url_template;
+    size_t url_template_length;
+};
+#endif
+
 // The handle of the singleton root node.
 #define SG_ROOT_NODE_HANDLE 1
 
@@ -768,9 +797,8 @@ struct sg_partial_paths sg_partial_path_database_partial_paths(const struct sg_p
 // We copy the partial path content into the partial path database.  The array you pass in does
 // not need to outlive the call to this function.
 //
-// You should take care not to add a partial path to the database multiple times.  This won't
-// cause an _error_, in that nothing will break, but it will probably cause you to get duplicate
-// paths from the path-stitching algorithm.
+// Identical partial paths are only stored once.  If a partial path is already in the database,
+// the handle of the existing partial path is returned for it.
 //
 // You must also provide an `out` array, which must also have room for `count` elements.  We will
 // fill this array in with the `sg_partial_path_edge_list` instances for each partial path edge
@@ -852,6 +880,29 @@ void sg_forward_partial_path_stitcher_process_next_phase(const struct sg_stack_g
 // Frees a forward path stitcher.
 void sg_forward_partial_path_stitcher_free(struct sg_forward_partial_path_stitcher *stitcher);
 
+#if defined(SG_FEATURE_VISUALIZATION)
+// Returns a self-contained HTML page that visualizes a stack graph and the partial paths in a
+// database.  The title is given as UTF-8, and does not need to be NUL-terminated.  If `db` is
+// null, no partial paths are included, and `partials` can be null as well.  If `options` is null,
+// all files are included, and clicks on nodes are not reported.
+//
+// The page is returned as a NUL-terminated UTF-8 string, which you must free with
+// `sg_html_free`.  Returns null if the title or URL template is not valid UTF-8, or if the page
+// could not be created.
+char *sg_stack_graph_to_html(const struct sg_stack_graph *graph,
+                             struct sg_partial_path_arena *partials,
+                             struct sg_partial_path_database *db,
+                             const char *title,
+                             size_t title_length,
+                             const struct sg_visualization_options *options);
+#endif
+
+#if defined(SG_FEATURE_VISUALIZATION)
+// Frees an HTML page that was returned by `sg_stack_graph_to_html`.  Passing null, which
+// `sg_stack_graph_to_html` returns on failure, is a no-op.
+void sg_html_free(char *html);
+#endif
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
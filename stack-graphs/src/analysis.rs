@@ -0,0 +1,1725 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines workspace-wide analyses over the stack graphs and partial paths in a database.
+//!
+//! The main analysis is [`find_dead_definitions`][], which reports definitions that no reference
+//! in the database resolves to.  Definitions that are reachable from the root node are considered
+//! _exported_, because they can be referenced from code that is not part of the database, and are
+//! not reported unless explicitly requested.
+//!
+//! The [`find_unresolved_references`][] analysis reports references that do not resolve to any
+//! definition in the database, which measures how much of the code name resolution covers.
+//!
+//! The [`rename`][] analysis computes the text edits that rename a definition and all references
+//! that resolve to it, and reports conflicts where the new name would resolve to a different
+//! definition.
+//!
+//! The [`callers_of`][] and [`callees_of`][] analyses approximate the call hierarchy of a
+//! definition, from references that are calls according to their syntax type.
+//!
+//! The [`import_graph`][] analysis computes the dependencies between files from the references
+//! that resolve to definitions in other files, and detects import cycles.
+//!
+//! The [`group_references_by_definition`][] analysis groups the references in a single file by the
+//! definitions they resolve to, which outlines the definitions a file depends on, and the files
+//! that must be read to understand it.
+//!
+//! The [`resolve_all_references`][] job resolves every reference in the database, and stores the
+//! resolutions in the database, so that they can be looked up without stitching.
+//!
+//! The [`binding_drift`][] analysis compares the resolutions in two databases, such as the
+//! databases for the versions before and after a commit, and reports the references whose
+//! resolutions changed.
+//!
+//! The [`symbol_contexts`][] extraction describes every definition in a file by its qualified
+//! name, kind, signature, and doc comment, in a form that is suitable for feeding code search or
+//! embedding pipelines.
+//!
+//! The analyses that consider the whole database, which are [`find_dead_definitions`][],
+//! [`find_unresolved_references`][], [`rename`][], [`callers_of`][], [`import_graph`][], and
+//! [`binding_drift`][], load the graphs of all indexed files into the reader, which makes them
+//! expensive for large databases.  The other analyses only load the data they need.
+//!
+//! The language configurations of the files in a database are not known, so references are
+//! resolved with the default [stitcher configuration][StitcherConfig], unless the analysis takes
+//! one as an argument.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use itertools::Itertools;
+
+use crate::arena::Handle;
+use crate::graph::DefinitionKind;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::graph::Symbol;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::Database;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::GraphEdgeCandidates;
+use crate::stitching::StitcherConfig;
+use crate::storage::FileStatus;
+use crate::storage::SQLiteReader;
+use crate::storage::StorageError;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// Configures which definitions are reported by [`find_dead_definitions`][].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeadDefinitionsConfig {
+    include_exported: bool,
+    stitcher: StitcherConfig,
+}
+
+impl DeadDefinitionsConfig {
+    /// Returns whether unused definitions that are reachable from the root node are reported.
+    pub fn include_exported(&self) -> bool {
+        self.include_exported
+    }
+
+    /// Sets whether unused definitions that are reachable from the root node are reported.
+    pub fn with_include_exported(mut self, include_exported: bool) -> Self {
+        self.include_exported = include_exported;
+        self
+    }
+
+    /// Returns the configuration of the stitcher that resolves the references in the database.
+    pub fn stitcher_config(&self) -> StitcherConfig {
+        self.stitcher
+    }
+
+    /// Sets the configuration of the stitcher that resolves the references in the database.
+    pub fn with_stitcher_config(mut self, stitcher: StitcherConfig) -> Self {
+        self.stitcher = stitcher;
+        self
+    }
+}
+
+/// A definition that no reference in the database resolves to.
+#[derive(Clone, Debug)]
+pub struct DeadDefinition {
+    /// The definition node.  The handle is valid for the graph of the reader that was analyzed.
+    pub node: Handle<Node>,
+    /// The file containing the definition.
+    pub file: PathBuf,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The source span of the definition, if available.
+    pub span: Option<lsp_positions::Span>,
+    /// The kind of the definition, if available.
+    pub kind: Option<DefinitionKind>,
+    /// Whether the definition is reachable from the root node.
+    pub exported: bool,
+}
+
+/// Finds all definitions in the indexed files of the database that are not the end of any complete
+/// path starting at a reference in the database.
+///
+/// Results are ordered by file, and by node within each file.
+pub fn find_dead_definitions(
+    db: &mut SQLiteReader,
+    config: &DeadDefinitionsConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<DeadDefinition>, StorageError> {
+    let files = indexed_files(db)?;
+    for file in &files {
+        cancellation_flag.check("loading partial paths")?;
+        db.load_partial_paths_for_file(file, cancellation_flag)?;
+    }
+
+    let exported = {
+        let (graph, _, database) = db.get();
+        exported_nodes(graph, database)
+    };
+
+    let resolved = resolve_references(db, &files, config.stitcher, cancellation_flag)?
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .collect::<HashSet<_>>();
+
+    let (graph, _, _) = db.get();
+    let mut results = Vec::new();
+    for file_name in &files {
+        let file = match graph.get_file(file_name) {
+            Some(file) => file,
+            None => continue,
+        };
+        for node in graph.nodes_for_file(file) {
+            if !graph[node].is_definition() || resolved.contains(&node) {
+                continue;
+            }
+            let is_exported = exported.contains(&node);
+            if is_exported && !config.include_exported {
+                continue;
+            }
+            let symbol = match graph[node].symbol() {
+                Some(symbol) => graph[symbol].to_string(),
+                None => continue,
+            };
+            results.push(DeadDefinition {
+                node,
+                file: PathBuf::from(file_name),
+                symbol,
+                span: graph.source_info(node).map(|info| info.span.clone()),
+                kind: graph.definition_kind(node),
+                exported: is_exported,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// A reference that does not resolve to any definition in the database.
+#[derive(Clone, Debug)]
+pub struct UnresolvedReference {
+    /// The reference node.  The handle is valid for the graph of the reader that was analyzed.
+    pub node: Handle<Node>,
+    /// The file containing the reference.
+    pub file: PathBuf,
+    /// The symbol of the reference.
+    pub symbol: String,
+    /// The source span of the reference, if available.
+    pub span: Option<lsp_positions::Span>,
+}
+
+/// Finds all references in the indexed files of the database that are not the start of any
+/// complete path.
+///
+/// Results are ordered by file, and by node within each file.
+pub fn find_unresolved_references(
+    db: &mut SQLiteReader,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<UnresolvedReference>, StorageError> {
+    let files = indexed_files(db)?;
+    for file in &files {
+        cancellation_flag.check("loading partial paths")?;
+        db.load_partial_paths_for_file(file, cancellation_flag)?;
+    }
+
+    let resolved = resolve_references(db, &files, config, cancellation_flag)?
+        .into_iter()
+        .map(|(reference, _)| reference)
+        .collect::<HashSet<_>>();
+
+    let (graph, _, _) = db.get();
+    let mut results = Vec::new();
+    for file_name in &files {
+        let file = match graph.get_file(file_name) {
+            Some(file) => file,
+            None => continue,
+        };
+        for node in graph.nodes_for_file(file) {
+            if !graph[node].is_reference() || resolved.contains(&node) {
+                continue;
+            }
+            let symbol = match graph[node].symbol() {
+                Some(symbol) => graph[symbol].to_string(),
+                None => continue,
+            };
+            results.push(UnresolvedReference {
+                node,
+                file: PathBuf::from(file_name),
+                symbol,
+                span: graph.source_info(node).map(|info| info.span.clone()),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Returns all nodes that are reachable from the root node by following the partial paths in the
+/// database.
+fn exported_nodes(graph: &StackGraph, database: &Database) -> HashSet<Handle<Node>> {
+    let paths = database
+        .iter_partial_paths()
+        .map(|handle| (database[handle].start_node, database[handle].end_node))
+        .collect::<Vec<_>>();
+    let mut reachable = HashSet::new();
+    reachable.insert(StackGraph::root_node());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (start_node, end_node) in &paths {
+            if reachable.contains(start_node) && reachable.insert(*end_node) {
+                changed = true;
+            }
+        }
+    }
+    reachable.retain(|node| graph[*node].is_definition());
+    reachable
+}
+
+/// A replacement of a span of text in a source file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TextEdit {
+    /// The file to edit.
+    pub file: PathBuf,
+    /// The span of the text to replace.
+    pub span: lsp_positions::Span,
+    /// The text to replace the span with.
+    pub replacement: String,
+}
+
+/// A renamed reference for which the new name resolves to a definition that is not renamed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RenameConflict {
+    /// The file containing the reference.
+    pub file: PathBuf,
+    /// The source span of the reference.
+    pub span: lsp_positions::Span,
+    /// The file containing the conflicting definition.
+    pub definition_file: PathBuf,
+    /// The source span of the conflicting definition.
+    pub definition_span: lsp_positions::Span,
+}
+
+/// The result of a [`rename`][].
+#[derive(Clone, Debug, Default)]
+pub struct RenameResult {
+    /// The edits that rename the definitions and all their references, ordered by file and span.
+    pub edits: Vec<TextEdit>,
+    /// The references for which the new name would be shadowed by other definitions.
+    pub conflicts: Vec<RenameConflict>,
+}
+
+impl RenameResult {
+    /// Returns whether the edits can be applied without changing the meaning of any reference.
+    pub fn is_safe(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Computes the edits to rename the definition at the given position in a file to a new name.  If
+/// the position is at a reference, the definitions that the reference resolves to are renamed.  All
+/// references in the database that resolve to the renamed definitions are renamed as well.
+///
+/// The edits are returned together with any conflicts, where the new name at a renamed reference
+/// resolves to a definition that is not renamed.  It is up to the caller to decide whether edits
+/// with conflicts are applied.  If there is no definition or reference at the given position, the
+/// result is empty.
+pub fn rename(
+    db: &mut SQLiteReader,
+    file: &str,
+    position: &lsp_positions::Position,
+    new_name: &str,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<RenameResult, StorageError> {
+    let files = indexed_files(db)?;
+    if !files.iter().any(|f| f == file) {
+        return Ok(RenameResult::default());
+    }
+    for file in &files {
+        cancellation_flag.check("loading graphs")?;
+        db.load_graph_for_file(file)?;
+    }
+
+    let config = database_stitcher_config();
+    let resolutions = resolve_references(db, &files, config, cancellation_flag)?;
+
+    let (graph, _, _) = db.get();
+    let definitions = definitions_at_position(graph, file, position, &resolutions);
+    let mut references = resolutions
+        .iter()
+        .filter(|(_, definition)| definitions.contains(definition))
+        .map(|(reference, _)| *reference)
+        .collect::<Vec<_>>();
+    references.sort();
+    references.dedup();
+
+    let mut edits = definitions
+        .iter()
+        .chain(references.iter())
+        .filter_map(|node| {
+            let (file, span) = location(graph, *node)?;
+            Some(TextEdit {
+                file,
+                span,
+                replacement: new_name.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    edits.sort_by(|a, b| a.file.cmp(&b.file).then_with(|| a.span.cmp(&b.span)));
+    edits.dedup();
+
+    let conflicts = find_rename_conflicts(
+        db,
+        &references,
+        &definitions,
+        new_name,
+        config,
+        cancellation_flag,
+    )?;
+
+    Ok(RenameResult { edits, conflicts })
+}
+
+/// Finds the renamed references for which the new name resolves to a definition that is not
+/// renamed.  The new name is resolved from the reference nodes, using the edges of the loaded
+/// graphs, together with the paths that the references take to the renamed definitions, which
+/// define the new name after renaming.  Definitions of the new name that the renamed definitions
+/// shadow, according to the shadowing policy of the stitcher configuration, are not conflicts.
+fn find_rename_conflicts(
+    db: &mut SQLiteReader,
+    references: &[Handle<Node>],
+    definitions: &HashSet<Handle<Node>>,
+    new_name: &str,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<RenameConflict>, StorageError> {
+    let (graph, partials, _) = db.get();
+    let new_symbol = graph.add_symbol(new_name);
+    let graph = &*graph;
+
+    let mut conflicts = Vec::new();
+    let mut seen = HashSet::new();
+    for reference in references {
+        let (file, span) = match location(graph, *reference) {
+            Some(location) => location,
+            None => continue,
+        };
+        let old_symbol = match graph[*reference].symbol() {
+            Some(symbol) => symbol,
+            None => continue,
+        };
+        let mut paths = Vec::new();
+        find_complete_paths_as_symbol(
+            graph,
+            partials,
+            *reference,
+            old_symbol,
+            config,
+            cancellation_flag,
+            |path| {
+                if definitions.contains(&path.end_node) {
+                    paths.push(path.clone());
+                }
+            },
+        )?;
+        find_complete_paths_as_symbol(
+            graph,
+            partials,
+            *reference,
+            new_symbol,
+            config,
+            cancellation_flag,
+            |path| {
+                if !definitions.contains(&path.end_node) {
+                    paths.push(path.clone());
+                }
+            },
+        )?;
+        config
+            .shadowing_policy()
+            .remove_shadowed_paths(graph, partials, &mut paths);
+        for path in paths {
+            if definitions.contains(&path.end_node) || !seen.insert((*reference, path.end_node)) {
+                continue;
+            }
+            if let Some((definition_file, definition_span)) = location(graph, path.end_node) {
+                conflicts.push(RenameConflict {
+                    file: file.clone(),
+                    span: span.clone(),
+                    definition_file,
+                    definition_span,
+                });
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Finds the complete paths from a reference as if it referred to the given symbol, using the
+/// edges of the graph.  The rest of the symbol stack of the reference, such as the scopes that are
+/// attached to a scoped symbol, is kept, so that the symbol is resolved in the same context.
+fn find_complete_paths_as_symbol<F>(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    reference: Handle<Node>,
+    symbol: Handle<Symbol>,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+    mut visit: F,
+) -> Result<(), CancellationError>
+where
+    F: FnMut(&PartialPath),
+{
+    let mut initial_path = PartialPath::from_node(graph, partials, reference);
+    initial_path.eliminate_precondition_stack_variables(partials);
+    if let Some(mut top) = initial_path.symbol_stack_postcondition.pop_front(partials) {
+        top.symbol = symbol;
+        initial_path
+            .symbol_stack_postcondition
+            .push_front(partials, top);
+    }
+    let mut stitcher = ForwardPartialPathStitcher::from_partial_paths(
+        graph,
+        partials,
+        std::iter::once(initial_path),
+    );
+    config.apply(&mut stitcher);
+    stitcher.set_check_only_join_nodes(true);
+
+    let mut candidates = GraphEdgeCandidates::new(graph, partials, None);
+    while !stitcher.is_complete() {
+        cancellation_flag.check("finding rename conflicts")?;
+        stitcher.process_next_phase(&mut candidates, |_, _, _| true);
+        for path in stitcher.previous_phase_partial_paths() {
+            if path.is_complete(graph) {
+                visit(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the definition and reference nodes in the given file whose source span contains the
+/// given position.
+fn nodes_at_position<'a>(
+    graph: &'a StackGraph,
+    file: &str,
+    position: &'a lsp_positions::Position,
+) -> impl Iterator<Item = Handle<Node>> + 'a {
+    graph
+        .get_file(file)
+        .into_iter()
+        .flat_map(move |file| graph.nodes_for_file(file))
+        .filter(move |node| {
+            let node_data = &graph[*node];
+            if !node_data.is_definition() && !node_data.is_reference() {
+                return false;
+            }
+            graph
+                .source_info(*node)
+                .map_or(false, |info| info.span.contains(position))
+        })
+}
+
+/// Returns the definitions at the given position in a file, and the definitions that the
+/// references at the given position resolve to according to the given resolutions.
+fn definitions_at_position(
+    graph: &StackGraph,
+    file: &str,
+    position: &lsp_positions::Position,
+    resolutions: &[(Handle<Node>, Handle<Node>)],
+) -> HashSet<Handle<Node>> {
+    let mut definitions = HashSet::new();
+    for node in nodes_at_position(graph, file, position) {
+        if graph[node].is_definition() {
+            definitions.insert(node);
+        } else {
+            definitions.extend(
+                resolutions
+                    .iter()
+                    .filter(|(reference, _)| *reference == node)
+                    .map(|(_, definition)| *definition),
+            );
+        }
+    }
+    definitions
+}
+
+/// Returns the file and source span of a node, if it has a non-empty span.
+fn location(graph: &StackGraph, node: Handle<Node>) -> Option<(PathBuf, lsp_positions::Span)> {
+    let file = graph[node].id().file()?;
+    let span = &graph.source_info(node)?.span;
+    if span.start == span.end {
+        return None;
+    }
+    Some((PathBuf::from(graph[file].name()), span.clone()))
+}
+
+/// Configures which references are considered calls by [`callers_of`][] and [`callees_of`][].
+#[derive(Clone, Debug)]
+pub struct CallHierarchyConfig {
+    call_syntax_types: HashSet<String>,
+}
+
+impl CallHierarchyConfig {
+    /// Returns the syntax types of references that are calls.
+    pub fn call_syntax_types(&self) -> &HashSet<String> {
+        &self.call_syntax_types
+    }
+
+    /// Sets the syntax types of references that are calls.
+    pub fn with_call_syntax_types<I, S>(mut self, call_syntax_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.call_syntax_types = call_syntax_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns whether the given reference, which resolves to the given definition, is a call.  A
+    /// reference with a syntax type is a call if its syntax type is one of the call syntax types.
+    /// A reference without a syntax type is a call if it resolves to a function or method.
+    pub fn is_call(
+        &self,
+        graph: &StackGraph,
+        reference: Handle<Node>,
+        definition: Handle<Node>,
+    ) -> bool {
+        let syntax_type = graph
+            .source_info(reference)
+            .and_then(|info| info.syntax_type.into_option());
+        match syntax_type {
+            Some(syntax_type) => self.call_syntax_types.contains(&graph[syntax_type]),
+            None => matches!(
+                graph.definition_kind(definition),
+                Some(DefinitionKind::Function) | Some(DefinitionKind::Method)
+            ),
+        }
+    }
+}
+
+impl Default for CallHierarchyConfig {
+    fn default() -> Self {
+        Self {
+            call_syntax_types: ["call", "method_call"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// A definition in a call hierarchy, together with the calls that connect it to the definition
+/// whose hierarchy was requested.
+#[derive(Clone, Debug)]
+pub struct CallHierarchyItem {
+    /// The definition node.  The handle is valid for the graph of the reader that was analyzed.
+    pub node: Handle<Node>,
+    /// The file containing the definition.
+    pub file: PathBuf,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The source span of the definition, if available.
+    pub span: Option<lsp_positions::Span>,
+    /// The kind of the definition, if available.
+    pub kind: Option<DefinitionKind>,
+    /// The calls that connect this definition to the definition whose hierarchy was requested,
+    /// ordered by file and span.
+    pub call_sites: Vec<CallSite>,
+}
+
+/// The location of a call.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct CallSite {
+    /// The file containing the call.
+    pub file: PathBuf,
+    /// The source span of the call.
+    pub span: lsp_positions::Span,
+}
+
+impl CallHierarchyItem {
+    fn new(graph: &StackGraph, node: Handle<Node>, mut call_sites: Vec<CallSite>) -> Option<Self> {
+        let file = graph[node].id().file()?;
+        let symbol = graph[node].symbol()?;
+        call_sites.sort();
+        call_sites.dedup();
+        Some(Self {
+            node,
+            file: PathBuf::from(graph[file].name()),
+            symbol: graph[symbol].to_string(),
+            span: graph.source_info(node).map(|info| info.span.clone()),
+            kind: graph.definition_kind(node),
+            call_sites,
+        })
+    }
+}
+
+/// Finds the callers of the definition at the given position in a file.  If the position is at a
+/// reference, the callers of the definitions that the reference resolves to are returned.
+///
+/// The caller of a call is the innermost definition whose definiens contains the call.  Calls that
+/// are not contained in the definiens of any definition, such as calls at the top level of a
+/// file, are not reported.  Callers are ordered by file and span.
+pub fn callers_of(
+    db: &mut SQLiteReader,
+    file: &str,
+    position: &lsp_positions::Position,
+    config: &CallHierarchyConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<CallHierarchyItem>, StorageError> {
+    let files = indexed_files(db)?;
+    if !files.iter().any(|f| f == file) {
+        return Ok(Vec::new());
+    }
+    for file in &files {
+        cancellation_flag.check("loading graphs")?;
+        db.load_graph_for_file(file)?;
+    }
+
+    let stitcher_config = database_stitcher_config();
+    let resolutions = resolve_references(db, &files, stitcher_config, cancellation_flag)?;
+
+    let (graph, _, _) = db.get();
+    let definitions = definitions_at_position(graph, file, position, &resolutions);
+    let mut calls_per_caller = BTreeMap::<Handle<Node>, Vec<CallSite>>::new();
+    for (reference, definition) in &resolutions {
+        if !definitions.contains(definition) || !config.is_call(graph, *reference, *definition) {
+            continue;
+        }
+        let caller = match enclosing_definition(graph, *reference) {
+            Some(caller) => caller,
+            None => continue,
+        };
+        if let Some((file, span)) = location(graph, *reference) {
+            calls_per_caller
+                .entry(caller)
+                .or_default()
+                .push(CallSite { file, span });
+        }
+    }
+    Ok(call_hierarchy_items(graph, calls_per_caller))
+}
+
+/// Finds the callees of the definition at the given position in a file.  If the position is at a
+/// reference, the callees of the definitions that the reference resolves to are returned.
+///
+/// The callees of a definition are the definitions that the calls in its definiens resolve to.
+/// Callees are ordered by file and span.
+pub fn callees_of(
+    db: &mut SQLiteReader,
+    file: &str,
+    position: &lsp_positions::Position,
+    config: &CallHierarchyConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<CallHierarchyItem>, StorageError> {
+    if !matches!(db.status_for_file::<&str>(file, None)?, FileStatus::Indexed) {
+        return Ok(Vec::new());
+    }
+    db.load_graph_for_file(file)?;
+
+    let stitcher_config = database_stitcher_config();
+    let references = {
+        let (graph, _, _) = db.get();
+        nodes_at_position(graph, file, position)
+            .filter(|node| graph[*node].is_reference())
+            .collect::<Vec<_>>()
+    };
+    let resolutions = resolve(db, references, stitcher_config, cancellation_flag)?;
+
+    let calls = {
+        let (graph, _, _) = db.get();
+        let definitions = definitions_at_position(graph, file, position, &resolutions);
+        let mut calls = Vec::new();
+        for definition in &definitions {
+            let definiens = match graph.source_info(*definition) {
+                Some(info) if info.definiens_span.start != info.definiens_span.end => {
+                    &info.definiens_span
+                }
+                _ => continue,
+            };
+            let definition_file = match graph[*definition].id().file() {
+                Some(file) => file,
+                None => continue,
+            };
+            calls.extend(graph.nodes_for_file(definition_file).filter(|node| {
+                graph[*node].is_reference()
+                    && graph
+                        .source_info(*node)
+                        .map_or(false, |info| definiens.contains(&info.span.start))
+            }));
+        }
+        calls
+    };
+    let resolutions = resolve(db, calls, stitcher_config, cancellation_flag)?;
+
+    let (graph, _, _) = db.get();
+    let mut calls_per_callee = BTreeMap::<Handle<Node>, Vec<CallSite>>::new();
+    for (reference, definition) in &resolutions {
+        if !config.is_call(graph, *reference, *definition) {
+            continue;
+        }
+        if let Some((file, span)) = location(graph, *reference) {
+            calls_per_callee
+                .entry(*definition)
+                .or_default()
+                .push(CallSite { file, span });
+        }
+    }
+    Ok(call_hierarchy_items(graph, calls_per_callee))
+}
+
+/// Returns the innermost definition in the file of the given node whose definiens contains the
+/// start of the node's span.
+fn enclosing_definition(graph: &StackGraph, node: Handle<Node>) -> Option<Handle<Node>> {
+    let file = graph[node].id().file()?;
+    let position = &graph.source_info(node)?.span.start;
+    graph
+        .nodes_for_file(file)
+        .filter(|candidate| graph[*candidate].is_definition())
+        .filter_map(|candidate| {
+            let definiens = &graph.source_info(candidate)?.definiens_span;
+            if definiens.start == definiens.end || !definiens.contains(position) {
+                return None;
+            }
+            Some((candidate, definiens))
+        })
+        .max_by(|(_, a), (_, b)| a.start.cmp(&b.start).then_with(|| b.end.cmp(&a.end)))
+        .map(|(candidate, _)| candidate)
+}
+
+fn call_hierarchy_items(
+    graph: &StackGraph,
+    calls: BTreeMap<Handle<Node>, Vec<CallSite>>,
+) -> Vec<CallHierarchyItem> {
+    let mut items = calls
+        .into_iter()
+        .filter_map(|(node, call_sites)| CallHierarchyItem::new(graph, node, call_sites))
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| a.file.cmp(&b.file).then_with(|| a.span.cmp(&b.span)));
+    items
+}
+
+/// A file-level dependency graph, derived from references that resolve to definitions in other
+/// files.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportGraph {
+    /// All indexed files, in order.
+    pub files: Vec<PathBuf>,
+    /// The dependencies between files, ordered by file and dependency.
+    pub dependencies: Vec<FileDependency>,
+}
+
+/// A dependency of one file on another.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileDependency {
+    /// The file containing the references.
+    pub file: PathBuf,
+    /// The file containing the definitions that the references resolve to.
+    pub dependency: PathBuf,
+    /// The number of references in the file that resolve to definitions in the dependency.
+    pub references: usize,
+}
+
+impl ImportGraph {
+    /// Returns the files that the given file depends on.
+    pub fn dependencies_of<'a>(&'a self, file: &'a Path) -> impl Iterator<Item = &'a Path> + 'a {
+        self.dependencies
+            .iter()
+            .filter(move |d| d.file == file)
+            .map(|d| d.dependency.as_path())
+    }
+
+    /// Returns the strongly connected components of the graph, in reverse topological order, such
+    /// that every component only depends on itself and components that appear before it.  The
+    /// files within each component are ordered.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<PathBuf>> {
+        let indices = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file.as_path(), i))
+            .collect::<BTreeMap<_, _>>();
+        let mut successors = vec![Vec::new(); self.files.len()];
+        for dependency in &self.dependencies {
+            if let (Some(from), Some(to)) = (
+                indices.get(dependency.file.as_path()),
+                indices.get(dependency.dependency.as_path()),
+            ) {
+                successors[*from].push(*to);
+            }
+        }
+
+        let mut tarjan = Tarjan::new(&successors);
+        for node in 0..self.files.len() {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        tarjan
+            .components
+            .into_iter()
+            .map(|mut component| {
+                component.sort();
+                component
+                    .into_iter()
+                    .map(|i| self.files[i].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the import cycles in the graph, which are the strongly connected components that
+    /// consist of more than one file.
+    pub fn cycles(&self) -> Vec<Vec<PathBuf>> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .collect()
+    }
+
+    /// Renders the graph in the DOT format of Graphviz.  Edges are labeled with the number of
+    /// references they represent.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph imports {{").unwrap();
+        for file in &self.files {
+            writeln!(dot, "  {};", dot_string(file)).unwrap();
+        }
+        for dependency in &self.dependencies {
+            writeln!(
+                dot,
+                "  {} -> {} [label=\"{}\"];",
+                dot_string(&dependency.file),
+                dot_string(&dependency.dependency),
+                dependency.references
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+fn dot_string(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Tarjan's strongly connected components algorithm, using an explicit stack to support deep
+/// graphs.
+struct Tarjan<'a> {
+    successors: &'a [Vec<usize>],
+    next_index: usize,
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(successors: &'a [Vec<usize>]) -> Self {
+        let count = successors.len();
+        Self {
+            successors,
+            next_index: 0,
+            index: vec![None; count],
+            lowlink: vec![0; count],
+            on_stack: vec![false; count],
+            stack: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, root: usize) {
+        let mut work = vec![(root, 0usize)];
+        self.enter(root);
+        while let Some((node, next)) = work.pop() {
+            if let Some(&successor) = self.successors[node].get(next) {
+                work.push((node, next + 1));
+                match self.index[successor] {
+                    None => {
+                        self.enter(successor);
+                        work.push((successor, 0));
+                    }
+                    Some(index) if self.on_stack[successor] => {
+                        self.lowlink[node] = self.lowlink[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+            if self.lowlink[node] == self.index[node].unwrap() {
+                let mut component = Vec::new();
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+            if let Some((parent, _)) = work.last() {
+                self.lowlink[*parent] = self.lowlink[*parent].min(self.lowlink[node]);
+            }
+        }
+    }
+
+    fn enter(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.lowlink[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+    }
+}
+
+/// Computes the file-level dependency graph of the database, by resolving all references and
+/// recording the files of the definitions they resolve to.  References that resolve to definitions
+/// in the same file are not recorded.
+pub fn import_graph(
+    db: &mut SQLiteReader,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<ImportGraph, StorageError> {
+    let files = indexed_files(db)?;
+    for file in &files {
+        cancellation_flag.check("loading graphs")?;
+        db.load_graph_for_file(file)?;
+    }
+
+    let config = database_stitcher_config();
+    let mut resolutions = resolve_references(db, &files, config, cancellation_flag)?;
+    resolutions.sort();
+    resolutions.dedup();
+
+    let (graph, _, _) = db.get();
+    let mut counts = BTreeMap::<(PathBuf, PathBuf), usize>::new();
+    for (reference, definition) in resolutions {
+        let file = match graph[reference].id().file() {
+            Some(file) => file,
+            None => continue,
+        };
+        let dependency = match graph[definition].id().file() {
+            Some(dependency) => dependency,
+            None => continue,
+        };
+        if file == dependency {
+            continue;
+        }
+        *counts
+            .entry((
+                PathBuf::from(graph[file].name()),
+                PathBuf::from(graph[dependency].name()),
+            ))
+            .or_default() += 1;
+    }
+
+    Ok(ImportGraph {
+        files: files.into_iter().map(PathBuf::from).collect(),
+        dependencies: counts
+            .into_iter()
+            .map(|((file, dependency), references)| FileDependency {
+                file,
+                dependency,
+                references,
+            })
+            .collect(),
+    })
+}
+
+/// The references in a file that resolve to the same definition, as returned by
+/// [`group_references_by_definition`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReferenceGroup {
+    /// The definition node.  The handle is valid for the graph of the reader that was analyzed.
+    pub definition: Handle<Node>,
+    /// The file containing the definition.
+    pub file: PathBuf,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The source span of the definition, if available.
+    pub span: Option<lsp_positions::Span>,
+    /// The kind of the definition, if available.
+    pub kind: Option<DefinitionKind>,
+    /// Whether the definition is in a different file than the references.
+    pub external: bool,
+    /// The source spans of the references that resolve to the definition, in order.  References
+    /// without a source span are not included.
+    pub references: Vec<lsp_positions::Span>,
+}
+
+/// Resolves all references in the given file, and groups them by the definitions they resolve to.
+/// A reference that resolves to several definitions is included in the group of each of them.
+///
+/// Groups are ordered by file and span of the definition, so that the groups of external
+/// definitions are an outline of the external dependencies of the file, and list exactly the
+/// upstream files that must be read to understand it.  Only the graphs and partial paths that are
+/// needed to resolve the references are loaded into the reader.
+pub fn group_references_by_definition(
+    db: &mut SQLiteReader,
+    file: &str,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ReferenceGroup>, StorageError> {
+    if !matches!(db.status_for_file::<&str>(file, None)?, FileStatus::Indexed) {
+        return Ok(Vec::new());
+    }
+    db.load_graph_for_file(file)?;
+
+    let config = database_stitcher_config();
+    let mut resolutions = resolve_references(db, &[file.to_string()], config, cancellation_flag)?;
+    resolutions.sort();
+    resolutions.dedup();
+
+    let (graph, _, _) = db.get();
+    let mut references_per_definition = HashMap::<Handle<Node>, Vec<lsp_positions::Span>>::new();
+    for (reference, definition) in resolutions {
+        let references = references_per_definition.entry(definition).or_default();
+        if let Some((_, span)) = location(graph, reference) {
+            references.push(span);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (definition, mut references) in references_per_definition {
+        let definition_file = match graph[definition].id().file() {
+            Some(definition_file) => graph[definition_file].name(),
+            None => continue,
+        };
+        let symbol = match graph[definition].symbol() {
+            Some(symbol) => graph[symbol].to_string(),
+            None => continue,
+        };
+        references.sort();
+        groups.push(ReferenceGroup {
+            definition,
+            file: PathBuf::from(definition_file),
+            symbol,
+            span: location(graph, definition).map(|(_, span)| span),
+            kind: graph.definition_kind(definition),
+            external: definition_file != file,
+            references,
+        });
+    }
+    groups.sort_by(|a, b| {
+        (&a.file, &a.span, &a.symbol, a.definition).cmp(&(
+            &b.file,
+            &b.span,
+            &b.symbol,
+            b.definition,
+        ))
+    });
+    Ok(groups)
+}
+
+/// Configures a [`resolve_all_references`][] job.
+#[derive(Clone, Debug)]
+pub struct ResolveAllOptions {
+    batch_size: usize,
+    resume: bool,
+    stitcher: StitcherConfig,
+    language: Option<String>,
+}
+
+impl ResolveAllOptions {
+    /// Returns the maximum number of files that are resolved and stored together.  Larger
+    /// components of mutually dependent files are never split.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Sets the maximum number of files that are resolved and stored together.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns whether files with stored resolutions are skipped, which resumes an earlier job.
+    pub fn resume(&self) -> bool {
+        self.resume
+    }
+
+    /// Sets whether files with stored resolutions are skipped.  If not, all stored resolutions are
+    /// removed before the job starts.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Returns the configuration of the stitcher that resolves the references.
+    pub fn stitcher_config(&self) -> StitcherConfig {
+        self.stitcher
+    }
+
+    /// Sets the configuration of the stitcher that resolves the references.
+    pub fn with_stitcher_config(mut self, stitcher: StitcherConfig) -> Self {
+        self.stitcher = stitcher;
+        self
+    }
+
+    /// Returns the [language][crate::graph::StackGraph::set_file_language] of the files whose
+    /// references are resolved, or `None` if the references in all files are resolved.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Sets the language of the files whose references are resolved.  The references can still
+    /// resolve to definitions in files of other languages.
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.language = language;
+        self
+    }
+}
+
+impl Default for ResolveAllOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            resume: true,
+            stitcher: database_stitcher_config(),
+            language: None,
+        }
+    }
+}
+
+/// The progress of a [`resolve_all_references`][] job.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResolutionProgress {
+    /// The number of indexed files in the database.
+    pub files_total: usize,
+    /// The number of files that were skipped, because their resolutions were already stored.
+    pub files_skipped: usize,
+    /// The number of files that were resolved by this job so far.
+    pub files_resolved: usize,
+    /// The number of references that were resolved by this job so far.
+    pub references: usize,
+    /// The number of resolutions that were stored by this job so far.
+    pub resolutions: usize,
+}
+
+impl ResolutionProgress {
+    /// Returns whether all indexed files have stored resolutions.
+    pub fn is_complete(&self) -> bool {
+        self.files_skipped + self.files_resolved == self.files_total
+    }
+}
+
+/// Resolves all references in the database, and stores the resolutions in the database.
+///
+/// Files are resolved in batches, ordered such that files are resolved after the files they depend
+/// on, and mutually dependent files are resolved in the same batch.  The resolutions of each batch
+/// are stored in a single transaction, after which the loaded data is cleared from the reader.  If
+/// the job is cancelled, the batches that were completed remain stored, and a later job that
+/// [resumes][ResolveAllOptions::with_resume] only resolves the remaining files.  The progress is
+/// reported after every batch.
+pub fn resolve_all_references<F>(
+    db: &mut SQLiteReader,
+    options: &ResolveAllOptions,
+    cancellation_flag: &dyn CancellationFlag,
+    mut progress: F,
+) -> Result<ResolutionProgress, StorageError>
+where
+    F: FnMut(&ResolutionProgress),
+{
+    if !options.resume {
+        db.clean_resolutions()?;
+    }
+    let files = indexed_files_in_language(db, options.language())?;
+    let resolved_files = db.list_resolved_files()?;
+    let pending_files = files
+        .iter()
+        .filter(|file| !resolved_files.contains(*file))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut status = ResolutionProgress {
+        files_total: files.len(),
+        files_skipped: files.len() - pending_files.len(),
+        ..Default::default()
+    };
+    progress(&status);
+
+    for batch in dependency_ordered_batches(db, pending_files, options.batch_size)? {
+        cancellation_flag.check("resolving references")?;
+        for file in &batch {
+            db.load_graph_for_file(file)?;
+        }
+        let references = {
+            let (graph, _, _) = db.get();
+            batch
+                .iter()
+                .filter_map(|file| graph.get_file(file))
+                .flat_map(|file| graph.nodes_for_file(file))
+                .filter(|node| graph[*node].is_reference())
+                .collect::<Vec<_>>()
+        };
+        status.references += references.len();
+        // The files that participate in the paths of a reference are recorded, so that its
+        // resolutions are not fresh anymore if any of them changes.
+        let mut resolutions = Vec::new();
+        let mut dependencies = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            db,
+            references,
+            options.stitcher,
+            cancellation_flag,
+            |graph, partials, path| {
+                resolutions.push((path.start_node, path.end_node));
+                dependencies.extend(
+                    path.participating_files(graph, partials)
+                        .into_iter()
+                        .map(|file| (path.start_node, file)),
+                );
+            },
+        )?;
+        resolutions.sort();
+        resolutions.dedup();
+        dependencies.sort();
+        dependencies.dedup();
+        db.store_resolutions_for_files(&batch, &resolutions, &dependencies)?;
+        db.clear();
+
+        status.files_resolved += batch.len();
+        status.resolutions += resolutions.len();
+        progress(&status);
+    }
+    Ok(status)
+}
+
+/// Splits the given files into batches of at most the given size, such that files come after the
+/// files they depend on, and strongly connected components are not split.
+fn dependency_ordered_batches(
+    db: &mut SQLiteReader,
+    files: Vec<String>,
+    batch_size: usize,
+) -> Result<Vec<Vec<String>>, StorageError> {
+    let mut dependencies = Vec::new();
+    for file in &files {
+        for dependency in db.dependencies_of_file(Path::new(file))? {
+            dependencies.push(FileDependency {
+                file: PathBuf::from(file),
+                dependency,
+                references: 0,
+            });
+        }
+    }
+    let imports = ImportGraph {
+        files: files.into_iter().map(PathBuf::from).collect(),
+        dependencies,
+    };
+
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    for component in imports.strongly_connected_components() {
+        if !batch.is_empty() && batch.len() + component.len() > batch_size {
+            batches.push(std::mem::take(&mut batch));
+        }
+        batch.extend(
+            component
+                .into_iter()
+                .map(|file| file.to_string_lossy().to_string()),
+        );
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// Returns the names of all indexed files in the database, in order.
+fn indexed_files(db: &mut SQLiteReader) -> Result<Vec<String>, StorageError> {
+    indexed_files_in_language(db, None)
+}
+
+/// Returns the names of the indexed files of the given language in the database, or of all
+/// indexed files if no language is given, in order.
+fn indexed_files_in_language(
+    db: &mut SQLiteReader,
+    language: Option<&str>,
+) -> Result<Vec<String>, StorageError> {
+    let mut files = Vec::new();
+    for entry in db.list_all()?.try_iter()? {
+        let entry = entry?;
+        if language.is_some() && entry.language.as_deref() != language {
+            continue;
+        }
+        if let FileStatus::Indexed = entry.status {
+            files.push(entry.path.to_string_lossy().to_string());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Resolves all references in the given files, which must have been loaded, and returns the
+/// pairs of references and the definitions they resolve to.
+fn resolve_references(
+    db: &mut SQLiteReader,
+    files: &[String],
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<(Handle<Node>, Handle<Node>)>, StorageError> {
+    let references = {
+        let (graph, _, _) = db.get();
+        files
+            .iter()
+            .filter_map(|file| graph.get_file(file))
+            .flat_map(|file| graph.nodes_for_file(file))
+            .filter(|node| graph[*node].is_reference())
+            .collect::<Vec<_>>()
+    };
+    resolve(db, references, config, cancellation_flag)
+}
+
+/// Returns the stitcher configuration for resolving references in a database, whose language
+/// configurations are not known.
+fn database_stitcher_config() -> StitcherConfig {
+    StitcherConfig::default()
+}
+
+/// Resolves the given references, and returns the pairs of references and the definitions they
+/// resolve to.
+fn resolve(
+    db: &mut SQLiteReader,
+    references: Vec<Handle<Node>>,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<(Handle<Node>, Handle<Node>)>, StorageError> {
+    let mut resolutions = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        db,
+        references,
+        config,
+        cancellation_flag,
+        |_, _, path| {
+            resolutions.push((path.start_node, path.end_node));
+        },
+    )?;
+    Ok(resolutions)
+}
+
+/// A reference or definition in a [`binding_drift`][] report.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct BindingLocation {
+    /// The file containing the node.
+    pub file: PathBuf,
+    /// The symbol of the node.
+    pub symbol: String,
+    /// The source span of the node.
+    pub span: lsp_positions::Span,
+}
+
+/// How the resolution of a reference changed between two databases.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum DriftKind {
+    /// The reference resolves to different definitions.
+    Changed,
+    /// The reference did not exist or resolve before, and resolves now.
+    Appeared,
+    /// The reference resolved before, and does not exist or resolve anymore.
+    Disappeared,
+}
+
+/// A reference whose resolution changed between two databases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BindingDrift {
+    pub kind: DriftKind,
+    /// The reference, as it appears in the second database if it exists there, and as it appeared
+    /// in the first database otherwise.
+    pub reference: BindingLocation,
+    /// The definitions the reference resolved to in the first database, ordered by location.
+    pub before: Vec<BindingLocation>,
+    /// The definitions the reference resolves to in the second database, ordered by location.
+    pub after: Vec<BindingLocation>,
+}
+
+/// Compares the resolutions of all references in two databases, such as the databases for the
+/// versions before and after a commit, and returns the references whose resolutions changed,
+/// appeared, or disappeared.
+///
+/// Nodes cannot be identified by their spans or local IDs across versions, because these change
+/// whenever code is added earlier in the file.  References and definitions are instead identified
+/// by their file, their symbol, and their position among the nodes with the same symbol in the
+/// file.  A reference that is moved within its file therefore keeps its identity, as long as it is
+/// not reordered with other references to the same symbol.
+/// Results are ordered by reference.
+pub fn binding_drift(
+    before: &mut SQLiteReader,
+    after: &mut SQLiteReader,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<BindingDrift>, StorageError> {
+    let mut before = bindings(before, cancellation_flag)?;
+    let after = bindings(after, cancellation_flag)?;
+
+    let mut drifts = Vec::new();
+    for (key, (reference, after_definitions)) in after {
+        let before_definitions = before
+            .remove(&key)
+            .map(|(_, definitions)| definitions)
+            .unwrap_or_default();
+        let kind = match (before_definitions.is_empty(), after_definitions.is_empty()) {
+            (true, true) => continue,
+            (true, false) => DriftKind::Appeared,
+            (false, true) => DriftKind::Disappeared,
+            (false, false) => {
+                if before_definitions.keys().eq(after_definitions.keys()) {
+                    continue;
+                }
+                DriftKind::Changed
+            }
+        };
+        drifts.push(BindingDrift {
+            kind,
+            reference,
+            before: before_definitions.into_values().sorted().collect(),
+            after: after_definitions.into_values().sorted().collect(),
+        });
+    }
+    for (_, (reference, before_definitions)) in before {
+        if before_definitions.is_empty() {
+            continue;
+        }
+        drifts.push(BindingDrift {
+            kind: DriftKind::Disappeared,
+            reference,
+            before: before_definitions.into_values().sorted().collect(),
+            after: Vec::new(),
+        });
+    }
+    drifts.sort_by(|a, b| a.reference.cmp(&b.reference));
+    Ok(drifts)
+}
+
+/// Identifies a reference or definition across databases by its file, its symbol, and its position
+/// among the nodes with the same symbol in the file.
+type BindingKey = (PathBuf, String, usize);
+
+/// The resolved definitions of a reference.
+type Binding = (BindingLocation, BTreeMap<BindingKey, BindingLocation>);
+
+/// Resolves all references in the database, and returns the definitions of every reference.
+fn bindings(
+    db: &mut SQLiteReader,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<BTreeMap<BindingKey, Binding>, StorageError> {
+    let files = indexed_files(db)?;
+    for file in &files {
+        cancellation_flag.check("loading graphs")?;
+        db.load_graph_for_file(file)?;
+    }
+
+    let config = database_stitcher_config();
+    let resolutions = resolve_references(db, &files, config, cancellation_flag)?;
+
+    let (graph, _, _) = db.get();
+    let references = binding_keys(graph, &files, |node| node.is_reference());
+    let definitions = binding_keys(graph, &files, |node| node.is_definition());
+    let mut bindings = references
+        .values()
+        .map(|(key, location)| (key.clone(), (location.clone(), BTreeMap::new())))
+        .collect::<BTreeMap<_, _>>();
+    for (reference, definition) in resolutions {
+        let (reference, definition) =
+            match (references.get(&reference), definitions.get(&definition)) {
+                (Some((reference, _)), Some(definition)) => (reference, definition),
+                _ => continue,
+            };
+        if let Some((_, targets)) = bindings.get_mut(reference) {
+            targets.insert(definition.0.clone(), definition.1.clone());
+        }
+    }
+    Ok(bindings)
+}
+
+/// Returns the binding keys and locations of the nodes in the given files that match the filter.
+fn binding_keys<F>(
+    graph: &StackGraph,
+    files: &[String],
+    filter: F,
+) -> HashMap<Handle<Node>, (BindingKey, BindingLocation)>
+where
+    F: Fn(&Node) -> bool,
+{
+    let mut keys = HashMap::new();
+    for file in files.iter().filter_map(|file| graph.get_file(file)) {
+        let mut nodes = graph
+            .nodes_for_file(file)
+            .filter(|node| filter(&graph[*node]))
+            .filter_map(|node| {
+                let symbol = graph[graph[node].symbol()?].to_string();
+                let span = graph
+                    .source_info(node)
+                    .map(|source_info| source_info.span.clone())
+                    .unwrap_or_default();
+                Some((span, graph[node].id().local_id(), symbol, node))
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        let mut ordinals = HashMap::<String, usize>::new();
+        for (span, _, symbol, node) in nodes {
+            let ordinal = ordinals.entry(symbol.clone()).or_default();
+            let path = PathBuf::from(graph[file].name());
+            let key = (path.clone(), symbol.clone(), *ordinal);
+            *ordinal += 1;
+            let location = BindingLocation {
+                file: path,
+                symbol,
+                span,
+            };
+            keys.insert(node, (key, location));
+        }
+    }
+    keys
+}
+
+/// The context of a definition, as extracted by [`symbol_contexts`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SymbolContext {
+    /// The definition node.  The handle is valid for the graph of the reader that was analyzed.
+    pub node: Handle<Node>,
+    /// The file containing the definition.
+    pub file: PathBuf,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The qualified name of the definition.  This is the fully qualified name from the source
+    /// info of the definition if it has one, and otherwise the symbols of the enclosing
+    /// definitions and the definition itself, separated by dots.
+    pub qualified_name: String,
+    /// The kind of the definition, if available.
+    pub kind: Option<DefinitionKind>,
+    /// The source span of the definition.
+    pub span: lsp_positions::Span,
+    /// The line of source code that contains the definition, without surrounding whitespace,
+    /// if available.
+    pub signature: Option<String>,
+    /// The doc comment of the definition, without comment markers, if the source of the file was
+    /// given and the definition has one.
+    pub doc: Option<String>,
+}
+
+/// Returns the contexts of all definitions with a source span in the given file, ordered by span.
+///
+/// Doc comments are found in the given source of the file, if any, which must be the source the
+/// file was indexed from.  The doc comment of a definition consists of the comment lines directly
+/// above the line of the definition or of its definiens, skipping attributes and decorators, or,
+/// if there are none, of a docstring on the lines directly below the definition.
+pub fn symbol_contexts(
+    db: &mut SQLiteReader,
+    file: &str,
+    source: Option<&str>,
+) -> Result<Vec<SymbolContext>, StorageError> {
+    if !matches!(db.status_for_file::<&str>(file, None)?, FileStatus::Indexed) {
+        return Ok(Vec::new());
+    }
+    let file_handle = db.load_graph_for_file(file)?;
+    let (graph, _, _) = db.get();
+    let lines = source.map(|source| source.lines().collect::<Vec<_>>());
+
+    let mut contexts = Vec::new();
+    for node in graph.nodes_for_file(file_handle) {
+        if !graph[node].is_definition() {
+            continue;
+        }
+        let (symbol, source_info) = match (graph[node].symbol(), graph.source_info(node)) {
+            (Some(symbol), Some(source_info)) if source_info.span.start != source_info.span.end => {
+                (graph[symbol].to_string(), source_info)
+            }
+            _ => continue,
+        };
+        let qualified_name = match source_info.fully_qualified_name.into_option() {
+            Some(name) => graph[name].to_string(),
+            None => qualified_name(graph, node, &symbol),
+        };
+        let signature = source_info
+            .containing_line
+            .into_option()
+            .map(|line| graph[line].trim().to_string());
+        let doc = lines.as_ref().and_then(|lines| {
+            let mut line = source_info.span.start.line;
+            if source_info.definiens_span.start != source_info.definiens_span.end {
+                line = line.min(source_info.definiens_span.start.line);
+            }
+            doc_comment(lines, line, source_info.span.end.line)
+        });
+        contexts.push(SymbolContext {
+            node,
+            file: PathBuf::from(file),
+            symbol,
+            qualified_name,
+            kind: graph.definition_kind(node),
+            span: source_info.span.clone(),
+            signature,
+            doc,
+        });
+    }
+    contexts.sort_by(|a, b| {
+        (&a.span, graph[a.node].id().local_id()).cmp(&(&b.span, graph[b.node].id().local_id()))
+    });
+    Ok(contexts)
+}
+
+/// Returns the symbols of the enclosing definitions of the given definition, and the given
+/// symbol, separated by dots.
+fn qualified_name(graph: &StackGraph, node: Handle<Node>, symbol: &str) -> String {
+    let mut names = vec![symbol.to_string()];
+    let mut visited = HashSet::new();
+    visited.insert(node);
+    let mut current = node;
+    while let Some(enclosing) = enclosing_definition(graph, current) {
+        if !visited.insert(enclosing) {
+            break;
+        }
+        if let Some(symbol) = graph[enclosing].symbol() {
+            names.push(graph[symbol].to_string());
+        }
+        current = enclosing;
+    }
+    names.reverse();
+    names.join(".")
+}
+
+/// Comment markers that are removed from the start of doc comment lines, longest first.
+const DOC_COMMENT_PREFIXES: &[&str] = &["///", "//!", "/**", "/*", "//", "--", "*/", "*", "#"];
+
+/// Returns the doc comment of a definition that starts at `first_line` and whose name ends at
+/// `last_line`.
+fn doc_comment(lines: &[&str], first_line: usize, last_line: usize) -> Option<String> {
+    let mut comment = Vec::new();
+    let mut line = first_line;
+    while line > 0 {
+        line -= 1;
+        let text = lines.get(line)?.trim();
+        if text.starts_with('@') || text.starts_with("#[") {
+            // attributes and decorators are part of the definition
+            continue;
+        }
+        match DOC_COMMENT_PREFIXES
+            .iter()
+            .find(|prefix| text.starts_with(*prefix))
+        {
+            Some(prefix) => comment.push(text[prefix.len()..].trim_end_matches("*/").trim()),
+            None => break,
+        }
+    }
+    comment.reverse();
+    if comment.is_empty() {
+        comment = docstring(lines, last_line + 1);
+    }
+    let start = comment.iter().position(|line| !line.is_empty())?;
+    let end = comment.iter().rposition(|line| !line.is_empty())?;
+    Some(comment[start..=end].join("\n"))
+}
+
+/// Returns the lines of the docstring that starts on the given line, if any.
+fn docstring<'a>(lines: &[&'a str], line: usize) -> Vec<&'a str> {
+    let text = match lines.get(line) {
+        Some(text) => text.trim(),
+        None => return Vec::new(),
+    };
+    let quote = match ["\"\"\"", "'''"].iter().find(|q| text.starts_with(*q)) {
+        Some(quote) => *quote,
+        None => return Vec::new(),
+    };
+    let text = &text[quote.len()..];
+    if let Some(end) = text.find(quote) {
+        return vec![text[..end].trim()];
+    }
+    let mut docstring = vec![text.trim()];
+    for text in lines[line + 1..].iter().map(|text| text.trim()) {
+        if let Some(end) = text.find(quote) {
+            docstring.push(text[..end].trim());
+            return docstring;
+        }
+        docstring.push(text);
+    }
+    // unterminated docstring
+    Vec::new()
+}
@@ -38,12 +38,18 @@ use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
 use std::ops::Index;
 use std::ops::IndexMut;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use bitvec::vec::BitVec;
 use controlled_option::Niche;
+use thiserror::Error;
 
 use crate::utils::cmp_option;
 use crate::utils::equals_option;
+use crate::CancellationError;
+use crate::CancellationFlag;
 
 //-------------------------------------------------------------------------------------------------
 // Arenas and handles
@@ -236,6 +242,8 @@ pub struct Arena<T> {
     /// Storage for all arena items. Index 0 is unused (reserved for null handles).
     /// Items at indices 1+ are initialized.
     items: Vec<MaybeUninit<T>>,
+    /// The memory budget that is charged for the items in this arena, if any.
+    budget: Option<MemoryBudget>,
 }
 
 impl<T> Drop for Arena<T> {
@@ -251,6 +259,9 @@ impl<T> Drop for Arena<T> {
     /// - All items at indices 1+ were initialized via `add()`
     /// - We transmute `MaybeUninit<T>` to `T`, which is valid for initialized data
     fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.refund(self.allocated_bytes());
+        }
         unsafe {
             // Get a mutable slice of all initialized items (skip index 0)
             let items = std::mem::transmute::<_, &mut [T]>(&mut self.items[1..]) as *mut [T];
@@ -268,6 +279,7 @@ impl<T> Arena<T> {
     pub fn new() -> Arena<T> {
         Arena {
             items: vec![MaybeUninit::uninit()],
+            budget: None,
         }
     }
 
@@ -290,6 +302,9 @@ impl<T> Arena<T> {
     /// ```
     #[inline(always)]
     pub fn clear(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.refund(self.allocated_bytes());
+        }
         // Keep index 0 (the unused slot), truncate everything else
         self.items.truncate(1);
     }
@@ -317,6 +332,22 @@ impl<T> Arena<T> {
     /// assert_eq!(arena[h1], arena[h2]);  // Same value
     /// ```
     pub fn add(&mut self, item: T) -> Handle<T> {
+        if let Some(budget) = &self.budget {
+            budget.charge(std::mem::size_of::<T>());
+        }
+        self.push(item)
+    }
+
+    /// Adds a new instance to this arena, like [`add`][Self::add], unless that would exceed the
+    /// arena's [memory budget][MemoryBudget].  In that case, the arena is not changed.
+    pub fn try_add(&mut self, item: T) -> Result<Handle<T>, OutOfBudget> {
+        if let Some(budget) = &self.budget {
+            budget.try_charge(std::mem::size_of::<T>())?;
+        }
+        Ok(self.push(item))
+    }
+
+    fn push(&mut self, item: T) -> Handle<T> {
         // Get the index for the new item (current length)
         let index = self.items.len() as u32;
 
@@ -356,6 +387,157 @@ impl<T> Arena<T> {
     pub fn len(&self) -> usize {
         self.items.len()
     }
+
+    /// Removes all instances that were added after this arena had the given
+    /// [length][Self::len].  All handles to the removed instances become invalid.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        let len = len.max(1);
+        if len >= self.items.len() {
+            return;
+        }
+        if let Some(budget) = &self.budget {
+            budget.refund((self.items.len() - len) * std::mem::size_of::<T>());
+        }
+        for item in self.items.drain(len..) {
+            drop(unsafe { item.assume_init() });
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more instances, so that adding them does not
+    /// reallocate the arena's storage.  Reserved capacity is not charged to the arena's
+    /// [memory budget][MemoryBudget].
+    pub fn reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+    }
+
+    /// Assigns a memory budget to this arena, which is charged for all of its current and future
+    /// instances.  Any previously assigned budget is refunded.
+    pub fn set_budget(&mut self, budget: Option<MemoryBudget>) {
+        let bytes = self.allocated_bytes();
+        if let Some(previous) = self.budget.take() {
+            previous.refund(bytes);
+        }
+        if let Some(budget) = &budget {
+            budget.charge(bytes);
+        }
+        self.budget = budget;
+    }
+
+    /// Returns the memory budget that is charged for the instances in this arena, if any.
+    pub fn budget(&self) -> Option<&MemoryBudget> {
+        self.budget.as_ref()
+    }
+
+    /// Returns the number of bytes that the instances in this arena are charged for.
+    fn allocated_bytes(&self) -> usize {
+        (self.items.len() - 1) * std::mem::size_of::<T>()
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Memory budgets
+
+/// A limit on the number of bytes that a group of arenas can allocate.
+///
+/// A budget is shared by all of the arenas that it is [assigned to][Arena::set_budget], and by
+/// all of its clones.  This makes it possible to bound the memory of a
+/// [`StackGraph`][crate::graph::StackGraph], its [`PartialPaths`][crate::partial::PartialPaths],
+/// and a [`Database`][crate::stitching::Database] together, for example for a single query in a
+/// server that handles many of them.
+///
+/// Arenas are charged for the size of every instance that is added to them, and refunded when
+/// they are cleared or dropped.  Memory that is not allocated in arenas, such as the content of
+/// interned strings or the supplemental data of nodes, is not counted, so the budget is an
+/// approximation of the actual memory use.
+///
+/// Adding instances with [`Arena::add`][] never fails because of the budget, so that existing
+/// code does not need to handle errors.  Instead, the budget is checked with
+/// [`check_usage`][Self::check_usage], which returns a structured [`OutOfBudget`][] error once the
+/// budget is exceeded.  Budgets also implement [`CancellationFlag`][], so that they can be passed
+/// to long-running operations such as path stitching, which then stop when the budget is
+/// exceeded.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget(Arc<MemoryBudgetInner>);
+
+#[derive(Debug)]
+struct MemoryBudgetInner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates a new budget that allows the given number of bytes to be allocated.
+    pub fn new(limit: usize) -> MemoryBudget {
+        MemoryBudget(Arc::new(MemoryBudgetInner {
+            limit,
+            used: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Returns the number of bytes that this budget allows.
+    pub fn limit(&self) -> usize {
+        self.0.limit
+    }
+
+    /// Returns the number of bytes that are currently charged to this budget.
+    pub fn used(&self) -> usize {
+        self.0.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes that can still be allocated within this budget.
+    pub fn remaining(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    /// Returns an error if more bytes are used than this budget allows.
+    pub fn check_usage(&self) -> Result<(), OutOfBudget> {
+        let used = self.used();
+        if used > self.0.limit {
+            return Err(OutOfBudget {
+                limit: self.0.limit,
+                used,
+            });
+        }
+        Ok(())
+    }
+
+    fn charge(&self, bytes: usize) {
+        self.0.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn try_charge(&self, bytes: usize) -> Result<(), OutOfBudget> {
+        let limit = self.0.limit;
+        self.0
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used + bytes).filter(|used| *used <= limit)
+            })
+            .map(|_| ())
+            .map_err(|used| OutOfBudget {
+                limit,
+                used: used + bytes,
+            })
+    }
+
+    fn refund(&self, bytes: usize) {
+        self.0.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+impl CancellationFlag for MemoryBudget {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        self.check_usage().map_err(|_| CancellationError(at))
+    }
+}
+
+/// The error that is returned when a [`MemoryBudget`][] is exceeded.
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("memory budget of {limit} bytes exceeded, {used} bytes needed")]
+pub struct OutOfBudget {
+    /// The number of bytes that the budget allows.
+    pub limit: usize,
+    /// The number of bytes that are, or would have been, used.
+    pub used: usize,
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -459,6 +641,17 @@ impl<H, T> SupplementalArena<H, T> {
         self.items.len()
     }
 
+    /// Removes the items belonging to all handles at or after the given index.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        let len = len.max(1);
+        if len >= self.items.len() {
+            return;
+        }
+        for item in self.items.drain(len..) {
+            drop(unsafe { item.assume_init() });
+        }
+    }
+
     /// Iterate over the items in this arena.
     pub(crate) fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
         self.items
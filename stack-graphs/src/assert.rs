@@ -56,7 +56,7 @@
 //!
 //! ## Assertion Workflow
 //!
-//! 1. **Parse annotations** from test files to create [`Assertion`][] objects
+//! 1. **Parse annotations** from test files to create [`Assertion`][] objects, using [`parse`][]
 //! 2. **Build the stack graph** for the test file
 //! 3. **Run assertions** using [`Assertion::run()`][]
 //! 4. **Check results** - assertions return `Ok(())` on success or [`AssertionError`][] on failure
@@ -92,6 +92,9 @@
 
 use itertools::Itertools;
 use lsp_positions::Position;
+use lsp_positions::PositionedSubstring;
+use lsp_positions::SpanCalculator;
+use thiserror::Error;
 
 use crate::arena::Handle;
 use crate::graph::File;
@@ -530,17 +533,14 @@ impl Assertion {
                 },
             )?;
 
-            // Filter out shadowed paths (keep only non-shadowed ones)
-            // A path is shadowed if another path with the same start and end
-            // is more specific (has more precise scope information)
-            for reference_path in &reference_paths {
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
-                }
-            }
+            // Filter out shadowed paths (keep only non-shadowed ones), as decided by the
+            // configured shadowing policy
+            stitcher_config.shadowing_policy().remove_shadowed_paths(
+                graph,
+                partials,
+                &mut reference_paths,
+            );
+            actual_paths.extend(reference_paths);
         }
 
         // Check that actual paths match expected targets
@@ -665,3 +665,217 @@ impl Assertion {
         Ok(())
     }
 }
+
+/// The syntax of assertion comments in annotated source files.
+///
+/// By default, any line that contains an assertion, such as `^ defined: 5`, is an assertion line.
+/// If comment prefixes are configured, only lines that start with one of the prefixes, ignoring
+/// leading whitespace, are assertion lines, so that source lines containing `^` are not mistaken
+/// for assertions.
+#[derive(Clone, Debug, Default)]
+pub struct AssertionSyntax {
+    comment_prefixes: Vec<String>,
+}
+
+impl AssertionSyntax {
+    /// Returns a syntax that accepts assertions on any line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a prefix that starts a comment, such as `#` or `//`.
+    pub fn with_comment_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.comment_prefixes.push(prefix.into());
+        self
+    }
+
+    pub fn comment_prefixes(&self) -> &[String] {
+        &self.comment_prefixes
+    }
+
+    /// Returns the assertion in the line, if it is an assertion line.
+    fn find_assertion<'a>(&self, line: &'a str) -> Option<AssertionMatch<'a>> {
+        if !self.comment_prefixes.is_empty() {
+            let content = line.trim_start();
+            if !self
+                .comment_prefixes
+                .iter()
+                .any(|prefix| content.starts_with(prefix.as_str()))
+            {
+                return None;
+            }
+        }
+        line.match_indices('^')
+            .find_map(|(caret, _)| AssertionMatch::parse(line, caret))
+    }
+}
+
+/// An assertion in a line, of the form `^ NAME: VALUE, ...`.
+struct AssertionMatch<'a> {
+    /// The UTF-8 offset of the caret in the line.
+    caret: usize,
+    name: &'a str,
+    values: Vec<&'a str>,
+}
+
+impl<'a> AssertionMatch<'a> {
+    fn parse(line: &'a str, caret: usize) -> Option<Self> {
+        let rest = line[caret + 1..].trim_start();
+        let name_length = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if name_length == 0 || !rest[name_length..].starts_with(':') {
+            return None;
+        }
+        let name = &rest[..name_length];
+
+        let is_value_char = |c: char| !(c.is_whitespace() || c == ',');
+        let mut values = Vec::new();
+        let mut rest = rest[name_length + 1..].trim_start();
+        loop {
+            let value_length = rest.find(|c| !is_value_char(c)).unwrap_or(rest.len());
+            if value_length == 0 {
+                break;
+            }
+            values.push(&rest[..value_length]);
+            // another value must follow a comma, otherwise the values end here
+            let next = rest[value_length..].trim_start();
+            match next.strip_prefix(',') {
+                Some(next) if next.trim_start().starts_with(is_value_char) => {
+                    rest = next.trim_start();
+                }
+                _ => break,
+            }
+        }
+
+        Some(Self {
+            caret,
+            name,
+            values,
+        })
+    }
+}
+
+/// An error that can occur while parsing assertions.  Lines and columns are 0-based.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum AssertionParseError {
+    #[error("Assertion on line {} refers to non-source line", .0 + 1)]
+    AssertionRefersToNonSourceLine(usize),
+    #[error("Invalid assertion {} on line {}", .1, .0 + 1)]
+    InvalidAssertion(usize, String),
+    #[error("Assertion on line {} refers to missing column {} on line {}", .0 + 1, .1 + 1, .2 + 1)]
+    InvalidColumn(usize, usize, usize),
+}
+
+/// Parses the assertions in an annotated source file.  The target lines of `defined` assertions
+/// are lines of the same file.  See [`parse_with_line_files`][] for sources that consist of
+/// several files.
+///
+/// Consecutive lines with assertions all apply to the last source line without an assertion.
+/// The supported assertions are:
+///
+///  - `defined`: takes a comma-separated list of 1-based line numbers, and expects a reference at
+///    this position to resolve to definitions on those lines.
+///  - `defines`: takes a comma-separated list of names, and expects definitions at this position
+///    with the given names.
+///  - `refers`: takes a comma-separated list of names, and expects references at this position
+///    with the given names.
+pub fn parse(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    source: &str,
+    syntax: &AssertionSyntax,
+) -> Result<Vec<Assertion>, AssertionParseError> {
+    parse_with_line_files(graph, file, source, syntax, |_| Some(file))
+}
+
+/// Parses the assertions in an annotated source file, like [`parse`][].  The `line_file` closure
+/// returns the file that a 0-based line of the source belongs to, which is the file of the target
+/// of `defined` assertions referring to that line.
+pub fn parse_with_line_files<F>(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    source: &str,
+    syntax: &AssertionSyntax,
+    line_file: F,
+) -> Result<Vec<Assertion>, AssertionParseError>
+where
+    F: Fn(usize) -> Option<Handle<File>>,
+{
+    let mut assertions = Vec::new();
+    let mut current_line_span_calculator = SpanCalculator::new(source);
+    let mut last_regular_line: Option<(usize, PositionedSubstring)> = None;
+    let mut last_regular_line_span_calculator = SpanCalculator::new(source);
+    for (current_line_number, current_line) in PositionedSubstring::lines_iter(source).enumerate() {
+        let m = match syntax.find_assertion(current_line.content) {
+            Some(m) => m,
+            None => {
+                // regular source line
+                last_regular_line = Some((current_line_number, current_line));
+                continue;
+            }
+        };
+
+        let (last_regular_line_number, last_regular_line) = last_regular_line.as_ref().ok_or(
+            AssertionParseError::AssertionRefersToNonSourceLine(current_line_number),
+        )?;
+
+        let column_grapheme_offset = current_line_span_calculator
+            .for_line_and_column(current_line_number, current_line.utf8_bounds.start, m.caret)
+            .column
+            .grapheme_offset;
+        if column_grapheme_offset >= last_regular_line.grapheme_length {
+            return Err(AssertionParseError::InvalidColumn(
+                current_line_number,
+                column_grapheme_offset,
+                *last_regular_line_number,
+            ));
+        }
+        let position = last_regular_line_span_calculator.for_line_and_grapheme(
+            *last_regular_line_number,
+            last_regular_line.utf8_bounds.start,
+            column_grapheme_offset,
+        );
+        let source = AssertionSource { file, position };
+
+        match m.name {
+            "defined" => {
+                let mut targets = Vec::new();
+                let line_numbers = m.values.iter().flat_map(|value| {
+                    value
+                        .split(|c: char| !c.is_ascii_digit())
+                        .filter(|digits| !digits.is_empty())
+                });
+                for line in line_numbers {
+                    let line = line
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|line| line.checked_sub(1))
+                        .ok_or(AssertionParseError::AssertionRefersToNonSourceLine(
+                            current_line_number,
+                        ))?;
+                    let file = line_file(line).ok_or(
+                        AssertionParseError::AssertionRefersToNonSourceLine(current_line_number),
+                    )?;
+                    targets.push(AssertionTarget { file, line });
+                }
+                assertions.push(Assertion::Defined { source, targets });
+            }
+            "defines" => {
+                let symbols = m.values.iter().map(|name| graph.add_symbol(name)).collect();
+                assertions.push(Assertion::Defines { source, symbols });
+            }
+            "refers" => {
+                let symbols = m.values.iter().map(|name| graph.add_symbol(name)).collect();
+                assertions.push(Assertion::Refers { source, symbols });
+            }
+            name => {
+                return Err(AssertionParseError::InvalidAssertion(
+                    current_line_number,
+                    name.to_string(),
+                ));
+            }
+        }
+    }
+    Ok(assertions)
+}
@@ -165,9 +165,22 @@
 //!
 //! ## Thread Safety
 //!
-//! - **Stack graphs are NOT thread-safe**. Don't access the same graph from multiple threads.
-//! - Create separate graphs per thread if parallel processing is needed.
-//! - Path stitching can be parallelized by running separate stitcher instances per thread.
+//! The C API keeps no global or thread-local state.  Every function only accesses the instances
+//! that are passed to it, so the API is re-entrant:
+//!
+//! - **Independent instances can be used concurrently.** Several host threads can each work with
+//!   their own `sg_stack_graph`, `sg_partial_path_arena`, `sg_partial_path_database`, and
+//!   stitcher at the same time.
+//! - **Instances have no thread affinity.** An instance can be created on one thread and used or
+//!   freed on another, as long as the host synchronizes the hand-over.
+//! - **An instance must not be modified while it is used by another thread.** Functions that take
+//!   a mutable pointer (such as `sg_stack_graph*`) modify the instance.  Functions that take a
+//!   const pointer (such as `const sg_stack_graph*`) only read it, so several threads can find
+//!   paths in the same graph at the same time, as long as each thread uses its own partial path
+//!   arena, database, path lists, and stitcher, and no thread modifies the graph meanwhile.
+//! - **Cancellation flags can be shared.** A non-zero cancellation flag cancels every computation
+//!   that it is passed to.  The flag is only read, never reset, so one flag can cancel
+//!   computations on several threads, and must be reset by the host before it is reused.
 //!
 //! ## Naming Conventions
 //!
@@ -180,7 +193,10 @@
 //! ## Cargo Features
 //!
 //! This module requires no special cargo features - it's available by default when building
-//! the stack-graphs crate as a C library.
+//! the stack-graphs crate as a C library.  The `visualization` feature adds
+//! `sg_stack_graph_to_html`, which creates visualizations of stack graphs.  Its declarations in the
+//! header are guarded by `SG_FEATURE_VISUALIZATION`, which embedders must define when they link
+//! against a library that was built with the feature.
 //!
 //! ## Building the C Library
 //!
@@ -211,7 +227,11 @@
 
 #![allow(non_camel_case_types)]
 
+#[cfg(feature = "visualization")]
+use std::collections::HashSet;
 use std::convert::TryInto;
+#[cfg(feature = "visualization")]
+use std::ffi::CString;
 use std::sync::atomic::AtomicUsize;
 
 use libc::c_char;
@@ -221,6 +241,7 @@ use crate::graph::File;
 use crate::graph::InternedString;
 use crate::graph::Node;
 use crate::graph::NodeID;
+use crate::graph::Precedence;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
 use crate::partial::PartialPath;
@@ -815,7 +836,7 @@ pub extern "C" fn sg_stack_graph_add_edges(
     for i in 0..count {
         let source = unsafe { std::mem::transmute(edges[i].source) };
         let sink = unsafe { std::mem::transmute(edges[i].sink) };
-        graph.add_edge(source, sink, edges[i].precedence);
+        graph.add_edge(source, sink, Precedence::new(edges[i].precedence));
     }
 }
 
@@ -1398,6 +1419,7 @@ pub extern "C" fn sg_partial_path_arena_find_partial_paths_in_file(
             partial_path_list.partial_paths.push(path);
         },
     )
+    .map_err(CancellationError::from)
     .into()
 }
 
@@ -1473,9 +1495,8 @@ pub extern "C" fn sg_partial_path_database_partial_paths(
 /// We copy the partial path content into the partial path database.  The array you pass in does
 /// not need to outlive the call to this function.
 ///
-/// You should take care not to add a partial path to the database multiple times.  This won't
-/// cause an _error_, in that nothing will break, but it will probably cause you to get duplicate
-/// paths from the path-stitching algorithm.
+/// Identical partial paths are only stored once.  If a partial path is already in the database,
+/// the handle of the existing partial path is returned for it.
 ///
 /// You must also provide an `out` array, which must also have room for `count` elements.  We will
 /// fill this array in with the `sg_partial_path_edge_list` instances for each partial path edge
@@ -1775,12 +1796,14 @@ pub extern "C" fn sg_forward_partial_path_stitcher_free(
 //-------------------------------------------------------------------------------------------------
 // Cancellation
 
+/// A cancellation flag that is owned by the host.  The flag is only read, so that a flag that is
+/// shared by several computations cancels all of them.
 struct AtomicUsizeCancellationFlag<'a>(Option<&'a AtomicUsize>);
 impl CancellationFlag for AtomicUsizeCancellationFlag<'_> {
     fn check(&self, at: &'static str) -> Result<(), crate::CancellationError> {
         self.0
             .map(|flag| {
-                if flag.fetch_and(0b0, std::sync::atomic::Ordering::Relaxed) != 0 {
+                if flag.load(std::sync::atomic::Ordering::Relaxed) != 0 {
                     Err(CancellationError(at))
                 } else {
                     Ok(())
@@ -1806,3 +1829,125 @@ impl<T> From<Result<T, CancellationError>> for sg_result {
         }
     }
 }
+
+//-------------------------------------------------------------------------------------------------
+// Visualization
+
+/// How a visualization reports clicks on nodes to the page or application that hosts it, so that
+/// it can jump to the source of the node.
+#[cfg(feature = "visualization")]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum sg_source_navigation {
+    /// Clicks are not reported.
+    SG_SOURCE_NAVIGATION_NONE,
+    /// A navigation message is posted to the parent window.
+    SG_SOURCE_NAVIGATION_POST_MESSAGE,
+    /// A URL built from the `url_template` of the visualization options is opened.
+    SG_SOURCE_NAVIGATION_URL,
+}
+
+/// Options for the visualizations that are created by `sg_stack_graph_to_html`.
+#[cfg(feature = "visualization")]
+#[repr(C)]
+pub struct sg_visualization_options {
+    /// The files whose nodes, edges, and paths are included in the visualization.  If
+    /// `file_count` is zero, all files are included.
+    pub files: *const sg_file_handle,
+    pub file_count: usize,
+    /// How the visualization reports clicks on nodes to its host.
+    pub source_navigation: sg_source_navigation,
+    /// The URL template that is used with `SG_SOURCE_NAVIGATION_URL`, as UTF-8.  It does not
+    /// need to be NUL-terminated.
+    pub url_template: *const c_char,
+    pub url_template_length: usize,
+}
+
+/// Returns a self-contained HTML page that visualizes a stack graph and the partial paths in a
+/// database.  The title is given as UTF-8, and does not need to be NUL-terminated.  If `db` is
+/// null, no partial paths are included, and `partials` can be null as well.  If `options` is null,
+/// all files are included, and clicks on nodes are not reported.
+///
+/// The page is returned as a NUL-terminated UTF-8 string, which you must free with
+/// `sg_html_free`.  Returns null if the title or URL template is not valid UTF-8, or if the page
+/// could not be created.
+#[cfg(feature = "visualization")]
+#[no_mangle]
+pub extern "C" fn sg_stack_graph_to_html(
+    graph: *const sg_stack_graph,
+    partials: *mut sg_partial_path_arena,
+    db: *mut sg_partial_path_database,
+    title: *const c_char,
+    title_length: usize,
+    options: *const sg_visualization_options,
+) -> *mut c_char {
+    use crate::visualization::SourceNavigation;
+
+    let graph = unsafe { &(*graph).inner };
+    let title = unsafe { std::slice::from_raw_parts(title as *const u8, title_length) };
+    let title = match std::str::from_utf8(title) {
+        Ok(title) => title,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let options = unsafe { options.as_ref() };
+    let files = options
+        .filter(|options| options.file_count > 0)
+        .map(|options| {
+            let files = unsafe { std::slice::from_raw_parts(options.files, options.file_count) };
+            files
+                .iter()
+                .map(|file| (*file).into())
+                .collect::<HashSet<Handle<File>>>()
+        });
+    let navigation = match options {
+        Some(options) => match options.source_navigation {
+            sg_source_navigation::SG_SOURCE_NAVIGATION_NONE => SourceNavigation::None,
+            sg_source_navigation::SG_SOURCE_NAVIGATION_POST_MESSAGE => {
+                SourceNavigation::PostMessage
+            }
+            sg_source_navigation::SG_SOURCE_NAVIGATION_URL => {
+                let template = unsafe {
+                    std::slice::from_raw_parts(
+                        options.url_template as *const u8,
+                        options.url_template_length,
+                    )
+                };
+                match std::str::from_utf8(template) {
+                    Ok(template) => SourceNavigation::Url(template.to_string()),
+                    Err(_) => return std::ptr::null_mut(),
+                }
+            }
+        },
+        None => SourceNavigation::None,
+    };
+    let filter = |_: &StackGraph, file: &Handle<File>| {
+        files.as_ref().map_or(true, |files| files.contains(file))
+    };
+
+    let mut empty_partials = PartialPaths::new();
+    let mut empty_db = Database::new();
+    let (partials, db) = match unsafe { db.as_mut() } {
+        Some(db) => (unsafe { &mut (*partials).inner }, &mut db.inner),
+        None => (&mut empty_partials, &mut empty_db),
+    };
+    let html = match graph.to_html_string_with_navigation(title, partials, db, &filter, &navigation)
+    {
+        Ok(html) => html,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match CString::new(html) {
+        Ok(html) => html.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Frees an HTML page that was returned by `sg_stack_graph_to_html`.  Passing null, which
+/// `sg_stack_graph_to_html` returns on failure, is a no-op.
+#[cfg(feature = "visualization")]
+#[no_mangle]
+pub extern "C" fn sg_html_free(html: *mut c_char) {
+    if html.is_null() {
+        return;
+    }
+    drop(unsafe { CString::from_raw(html) })
+}
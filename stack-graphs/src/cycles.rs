@@ -460,6 +460,20 @@ impl<H> AppendingCycleDetector<H> {
             InternedOrHandle::Database(appendage),
         );
     }
+
+    /// Returns the handles of the appendables that were appended, starting with the most recently
+    /// appended one.
+    pub fn appended<'a>(
+        &self,
+        appendables: &'a Appendables<H>,
+    ) -> impl Iterator<Item = &'a H> + 'a {
+        self.appendages
+            .iter(&appendables.elements)
+            .filter_map(|appendage| match appendage {
+                InternedOrHandle::Interned(_) => None,
+                InternedOrHandle::Database(handle) => Some(handle),
+            })
+    }
 }
 
 impl<H> AppendingCycleDetector<H>
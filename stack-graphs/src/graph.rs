@@ -50,18 +50,30 @@
 //! [`File`]: struct.File.html
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::hash::Hash;
+use std::iter::FromIterator;
 use std::num::NonZeroU32;
+use std::ops::Deref;
+use std::ops::DerefMut;
 use std::ops::Index;
 use std::ops::IndexMut;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use controlled_option::ControlledOption;
 use either::Either;
 use fxhash::FxHashMap;
+use lsp_positions::Span;
 use smallvec::SmallVec;
 
 use crate::arena::Arena;
 use crate::arena::Handle;
+use crate::arena::HandleSet;
+use crate::arena::MemoryBudget;
 use crate::arena::SupplementalArena;
 
 //-------------------------------------------------------------------------------------------------
@@ -92,6 +104,14 @@ struct InternedStringContent {
 /// This is the size of the first buffer; subsequent buffers grow exponentially.
 const INITIAL_STRING_CAPACITY: usize = 512;
 
+/// Expected average length of symbols in bytes, used to size the first string intern buffer in
+/// [`StackGraph::with_capacity`][].
+const EXPECTED_SYMBOL_LENGTH: usize = 16;
+
+/// Expected average length of file names in bytes, used to size the first string intern buffer
+/// in [`StackGraph::with_capacity`][].
+const EXPECTED_FILE_NAME_LENGTH: usize = 64;
+
 /// Arena for interning strings efficiently.
 ///
 /// This structure implements a string interner that guarantees stable pointers to string data.
@@ -138,12 +158,37 @@ impl InternedStringArena {
     /// The arena starts with a single buffer of `INITIAL_STRING_CAPACITY` bytes.
     /// Additional buffers are allocated as needed, each time doubling in size.
     fn new() -> InternedStringArena {
+        Self::with_capacity(INITIAL_STRING_CAPACITY)
+    }
+
+    /// Creates a new string interning arena whose first buffer can hold `capacity` bytes, or
+    /// `INITIAL_STRING_CAPACITY` bytes if that is larger.
+    fn with_capacity(capacity: usize) -> InternedStringArena {
         InternedStringArena {
-            current_buffer: Vec::with_capacity(INITIAL_STRING_CAPACITY),
+            current_buffer: Vec::with_capacity(capacity.max(INITIAL_STRING_CAPACITY)),
             full_buffers: Vec::new(),
         }
     }
 
+    /// Returns the number of bytes of interned string content.
+    fn len(&self) -> usize {
+        self.full_buffers.iter().map(|b| b.len()).sum::<usize>() + self.current_buffer.len()
+    }
+
+    /// Returns the number of bytes allocated for interned string content.
+    fn capacity(&self) -> usize {
+        self.full_buffers
+            .iter()
+            .map(|b| b.capacity())
+            .sum::<usize>()
+            + self.current_buffer.capacity()
+    }
+
+    /// Returns the number of buffers allocated for interned string content.
+    fn buffer_count(&self) -> usize {
+        self.full_buffers.len() + 1
+    }
+
     /// Interns a new string, returning a stable pointer to its content.
     ///
     /// This method does NOT check for duplicates - deduplication is handled by
@@ -371,11 +416,18 @@ impl StackGraph {
         handle
     }
 
-    /// Returns an iterator over all of the handles of all of the symbols in this stack graph.
-    /// (Note that because we're only returning _handles_, this iterator does not retain a
-    /// reference to the `StackGraph`.)
+    /// Returns an iterator over all of the handles of all of the symbols in this stack graph, in
+    /// the graph's [iteration order][Self::set_iteration_order].  (Note that because we're only
+    /// returning _handles_, this iterator does not retain a reference to the `StackGraph`.)
     pub fn iter_symbols(&self) -> impl Iterator<Item = Handle<Symbol>> {
-        self.symbols.iter_handles()
+        match self.iteration_order {
+            IterationOrder::Insertion => Either::Left(self.symbols.iter_handles()),
+            IterationOrder::Sorted => {
+                let mut symbols = self.symbols.iter_handles().collect::<Vec<_>>();
+                symbols.sort_by(|a, b| self[*a].cmp(&self[*b]));
+                Either::Right(symbols.into_iter())
+            }
+        }
     }
 }
 
@@ -492,15 +544,54 @@ impl Handle<InternedString> {
 /// choice.  If your files belong to packages or repositories, they should include the package or
 /// repository IDs to make sure that files in different packages or repositories don't clash with
 /// each other.
+///
+/// Files can also be identified _structurally_, by a root ID and a path relative to that root (see
+/// [`add_file_in_root`][StackGraph::add_file_in_root]), which keeps their identity independent of
+/// where the root is checked out.  The root and relative path are stored with the file, and are
+/// never parsed back out of its name, so names that look like URIs (`file://…`) belong to files
+/// without a root.  Use [`FileRoots`][] to map root IDs to local directories when the files need to
+/// be located on disk.
+///
+/// Files can be tagged with the [language][StackGraph::set_file_language] they were analyzed with,
+/// so that graphs and databases that contain files in several languages can be queried per
+/// language.
+///
+/// Files that could only be parsed partially can record the [regions][StackGraph::add_parse_error]
+/// that contained parse errors.  The graph of such a file may be incomplete, and results that
+/// involve it should be treated with suspicion.
 pub struct File {
     /// The name of this source file.
     name: InternedStringContent,
+    /// The ID of the root this file belongs to, if any.
+    root: Option<InternedStringContent>,
+    /// The path of this file relative to its root, if it belongs to one.
+    relative_path: Option<InternedStringContent>,
 }
 
 impl File {
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
+
+    /// Returns the ID of the root this file belongs to, if any.
+    pub fn root(&self) -> Option<&str> {
+        self.root.as_ref().map(|root| root.as_str())
+    }
+
+    /// Returns the path of this file relative to its root.  For files that do not belong to a
+    /// root, this is the file's name.
+    pub fn relative_path(&self) -> &str {
+        self.relative_path
+            .as_ref()
+            .map_or(self.name(), |path| path.as_str())
+    }
+}
+
+/// Returns the name of the file with the given root ID and root-relative path.  The name is only
+/// used to identify the file in graphs and databases; the root of a file is never derived from
+/// its name.
+pub fn file_name_in_root(root: &str, relative_path: &str) -> String {
+    format!("{}://{}", root, relative_path)
 }
 
 impl StackGraph {
@@ -519,7 +610,11 @@ impl StackGraph {
 
         let interned = self.interned_strings.add(name);
         let hash_key = unsafe { interned.as_hash_key() };
-        let handle = self.files.add(File { name: interned });
+        let handle = self.files.add(File {
+            name: interned,
+            root: None,
+            relative_path: None,
+        });
         self.file_handles.insert(hash_key, handle);
         Ok(handle)
     }
@@ -537,20 +632,281 @@ impl StackGraph {
         let name = name.as_ref();
         self.file_handles.get(name).copied()
     }
+
+    /// Adds a file, identified by a root ID and a path relative to that root, to the stack graph.
+    /// The file is named using [`file_name_in_root`][], and behaves like [`add_file`][Self::add_file]
+    /// otherwise.  Names must be unique regardless of roots, so this returns `Err` if a file
+    /// without a root already has that name.
+    pub fn add_file_in_root(
+        &mut self,
+        root: &str,
+        relative_path: &str,
+    ) -> Result<Handle<File>, Handle<File>> {
+        let handle = self.add_file(&file_name_in_root(root, relative_path))?;
+        let root = self.interned_strings.add(root);
+        let relative_path = self.interned_strings.add(relative_path);
+        let file = self.files.get_mut(handle);
+        file.root = Some(root);
+        file.relative_path = Some(relative_path);
+        Ok(handle)
+    }
+
+    /// Adds a file, identified by a root ID and a path relative to that root, to the stack graph,
+    /// returning its handle.
+    pub fn get_or_create_file_in_root(&mut self, root: &str, relative_path: &str) -> Handle<File> {
+        self.add_file_in_root(root, relative_path)
+            .unwrap_or_else(|handle| handle)
+    }
+
+    /// Adds a file with the same name and root as a file of another stack graph.  Behaves like
+    /// [`add_file`][Self::add_file] otherwise.
+    pub fn add_file_of(
+        &mut self,
+        other: &StackGraph,
+        other_file: Handle<File>,
+    ) -> Result<Handle<File>, Handle<File>> {
+        let other_file = &other[other_file];
+        match other_file.root() {
+            Some(root) => self.add_file_in_root(root, other_file.relative_path()),
+            None => self.add_file(other_file.name()),
+        }
+    }
+
+    /// Returns the file with a particular root ID and root-relative path, if it exists.
+    pub fn get_file_in_root(&self, root: &str, relative_path: &str) -> Option<Handle<File>> {
+        self.get_file(&file_name_in_root(root, relative_path))
+            .filter(|file| self[*file].root() == Some(root))
+    }
+
+    /// Tags a file with the name of the language it was analyzed with, such as `python` or
+    /// `typescript`.  Names are compared exactly, so the same name should be used for all files of
+    /// a language.
+    pub fn set_file_language(&mut self, file: Handle<File>, language: &str) {
+        let language = self.add_string(language);
+        self.file_languages[file] = ControlledOption::some(language);
+    }
+
+    /// Returns the name of the language the given file was analyzed with, if it is known.
+    pub fn file_language(&self, file: Handle<File>) -> Option<&str> {
+        self.file_languages
+            .get(file)
+            .and_then(|language| language.into_option())
+            .map(|language| &self[language])
+    }
+
+    /// Returns an iterator over all of the files in the stack graph that are tagged with the given
+    /// language.
+    pub fn iter_files_in_language<'a>(
+        &'a self,
+        language: &'a str,
+    ) -> impl Iterator<Item = Handle<File>> + 'a {
+        self.iter_files()
+            .filter(move |file| self.file_language(*file) == Some(language))
+    }
+
+    /// Records that the given region of a file contained a parse error, so that the graph of the
+    /// file may be missing nodes and edges for it.
+    pub fn add_parse_error(&mut self, file: Handle<File>, span: Span) {
+        self.file_parse_errors[file].push(span);
+    }
+
+    /// Returns the regions of the given file that contained parse errors, in the order they were
+    /// recorded.  The graph of a file without parse errors is complete.
+    pub fn parse_errors(&self, file: Handle<File>) -> &[Span] {
+        self.file_parse_errors
+            .get(file)
+            .map(|spans| spans.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Assigns a file to a root partition, such as the package or crate it belongs to.  This
+    /// overrides the partition of the file's [root][Self::set_root_partition], if it has one.  See
+    /// [`add_partition_visibility`][Self::add_partition_visibility] for how partitions restrict
+    /// path stitching.
+    pub fn set_file_partition(&mut self, file: Handle<File>, partition: &str) {
+        let partition = self.add_string(partition);
+        self.file_partitions[file] = ControlledOption::some(partition);
+        self.has_partitions = true;
+    }
+
+    /// Assigns all files in the given file root, including files that are added later, to a root
+    /// partition.  This is convenient for graphs whose files are loaded on demand, for instance
+    /// from a database, and whose file roots correspond to packages.
+    pub fn set_root_partition(&mut self, root: &str, partition: &str) {
+        let partition = self.add_string(partition);
+        self.root_partitions.insert(root.to_string(), partition);
+        self.has_partitions = true;
+    }
+
+    /// Returns the root partition of the given file, if it belongs to one.
+    pub fn file_partition(&self, file: Handle<File>) -> Option<&str> {
+        self.file_partition_handle(file)
+            .map(|partition| &self[partition])
+    }
+
+    fn file_partition_handle(&self, file: Handle<File>) -> Option<Handle<InternedString>> {
+        if let Some(partition) = self
+            .file_partitions
+            .get(file)
+            .and_then(|partition| partition.into_option())
+        {
+            return Some(partition);
+        }
+        let root = self[file].root()?;
+        self.root_partitions.get(root).copied()
+    }
+
+    /// Allows paths to cross the root node from files in the `from` partition into files in the
+    /// `to` partition.  Paths can always cross between files of the same partition, and from and
+    /// into files that do not belong to any partition.  Visibility is not transitive, and applies
+    /// to each crossing of a path separately, so a definition that a package re-exports from an
+    /// internal package is only found if the re-exporting package can see the internal one.
+    ///
+    /// Root partitions are enforced by the [path stitcher][crate::stitching], for paths that are
+    /// stitched in this graph.  They are not serialized, and have to be declared again on graphs
+    /// that are loaded from storage.
+    pub fn add_partition_visibility(&mut self, from: &str, to: &str) {
+        let from = self.add_string(from);
+        let to = self.add_string(to);
+        self.partition_visibility.insert((from, to));
+        self.has_partitions = true;
+    }
+
+    /// Returns whether any root partitions or visibility between them were declared.
+    pub fn has_root_partitions(&self) -> bool {
+        self.has_partitions
+    }
+
+    /// Returns whether a path can cross the root node from the given file into the other one.
+    pub fn is_visible_across_root(&self, from_file: Handle<File>, to_file: Handle<File>) -> bool {
+        if !self.has_partitions {
+            return true;
+        }
+        let (from, to) = match (
+            self.file_partition_handle(from_file),
+            self.file_partition_handle(to_file),
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return true,
+        };
+        from == to || self.partition_visibility.contains(&(from, to))
+    }
+
+    /// Copies the root partitions of file roots, and the visibility between partitions, of the
+    /// given graph into this graph.
+    pub(crate) fn add_partitions_from_graph(&mut self, other: &StackGraph) {
+        for (root, partition) in &other.root_partitions {
+            self.set_root_partition(root, &other[*partition]);
+        }
+        for (from, to) in &other.partition_visibility {
+            self.add_partition_visibility(&other[*from], &other[*to]);
+        }
+    }
+
+    /// Copies the root partition that was explicitly assigned to a file of the given graph to a
+    /// file of this graph.
+    pub(crate) fn copy_file_partition(
+        &mut self,
+        file: Handle<File>,
+        other: &StackGraph,
+        other_file: Handle<File>,
+    ) {
+        if let Some(partition) = other
+            .file_partitions
+            .get(other_file)
+            .and_then(|partition| partition.into_option())
+        {
+            self.set_file_partition(file, &other[partition]);
+        }
+    }
+}
+
+/// Maps file root IDs to local directories.
+///
+/// Graphs and databases that name their files using root IDs can be built on one machine and used
+/// on another, where the roots are checked out at different locations.  A `FileRoots` value
+/// describes the local locations, and is used to translate between file names and local paths.
+#[derive(Clone, Debug, Default)]
+pub struct FileRoots {
+    roots: Vec<(String, PathBuf)>,
+}
+
+impl FileRoots {
+    pub fn new() -> FileRoots {
+        FileRoots::default()
+    }
+
+    /// Maps the given root ID to a local directory, replacing any previous mapping for the root.
+    pub fn add_root<P: AsRef<Path>>(&mut self, root: &str, directory: P) {
+        let directory = directory.as_ref().to_path_buf();
+        match self.roots.iter_mut().find(|(id, _)| id == root) {
+            Some((_, existing)) => *existing = directory,
+            None => self.roots.push((root.to_string(), directory)),
+        }
+    }
+
+    /// Returns the local directory of the given root ID, if it is mapped.
+    pub fn root_directory(&self, root: &str) -> Option<&Path> {
+        self.roots
+            .iter()
+            .find(|(id, _)| id == root)
+            .map(|(_, directory)| directory.as_path())
+    }
+
+    /// Returns the local path of the given file.  Files that belong to a root are located in the
+    /// root's local directory.  The names of files that do not belong to a root, or that belong
+    /// to an unmapped root, are returned as is.
+    pub fn local_path(&self, file: &File) -> PathBuf {
+        if let Some(directory) = file.root().and_then(|root| self.root_directory(root)) {
+            return directory.join(file.relative_path());
+        }
+        PathBuf::from(file.name())
+    }
+
+    /// Returns the file name for the given local path.  If the path is in the directory of one of
+    /// the roots, the name consists of the root ID and the path relative to the root directory.
+    /// When roots are nested, the innermost root is used.  Paths outside of any root are named by
+    /// the path itself.
+    pub fn file_name(&self, path: &Path) -> String {
+        self.roots
+            .iter()
+            .filter_map(|(root, directory)| {
+                path.strip_prefix(directory)
+                    .ok()
+                    .map(|relative_path| (root, directory, relative_path))
+            })
+            .max_by_key(|(_, directory, _)| directory.components().count())
+            .map(|(root, _, relative_path)| {
+                let relative_path = relative_path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                file_name_in_root(root, &relative_path)
+            })
+            .unwrap_or_else(|| path.to_string_lossy().to_string())
+    }
 }
 
 impl StackGraph {
-    /// Returns an iterator of all of the nodes that belong to a particular file.  Note that this
-    /// does **_not_** include the singleton _root_ or _jump to scope_ nodes.
+    /// Returns an iterator of all of the nodes that belong to a particular file, ordered by their
+    /// local IDs, regardless of the graph's [iteration order][Self::set_iteration_order].  Note
+    /// that this does **_not_** include the singleton _root_ or _jump to scope_ nodes.
     pub fn nodes_for_file(&self, file: Handle<File>) -> impl Iterator<Item = Handle<Node>> + '_ {
         self.node_id_handles.nodes_for_file(file)
     }
 
-    /// Returns an iterator over all of the handles of all of the files in this stack graph.  (Note
-    /// that because we're only returning _handles_, this iterator does not retain a reference to
-    /// the `StackGraph`.)
+    /// Returns an iterator over all of the handles of all of the files in this stack graph, in the
+    /// graph's [iteration order][Self::set_iteration_order].
     pub fn iter_files(&self) -> impl Iterator<Item = Handle<File>> + '_ {
-        self.files.iter_handles()
+        match self.iteration_order {
+            IterationOrder::Insertion => Either::Left(self.files.iter_handles()),
+            IterationOrder::Sorted => {
+                let mut files = self.files.iter_handles().collect::<Vec<_>>();
+                files.sort_by(|a, b| self[*a].name().cmp(self[*b].name()));
+                Either::Right(files.into_iter())
+            }
+        }
     }
 }
 
@@ -596,6 +952,12 @@ impl Handle<File> {
 ///
 /// Each node (except for the _root node_ and _jump to scope_ node) lives in a file, and has a
 /// _local ID_ that must be unique within its file.
+///
+/// Local IDs starting at [`SYNTHETIC_LOCAL_ID_BASE`][] are reserved for _synthetic_ nodes, which
+/// tooling creates at query time, such as a reference node for the identifier that a completion
+/// is requested for.  Graph builders never assign IDs in that range, so synthetic nodes cannot
+/// collide with the nodes of a file.  See [`StackGraph::synthetic_scope`][] for how to create
+/// them.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct NodeID {
@@ -606,6 +968,9 @@ pub struct NodeID {
 pub(crate) const ROOT_NODE_ID: u32 = 1;
 pub(crate) const JUMP_TO_NODE_ID: u32 = 2;
 
+/// The first local ID of the namespace that is reserved for [synthetic nodes][NodeID].
+pub const SYNTHETIC_LOCAL_ID_BASE: u32 = 1 << 31;
+
 impl NodeID {
     /// Returns the ID of the singleton _root node_.
     #[inline(always)]
@@ -646,6 +1011,13 @@ impl NodeID {
         self.file.is_none() && self.local_id == JUMP_TO_NODE_ID
     }
 
+    /// Returns whether this ID is in the namespace that is reserved for [synthetic
+    /// nodes][NodeID].
+    #[inline(always)]
+    pub fn is_synthetic(self) -> bool {
+        self.file.is_some() && self.local_id >= SYNTHETIC_LOCAL_ID_BASE
+    }
+
     /// Returns the file that this node belongs to.  Returns `None` for the singleton _root_ and
     /// _jump to scope_ nodes, which belong to all files.
     #[inline(always)]
@@ -716,7 +1088,76 @@ pub enum Node {
     Scope(ScopeNode),
 }
 
+/// The kind of a [node][Node], without any of its data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NodeKind {
+    DropScopes,
+    JumpTo,
+    PopScopedSymbol,
+    PopSymbol,
+    PushScopedSymbol,
+    PushSymbol,
+    Root,
+    Scope,
+}
+
+impl NodeKind {
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+/// A set of [node kinds][NodeKind].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct NodeKindSet(u8);
+
+impl NodeKindSet {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(self, kind: NodeKind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+
+    pub fn insert(&mut self, kind: NodeKind) {
+        self.0 |= kind.bit();
+    }
+
+    pub fn remove(&mut self, kind: NodeKind) {
+        self.0 &= !kind.bit();
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl FromIterator<NodeKind> for NodeKindSet {
+    fn from_iter<I: IntoIterator<Item = NodeKind>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for kind in iter {
+            set.insert(kind);
+        }
+        set
+    }
+}
+
 impl Node {
+    /// Returns the kind of this node.
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            Node::DropScopes(_) => NodeKind::DropScopes,
+            Node::JumpTo(_) => NodeKind::JumpTo,
+            Node::PopScopedSymbol(_) => NodeKind::PopScopedSymbol,
+            Node::PopSymbol(_) => NodeKind::PopSymbol,
+            Node::PushScopedSymbol(_) => NodeKind::PushScopedSymbol,
+            Node::PushSymbol(_) => NodeKind::PushSymbol,
+            Node::Root(_) => NodeKind::Root,
+            Node::Scope(_) => NodeKind::Scope,
+        }
+    }
+
     #[inline(always)]
     pub fn is_exported_scope(&self) -> bool {
         match self {
@@ -835,10 +1276,42 @@ impl StackGraph {
         self.node_id_handles.unused_id(file)
     }
 
-    /// Returns an iterator of all of the nodes in the graph.  (Note that because we're only
-    /// returning _handles_, this iterator does not retain a reference to the `StackGraph`.)
+    /// Returns an unused [`NodeID`][] for the given file in the namespace that is reserved for
+    /// [synthetic nodes][NodeID].  Such IDs never collide with the IDs that graph builders assign.
+    pub fn new_synthetic_node_id(&mut self, file: Handle<File>) -> NodeID {
+        self.node_id_handles.unused_synthetic_id(file)
+    }
+
+    /// Returns an iterator of all of the nodes in the graph, in the graph's [iteration
+    /// order][Self::set_iteration_order].  (Note that because we're only returning _handles_, this
+    /// iterator does not retain a reference to the `StackGraph`.)
     pub fn iter_nodes(&self) -> impl Iterator<Item = Handle<Node>> {
-        self.nodes.iter_handles()
+        match self.iteration_order {
+            IterationOrder::Insertion => Either::Left(self.nodes.iter_handles()),
+            IterationOrder::Sorted => {
+                let mut nodes = self.nodes.iter_handles().collect::<Vec<_>>();
+                nodes.sort_by(|a, b| self.cmp_node_ids(self[*a].id(), self[*b].id()));
+                Either::Right(nodes.into_iter())
+            }
+        }
+    }
+
+    /// Returns the handle to the node with the given stable ID, which consists of the name of the
+    /// node's file and its local ID, if it exists.  The singleton _root_ and _jump to scope_ nodes
+    /// have no file.  Unlike handles, stable IDs remain valid when a graph is serialized and
+    /// loaded again, or merged into another graph.
+    pub fn node_handle_for_stable_id(
+        &self,
+        file: Option<&str>,
+        local_id: u32,
+    ) -> Option<Handle<Node>> {
+        let id = match file {
+            Some(file) => NodeID::new_in_file(self.get_file(file)?, local_id),
+            None if local_id == ROOT_NODE_ID => NodeID::root(),
+            None if local_id == JUMP_TO_NODE_ID => NodeID::jump_to(),
+            None => return None,
+        };
+        self.node_for_id(id)
     }
 
     /// Returns the handle to the node with a particular ID, if it exists.
@@ -1330,20 +1803,44 @@ impl Display for RootNode {
     }
 }
 
+type NodeHandleTable = SupplementalArena<File, Vec<Option<Handle<Node>>>>;
+
 struct NodeIDHandles {
-    files: SupplementalArena<File, Vec<Option<Handle<Node>>>>,
+    files: NodeHandleTable,
+    /// The handles of synthetic nodes, indexed by their offset from [`SYNTHETIC_LOCAL_ID_BASE`][].
+    synthetic: NodeHandleTable,
 }
 
 impl NodeIDHandles {
     fn new() -> NodeIDHandles {
         NodeIDHandles {
             files: SupplementalArena::new(),
+            synthetic: SupplementalArena::new(),
+        }
+    }
+
+    /// Returns the table that holds the given node ID, and the ID's index in that table.
+    fn table(&self, node_id: NodeID) -> (&NodeHandleTable, usize) {
+        if node_id.is_synthetic() {
+            let node_index = (node_id.local_id - SYNTHETIC_LOCAL_ID_BASE) as usize;
+            (&self.synthetic, node_index)
+        } else {
+            (&self.files, node_id.local_id as usize)
+        }
+    }
+
+    fn table_mut(&mut self, node_id: NodeID) -> (&mut NodeHandleTable, usize) {
+        if node_id.is_synthetic() {
+            let node_index = (node_id.local_id - SYNTHETIC_LOCAL_ID_BASE) as usize;
+            (&mut self.synthetic, node_index)
+        } else {
+            (&mut self.files, node_id.local_id as usize)
         }
     }
 
     fn try_handle_for_id(&self, node_id: NodeID) -> Option<Handle<Node>> {
-        let file_entry = self.files.get(node_id.file().unwrap())?;
-        let node_index = node_id.local_id as usize;
+        let (table, node_index) = self.table(node_id);
+        let file_entry = table.get(node_id.file().unwrap())?;
         if node_index >= file_entry.len() {
             return None;
         }
@@ -1351,8 +1848,8 @@ impl NodeIDHandles {
     }
 
     fn handle_for_id(&mut self, node_id: NodeID) -> Option<Handle<Node>> {
-        let file_entry = &mut self.files[node_id.file().unwrap()];
-        let node_index = node_id.local_id as usize;
+        let (table, node_index) = self.table_mut(node_id);
+        let file_entry = &mut table[node_id.file().unwrap()];
         if node_index >= file_entry.len() {
             file_entry.resize(node_index + 1, None);
         }
@@ -1360,11 +1857,26 @@ impl NodeIDHandles {
     }
 
     fn set_handle_for_id(&mut self, node_id: NodeID, handle: Handle<Node>) {
-        let file_entry = &mut self.files[node_id.file().unwrap()];
-        let node_index = node_id.local_id as usize;
+        let (table, node_index) = self.table_mut(node_id);
+        let file_entry = &mut table[node_id.file().unwrap()];
         file_entry[node_index] = Some(handle);
     }
 
+    /// Forgets the handle of the given node ID, so that the ID can be used again.
+    fn remove_id(&mut self, node_id: NodeID) {
+        let (table, node_index) = self.table_mut(node_id);
+        let file_entry = match table.get_mut(node_id.file().unwrap()) {
+            Some(file_entry) => file_entry,
+            None => return,
+        };
+        if let Some(entry) = file_entry.get_mut(node_index) {
+            *entry = None;
+        }
+        while let Some(None) = file_entry.last() {
+            file_entry.pop();
+        }
+    }
+
     fn unused_id(&mut self, file: Handle<File>) -> NodeID {
         let local_id = self
             .files
@@ -1374,12 +1886,19 @@ impl NodeIDHandles {
         NodeID::new_in_file(file, local_id)
     }
 
+    fn unused_synthetic_id(&mut self, file: Handle<File>) -> NodeID {
+        let offset = self
+            .synthetic
+            .get(file)
+            .map(|file_entry| file_entry.len() as u32)
+            .unwrap_or(0);
+        NodeID::new_in_file(file, SYNTHETIC_LOCAL_ID_BASE + offset)
+    }
+
     fn nodes_for_file(&self, file: Handle<File>) -> impl Iterator<Item = Handle<Node>> + '_ {
-        let file_entry = match self.files.get(file) {
-            Some(file_entry) => file_entry,
-            None => return Either::Left(std::iter::empty()),
-        };
-        Either::Right(file_entry.iter().filter_map(|entry| *entry))
+        let file_entry = self.files.get(file).into_iter().flatten();
+        let synthetic_entry = self.synthetic.get(file).into_iter().flatten();
+        file_entry.chain(synthetic_entry).filter_map(|entry| *entry)
     }
 }
 
@@ -1461,20 +1980,99 @@ impl<'a> Display for DisplayScopeNode<'a> {
 pub struct Edge {
     pub source: Handle<Node>,
     pub sink: Handle<Node>,
-    pub precedence: i32,
+    pub precedence: Precedence,
+}
+
+/// The precedence of an edge, which decides which of two paths shadows the other.
+///
+/// If two paths follow the same edges up to some node, and then follow different edges out of
+/// that node, the path that follows the edge with the _higher_ precedence shadows the other one,
+/// and only the unshadowed path is reported as a binding.  Precedences are only compared between
+/// edges with the same source node, so they only need to be consistent among the outgoing edges of
+/// a node.  Most edges have the [default][Self::DEFAULT] precedence, and language rules should use
+/// [`HIGH`][Self::HIGH] for edges to bindings that shadow others, such as local definitions
+/// shadowing outer ones, and [`LOW`][Self::LOW] for fallbacks, such as builtins, instead of
+/// choosing their own numbers.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Precedence(i32);
+
+impl Precedence {
+    /// The precedence of most edges, which neither shadow nor are shadowed by each other.
+    pub const DEFAULT: Precedence = Precedence(0);
+    /// The precedence of edges that shadow edges with the default precedence.
+    pub const HIGH: Precedence = Precedence(1);
+    /// The precedence of edges that are shadowed by edges with the default precedence.
+    pub const LOW: Precedence = Precedence(-1);
+
+    /// Returns the precedence with the given numeric value.  Higher values take precedence.
+    pub const fn new(value: i32) -> Precedence {
+        Precedence(value)
+    }
+
+    /// Returns the numeric value of this precedence, as stored in serialized graphs and used in
+    /// the C API.
+    pub const fn value(self) -> i32 {
+        self.0
+    }
+
+    /// Returns whether an edge with this precedence shadows an edge with the other precedence
+    /// that leaves the same node.
+    pub fn shadows(self, other: Precedence) -> bool {
+        self > other
+    }
+}
+
+impl From<i32> for Precedence {
+    fn from(value: i32) -> Precedence {
+        Precedence(value)
+    }
+}
+
+impl From<Precedence> for i32 {
+    fn from(precedence: Precedence) -> i32 {
+        precedence.0
+    }
+}
+
+impl Display for Precedence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
+/// The confidence of edges that are certain, which is the default.
+pub const MAX_EDGE_CONFIDENCE: u8 = 100;
+
 pub(crate) struct OutgoingEdge {
     sink: Handle<Node>,
-    precedence: i32,
+    precedence: Precedence,
+    condition: ControlledOption<Handle<InternedString>>,
+    confidence: u8,
 }
 
 impl StackGraph {
     /// Adds a new edge to the stack graph.
-    pub fn add_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, precedence: i32) {
+    pub fn add_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, precedence: Precedence) {
         let edges = &mut self.outgoing_edges[source];
         if let Err(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
-            edges.insert(index, OutgoingEdge { sink, precedence });
+            edges.insert(
+                index,
+                OutgoingEdge {
+                    sink,
+                    precedence,
+                    condition: ControlledOption::none(),
+                    confidence: MAX_EDGE_CONFIDENCE,
+                },
+            );
+            if let Some(journal) = &mut self.synthetic_journal {
+                let degree = self
+                    .incoming_edges
+                    .get(sink)
+                    .cloned()
+                    .unwrap_or(Degree::Zero);
+                journal.record_edge(source, sink, degree);
+            }
             self.incoming_edges[sink] += Degree::One;
         }
     }
@@ -1484,7 +2082,7 @@ impl StackGraph {
         &mut self,
         source: Handle<Node>,
         sink: Handle<Node>,
-        precedence: i32,
+        precedence: Precedence,
     ) {
         let edges = &mut self.outgoing_edges[source];
         if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
@@ -1492,15 +2090,92 @@ impl StackGraph {
         }
     }
 
-    /// Returns an iterator of all of the edges that begin at a particular source node.
+    /// Attaches a condition to the given edge between the root node and another node.  Conditions
+    /// let languages with path-based module systems, such as Java packages or Go modules, name the
+    /// module that a root edge belongs to, instead of encoding the module path as a chain of
+    /// symbols.
+    ///
+    /// A path that arrives at the root node along an edge with a condition can only leave the root
+    /// node along edges with the same condition, or without a condition.  A path that arrives at
+    /// the root node along an edge without a condition can only leave it along edges without a
+    /// condition.  A path that starts at the root node can leave it along any edge.
+    ///
+    /// Does nothing if the edge does not exist, or if neither of its nodes is the root node.
+    pub fn set_edge_condition(
+        &mut self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+        condition: Handle<InternedString>,
+    ) {
+        if !self[source].is_root() && !self[sink].is_root() {
+            return;
+        }
+        let edges = &mut self.outgoing_edges[source];
+        if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
+            edges[index].condition = ControlledOption::some(condition);
+        }
+    }
+
+    /// Returns the condition of the given edge, if it has one.  See
+    /// [`set_edge_condition`][Self::set_edge_condition].
+    pub fn edge_condition(
+        &self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+    ) -> Option<Handle<InternedString>> {
+        let edges = self.outgoing_edges.get(source)?;
+        let index = edges.binary_search_by_key(&sink, |o| o.sink).ok()?;
+        edges[index].condition.into_option()
+    }
+
+    /// Sets the confidence of the given edge, as a percentage between 0 and 100.  Languages whose
+    /// rules cannot always determine name bindings precisely, such as dynamic languages that
+    /// resolve members via duck typing, use this to mark the edges that are guessed.  The
+    /// [confidence][crate::partial::PartialPath::confidence] of a path is the product of the
+    /// confidences of its edges.  Larger values are capped at 100.
+    ///
+    /// Edges are certain, with a confidence of 100, unless a confidence is set.  Does nothing if
+    /// the edge does not exist.
+    pub fn set_edge_confidence(
+        &mut self,
+        source: Handle<Node>,
+        sink: Handle<Node>,
+        confidence: u8,
+    ) {
+        let edges = &mut self.outgoing_edges[source];
+        if let Ok(index) = edges.binary_search_by_key(&sink, |o| o.sink) {
+            edges[index].confidence = confidence.min(MAX_EDGE_CONFIDENCE);
+        }
+    }
+
+    /// Returns the confidence of the given edge, as a percentage.  See
+    /// [`set_edge_confidence`][Self::set_edge_confidence].  Returns `None` if the edge does not
+    /// exist.
+    pub fn edge_confidence(&self, source: Handle<Node>, sink: Handle<Node>) -> Option<u8> {
+        let edges = self.outgoing_edges.get(source)?;
+        let index = edges.binary_search_by_key(&sink, |o| o.sink).ok()?;
+        Some(edges[index].confidence)
+    }
+
+    /// Returns an iterator of all of the edges that begin at a particular source node.  In the
+    /// graph's [sorted iteration order][IterationOrder::Sorted], the edges are ordered by the IDs
+    /// of their sink nodes.
     pub fn outgoing_edges(&self, source: Handle<Node>) -> impl Iterator<Item = Edge> + '_ {
-        match self.outgoing_edges.get(source) {
-            Some(edges) => Either::Right(edges.iter().map(move |o| Edge {
+        let edges = match self.outgoing_edges.get(source) {
+            Some(edges) => edges.iter().map(move |o| Edge {
                 source,
                 sink: o.sink,
                 precedence: o.precedence,
-            })),
-            None => Either::Left(std::iter::empty()),
+            }),
+            None => return Either::Left(std::iter::empty()),
+        };
+        match self.iteration_order {
+            IterationOrder::Insertion => Either::Right(Either::Left(edges)),
+            IterationOrder::Sorted => {
+                let mut edges = edges.collect::<Vec<_>>();
+                edges.sort_by(|a, b| self.cmp_node_ids(self[a.sink].id(), self[b.sink].id()));
+                Either::Right(Either::Right(edges.into_iter()))
+            }
         }
     }
 
@@ -1513,6 +2188,213 @@ impl StackGraph {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Node aliases
+
+impl StackGraph {
+    /// Declares that two nodes are equivalent, such as a re-exported definition and its original.
+    /// Path stitching treats a path that arrives at either node as if it arrived at both: the path
+    /// is extended from both nodes, and it is complete if it would be complete at either of them.
+    /// This is cheaper than modeling re-exports with chains of push and pop nodes.
+    ///
+    /// Aliases are transitive, so declaring `a` equivalent to `b`, and `b` equivalent to `c`, makes
+    /// all three nodes equivalent.  The singleton _root_ and _jump to scope_ nodes cannot have
+    /// aliases, and declaring them equivalent to another node has no effect.
+    pub fn add_node_alias(&mut self, node: Handle<Node>, alias: Handle<Node>) {
+        if node == alias || self[node].file().is_none() || self[alias].file().is_none() {
+            return;
+        }
+        let mut equivalent = SmallVec::<[Handle<Node>; 4]>::new();
+        equivalent.push(node);
+        equivalent.push(alias);
+        equivalent.extend_from_slice(self.node_aliases(node));
+        equivalent.extend_from_slice(self.node_aliases(alias));
+        equivalent.sort();
+        equivalent.dedup();
+        for member in &equivalent {
+            if let Some(journal) = &mut self.synthetic_journal {
+                journal.record_aliases(*member, self.node_aliases.get(*member));
+            }
+            self.node_aliases[*member] = equivalent
+                .iter()
+                .copied()
+                .filter(|other| other != member)
+                .collect();
+        }
+    }
+
+    /// Returns the nodes that are declared equivalent to the given node.
+    pub fn node_aliases(&self, node: Handle<Node>) -> &[Handle<Node>] {
+        self.node_aliases
+            .get(node)
+            .map_or(&[], |aliases| aliases.as_slice())
+    }
+
+    /// Returns whether two different nodes are declared equivalent.
+    pub fn are_aliases(&self, node: Handle<Node>, other: Handle<Node>) -> bool {
+        self.node_aliases(node).contains(&other)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Speculative nodes
+
+impl StackGraph {
+    /// Marks a node as speculative.  Languages use this for nodes whose edges are guesses that are
+    /// often wrong, such as imports of modules whose names are computed from strings at runtime.
+    /// Unlike a low [edge confidence][Self::set_edge_confidence], which ranks the definitions that
+    /// a reference resolves to, speculative answers are meant to be hidden entirely by consumers
+    /// that only want answers they can rely on.  A path that visits a speculative node is
+    /// [speculative][crate::partial::PartialPath::is_speculative] itself.
+    ///
+    /// The singleton _root_ and _jump to scope_ nodes cannot be speculative, and marking them has
+    /// no effect.
+    pub fn mark_speculative(&mut self, node: Handle<Node>) {
+        if self[node].file().is_none() {
+            return;
+        }
+        self.speculative_nodes.add(node);
+    }
+
+    /// Returns whether a node is [speculative][Self::mark_speculative].
+    pub fn is_speculative(&self, node: Handle<Node>) -> bool {
+        self.speculative_nodes.contains(node)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Synthetic nodes
+
+/// Records how a stack graph changes while a [synthetic scope][StackGraph::synthetic_scope] is
+/// alive, so that the changes can be undone when it ends.
+struct SyntheticJournal {
+    /// The number of nodes in the graph when the scope began.  All nodes with larger handles were
+    /// added while the scope was alive.
+    first_node: usize,
+    /// The edges that were added while the scope was alive.
+    edges: Vec<(Handle<Node>, Handle<Node>)>,
+    /// The incoming degrees of existing nodes before edges to them were added.
+    incoming_degrees: Vec<(Handle<Node>, Degree)>,
+    /// The aliases of existing nodes before new aliases were declared for them.
+    node_aliases: Vec<(Handle<Node>, SmallVec<[Handle<Node>; 2]>)>,
+}
+
+impl SyntheticJournal {
+    fn is_existing(&self, node: Handle<Node>) -> bool {
+        node.as_usize() < self.first_node
+    }
+
+    fn record_edge(&mut self, source: Handle<Node>, sink: Handle<Node>, degree: Degree) {
+        self.edges.push((source, sink));
+        if self.is_existing(sink) {
+            self.incoming_degrees.push((sink, degree));
+        }
+    }
+
+    fn record_aliases(
+        &mut self,
+        node: Handle<Node>,
+        aliases: Option<&SmallVec<[Handle<Node>; 2]>>,
+    ) {
+        if self.is_existing(node) {
+            self.node_aliases
+                .push((node, aliases.cloned().unwrap_or_default()));
+        }
+    }
+}
+
+impl StackGraph {
+    /// Begins a scope for [synthetic nodes][NodeID], which tooling adds to the graph to answer a
+    /// single query, such as a reference node for the identifier that a completion is requested
+    /// for.  Create their IDs with [`new_synthetic_node_id`][Self::new_synthetic_node_id], so that
+    /// they do not collide with the nodes of the file.
+    ///
+    /// All nodes and edges that are added to the graph while the returned scope is alive, and all
+    /// node aliases that are declared for existing nodes, are removed again when the scope is
+    /// dropped.  Other changes, such as new symbols and files, are kept.  Partial paths that refer
+    /// to the removed nodes must not be used after the scope ends.  Panics if the graph already
+    /// has a synthetic scope.
+    pub fn synthetic_scope(&mut self) -> SyntheticScope<'_> {
+        assert!(
+            self.synthetic_journal.is_none(),
+            "synthetic scopes cannot be nested"
+        );
+        self.synthetic_journal = Some(SyntheticJournal {
+            first_node: self.nodes.len(),
+            edges: Vec::new(),
+            incoming_degrees: Vec::new(),
+            node_aliases: Vec::new(),
+        });
+        SyntheticScope { graph: self }
+    }
+
+    /// Undoes the changes that were recorded while the synthetic scope was alive.
+    fn end_synthetic_scope(&mut self) {
+        let journal = match self.synthetic_journal.take() {
+            Some(journal) => journal,
+            None => return,
+        };
+        for (source, sink) in &journal.edges {
+            if !journal.is_existing(*source) {
+                continue;
+            }
+            if let Some(edges) = self.outgoing_edges.get_mut(*source) {
+                edges.retain(|o| o.sink != *sink);
+            }
+            if let Some(edges) = self.edge_debug_info.get_mut(*source) {
+                edges.retain(|e| e.0 != *sink);
+            }
+        }
+        // Restore in reverse, so that the value from before the first change wins.
+        for (node, degree) in journal.incoming_degrees.into_iter().rev() {
+            self.incoming_edges[node] = degree;
+        }
+        for (node, aliases) in journal.node_aliases.into_iter().rev() {
+            self.node_aliases[node] = aliases;
+        }
+        for index in journal.first_node..self.nodes.len() {
+            let node = Handle::new(NonZeroU32::new(index as u32).unwrap());
+            let id = self[node].id();
+            self.node_id_handles.remove_id(id);
+            self.speculative_nodes.remove(node);
+        }
+        self.nodes.truncate(journal.first_node);
+        self.source_info.truncate(journal.first_node);
+        self.outgoing_edges.truncate(journal.first_node);
+        self.incoming_edges.truncate(journal.first_node);
+        self.node_debug_info.truncate(journal.first_node);
+        self.edge_debug_info.truncate(journal.first_node);
+        self.node_aliases.truncate(journal.first_node);
+    }
+}
+
+/// A scope for [synthetic nodes][NodeID], which is created by
+/// [`StackGraph::synthetic_scope`][].  It dereferences to the stack graph, and removes the nodes
+/// and edges that were added to the graph when it is dropped.
+pub struct SyntheticScope<'a> {
+    graph: &'a mut StackGraph,
+}
+
+impl Deref for SyntheticScope<'_> {
+    type Target = StackGraph;
+
+    fn deref(&self) -> &StackGraph {
+        &*self.graph
+    }
+}
+
+impl DerefMut for SyntheticScope<'_> {
+    fn deref_mut(&mut self) -> &mut StackGraph {
+        &mut *self.graph
+    }
+}
+
+impl Drop for SyntheticScope<'_> {
+    fn drop(&mut self) {
+        self.graph.end_synthetic_scope();
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Source code
 
@@ -1547,6 +2429,75 @@ impl StackGraph {
     pub fn source_info_mut(&mut self, node: Handle<Node>) -> &mut SourceInfo {
         &mut self.source_info[node]
     }
+
+    /// Returns the kind of entity that a stack graph node represents, based on its syntax type.
+    /// Returns `None` if the node has no syntax type.
+    pub fn definition_kind(&self, node: Handle<Node>) -> Option<DefinitionKind> {
+        let syntax_type = self.source_info(node)?.syntax_type.into_option()?;
+        Some(DefinitionKind::from_syntax_type(&self[syntax_type]))
+    }
+}
+
+/// The kind of entity that a definition represents, which is derived from the syntax type of its
+/// node.  Common syntax types are classified as one of the known kinds, while any other syntax
+/// type is kept as is, so that languages can use kinds that are specific to them.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DefinitionKind {
+    Module,
+    Class,
+    Interface,
+    Type,
+    Function,
+    Method,
+    Field,
+    Variable,
+    Other(String),
+}
+
+impl DefinitionKind {
+    /// Returns the kind for the given syntax type.
+    pub fn from_syntax_type(syntax_type: &str) -> DefinitionKind {
+        match syntax_type {
+            "module" => DefinitionKind::Module,
+            "class" => DefinitionKind::Class,
+            "interface" => DefinitionKind::Interface,
+            "type" => DefinitionKind::Type,
+            "function" => DefinitionKind::Function,
+            "method" => DefinitionKind::Method,
+            "field" => DefinitionKind::Field,
+            "variable" => DefinitionKind::Variable,
+            other => DefinitionKind::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the syntax type of this kind.
+    pub fn as_str(&self) -> &str {
+        match self {
+            DefinitionKind::Module => "module",
+            DefinitionKind::Class => "class",
+            DefinitionKind::Interface => "interface",
+            DefinitionKind::Type => "type",
+            DefinitionKind::Function => "function",
+            DefinitionKind::Method => "method",
+            DefinitionKind::Field => "field",
+            DefinitionKind::Variable => "variable",
+            DefinitionKind::Other(other) => other,
+        }
+    }
+}
+
+impl Display for DefinitionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for DefinitionKind {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DefinitionKind::from_syntax_type(s))
+    }
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -1559,6 +2510,13 @@ pub struct DebugInfo {
 }
 
 impl DebugInfo {
+    /// The key of the debug entry containing the location of the TSG statement that created a
+    /// node or edge, as `line LINE column COLUMN`.
+    pub const TSG_LOCATION: &'static str = "tsg_location";
+    /// The key of the debug entry containing the location of the TSG stanza (rule) that created a
+    /// node or edge, as `PATH:LINE:COLUMN`.
+    pub const TSG_RULE: &'static str = "tsg_rule";
+
     pub fn add(&mut self, key: Handle<InternedString>, value: Handle<InternedString>) {
         self.entries.push(DebugEntry { key, value });
     }
@@ -1566,6 +2524,14 @@ impl DebugInfo {
     pub fn iter(&self) -> std::slice::Iter<DebugEntry> {
         self.entries.iter()
     }
+
+    /// Returns the value of the first debug entry with the given key, if any.
+    pub fn get<'a>(&self, graph: &'a StackGraph, key: &str) -> Option<&'a str> {
+        self.entries
+            .iter()
+            .find(|entry| &graph[entry.key] == key)
+            .map(|entry| &graph[entry.value])
+    }
 }
 
 /// A debug entry consisting of a string key-value air of strings.
@@ -1595,6 +2561,20 @@ impl StackGraph {
         })
     }
 
+    /// Returns the location of the TSG rule that created the stack graph node, if it was
+    /// recorded.  See [`DebugInfo::TSG_RULE`][] for the format.
+    pub fn node_rule(&self, node: Handle<Node>) -> Option<&str> {
+        self.node_debug_info(node)
+            .and_then(|info| info.get(self, DebugInfo::TSG_RULE))
+    }
+
+    /// Returns the location of the TSG rule that created the stack graph edge, if it was
+    /// recorded.  See [`DebugInfo::TSG_RULE`][] for the format.
+    pub fn edge_rule(&self, source: Handle<Node>, sink: Handle<Node>) -> Option<&str> {
+        self.edge_debug_info(source, sink)
+            .and_then(|info| info.get(self, DebugInfo::TSG_RULE))
+    }
+
     /// Returns a mutable reference to the debug info about the stack graph edge.
     pub fn edge_debug_info_mut(
         &mut self,
@@ -1613,6 +2593,440 @@ impl StackGraph {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Fingerprints
+
+/// A stable hash of the subgraph of a file.  Fingerprints are displayed as 16 hexadecimal digits,
+/// and can be parsed from that form.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Fingerprint(pub u64);
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Fingerprint)
+    }
+}
+
+impl StackGraph {
+    /// Returns the fingerprint of the subgraph of a file.  The subgraph consists of the file's
+    /// language, its nodes, and the edges between them and the singleton _root_ and _jump to scope_ nodes,
+    /// together with their source and debug info and edge conditions, the [aliases][Self::add_node_alias] between
+    /// the file's nodes, and which of the file's nodes are [speculative][Self::mark_speculative].
+    ///
+    /// The fingerprint only depends on the data that is preserved when the subgraph is
+    /// [serialized][crate::serde::StackGraph], and not on the handles of nodes, files, or strings.
+    /// It is therefore the same when the subgraph is loaded into a different stack graph, which
+    /// makes it suitable to detect unchanged graphs, and to verify cached graphs when they are
+    /// loaded.
+    pub fn fingerprint_for_file(&self, file: Handle<File>) -> Fingerprint {
+        let mut hasher = FingerprintHasher::new();
+        hasher.write_str(self[file].name());
+        match self.file_language(file) {
+            Some(language) => {
+                hasher.write_u8(1);
+                hasher.write_str(language);
+            }
+            None => hasher.write_u8(0),
+        }
+
+        let mut nodes = self.nodes_for_file(file).collect::<Vec<_>>();
+        nodes.sort_by_key(|node| self[*node].id().local_id());
+        hasher.write_usize(nodes.len());
+        for node in &nodes {
+            self.hash_node(&mut hasher, *node);
+        }
+
+        // Edges are ordered by their source and sink node IDs, which, unlike handles, do not
+        // depend on the order in which nodes were added.
+        let sort_key = |node: Handle<Node>| {
+            let id = self[node].id();
+            (id.file().is_some(), id.local_id())
+        };
+        let mut edges = nodes
+            .iter()
+            .copied()
+            .chain(std::iter::once(StackGraph::root_node()))
+            .flat_map(|source| self.outgoing_edges(source))
+            .filter(|edge| self[edge.sink].is_in_file(file))
+            .filter(|edge| self[edge.source].file().is_some() || self[edge.sink].file().is_some())
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|edge| (sort_key(edge.source), sort_key(edge.sink)));
+        hasher.write_usize(edges.len());
+        for edge in &edges {
+            self.hash_node_id(&mut hasher, self[edge.source].id());
+            self.hash_node_id(&mut hasher, self[edge.sink].id());
+            hasher.write_u32(edge.precedence.value() as u32);
+            match self.edge_condition(edge.source, edge.sink) {
+                Some(condition) => {
+                    hasher.write_u8(1);
+                    hasher.write_str(&self[condition]);
+                }
+                None => hasher.write_u8(0),
+            }
+            hasher.write_u8(
+                self.edge_confidence(edge.source, edge.sink)
+                    .unwrap_or(MAX_EDGE_CONFIDENCE),
+            );
+            self.hash_debug_info(&mut hasher, self.edge_debug_info(edge.source, edge.sink));
+        }
+
+        // Only aliases between nodes of the file are part of its subgraph.  Each pair is included
+        // once, ordered by the local IDs of its nodes.
+        let mut aliases = nodes
+            .iter()
+            .flat_map(|node| {
+                self.node_aliases(*node)
+                    .iter()
+                    .filter(|alias| self[**alias].file() == Some(file))
+                    .map(move |alias| (self[*node].id().local_id(), self[*alias].id().local_id()))
+            })
+            .filter(|(node, alias)| node < alias)
+            .collect::<Vec<_>>();
+        aliases.sort();
+        hasher.write_usize(aliases.len());
+        for (node, alias) in aliases {
+            hasher.write_u32(node);
+            hasher.write_u32(alias);
+        }
+
+        // Nodes are already ordered by their local IDs.
+        let speculative = nodes
+            .iter()
+            .filter(|node| self.is_speculative(**node))
+            .map(|node| self[*node].id().local_id())
+            .collect::<Vec<_>>();
+        hasher.write_usize(speculative.len());
+        for node in speculative {
+            hasher.write_u32(node);
+        }
+
+        hasher.finish()
+    }
+
+    fn hash_node(&self, hasher: &mut FingerprintHasher, handle: Handle<Node>) {
+        let node = &self[handle];
+        hasher.write_u8(match node {
+            Node::DropScopes(_) => 0,
+            Node::JumpTo(_) => 1,
+            Node::PopScopedSymbol(_) => 2,
+            Node::PopSymbol(_) => 3,
+            Node::PushScopedSymbol(_) => 4,
+            Node::PushSymbol(_) => 5,
+            Node::Root(_) => 6,
+            Node::Scope(_) => 7,
+        });
+        hasher.write_u32(node.id().local_id());
+        if let Some(symbol) = node.symbol() {
+            hasher.write_str(&self[symbol]);
+        }
+        if let Some(scope) = node.scope() {
+            self.hash_node_id(hasher, scope);
+        }
+        hasher.write_bool(node.is_definition());
+        hasher.write_bool(node.is_reference());
+        hasher.write_bool(node.is_exported_scope());
+
+        // A node without source info is indistinguishable from one with empty source info after
+        // it has been serialized, so both must result in the same fingerprint.  The containing
+        // line and fully qualified name are not serialized, and are therefore not included.
+        let default_source_info = SourceInfo::default();
+        let source_info = self.source_info(handle).unwrap_or(&default_source_info);
+        hasher.write_span(&source_info.span);
+        match source_info.syntax_type.into_option() {
+            Some(syntax_type) => {
+                hasher.write_bool(true);
+                hasher.write_str(&self[syntax_type]);
+            }
+            None => hasher.write_bool(false),
+        }
+        hasher.write_span(&source_info.definiens_span);
+
+        self.hash_debug_info(hasher, self.node_debug_info(handle));
+    }
+
+    fn hash_node_id(&self, hasher: &mut FingerprintHasher, id: NodeID) {
+        match id.file() {
+            Some(file) => {
+                hasher.write_bool(true);
+                hasher.write_str(self[file].name());
+            }
+            None => hasher.write_bool(false),
+        }
+        hasher.write_u32(id.local_id());
+    }
+
+    fn hash_debug_info(&self, hasher: &mut FingerprintHasher, debug_info: Option<&DebugInfo>) {
+        let entries = debug_info
+            .map(|info| info.entries.as_slice())
+            .unwrap_or(&[]);
+        hasher.write_usize(entries.len());
+        for entry in entries {
+            hasher.write_str(&self[entry.key]);
+            hasher.write_str(&self[entry.value]);
+        }
+    }
+}
+
+/// Computes fingerprints using the 64-bit FNV-1a hash function.  We do not use the hashers from
+/// the standard library, because their output is not guaranteed to be the same across releases
+/// and platforms, which matters for fingerprints that are persisted.
+struct FingerprintHasher(u64);
+
+impl FingerprintHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x00000100000001b3;
+
+    fn new() -> FingerprintHasher {
+        FingerprintHasher(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write(&(value as u64).to_le_bytes());
+    }
+
+    fn write_str(&mut self, value: &str) {
+        self.write_usize(value.len());
+        self.write(value.as_bytes());
+    }
+
+    fn write_position(&mut self, position: &lsp_positions::Position) {
+        self.write_usize(position.line);
+        self.write_usize(position.column.utf8_offset);
+        self.write_usize(position.column.utf16_offset);
+        self.write_usize(position.column.grapheme_offset);
+        self.write_usize(position.containing_line.start);
+        self.write_usize(position.containing_line.end);
+        self.write_usize(position.trimmed_line.start);
+        self.write_usize(position.trimmed_line.end);
+    }
+
+    fn write_span(&mut self, span: &lsp_positions::Span) {
+        self.write_position(&span.start);
+        self.write_position(&span.end);
+    }
+
+    fn finish(self) -> Fingerprint {
+        Fingerprint(self.0)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Iteration order
+
+/// The order in which a [`StackGraph`][] or a [`Database`][crate::stitching::Database] returns its
+/// contents.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IterationOrder {
+    /// Contents are returned in the order in which they were added.  This is the cheapest order,
+    /// but it depends on the order in which files were loaded or indexed, which can differ between
+    /// runs, for example when files are indexed in parallel.
+    Insertion,
+    /// Contents are returned sorted by their content: files by name, symbols by their text, nodes
+    /// by the names of their files and their local IDs, edges by the IDs of their sink nodes, and
+    /// partial paths by their nodes, stacks, and edges.  The order only depends on the contents,
+    /// which makes outputs reproducible across runs and platforms, at the cost of sorting.
+    Sorted,
+}
+
+impl Default for IterationOrder {
+    fn default() -> Self {
+        Self::Insertion
+    }
+}
+
+impl StackGraph {
+    /// Sets the order in which this graph returns its files, symbols, nodes, and edges.  The
+    /// default is [insertion order][IterationOrder::Insertion].
+    pub fn set_iteration_order(&mut self, order: IterationOrder) {
+        self.iteration_order = order;
+    }
+
+    /// Returns the order in which this graph returns its files, symbols, nodes, and edges.
+    pub fn iteration_order(&self) -> IterationOrder {
+        self.iteration_order
+    }
+
+    /// Compares two node IDs by the names of their files and their local IDs.  Unlike comparing
+    /// the IDs directly, the result does not depend on the order in which files were added to the
+    /// graph.  The IDs of the singleton _root_ and _jump to scope_ nodes come first.
+    pub fn cmp_node_ids(&self, a: NodeID, b: NodeID) -> std::cmp::Ordering {
+        let key = |id: NodeID| (id.file().map(|file| self[file].name()), id.local_id());
+        key(a).cmp(&key(b))
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Graph diffs
+
+/// A definition or reference in a file's subgraph.  Definitions and references are identified by
+/// their symbol and span, so that changes to unrelated parts of the file do not show up as
+/// changes to them.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DiffNode {
+    /// The local ID of the node, in the graph that contains it.
+    pub local_id: u32,
+    pub symbol: String,
+    pub span: Span,
+}
+
+/// An edge in a file's subgraph.  Edges are identified by the displayed IDs of their source and
+/// sink nodes, such as `test.py(3)` or `[root]`, and their precedence.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DiffEdge {
+    pub source: String,
+    pub sink: String,
+    pub precedence: Precedence,
+}
+
+/// The semantic differences between two versions of a file's subgraph, as returned by [`diff`][].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GraphDiff {
+    pub added_definitions: Vec<DiffNode>,
+    pub removed_definitions: Vec<DiffNode>,
+    pub added_references: Vec<DiffNode>,
+    pub removed_references: Vec<DiffNode>,
+    pub added_edges: Vec<DiffEdge>,
+    pub removed_edges: Vec<DiffEdge>,
+}
+
+impl GraphDiff {
+    /// Returns whether the two versions of the subgraph are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added_definitions.is_empty()
+            && self.removed_definitions.is_empty()
+            && self.added_references.is_empty()
+            && self.removed_references.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+/// Returns the definitions, references, and edges that were added to or removed from the subgraph
+/// of a file between an old and a new stack graph.  The subgraph consists of the same nodes and
+/// edges as for [fingerprints][StackGraph::fingerprint_for_file].  A file that does not exist in
+/// one of the graphs is treated as an empty subgraph.
+pub fn diff(old: &StackGraph, new: &StackGraph, file: &str) -> GraphDiff {
+    let old = DiffSubgraph::new(old, file);
+    let new = DiffSubgraph::new(new, file);
+    let node_key = |node: &DiffNode| (node.symbol.clone(), node.span.clone());
+    let edge_key = |edge: &DiffEdge| edge.clone();
+    GraphDiff {
+        added_definitions: multiset_difference(&new.definitions, &old.definitions, node_key),
+        removed_definitions: multiset_difference(&old.definitions, &new.definitions, node_key),
+        added_references: multiset_difference(&new.references, &old.references, node_key),
+        removed_references: multiset_difference(&old.references, &new.references, node_key),
+        added_edges: multiset_difference(&new.edges, &old.edges, edge_key),
+        removed_edges: multiset_difference(&old.edges, &new.edges, edge_key),
+    }
+}
+
+/// The definitions, references, and edges of a file's subgraph, ordered by local ID.
+#[derive(Default)]
+struct DiffSubgraph {
+    definitions: Vec<DiffNode>,
+    references: Vec<DiffNode>,
+    edges: Vec<DiffEdge>,
+}
+
+impl DiffSubgraph {
+    fn new(graph: &StackGraph, file: &str) -> Self {
+        let mut subgraph = Self::default();
+        let file = match graph.get_file(file) {
+            Some(file) => file,
+            None => return subgraph,
+        };
+
+        let mut nodes = graph.nodes_for_file(file).collect::<Vec<_>>();
+        nodes.sort_by_key(|node| graph[*node].id().local_id());
+        for node in &nodes {
+            let symbol = match graph[*node].symbol() {
+                Some(symbol) => graph[symbol].to_string(),
+                None => continue,
+            };
+            let diff_node = DiffNode {
+                local_id: graph[*node].id().local_id(),
+                symbol,
+                span: graph
+                    .source_info(*node)
+                    .map(|source_info| source_info.span.clone())
+                    .unwrap_or_default(),
+            };
+            if graph[*node].is_definition() {
+                subgraph.definitions.push(diff_node);
+            } else if graph[*node].is_reference() {
+                subgraph.references.push(diff_node);
+            }
+        }
+
+        subgraph.edges = nodes
+            .iter()
+            .copied()
+            .chain(std::iter::once(StackGraph::root_node()))
+            .flat_map(|source| graph.outgoing_edges(source))
+            .filter(|edge| graph[edge.sink].is_in_file(file))
+            .filter(|edge| graph[edge.source].file().is_some() || graph[edge.sink].file().is_some())
+            .map(|edge| DiffEdge {
+                source: graph[edge.source].id().display(graph).to_string(),
+                sink: graph[edge.sink].id().display(graph).to_string(),
+                precedence: edge.precedence,
+            })
+            .collect();
+        subgraph
+    }
+}
+
+/// Returns the items that are not matched by an item with the same key in `others`, counting
+/// duplicates.
+fn multiset_difference<T, K, F>(items: &[T], others: &[T], key: F) -> Vec<T>
+where
+    T: Clone,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let mut counts = HashMap::new();
+    for other in others {
+        *counts.entry(key(other)).or_insert(0usize) += 1;
+    }
+    items
+        .iter()
+        .filter(|item| match counts.get_mut(&key(item)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
 //-------------------------------------------------------------------------------------------------
 // Stack graphs
 
@@ -1680,7 +3094,7 @@ impl std::ops::AddAssign for Degree {
 /// # Example
 ///
 /// ```no_run
-/// use stack_graphs::graph::{StackGraph, NodeID};
+/// use stack_graphs::graph::{NodeID, Precedence, StackGraph};
 ///
 /// // Create a new empty stack graph
 /// let mut graph = StackGraph::new();
@@ -1705,7 +3119,7 @@ impl std::ops::AddAssign for Degree {
 /// ).unwrap();
 ///
 /// // Connect them with an edge
-/// graph.add_edge(scope, definition, 0);
+/// graph.add_edge(scope, definition, Precedence::DEFAULT);
 /// ```
 ///
 /// # Structure
@@ -1722,12 +3136,16 @@ impl std::ops::AddAssign for Degree {
 /// **File Management:**
 /// - `files`: All files in the graph
 /// - `file_handles`: Fast lookup for existing files
+/// - `file_languages`: Optional language of each file
+/// - `file_parse_errors`: Regions of each file that contained parse errors
 ///
 /// **Node Management:**
 /// - `nodes`: All nodes in the graph (including root and jump-to)
 /// - `node_id_handles`: Mapping from NodeID to Handle<Node>
 /// - `source_info`: Optional source location info for nodes
 /// - `node_debug_info`: Optional debug metadata for nodes
+/// - `speculative_nodes`: Nodes whose edges are guesses
+/// - `synthetic_journal`: Changes to undo when the current synthetic scope ends
 ///
 /// **Edge Management:**
 /// - `outgoing_edges`: For each node, its outgoing edges
@@ -1755,6 +3173,25 @@ pub struct StackGraph {
     /// Fast lookup table: file path → file handle.
     file_handles: FxHashMap<&'static str, Handle<File>>,
 
+    /// Optional language of each file.
+    file_languages: SupplementalArena<File, ControlledOption<Handle<InternedString>>>,
+
+    /// Regions of each file that contained parse errors.
+    file_parse_errors: SupplementalArena<File, Vec<Span>>,
+
+    /// Optional root partition of each file.
+    file_partitions: SupplementalArena<File, ControlledOption<Handle<InternedString>>>,
+
+    /// Root partitions of the files in each file root.
+    root_partitions: HashMap<String, Handle<InternedString>>,
+
+    /// The pairs of root partitions whose files can see each other, from the seeing to the seen
+    /// partition.
+    partition_visibility: HashSet<(Handle<InternedString>, Handle<InternedString>)>,
+
+    /// Whether any root partitions were declared.
+    has_partitions: bool,
+
     /// Arena of all nodes (scope, push/pop symbol, root, etc.).
     pub(crate) nodes: Arena<Node>,
 
@@ -1775,6 +3212,18 @@ pub struct StackGraph {
 
     /// Optional debug metadata for edges (indexed by source node and sink node).
     pub(crate) edge_debug_info: SupplementalArena<Node, SmallVec<[(Handle<Node>, DebugInfo); 4]>>,
+
+    /// For each node, the nodes that are declared equivalent to it.
+    node_aliases: SupplementalArena<Node, SmallVec<[Handle<Node>; 2]>>,
+
+    /// The nodes whose edges are guesses.
+    speculative_nodes: HandleSet<Node>,
+
+    /// The changes to undo when the current synthetic scope ends, if there is one.
+    synthetic_journal: Option<SyntheticJournal>,
+
+    /// The order in which files, symbols, nodes, and edges are returned.
+    iteration_order: IterationOrder,
 }
 
 impl StackGraph {
@@ -1783,6 +3232,61 @@ impl StackGraph {
         StackGraph::default()
     }
 
+    /// Creates a new, initially empty stack graph with room for the given number of files, nodes,
+    /// and symbols.  Pre-sizing the arenas, the lookup tables of the string interner, and its
+    /// first buffer avoids the reallocation and rehashing spikes of a growing graph, when the
+    /// size of the graph can be estimated up front, for example from a previous indexing run.
+    /// Use [`interner_stats`][Self::interner_stats] to inspect the resulting sizes.
+    pub fn with_capacity(files: usize, nodes: usize, symbols: usize) -> StackGraph {
+        let mut graph = StackGraph::default();
+        graph.interned_strings = InternedStringArena::with_capacity(
+            symbols * EXPECTED_SYMBOL_LENGTH + files * EXPECTED_FILE_NAME_LENGTH,
+        );
+        graph.symbols.reserve(symbols);
+        graph.symbol_handles.reserve(symbols);
+        graph.files.reserve(files);
+        graph.file_handles.reserve(files);
+        graph.nodes.reserve(nodes);
+        graph
+    }
+
+    /// Returns statistics about the string interner of this graph.
+    pub fn interner_stats(&self) -> InternerStats {
+        let load_factor = |len: usize, capacity: usize| {
+            if capacity == 0 {
+                0.0
+            } else {
+                len as f64 / capacity as f64
+            }
+        };
+        InternerStats {
+            symbols: self.symbol_handles.len(),
+            strings: self.string_handles.len(),
+            files: self.file_handles.len(),
+            bytes: self.interned_strings.len(),
+            allocated_bytes: self.interned_strings.capacity(),
+            buffers: self.interned_strings.buffer_count(),
+            symbol_load_factor: load_factor(
+                self.symbol_handles.len(),
+                self.symbol_handles.capacity(),
+            ),
+            string_load_factor: load_factor(
+                self.string_handles.len(),
+                self.string_handles.capacity(),
+            ),
+            file_load_factor: load_factor(self.file_handles.len(), self.file_handles.capacity()),
+        }
+    }
+
+    /// Assigns a memory budget to the arenas of this stack graph.  See [`MemoryBudget`][] for
+    /// which allocations are counted, and how the budget is enforced.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.symbols.set_budget(budget.clone());
+        self.strings.set_budget(budget.clone());
+        self.files.set_budget(budget.clone());
+        self.nodes.set_budget(budget);
+    }
+
     /// Copies the given stack graph into this stack graph. Panics if any of the files
     /// in the other stack graph are already defined in the current one.
     pub fn add_from_graph(
@@ -1791,10 +3295,18 @@ impl StackGraph {
     ) -> Result<Vec<Handle<File>>, Handle<File>> {
         let mut files = HashMap::new();
         for other_file in other.iter_files() {
-            let file = self.add_file(other[other_file].name())?;
+            let file = self.add_file_of(other, other_file)?;
+            if let Some(language) = other.file_language(other_file) {
+                self.set_file_language(file, language);
+            }
+            for span in other.parse_errors(other_file) {
+                self.add_parse_error(file, span.clone());
+            }
+            self.copy_file_partition(file, other, other_file);
             files.insert(other_file, file);
         }
         let files = files;
+        self.add_partitions_from_graph(other);
         let node_id = |other_node_id: NodeID| {
             if other_node_id.is_root() {
                 NodeID::root()
@@ -1810,7 +3322,7 @@ impl StackGraph {
         let mut nodes = HashMap::new();
         nodes.insert(Self::root_node(), Self::root_node());
         nodes.insert(Self::jump_to_node(), Self::jump_to_node());
-        for other_file in files.keys().cloned() {
+        for other_file in other.iter_files() {
             let file = files[&other_file];
             for other_node in other.nodes_for_file(other_file) {
                 let value: Node = match other[other_node] {
@@ -1926,6 +3438,9 @@ impl StackGraph {
                             .collect::<Vec<_>>(),
                     };
                 }
+                if other.is_speculative(other_node) {
+                    self.mark_speculative(node);
+                }
             }
             for other_node in nodes.keys().cloned() {
                 for other_edge in other.outgoing_edges(other_node) {
@@ -1934,10 +3449,134 @@ impl StackGraph {
                         nodes[&other_edge.sink],
                         other_edge.precedence,
                     );
+                    if let Some(condition) =
+                        other.edge_condition(other_edge.source, other_edge.sink)
+                    {
+                        let condition = self.add_string(&other[condition]);
+                        self.set_edge_condition(
+                            nodes[&other_edge.source],
+                            nodes[&other_edge.sink],
+                            condition,
+                        );
+                    }
+                    if let Some(confidence) =
+                        other.edge_confidence(other_edge.source, other_edge.sink)
+                    {
+                        self.set_edge_confidence(
+                            nodes[&other_edge.source],
+                            nodes[&other_edge.sink],
+                            confidence,
+                        );
+                    }
+                }
+            }
+        }
+        for (other_node, node) in &nodes {
+            for other_alias in other.node_aliases(*other_node) {
+                if let Some(alias) = nodes.get(other_alias) {
+                    self.add_node_alias(*node, *alias);
+                }
+            }
+        }
+        Ok(other
+            .iter_files()
+            .map(|other_file| files[&other_file])
+            .collect())
+    }
+
+    /// Rebuilds the arenas of this stack graph densely, keeping only the files for which `retain`
+    /// returns true, and returns a table that maps the handles of the kept files and nodes to
+    /// their new handles.  Symbols and strings that are no longer used are dropped as well, so
+    /// that long-lived graphs from which files are removed repeatedly do not keep growing.  The
+    /// graph is rebuilt from its [serialized form][crate::serde::StackGraph], so all data that
+    /// is persisted for a file is kept.  After this call, all existing handles into this graph
+    /// are invalid, and must be translated using the returned table.  To also rebuild a
+    /// [`Database`][crate::stitching::Database] of partial paths for the compacted graph, use
+    /// [`Database::compact_with_graph`][crate::stitching::Database::compact_with_graph].
+    pub fn compact<F>(&mut self, retain: F) -> HandleRemap
+    where
+        F: Fn(&StackGraph, &Handle<File>) -> bool,
+    {
+        let serialized = crate::serde::StackGraph::from_graph_filter(self, &retain);
+        let mut graph = StackGraph::new();
+        graph.set_iteration_order(self.iteration_order);
+        graph.set_memory_budget(self.nodes.budget().cloned());
+        serialized
+            .load_into(&mut graph)
+            .expect("serialized graph to load into empty graph");
+        graph.add_partitions_from_graph(self);
+
+        let mut remap = HandleRemap::default();
+        remap.nodes.insert(Self::root_node(), Self::root_node());
+        remap
+            .nodes
+            .insert(Self::jump_to_node(), Self::jump_to_node());
+        for file in self.iter_files() {
+            let new_file = match graph.get_file(self[file].name()) {
+                Some(new_file) => new_file,
+                None => continue,
+            };
+            remap.files.insert(file, new_file);
+            graph.copy_file_partition(new_file, self, file);
+            for node in self.nodes_for_file(file) {
+                let id = NodeID::new_in_file(new_file, self[node].id().local_id());
+                if let Some(new_node) = graph.node_for_id(id) {
+                    remap.nodes.insert(node, new_node);
                 }
             }
         }
-        Ok(files.into_values().collect())
+        *self = graph;
+        remap
+    }
+}
+
+/// Statistics about the string interner of a [`StackGraph`][], as returned by
+/// [`StackGraph::interner_stats`][].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InternerStats {
+    /// The number of interned symbols.
+    pub symbols: usize,
+    /// The number of interned strings that are not symbols or file names.
+    pub strings: usize,
+    /// The number of interned file names.
+    pub files: usize,
+    /// The number of bytes of interned string content.
+    pub bytes: usize,
+    /// The number of bytes allocated for interned string content.
+    pub allocated_bytes: usize,
+    /// The number of buffers allocated for interned string content.  Every buffer after the
+    /// first is allocated when the previous one is full.
+    pub buffers: usize,
+    /// The ratio of the number of symbols to the capacity of the symbol lookup table.
+    pub symbol_load_factor: f64,
+    /// The ratio of the number of strings to the capacity of the string lookup table.
+    pub string_load_factor: f64,
+    /// The ratio of the number of files to the capacity of the file lookup table.
+    pub file_load_factor: f64,
+}
+
+/// Maps the handles of files and nodes of a stack graph to their handles after the graph was
+/// [compacted][StackGraph::compact].  Files and nodes that were removed have no new handle.
+#[derive(Clone, Debug, Default)]
+pub struct HandleRemap {
+    files: HashMap<Handle<File>, Handle<File>>,
+    nodes: HashMap<Handle<Node>, Handle<Node>>,
+}
+
+impl HandleRemap {
+    /// Returns the new handle of the given file, or `None` if it was removed.
+    pub fn file(&self, file: Handle<File>) -> Option<Handle<File>> {
+        self.files.get(&file).copied()
+    }
+
+    /// Returns the new handle of the given node, or `None` if it was removed.
+    pub fn node(&self, node: Handle<Node>) -> Option<Handle<Node>> {
+        self.nodes.get(&node).copied()
+    }
+
+    /// Returns the number of nodes that were kept, including the singleton nodes.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
     }
 }
 
@@ -1955,6 +3594,12 @@ impl Default for StackGraph {
             string_handles: FxHashMap::default(),
             files: Arena::new(),
             file_handles: FxHashMap::default(),
+            file_languages: SupplementalArena::new(),
+            file_parse_errors: SupplementalArena::new(),
+            file_partitions: SupplementalArena::new(),
+            root_partitions: HashMap::new(),
+            partition_visibility: HashSet::new(),
+            has_partitions: false,
             nodes,
             source_info: SupplementalArena::new(),
             node_id_handles: NodeIDHandles::new(),
@@ -1962,6 +3607,65 @@ impl Default for StackGraph {
             incoming_edges: SupplementalArena::new(),
             node_debug_info: SupplementalArena::new(),
             edge_debug_info: SupplementalArena::new(),
+            node_aliases: SupplementalArena::new(),
+            speculative_nodes: HandleSet::new(),
+            synthetic_journal: None,
+            iteration_order: IterationOrder::default(),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Frozen stack graphs
+
+/// An immutable stack graph that can be shared between threads.
+///
+/// A frozen graph is created with [`StackGraph::freeze`][].  It only gives out shared references
+/// to the underlying [`StackGraph`][], so the type system guarantees that it is not modified while
+/// it is shared.  Cloning a frozen graph is cheap, and all clones refer to the same graph, which
+/// allows path stitching to run on many threads at once, each with its own
+/// [`PartialPaths`][crate::partial::PartialPaths] and
+/// [`Database`][crate::stitching::Database].  Graphs for new files can be constructed at the same
+/// time in a separate working graph, and added with [`StackGraph::add_from_graph`][] once the
+/// frozen graph has been [thawed][FrozenStackGraph::thaw].
+#[derive(Clone)]
+pub struct FrozenStackGraph {
+    graph: Arc<StackGraph>,
+}
+
+impl StackGraph {
+    /// Freezes this stack graph, so that it can be shared between threads.
+    pub fn freeze(self) -> FrozenStackGraph {
+        FrozenStackGraph {
+            graph: Arc::new(self),
         }
     }
 }
+
+impl FrozenStackGraph {
+    /// Returns the stack graph, so that it can be modified again.  This fails, returning the
+    /// frozen graph, if there are other clones of it.
+    pub fn thaw(self) -> Result<StackGraph, FrozenStackGraph> {
+        Arc::try_unwrap(self.graph).map_err(|graph| FrozenStackGraph { graph })
+    }
+
+    /// Returns whether this is the only clone of the frozen graph, in which case
+    /// [`thaw`][Self::thaw] succeeds.
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.graph) == 1
+    }
+}
+
+impl Deref for FrozenStackGraph {
+    type Target = StackGraph;
+
+    fn deref(&self) -> &StackGraph {
+        &self.graph
+    }
+}
+
+impl AsRef<StackGraph> for FrozenStackGraph {
+    fn as_ref(&self) -> &StackGraph {
+        &self.graph
+    }
+}
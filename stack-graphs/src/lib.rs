@@ -33,7 +33,7 @@
 //! ## Quick Example
 //!
 //! ```no_run
-//! use stack_graphs::graph::{StackGraph, NodeID};
+//! use stack_graphs::graph::{NodeID, Precedence, StackGraph};
 //! use stack_graphs::partial::PartialPaths;
 //! use stack_graphs::stitching::{Database, ForwardPartialPathStitcher};
 //! use stack_graphs::NoCancellation;
@@ -52,7 +52,7 @@
 //! ).unwrap();
 //!
 //! // Connect them
-//! graph.add_edge(scope, definition, 0);
+//! graph.add_edge(scope, definition, Precedence::DEFAULT);
 //!
 //! // Build partial paths for this file
 //! let mut partials = PartialPaths::new();
@@ -106,6 +106,8 @@ use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
+#[cfg(feature = "storage")]
+pub mod analysis;
 pub mod arena;
 pub mod assert;
 pub mod c;
@@ -113,8 +115,16 @@ pub mod cycles;
 #[macro_use]
 mod debugging;
 pub mod graph;
+#[cfg(feature = "lsif")]
+pub mod lsif;
+pub mod neo4j;
+pub mod overlay;
+pub mod parallel;
 pub mod partial;
 pub mod paths;
+pub mod query;
+#[cfg(feature = "storage")]
+pub mod resolve;
 pub mod serde;
 pub mod stats;
 pub mod stitching;
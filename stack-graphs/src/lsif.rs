@@ -0,0 +1,472 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Imports [LSIF][] dumps into stack graphs, so that stack graph tooling can be used for
+//! languages that only have an LSIF indexer.
+//!
+//! LSIF dumps contain resolved references rather than name binding rules, so the imported stack
+//! graph is an approximation: every definition and reference in the dump becomes a chain of pop
+//! or push symbol nodes through the root node, which resolve to each other by construction.
+//!
+//! - Ranges with a non-local [moniker][] use the chain `scheme`, `identifier`.  Definitions
+//!   become `[root] -> pop scheme -> pop identifier`, and references become
+//!   `push identifier -> push scheme -> [root]`.  References with an import moniker therefore
+//!   resolve to definitions with the matching export moniker, even if those were imported from a
+//!   different dump.
+//! - Other ranges use a single symbol that identifies the location of the first definition in
+//!   their definition result, such as `/src/main.ts:3:10`.
+//! - Ranges without a definition result or moniker cannot be resolved, and are skipped.
+//!
+//! Ranges are definitions if they are items of their definition result, or, if they have no
+//! definition result, if they are tagged as definitions.  All other ranges are references.
+//!
+//! Documents become files named after their URIs, where `file://` URIs are converted to paths.
+//! LSIF dumps do not contain source text, so the columns of all positions are set to the LSIF
+//! character offsets in all units, which is only exact for ASCII source.
+//!
+//! [LSIF]: https://microsoft.github.io/language-server-protocol/specifications/lsif/0.6.0/specification/
+//! [moniker]: https://microsoft.github.io/language-server-protocol/specifications/lsif/0.6.0/specification/#exportsImports
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Read;
+
+use controlled_option::ControlledOption;
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::Precedence;
+use crate::graph::StackGraph;
+
+#[derive(Debug, Error)]
+pub enum LsifError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("invalid LSIF element `{0}`")]
+    InvalidElement(String),
+    #[error("duplicate file `{0}`")]
+    FileAlreadyPresent(String),
+}
+
+/// A summary of the contents of an imported LSIF dump.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LsifImport {
+    /// The files created for the documents in the dump, in the order of the dump.
+    pub files: Vec<Handle<File>>,
+    /// The number of imported definitions.
+    pub definitions: usize,
+    /// The number of imported references.
+    pub references: usize,
+    /// The number of ranges that were skipped because they could not be resolved.
+    pub skipped_ranges: usize,
+}
+
+impl StackGraph {
+    /// Adds the documents of an LSIF dump to this stack graph.  The dump can be in the JSON lines
+    /// format, or a single JSON array of elements.  It is an error if any of the documents are
+    /// already present in the graph.
+    pub fn add_from_lsif<R: Read>(&mut self, reader: R) -> Result<LsifImport, LsifError> {
+        let mut dump = LsifDump::default();
+        for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+            match value? {
+                Value::Array(elements) => {
+                    for element in &elements {
+                        dump.add_element(element)?;
+                    }
+                }
+                element => dump.add_element(&element)?,
+            }
+        }
+        dump.load_into(self)
+    }
+}
+
+type Id = String;
+
+struct LsifRange {
+    span: Span,
+    definiens_span: Span,
+    syntax_type: Option<&'static str>,
+    has_definition_tag: bool,
+}
+
+struct LsifMoniker {
+    scheme: String,
+    identifier: String,
+    is_local: bool,
+}
+
+#[derive(Default)]
+struct LsifDump {
+    documents: Vec<(Id, String)>,
+    ranges: HashMap<Id, LsifRange>,
+    monikers: HashMap<Id, LsifMoniker>,
+    definition_results: HashSet<Id>,
+    /// The document containing each range.
+    containing_documents: HashMap<Id, Id>,
+    /// The ranges contained in each document, in the order of the dump.
+    contained_ranges: HashMap<Id, Vec<Id>>,
+    next: HashMap<Id, Id>,
+    definition_edges: HashMap<Id, Id>,
+    moniker_edges: HashMap<Id, Id>,
+    /// The ranges of each definition result.
+    definition_items: HashMap<Id, Vec<Id>>,
+}
+
+impl LsifDump {
+    fn add_element(&mut self, element: &Value) -> Result<(), LsifError> {
+        let invalid = || LsifError::InvalidElement(element.to_string());
+        let id = element.get("id").and_then(id).ok_or_else(invalid)?;
+        let label = element
+            .get("label")
+            .and_then(Value::as_str)
+            .ok_or_else(invalid)?;
+        match element.get("type").and_then(Value::as_str) {
+            Some("vertex") => self.add_vertex(id, label, element).ok_or_else(invalid),
+            Some("edge") => self.add_edge(label, element).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn add_vertex(&mut self, id: Id, label: &str, vertex: &Value) -> Option<()> {
+        match label {
+            "document" => {
+                let uri = vertex.get("uri")?.as_str()?;
+                self.documents.push((id, file_name_for_uri(uri)));
+            }
+            "range" => {
+                let span = span_for_range(vertex)?;
+                let tag = vertex.get("tag");
+                let definiens_span = match tag.and_then(|t| t.get("fullRange")) {
+                    Some(full_range) => span_for_range(full_range)?,
+                    None => Span::default(),
+                };
+                let syntax_type = tag
+                    .and_then(|t| t.get("kind"))
+                    .and_then(Value::as_u64)
+                    .and_then(syntax_type_for_symbol_kind);
+                let has_definition_tag =
+                    tag.and_then(|t| t.get("type")).and_then(Value::as_str) == Some("definition");
+                self.ranges.insert(
+                    id,
+                    LsifRange {
+                        span,
+                        definiens_span,
+                        syntax_type,
+                        has_definition_tag,
+                    },
+                );
+            }
+            "moniker" => {
+                let scheme = vertex.get("scheme")?.as_str()?.to_string();
+                let identifier = vertex.get("identifier")?.as_str()?.to_string();
+                let is_local = vertex.get("kind").and_then(Value::as_str) == Some("local");
+                self.monikers.insert(
+                    id,
+                    LsifMoniker {
+                        scheme,
+                        identifier,
+                        is_local,
+                    },
+                );
+            }
+            "definitionResult" => {
+                self.definition_results.insert(id);
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn add_edge(&mut self, label: &str, edge: &Value) -> Option<()> {
+        let out_v = edge.get("outV").and_then(id)?;
+        let in_vs = match (edge.get("inV"), edge.get("inVs")) {
+            (Some(in_v), _) => vec![id(in_v)?],
+            (None, Some(Value::Array(in_vs))) => in_vs.iter().map(id).collect::<Option<_>>()?,
+            _ => return None,
+        };
+        match label {
+            "contains" => {
+                for in_v in in_vs {
+                    self.set_document(in_v, &out_v);
+                }
+            }
+            "next" => {
+                self.next.insert(out_v, in_vs.into_iter().next()?);
+            }
+            "textDocument/definition" => {
+                self.definition_edges
+                    .insert(out_v, in_vs.into_iter().next()?);
+            }
+            "moniker" => {
+                self.moniker_edges.insert(out_v, in_vs.into_iter().next()?);
+            }
+            "item" => {
+                // Item edges name the document of their ranges, in case there is no contains edge.
+                let document = edge
+                    .get("shard")
+                    .or_else(|| edge.get("document"))
+                    .and_then(id);
+                if let Some(document) = &document {
+                    for in_v in &in_vs {
+                        self.set_document(in_v.clone(), document);
+                    }
+                }
+                self.definition_items
+                    .entry(out_v)
+                    .or_default()
+                    .extend(in_vs);
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn set_document(&mut self, range: Id, document: &Id) {
+        if !self.containing_documents.contains_key(&range) {
+            self.containing_documents
+                .insert(range.clone(), document.clone());
+            self.contained_ranges
+                .entry(document.clone())
+                .or_default()
+                .push(range);
+        }
+    }
+
+    /// Returns the definition result and non-local moniker of a range, by following the chain
+    /// of result sets that starts at the range.
+    fn targets(&self, range: &Id) -> (Option<&Id>, Option<&LsifMoniker>) {
+        let mut definition_result = None;
+        let mut moniker = None;
+        let mut current = range;
+        // Result set chains are acyclic in well-formed dumps, but we bound the walk anyway.
+        for _ in 0..=self.next.len() {
+            if definition_result.is_none() {
+                definition_result = self
+                    .definition_edges
+                    .get(current)
+                    .filter(|d| self.definition_results.contains(*d));
+            }
+            if moniker.is_none() {
+                moniker = self
+                    .moniker_edges
+                    .get(current)
+                    .and_then(|m| self.monikers.get(m))
+                    .filter(|m| !m.is_local);
+            }
+            match self.next.get(current) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        (definition_result, moniker)
+    }
+
+    /// Returns the symbol that identifies the location of the first definition of a definition
+    /// result.
+    fn location_symbol(&self, definition_result: &Id) -> Option<String> {
+        self.definition_items
+            .get(definition_result)?
+            .iter()
+            .filter_map(|range| {
+                let document = self.containing_documents.get(range)?;
+                let (_, file_name) = self.documents.iter().find(|(id, _)| id == document)?;
+                let start = &self.ranges.get(range)?.span.start;
+                Some((file_name, start.line, start.column.utf16_offset))
+            })
+            .min()
+            .map(|(file_name, line, column)| format!("{}:{}:{}", file_name, line + 1, column + 1))
+    }
+
+    fn load_into(self, graph: &mut StackGraph) -> Result<LsifImport, LsifError> {
+        let mut import = LsifImport::default();
+        for (document, file_name) in &self.documents {
+            let file = graph
+                .add_file(file_name)
+                .map_err(|_| LsifError::FileAlreadyPresent(file_name.clone()))?;
+            import.files.push(file);
+            let ranges = match self.contained_ranges.get(document) {
+                Some(ranges) => ranges,
+                None => continue,
+            };
+            for range_id in ranges {
+                let range = match self.ranges.get(range_id) {
+                    Some(range) => range,
+                    None => continue,
+                };
+                let (definition_result, moniker) = self.targets(range_id);
+                let symbols = match (moniker, definition_result) {
+                    (Some(moniker), _) => vec![moniker.scheme.clone(), moniker.identifier.clone()],
+                    (None, Some(definition_result)) => {
+                        match self.location_symbol(definition_result) {
+                            Some(symbol) => vec![symbol],
+                            None => {
+                                import.skipped_ranges += 1;
+                                continue;
+                            }
+                        }
+                    }
+                    (None, None) => {
+                        import.skipped_ranges += 1;
+                        continue;
+                    }
+                };
+                let is_definition = match definition_result {
+                    Some(d) => self
+                        .definition_items
+                        .get(d)
+                        .map_or(false, |items| items.contains(range_id)),
+                    None => range.has_definition_tag,
+                };
+                let node = if is_definition {
+                    import.definitions += 1;
+                    add_definition_chain(graph, file, &symbols)
+                } else {
+                    import.references += 1;
+                    add_reference_chain(graph, file, &symbols)
+                };
+                graph.source_info_mut(node).span = range.span.clone();
+                if is_definition {
+                    let syntax_type = range.syntax_type.map(|t| graph.add_string(t));
+                    let fully_qualified_name = moniker.map(|m| graph.add_string(&m.identifier));
+                    let source_info = graph.source_info_mut(node);
+                    source_info.definiens_span = range.definiens_span.clone();
+                    source_info.syntax_type = ControlledOption::from_option(syntax_type);
+                    source_info.fully_qualified_name =
+                        ControlledOption::from_option(fully_qualified_name);
+                }
+            }
+        }
+        Ok(import)
+    }
+}
+
+/// Adds the chain `[root] -> pop symbols[0] -> ... -> pop symbols[n-1]`, and returns the last
+/// node, which is the definition.
+fn add_definition_chain(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    symbols: &[String],
+) -> Handle<Node> {
+    let mut previous = StackGraph::root_node();
+    for (i, symbol) in symbols.iter().enumerate() {
+        let id = graph.new_node_id(file);
+        let symbol = graph.add_symbol(symbol);
+        let node = graph
+            .add_pop_symbol_node(id, symbol, i + 1 == symbols.len())
+            .expect("new node ID");
+        graph.add_edge(previous, node, Precedence::DEFAULT);
+        previous = node;
+    }
+    previous
+}
+
+/// Adds the chain `push symbols[n-1] -> ... -> push symbols[0] -> [root]`, and returns the first
+/// node, which is the reference.
+fn add_reference_chain(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    symbols: &[String],
+) -> Handle<Node> {
+    let mut reference = None;
+    let mut previous = None;
+    for (i, symbol) in symbols.iter().enumerate().rev() {
+        let id = graph.new_node_id(file);
+        let symbol = graph.add_symbol(symbol);
+        let node = graph
+            .add_push_symbol_node(id, symbol, i + 1 == symbols.len())
+            .expect("new node ID");
+        match previous {
+            Some(previous) => graph.add_edge(previous, node, Precedence::DEFAULT),
+            None => reference = Some(node),
+        }
+        previous = Some(node);
+    }
+    graph.add_edge(
+        previous.unwrap(),
+        StackGraph::root_node(),
+        Precedence::DEFAULT,
+    );
+    reference.unwrap()
+}
+
+/// Returns the ID of an LSIF element, which can be a number or a string.
+fn id(value: &Value) -> Option<Id> {
+    match value {
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(string) => Some(string.clone()),
+        _ => None,
+    }
+}
+
+fn span_for_range(range: &Value) -> Option<Span> {
+    Some(Span {
+        start: position(range.get("start")?)?,
+        end: position(range.get("end")?)?,
+    })
+}
+
+fn position(position: &Value) -> Option<Position> {
+    let character = position.get("character")?.as_u64()? as usize;
+    Some(Position {
+        line: position.get("line")?.as_u64()? as usize,
+        column: Offset {
+            utf8_offset: character,
+            utf16_offset: character,
+            grapheme_offset: character,
+        },
+        ..Position::default()
+    })
+}
+
+/// Returns the syntax type for an LSP `SymbolKind`, using the names understood by
+/// [`DefinitionKind`][crate::graph::DefinitionKind].
+fn syntax_type_for_symbol_kind(kind: u64) -> Option<&'static str> {
+    match kind {
+        2 | 3 | 4 => Some("module"),
+        5 | 23 => Some("class"),
+        6 | 9 => Some("method"),
+        7 | 8 | 22 => Some("field"),
+        10 | 26 => Some("type"),
+        11 => Some("interface"),
+        12 => Some("function"),
+        13 | 14 => Some("variable"),
+        _ => None,
+    }
+}
+
+/// Returns the file name for a document URI.  `file://` URIs are converted to (percent-decoded)
+/// paths, and other URIs are used as they are.
+fn file_name_for_uri(uri: &str) -> String {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path,
+        None => return uri.to_string(),
+    };
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            decoded.push(u8::from_str_radix(&path[i + 1..i + 3], 16).unwrap());
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,339 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Exports stack graphs to [Neo4j][], so that name binding data can be combined with other code
+//! intelligence data in a graph database.
+//!
+//! Stack graphs can be exported as a script of [Cypher][] statements using
+//! [`StackGraph::to_cypher_string`][], or as CSV files for the Neo4j [bulk importer][] using
+//! [`StackGraph::to_neo4j_csv`][].  Both use the same data model:
+//!
+//! - Every stack graph node becomes a node with the `StackGraphNode` label, and a label for the
+//!   node type, such as `PushSymbol` or `Scope`.  Nodes are identified by their `id` property,
+//!   which is the displayed node ID, such as `test.py(3)` or `[root]`.  Other properties are
+//!   `file`, `local_id`, `symbol`, `scope`, `is_definition`, `is_reference`, `is_exported`,
+//!   `syntax_type`, and the source span as `start_line`, `start_column`, `end_line`, and
+//!   `end_column`.  Lines are 0-indexed, and columns are 0-indexed UTF-16 code unit offsets, as
+//!   in LSP.  Properties that do not apply to a node are omitted.
+//! - Every stack graph edge becomes an `EDGE` relationship with a `precedence` property.
+//! - Every resolution, if provided, becomes a `RESOLVES_TO` relationship from the reference to
+//!   the definition.
+//!
+//! [Neo4j]: https://neo4j.com/
+//! [Cypher]: https://neo4j.com/docs/cypher-manual/current/
+//! [bulk importer]: https://neo4j.com/docs/operations-manual/current/tools/neo4j-admin/neo4j-admin-import/
+
+use std::fmt::Write as _;
+
+use crate::arena::Handle;
+use crate::graph::Node;
+use crate::graph::Precedence;
+use crate::graph::StackGraph;
+use crate::serde::Filter;
+use crate::serde::ImplicationFilter;
+
+/// The label of all exported stack graph nodes.
+pub const NODE_LABEL: &str = "StackGraphNode";
+/// The type of relationships for stack graph edges.
+pub const EDGE_TYPE: &str = "EDGE";
+/// The type of relationships for resolutions.
+pub const RESOLUTION_TYPE: &str = "RESOLVES_TO";
+
+/// The contents of the CSV files for a Neo4j bulk import of a stack graph.  Write them to files
+/// and import them with `neo4j-admin database import full --nodes=NODES_FILE
+/// --relationships=RELATIONSHIPS_FILE`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Neo4jCsv {
+    pub nodes: String,
+    pub relationships: String,
+}
+
+impl StackGraph {
+    /// Returns a script of Cypher statements that creates the nodes and edges of this stack graph
+    /// that are included by the filter, and relationships for the given resolutions, which are
+    /// pairs of reference and definition nodes.
+    pub fn to_cypher_string(
+        &self,
+        resolutions: &[(Handle<Node>, Handle<Node>)],
+        filter: &dyn Filter,
+    ) -> String {
+        let filter = ImplicationFilter(filter);
+        let mut cypher = String::new();
+        writeln!(
+            cypher,
+            "CREATE INDEX stack_graph_node_id IF NOT EXISTS FOR (n:{}) ON (n.id);",
+            NODE_LABEL
+        )
+        .unwrap();
+        for node in self.iter_nodes().filter(|n| filter.include_node(self, n)) {
+            let record = NodeRecord::new(self, node);
+            write!(
+                cypher,
+                "CREATE (:{}:{} {{id: {}",
+                NODE_LABEL,
+                record.label,
+                cypher_string(&record.id)
+            )
+            .unwrap();
+            for (name, value) in record.properties() {
+                write!(cypher, ", {}: {}", name, value.to_cypher()).unwrap();
+            }
+            writeln!(cypher, "}});").unwrap();
+        }
+        for (source, sink, precedence) in self.exported_edges(&filter) {
+            writeln!(
+                cypher,
+                "MATCH (source:{} {{id: {}}}), (sink:{} {{id: {}}}) CREATE (source)-[:{} {{precedence: {}}}]->(sink);",
+                NODE_LABEL,
+                cypher_string(&self.node_id_string(source)),
+                NODE_LABEL,
+                cypher_string(&self.node_id_string(sink)),
+                EDGE_TYPE,
+                precedence,
+            )
+            .unwrap();
+        }
+        for (reference, definition) in self.exported_resolutions(resolutions, &filter) {
+            writeln!(
+                cypher,
+                "MATCH (reference:{} {{id: {}}}), (definition:{} {{id: {}}}) CREATE (reference)-[:{}]->(definition);",
+                NODE_LABEL,
+                cypher_string(&self.node_id_string(reference)),
+                NODE_LABEL,
+                cypher_string(&self.node_id_string(definition)),
+                RESOLUTION_TYPE,
+            )
+            .unwrap();
+        }
+        cypher
+    }
+
+    /// Returns the CSV files for a Neo4j bulk import of the nodes and edges of this stack graph
+    /// that are included by the filter, and relationships for the given resolutions, which are
+    /// pairs of reference and definition nodes.
+    pub fn to_neo4j_csv(
+        &self,
+        resolutions: &[(Handle<Node>, Handle<Node>)],
+        filter: &dyn Filter,
+    ) -> Neo4jCsv {
+        let filter = ImplicationFilter(filter);
+        let mut csv = Neo4jCsv::default();
+
+        write!(csv.nodes, "id:ID,:LABEL").unwrap();
+        for (name, property_type) in NODE_PROPERTIES {
+            write!(csv.nodes, ",{}:{}", name, property_type).unwrap();
+        }
+        writeln!(csv.nodes).unwrap();
+        for node in self.iter_nodes().filter(|n| filter.include_node(self, n)) {
+            let record = NodeRecord::new(self, node);
+            write!(
+                csv.nodes,
+                "{},{};{}",
+                csv_string(&record.id),
+                NODE_LABEL,
+                record.label
+            )
+            .unwrap();
+            let properties = record.properties();
+            for (name, _) in NODE_PROPERTIES {
+                csv.nodes.push(',');
+                if let Some((_, value)) = properties.iter().find(|(n, _)| *n == name) {
+                    csv.nodes.push_str(&value.to_csv());
+                }
+            }
+            writeln!(csv.nodes).unwrap();
+        }
+
+        writeln!(csv.relationships, ":START_ID,:END_ID,:TYPE,precedence:int").unwrap();
+        for (source, sink, precedence) in self.exported_edges(&filter) {
+            writeln!(
+                csv.relationships,
+                "{},{},{},{}",
+                csv_string(&self.node_id_string(source)),
+                csv_string(&self.node_id_string(sink)),
+                EDGE_TYPE,
+                precedence
+            )
+            .unwrap();
+        }
+        for (reference, definition) in self.exported_resolutions(resolutions, &filter) {
+            writeln!(
+                csv.relationships,
+                "{},{},{},",
+                csv_string(&self.node_id_string(reference)),
+                csv_string(&self.node_id_string(definition)),
+                RESOLUTION_TYPE,
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    fn node_id_string(&self, node: Handle<Node>) -> String {
+        self[node].id().display(self).to_string()
+    }
+
+    fn exported_edges<'a>(
+        &'a self,
+        filter: &'a dyn Filter,
+    ) -> impl Iterator<Item = (Handle<Node>, Handle<Node>, Precedence)> + 'a {
+        self.iter_nodes()
+            .filter(move |n| filter.include_node(self, n))
+            .flat_map(move |source| self.outgoing_edges(source))
+            .filter(move |e| filter.include_edge(self, &e.source, &e.sink))
+            .map(|e| (e.source, e.sink, e.precedence))
+    }
+
+    fn exported_resolutions<'a>(
+        &'a self,
+        resolutions: &'a [(Handle<Node>, Handle<Node>)],
+        filter: &'a dyn Filter,
+    ) -> impl Iterator<Item = (Handle<Node>, Handle<Node>)> + 'a {
+        resolutions
+            .iter()
+            .copied()
+            .filter(move |(reference, definition)| {
+                filter.include_node(self, reference) && filter.include_node(self, definition)
+            })
+    }
+}
+
+/// The names and Neo4j types of the properties of exported nodes, in CSV column order.
+const NODE_PROPERTIES: [(&str, &str); 12] = [
+    ("file", "string"),
+    ("local_id", "int"),
+    ("symbol", "string"),
+    ("scope", "string"),
+    ("is_definition", "boolean"),
+    ("is_reference", "boolean"),
+    ("is_exported", "boolean"),
+    ("syntax_type", "string"),
+    ("start_line", "int"),
+    ("start_column", "int"),
+    ("end_line", "int"),
+    ("end_column", "int"),
+];
+
+/// The exported data of a stack graph node.
+struct NodeRecord<'a> {
+    id: String,
+    label: &'static str,
+    graph: &'a StackGraph,
+    node: Handle<Node>,
+}
+
+impl<'a> NodeRecord<'a> {
+    fn new(graph: &'a StackGraph, node: Handle<Node>) -> Self {
+        let label = match &graph[node] {
+            Node::DropScopes(_) => "DropScopes",
+            Node::JumpTo(_) => "JumpToScope",
+            Node::PopScopedSymbol(_) => "PopScopedSymbol",
+            Node::PopSymbol(_) => "PopSymbol",
+            Node::PushScopedSymbol(_) => "PushScopedSymbol",
+            Node::PushSymbol(_) => "PushSymbol",
+            Node::Root(_) => "Root",
+            Node::Scope(_) => "Scope",
+        };
+        NodeRecord {
+            id: graph.node_id_string(node),
+            label,
+            graph,
+            node,
+        }
+    }
+
+    /// Returns the properties, except the ID, that apply to this node.
+    fn properties(&self) -> Vec<(&'static str, Value)> {
+        let graph = self.graph;
+        let node = &graph[self.node];
+        let mut properties = Vec::new();
+        if let Some(file) = node.file() {
+            properties.push(("file", Value::String(graph[file].name().to_string())));
+            properties.push(("local_id", Value::Int(node.id().local_id() as i64)));
+        }
+        if let Some(symbol) = node.symbol() {
+            properties.push(("symbol", Value::String(graph[symbol].to_string())));
+        }
+        if let Some(scope) = node.scope() {
+            properties.push(("scope", Value::String(scope.display(graph).to_string())));
+        }
+        if node.symbol().is_some() {
+            properties.push(("is_definition", Value::Bool(node.is_definition())));
+            properties.push(("is_reference", Value::Bool(node.is_reference())));
+        }
+        if let Node::Scope(scope) = node {
+            properties.push(("is_exported", Value::Bool(scope.is_exported)));
+        }
+        if let Some(source_info) = graph.source_info(self.node) {
+            if let Some(syntax_type) = source_info.syntax_type.into_option() {
+                properties.push(("syntax_type", Value::String(graph[syntax_type].to_string())));
+            }
+            let span = &source_info.span;
+            if *span != lsp_positions::Span::default() {
+                properties.push(("start_line", Value::Int(span.start.line as i64)));
+                properties.push((
+                    "start_column",
+                    Value::Int(span.start.column.utf16_offset as i64),
+                ));
+                properties.push(("end_line", Value::Int(span.end.line as i64)));
+                properties.push((
+                    "end_column",
+                    Value::Int(span.end.column.utf16_offset as i64),
+                ));
+            }
+        }
+        properties
+    }
+}
+
+/// A property value of an exported node.
+enum Value {
+    String(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl Value {
+    fn to_cypher(&self) -> String {
+        match self {
+            Value::String(value) => cypher_string(value),
+            Value::Int(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            Value::String(value) => csv_string(value),
+            Value::Int(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+/// Returns the value as a double quoted Cypher string literal.
+fn cypher_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Returns the value as a double quoted CSV field.
+fn csv_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
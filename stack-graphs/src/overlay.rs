@@ -0,0 +1,354 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Layers temporary nodes and edges on top of an immutable stack graph.
+//!
+//! Editor features often need answers for a graph that differs slightly from the one that was
+//! indexed, such as the graph of a buffer with unsaved changes, or a hypothetical edge that a tool
+//! wants to test.  A [`GraphOverlay`][] holds a shared reference to a base [`StackGraph`][], which
+//! is never modified, and a small graph of its own, to which the temporary nodes and edges are
+//! added.  A file in the overlay _shadows_ the file of the same name in the base graph.
+//!
+//! The overlay acts as the [candidates][ForwardCandidates] of path stitching.  Whenever a path
+//! reaches a node of the base graph, the file of that node and the edges leaving it are copied
+//! into the overlay, so only the parts of the base graph that a query actually visits are copied,
+//! and many overlays can share one (for instance [frozen][StackGraph::freeze]) base graph.
+//!
+//! ```
+//! # use stack_graphs::graph::NodeID;
+//! # use stack_graphs::graph::StackGraph;
+//! # use stack_graphs::overlay::GraphOverlay;
+//! # use stack_graphs::stitching::ForwardPartialPathStitcher;
+//! # use stack_graphs::stitching::StitcherConfig;
+//! # use stack_graphs::NoCancellation;
+//! # let base = StackGraph::new();
+//! let mut overlay = GraphOverlay::new(&base);
+//! let graph = overlay.graph_mut();
+//! let file = graph.add_file("unsaved.py").unwrap();
+//! let x = graph.add_symbol("x");
+//! let reference = graph
+//!     .add_push_symbol_node(NodeID::new_in_file(file, 0), x, true)
+//!     .unwrap();
+//! graph.add_edge(reference, StackGraph::root_node(), 0.into());
+//! ForwardPartialPathStitcher::find_all_complete_partial_paths(
+//!     &mut overlay,
+//!     vec![reference],
+//!     StitcherConfig::default(),
+//!     &NoCancellation,
+//!     |_graph, _partials, _path| {},
+//! )
+//! .unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use controlled_option::ControlledOption;
+
+use crate::arena::Handle;
+use crate::graph::Degree;
+use crate::graph::Edge;
+use crate::graph::File;
+use crate::graph::InternedString;
+use crate::graph::Node;
+use crate::graph::NodeID;
+use crate::graph::SourceInfo;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::ForwardCandidates;
+use crate::stitching::GraphEdges;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// Temporary nodes and edges on top of an immutable base stack graph.  See the [module
+/// documentation][self] for details.
+pub struct GraphOverlay<'a> {
+    base: &'a StackGraph,
+    graph: StackGraph,
+    partials: PartialPaths,
+    edges: GraphEdges,
+    /// The files of the base graph that were copied into the overlay.
+    files: HashMap<Handle<File>, Handle<File>>,
+    /// The nodes of the base graph that were copied into the overlay.
+    nodes: HashMap<Handle<Node>, Handle<Node>>,
+    /// The base graph nodes of the copied nodes.
+    base_nodes: HashMap<Handle<Node>, Handle<Node>>,
+    /// The copied nodes whose outgoing edges were copied as well.
+    loaded_nodes: HashSet<Handle<Node>>,
+    /// The symbols for which the root node's outgoing edges were copied.  `None` stands for all of
+    /// them.
+    loaded_root_symbols: HashSet<Option<String>>,
+}
+
+impl<'a> GraphOverlay<'a> {
+    /// Creates a new, empty overlay on top of the given base graph.
+    pub fn new(base: &'a StackGraph) -> Self {
+        let mut graph = StackGraph::new();
+        graph.add_partitions_from_graph(base);
+        Self {
+            base,
+            graph,
+            partials: PartialPaths::new(),
+            edges: GraphEdges,
+            files: HashMap::new(),
+            nodes: HashMap::new(),
+            base_nodes: HashMap::new(),
+            loaded_nodes: HashSet::new(),
+            loaded_root_symbols: HashSet::new(),
+        }
+    }
+
+    /// Returns the base graph of this overlay.
+    pub fn base(&self) -> &'a StackGraph {
+        self.base
+    }
+
+    /// Returns the graph of this overlay, which contains the temporary nodes and edges, and the
+    /// parts of the base graph that were copied so far.
+    pub fn graph(&self) -> &StackGraph {
+        &self.graph
+    }
+
+    /// Returns the graph of this overlay, so that temporary nodes and edges can be added to it.
+    /// Files that are added to it shadow the files of the same name in the base graph.  To add an
+    /// edge that starts at a node of the base graph, get its copy with
+    /// [`overlay_node`][Self::overlay_node] first.
+    pub fn graph_mut(&mut self) -> &mut StackGraph {
+        &mut self.graph
+    }
+
+    /// Returns the partial path arena that is used for stitching paths in this overlay.
+    pub fn partials(&mut self) -> &mut PartialPaths {
+        &mut self.partials
+    }
+
+    /// Returns whether the given file of the base graph is shadowed by a file of the overlay.
+    pub fn is_shadowed(&self, base_file: Handle<File>) -> bool {
+        !self.files.contains_key(&base_file)
+            && self.graph.get_file(self.base[base_file].name()).is_some()
+    }
+
+    /// Returns the copy of a file of the base graph in the overlay, copying the file's nodes if
+    /// that has not been done yet.  Returns `None` if the file is shadowed.
+    pub fn overlay_file(&mut self, base_file: Handle<File>) -> Option<Handle<File>> {
+        if let Some(file) = self.files.get(&base_file) {
+            return Some(*file);
+        }
+        let base = self.base;
+        let file = self.graph.add_file_of(base, base_file).ok()?;
+        if let Some(language) = base.file_language(base_file) {
+            self.graph.set_file_language(file, language);
+        }
+        self.graph.copy_file_partition(file, base, base_file);
+        self.files.insert(base_file, file);
+        for base_node in base.nodes_for_file(base_file) {
+            let node = self.copy_node(base_node, file);
+            self.nodes.insert(base_node, node);
+            self.base_nodes.insert(node, base_node);
+        }
+        Some(file)
+    }
+
+    /// Returns the copy of a node of the base graph in the overlay, copying the nodes of its file
+    /// if that has not been done yet.  Returns `None` if the node's file is shadowed.
+    pub fn overlay_node(&mut self, base_node: Handle<Node>) -> Option<Handle<Node>> {
+        let base = self.base;
+        let base_file = match base[base_node].file() {
+            Some(base_file) => base_file,
+            // The singleton nodes have the same handles in every graph.
+            None => return Some(base_node),
+        };
+        self.overlay_file(base_file)?;
+        self.nodes.get(&base_node).copied()
+    }
+
+    /// Returns the node of the base graph that a node of the overlay is a copy of.  Returns `None`
+    /// for the temporary nodes of the overlay.
+    pub fn base_node(&self, node: Handle<Node>) -> Option<Handle<Node>> {
+        if self.graph[node].file().is_none() {
+            return Some(node);
+        }
+        self.base_nodes.get(&node).copied()
+    }
+
+    fn copy_node(&mut self, base_node: Handle<Node>, file: Handle<File>) -> Handle<Node> {
+        let base = self.base;
+        let id = NodeID::new_in_file(file, base[base_node].id().local_id());
+        let symbol = base[base_node]
+            .symbol()
+            .map(|symbol| self.graph.add_symbol(&base[symbol]));
+        let node = match &base[base_node] {
+            Node::DropScopes(_) => self.graph.add_drop_scopes_node(id),
+            Node::PopScopedSymbol(node) => {
+                self.graph
+                    .add_pop_scoped_symbol_node(id, symbol.unwrap(), node.is_definition)
+            }
+            Node::PopSymbol(node) => {
+                self.graph
+                    .add_pop_symbol_node(id, symbol.unwrap(), node.is_definition)
+            }
+            Node::PushScopedSymbol(node) => {
+                let scope = self.overlay_node_id(node.scope);
+                self.graph.add_push_scoped_symbol_node(
+                    id,
+                    symbol.unwrap(),
+                    scope,
+                    node.is_reference,
+                )
+            }
+            Node::PushSymbol(node) => {
+                self.graph
+                    .add_push_symbol_node(id, symbol.unwrap(), node.is_reference)
+            }
+            Node::Scope(node) => self.graph.add_scope_node(id, node.is_exported),
+            Node::JumpTo(_) | Node::Root(_) => unreachable!("singleton nodes belong to no file"),
+        }
+        .expect("node IDs to be unique in the base graph");
+        if let Some(source_info) = base.source_info(base_node) {
+            let syntax_type = self.copy_string(source_info.syntax_type);
+            let containing_line = self.copy_string(source_info.containing_line);
+            let fully_qualified_name = self.copy_string(source_info.fully_qualified_name);
+            *self.graph.source_info_mut(node) = SourceInfo {
+                span: source_info.span.clone(),
+                syntax_type,
+                containing_line,
+                definiens_span: source_info.definiens_span.clone(),
+                fully_qualified_name,
+            };
+        }
+        if base.is_speculative(base_node) {
+            self.graph.mark_speculative(node);
+        }
+        node
+    }
+
+    fn copy_string(
+        &mut self,
+        string: ControlledOption<Handle<InternedString>>,
+    ) -> ControlledOption<Handle<InternedString>> {
+        let base = self.base;
+        string
+            .into_option()
+            .map(|string| self.graph.add_string(&base[string]))
+            .into()
+    }
+
+    /// Translates the ID of a node of the base graph, which might not have been copied yet, to its
+    /// ID in the overlay.
+    fn overlay_node_id(&mut self, base_id: NodeID) -> NodeID {
+        let base = self.base;
+        let base_file = match base_id.file() {
+            Some(base_file) => base_file,
+            None => return base_id,
+        };
+        let file = self
+            .overlay_file(base_file)
+            .or_else(|| self.graph.get_file(base[base_file].name()))
+            .unwrap();
+        NodeID::new_in_file(file, base_id.local_id())
+    }
+
+    /// Copies the outgoing edges and aliases of a node of the base graph into the overlay.  For
+    /// the root node, only the edges that can continue a path with the given symbol stack are
+    /// copied.
+    fn load_node(&mut self, node: Handle<Node>, top_symbol: Option<&str>) {
+        let base = self.base;
+        let base_node = match self.base_node(node) {
+            Some(base_node) => base_node,
+            None => return,
+        };
+        if base_node == StackGraph::root_node() {
+            let key = top_symbol.map(str::to_string);
+            if self.loaded_root_symbols.contains(&None) || !self.loaded_root_symbols.insert(key) {
+                return;
+            }
+        } else if !self.loaded_nodes.insert(node) {
+            return;
+        }
+        for alias in base.node_aliases(base_node) {
+            if let Some(alias) = self.overlay_node(*alias) {
+                self.graph.add_node_alias(node, alias);
+            }
+        }
+        for edge in base.outgoing_edges(base_node) {
+            if base_node == StackGraph::root_node() {
+                let popped_symbol = match &base[edge.sink] {
+                    Node::PopSymbol(sink) => Some(sink.symbol),
+                    Node::PopScopedSymbol(sink) => Some(sink.symbol),
+                    _ => None,
+                };
+                if let (Some(top_symbol), Some(popped_symbol)) = (top_symbol, popped_symbol) {
+                    if base[popped_symbol].as_str() != top_symbol {
+                        continue;
+                    }
+                }
+            }
+            let sink = match self.overlay_node(edge.sink) {
+                Some(sink) => sink,
+                None => continue,
+            };
+            self.graph.add_edge(node, sink, edge.precedence);
+            if let Some(condition) = base.edge_condition(edge.source, edge.sink) {
+                let condition = self.graph.add_string(&base[condition]);
+                self.graph.set_edge_condition(node, sink, condition);
+            }
+            if let Some(confidence) = base.edge_confidence(edge.source, edge.sink) {
+                self.graph.set_edge_confidence(node, sink, confidence);
+            }
+        }
+    }
+}
+
+impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for GraphOverlay<'_> {
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError> {
+        cancellation_flag.check("loading overlay candidates")?;
+        let mut symbol_stack = path.symbol_stack_postcondition;
+        let top_symbol = symbol_stack
+            .pop_front(&mut self.partials)
+            .map(|symbol| self.graph[symbol.symbol].as_str().to_string());
+        self.load_node(path.end_node, top_symbol.as_deref());
+        // Loading the end node declares its aliases, whose edges are candidates as well.
+        let aliases = self.graph.node_aliases(path.end_node).to_vec();
+        for alias in aliases {
+            self.load_node(alias, top_symbol.as_deref());
+        }
+        Ok(())
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Edge>,
+    {
+        // Paths can be extended from the end node and from all of its aliases.
+        let graph = &self.graph;
+        result.extend(
+            std::iter::once(path.end_node)
+                .chain(graph.node_aliases(path.end_node).iter().copied())
+                .flat_map(|node| graph.outgoing_edges(node)),
+        );
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        // The edges of the base graph are only copied when they are needed, so the overlay's own
+        // degree can be too small.  Overestimating it is safe, since it only enables additional
+        // checks for similar paths.
+        let degree = self.graph.incoming_edge_degree(path.end_node);
+        match self.base_node(path.end_node) {
+            Some(base_node) => degree + self.base.incoming_edge_degree(base_node),
+            None => degree,
+        }
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &GraphEdges) {
+        (&self.graph, &mut self.partials, &self.edges)
+    }
+}
@@ -0,0 +1,364 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Builds the stack graphs and partial paths of many files in parallel.
+//!
+//! Stack graphs are built per file, and the partial paths of a file only depend on the graph of
+//! that file, so both can be computed for many files concurrently.  A [`ParallelBuilder`][] runs
+//! a construction function for every input on a pool of worker threads, computes the partial paths
+//! of each resulting file graph, and hands the results to a single consumer running on the calling
+//! thread, which can store them in a database or merge them into a larger graph.  The number of
+//! results that are waiting for the consumer is bounded, so that workers that are faster than the
+//! consumer are blocked instead of keeping an unbounded number of file graphs in memory.
+//!
+//! Inputs can also be taken from a [`WorkQueue`][], which can be shared with other threads while
+//! it is being processed.  Inputs that are [prioritized][WorkQueue::prioritize], such as files that
+//! are open in an editor, jump the queue, and are built by the next worker that becomes available.
+//!
+//! ```
+//! use stack_graphs::graph::NodeID;
+//! use stack_graphs::parallel::ParallelBuilder;
+//! use stack_graphs::NoCancellation;
+//!
+//! let names = vec!["a.py", "b.py", "c.py"];
+//! let mut built = Vec::new();
+//! ParallelBuilder::new()
+//!     .with_jobs(2)
+//!     .run(
+//!         &names,
+//!         |name, graph| {
+//!             let file = graph.get_or_create_file(*name);
+//!             graph.add_scope_node(NodeID::new_in_file(file, 0), true);
+//!             Ok::<_, std::convert::Infallible>(file)
+//!         },
+//!         &NoCancellation,
+//!         |index, result| {
+//!             built.push((index, result.unwrap().paths.len()));
+//!             Ok::<_, std::convert::Infallible>(())
+//!         },
+//!     )
+//!     .unwrap();
+//! assert_eq!(built.len(), names.len());
+//! ```
+
+use std::collections::VecDeque;
+use std::iter::FromIterator;
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::PartialPathsConfig;
+use crate::stitching::Stats;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// The stack graph and partial paths that were built for a single input.
+pub struct BuiltFile {
+    /// The graph containing the file.
+    pub graph: StackGraph,
+    /// The file that was built.
+    pub file: Handle<File>,
+    /// The partial paths arena that owns the symbol and scope stacks of the paths.
+    pub partials: PartialPaths,
+    /// The partial paths of the file.
+    pub paths: Vec<PartialPath>,
+    /// The statistics of computing the partial paths.
+    pub stats: Stats,
+}
+
+/// An error that occurred while building the graph or partial paths for an input.
+#[derive(Debug, Error)]
+pub enum BuildError<E> {
+    /// The construction function failed.
+    #[error("{0}")]
+    Build(E),
+    /// Building was cancelled, either before the input was started, or while computing its
+    /// partial paths.
+    #[error(transparent)]
+    Cancelled(#[from] CancellationError),
+}
+
+/// Builds the stack graphs and partial paths of many inputs on a pool of worker threads.
+#[derive(Clone, Debug)]
+pub struct ParallelBuilder {
+    jobs: usize,
+    queue_size: usize,
+    paths_config: PartialPathsConfig,
+}
+
+impl ParallelBuilder {
+    /// Creates a builder that uses as many worker threads as there is available parallelism, and
+    /// the default partial paths configuration.
+    pub fn new() -> Self {
+        let jobs = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self {
+            jobs,
+            queue_size: 2 * jobs,
+            paths_config: PartialPathsConfig::default(),
+        }
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Sets the number of worker threads.  With a single job, all inputs are built on the calling
+    /// thread.
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        assert!(jobs > 0, "number of jobs must be positive");
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn queue_size(&self) -> usize {
+        self.queue_size
+    }
+
+    /// Sets the number of results that may wait for the consumer before workers are blocked.
+    pub fn with_queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    pub fn paths_config(&self) -> &PartialPathsConfig {
+        &self.paths_config
+    }
+
+    /// Sets the configuration that is used to compute the partial paths of every file.
+    pub fn with_paths_config(mut self, paths_config: PartialPathsConfig) -> Self {
+        self.paths_config = paths_config;
+        self
+    }
+
+    /// Builds every input and passes the results to `consume`, together with the index of the
+    /// input they belong to.  The `build` function is given a fresh graph for every input, and
+    /// must return the file it built in that graph.  Results are passed in the order in which
+    /// they are completed, which is not necessarily the order of the inputs.
+    ///
+    /// Every input gets exactly one result, unless `consume` returns an error, in which case the
+    /// workers are stopped and the error is returned.  Once the cancellation flag is set, inputs
+    /// that are not yet started are not built anymore, and get a [`BuildError::Cancelled`][]
+    /// result.
+    pub fn run<I, B, E, C, CE>(
+        &self,
+        inputs: &[I],
+        build: B,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+        mut consume: C,
+    ) -> Result<(), CE>
+    where
+        I: Sync,
+        B: Fn(&I, &mut StackGraph) -> Result<Handle<File>, E> + Sync,
+        E: Send,
+        C: FnMut(usize, Result<BuiltFile, BuildError<E>>) -> Result<(), CE>,
+    {
+        let queue = inputs.iter().enumerate().collect::<WorkQueue<_>>();
+        self.run_queue(
+            &queue,
+            |(_, input), graph| build(input, graph),
+            cancellation_flag,
+            |(index, _), result| consume(index, result),
+        )
+    }
+
+    /// Builds the inputs in a work queue and passes the results to `consume`, together with the
+    /// input they belong to.  Workers take the next input from the queue whenever they are done
+    /// with the previous one, so inputs that are [prioritized][WorkQueue::prioritize] while the
+    /// queue is being processed are built as soon as a worker becomes available.  Workers stop
+    /// once the queue is empty, and inputs that are added after that remain in the queue.
+    ///
+    /// Results are passed and cancellation is handled as for [`run`][Self::run].
+    pub fn run_queue<I, B, E, C, CE>(
+        &self,
+        queue: &WorkQueue<I>,
+        build: B,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+        mut consume: C,
+    ) -> Result<(), CE>
+    where
+        I: Send,
+        B: Fn(&I, &mut StackGraph) -> Result<Handle<File>, E> + Sync,
+        E: Send,
+        C: FnMut(I, Result<BuiltFile, BuildError<E>>) -> Result<(), CE>,
+    {
+        let jobs = self.jobs.min(queue.len());
+        if jobs <= 1 {
+            while let Some(input) = queue.pop() {
+                let result = self.build_one(&input, &build, cancellation_flag);
+                consume(input, result)?;
+            }
+            return Ok(());
+        }
+
+        std::thread::scope(|scope| {
+            let (sender, receiver) = mpsc::sync_channel(self.queue_size);
+            for _ in 0..jobs {
+                let sender = sender.clone();
+                let build = &build;
+                scope.spawn(move || {
+                    while let Some(input) = queue.pop() {
+                        let result = self.build_one(&input, build, cancellation_flag);
+                        if sender.send((input, result)).is_err() {
+                            // the receiver stopped because the consumer failed
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+            for (input, result) in receiver {
+                consume(input, result)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn build_one<I, B, E>(
+        &self,
+        input: &I,
+        build: &B,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<BuiltFile, BuildError<E>>
+    where
+        B: Fn(&I, &mut StackGraph) -> Result<Handle<File>, E>,
+    {
+        cancellation_flag.check("building file")?;
+        let mut graph = StackGraph::new();
+        let file = build(input, &mut graph).map_err(BuildError::Build)?;
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        let stats = ForwardPartialPathStitcher::find_partial_paths_in_file(
+            &graph,
+            &mut partials,
+            file,
+            &self.paths_config,
+            cancellation_flag,
+            |_graph, _partials, path| paths.push(path.clone()),
+        )
+        .map_err(|err| BuildError::Cancelled(err.error))?;
+        Ok(BuiltFile {
+            graph,
+            file,
+            partials,
+            paths,
+            stats,
+        })
+    }
+}
+
+impl Default for ParallelBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A queue of inputs that can be shared between threads, in which inputs that are prioritized,
+/// such as files that are open in an editor, are taken before inputs that were added for batch
+/// processing.  Prioritized inputs are taken in the reverse order in which they were prioritized,
+/// so that the most recent request is served first.
+///
+/// All consumers take inputs from the front of the same queue, and there is no work-stealing
+/// between them: an input is taken by whichever consumer asks for the next one first.
+pub struct WorkQueue<I> {
+    inputs: Mutex<WorkQueueInputs<I>>,
+}
+
+struct WorkQueueInputs<I> {
+    prioritized: VecDeque<I>,
+    batch: VecDeque<I>,
+}
+
+impl<I> WorkQueue<I> {
+    pub fn new() -> Self {
+        Self {
+            inputs: Mutex::new(WorkQueueInputs {
+                prioritized: VecDeque::new(),
+                batch: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Adds an input for batch processing, after all inputs that are already in the queue.
+    pub fn push(&self, input: I) {
+        self.inputs.lock().unwrap().batch.push_back(input);
+    }
+
+    /// Takes the next input from the queue, which is the most recently prioritized input if there
+    /// is one, and the oldest batch input otherwise.
+    pub fn pop(&self) -> Option<I> {
+        let mut inputs = self.inputs.lock().unwrap();
+        inputs
+            .prioritized
+            .pop_front()
+            .or_else(|| inputs.batch.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        let inputs = self.inputs.lock().unwrap();
+        inputs.prioritized.len() + inputs.batch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds an input that is taken before all inputs that are already in the queue.  If inputs
+    /// that are the same according to `is_same` are already in the queue, the first of them is
+    /// moved to the front instead, and the given input and the other ones are dropped.  This
+    /// keeps the details that the queued input was added with, such as the project root of a file
+    /// that was queued as part of its directory.
+    pub fn prioritize_matching<F>(&self, input: I, is_same: F)
+    where
+        F: Fn(&I, &I) -> bool,
+    {
+        let mut inputs = self.inputs.lock().unwrap();
+        let queued = match inputs.prioritized.iter().position(|i| is_same(i, &input)) {
+            Some(index) => inputs.prioritized.remove(index),
+            None => match inputs.batch.iter().position(|i| is_same(i, &input)) {
+                Some(index) => inputs.batch.remove(index),
+                None => None,
+            },
+        };
+        inputs.prioritized.retain(|i| !is_same(i, &input));
+        inputs.batch.retain(|i| !is_same(i, &input));
+        inputs.prioritized.push_front(queued.unwrap_or(input));
+    }
+}
+
+impl<I: PartialEq> WorkQueue<I> {
+    /// Adds an input that is taken before all inputs that are already in the queue.  If the input
+    /// is already in the queue, it is moved to the front instead of being added twice.
+    pub fn prioritize(&self, input: I) {
+        self.prioritize_matching(input, |a, b| a == b);
+    }
+}
+
+impl<I> Default for WorkQueue<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> FromIterator<I> for WorkQueue<I> {
+    fn from_iter<T: IntoIterator<Item = I>>(iter: T) -> Self {
+        Self {
+            inputs: Mutex::new(WorkQueueInputs {
+                prioritized: VecDeque::new(),
+                batch: iter.into_iter().collect(),
+            }),
+        }
+    }
+}
@@ -141,11 +141,16 @@ use smallvec::SmallVec;
 use crate::arena::Deque;
 use crate::arena::DequeArena;
 use crate::arena::Handle;
+use crate::arena::MemoryBudget;
 use crate::graph::Edge;
+use crate::graph::File;
+use crate::graph::InternedString;
 use crate::graph::Node;
 use crate::graph::NodeID;
+use crate::graph::Precedence;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
+use crate::graph::MAX_EDGE_CONFIDENCE;
 use crate::paths::PathResolutionError;
 use crate::utils::cmp_option;
 use crate::utils::equals_option;
@@ -1389,6 +1394,31 @@ impl PartialScopeStack {
             })
     }
 
+    /// Compares two partial scope stacks by the IDs of their scopes, using
+    /// [`StackGraph::cmp_node_ids`][], so that the result does not depend on the order in which
+    /// nodes were added to the graph.
+    pub fn cmp_by_content(
+        self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        other: PartialScopeStack,
+    ) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+            .then_with(|| {
+                self.scopes
+                    .cmp_with(&mut partials.partial_scope_stacks, other.scopes, |a, b| {
+                        graph.cmp_node_ids(graph[*a].id(), graph[*b].id())
+                    })
+            })
+            .then_with(|| {
+                cmp_option(
+                    self.variable.into_option(),
+                    other.variable.into_option(),
+                    |a, b| a.cmp(&b),
+                )
+            })
+    }
+
     /// Returns an iterator over the scopes in this partial scope stack.
     pub fn iter_scopes<'a>(
         &self,
@@ -1516,6 +1546,23 @@ impl PartialSymbolStackBindings {
         Ok(())
     }
 
+    /// Returns whether any symbol stack variables are bound.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.iter().all(Option::is_none)
+    }
+
+    /// Returns an iterator over all bound symbol stack variables and the partial symbol stacks
+    /// they matched, ordered by variable.
+    pub fn iter(&self) -> impl Iterator<Item = (SymbolStackVariable, PartialSymbolStack)> + '_ {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter_map(|(index, binding)| {
+                let variable = SymbolStackVariable::new(index as u32 + 1).unwrap();
+                binding.map(|binding| (variable, binding))
+            })
+    }
+
     pub fn display<'a>(
         &'a mut self,
         graph: &'a StackGraph,
@@ -1605,6 +1652,23 @@ impl PartialScopeStackBindings {
         Ok(())
     }
 
+    /// Returns whether any scope stack variables are bound.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.iter().all(Option::is_none)
+    }
+
+    /// Returns an iterator over all bound scope stack variables and the partial scope stacks they
+    /// matched, ordered by variable.
+    pub fn iter(&self) -> impl Iterator<Item = (ScopeStackVariable, PartialScopeStack)> + '_ {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter_map(|(index, binding)| {
+                let variable = ScopeStackVariable::new(index as u32 + 1).unwrap();
+                binding.map(|binding| (variable, binding))
+            })
+    }
+
     pub fn display<'a>(
         &'a mut self,
         graph: &'a StackGraph,
@@ -1657,14 +1721,14 @@ impl<'a> DisplayWithPartialPaths for &'a mut PartialScopeStackBindings {
 #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PartialPathEdge {
     pub source_node_id: NodeID,
-    pub precedence: i32,
+    pub precedence: Precedence,
 }
 
 impl PartialPathEdge {
     /// Returns whether one edge shadows another.  Note that shadowing is not commutative — if path
     /// A shadows path B, the reverse is not true.
     pub fn shadows(self, other: PartialPathEdge) -> bool {
-        self.source_node_id == other.source_node_id && self.precedence > other.precedence
+        self.source_node_id == other.source_node_id && self.precedence.shadows(other.precedence)
     }
 
     pub fn display<'a>(
@@ -1687,7 +1751,7 @@ impl DisplayWithPartialPaths for PartialPathEdge {
             Some(node) => write!(f, "{:#}", node.display(graph))?,
             None => write!(f, "[missing]")?,
         }
-        if self.precedence != 0 {
+        if self.precedence != Precedence::DEFAULT {
             write!(f, "({})", self.precedence)?;
         }
         Ok(())
@@ -1824,6 +1888,36 @@ impl PartialPathEdgeList {
         }
     }
 
+    /// Compares two edge lists by the IDs of their source nodes, using
+    /// [`StackGraph::cmp_node_ids`][], and their precedences, so that the result does not depend
+    /// on the order in which files were added to the graph.
+    pub fn cmp_by_content(
+        mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        mut other: PartialPathEdgeList,
+    ) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        while let Some(self_edge) = self.pop_front(partials) {
+            if let Some(other_edge) = other.pop_front(partials) {
+                match graph
+                    .cmp_node_ids(self_edge.source_node_id, other_edge.source_node_id)
+                    .then(self_edge.precedence.cmp(&other_edge.precedence))
+                {
+                    Ordering::Equal => continue,
+                    result @ _ => return result,
+                }
+            } else {
+                return Ordering::Greater;
+            }
+        }
+        if other.edges.is_empty() {
+            Ordering::Equal
+        } else {
+            Ordering::Less
+        }
+    }
+
     /// Returns an iterator over the contents of this edge list.
     pub fn iter<'a>(
         &self,
@@ -1997,6 +2091,49 @@ impl PartialPath {
             })
     }
 
+    /// Compares two partial paths by their content: the IDs of their start and end nodes, their
+    /// pre- and postconditions, and their edges.  Unlike [`cmp`][Self::cmp], the result does not
+    /// depend on the order in which files and nodes were added to the graph, which makes it
+    /// suitable for [sorted iteration][crate::graph::IterationOrder::Sorted].
+    pub fn cmp_by_content(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        other: &PartialPath,
+    ) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+            .then_with(|| {
+                graph.cmp_node_ids(graph[self.start_node].id(), graph[other.start_node].id())
+            })
+            .then_with(|| graph.cmp_node_ids(graph[self.end_node].id(), graph[other.end_node].id()))
+            .then_with(|| {
+                self.symbol_stack_precondition
+                    .cmp(graph, partials, other.symbol_stack_precondition)
+            })
+            .then_with(|| {
+                self.symbol_stack_postcondition.cmp(
+                    graph,
+                    partials,
+                    other.symbol_stack_postcondition,
+                )
+            })
+            .then_with(|| {
+                self.scope_stack_precondition.cmp_by_content(
+                    graph,
+                    partials,
+                    other.scope_stack_precondition,
+                )
+            })
+            .then_with(|| {
+                self.scope_stack_postcondition.cmp_by_content(
+                    graph,
+                    partials,
+                    other.scope_stack_postcondition,
+                )
+            })
+            .then_with(|| self.edges.cmp_by_content(graph, partials, other.edges))
+    }
+
     /// Returns whether a partial path represents the start of a name binding from a reference to a
     /// definition.
     pub fn starts_at_reference(&self, graph: &StackGraph) -> bool {
@@ -2029,6 +2166,44 @@ impl PartialPath {
         graph[self.end_node].is_jump_to()
     }
 
+    /// Returns the files whose nodes participate in this partial path, in the order in which they
+    /// are first encountered.  The singleton _root_ and _jump to scope_ nodes do not belong to any
+    /// file, and are not reported.
+    ///
+    /// This can be used to record which files a resolution depends on, so that it can be
+    /// invalidated when any of those files change.
+    pub fn participating_files(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Vec<Handle<File>> {
+        let mut files = Vec::new();
+        let node_files = self
+            .edges
+            .iter(partials)
+            .map(|edge| edge.source_node_id.file())
+            .chain(std::iter::once(graph[self.end_node].file()));
+        for file in node_files.flatten() {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        files
+    }
+
+    /// Returns the intermediate nodes of this partial path that push or pop symbols, in the order
+    /// in which they are visited.  Together with the files they belong to, they explain how the
+    /// path resolves, such as the imports and re-exports that it follows.  The start and end nodes
+    /// are not included, and neither are nodes that are not loaded in the graph.
+    pub fn explain(&self, graph: &StackGraph, partials: &mut PartialPaths) -> Vec<Handle<Node>> {
+        self.edges
+            .iter(partials)
+            .skip(1)
+            .filter_map(|edge| graph.node_for_id(edge.source_node_id))
+            .filter(|node| *node != self.end_node && graph[*node].symbol().is_some())
+            .collect()
+    }
+
     /// Returns whether a partial path is cyclic---that is, it starts and ends at the same node,
     /// and its postcondition is compatible with its precondition.  If the path is cyclic, a
     /// tuple is returned indicating whether cycle requires strengthening the pre- or postcondition.
@@ -2358,7 +2533,7 @@ impl PartialPath {
             partials,
             PartialPathEdge {
                 source_node_id: graph[self.end_node].id(),
-                precedence: 0,
+                precedence: Precedence::DEFAULT,
             },
         );
         self.end_node = top_scope;
@@ -2405,6 +2580,146 @@ impl PartialPath {
 
         Ok(())
     }
+
+    /// Moves the end of this partial path to an [alias][StackGraph::add_node_alias] of its end
+    /// node.  The stack effects of the alias are not applied, because arriving at a node counts as
+    /// arriving at all of its aliases.  Does nothing if the partial path already ends at the given
+    /// node, and returns an error if the node is not an alias of the end node.
+    pub fn resolve_to_alias(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        node: Handle<Node>,
+    ) -> Result<(), PathResolutionError> {
+        if self.end_node == node {
+            return Ok(());
+        }
+        if !graph.are_aliases(self.end_node, node) {
+            return Err(PathResolutionError::IncorrectSourceNode);
+        }
+        self.edges.push_back(
+            partials,
+            PartialPathEdge {
+                source_node_id: graph[self.end_node].id(),
+                precedence: Precedence::DEFAULT,
+            },
+        );
+        self.end_node = node;
+        Ok(())
+    }
+
+    /// Returns the [condition][StackGraph::set_edge_condition] of the edge along which this
+    /// partial path arrives at the root node.  Returns `None` if the partial path does not end at
+    /// the root node, or if that edge does not have a condition.
+    pub fn root_arrival_condition(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Option<Handle<InternedString>> {
+        if !graph[self.end_node].is_root() {
+            return None;
+        }
+        let mut edges = self.edges;
+        let source = graph.node_for_id(edges.pop_back(partials)?.source_node_id)?;
+        graph.edge_condition(source, self.end_node)
+    }
+
+    /// Returns the [condition][StackGraph::set_edge_condition] of the edge along which this
+    /// partial path leaves the root node.  Returns `None` if the partial path does not start at
+    /// the root node, or if that edge does not have a condition.
+    pub fn root_departure_condition(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Option<Handle<InternedString>> {
+        if !graph[self.start_node].is_root() {
+            return None;
+        }
+        let mut edges = self.edges;
+        edges.pop_front(partials)?;
+        let sink = match edges.pop_front(partials) {
+            Some(edge) => graph.node_for_id(edge.source_node_id)?,
+            None => self.end_node,
+        };
+        graph.edge_condition(self.start_node, sink)
+    }
+
+    /// Ensures that this partial path, which ends at the root node, can leave the root node along
+    /// an edge with the given condition.  A path that has not arrived at the root node along an
+    /// edge, because it starts there, can leave it along any edge.
+    pub fn ensure_root_condition(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        condition: Option<Handle<InternedString>>,
+    ) -> Result<(), PathResolutionError> {
+        let condition = match condition {
+            Some(condition) => condition,
+            None => return Ok(()),
+        };
+        if self.edges.is_empty() {
+            return Ok(());
+        }
+        if self.root_arrival_condition(graph, partials) != Some(condition) {
+            return Err(PathResolutionError::IncompatibleRootCondition);
+        }
+        Ok(())
+    }
+
+    /// Returns the confidence of this partial path, as a value between 0 and 1.  This is the
+    /// product of the [confidences][StackGraph::set_edge_confidence] of the edges of the path, so
+    /// it carries over when partial paths are concatenated.  Returns `None` if all edges of the
+    /// path are certain, which is the case for all paths in languages that do not set edge
+    /// confidences.  Returns an error if the path visits a node that is not loaded in the graph,
+    /// since the confidences of its edges are unknown then.
+    pub fn confidence(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<Option<f32>, PathResolutionError> {
+        let mut confidence = None;
+        let mut edges = self.edges;
+        let mut next = edges.pop_front(partials);
+        while let Some(edge) = next {
+            next = edges.pop_front(partials);
+            let source = graph
+                .node_for_id(edge.source_node_id)
+                .ok_or(PathResolutionError::UnknownNode)?;
+            let sink = match next {
+                Some(next) => graph
+                    .node_for_id(next.source_node_id)
+                    .ok_or(PathResolutionError::UnknownNode)?,
+                None => self.end_node,
+            };
+            let edge_confidence = graph
+                .edge_confidence(source, sink)
+                .unwrap_or(MAX_EDGE_CONFIDENCE);
+            if edge_confidence < MAX_EDGE_CONFIDENCE {
+                let edge_confidence = edge_confidence as f32 / MAX_EDGE_CONFIDENCE as f32;
+                confidence = Some(confidence.unwrap_or(1.0) * edge_confidence);
+            }
+        }
+        Ok(confidence)
+    }
+
+    /// Returns whether this partial path visits a [speculative][StackGraph::mark_speculative]
+    /// node.  This carries over when partial paths are concatenated, so that the definitions found
+    /// by speculative paths can be hidden.
+    pub fn is_speculative(&self, graph: &StackGraph, partials: &mut PartialPaths) -> bool {
+        if graph.is_speculative(self.start_node) || graph.is_speculative(self.end_node) {
+            return true;
+        }
+        let mut edges = self.edges;
+        while let Some(edge) = edges.pop_front(partials) {
+            if graph
+                .node_for_id(edge.source_node_id)
+                .map_or(false, |node| graph.is_speculative(node))
+            {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 impl Node {
@@ -2649,13 +2964,44 @@ impl PartialPath {
     /// common, then we ensure that the variables bind to the same values on both sides.  It's your
     /// responsibility to update the two partial paths so that they have no variables in common, if
     /// that's needed for your use case.
-    #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
     pub fn concatenate(
         &mut self,
         graph: &StackGraph,
         partials: &mut PartialPaths,
         rhs: &PartialPath,
     ) -> Result<(), PathResolutionError> {
+        self.concatenate_inner(graph, partials, rhs)?;
+        Ok(())
+    }
+
+    /// Attempts to append a partial path to this one, like [`concatenate`][Self::concatenate],
+    /// but leaves this path unchanged.  The result contains the concatenated path, as well as the
+    /// bindings of the symbol and scope stack variables that were needed to join the two paths.
+    /// These bindings describe what the stack variables of the left- and right-hand paths stand
+    /// for in the concatenated path, and can be applied to other partial stacks using their
+    /// `apply_partial_bindings` methods.
+    pub fn concatenate_with_bindings(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        rhs: &PartialPath,
+    ) -> Result<ConcatenationResult, PathResolutionError> {
+        let mut path = self.clone();
+        let join = path.concatenate_inner(graph, partials, rhs)?;
+        Ok(ConcatenationResult {
+            path,
+            symbol_bindings: join.symbol_bindings,
+            scope_bindings: join.scope_bindings,
+        })
+    }
+
+    #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
+    fn concatenate_inner(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        rhs: &PartialPath,
+    ) -> Result<Join, PathResolutionError> {
         let lhs = self;
 
         #[cfg_attr(not(feature = "copious-debugging"), allow(unused_mut))]
@@ -2707,6 +3053,33 @@ impl PartialPath {
 
         lhs.resolve_from_postcondition(graph, partials)?;
 
+        Ok(join)
+    }
+
+    /// Substitutes the bound symbol and scope stack variables in the pre- and postconditions of
+    /// this partial path.  Variables that are not bound are left unchanged.
+    pub fn apply_partial_bindings(
+        &mut self,
+        partials: &mut PartialPaths,
+        symbol_bindings: &PartialSymbolStackBindings,
+        scope_bindings: &PartialScopeStackBindings,
+    ) -> Result<(), PathResolutionError> {
+        self.symbol_stack_precondition = self.symbol_stack_precondition.apply_partial_bindings(
+            partials,
+            symbol_bindings,
+            scope_bindings,
+        )?;
+        self.symbol_stack_postcondition = self.symbol_stack_postcondition.apply_partial_bindings(
+            partials,
+            symbol_bindings,
+            scope_bindings,
+        )?;
+        self.scope_stack_precondition = self
+            .scope_stack_precondition
+            .apply_partial_bindings(partials, scope_bindings)?;
+        self.scope_stack_postcondition = self
+            .scope_stack_postcondition
+            .apply_partial_bindings(partials, scope_bindings)?;
         Ok(())
     }
 
@@ -2777,6 +3150,16 @@ impl PartialPath {
     }
 }
 
+/// The result of [`PartialPath::concatenate_with_bindings`][].
+pub struct ConcatenationResult {
+    /// The concatenated partial path.
+    pub path: PartialPath,
+    /// The bindings of the symbol stack variables of both partial paths.
+    pub symbol_bindings: PartialSymbolStackBindings,
+    /// The bindings of the scope stack variables of both partial paths.
+    pub scope_bindings: PartialScopeStackBindings,
+}
+
 struct Join {
     #[cfg_attr(not(feature = "copious-debugging"), allow(dead_code))]
     pub unified_symbol_stack: PartialSymbolStack,
@@ -2806,7 +3189,14 @@ impl PartialPaths {
         }
     }
 
-    #[cfg_attr(not(feature = "storage"), allow(dead_code))]
+    /// Assigns a memory budget to the arenas of this instance.  See [`MemoryBudget`][] for which
+    /// allocations are counted, and how the budget is enforced.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.partial_symbol_stacks.set_budget(budget.clone());
+        self.partial_scope_stacks.set_budget(budget.clone());
+        self.partial_path_edges.set_budget(budget);
+    }
+
     pub(crate) fn clear(&mut self) {
         self.partial_symbol_stacks.clear();
         self.partial_scope_stacks.clear();
@@ -70,6 +70,7 @@ use std::collections::VecDeque;
 /// ## Structure Errors
 /// - [`IncorrectSourceNode`](PathResolutionError::IncorrectSourceNode): Edges don't connect properly
 /// - [`IncorrectFile`](PathResolutionError::IncorrectFile): Path spans multiple files incorrectly
+/// - [`IncompatibleRootCondition`](PathResolutionError::IncompatibleRootCondition): Path leaves the root under a different condition
 ///
 /// ## Partial Path Errors
 /// - [`ScopeStackUnsatisfied`](PathResolutionError::ScopeStackUnsatisfied): Precondition not met
@@ -80,6 +81,7 @@ use std::collections::VecDeque;
 /// ## Other Errors
 /// - [`DisallowedCycle`](PathResolutionError::DisallowedCycle): Cycle detection triggered
 /// - [`UnknownAttachedScope`](PathResolutionError::UnknownAttachedScope): Referenced scope doesn't exist
+/// - [`UnknownNode`](PathResolutionError::UnknownNode): Node on the path isn't loaded
 #[derive(Debug)]
 pub enum PathResolutionError {
     /// The path contains a cycle, and the cycle is disallowed.
@@ -101,6 +103,14 @@ pub enum PathResolutionError {
     /// an invalid path (likely missing the corresponding push operation).
     EmptySymbolStack,
 
+    /// The path leaves the root node along an edge whose condition does not match the edge along
+    /// which it arrived at the root node.
+    ///
+    /// Conditions on root edges restrict which definitions are reachable from a reference through
+    /// the root node, for example to the definitions in the module that the reference imports.
+    /// See [`StackGraph::set_edge_condition`][crate::graph::StackGraph::set_edge_condition].
+    IncompatibleRootCondition,
+
     /// The partial path contains multiple references to a scope stack variable, and those
     /// references can't unify on a single scope stack.
     ///
@@ -179,6 +189,13 @@ pub enum PathResolutionError {
     /// Push-scoped-symbol nodes reference an exported scope by ID. If that scope isn't in the
     /// graph, this error is returned.
     UnknownAttachedScope,
+
+    /// The path visits a node that is not loaded in the graph.
+    ///
+    /// Partial paths can refer to nodes of files whose graphs have not been loaded, for example
+    /// when they are read from a database.  Properties of the edges to and from such nodes, like
+    /// their confidence, are unknown.
+    UnknownNode,
 }
 
 /// A collection that can be used to receive the results of the [`Path::extend`][] method.
@@ -0,0 +1,450 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! A small query language for asking structural questions about stack graphs.
+//!
+//! A query selects either the definitions or the references in a stack graph, and narrows them
+//! down with a sequence of filters.  For example, the following query selects all function
+//! definitions named `get_*` in Python files under `src`, that have more than three references:
+//!
+//! ```text
+//! definitions named "get_*" kind function in "src/**/*.py" references > 3
+//! ```
+//!
+//! And the following query selects all unresolved references in test files:
+//!
+//! ```text
+//! references in "tests/**" definitions = 0
+//! ```
+//!
+//! The supported filters are:
+//!
+//! - `named PATTERN` selects nodes whose symbol matches the pattern.
+//! - `in PATTERN` selects nodes whose file name matches the pattern.
+//! - `language NAME` selects nodes in files [tagged][StackGraph::set_file_language] with the
+//!   language.
+//! - `kind KIND` selects definitions of the given [kind][`DefinitionKind`].
+//! - `references OP N` selects definitions with a number of references that compares to `N`.
+//! - `definitions OP N` selects references with a number of definitions that compares to `N`.
+//!
+//! Patterns can contain `?` to match any character, `*` to match any sequence of characters
+//! except `/`, and `**` to match any sequence of characters.  The comparison operators are `=`,
+//! `!=`, `<`, `<=`, `>`, and `>=`.  Patterns and other arguments that contain whitespace or
+//! operator characters must be double quoted.
+//!
+//! Parse a query with [`Query::parse`][], and run it against a stack graph with
+//! [`Query::execute`][].  References are resolved by stitching the edges of the stack graph, so
+//! all files that may contain relevant definitions or references must be loaded in the graph.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::arena::Handle;
+use crate::graph::DefinitionKind;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPaths;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::GraphEdgeCandidates;
+use crate::stitching::StitcherConfig;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// An error that occurred while parsing a query.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum QueryError {
+    #[error("unexpected end of query, expected {0}")]
+    UnexpectedEnd(&'static str),
+    #[error("unexpected `{found}` at offset {offset}, expected {expected}")]
+    UnexpectedToken {
+        found: String,
+        offset: usize,
+        expected: &'static str,
+    },
+    #[error("invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("unterminated string at offset {0}")]
+    UnterminatedString(usize),
+    #[error("`{0}` filter cannot be used in a query for {1}")]
+    InvalidFilter(&'static str, &'static str),
+}
+
+/// The kind of nodes selected by a query.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QueryTarget {
+    Definitions,
+    References,
+}
+
+impl QueryTarget {
+    fn as_str(&self) -> &'static str {
+        match self {
+            QueryTarget::Definitions => "definitions",
+            QueryTarget::References => "references",
+        }
+    }
+}
+
+/// A comparison operator used in count filters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn parse(s: &str) -> Option<Comparison> {
+        match s {
+            "=" | "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the comparison holds between the given values.
+    pub fn holds(&self, lhs: usize, rhs: usize) -> bool {
+        match self {
+            Comparison::Equal => lhs == rhs,
+            Comparison::NotEqual => lhs != rhs,
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+}
+
+/// A filter on the nodes selected by a query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QueryFilter {
+    /// Selects nodes whose symbol matches the pattern.
+    Named(String),
+    /// Selects nodes whose file name matches the pattern.
+    InFile(String),
+    /// Selects nodes in files of the given language.
+    Language(String),
+    /// Selects definitions of the given kind.
+    Kind(DefinitionKind),
+    /// Selects nodes whose number of resolutions (references of definitions, or definitions of
+    /// references) compares to the given count.
+    Count(Comparison, usize),
+}
+
+/// A parsed query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Query {
+    target: QueryTarget,
+    filters: Vec<QueryFilter>,
+}
+
+impl Query {
+    /// Parses a query from its textual syntax.
+    pub fn parse(source: &str) -> Result<Query, QueryError> {
+        let mut tokens = Tokens::new(source)?;
+        let target = match tokens.next_word("`definitions` or `references`")? {
+            (_, "definitions") => QueryTarget::Definitions,
+            (_, "references") => QueryTarget::References,
+            (offset, found) => {
+                return Err(QueryError::UnexpectedToken {
+                    found: found.to_string(),
+                    offset,
+                    expected: "`definitions` or `references`",
+                })
+            }
+        };
+        let mut filters = Vec::new();
+        while !tokens.is_empty() {
+            let filter = match tokens.next_word("filter")? {
+                (_, "named") => QueryFilter::Named(tokens.next_word("pattern")?.1.to_string()),
+                (_, "in") => QueryFilter::InFile(tokens.next_word("pattern")?.1.to_string()),
+                (_, "language") => {
+                    QueryFilter::Language(tokens.next_word("language")?.1.to_string())
+                }
+                (_, "kind") => {
+                    if target != QueryTarget::Definitions {
+                        return Err(QueryError::InvalidFilter("kind", target.as_str()));
+                    }
+                    QueryFilter::Kind(DefinitionKind::from_syntax_type(
+                        tokens.next_word("kind")?.1,
+                    ))
+                }
+                (_, count @ "references") | (_, count @ "definitions") => {
+                    if count == target.as_str() {
+                        return Err(QueryError::InvalidFilter(target.as_str(), target.as_str()));
+                    }
+                    let (offset, op) = tokens.next_word("comparison operator")?;
+                    let comparison =
+                        Comparison::parse(op).ok_or_else(|| QueryError::UnexpectedToken {
+                            found: op.to_string(),
+                            offset,
+                            expected: "comparison operator",
+                        })?;
+                    let (_, count) = tokens.next_word("number")?;
+                    let count = count
+                        .parse::<usize>()
+                        .map_err(|_| QueryError::InvalidNumber(count.to_string()))?;
+                    QueryFilter::Count(comparison, count)
+                }
+                (offset, found) => {
+                    return Err(QueryError::UnexpectedToken {
+                        found: found.to_string(),
+                        offset,
+                        expected: "filter",
+                    })
+                }
+            };
+            filters.push(filter);
+        }
+        Ok(Query { target, filters })
+    }
+
+    /// Returns the kind of nodes selected by this query.
+    pub fn target(&self) -> QueryTarget {
+        self.target
+    }
+
+    /// Returns the filters of this query.
+    pub fn filters(&self) -> &[QueryFilter] {
+        &self.filters
+    }
+
+    /// Runs this query against the given stack graph.  References are resolved by stitching the
+    /// edges of the graph, using the given stitcher configuration.  Results are sorted by node.
+    pub fn execute(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<QueryResults, CancellationError> {
+        let mut nodes = graph
+            .iter_nodes()
+            .filter(|node| match self.target {
+                QueryTarget::Definitions => graph[*node].is_definition(),
+                QueryTarget::References => graph[*node].is_reference(),
+            })
+            .filter(|node| self.matches_node(graph, *node))
+            .collect::<Vec<_>>();
+
+        // Definitions are found by resolving the selected references, while references are found
+        // by resolving all references in the graph.
+        let references = match self.target {
+            QueryTarget::Definitions => graph
+                .iter_nodes()
+                .filter(|node| graph[*node].is_reference())
+                .collect::<Vec<_>>(),
+            QueryTarget::References => nodes.clone(),
+        };
+        let mut seen = HashSet::new();
+        let mut resolutions = HashMap::<Handle<Node>, Vec<Handle<Node>>>::new();
+        let mut candidates = GraphEdgeCandidates::new(graph, partials, None);
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut candidates,
+            references,
+            config,
+            cancellation_flag,
+            |_, _, path| {
+                if !seen.insert((path.start_node, path.end_node)) {
+                    return;
+                }
+                let (from, to) = match self.target {
+                    QueryTarget::Definitions => (path.end_node, path.start_node),
+                    QueryTarget::References => (path.start_node, path.end_node),
+                };
+                resolutions.entry(from).or_default().push(to);
+            },
+        )?;
+
+        nodes.retain(|node| {
+            let count = resolutions.get(node).map_or(0, Vec::len);
+            self.filters.iter().all(|filter| match filter {
+                QueryFilter::Count(comparison, expected) => comparison.holds(count, *expected),
+                _ => true,
+            })
+        });
+        nodes.sort();
+        let matches = nodes.into_iter().map(|node| {
+            let mut related = resolutions.remove(&node).unwrap_or_default();
+            related.sort();
+            (node, related)
+        });
+        Ok(match self.target {
+            QueryTarget::Definitions => QueryResults::Definitions(
+                matches
+                    .map(|(definition, references)| DefinitionMatch {
+                        definition,
+                        references,
+                    })
+                    .collect(),
+            ),
+            QueryTarget::References => QueryResults::References(
+                matches
+                    .map(|(reference, definitions)| ReferenceMatch {
+                        reference,
+                        definitions,
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Returns whether the node passes all filters that do not depend on resolution.
+    fn matches_node(&self, graph: &StackGraph, node: Handle<Node>) -> bool {
+        self.filters.iter().all(|filter| match filter {
+            QueryFilter::Named(pattern) => graph[node]
+                .symbol()
+                .map_or(false, |symbol| glob_matches(pattern, &graph[symbol])),
+            QueryFilter::InFile(pattern) => graph[node]
+                .file()
+                .map_or(false, |file| glob_matches(pattern, graph[file].name())),
+            QueryFilter::Language(language) => graph[node]
+                .file()
+                .map_or(false, |file| graph.file_language(file) == Some(language)),
+            QueryFilter::Kind(kind) => graph.definition_kind(node).as_ref() == Some(kind),
+            QueryFilter::Count(_, _) => true,
+        })
+    }
+}
+
+/// The results of a query, typed by the query target.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QueryResults {
+    Definitions(Vec<DefinitionMatch>),
+    References(Vec<ReferenceMatch>),
+}
+
+impl QueryResults {
+    /// Returns the number of results.
+    pub fn len(&self) -> usize {
+        match self {
+            QueryResults::Definitions(matches) => matches.len(),
+            QueryResults::References(matches) => matches.len(),
+        }
+    }
+
+    /// Returns whether there are no results.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A definition selected by a query, with the references that resolve to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DefinitionMatch {
+    pub definition: Handle<Node>,
+    pub references: Vec<Handle<Node>>,
+}
+
+/// A reference selected by a query, with the definitions it resolves to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReferenceMatch {
+    pub reference: Handle<Node>,
+    pub definitions: Vec<Handle<Node>>,
+}
+
+//-------------------------------------------------------------------------------------------------
+// Tokenizer
+
+/// The tokens of a query, which are words, quoted strings, or comparison operators.  Each token
+/// is paired with its byte offset in the query.
+struct Tokens<'a> {
+    tokens: std::vec::IntoIter<(usize, &'a str)>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Result<Self, QueryError> {
+        let is_operator = |c: char| c == '<' || c == '>' || c == '=' || c == '!';
+        let mut tokens = Vec::new();
+        let mut chars = source.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            let end = if c == '"' {
+                match chars.find(|(_, c)| *c == '"') {
+                    Some((end, _)) => {
+                        tokens.push((start, &source[start + 1..end]));
+                        continue;
+                    }
+                    None => return Err(QueryError::UnterminatedString(start)),
+                }
+            } else if is_operator(c) {
+                let mut end = start + c.len_utf8();
+                while let Some((_, c)) = chars.next_if(|(_, c)| is_operator(*c)) {
+                    end += c.len_utf8();
+                }
+                end
+            } else {
+                let mut end = start + c.len_utf8();
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !is_operator(*c) && *c != '"')
+                {
+                    end += c.len_utf8();
+                }
+                end
+            };
+            tokens.push((start, &source[start..end]));
+        }
+        Ok(Tokens {
+            tokens: tokens.into_iter(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.as_slice().is_empty()
+    }
+
+    fn next_word(&mut self, expected: &'static str) -> Result<(usize, &'a str), QueryError> {
+        self.tokens
+            .next()
+            .ok_or(QueryError::UnexpectedEnd(expected))
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Patterns
+
+/// Returns whether the value matches the glob pattern.  A `?` matches any character, a `*`
+/// matches any sequence of characters except `/`, and `**` matches any sequence of characters.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let value = value.chars().collect::<Vec<_>>();
+    glob_matches_from(&pattern, &value)
+}
+
+fn glob_matches_from(pattern: &[char], value: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('*', rest)) => {
+            let (crosses_separators, rest) = match rest.split_first() {
+                Some(('*', rest)) => (true, rest),
+                _ => (false, rest),
+            };
+            for skip in 0..=value.len() {
+                if glob_matches_from(rest, &value[skip..]) {
+                    return true;
+                }
+                if skip < value.len() && value[skip] == '/' && !crosses_separators {
+                    return false;
+                }
+            }
+            false
+        }
+        Some(('?', rest)) => !value.is_empty() && glob_matches_from(rest, &value[1..]),
+        Some((c, rest)) => value.first() == Some(c) && glob_matches_from(rest, &value[1..]),
+    }
+}
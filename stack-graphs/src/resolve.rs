@@ -0,0 +1,618 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines name resolution queries against a database, and a cache for their results.
+//!
+//! The [`find_definitions`][] query resolves the references at a position in a file to their
+//! definitions.  With the `tokio` feature, an [`AsyncResolver`][] runs it from async code,
+//! optionally using a cache.
+//!
+//! Resolving a reference requires loading graphs and partial paths from the database and
+//! stitching them together, which is expensive compared to the rate at which editors and agents
+//! repeat the same queries.  A [`QueryCache`][] memoizes the results of definition and reference
+//! queries, keyed by the position that was queried.
+//!
+//! Every cached result records the files that were involved in computing it, together with the
+//! tags those files were stored with.  These are the file of the query, the files that the caller
+//! reports as involved, such as the files of the definitions that were found, and the files that
+//! the database records as [dependencies][SQLiteReader::dependencies_of_file] of the file of the
+//! query.  The files involved do not cover files that are added to the database later, which can
+//! add definitions or references that the result is missing.  Every cached result therefore also
+//! records the [generation][SQLiteReader::generation] of the database, which increases whenever
+//! any file is stored in or removed from the database.  A cached result is only returned if the
+//! database is still at the same generation, and none of the files involved have a different tag
+//! than when the result was cached.  Otherwise it is discarded, and the query must be computed
+//! again.
+//!
+//! A cache can be shared between threads, so that a single cache can serve all requests of a
+//! server.  The cache does not own a database connection; each lookup is checked against the
+//! database reader that the caller uses to compute the query.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use lsp_positions::Span;
+
+use crate::arena::Handle;
+use crate::graph::DefinitionKind;
+use crate::graph::File;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::stitching::ForwardPartialPathStitcher;
+use crate::stitching::Instrumentation;
+use crate::stitching::NoResolutionExtension;
+use crate::stitching::StitcherConfig;
+use crate::storage::FileStatus;
+use crate::storage::SQLiteReader;
+#[cfg(feature = "tokio")]
+use crate::storage::SQLiteReaderAsync;
+use crate::storage::StorageError;
+use crate::CancellationFlag;
+
+/// The kind of a cached query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QueryKind {
+    /// The definitions that a reference resolves to.
+    Definitions,
+    /// The references that resolve to a definition.
+    References,
+}
+
+/// The position that a query was made at.  Lines and columns are 0-indexed, and are counted in
+/// whatever unit the caller uses consistently.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QueryPosition {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl QueryPosition {
+    pub fn new<F: Into<String>>(file: F, line: usize, column: usize) -> QueryPosition {
+        QueryPosition {
+            file: file.into(),
+            line,
+            column,
+        }
+    }
+}
+
+/// Statistics about the lookups in a [`QueryCache`][].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryCacheStats {
+    /// The number of lookups that returned a cached result.
+    pub hits: usize,
+    /// The number of lookups that did not return a cached result, including those whose result
+    /// was invalidated.
+    pub misses: usize,
+    /// The number of cached results that were discarded because a file they depend on changed,
+    /// or that were explicitly invalidated.
+    pub invalidations: usize,
+    /// The number of cached results that were discarded to make room for new ones.
+    pub evictions: usize,
+}
+
+type QueryKey = (QueryKind, QueryPosition);
+
+struct CacheEntry<R> {
+    /// The files that the result depends on, with the tags they had when the result was cached,
+    /// or `None` if they were not in the database.
+    dependencies: Vec<(String, Option<String>)>,
+    /// The generation of the database when the result was cached.
+    generation: u64,
+    result: R,
+    sequence: u64,
+}
+
+struct CacheState<R> {
+    entries: HashMap<QueryKey, CacheEntry<R>>,
+    /// The keys of the entries, in the order in which they were inserted.
+    insertion_order: BTreeMap<u64, QueryKey>,
+    next_sequence: u64,
+    stats: QueryCacheStats,
+}
+
+impl<R> CacheState<R> {
+    fn remove(&mut self, key: &QueryKey) -> Option<CacheEntry<R>> {
+        let entry = self.entries.remove(key)?;
+        self.insertion_order.remove(&entry.sequence);
+        Some(entry)
+    }
+}
+
+/// A cache of query results that is invalidated when any of the files involved in a query
+/// change.  See the [module documentation][self] for details.
+pub struct QueryCache<R> {
+    state: Mutex<CacheState<R>>,
+    max_entries: Option<usize>,
+}
+
+impl<R: Clone> QueryCache<R> {
+    /// Creates a new, empty cache, without a limit on the number of cached results.
+    pub fn new() -> QueryCache<R> {
+        QueryCache {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                insertion_order: BTreeMap::new(),
+                next_sequence: 0,
+                stats: QueryCacheStats::default(),
+            }),
+            max_entries: None,
+        }
+    }
+
+    /// Creates a new, empty cache that keeps at most the given number of results.  When the cache
+    /// is full, the oldest result is discarded to make room for a new one.
+    pub fn with_capacity(max_entries: usize) -> QueryCache<R> {
+        QueryCache {
+            max_entries: Some(max_entries),
+            ..QueryCache::new()
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState<R>> {
+        // The state is consistent after every operation, so it is still usable if a thread
+        // panicked while holding the lock.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the cached result of a query, if there is one, and the given database has not
+    /// changed since it was cached.  A result that is out of date is discarded.
+    pub fn get(
+        &self,
+        db: &mut SQLiteReader,
+        kind: QueryKind,
+        position: &QueryPosition,
+    ) -> Result<Option<R>, StorageError> {
+        let key = (kind, position.clone());
+        // Do not hold the lock while querying the database, so that other threads can use the
+        // cache in the meantime.
+        let (dependencies, generation, sequence) = {
+            let mut state = self.state();
+            match state.entries.get(&key) {
+                Some(entry) => (entry.dependencies.clone(), entry.generation, entry.sequence),
+                None => {
+                    state.stats.misses += 1;
+                    return Ok(None);
+                }
+            }
+        };
+        let mut is_fresh = db.generation()? == generation;
+        if is_fresh {
+            for (file, tag) in &dependencies {
+                if db.tag_for_file(file)? != *tag {
+                    is_fresh = false;
+                    break;
+                }
+            }
+        }
+
+        let mut state = self.state();
+        // The entry may have been replaced while the lock was released, in which case the new
+        // entry has not been checked.
+        if state.entries.get(&key).map(|entry| entry.sequence) != Some(sequence) {
+            state.stats.misses += 1;
+            return Ok(None);
+        }
+        if !is_fresh {
+            state.remove(&key);
+            state.stats.invalidations += 1;
+            state.stats.misses += 1;
+            return Ok(None);
+        }
+        state.stats.hits += 1;
+        Ok(state.entries.get(&key).map(|entry| entry.result.clone()))
+    }
+
+    /// Caches the result of a query.  The result depends on the file of the query, the given
+    /// files, and the dependencies of the file of the query that are recorded in the database,
+    /// and is invalidated when any of them change, or when any file is stored in or removed from
+    /// the database.  The current tags of these files and the generation of the database are read
+    /// from the given database, which must be the database that the result was computed from.
+    pub fn insert<I, F>(
+        &self,
+        db: &mut SQLiteReader,
+        kind: QueryKind,
+        position: QueryPosition,
+        files_involved: I,
+        result: R,
+    ) -> Result<(), StorageError>
+    where
+        I: IntoIterator<Item = F>,
+        F: AsRef<str>,
+    {
+        let mut files = files_involved
+            .into_iter()
+            .map(|file| file.as_ref().to_string())
+            .collect::<Vec<_>>();
+        files.push(position.file.clone());
+        for dependency in db.dependencies_of_file(Path::new(&position.file))? {
+            files.push(dependency.to_string_lossy().into_owned());
+        }
+        files.sort();
+        files.dedup();
+        let mut dependencies = Vec::with_capacity(files.len());
+        for file in files {
+            let tag = db.tag_for_file(&file)?;
+            dependencies.push((file, tag));
+        }
+        let generation = db.generation()?;
+
+        let key = (kind, position);
+        let mut state = self.state();
+        state.remove(&key);
+        if let Some(max_entries) = self.max_entries {
+            while state.entries.len() >= max_entries {
+                let oldest = match state.insertion_order.values().next() {
+                    Some(oldest) => oldest.clone(),
+                    None => break,
+                };
+                state.remove(&oldest);
+                state.stats.evictions += 1;
+            }
+            if max_entries == 0 {
+                return Ok(());
+            }
+        }
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.insertion_order.insert(sequence, key.clone());
+        state.entries.insert(
+            key,
+            CacheEntry {
+                dependencies,
+                generation,
+                result,
+                sequence,
+            },
+        );
+        Ok(())
+    }
+
+    /// Discards all cached results that depend on the given file, and returns how many were
+    /// discarded.  This is not necessary to keep the cache correct, but frees the memory of
+    /// results that are known to be out of date, such as when a server is notified of a change.
+    pub fn invalidate_file(&self, file: &str) -> usize {
+        let mut state = self.state();
+        let keys = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.dependencies.iter().any(|(f, _)| f == file))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in &keys {
+            state.remove(key);
+        }
+        state.stats.invalidations += keys.len();
+        keys.len()
+    }
+
+    /// Discards all cached results.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.entries.clear();
+        state.insertion_order.clear();
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    /// Returns whether there are no cached results.
+    pub fn is_empty(&self) -> bool {
+        self.state().entries.is_empty()
+    }
+
+    /// Returns statistics about the lookups in this cache.
+    pub fn stats(&self) -> QueryCacheStats {
+        self.state().stats
+    }
+}
+
+impl<R: Clone> Default for QueryCache<R> {
+    fn default() -> QueryCache<R> {
+        QueryCache::new()
+    }
+}
+
+/// A definition that a reference resolves to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedDefinition {
+    /// The file containing the definition.
+    pub file: String,
+    /// The symbol of the definition.
+    pub symbol: String,
+    /// The source span of the definition, if available.
+    pub span: Option<Span>,
+    /// The [kind][StackGraph::definition_kind] of the definition, if its node has a syntax type.
+    pub kind: Option<DefinitionKind>,
+    /// The [confidence][PartialPath::confidence] of the most confident path that found the
+    /// definition, or `None` if that path is certain.
+    pub confidence: Option<f32>,
+    /// Whether the file of the reference or of the definition contained [parse
+    /// errors][StackGraph::parse_errors] when it was indexed.  The graph of such a file may be
+    /// incomplete, so the definition may be wrong, and other definitions may be missing.
+    pub possibly_incomplete: bool,
+    /// Whether the definition was only found by
+    /// [speculative][crate::partial::PartialPath::is_speculative] paths, which visit nodes whose
+    /// edges the rules of the language marked as guesses.
+    pub speculative: bool,
+}
+
+/// Finds the definitions of the references at the given position.  The column of the position is
+/// a UTF-8 byte offset within its line.  Definitions are ordered by file and span, and each
+/// definition is reported once, even if several references at the position resolve to it.
+/// Definitions whose paths are shadowed under the [shadowing
+/// policy][StitcherConfig::shadowing_policy] of the configuration are not reported.
+/// References that were [pre-resolved][SQLiteReader::local_resolutions_for_node] when their file
+/// was indexed are resolved without loading any partial paths.
+pub fn find_definitions(
+    db: &mut SQLiteReader,
+    position: &QueryPosition,
+    config: StitcherConfig,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ResolvedDefinition>, StorageError> {
+    find_definitions_inner(db, position, config, None, cancellation_flag)
+}
+
+/// Finds the definitions of the references at the given position, like [`find_definitions`][],
+/// reporting the internals of stitching to the instrumentation.  References that were
+/// pre-resolved when their file was indexed do not involve stitching, and are not reported.
+pub fn find_definitions_with_instrumentation(
+    db: &mut SQLiteReader,
+    position: &QueryPosition,
+    config: StitcherConfig,
+    instrumentation: Arc<dyn Instrumentation>,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ResolvedDefinition>, StorageError> {
+    find_definitions_inner(
+        db,
+        position,
+        config,
+        Some(instrumentation),
+        cancellation_flag,
+    )
+}
+
+fn find_definitions_inner(
+    db: &mut SQLiteReader,
+    position: &QueryPosition,
+    config: StitcherConfig,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<ResolvedDefinition>, StorageError> {
+    if !matches!(
+        db.status_for_file(&position.file, None::<&str>)?,
+        FileStatus::Indexed
+    ) {
+        return Ok(Vec::new());
+    }
+    let file = db.load_graph_for_file(&position.file)?;
+    let references = {
+        let (graph, _, _) = db.get();
+        graph
+            .nodes_for_file(file)
+            .filter(|node| graph[*node].is_reference())
+            .filter(|node| {
+                graph.source_info(*node).map_or(false, |info| {
+                    let start = (info.span.start.line, info.span.start.column.utf8_offset);
+                    let end = (info.span.end.line, info.span.end.column.utf8_offset);
+                    let position = (position.line, position.column);
+                    start <= position && position < end
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // References that were resolved within their file when it was indexed do not need any
+    // partial paths.
+    let mut definitions = Vec::new();
+    let mut unresolved = Vec::new();
+    for reference in references {
+        match db.local_resolutions_for_node(reference)? {
+            Some(nodes) => {
+                let (graph, _, _) = db.get();
+                definitions.extend(
+                    nodes
+                        .into_iter()
+                        .filter_map(|node| resolved_definition(graph, file, node, false, None)),
+                );
+            }
+            None => unresolved.push(reference),
+        }
+    }
+
+    if !unresolved.is_empty() {
+        db.load_partial_paths_for_file(&position.file, cancellation_flag)?;
+        let mut paths_per_reference = HashMap::<Handle<Node>, Vec<PartialPath>>::new();
+        let visit = |_: &StackGraph, _: &mut PartialPaths, path: &PartialPath| {
+            paths_per_reference
+                .entry(path.start_node)
+                .or_default()
+                .push(path.clone())
+        };
+        match instrumentation {
+            Some(instrumentation) => {
+                ForwardPartialPathStitcher::find_all_complete_partial_paths_with_instrumentation(
+                    db,
+                    unresolved,
+                    config,
+                    &mut NoResolutionExtension,
+                    instrumentation,
+                    cancellation_flag,
+                    visit,
+                )?
+            }
+            None => ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                db,
+                unresolved,
+                config,
+                cancellation_flag,
+                visit,
+            )?,
+        };
+
+        // Only the paths that are not shadowed under the configured policy are reported as the
+        // definitions of a reference.
+        let (graph, partials, _) = db.get();
+        for (_, mut paths) in paths_per_reference {
+            config
+                .shadowing_policy()
+                .remove_shadowed_paths(graph, partials, &mut paths);
+            for path in paths {
+                // The confidence of paths that visit nodes that are not loaded is unknown.
+                let confidence = match path.confidence(graph, partials) {
+                    Ok(confidence) => confidence,
+                    Err(_) => continue,
+                };
+                let speculative = path.is_speculative(graph, partials);
+                definitions.extend(resolved_definition(
+                    graph,
+                    file,
+                    path.end_node,
+                    speculative,
+                    confidence,
+                ));
+            }
+        }
+    }
+    definitions.sort_by(|a, b| (&a.file, &a.span, &a.symbol).cmp(&(&b.file, &b.span, &b.symbol)));
+    // A definition is only speculative if all paths that found it are, and it is as confident as
+    // the most confident path that found it.
+    definitions.dedup_by(|next, kept| {
+        let duplicate =
+            (&next.file, &next.span, &next.symbol) == (&kept.file, &kept.span, &kept.symbol);
+        if duplicate {
+            kept.speculative &= next.speculative;
+            kept.confidence = match (kept.confidence, next.confidence) {
+                (Some(kept), Some(next)) => Some(kept.max(next)),
+                _ => None,
+            };
+        }
+        duplicate
+    });
+    Ok(definitions)
+}
+
+fn resolved_definition(
+    graph: &StackGraph,
+    reference_file: Handle<File>,
+    node: Handle<Node>,
+    speculative: bool,
+    confidence: Option<f32>,
+) -> Option<ResolvedDefinition> {
+    let file = graph[node].file()?;
+    let symbol = graph[node].symbol()?;
+    Some(ResolvedDefinition {
+        file: graph[file].name().to_string(),
+        symbol: graph[symbol].to_string(),
+        span: graph.source_info(node).map(|info| info.span.clone()),
+        kind: graph.definition_kind(node),
+        confidence,
+        possibly_incomplete: !graph.parse_errors(reference_file).is_empty()
+            || !graph.parse_errors(file).is_empty(),
+        speculative,
+    })
+}
+
+/// Runs resolution queries from async code.  Queries run on the blocking thread pool of a
+/// [`SQLiteReaderAsync`][], and stop when their future is dropped.  If the resolver has a
+/// [`QueryCache`][], results are looked up in and added to it.  The resolver can be cloned
+/// cheaply, and all clones share the same reader and cache.
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub struct AsyncResolver {
+    reader: SQLiteReaderAsync,
+    cache: Option<Arc<QueryCache<Vec<ResolvedDefinition>>>>,
+    config: StitcherConfig,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncResolver {
+    /// Creates a new resolver that runs queries with the given reader, without a cache.
+    pub fn new(reader: SQLiteReaderAsync) -> AsyncResolver {
+        AsyncResolver {
+            reader,
+            cache: None,
+            config: StitcherConfig::default(),
+            instrumentation: None,
+        }
+    }
+
+    /// Sets the cache that query results are looked up in and added to.
+    pub fn with_cache(mut self, cache: Arc<QueryCache<Vec<ResolvedDefinition>>>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Sets the configuration of the stitcher that resolves references.
+    pub fn with_config(mut self, config: StitcherConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the instrumentation that observes the stitching of queries.  Queries that are answered
+    /// from the cache are not observed.
+    pub fn with_instrumentation(mut self, instrumentation: Arc<dyn Instrumentation>) -> Self {
+        self.instrumentation = Some(instrumentation);
+        self
+    }
+
+    /// Returns the reader that queries are run with.
+    pub fn reader(&self) -> &SQLiteReaderAsync {
+        &self.reader
+    }
+
+    /// Finds the definitions of the references at the given position.  See
+    /// [`find_definitions`][] for details.
+    pub async fn find_definitions(
+        &self,
+        position: QueryPosition,
+    ) -> Result<Vec<ResolvedDefinition>, StorageError> {
+        let cache = self.cache.clone();
+        let config = self.config;
+        let instrumentation = self.instrumentation.clone();
+        self.reader
+            .run(move |db, cancellation_flag| {
+                if let Some(cache) = &cache {
+                    if let Some(definitions) = cache.get(db, QueryKind::Definitions, &position)? {
+                        return Ok(definitions);
+                    }
+                }
+                let definitions = find_definitions_inner(
+                    db,
+                    &position,
+                    config,
+                    instrumentation,
+                    cancellation_flag,
+                )?;
+                if let Some(cache) = &cache {
+                    let files = definitions
+                        .iter()
+                        .map(|definition| definition.file.clone())
+                        .collect::<Vec<_>>();
+                    cache.insert(
+                        db,
+                        QueryKind::Definitions,
+                        position,
+                        files,
+                        definitions.clone(),
+                    )?;
+                }
+                Ok(definitions)
+            })
+            .await
+    }
+}
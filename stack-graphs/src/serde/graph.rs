@@ -8,6 +8,8 @@
 use thiserror::Error;
 
 use crate::arena::Handle;
+use crate::graph::Fingerprint;
+use crate::graph::Precedence;
 
 use super::Filter;
 use super::ImplicationFilter;
@@ -16,10 +18,40 @@ use super::NoFilter;
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct StackGraph {
     pub files: Files,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "FileLanguages::is_empty")
+    )]
+    pub file_languages: FileLanguages,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "RootedFiles::is_empty")
+    )]
+    pub rooted_files: RootedFiles,
     pub nodes: Nodes,
     pub edges: Edges,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Aliases::is_empty")
+    )]
+    pub aliases: Aliases,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "SpeculativeNodes::is_empty")
+    )]
+    pub speculative_nodes: SpeculativeNodes,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "NodeHandles::is_empty")
+    )]
+    pub node_handles: NodeHandles,
 }
 
 #[derive(Debug, Error, Eq, PartialEq)]
@@ -34,6 +66,12 @@ pub enum Error {
     InvalidStackVariable(u32),
     #[error("failed to locate node `{0}` in graph")]
     NodeNotFound(NodeID),
+    #[error("loaded file `{file}` has fingerprint {actual}, expected {expected}")]
+    FingerprintMismatch {
+        file: String,
+        expected: Fingerprint,
+        actual: Fingerprint,
+    },
 }
 
 impl StackGraph {
@@ -44,12 +82,77 @@ impl StackGraph {
     pub fn from_graph_filter<'a>(graph: &crate::graph::StackGraph, filter: &'a dyn Filter) -> Self {
         let filter = ImplicationFilter(filter);
         let files = graph.filter_files(&filter);
+        let file_languages = graph.filter_file_languages(&filter);
+        let rooted_files = graph.filter_rooted_files(&filter);
         let nodes = graph.filter_nodes(&filter);
         let edges = graph.filter_edges(&filter);
+        let aliases = graph.filter_aliases(&filter);
+        let speculative_nodes = graph.filter_speculative_nodes(&filter);
         Self {
             files,
+            file_languages,
+            rooted_files,
             nodes,
             edges,
+            aliases,
+            speculative_nodes,
+            node_handles: NodeHandles::default(),
+        }
+    }
+
+    /// Returns this serialized graph with a [table][NodeHandles] that maps the handles of its
+    /// nodes in the given graph, which must be the graph it was serialized from, to their stable
+    /// IDs.
+    pub fn with_node_handles(mut self, graph: &crate::graph::StackGraph) -> Self {
+        let mut data = self
+            .nodes
+            .data
+            .iter()
+            .filter_map(|node| {
+                let id = node.id();
+                let handle = graph.node_for_id(id.to_node_id(graph).ok()?)?;
+                Some(NodeHandle {
+                    handle: handle.as_u32(),
+                    id: id.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        data.sort_by_key(|entry| entry.handle);
+        self.node_handles = NodeHandles { data };
+        self
+    }
+
+    /// Renames a file of this serialized graph, and moves all of its nodes to the new file name.
+    /// Local IDs are kept, so the nodes keep their identity relative to the file.  The renamed
+    /// file no longer belongs to a root.
+    pub fn rename_file(&mut self, old: &str, new: &str) {
+        for file in &mut self.files.data {
+            if file == old {
+                *file = new.to_string();
+            }
+        }
+        for language in &mut self.file_languages.data {
+            if language.file == old {
+                language.file = new.to_string();
+            }
+        }
+        self.rooted_files.data.retain(|rooted| rooted.file != old);
+        for node in &mut self.nodes.data {
+            node.rename_file(old, new);
+        }
+        for edge in &mut self.edges.data {
+            edge.source.rename_file(old, new);
+            edge.sink.rename_file(old, new);
+        }
+        for alias in &mut self.aliases.data {
+            alias.node.rename_file(old, new);
+            alias.alias.rename_file(old, new);
+        }
+        for id in &mut self.speculative_nodes.data {
+            id.rename_file(old, new);
+        }
+        for entry in &mut self.node_handles.data {
+            entry.id.rename_file(old, new);
         }
     }
 
@@ -57,15 +160,91 @@ impl StackGraph {
         self.load_files(graph)?;
         self.load_nodes(graph)?;
         self.load_edges(graph)?;
+        self.load_aliases(graph)?;
+        self.load_speculative_nodes(graph)?;
+        Ok(())
+    }
+
+    /// Loads the nodes of this graph into the given graph, which must already contain their
+    /// files.  Together with [`load_edges_into`][Self::load_edges_into], this allows loading a
+    /// graph that was serialized in parts whose edges refer to nodes of other parts: the nodes of
+    /// all parts are loaded first, followed by their edges.
+    pub fn load_nodes_into(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        self.load_nodes(graph)?;
+        self.load_speculative_nodes(graph)?;
+        Ok(())
+    }
+
+    /// Loads the edges and aliases of this graph into the given graph, which must already contain
+    /// their nodes.  See [`load_nodes_into`][Self::load_nodes_into].
+    pub fn load_edges_into(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        self.load_edges(graph)?;
+        self.load_aliases(graph)?;
         Ok(())
     }
 
+    /// Loads this graph into the given graph, like [`load_into`][Self::load_into], and verifies
+    /// that the [fingerprint][crate::graph::StackGraph::fingerprint_for_file] of the given file
+    /// in the resulting graph is the expected one.  This can be used to detect corrupted or
+    /// outdated cached graphs.
+    pub fn load_into_verified(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        file: &str,
+        expected: Fingerprint,
+    ) -> Result<(), Error> {
+        self.load_into(graph)?;
+        let handle = graph
+            .get_file(file)
+            .ok_or_else(|| Error::FileNotFound(file.to_string()))?;
+        let actual = graph.fingerprint_for_file(handle);
+        if actual != expected {
+            return Err(Error::FingerprintMismatch {
+                file: file.to_string(),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds the given file of this graph to the given graph, or returns the existing file with
+    /// that name.  Files that belong to a root are added to that root.
+    pub(crate) fn get_or_create_file(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        file: &str,
+    ) -> Handle<crate::graph::File> {
+        self.add_file(graph, file).unwrap_or_else(|handle| handle)
+    }
+
+    fn add_file(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        file: &str,
+    ) -> Result<Handle<crate::graph::File>, Handle<crate::graph::File>> {
+        match self
+            .rooted_files
+            .data
+            .iter()
+            .find(|rooted| rooted.file == file)
+        {
+            Some(rooted) => graph.add_file_in_root(&rooted.root, &rooted.relative_path),
+            None => graph.add_file(file),
+        }
+    }
+
     fn load_files(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
         for file in self.files.data.iter() {
-            graph
-                .add_file(&file)
+            self.add_file(graph, file)
                 .map_err(|_| Error::FileAlreadyPresent(file.to_owned()))?;
         }
+        for FileLanguage { file, language } in &self.file_languages.data {
+            let handle = graph
+                .get_file(file)
+                .ok_or_else(|| Error::FileNotFound(file.to_owned()))?;
+            graph.set_file_language(handle, language);
+        }
 
         Ok(())
     }
@@ -143,6 +322,7 @@ impl StackGraph {
                             .as_ref()
                             .map(|st| graph.add_string(&st))
                             .into(),
+                        definiens_span: source_info.definiens_span.clone().unwrap_or_default(),
                         ..Default::default()
                     };
                 }
@@ -170,6 +350,8 @@ impl StackGraph {
             source,
             sink,
             precedence,
+            condition,
+            confidence,
             debug_info,
         } in &self.edges.data
         {
@@ -183,7 +365,16 @@ impl StackGraph {
                 .node_for_id(sink_id)
                 .ok_or(Error::InvalidGlobalNodeID(sink.local_id))?;
 
-            graph.add_edge(source_handle, sink_handle, *precedence);
+            graph.add_edge(source_handle, sink_handle, Precedence::new(*precedence));
+
+            if let Some(condition) = condition {
+                let condition = graph.add_string(condition);
+                graph.set_edge_condition(source_handle, sink_handle, condition);
+            }
+
+            if let Some(confidence) = confidence {
+                graph.set_edge_confidence(source_handle, sink_handle, *confidence);
+            }
 
             // load debug-info of each node
             if let Some(debug_info) = debug_info {
@@ -200,6 +391,34 @@ impl StackGraph {
         }
         Ok(())
     }
+
+    fn load_aliases(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        for Alias { node, alias } in &self.aliases.data {
+            let node_id = node.to_node_id(graph)?;
+            let alias_id = alias.to_node_id(graph)?;
+
+            let node_handle = graph
+                .node_for_id(node_id)
+                .ok_or(Error::InvalidGlobalNodeID(node.local_id))?;
+            let alias_handle = graph
+                .node_for_id(alias_id)
+                .ok_or(Error::InvalidGlobalNodeID(alias.local_id))?;
+
+            graph.add_node_alias(node_handle, alias_handle);
+        }
+        Ok(())
+    }
+
+    fn load_speculative_nodes(&self, graph: &mut crate::graph::StackGraph) -> Result<(), Error> {
+        for node in &self.speculative_nodes.data {
+            let node_id = node.to_node_id(graph)?;
+            let node_handle = graph
+                .node_for_id(node_id)
+                .ok_or(Error::InvalidGlobalNodeID(node.local_id))?;
+            graph.mark_speculative(node_handle);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -209,10 +428,17 @@ impl StackGraph {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Files {
     pub data: Vec<String>,
 }
 
+/// The [languages][crate::graph::StackGraph::set_file_language] of the files that are tagged with
+/// one.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[cfg_attr(
     feature = "serde",
@@ -220,6 +446,83 @@ pub struct Files {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct FileLanguages {
+    pub data: Vec<FileLanguage>,
+}
+
+impl FileLanguages {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct FileLanguage {
+    pub file: String,
+    pub language: String,
+}
+
+/// The [roots][crate::graph::StackGraph::add_file_in_root] of the files that belong to one.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct RootedFiles {
+    pub data: Vec<RootedFile>,
+}
+
+impl RootedFiles {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct RootedFile {
+    pub file: String,
+    pub root: String,
+    pub relative_path: String,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Nodes {
     pub data: Vec<Node>,
 }
@@ -232,6 +535,11 @@ pub struct Nodes {
     serde(tag = "type", rename_all = "snake_case"),
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub enum Node {
     DropScopes {
         id: NodeID,
@@ -293,6 +601,19 @@ pub enum Node {
 }
 
 impl Node {
+    pub fn id(&self) -> &NodeID {
+        match self {
+            Self::DropScopes { id, .. } => id,
+            Self::JumpToScope { id, .. } => id,
+            Self::PopScopedSymbol { id, .. } => id,
+            Self::PopSymbol { id, .. } => id,
+            Self::PushScopedSymbol { id, .. } => id,
+            Self::PushSymbol { id, .. } => id,
+            Self::Root { id, .. } => id,
+            Self::Scope { id, .. } => id,
+        }
+    }
+
     fn source_info(&self) -> Option<&SourceInfo> {
         match self {
             Self::DropScopes { source_info, .. } => source_info,
@@ -320,6 +641,22 @@ impl Node {
         }
         .as_ref()
     }
+
+    fn rename_file(&mut self, old: &str, new: &str) {
+        match self {
+            Self::PushScopedSymbol { id, scope, .. } => {
+                id.rename_file(old, new);
+                scope.rename_file(old, new);
+            }
+            Self::DropScopes { id, .. }
+            | Self::JumpToScope { id, .. }
+            | Self::PopScopedSymbol { id, .. }
+            | Self::PopSymbol { id, .. }
+            | Self::PushSymbol { id, .. }
+            | Self::Root { id, .. }
+            | Self::Scope { id, .. } => id.rename_file(old, new),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -329,9 +666,24 @@ impl Node {
     derive(serde::Deserialize, serde::Serialize),
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct SourceInfo {
     pub span: lsp_positions::Span,
     pub syntax_type: Option<String>,
+    pub definiens_span: Option<lsp_positions::Span>,
+}
+
+impl SourceInfo {
+    /// Returns the kind of entity that the node represents, based on its syntax type.
+    pub fn definition_kind(&self) -> Option<crate::graph::DefinitionKind> {
+        self.syntax_type
+            .as_deref()
+            .map(crate::graph::DefinitionKind::from_syntax_type)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -341,13 +693,39 @@ pub struct SourceInfo {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct DebugInfo {
     pub data: Vec<DebugEntry>,
 }
 
+impl DebugInfo {
+    /// Returns the value of the first debug entry with the given key, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.data
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Returns the location of the TSG rule that created the node or edge, if it was recorded.
+    /// See [`crate::graph::DebugInfo::TSG_RULE`][] for the format.
+    pub fn rule(&self) -> Option<&str> {
+        self.get(crate::graph::DebugInfo::TSG_RULE)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct DebugEntry {
     pub key: String,
     pub value: String,
@@ -360,6 +738,11 @@ pub struct DebugEntry {
     derive(serde::Deserialize, serde::Serialize),
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct NodeID {
     pub file: Option<String>,
     pub local_id: u32,
@@ -404,6 +787,13 @@ impl NodeID {
             .node_for_id(value)
             .ok_or_else(|| Error::NodeNotFound(self.clone()))?)
     }
+
+    /// Moves this ID to the file `new` if it belongs to the file `old`.
+    pub(crate) fn rename_file(&mut self, old: &str, new: &str) {
+        if self.file.as_deref() == Some(old) {
+            self.file = Some(new.to_string());
+        }
+    }
 }
 
 impl std::fmt::Display for NodeID {
@@ -422,6 +812,11 @@ impl std::fmt::Display for NodeID {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Edges {
     pub data: Vec<Edge>,
 }
@@ -433,13 +828,135 @@ pub struct Edges {
     derive(serde::Deserialize, serde::Serialize),
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
 pub struct Edge {
     pub source: NodeID,
     pub sink: NodeID,
     pub precedence: i32,
+    /// The [condition][crate::graph::StackGraph::set_edge_condition] of an edge to or from the
+    /// root node.
+    pub condition: Option<String>,
+    /// The [confidence][crate::graph::StackGraph::set_edge_confidence] of an edge, if it is not
+    /// certain.
+    pub confidence: Option<u8>,
     pub debug_info: Option<DebugInfo>,
 }
 
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct Aliases {
+    pub data: Vec<Alias>,
+}
+
+impl Aliases {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// A declaration that two nodes are equivalent.  See
+/// [`StackGraph::add_node_alias`][crate::graph::StackGraph::add_node_alias].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct Alias {
+    pub node: NodeID,
+    pub alias: NodeID,
+}
+
+/// The nodes that are [speculative][crate::graph::StackGraph::mark_speculative].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct SpeculativeNodes {
+    pub data: Vec<NodeID>,
+}
+
+impl SpeculativeNodes {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// A table that maps the handles that nodes had in the graph they were serialized from to their
+/// stable IDs.  Handles are only valid for a single graph, and nodes get different handles when a
+/// serialized graph is loaded, or when graphs are merged.  Systems that stored handles can use
+/// this table to translate them to stable IDs, which can be resolved in any graph that contains
+/// the node with [`StackGraph::node_handle_for_stable_id`][crate::graph::StackGraph::node_handle_for_stable_id].
+/// The table is only included by [`StackGraph::with_node_handles`][].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct NodeHandles {
+    pub data: Vec<NodeHandle>,
+}
+
+impl NodeHandles {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the stable ID of the node that had the given handle.
+    pub fn stable_id(&self, handle: u32) -> Option<&NodeID> {
+        let index = self
+            .data
+            .binary_search_by_key(&handle, |entry| entry.handle)
+            .ok()?;
+        Some(&self.data[index].id)
+    }
+}
+
+/// The handle that a node had in the graph it was serialized from, and its stable ID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize),
+    archive(check_bytes)
+)]
+pub struct NodeHandle {
+    pub handle: u32,
+    pub id: NodeID,
+}
+
 impl crate::graph::StackGraph {
     pub fn to_serializable(&self) -> StackGraph {
         self.to_serializable_filter(&NoFilter)
@@ -459,6 +976,37 @@ impl crate::graph::StackGraph {
         }
     }
 
+    fn filter_file_languages<'a>(&self, filter: &'a dyn Filter) -> FileLanguages {
+        FileLanguages {
+            data: self
+                .iter_files()
+                .filter(|f| filter.include_file(self, f))
+                .filter_map(|f| {
+                    Some(FileLanguage {
+                        file: self[f].name().to_owned(),
+                        language: self.file_language(f)?.to_owned(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }
+    }
+
+    fn filter_rooted_files<'a>(&self, filter: &'a dyn Filter) -> RootedFiles {
+        RootedFiles {
+            data: self
+                .iter_files()
+                .filter(|f| filter.include_file(self, f))
+                .filter_map(|f| {
+                    Some(RootedFile {
+                        file: self[f].name().to_owned(),
+                        root: self[f].root()?.to_owned(),
+                        relative_path: self[f].relative_path().to_owned(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }
+    }
+
     fn filter_node<'a>(&self, _filter: &'a dyn Filter, id: crate::graph::NodeID) -> NodeID {
         let file = id.file().map(|idx| self[idx].name().to_owned());
         let local_id = id.local_id();
@@ -473,6 +1021,11 @@ impl crate::graph::StackGraph {
         self.source_info(handle).map(|info| SourceInfo {
             span: info.span.clone(),
             syntax_type: info.syntax_type.into_option().map(|ty| self[ty].to_owned()),
+            definiens_span: if info.definiens_span == lsp_positions::Span::default() {
+                None
+            } else {
+                Some(info.definiens_span.clone())
+            },
         })
     }
 
@@ -570,7 +1123,13 @@ impl crate::graph::StackGraph {
                         .map(|e| Edge {
                             source: self.filter_node(filter, self[e.source].id()),
                             sink: self.filter_node(filter, self[e.sink].id()),
-                            precedence: e.precedence,
+                            precedence: e.precedence.value(),
+                            condition: self
+                                .edge_condition(e.source, e.sink)
+                                .map(|condition| self[condition].to_owned()),
+                            confidence: self
+                                .edge_confidence(e.source, e.sink)
+                                .filter(|c| *c < crate::graph::MAX_EDGE_CONFIDENCE),
                             debug_info: self.filter_edge_debug_info(filter, e.source, e.sink),
                         })
                 })
@@ -596,4 +1155,33 @@ impl crate::graph::StackGraph {
                     .collect(),
             })
     }
+
+    fn filter_aliases<'a>(&self, filter: &'a dyn Filter) -> Aliases {
+        Aliases {
+            data: self
+                .iter_nodes()
+                .flat_map(|node| {
+                    self.node_aliases(node)
+                        .iter()
+                        .filter(move |alias| node < **alias)
+                        .filter(move |alias| filter.include_edge(self, &node, *alias))
+                        .map(move |alias| Alias {
+                            node: self.filter_node(filter, self[node].id()),
+                            alias: self.filter_node(filter, self[*alias].id()),
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    fn filter_speculative_nodes<'a>(&self, filter: &'a dyn Filter) -> SpeculativeNodes {
+        SpeculativeNodes {
+            data: self
+                .iter_nodes()
+                .filter(|node| self.is_speculative(*node))
+                .filter(|node| filter.include_node(self, node))
+                .map(|node| self.filter_node(filter, self[node].id()))
+                .collect(),
+        }
+    }
 }
@@ -35,12 +35,16 @@
 //! The [`partial`][] module provides:
 //! - [`PartialPaths`][partial::PartialPaths]: Serializable partial path database
 //! - [`PartialPath`][partial::PartialPath]: Individual partial paths
+//! - [`StandalonePartialPath`][partial::StandalonePartialPath]: Individual partial paths together
+//!   with the nodes and edges they refer to, which can be loaded without the original graph
 //!
 //! ### Stitching Data Serialization
 //!
 //! The [`stitching`][] module provides:
 //! - [`Database`][stitching::Database]: Serializable path database
 //! - Pre-computed partial paths for efficient querying
+//! - A [human-readable textual format][crate::stitching::Database::to_text] for reviewing path
+//!   databases, and using them as test expectations
 //!
 //! ### Filtering
 //!
@@ -144,8 +148,10 @@ mod filter;
 mod graph;
 mod partial;
 mod stitching;
+mod text;
 
 pub use filter::*;
 pub use graph::*;
 pub use partial::*;
 pub use stitching::*;
+pub use text::*;
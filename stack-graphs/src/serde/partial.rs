@@ -5,9 +5,17 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use crate::arena::Handle;
+use crate::graph::File;
+use crate::graph::Node;
 use crate::partial::PartialPaths;
 
 use super::Error;
+use super::Filter;
 use super::NodeID;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -79,6 +87,160 @@ impl PartialPath {
             edges: self.edges.to_partial_path_edge_list(graph, partials)?,
         })
     }
+
+    /// Moves all nodes of this path that belong to the file `old` to the file `new`.
+    pub fn rename_file(&mut self, old: &str, new: &str) {
+        self.start_node.rename_file(old, new);
+        self.end_node.rename_file(old, new);
+        self.symbol_stack_precondition.rename_file(old, new);
+        self.symbol_stack_postcondition.rename_file(old, new);
+        self.scope_stack_precondition.rename_file(old, new);
+        self.scope_stack_postcondition.rename_file(old, new);
+        for edge in &mut self.edges.edges {
+            edge.source.rename_file(old, new);
+        }
+    }
+}
+
+impl crate::partial::PartialPath {
+    pub fn to_serializable(
+        &self,
+        graph: &crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> PartialPath {
+        PartialPath::from_partial_path(graph, partials, self)
+    }
+
+    pub fn to_standalone(
+        &self,
+        graph: &crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> StandalonePartialPath {
+        StandalonePartialPath::from_partial_path(graph, partials, self)
+    }
+}
+
+/// A partial path together with the part of the stack graph that it refers to.  A [`PartialPath`][]
+/// only refers to the nodes it visits, and can only be loaded into a graph that contains them.  A
+/// stand-alone path also contains those nodes, the nodes in its scope stacks, and the edges it
+/// follows, so that it can be loaded into an empty graph.  This can be used to log or transmit
+/// individual paths, or to keep them as expectations in tests, without the graph or database they
+/// were found in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+pub struct StandalonePartialPath {
+    pub graph: super::StackGraph,
+    pub path: PartialPath,
+}
+
+impl StandalonePartialPath {
+    pub fn from_partial_path(
+        graph: &crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+        value: &crate::partial::PartialPath,
+    ) -> Self {
+        let filter = PathFilter::new(graph, partials, value);
+        Self {
+            graph: super::StackGraph::from_graph_filter(graph, &filter),
+            path: PartialPath::from_partial_path(graph, partials, value),
+        }
+    }
+
+    /// Loads the nodes and edges of this path into the given graph, and returns the path.  An
+    /// error is returned if the graph already contains any of the files of the path.
+    pub fn load_into(
+        &self,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::partial::PartialPath, Error> {
+        self.graph.load_into(graph)?;
+        self.path.to_partial_path(graph, partials)
+    }
+}
+
+/// A filter that includes the nodes that a partial path refers to, and the edges it follows.
+struct PathFilter {
+    files: HashSet<Handle<File>>,
+    nodes: HashSet<Handle<Node>>,
+    edges: HashSet<(Handle<Node>, Handle<Node>)>,
+}
+
+impl PathFilter {
+    fn new(
+        graph: &crate::graph::StackGraph,
+        partials: &PartialPaths,
+        path: &crate::partial::PartialPath,
+    ) -> Self {
+        let visited = path
+            .edges
+            .iter_unordered(partials)
+            .map(|edge| {
+                graph
+                    .node_for_id(edge.source_node_id)
+                    .expect("edge source node not in graph")
+            })
+            .chain(std::iter::once(path.end_node))
+            .collect::<Vec<_>>();
+        let edges = visited.iter().copied().tuple_windows().collect();
+
+        let mut nodes = visited.into_iter().collect::<HashSet<_>>();
+        nodes.insert(path.start_node);
+        for stack in [
+            &path.scope_stack_precondition,
+            &path.scope_stack_postcondition,
+        ] {
+            nodes.extend(stack.iter_unordered(partials));
+        }
+        for stack in [
+            &path.symbol_stack_precondition,
+            &path.symbol_stack_postcondition,
+        ] {
+            for symbol in stack.iter_unordered(partials) {
+                if let Some(scopes) = symbol.scopes.into_option() {
+                    nodes.extend(scopes.iter_unordered(partials));
+                }
+            }
+        }
+
+        let files = nodes
+            .iter()
+            .filter_map(|node| graph[*node].id().file())
+            .collect();
+        Self {
+            files,
+            nodes,
+            edges,
+        }
+    }
+}
+
+impl Filter for PathFilter {
+    fn include_file(&self, _graph: &crate::graph::StackGraph, file: &Handle<File>) -> bool {
+        self.files.contains(file)
+    }
+
+    fn include_node(&self, _graph: &crate::graph::StackGraph, node: &Handle<Node>) -> bool {
+        self.nodes.contains(node)
+    }
+
+    fn include_edge(
+        &self,
+        _graph: &crate::graph::StackGraph,
+        source: &Handle<Node>,
+        sink: &Handle<Node>,
+    ) -> bool {
+        self.edges.contains(&(*source, *sink))
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &crate::graph::StackGraph,
+        _paths: &PartialPaths,
+        _path: &crate::partial::PartialPath,
+    ) -> bool {
+        true
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -90,7 +252,7 @@ impl PartialPath {
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialScopeStack {
     pub(crate) scopes: Vec<NodeID>,
-    variable: Option<ScopeStackVariable>,
+    pub(crate) variable: Option<ScopeStackVariable>,
 }
 
 impl PartialScopeStack {
@@ -129,6 +291,12 @@ impl PartialScopeStack {
         }
         Ok(value)
     }
+
+    fn rename_file(&mut self, old: &str, new: &str) {
+        for scope in &mut self.scopes {
+            scope.rename_file(old, new);
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -138,7 +306,7 @@ impl PartialScopeStack {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-pub struct ScopeStackVariable(u32);
+pub struct ScopeStackVariable(pub(crate) u32);
 
 impl ScopeStackVariable {
     pub fn from_scope_stack_variable(value: crate::partial::ScopeStackVariable) -> Self {
@@ -160,7 +328,7 @@ impl ScopeStackVariable {
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialSymbolStack {
     pub(crate) symbols: Vec<PartialScopedSymbol>,
-    variable: Option<SymbolStackVariable>,
+    pub(crate) variable: Option<SymbolStackVariable>,
 }
 
 impl PartialSymbolStack {
@@ -201,6 +369,14 @@ impl PartialSymbolStack {
         }
         Ok(value)
     }
+
+    fn rename_file(&mut self, old: &str, new: &str) {
+        for symbol in &mut self.symbols {
+            if let Some(scopes) = &mut symbol.scopes {
+                scopes.rename_file(old, new);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -210,7 +386,7 @@ impl PartialSymbolStack {
     serde(transparent)
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-pub struct SymbolStackVariable(u32);
+pub struct SymbolStackVariable(pub(crate) u32);
 
 impl SymbolStackVariable {
     pub fn from_symbol_stack_variable(value: crate::partial::SymbolStackVariable) -> Self {
@@ -231,7 +407,7 @@ impl SymbolStackVariable {
 )]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialScopedSymbol {
-    symbol: String,
+    pub(crate) symbol: String,
     pub(crate) scopes: Option<PartialScopeStack>,
 }
 
@@ -312,7 +488,7 @@ impl PartialPathEdgeList {
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
 pub struct PartialPathEdge {
     pub(crate) source: NodeID,
-    precedence: i32,
+    pub(crate) precedence: i32,
 }
 
 impl PartialPathEdge {
@@ -323,7 +499,7 @@ impl PartialPathEdge {
     ) -> Self {
         Self {
             source: NodeID::from_node_id(graph, value.source_node_id),
-            precedence: value.precedence,
+            precedence: value.precedence.value(),
         }
     }
 
@@ -334,7 +510,7 @@ impl PartialPathEdge {
     ) -> Result<crate::partial::PartialPathEdge, Error> {
         Ok(crate::partial::PartialPathEdge {
             source_node_id: self.source.to_node_id(graph)?,
-            precedence: self.precedence,
+            precedence: crate::graph::Precedence::new(self.precedence),
         })
     }
 }
@@ -41,7 +41,7 @@ impl Database {
     ) -> Self {
         let filter = ImplicationFilter(filter);
         let mut paths = Vec::new();
-        for path in value.iter_partial_paths() {
+        for path in value.ordered_partial_paths(graph, partials) {
             let path = &value[path];
             if !filter.include_partial_path(graph, partials, path) {
                 continue;
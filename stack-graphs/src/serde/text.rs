@@ -0,0 +1,387 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! A human-readable textual format for partial path databases, which is described at
+//! [`Database::to_text`][crate::stitching::Database::to_text].
+
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::partial::PartialPaths;
+
+use super::Error;
+use super::NodeID;
+use super::PartialPath;
+use super::PartialPathEdge;
+use super::PartialPathEdgeList;
+use super::PartialScopeStack;
+use super::PartialScopedSymbol;
+use super::PartialSymbolStack;
+use super::ScopeStackVariable;
+use super::SymbolStackVariable;
+
+/// An error that occurred while parsing the textual format of a partial path database.
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum TextError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: {error}")]
+    Load {
+        line: usize,
+        #[source]
+        error: Error,
+    },
+}
+
+impl crate::stitching::Database {
+    /// Returns the textual format of this database.  Every partial path is written on a line of
+    /// its own, with its start and end nodes, its preconditions and postconditions, and the edges
+    /// it follows:
+    ///
+    /// ```text
+    /// "test.py":3 -> 1 : <"." "x" %1> ($1) => <%1> ($1) via "test.py":3 "test.py":8@1
+    /// ```
+    ///
+    /// Nodes are written as their quoted file name and local ID, and the root and jump to scope
+    /// nodes as their local IDs only.  Symbol stacks are written between angle brackets, as their
+    /// quoted symbols followed by an optional `%N` variable.  A symbol that has attached scopes is
+    /// followed by a slash and its scope stack.  Scope stacks are written between parentheses, as
+    /// their scope nodes followed by an optional `$N` variable.  Edges are written as their source
+    /// nodes, followed by their precedence after an `@` if it is not zero.
+    ///
+    /// Paths are written in a stable order, so that the text of a database can be reviewed and
+    /// compared, and used as the expected result of tests.
+    pub fn to_text(&self, graph: &crate::graph::StackGraph, partials: &mut PartialPaths) -> String {
+        let mut text = String::new();
+        for path in self.ordered_partial_paths(graph, partials) {
+            let path = PartialPath::from_partial_path(graph, partials, &self[path]);
+            write_path(&mut text, &path);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parses a database from its [textual format][Self::to_text].  Empty lines, and lines
+    /// starting with `#`, are ignored.  The files of all nodes that the paths refer to must exist
+    /// in the graph.
+    pub fn from_text(
+        text: &str,
+        graph: &mut crate::graph::StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Result<crate::stitching::Database, TextError> {
+        let mut db = crate::stitching::Database::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let path = Parser::new(line)
+                .path()
+                .map_err(|message| TextError::Syntax {
+                    line: line_number,
+                    message,
+                })?;
+            let path = path
+                .to_partial_path(graph, partials)
+                .map_err(|error| TextError::Load {
+                    line: line_number,
+                    error,
+                })?;
+            db.add_partial_path(graph, partials, path);
+        }
+        Ok(db)
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Writing
+
+fn write_path(text: &mut String, path: &PartialPath) {
+    write_node(text, &path.start_node);
+    text.push_str(" -> ");
+    write_node(text, &path.end_node);
+    text.push_str(" : ");
+    write_symbol_stack(text, &path.symbol_stack_precondition);
+    text.push(' ');
+    write_scope_stack(text, &path.scope_stack_precondition);
+    text.push_str(" => ");
+    write_symbol_stack(text, &path.symbol_stack_postcondition);
+    text.push(' ');
+    write_scope_stack(text, &path.scope_stack_postcondition);
+    if !path.edges.edges.is_empty() {
+        text.push_str(" via");
+        for edge in &path.edges.edges {
+            text.push(' ');
+            write_node(text, &edge.source);
+            if edge.precedence != 0 {
+                write!(text, "@{}", edge.precedence).unwrap();
+            }
+        }
+    }
+}
+
+fn write_node(text: &mut String, node: &NodeID) {
+    if let Some(file) = &node.file {
+        write_string(text, file);
+        text.push(':');
+    }
+    write!(text, "{}", node.local_id).unwrap();
+}
+
+fn write_symbol_stack(text: &mut String, stack: &PartialSymbolStack) {
+    text.push('<');
+    let mut first = true;
+    for symbol in &stack.symbols {
+        if !first {
+            text.push(' ');
+        }
+        first = false;
+        write_string(text, &symbol.symbol);
+        if let Some(scopes) = &symbol.scopes {
+            text.push('/');
+            write_scope_stack(text, scopes);
+        }
+    }
+    if let Some(variable) = &stack.variable {
+        if !first {
+            text.push(' ');
+        }
+        write!(text, "%{}", variable.0).unwrap();
+    }
+    text.push('>');
+}
+
+fn write_scope_stack(text: &mut String, stack: &PartialScopeStack) {
+    text.push('(');
+    let mut first = true;
+    for scope in &stack.scopes {
+        if !first {
+            text.push(' ');
+        }
+        first = false;
+        write_node(text, scope);
+    }
+    if let Some(variable) = &stack.variable {
+        if !first {
+            text.push(' ');
+        }
+        write!(text, "${}", variable.0).unwrap();
+    }
+    text.push(')');
+}
+
+fn write_string(text: &mut String, value: &str) {
+    text.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+}
+
+//-------------------------------------------------------------------------------------------------
+// Parsing
+
+/// Parses a single line of the textual format.  Errors are reported as messages, which are
+/// combined with the line number by the caller.
+struct Parser<'a> {
+    line: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { line, position: 0 }
+    }
+
+    fn path(&mut self) -> Result<PartialPath, String> {
+        let start_node = self.node()?;
+        self.expect("->")?;
+        let end_node = self.node()?;
+        self.expect(":")?;
+        let symbol_stack_precondition = self.symbol_stack()?;
+        let scope_stack_precondition = self.scope_stack()?;
+        self.expect("=>")?;
+        let symbol_stack_postcondition = self.symbol_stack()?;
+        let scope_stack_postcondition = self.scope_stack()?;
+        let mut edges = Vec::new();
+        if self.accept("via") {
+            while !self.at_end() {
+                let source = self.node()?;
+                let precedence = if self.accept("@") { self.integer()? } else { 0 };
+                edges.push(PartialPathEdge { source, precedence });
+            }
+        }
+        if !self.at_end() {
+            return Err(self.error("end of line"));
+        }
+        Ok(PartialPath {
+            start_node,
+            end_node,
+            symbol_stack_precondition,
+            symbol_stack_postcondition,
+            scope_stack_precondition,
+            scope_stack_postcondition,
+            edges: PartialPathEdgeList { edges },
+        })
+    }
+
+    fn node(&mut self) -> Result<NodeID, String> {
+        self.skip_whitespace();
+        if self.peek() == Some('"') {
+            let file = self.string()?;
+            self.expect(":")?;
+            let local_id = self.integer()?;
+            Ok(NodeID {
+                file: Some(file),
+                local_id,
+            })
+        } else {
+            Ok(NodeID {
+                file: None,
+                local_id: self.integer()?,
+            })
+        }
+    }
+
+    fn symbol_stack(&mut self) -> Result<PartialSymbolStack, String> {
+        self.expect("<")?;
+        let mut symbols = Vec::new();
+        let mut variable = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('>') => break,
+                Some('%') if variable.is_none() => {
+                    self.position += 1;
+                    variable = Some(SymbolStackVariable(self.integer()?));
+                }
+                Some('"') if variable.is_none() => {
+                    let symbol = self.string()?;
+                    let scopes = if self.accept("/") {
+                        Some(self.scope_stack()?)
+                    } else {
+                        None
+                    };
+                    symbols.push(PartialScopedSymbol { symbol, scopes });
+                }
+                _ => return Err(self.error("symbol, variable, or `>`")),
+            }
+        }
+        self.expect(">")?;
+        Ok(PartialSymbolStack { symbols, variable })
+    }
+
+    fn scope_stack(&mut self) -> Result<PartialScopeStack, String> {
+        self.expect("(")?;
+        let mut scopes = Vec::new();
+        let mut variable = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(')') => break,
+                Some('$') if variable.is_none() => {
+                    self.position += 1;
+                    variable = Some(ScopeStackVariable(self.integer()?));
+                }
+                Some(_) if variable.is_none() => scopes.push(self.node()?),
+                _ => return Err(self.error("scope, variable, or `)`")),
+            }
+        }
+        self.expect(")")?;
+        Ok(PartialScopeStack { scopes, variable })
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += offset + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    _ => {
+                        self.position += offset;
+                        return Err(self.error("escape sequence"));
+                    }
+                },
+                c => value.push(c),
+            }
+        }
+        self.position = self.line.len();
+        Err(self.error("closing `\"`"))
+    }
+
+    fn integer<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let length = rest
+            .char_indices()
+            .find(|(offset, c)| !(c.is_ascii_digit() || (*offset == 0 && *c == '-')))
+            .map_or(rest.len(), |(offset, _)| offset);
+        let value = rest[..length].parse().map_err(|_| self.error("number"))?;
+        self.position += length;
+        Ok(value)
+    }
+
+    /// Consumes the given token if it is next, and returns whether it was.
+    fn accept(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.accept(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", token)))
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("expected {} at column {}", expected, self.position + 1)
+    }
+}
@@ -161,13 +161,21 @@
 //! [partial paths]: ../partial/index.html
 
 use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
 #[cfg(feature = "copious-debugging")]
 use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
 
+use controlled_option::ControlledOption;
 use itertools::izip;
 use itertools::Itertools;
+use thiserror::Error;
 
 use crate::arena::Arena;
 use crate::arena::Handle;
@@ -175,6 +183,7 @@ use crate::arena::HandleSet;
 use crate::arena::List;
 use crate::arena::ListArena;
 use crate::arena::ListCell;
+use crate::arena::MemoryBudget;
 use crate::arena::SupplementalArena;
 use crate::cycles::Appendables;
 use crate::cycles::AppendingCycleDetector;
@@ -183,18 +192,25 @@ use crate::cycles::SimilarPathStats;
 use crate::graph::Degree;
 use crate::graph::Edge;
 use crate::graph::File;
+use crate::graph::HandleRemap;
+use crate::graph::IterationOrder;
 use crate::graph::Node;
+use crate::graph::NodeID;
+use crate::graph::NodeKind;
+use crate::graph::NodeKindSet;
 use crate::graph::StackGraph;
 use crate::graph::Symbol;
 use crate::partial::Cyclicity;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
+use crate::partial::PartialScopedSymbol;
 use crate::partial::PartialSymbolStack;
 use crate::paths::Extend;
 use crate::paths::PathResolutionError;
 use crate::stats::FrequencyDistribution;
 use crate::CancellationError;
 use crate::CancellationFlag;
+use crate::NoCancellation;
 
 //-------------------------------------------------------------------------------------------------
 // Appendable
@@ -232,6 +248,11 @@ impl Appendable for Edge {
         path: &mut PartialPath,
     ) -> Result<(), PathResolutionError> {
         path.resolve_to_node(graph, partials, self.source)?;
+        path.resolve_to_alias(graph, partials, self.source)?;
+        if graph[self.source].is_root() {
+            let condition = graph.edge_condition(self.source, self.sink);
+            path.ensure_root_condition(graph, partials, condition)?;
+        }
         path.append(graph, partials, *self)
     }
 
@@ -264,6 +285,11 @@ impl Appendable for PartialPath {
         path: &mut PartialPath,
     ) -> Result<(), PathResolutionError> {
         path.resolve_to_node(graph, partials, self.start_node)?;
+        path.resolve_to_alias(graph, partials, self.start_node)?;
+        if graph[self.start_node].is_root() {
+            let condition = self.root_departure_condition(graph, partials);
+            path.ensure_root_condition(graph, partials, condition)?;
+        }
         path.ensure_no_overlapping_variables(partials, self);
         path.concatenate(graph, partials, self)?;
         Ok(())
@@ -335,6 +361,11 @@ where
 
     /// Get the graph, partial path arena, and database backing this candidates instance.
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Db);
+
+    /// Records that the given candidates were appended to build a complete path that was reported
+    /// as a result.  Implementations can use this to keep frequently used candidates readily
+    /// available.  Does nothing by default.
+    fn record_resolution(&mut self, _candidates: &[H]) {}
 }
 
 //-------------------------------------------------------------------------------------------------
@@ -368,10 +399,15 @@ impl ForwardCandidates<Edge, Edge, GraphEdges, CancellationError> for GraphEdgeC
     where
         R: std::iter::Extend<Edge>,
     {
-        result.extend(self.graph.outgoing_edges(path.end_node).filter(|e| {
-            self.file
-                .map_or(true, |file| self.graph[e.sink].is_in_file(file))
-        }));
+        // Paths can be extended from the end node and from all of its aliases.
+        let graph = self.graph;
+        let file = self.file;
+        result.extend(
+            std::iter::once(path.end_node)
+                .chain(graph.node_aliases(path.end_node).iter().copied())
+                .flat_map(|node| graph.outgoing_edges(node))
+                .filter(|e| file.map_or(true, |file| graph[e.sink].is_in_file(file))),
+        );
     }
 
     fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
@@ -418,6 +454,10 @@ pub struct Database {
     root_paths_by_precondition_without_variable:
         SupplementalArena<SymbolStackKeyCell, Vec<Handle<PartialPath>>>,
     incoming_paths: SupplementalArena<Node, Degree>,
+    paths_by_content_hash: HashMap<u64, Vec<Handle<PartialPath>>>,
+    deduplicate_paths: bool,
+    iteration_order: IterationOrder,
+    hot_paths: HotPaths,
 }
 
 impl Database {
@@ -433,12 +473,22 @@ impl Database {
             root_paths_by_precondition_with_variable: SupplementalArena::new(),
             root_paths_by_precondition_without_variable: SupplementalArena::new(),
             incoming_paths: SupplementalArena::new(),
+            paths_by_content_hash: HashMap::new(),
+            deduplicate_paths: true,
+            iteration_order: IterationOrder::default(),
+            hot_paths: HotPaths::default(),
         }
     }
 
+    /// Assigns a memory budget to the arenas of this database.  See [`MemoryBudget`][] for which
+    /// allocations are counted, and how the budget is enforced.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.partial_paths.set_budget(budget.clone());
+        self.symbol_stack_keys.set_budget(budget);
+    }
+
     /// Clear the database.  After this, all previous handles into the database are
     /// invalid.
-    #[cfg_attr(not(feature = "storage"), allow(dead_code))]
     pub(crate) fn clear(&mut self) {
         self.partial_paths.clear();
         self.local_nodes.clear();
@@ -449,10 +499,82 @@ impl Database {
         self.root_paths_by_precondition_with_variable.clear();
         self.root_paths_by_precondition_without_variable.clear();
         self.incoming_paths.clear();
+        self.paths_by_content_hash.clear();
+        self.hot_paths.clear();
+    }
+
+    /// Sets whether [`add_partial_path`][Self::add_partial_path] deduplicates partial paths, which
+    /// is enabled by default.  Deduplication requires hashing every added path, which can be
+    /// disabled if the paths are known to be unique, for example because they come from a storage
+    /// layer that never contains duplicates.  Enabling deduplication does not remove duplicates
+    /// that were already added; use [`dedup`][Self::dedup] for that.
+    pub fn set_deduplicate_paths(&mut self, deduplicate_paths: bool) {
+        self.deduplicate_paths = deduplicate_paths;
+    }
+
+    /// Sets the order in which [`find_candidate_partial_paths`][Self::find_candidate_partial_paths]
+    /// and [`ordered_partial_paths`][Self::ordered_partial_paths] return partial paths.  The
+    /// default is [insertion order][IterationOrder::Insertion].  In [sorted
+    /// order][IterationOrder::Sorted], the stitching results only depend on the contents of the
+    /// database, and not on the order in which paths were added or loaded.
+    pub fn set_iteration_order(&mut self, order: IterationOrder) {
+        self.iteration_order = order;
+    }
+
+    /// Returns the order in which this database returns partial paths.
+    pub fn iteration_order(&self) -> IterationOrder {
+        self.iteration_order
+    }
+
+    /// Enables a cache of the `capacity` partial paths that most often participate in successful
+    /// resolutions, as [recorded][Self::record_resolution] by the stitcher.  Candidate lookups
+    /// from the root node whose results contain hot paths are remembered, so that repeated
+    /// lookups for the same symbol stack, which are common when the same framework or import
+    /// paths are traversed over and over during a session, do not have to search the database
+    /// again.  Remembered lookups are forgotten when a path that starts at the root node is added.
+    /// A capacity of zero, the default, disables the cache.
+    pub fn set_hot_path_capacity(&mut self, capacity: usize) {
+        self.hot_paths.capacity = capacity;
+        if capacity == 0 {
+            self.hot_paths.clear();
+        } else if self.hot_paths.hot.len() > capacity {
+            for path in self.hot_paths.hot.split_off(capacity) {
+                self.hot_paths.hot_set.remove(&path);
+            }
+            self.hot_paths.forget_cold_lookups();
+            self.hot_paths.decay_uses();
+        }
     }
 
-    /// Adds a partial path to this database.  We do not deduplicate partial paths in any way; it's
-    /// your responsibility to only add each partial path once.
+    /// Records that the given partial paths participated in a successful resolution.  This is
+    /// only tracked if the [hot path cache][Self::set_hot_path_capacity] is enabled.
+    pub fn record_resolution(&mut self, paths: &[Handle<PartialPath>]) {
+        if self.hot_paths.capacity == 0 {
+            return;
+        }
+        let paths = paths.iter().copied().collect::<HashSet<_>>();
+        for path in &paths {
+            *self.hot_paths.uses.entry(*path).or_default() += 1;
+        }
+        self.hot_paths.update(paths);
+    }
+
+    /// Returns the paths in the [hot path cache][Self::set_hot_path_capacity], starting with the
+    /// one that participated in the most resolutions.
+    pub fn hot_partial_paths(&self) -> impl Iterator<Item = Handle<PartialPath>> + '_ {
+        self.hot_paths.hot.iter().copied()
+    }
+
+    /// Returns statistics about the [hot path cache][Self::set_hot_path_capacity].  Statistics
+    /// are kept when the database is cleared.
+    pub fn hot_path_stats(&self) -> HotPathStats {
+        self.hot_paths.stats
+    }
+
+    /// Adds a partial path to this database.  Identical partial paths are only stored once; if the
+    /// database already contains a path with the same nodes, edges, and pre- and postconditions,
+    /// the handle of the existing path is returned.  If deduplication is disabled, it's your
+    /// responsibility to only add each partial path once.
     pub fn add_partial_path(
         &mut self,
         graph: &StackGraph,
@@ -470,11 +592,30 @@ impl Database {
             },
             path.display(graph, partials)
         );
+        let content_hash = if self.deduplicate_paths {
+            let content_hash = Self::content_hash(partials, &path);
+            if let Some(existing) = self.find_identical_partial_path(partials, content_hash, &path)
+            {
+                copious_debugging!("    Path already in database");
+                return existing;
+            }
+            Some(content_hash)
+        } else {
+            None
+        };
         let symbol_stack_precondition = path.symbol_stack_precondition;
         let handle = self.partial_paths.add(path);
+        if let Some(content_hash) = content_hash {
+            self.paths_by_content_hash
+                .entry(content_hash)
+                .or_default()
+                .push(handle);
+        }
 
         // If the partial path starts at the root node, index it by its symbol stack precondition.
         if graph[start_node].is_root() {
+            // the path can be a candidate for any remembered lookup
+            self.hot_paths.root_candidates.clear();
             // The join node is root, so there's no need to use half-open symbol stacks here, as we
             // do for [`PartialPath::concatenate`][].
             let mut key = SymbolStackKey::from_partial_symbol_stack(
@@ -502,9 +643,116 @@ impl Database {
         handle
     }
 
+    /// Removes duplicate partial paths from this database, and returns the number of paths that
+    /// were removed.  Paths are compared by content, in the same way as in
+    /// [`add_partial_path`][Self::add_partial_path], and the first of each set of identical paths
+    /// is kept.  This is needed for databases that were populated while deduplication was
+    /// disabled.  After this, all previous handles into the database are invalid.  Nodes that were
+    /// marked as local remain local.
+    pub fn dedup(&mut self, graph: &StackGraph, partials: &mut PartialPaths) -> usize {
+        let paths = self
+            .iter_partial_paths()
+            .map(|handle| self[handle].clone())
+            .collect::<Vec<_>>();
+        let local_nodes = std::mem::replace(&mut self.local_nodes, HandleSet::new());
+        let deduplicate_paths = self.deduplicate_paths;
+        self.clear();
+        self.local_nodes = local_nodes;
+        self.deduplicate_paths = true;
+        let count = paths.len();
+        for path in paths {
+            self.add_partial_path(graph, partials, path);
+        }
+        self.deduplicate_paths = deduplicate_paths;
+        count - self.iter_partial_paths().count()
+    }
+
+    /// Compacts the given graph, like [`StackGraph::compact`][], and rebuilds this database and
+    /// the partial paths arena for the compacted graph.  The graph and partial paths arena must
+    /// be the ones that the paths in this database were created with.  Paths that visit any of
+    /// the removed files are dropped, and nodes that were marked as local remain local.  After
+    /// this, all previous handles into the graph, the partial paths arena, and the database are
+    /// invalid.
+    pub fn compact_with_graph<F>(
+        &mut self,
+        graph: &mut StackGraph,
+        partials: &mut PartialPaths,
+        retain: F,
+    ) -> HandleRemap
+    where
+        F: Fn(&StackGraph, &Handle<File>) -> bool,
+    {
+        let paths = self
+            .iter_partial_paths()
+            .map(|handle| {
+                crate::serde::PartialPath::from_partial_path(graph, partials, &self[handle])
+            })
+            .collect::<Vec<_>>();
+        let local_nodes = self.local_nodes.iter().collect::<Vec<_>>();
+        let remap = graph.compact(retain);
+        partials.clear();
+        self.clear();
+        for node in local_nodes {
+            if let Some(node) = remap.node(node) {
+                self.local_nodes.add(node);
+            }
+        }
+        for path in paths {
+            // paths that visit removed files cannot be loaded into the compacted graph
+            if let Ok(path) = path.to_partial_path(graph, partials) {
+                self.add_partial_path(graph, partials, path);
+            }
+        }
+        remap
+    }
+
+    /// Returns a hash of the content of a partial path, which is used to find identical paths.
+    /// Paths with the same content have the same hash, but because nested scope stacks are not
+    /// taken into account, paths with the same hash must still be compared.
+    fn content_hash(partials: &mut PartialPaths, path: &PartialPath) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.start_node.hash(&mut hasher);
+        path.end_node.hash(&mut hasher);
+        for symbol_stack in [
+            path.symbol_stack_precondition,
+            path.symbol_stack_postcondition,
+        ] {
+            for symbol in symbol_stack.iter(partials) {
+                symbol.symbol.hash(&mut hasher);
+                symbol.scopes.is_some().hash(&mut hasher);
+            }
+            symbol_stack.has_variable().hash(&mut hasher);
+        }
+        for scope_stack in [
+            path.scope_stack_precondition,
+            path.scope_stack_postcondition,
+        ] {
+            for scope in scope_stack.iter_scopes(partials) {
+                scope.hash(&mut hasher);
+            }
+            scope_stack.has_variable().hash(&mut hasher);
+        }
+        path.edges.len().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn find_identical_partial_path(
+        &self,
+        partials: &mut PartialPaths,
+        content_hash: u64,
+        path: &PartialPath,
+    ) -> Option<Handle<PartialPath>> {
+        let candidates = self.paths_by_content_hash.get(&content_hash)?;
+        candidates.iter().copied().find(|candidate| {
+            let candidate = self.partial_paths.get(*candidate);
+            candidate.equals(partials, path) && candidate.edges.equals(partials, path.edges)
+        })
+    }
+
     /// Find all partial paths in this database that start at the given path's end node.
     /// If the end node is the root node, returns paths with a symbol stack precondition
-    /// that are compatible with the path's symbol stack post condition.
+    /// that are compatible with the path's symbol stack post condition.  The paths are returned
+    /// in the database's [iteration order][Self::set_iteration_order].
     pub fn find_candidate_partial_paths<R>(
         &mut self,
         graph: &StackGraph,
@@ -513,6 +761,28 @@ impl Database {
         result: &mut R,
     ) where
         R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        match self.iteration_order {
+            IterationOrder::Insertion => {
+                self.find_unordered_candidate_partial_paths(graph, partials, path, result)
+            }
+            IterationOrder::Sorted => {
+                let mut candidates = Vec::new();
+                self.find_unordered_candidate_partial_paths(graph, partials, path, &mut candidates);
+                candidates.sort_by(|a, b| self[*a].cmp_by_content(graph, partials, &self[*b]));
+                result.extend(candidates);
+            }
+        }
+    }
+
+    fn find_unordered_candidate_partial_paths<R>(
+        &mut self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+        result: &mut R,
+    ) where
+        R: std::iter::Extend<Handle<PartialPath>>,
     {
         if graph[path.end_node].is_root() {
             // The join node is root, so there's no need to use half-open symbol stacks here, as we
@@ -524,7 +794,11 @@ impl Database {
                 result,
             );
         } else {
+            // Paths can be extended from the end node and from all of its aliases.
             self.find_candidate_partial_paths_from_node(graph, partials, path.end_node, result);
+            for alias in graph.node_aliases(path.end_node) {
+                self.find_candidate_partial_paths_from_node(graph, partials, *alias, result);
+            }
         }
     }
 
@@ -539,13 +813,49 @@ impl Database {
         result: &mut R,
     ) where
         R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        let key = symbol_stack.map(|symbol_stack| {
+            (
+                SymbolStackKey::from_partial_symbol_stack(partials, self, symbol_stack),
+                symbol_stack.has_variable(),
+            )
+        });
+        if self.hot_paths.capacity == 0 {
+            self.find_candidate_partial_paths_from_root_key(graph, partials, key, result);
+            return;
+        }
+        let lookup = key.map(|(key, has_variable)| (key.back_handle(), has_variable));
+        self.hot_paths.stats.lookups += 1;
+        if let Some(paths) = self.hot_paths.root_candidates.get(&lookup) {
+            self.hot_paths.stats.hits += 1;
+            result.extend(paths.iter().copied());
+            return;
+        }
+        let mut paths = Vec::new();
+        self.find_candidate_partial_paths_from_root_key(graph, partials, key, &mut paths);
+        if paths
+            .iter()
+            .any(|path| self.hot_paths.hot_set.contains(path))
+        {
+            self.hot_paths.root_candidates.insert(lookup, paths.clone());
+        }
+        result.extend(paths);
+    }
+
+    #[cfg_attr(not(feature = "copious-debugging"), allow(unused_variables))]
+    fn find_candidate_partial_paths_from_root_key<R>(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        key: Option<(SymbolStackKey, bool)>,
+        result: &mut R,
+    ) where
+        R: std::iter::Extend<Handle<PartialPath>>,
     {
         // If the path currently ends at the root node, then we need to look up partial paths whose
         // symbol stack precondition is compatible with the path.
-        match symbol_stack {
-            Some(symbol_stack) => {
-                let mut key =
-                    SymbolStackKey::from_partial_symbol_stack(partials, self, symbol_stack);
+        match key {
+            Some((mut key, has_variable)) => {
                 copious_debugging!(
                     "      Search for symbol stack <{}>",
                     key.display(graph, self)
@@ -567,7 +877,7 @@ impl Database {
                     result.extend(paths.iter().copied());
                 }
                 // paths that have an extension of this symbol stack
-                if symbol_stack.has_variable() {
+                if has_variable {
                     if let Some(paths) = self
                         .root_paths_by_precondition_prefix
                         .get(key.back_handle())
@@ -739,13 +1049,29 @@ impl Database {
         self.local_nodes.contains(node)
     }
 
-    /// Returns an iterator over all of the handles of all of the partial paths in this database.
-    /// (Note that because we're only returning _handles_, this iterator does not retain a
-    /// reference to the `Database`.)
+    /// Returns an iterator over all of the handles of all of the partial paths in this database,
+    /// in the order in which they were added.  (Note that because we're only returning _handles_,
+    /// this iterator does not retain a reference to the `Database`.)  Use
+    /// [`ordered_partial_paths`][Self::ordered_partial_paths] to respect the database's iteration
+    /// order.
     pub fn iter_partial_paths(&self) -> impl Iterator<Item = Handle<PartialPath>> {
         self.partial_paths.iter_handles()
     }
 
+    /// Returns the handles of all of the partial paths in this database, in the database's
+    /// [iteration order][Self::set_iteration_order].
+    pub fn ordered_partial_paths(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+    ) -> Vec<Handle<PartialPath>> {
+        let mut paths = self.iter_partial_paths().collect::<Vec<_>>();
+        if self.iteration_order == IterationOrder::Sorted {
+            paths.sort_by(|a, b| self[*a].cmp_by_content(graph, partials, &self[*b]));
+        }
+        paths
+    }
+
     pub fn ensure_both_directions(&mut self, partials: &mut PartialPaths) {
         for path in self.partial_paths.iter_handles() {
             self.partial_paths
@@ -769,6 +1095,114 @@ impl std::ops::Index<Handle<PartialPath>> for Database {
     }
 }
 
+/// Statistics about the [hot path cache][Database::set_hot_path_capacity] of a database.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HotPathStats {
+    /// The number of candidate lookups from the root node while the cache was enabled.
+    pub lookups: usize,
+    /// The number of those lookups that were answered from the cache.
+    pub hits: usize,
+}
+
+impl HotPathStats {
+    /// Returns the fraction of lookups that were answered from the cache, or zero if there were
+    /// no lookups.
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+}
+
+/// The number of use counts that the hot path cache keeps per hot path.  When there are more, the
+/// counts are decayed, and the counts of paths that have not been used recently are dropped.
+const USE_COUNTS_PER_HOT_PATH: usize = 8;
+
+/// The hot path cache of a database.
+#[derive(Default)]
+struct HotPaths {
+    /// The maximum number of hot paths, or zero if the cache is disabled.
+    capacity: usize,
+    /// The number of resolutions that each path participated in, decayed over time.  Only
+    /// [`USE_COUNTS_PER_HOT_PATH`][] counts per hot path are kept.
+    uses: HashMap<Handle<PartialPath>, usize>,
+    /// The most used paths, in order of decreasing use.
+    hot: Vec<Handle<PartialPath>>,
+    hot_set: HashSet<Handle<PartialPath>>,
+    /// The candidates of remembered root lookups, by symbol stack key and whether the symbol stack
+    /// has a variable.  Only lookups whose results contain hot paths are remembered.
+    root_candidates: HashMap<Option<(SymbolStackKeyHandle, bool)>, Vec<Handle<PartialPath>>>,
+    stats: HotPathStats,
+}
+
+impl HotPaths {
+    fn clear(&mut self) {
+        self.uses.clear();
+        self.hot.clear();
+        self.hot_set.clear();
+        self.root_candidates.clear();
+    }
+
+    /// Updates the hot paths after the use counts of the given paths have increased.
+    fn update(&mut self, paths: HashSet<Handle<PartialPath>>) {
+        let uses = &self.uses;
+        // use counts of hot paths may have changed as well
+        self.hot.sort_by_key(|path| Reverse(uses[path]));
+        let mut changed = false;
+        for path in paths {
+            if self.hot_set.contains(&path) {
+                continue;
+            }
+            if self.hot.len() < self.capacity {
+                self.hot.push(path);
+            } else {
+                // replace the least used hot path, if this path is used more often
+                let coldest = *self.hot.last().expect("capacity is positive");
+                if uses[&path] <= uses[&coldest] {
+                    continue;
+                }
+                self.hot_set.remove(&coldest);
+                *self.hot.last_mut().unwrap() = path;
+            }
+            self.hot_set.insert(path);
+            changed = true;
+            // keep the least used hot path last
+            self.hot.sort_by_key(|path| Reverse(uses[path]));
+        }
+        if changed {
+            self.forget_cold_lookups();
+        }
+        self.decay_uses();
+    }
+
+    /// Halves the use counts until there are no more than [`USE_COUNTS_PER_HOT_PATH`][] counts
+    /// per hot path.  Cold paths whose counts drop to zero are forgotten, while hot paths keep a
+    /// count of at least one.  This keeps the counts bounded, and allows recently used paths to
+    /// replace hot paths that were used often a long time ago.
+    fn decay_uses(&mut self) {
+        let max_uses = self.capacity.saturating_mul(USE_COUNTS_PER_HOT_PATH);
+        while self.uses.len() > max_uses {
+            let hot_set = &self.hot_set;
+            self.uses.retain(|path, uses| {
+                *uses /= 2;
+                if hot_set.contains(path) {
+                    *uses = (*uses).max(1);
+                }
+                *uses > 0
+            });
+        }
+    }
+
+    /// Forgets remembered lookups that no longer contain any hot paths.
+    fn forget_cold_lookups(&mut self) {
+        let hot_set = &self.hot_set;
+        self.root_candidates
+            .retain(|_, paths| paths.iter().any(|path| hot_set.contains(path)));
+    }
+}
+
 impl ToAppendable<Handle<PartialPath>, PartialPath> for Database {
     fn get_appendable<'a>(&'a self, handle: &'a Handle<PartialPath>) -> &'a PartialPath {
         &self[*handle]
@@ -813,6 +1247,10 @@ impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, CancellationE
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
         (self.graph, self.partials, self.database)
     }
+
+    fn record_resolution(&mut self, candidates: &[Handle<PartialPath>]) {
+        self.database.record_resolution(candidates);
+    }
 }
 
 /// The key type that we use to find partial paths that start from the root node and have a
@@ -919,6 +1357,87 @@ impl<'a> Display for DisplaySymbolStackKey<'a> {
     }
 }
 
+//-------------------------------------------------------------------------------------------------
+// Resolution extensions
+
+/// Hooks that are invoked during path stitching, so that language-specific disambiguation, such
+/// as the method resolution order of Python classes, or declaration merging in TypeScript, can be
+/// layered on top of the stitcher.  All hooks accept everything by default.
+pub trait ResolutionExtension {
+    /// Called when a path is extended with a candidate, with the path before and after the
+    /// extension.  Returns whether the extension is kept.  Rejected extensions are not extended
+    /// any further.
+    fn accept_extension(
+        &mut self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _extension: &PartialPath,
+    ) -> bool {
+        true
+    }
+
+    /// Called when a complete path is found.  Returns whether the path is reported.
+    fn accept_complete_path(
+        &mut self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+/// A resolution extension that accepts all extensions and complete paths.
+pub struct NoResolutionExtension;
+
+impl ResolutionExtension for NoResolutionExtension {}
+
+//-------------------------------------------------------------------------------------------------
+// Instrumentation
+
+/// Callbacks that observe the internals of path stitching, so that metrics, tracing, or debugging
+/// UIs can be built on top of the stitcher, without the stitcher depending on any of them.  All
+/// callbacks do nothing by default.  Unlike a [`ResolutionExtension`][], instrumentation cannot
+/// change the result of stitching.
+///
+/// Callbacks take `&self`, so that a single instance can be shared between concurrent queries.
+/// Implementations that record data must use interior mutability, such as atomic counters.
+pub trait Instrumentation: Send + Sync {
+    /// Called when a candidate for extending a path has been loaded, before it is appended.
+    fn candidate_loaded(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _candidate: &dyn Appendable,
+    ) {
+    }
+
+    /// Called when a path is extended, with the path before and after the extension.
+    fn path_extended(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _extension: &PartialPath,
+    ) {
+    }
+
+    /// Called when a path is not extended any further, because it ends in a cycle.
+    fn cycle_pruned(&self, _graph: &StackGraph, _partials: &mut PartialPaths, _path: &PartialPath) {
+    }
+
+    /// Called when a complete path is reported as a result.
+    fn result_emitted(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+    ) {
+    }
+}
+
 //-------------------------------------------------------------------------------------------------
 // Stitching partial paths together
 
@@ -948,13 +1467,20 @@ impl<'a> Display for DisplaySymbolStackKey<'a> {
 /// completion, using the [`find_all_complete_partial_paths`][] method.
 ///
 /// [`find_all_complete_partial_paths`]: #method.find_all_complete_partial_paths
+///
+/// By default, each phase extends all of the partial paths found in the previous phase, which
+/// explores the paths breadth-first.  You can use [`set_strategy`][] to explore them depth-first
+/// or best-first instead, in which case each phase ends as soon as a path has been extended, so
+/// that the extensions can be loaded before deciding which path to extend next.
+///
+/// [`set_strategy`]: #method.set_strategy
 pub struct ForwardPartialPathStitcher<H> {
     candidates: Vec<H>,
     extensions: Vec<(PartialPath, AppendingCycleDetector<H>)>,
-    queue: VecDeque<(PartialPath, AppendingCycleDetector<H>, bool)>,
-    // tracks the number of initial paths in the queue because we do not want call
-    // extend_until on those
-    initial_paths_in_queue: usize,
+    queue: PathQueue<H>,
+    // the paths in next_iteration are the initial paths until the first phase has started,
+    // and we do not want to call extend_while on those
+    initial_paths_in_next_iteration: bool,
     // next_iteration is a tuple of queues instead of an queue of tuples so that the path queue
     // can be cheaply exposed through the C API as a continuous memory block
     next_iteration: (
@@ -964,8 +1490,13 @@ pub struct ForwardPartialPathStitcher<H> {
     ),
     appended_paths: Appendables<H>,
     similar_path_detector: Option<SimilarPathDetector<PartialPath>>,
+    shadowing_policy: ShadowingPolicy,
+    path_comparison: PathComparison,
     check_only_join_nodes: bool,
     max_work_per_phase: usize,
+    forbidden_node_kinds: NodeKindSet,
+    max_root_crossings: Option<usize>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
     initial_paths: usize,
     stats: Option<Stats>,
     #[cfg(feature = "copious-debugging")]
@@ -996,16 +1527,22 @@ impl<H> ForwardPartialPathStitcher<H> {
         Self {
             candidates: Vec::new(),
             extensions: Vec::new(),
-            queue: VecDeque::new(),
-            initial_paths_in_queue: initial_paths,
+            queue: PathQueue::new(StitchingStrategy::default()),
+            initial_paths_in_next_iteration: true,
             next_iteration,
             appended_paths,
             // By default, all paths are checked for similarity
             similar_path_detector: Some(SimilarPathDetector::new()),
+            shadowing_policy: ShadowingPolicy::default(),
+            path_comparison: PathComparison::default(),
             // By default, all nodes are checked for cycles and (if enabled) similarity
             check_only_join_nodes: false,
             // By default, there's no artificial bound on the amount of work done per phase
             max_work_per_phase: usize::MAX,
+            // By default, paths can go through any node, and cross the root node any number of times
+            forbidden_node_kinds: NodeKindSet::new(),
+            max_root_crossings: None,
+            instrumentation: None,
             initial_paths,
             stats: None,
             #[cfg(feature = "copious-debugging")]
@@ -1027,6 +1564,28 @@ impl<H> ForwardPartialPathStitcher<H> {
         }
     }
 
+    /// Sets the policy that decides which of two similar paths shadows the other, and is therefore
+    /// kept by similar path detection.  The default is [`ShadowingPolicy::StrictPrecedence`][].
+    pub fn set_shadowing_policy(&mut self, shadowing_policy: ShadowingPolicy) {
+        self.shadowing_policy = shadowing_policy;
+    }
+
+    /// Sets the strategy that decides which of two similar paths is kept by similar path
+    /// detection.  The default is [`PathComparison::PreferHigherPrecedence`][].
+    pub fn set_path_comparison(&mut self, path_comparison: PathComparison) {
+        self.path_comparison = path_comparison;
+    }
+
+    /// Sets the order in which partial paths are extended.  The default is
+    /// [`StitchingStrategy::BreadthFirst`][].  Paths that are already queued are kept.
+    pub fn set_strategy(&mut self, strategy: StitchingStrategy) {
+        if self.queue.strategy() == strategy {
+            return;
+        }
+        let queue = std::mem::replace(&mut self.queue, PathQueue::new(strategy));
+        self.queue.extend(queue.into_paths());
+    }
+
     /// Sets whether all nodes are checked for cycles and (if enabled) similar paths, or only nodes with multiple
     /// incoming candidates. Checking only join nodes is **unsafe** unless the database of candidates is stable
     /// between all stitching phases. If paths are added to the database from one phase to another, for example if
@@ -1044,6 +1603,27 @@ impl<H> ForwardPartialPathStitcher<H> {
         self.max_work_per_phase = max_work_per_phase;
     }
 
+    /// Sets the kinds of nodes that paths are not extended to.  Paths are only checked at the
+    /// nodes where they are extended, which are all nodes when stitching graph edges, but only the
+    /// start and end nodes of the partial paths that are appended when stitching partial paths.
+    /// For example, forbidding [root nodes][NodeKind::Root] restricts stitching to paths within
+    /// a file.
+    pub fn set_forbidden_node_kinds(&mut self, forbidden_node_kinds: NodeKindSet) {
+        self.forbidden_node_kinds = forbidden_node_kinds;
+    }
+
+    /// Sets the maximum number of times that paths leave the root node, which bounds the number
+    /// of hops between files.  Edges leaving the root node at the start of a path are not counted.
+    /// If you don't call this method, paths can cross the root node any number of times.
+    pub fn set_max_root_crossings(&mut self, max_root_crossings: Option<usize>) {
+        self.max_root_crossings = max_root_crossings;
+    }
+
+    /// Sets the instrumentation that observes this stitcher.
+    pub fn set_instrumentation(&mut self, instrumentation: Option<Arc<dyn Instrumentation>>) {
+        self.instrumentation = instrumentation;
+    }
+
     /// Sets whether to collect statistics during stitching.
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
@@ -1075,6 +1655,16 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         self.next_iteration.0.iter()
     }
 
+    /// Returns the candidates that were appended to build the partial path at the given index of
+    /// [`previous_phase_partial_paths`][Self::previous_phase_partial_paths], starting with the
+    /// most recently appended one.
+    pub fn previous_phase_appended_candidates(
+        &self,
+        index: usize,
+    ) -> impl Iterator<Item = &H> + '_ {
+        self.next_iteration.1[index].appended(&self.appended_paths)
+    }
+
     /// Returns a slice of all of the (possibly incomplete) partial paths that were encountered
     /// during the most recent phase of the algorithm.
     pub fn previous_phase_partial_paths_slice(&mut self) -> &[PartialPath] {
@@ -1098,6 +1688,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         partial_path: &PartialPath,
         cycle_detector: AppendingCycleDetector<H>,
         has_split: bool,
+        extension: &mut dyn ResolutionExtension,
     ) -> usize
     where
         A: Appendable,
@@ -1134,6 +1725,9 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             };
             if cyclic {
                 copious_debugging!("      is discontinued: cyclic");
+                if let Some(instrumentation) = &self.instrumentation {
+                    instrumentation.cycle_pruned(graph, partials, partial_path);
+                }
                 return 0;
             }
         }
@@ -1150,6 +1744,9 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         for candidate in &self.candidates {
             let appendable = db.get_appendable(candidate);
             copious_debugging!("      with {}", appendable.display(graph, partials));
+            if let Some(instrumentation) = &self.instrumentation {
+                instrumentation.candidate_loaded(graph, partials, partial_path, appendable);
+            }
 
             let mut new_partial_path = partial_path.clone();
             let mut new_cycle_detector = cycle_detector.clone();
@@ -1162,6 +1759,23 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                     continue;
                 }
             }
+            if !self.is_allowed_by_guards(
+                graph,
+                partials,
+                appendable.start_node(),
+                appendable.end_node(),
+                &new_partial_path,
+            ) {
+                copious_debugging!("        is rejected by traversal guards");
+                continue;
+            }
+            if !extension.accept_extension(graph, partials, partial_path, &new_partial_path) {
+                copious_debugging!("        is rejected by resolution extension");
+                continue;
+            }
+            if let Some(instrumentation) = &self.instrumentation {
+                instrumentation.path_extended(graph, partials, partial_path, &new_partial_path);
+            }
             new_cycle_detector.append(&mut self.appended_paths, candidate.clone());
             copious_debugging!("        is {}", new_partial_path.display(graph, partials));
             self.extensions.push((new_partial_path, new_cycle_detector));
@@ -1179,23 +1793,15 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
                         == Degree::Multiple);
             let (graph, partials, _) = candidates.get_graph_partials_and_db();
             if check_similar_path {
+                let shadowing_policy = self.shadowing_policy;
+                let path_comparison = self.path_comparison;
                 if let Some(similar_path_detector) = &mut self.similar_path_detector {
                     if similar_path_detector.add_path(
                         graph,
                         partials,
                         &new_partial_path,
                         |ps, left, right| {
-                            if !left.equals(ps, right) {
-                                None
-                            } else {
-                                if left.shadows(ps, right) {
-                                    Some(Ordering::Less)
-                                } else if right.shadows(ps, left) {
-                                    Some(Ordering::Greater)
-                                } else {
-                                    Some(Ordering::Equal)
-                                }
-                            }
+                            path_comparison.compare(graph, ps, shadowing_policy, left, right)
                         },
                     ) {
                         copious_debugging!(
@@ -1232,6 +1838,81 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         candidate_count
     }
 
+    /// Returns whether a path that was extended with an appendable from `start_node` to
+    /// `end_node` is allowed by the forbidden node kinds, the maximum number of root crossings, and
+    /// the [root partitions][StackGraph::add_partition_visibility] of the graph.
+    fn is_allowed_by_guards(
+        &self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        start_node: Handle<Node>,
+        end_node: Handle<Node>,
+        path: &PartialPath,
+    ) -> bool {
+        if self.forbidden_node_kinds.contains(graph[end_node].kind()) {
+            return false;
+        }
+        if let Some(max_root_crossings) = self.max_root_crossings {
+            // the number of crossings only changes when the path leaves the root node
+            if graph[start_node].is_root() {
+                let root_edges = path
+                    .edges
+                    .iter(partials)
+                    .filter(|edge| edge.source_node_id == NodeID::root())
+                    .count();
+                let crossings = if graph[path.start_node].is_root() {
+                    root_edges.saturating_sub(1)
+                } else {
+                    root_edges
+                };
+                if crossings > max_root_crossings {
+                    return false;
+                }
+            }
+        }
+        // paths can only move into another partition when they leave the root node
+        if graph[start_node].is_root()
+            && graph.has_root_partitions()
+            && !Self::crosses_visible_partitions(graph, partials, path)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Returns whether every crossing of the root node in a path goes from a file into a file
+    /// whose root partition is visible from it.  Crossings are attributed to the last file that the
+    /// path visited before the root node, and the first file it visits after it.
+    fn crosses_visible_partitions(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) -> bool {
+        let nodes = path
+            .edges
+            .iter(partials)
+            .map(|edge| edge.source_node_id)
+            .chain(std::iter::once(graph[path.end_node].id()));
+        let mut from_file = None;
+        let mut crossing = false;
+        for node in nodes {
+            if node.is_root() {
+                crossing = from_file.is_some();
+                continue;
+            }
+            let file = match node.file() {
+                Some(file) => file,
+                None => continue,
+            };
+            if crossing && !graph.is_visible_across_root(from_file.unwrap(), file) {
+                return false;
+            }
+            crossing = false;
+            from_file = Some(file);
+        }
+        true
+    }
+
     /// Returns whether the algorithm has completed.
     pub fn is_complete(&self) -> bool {
         self.queue.is_empty() && self.next_iteration.0.is_empty()
@@ -1256,55 +1937,296 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         C: ForwardCandidates<H, A, Db, Err>,
         E: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> bool,
     {
-        copious_debugging!("==> Start phase {}", self.phase_number);
-        self.queue.extend(izip!(
-            self.next_iteration.0.drain(..),
-            self.next_iteration.1.drain(..),
-            self.next_iteration.2.drain(..),
-        ));
-        if let Some(stats) = &mut self.stats {
-            stats.queued_paths_per_phase.record(self.queue.len());
-        }
-        let mut work_performed = 0;
-        while let Some((partial_path, cycle_detector, has_split)) = self.queue.pop_front() {
-            let (graph, partials, _) = candidates.get_graph_partials_and_db();
-            copious_debugging!(
-                "--> Candidate partial path {}",
-                partial_path.display(graph, partials)
-            );
-            if self.initial_paths_in_queue > 0 {
-                self.initial_paths_in_queue -= 1;
-            } else if !extend_while(graph, partials, &partial_path) {
-                copious_debugging!(
-                    "    Do not extend {}",
-                    partial_path.display(graph, partials)
-                );
-                continue;
-            }
-            work_performed += self.extend(candidates, &partial_path, cycle_detector, has_split);
-            if work_performed >= self.max_work_per_phase {
-                break;
-            }
-        }
-        if let Some(stats) = &mut self.stats {
-            stats.processed_paths_per_phase.record(work_performed);
-        }
-
-        #[cfg(feature = "copious-debugging")]
-        {
-            if let Some(similar_path_detector) = &self.similar_path_detector {
-                copious_debugging!(
-                    "    Max similar path bucket size: {}",
-                    similar_path_detector.max_bucket_size()
-                );
-            }
-            copious_debugging!("==> End phase {}", self.phase_number);
-            self.phase_number += 1;
-        }
+        self.process_next_phase_with_extension(candidates, extend_while, &mut NoResolutionExtension)
     }
-}
 
-impl ForwardPartialPathStitcher<Edge> {
+    /// Runs the next phase of the algorithm, like [`process_next_phase`][], consulting the
+    /// resolution extension about each extension of a path.
+    ///
+    /// [`process_next_phase`]: #method.process_next_phase
+    pub fn process_next_phase_with_extension<A, Db, C, E, Err>(
+        &mut self,
+        candidates: &mut C,
+        extend_while: E,
+        extension: &mut dyn ResolutionExtension,
+    ) where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        E: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> bool,
+    {
+        self.process_next_phase_inner(candidates, extend_while, extension, &NoCancellation)
+            .expect("cannot be cancelled");
+    }
+
+    /// Runs the next phase of the algorithm, like [`process_next_phase`][], checking the
+    /// cancellation flag before each path is extended, at the `"extending partial paths"`
+    /// checkpoint.  If the phase is cancelled, the paths that were not extended yet stay queued,
+    /// so [`previous_phase_partial_paths`][] returns the paths that were found so far, and the
+    /// stitcher can be resumed by processing the next phase.
+    ///
+    /// [`previous_phase_partial_paths`]: #method.previous_phase_partial_paths
+    /// [`process_next_phase`]: #method.process_next_phase
+    pub fn process_next_phase_with_cancellation<A, Db, C, E, Err>(
+        &mut self,
+        candidates: &mut C,
+        extend_while: E,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError>
+    where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        E: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> bool,
+    {
+        self.process_next_phase_inner(
+            candidates,
+            extend_while,
+            &mut NoResolutionExtension,
+            cancellation_flag,
+        )
+    }
+
+    fn process_next_phase_inner<A, Db, C, E, Err>(
+        &mut self,
+        candidates: &mut C,
+        extend_while: E,
+        extension: &mut dyn ResolutionExtension,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), CancellationError>
+    where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        E: Fn(&StackGraph, &mut PartialPaths, &PartialPath) -> bool,
+    {
+        copious_debugging!("==> Start phase {}", self.phase_number);
+        let is_initial = std::mem::replace(&mut self.initial_paths_in_next_iteration, false);
+        self.queue.extend(
+            izip!(
+                self.next_iteration.0.drain(..),
+                self.next_iteration.1.drain(..),
+                self.next_iteration.2.drain(..),
+            )
+            .map(|(partial_path, cycle_detector, has_split)| QueuedPath {
+                partial_path,
+                cycle_detector,
+                has_split,
+                is_initial,
+            }),
+        );
+        if let Some(stats) = &mut self.stats {
+            stats.queued_paths_per_phase.record(self.queue.len());
+        }
+        let mut work_performed = 0;
+        let mut result = Ok(());
+        while !self.queue.is_empty() {
+            if let Err(err) = cancellation_flag.check("extending partial paths") {
+                result = Err(err);
+                break;
+            }
+            let QueuedPath {
+                partial_path,
+                cycle_detector,
+                has_split,
+                is_initial,
+            } = self.queue.pop().expect("queue is not empty");
+            let (graph, partials, _) = candidates.get_graph_partials_and_db();
+            copious_debugging!(
+                "--> Candidate partial path {}",
+                partial_path.display(graph, partials)
+            );
+            if !is_initial && !extend_while(graph, partials, &partial_path) {
+                copious_debugging!(
+                    "    Do not extend {}",
+                    partial_path.display(graph, partials)
+                );
+                continue;
+            }
+            work_performed += self.extend(
+                candidates,
+                &partial_path,
+                cycle_detector,
+                has_split,
+                extension,
+            );
+            if work_performed >= self.max_work_per_phase {
+                break;
+            }
+            // Unless we explore breadth-first, the extensions of this path must be loaded
+            // before we can decide which path to extend next.
+            if self.queue.strategy() != StitchingStrategy::BreadthFirst
+                && !self.next_iteration.0.is_empty()
+            {
+                break;
+            }
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.processed_paths_per_phase.record(work_performed);
+        }
+
+        #[cfg(feature = "copious-debugging")]
+        {
+            if let Some(similar_path_detector) = &self.similar_path_detector {
+                copious_debugging!(
+                    "    Max similar path bucket size: {}",
+                    similar_path_detector.max_bucket_size()
+                );
+            }
+            copious_debugging!("==> End phase {}", self.phase_number);
+            self.phase_number += 1;
+        }
+        result
+    }
+
+    /// Returns the number of paths that still have to be extended.
+    fn pending_paths(&self) -> usize {
+        self.queue.len() + self.next_iteration.0.len()
+    }
+}
+
+/// The order in which a [`ForwardPartialPathStitcher`][] extends the partial paths it has found.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StitchingStrategy {
+    /// Extends all paths found in a phase before any of their extensions.  This is the default,
+    /// and the best choice when all complete paths are needed.
+    BreadthFirst,
+    /// Extends the extensions of a path before any other path, in the order in which they were
+    /// found.
+    DepthFirst,
+    /// Extends the most promising path first, which is the path with the fewest symbols left to
+    /// resolve, and of those the path with the fewest edges.  This finds the first complete paths
+    /// sooner than the other strategies.
+    BestFirst,
+}
+
+impl Default for StitchingStrategy {
+    fn default() -> Self {
+        Self::BreadthFirst
+    }
+}
+
+/// A partial path that is queued to be extended.
+struct QueuedPath<H> {
+    partial_path: PartialPath,
+    cycle_detector: AppendingCycleDetector<H>,
+    has_split: bool,
+    is_initial: bool,
+}
+
+/// The queue of partial paths to extend, in the order of a stitching strategy.
+enum PathQueue<H> {
+    BreadthFirst(VecDeque<QueuedPath<H>>),
+    DepthFirst(Vec<QueuedPath<H>>),
+    // paths are keyed by their priority, and the order in which they were queued, so that paths
+    // of equal priority are extended first-in, first-out
+    BestFirst(BinaryHeap<PrioritizedPath<H>>, usize),
+}
+
+impl<H> PathQueue<H> {
+    fn new(strategy: StitchingStrategy) -> Self {
+        match strategy {
+            StitchingStrategy::BreadthFirst => Self::BreadthFirst(VecDeque::new()),
+            StitchingStrategy::DepthFirst => Self::DepthFirst(Vec::new()),
+            StitchingStrategy::BestFirst => Self::BestFirst(BinaryHeap::new(), 0),
+        }
+    }
+
+    fn strategy(&self) -> StitchingStrategy {
+        match self {
+            Self::BreadthFirst(_) => StitchingStrategy::BreadthFirst,
+            Self::DepthFirst(_) => StitchingStrategy::DepthFirst,
+            Self::BestFirst(_, _) => StitchingStrategy::BestFirst,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::BreadthFirst(queue) => queue.len(),
+            Self::DepthFirst(stack) => stack.len(),
+            Self::BestFirst(heap, _) => heap.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queues paths, which are extended in the given order relative to each other, if the
+    /// strategy does not order them otherwise.
+    fn extend<I>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = QueuedPath<H>>,
+    {
+        match self {
+            Self::BreadthFirst(queue) => queue.extend(paths),
+            Self::DepthFirst(stack) => {
+                let start = stack.len();
+                stack.extend(paths);
+                stack[start..].reverse();
+            }
+            Self::BestFirst(heap, sequence_number) => {
+                for path in paths {
+                    let priority = (
+                        path.partial_path.symbol_stack_postcondition.len(),
+                        path.partial_path.edges.len(),
+                        *sequence_number,
+                    );
+                    *sequence_number += 1;
+                    heap.push(PrioritizedPath {
+                        priority: Reverse(priority),
+                        path,
+                    });
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<QueuedPath<H>> {
+        match self {
+            Self::BreadthFirst(queue) => queue.pop_front(),
+            Self::DepthFirst(stack) => stack.pop(),
+            Self::BestFirst(heap, _) => heap.pop().map(|p| p.path),
+        }
+    }
+
+    /// Returns the queued paths, in the order in which they would have been extended.
+    fn into_paths(mut self) -> Vec<QueuedPath<H>> {
+        let mut paths = Vec::with_capacity(self.len());
+        while let Some(path) = self.pop() {
+            paths.push(path);
+        }
+        paths
+    }
+}
+
+/// A queued path, ordered so that the most promising path is the greatest.
+struct PrioritizedPath<H> {
+    priority: Reverse<(usize, usize, usize)>,
+    path: QueuedPath<H>,
+}
+
+impl<H> PartialEq for PrioritizedPath<H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<H> Eq for PrioritizedPath<H> {}
+
+impl<H> PartialOrd for PrioritizedPath<H> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<H> Ord for PrioritizedPath<H> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+impl ForwardPartialPathStitcher<Edge> {
     /// Finds a minimal set of partial paths in a file, calling the `visit` closure for each one.
     ///
     /// This function ensures that the set of visited partial paths
@@ -1320,6 +2242,9 @@ impl ForwardPartialPathStitcher<Edge> {
     /// Caveat: Edges between nodes of different files are not used. Hence the returned set of partial
     /// paths will not cover paths going through those edges.
     ///
+    /// Cancellation is handled as for [`find_partial_paths_in_file`][].
+    ///
+    /// [`find_partial_paths_in_file`]: #method.find_partial_paths_in_file
     /// [`process_next_phase`]: #method.process_next_phase
     pub fn find_minimal_partial_path_set_in_file<F>(
         graph: &StackGraph,
@@ -1327,40 +2252,230 @@ impl ForwardPartialPathStitcher<Edge> {
         file: Handle<File>,
         config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, PartialPathsCancelled>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+    {
+        Self::find_partial_paths_in_file(
+            graph,
+            partials,
+            file,
+            &PartialPathsConfig::default().with_stitcher_config(config),
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds partial paths in a file, calling the `visit` closure for each one.  The `config`
+    /// determines at which nodes partial paths start and end, and whether paths are extended
+    /// beyond intermediate endpoints.
+    ///
+    /// Partial paths always start and end at endpoints, which are references, definitions,
+    /// exported scopes, and the root node, as well as any additional join nodes in the
+    /// configuration.  Paths ending in a _jump to scope_ node are also accepted.  Join nodes do
+    /// not need to be marked in the graph, because partial paths are stitched at whatever node
+    /// they end in.
+    ///
+    /// If the configuration is minimal, which is the default, the result is the same as for
+    /// [`find_minimal_partial_path_set_in_file`][].  Otherwise, paths are extended through
+    /// intermediate endpoints until they reach the root node, a _jump to scope_ node, or cannot
+    /// be extended any further, and every path between endpoints is visited.  This results in
+    /// more and longer partial paths, but fewer stitching steps at query time.
+    ///
+    /// Caveat: Edges between nodes of different files are not used. Hence the returned set of partial
+    /// paths will not cover paths going through those edges.
+    ///
+    /// The cancellation flag is checked at the following checkpoints:
+    ///
+    ///  - `"collecting initial partial paths"`, before each node of the file is considered as the
+    ///    start of a partial path, and
+    ///  - `"extending partial paths"`, before each partial path is extended.
+    ///
+    /// Partial paths are visited as soon as they are found, so when the computation is cancelled,
+    /// all partial paths that were found so far have been visited already.  They are a subset of
+    /// the partial paths of the file, which can be used as incomplete results.  The returned
+    /// [error][PartialPathsCancelled] describes how far the computation got.
+    ///
+    /// [`find_minimal_partial_path_set_in_file`]: #method.find_minimal_partial_path_set_in_file
+    pub fn find_partial_paths_in_file<F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: &PartialPathsConfig,
+        cancellation_flag: &dyn CancellationFlag,
         mut visit: F,
-    ) -> Result<Stats, CancellationError>
+    ) -> Result<Stats, PartialPathsCancelled>
     where
         F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
     {
-        fn as_complete_as_necessary(graph: &StackGraph, path: &PartialPath) -> bool {
-            path.starts_at_endpoint(graph)
-                && (path.ends_at_endpoint(graph) || path.ends_in_jump(graph))
-        }
+        let as_complete_as_necessary = |graph: &StackGraph, path: &PartialPath| {
+            config.is_endpoint(graph, path.start_node)
+                && (config.is_endpoint(graph, path.end_node) || path.ends_in_jump(graph))
+        };
+        let extend = |graph: &StackGraph, _: &mut PartialPaths, path: &PartialPath| {
+            if config.minimal {
+                !as_complete_as_necessary(graph, path)
+            } else {
+                !(graph[path.end_node].is_root() || path.ends_in_jump(graph))
+            }
+        };
 
-        let initial_paths = graph
+        let mut progress = PartialPathsProgress::default();
+        let mut initial_paths = Vec::new();
+        for node in graph
             .nodes_for_file(file)
             .chain(std::iter::once(StackGraph::root_node()))
-            .filter(|node| graph[*node].is_endpoint())
-            .map(|node| PartialPath::from_node(graph, partials, node))
-            .collect::<Vec<_>>();
+        {
+            if let Err(error) = cancellation_flag.check("collecting initial partial paths") {
+                return Err(PartialPathsCancelled {
+                    error,
+                    stats: Stats::default(),
+                    progress,
+                });
+            }
+            if config.is_endpoint(graph, node) {
+                initial_paths.push(PartialPath::from_node(graph, partials, node));
+            }
+        }
+        progress.initial_paths = initial_paths.len();
         let mut stitcher =
             ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
-        config.apply(&mut stitcher);
+        config.stitcher.apply(&mut stitcher);
         stitcher.set_check_only_join_nodes(true);
 
         let mut accepted_path_length = FrequencyDistribution::default();
         while !stitcher.is_complete() {
-            cancellation_flag.check("finding complete partial paths")?;
-            stitcher.process_next_phase(
+            progress.phases += 1;
+            let result = stitcher.process_next_phase_with_cancellation(
                 &mut GraphEdgeCandidates::new(graph, partials, Some(file)),
-                |g, _ps, p| !as_complete_as_necessary(g, p),
+                &extend,
+                cancellation_flag,
             );
             for path in stitcher.previous_phase_partial_paths() {
                 if as_complete_as_necessary(graph, path) {
                     accepted_path_length.record(path.edges.len());
+                    progress.accepted_paths += 1;
                     visit(graph, partials, path);
                 }
             }
+            if let Err(error) = result {
+                progress.pending_paths = stitcher.pending_paths();
+                return Err(PartialPathsCancelled {
+                    error,
+                    stats: Stats {
+                        accepted_path_length,
+                        ..stitcher.into_stats()
+                    },
+                    progress,
+                });
+            }
+        }
+
+        Ok(Stats {
+            accepted_path_length,
+            ..stitcher.into_stats()
+        })
+    }
+
+    /// Pre-resolves the references of a file that can only resolve to definitions in the same
+    /// file, calling the `visit` closure for each one with the complete paths from the reference
+    /// to its definitions.  The paths may be empty, if the reference cannot be resolved at all.
+    /// References that have a path that leaves the file, because it reaches the root node, a
+    /// _jump to scope_ node, or a node of another file, are not visited, because they need to be
+    /// resolved at query time.  The same holds for references that have a
+    /// [speculative][PartialPath::is_speculative] path, or a path that is not
+    /// [certain][PartialPath::confidence], so that such definitions can be told apart at query
+    /// time.
+    ///
+    /// This is the optional pass that is enabled by
+    /// [`PartialPathsConfig::with_local_resolution`][].  If it is disabled, no references are
+    /// visited, so that indexers can call this function unconditionally.
+    pub fn find_local_resolutions_in_file<F>(
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        file: Handle<File>,
+        config: &PartialPathsConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<Stats, CancellationError>
+    where
+        F: FnMut(&StackGraph, &mut PartialPaths, Handle<Node>, &[PartialPath]),
+    {
+        if !config.local_resolution {
+            return Ok(Stats::default());
+        }
+        let leaves_file = |graph: &StackGraph, path: &PartialPath| {
+            graph[path.end_node].is_root()
+                || path.ends_in_jump(graph)
+                || graph[path.end_node].file() != Some(file)
+        };
+
+        let references = graph
+            .nodes_for_file(file)
+            .filter(|node| graph[*node].is_reference())
+            .collect::<Vec<_>>();
+        let initial_paths = references
+            .iter()
+            .map(|node| {
+                let mut path = PartialPath::from_node(graph, partials, *node);
+                path.eliminate_precondition_stack_variables(partials);
+                path
+            })
+            .collect::<Vec<_>>();
+        let mut stitcher =
+            ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
+        config.stitcher.apply(&mut stitcher);
+        stitcher.set_check_only_join_nodes(true);
+
+        let mut non_local = HashSet::new();
+        let mut complete_paths = HashMap::<_, Vec<_>>::new();
+        let mut accepted_path_length = FrequencyDistribution::default();
+        while !stitcher.is_complete() {
+            cancellation_flag.check("finding local resolutions")?;
+            stitcher.process_next_phase(
+                &mut GraphEdgeCandidates::new(graph, partials, None),
+                |graph, _, path| !leaves_file(graph, path),
+            );
+            for path in stitcher.previous_phase_partial_paths() {
+                if leaves_file(graph, path)
+                    || path.is_speculative(graph, partials)
+                    || !matches!(path.confidence(graph, partials), Ok(None))
+                {
+                    non_local.insert(path.start_node);
+                    continue;
+                }
+                if path.is_complete(graph) {
+                    complete_paths
+                        .entry(path.start_node)
+                        .or_default()
+                        .push(path.clone());
+                }
+                // A path that arrives at a node also arrives at all of its aliases, so it is
+                // complete if it would be complete at any of them.
+                for alias in graph.node_aliases(path.end_node) {
+                    let mut alias_path = path.clone();
+                    if alias_path.resolve_to_alias(graph, partials, *alias).is_ok()
+                        && alias_path.is_complete(graph)
+                    {
+                        complete_paths
+                            .entry(path.start_node)
+                            .or_default()
+                            .push(alias_path);
+                    }
+                }
+            }
+        }
+
+        for reference in references {
+            if non_local.contains(&reference) {
+                continue;
+            }
+            let paths = complete_paths.remove(&reference).unwrap_or_default();
+            for path in &paths {
+                accepted_path_length.record(path.edges.len());
+            }
+            visit(graph, partials, reference, &paths);
         }
 
         Ok(Stats {
@@ -1387,6 +2502,98 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
         starting_nodes: I,
         config: StitcherConfig,
         cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, Err>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        Self::find_all_complete_partial_paths_with_extension(
+            candidates,
+            starting_nodes,
+            config,
+            &mut NoResolutionExtension,
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds all complete partial paths that are reachable from a set of starting nodes, like
+    /// [`find_all_complete_partial_paths`][], consulting the resolution extension about each
+    /// extension of a path, and each complete path before calling the `visit` closure on it.
+    ///
+    /// [`find_all_complete_partial_paths`]: #method.find_all_complete_partial_paths
+    pub fn find_all_complete_partial_paths_with_extension<I, F, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+        extension: &mut dyn ResolutionExtension,
+        cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, Err>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        Self::find_all_complete_partial_paths_inner(
+            candidates,
+            starting_nodes,
+            config,
+            extension,
+            None,
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Finds all complete partial paths that are reachable from a set of starting nodes, like
+    /// [`find_all_complete_partial_paths_with_extension`][], reporting the internals of stitching
+    /// to the instrumentation.
+    ///
+    /// [`find_all_complete_partial_paths_with_extension`]: #method.find_all_complete_partial_paths_with_extension
+    pub fn find_all_complete_partial_paths_with_instrumentation<I, F, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+        extension: &mut dyn ResolutionExtension,
+        instrumentation: Arc<dyn Instrumentation>,
+        cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<Stats, Err>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        Self::find_all_complete_partial_paths_inner(
+            candidates,
+            starting_nodes,
+            config,
+            extension,
+            Some(instrumentation),
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    fn find_all_complete_partial_paths_inner<I, F, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+        extension: &mut dyn ResolutionExtension,
+        instrumentation: Option<Arc<dyn Instrumentation>>,
+        cancellation_flag: &dyn CancellationFlag,
         mut visit: F,
     ) -> Result<Stats, Err>
     where
@@ -1411,6 +2618,7 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
         config.apply(&mut stitcher);
         stitcher.set_check_only_join_nodes(true);
+        stitcher.set_instrumentation(instrumentation.clone());
 
         let mut accepted_path_length = FrequencyDistribution::default();
         while !stitcher.is_complete() {
@@ -1418,14 +2626,47 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             for path in stitcher.previous_phase_partial_paths() {
                 candidates.load_forward_candidates(path, cancellation_flag)?;
             }
-            stitcher.process_next_phase(candidates, |_, _, _| true);
+            stitcher.process_next_phase_with_extension(candidates, |_, _, _| true, extension);
             let (graph, partials, _) = candidates.get_graph_partials_and_db();
-            for path in stitcher.previous_phase_partial_paths() {
-                if path.is_complete(graph) {
+            let mut resolutions = Vec::new();
+            for (index, path) in stitcher.previous_phase_partial_paths().enumerate() {
+                let mut is_resolution = false;
+                if path.is_complete(graph) && extension.accept_complete_path(graph, partials, path)
+                {
                     accepted_path_length.record(path.edges.len());
+                    if let Some(instrumentation) = &instrumentation {
+                        instrumentation.result_emitted(graph, partials, path);
+                    }
                     visit(graph, partials, path);
+                    is_resolution = true;
+                }
+                // A path that arrives at a node also arrives at all of its aliases, so it is
+                // complete if it would be complete at any of them.
+                for alias in graph.node_aliases(path.end_node) {
+                    let mut alias_path = path.clone();
+                    if alias_path.resolve_to_alias(graph, partials, *alias).is_ok()
+                        && alias_path.is_complete(graph)
+                        && extension.accept_complete_path(graph, partials, &alias_path)
+                    {
+                        accepted_path_length.record(alias_path.edges.len());
+                        if let Some(instrumentation) = &instrumentation {
+                            instrumentation.result_emitted(graph, partials, &alias_path);
+                        }
+                        visit(graph, partials, &alias_path);
+                        is_resolution = true;
+                    }
+                }
+                if is_resolution {
+                    resolutions.push(index);
                 }
             }
+            for index in resolutions {
+                let appended = stitcher
+                    .previous_phase_appended_candidates(index)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                candidates.record_resolution(&appended);
+            }
         }
 
         Ok(Stats {
@@ -1433,6 +2674,158 @@ impl<H: Clone> ForwardPartialPathStitcher<H> {
             ..stitcher.into_stats()
         })
     }
+
+    /// Finds a complete partial path that is reachable from a set of starting nodes, returning as
+    /// soon as one is found, or `None` if there is none.  This is much cheaper than
+    /// [`find_all_complete_partial_paths`][] when any result will do, such as for go-to-definition
+    /// in an editor.
+    ///
+    /// Paths are extended [best-first][StitchingStrategy::BestFirst], regardless of the strategy
+    /// in the configuration, and candidates are only loaded for the paths that are extended.  Of
+    /// the complete paths found in the same phase, the first one that is not shadowed by any of
+    /// the others under the configured [shadowing policy][ShadowingPolicy] is returned.  Complete
+    /// paths that are found in later phases are not considered, so a path that would shadow the
+    /// returned path may exist.
+    ///
+    /// [`find_all_complete_partial_paths`]: #method.find_all_complete_partial_paths
+    pub fn find_first_complete_partial_path<I, A, Db, C, Err>(
+        candidates: &mut C,
+        starting_nodes: I,
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Option<PartialPath>, Err>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        Err: std::convert::From<CancellationError>,
+    {
+        let (graph, partials, _) = candidates.get_graph_partials_and_db();
+        let initial_paths = starting_nodes
+            .into_iter()
+            .filter(|n| graph[*n].is_reference())
+            .map(|n| {
+                let mut p = PartialPath::from_node(graph, partials, n);
+                p.eliminate_precondition_stack_variables(partials);
+                p
+            })
+            .collect::<Vec<_>>();
+        let mut stitcher =
+            ForwardPartialPathStitcher::from_partial_paths(graph, partials, initial_paths);
+        config.apply(&mut stitcher);
+        stitcher.set_strategy(StitchingStrategy::BestFirst);
+        stitcher.set_check_only_join_nodes(true);
+
+        let mut complete_paths = Vec::new();
+        while !stitcher.is_complete() {
+            cancellation_flag.check("finding first complete partial path")?;
+            for path in stitcher.previous_phase_partial_paths() {
+                candidates.load_forward_candidates(path, cancellation_flag)?;
+            }
+            stitcher.process_next_phase(candidates, |_, _, _| true);
+            let (graph, partials, _) = candidates.get_graph_partials_and_db();
+            for path in stitcher.previous_phase_partial_paths() {
+                if path.is_complete(graph) {
+                    complete_paths.push(path.clone());
+                }
+                for alias in graph.node_aliases(path.end_node) {
+                    let mut alias_path = path.clone();
+                    if alias_path.resolve_to_alias(graph, partials, *alias).is_ok()
+                        && alias_path.is_complete(graph)
+                    {
+                        complete_paths.push(alias_path);
+                    }
+                }
+            }
+            if !complete_paths.is_empty() {
+                config.shadowing_policy().remove_shadowed_paths(
+                    graph,
+                    partials,
+                    &mut complete_paths,
+                );
+                return Ok(complete_paths.into_iter().next());
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<H: Clone> ForwardPartialPathStitcher<H> {
+    /// Finds the definitions of each segment of a chain of symbols, such as a member expression,
+    /// calling the `visit` closure with the index of the segment for each path that reaches a
+    /// definition.
+    ///
+    /// The symbols are given in the order in which they appear in the source, following the
+    /// conventions of the graph, e.g., `foo`, `.`, `bar` for `foo.bar`.  For every prefix of the
+    /// chain, we construct a partial path that starts at `scope` with the symbols of the prefix
+    /// pushed onto the symbol stack, and stitch it until it reaches a definition that consumes all
+    /// of them.  The paths for a prefix are reported with the index of its last segment.
+    /// Prefixes that end in a separator, like `.`, typically have no definitions.
+    ///
+    /// The starting scope must be a node that the candidates have paths for, such as the root
+    /// node or an exported scope when stitching with a [`Database`][], or any node when stitching
+    /// with [`GraphEdgeCandidates`][].
+    pub fn find_definitions_of_symbol_chain<A, Db, C, F, Err>(
+        candidates: &mut C,
+        scope: Handle<Node>,
+        symbols: &[Handle<Symbol>],
+        config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<Stats, Err>
+    where
+        A: Appendable,
+        Db: ToAppendable<H, A>,
+        C: ForwardCandidates<H, A, Db, Err>,
+        F: FnMut(&StackGraph, &mut PartialPaths, usize, &PartialPath),
+        Err: std::convert::From<CancellationError>,
+    {
+        let mut stats = Stats::default();
+        for segment in 0..symbols.len() {
+            let (graph, partials, _) = candidates.get_graph_partials_and_db();
+            let mut initial_path = PartialPath::from_node(graph, partials, scope);
+            initial_path.eliminate_precondition_stack_variables(partials);
+            for symbol in &symbols[..=segment] {
+                initial_path.symbol_stack_postcondition.push_back(
+                    partials,
+                    PartialScopedSymbol {
+                        symbol: *symbol,
+                        scopes: ControlledOption::none(),
+                    },
+                );
+            }
+            let mut stitcher = ForwardPartialPathStitcher::from_partial_paths(
+                graph,
+                partials,
+                std::iter::once(initial_path),
+            );
+            config.apply(&mut stitcher);
+            stitcher.set_check_only_join_nodes(true);
+
+            let mut accepted_path_length = FrequencyDistribution::default();
+            while !stitcher.is_complete() {
+                cancellation_flag.check("finding definitions of symbol chain")?;
+                for path in stitcher.previous_phase_partial_paths() {
+                    candidates.load_forward_candidates(path, cancellation_flag)?;
+                }
+                stitcher.process_next_phase(candidates, |_, _, _| true);
+                let (graph, partials, _) = candidates.get_graph_partials_and_db();
+                for path in stitcher.previous_phase_partial_paths() {
+                    if path.ends_at_definition(graph) {
+                        accepted_path_length.record(path.edges.len());
+                        visit(graph, partials, segment, path);
+                    }
+                }
+            }
+
+            stats += Stats {
+                accepted_path_length,
+                ..stitcher.into_stats()
+            };
+        }
+        Ok(stats)
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1503,6 +2896,16 @@ pub struct StitcherConfig {
     detect_similar_paths: bool,
     /// Collect statistics about path stitching.
     collect_stats: bool,
+    /// Decides which of two paths shadows the other.
+    shadowing_policy: ShadowingPolicy,
+    /// Decides which of two similar paths is kept.
+    path_comparison: PathComparison,
+    /// The order in which partial paths are extended.
+    strategy: StitchingStrategy,
+    /// The kinds of nodes that paths are not extended to.
+    forbidden_node_kinds: NodeKindSet,
+    /// The maximum number of times that paths leave the root node.
+    max_root_crossings: Option<usize>,
 }
 
 impl StitcherConfig {
@@ -1523,12 +2926,74 @@ impl StitcherConfig {
         self.collect_stats = collect_stats;
         self
     }
+
+    pub fn shadowing_policy(&self) -> ShadowingPolicy {
+        self.shadowing_policy
+    }
+
+    pub fn with_shadowing_policy(mut self, shadowing_policy: ShadowingPolicy) -> Self {
+        self.shadowing_policy = shadowing_policy;
+        self
+    }
+
+    pub fn path_comparison(&self) -> PathComparison {
+        self.path_comparison
+    }
+
+    pub fn with_path_comparison(mut self, path_comparison: PathComparison) -> Self {
+        self.path_comparison = path_comparison;
+        self
+    }
+
+    pub fn strategy(&self) -> StitchingStrategy {
+        self.strategy
+    }
+
+    pub fn with_strategy(mut self, strategy: StitchingStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn forbidden_node_kinds(&self) -> NodeKindSet {
+        self.forbidden_node_kinds
+    }
+
+    /// Forbids paths from being extended to nodes of the given kinds, in addition to the kinds
+    /// that are already forbidden.  For example, forbidding [root nodes][NodeKind::Root] restricts
+    /// lookups to a single file.  See [`ForwardPartialPathStitcher::set_forbidden_node_kinds`][]
+    /// for which nodes of a path are checked.
+    pub fn forbid_node_kinds<I>(mut self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = NodeKind>,
+    {
+        for kind in kinds {
+            self.forbidden_node_kinds.insert(kind);
+        }
+        self
+    }
+
+    pub fn max_root_crossings(&self) -> Option<usize> {
+        self.max_root_crossings
+    }
+
+    /// Sets the maximum number of times that paths leave the root node.  For example, allowing a
+    /// single crossing restricts lookups to definitions that are reachable with one hop from the
+    /// file of the reference to another file.
+    pub fn with_max_root_crossings(mut self, max_root_crossings: Option<usize>) -> Self {
+        self.max_root_crossings = max_root_crossings;
+        self
+    }
 }
 
 impl StitcherConfig {
-    fn apply<H>(&self, stitcher: &mut ForwardPartialPathStitcher<H>) {
+    pub(crate) fn apply<H>(&self, stitcher: &mut ForwardPartialPathStitcher<H>) {
         stitcher.set_similar_path_detection(self.detect_similar_paths);
         stitcher.set_collect_stats(self.collect_stats);
+        stitcher.set_shadowing_policy(self.shadowing_policy);
+        stitcher.set_path_comparison(self.path_comparison);
+        stitcher.set_strategy(self.strategy);
+        stitcher.set_forbidden_node_kinds(self.forbidden_node_kinds);
+        stitcher.set_max_root_crossings(self.max_root_crossings);
     }
 }
 
@@ -1537,6 +3002,277 @@ impl Default for StitcherConfig {
         Self {
             detect_similar_paths: true,
             collect_stats: false,
+            shadowing_policy: ShadowingPolicy::default(),
+            path_comparison: PathComparison::default(),
+            strategy: StitchingStrategy::default(),
+            forbidden_node_kinds: NodeKindSet::new(),
+            max_root_crossings: None,
+        }
+    }
+}
+
+/// Decides whether one complete or partial path shadows another, so that only the unshadowed
+/// paths are reported as the bindings of a reference.  Different languages disambiguate bindings
+/// differently, so the policy is part of the [stitcher configuration][StitcherConfig].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ShadowingPolicy {
+    /// A path shadows another if both follow the same edges up to a node, and the path then
+    /// follows an edge with a higher precedence than the other.  This is the relation implemented
+    /// by [`PartialPath::shadows`][], and the default.
+    StrictPrecedence,
+    /// A path that ends in the file it starts in shadows a path that ends in another file.  Paths
+    /// that are both file-local, or both not file-local, are compared by precedence, as in
+    /// [`StrictPrecedence`][Self::StrictPrecedence].
+    FileLocalFirst,
+    /// No path shadows another, and all paths are reported.
+    None,
+}
+
+impl ShadowingPolicy {
+    /// Returns whether `path` shadows `other` under this policy.  Note that shadowing is not
+    /// commutative — if path A shadows path B, the reverse is not true.
+    pub fn shadows(
+        self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        path: &PartialPath,
+        other: &PartialPath,
+    ) -> bool {
+        match self {
+            Self::StrictPrecedence => path.shadows(partials, other),
+            Self::FileLocalFirst => {
+                let is_file_local =
+                    |p: &PartialPath| graph[p.start_node].file() == graph[p.end_node].file();
+                match (is_file_local(path), is_file_local(other)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => path.shadows(partials, other),
+                }
+            }
+            Self::None => false,
+        }
+    }
+
+    /// Removes the paths that are shadowed by any of the other paths under this policy.  The paths
+    /// are typically all complete paths found for a single reference.
+    pub fn remove_shadowed_paths(
+        self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        paths: &mut Vec<PartialPath>,
+    ) {
+        if self == Self::None {
+            return;
+        }
+        let shadowed = paths
+            .iter()
+            .map(|path| {
+                paths
+                    .iter()
+                    .any(|other| self.shadows(graph, partials, other, path))
+            })
+            .collect::<Vec<_>>();
+        let mut shadowed = shadowed.into_iter();
+        paths.retain(|_| !shadowed.next().unwrap());
+    }
+}
+
+impl Default for ShadowingPolicy {
+    fn default() -> Self {
+        Self::StrictPrecedence
+    }
+}
+
+/// Decides which of two similar paths is kept by similar path detection, which otherwise has to
+/// consider an exponential number of paths in some graphs.  Paths are similar if they have the
+/// same start and end node, and the same pre- and postconditions, but they can differ in the
+/// edges they follow.  The strategy is part of the [stitcher configuration][StitcherConfig].
+///
+/// Under every strategy, paths that are equally good are compared by the [shadowing
+/// policy][ShadowingPolicy], and if neither path shadows the other, the path that was found first
+/// is kept.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PathComparison {
+    /// Keeps the path that follows fewer edges.
+    PreferShorter,
+    /// Keeps the path that shadows the other, and otherwise the path that was found first.  This
+    /// is the default.
+    PreferHigherPrecedence,
+    /// Keeps the path that passes through the root node fewer times, which prefers paths that
+    /// stay within files over paths that go through imports or exports.
+    PreferFewerRootCrossings,
+}
+
+impl PathComparison {
+    /// Compares two paths, and returns [`Ordering::Less`][] if `path` is better than `other`,
+    /// [`Ordering::Greater`][] if it is worse, and [`Ordering::Equal`][] if it is neither.  Returns
+    /// `None` if the paths are not similar, in which case both paths should be kept.
+    pub fn compare(
+        self,
+        graph: &StackGraph,
+        partials: &mut PartialPaths,
+        shadowing_policy: ShadowingPolicy,
+        path: &PartialPath,
+        other: &PartialPath,
+    ) -> Option<Ordering> {
+        if !path.equals(partials, other) {
+            return None;
+        }
+        let ordering = match self {
+            Self::PreferShorter => path.edges.len().cmp(&other.edges.len()),
+            Self::PreferHigherPrecedence => Ordering::Equal,
+            Self::PreferFewerRootCrossings => {
+                Self::root_crossings(partials, path).cmp(&Self::root_crossings(partials, other))
+            }
+        };
+        if ordering != Ordering::Equal {
+            Some(ordering)
+        } else if shadowing_policy.shadows(graph, partials, path, other) {
+            Some(Ordering::Less)
+        } else if shadowing_policy.shadows(graph, partials, other, path) {
+            Some(Ordering::Greater)
+        } else {
+            Some(Ordering::Equal)
+        }
+    }
+
+    /// Returns the number of times a path passes through the root node.
+    fn root_crossings(partials: &mut PartialPaths, path: &PartialPath) -> usize {
+        let end = (path.end_node == StackGraph::root_node()) as usize;
+        end + path
+            .edges
+            .iter(partials)
+            .filter(|edge| edge.source_node_id.is_root())
+            .count()
+    }
+}
+
+impl Default for PathComparison {
+    fn default() -> Self {
+        Self::PreferHigherPrecedence
+    }
+}
+
+/// The error that is returned when computing the partial paths of a file is cancelled.  The
+/// partial paths that were found before the computation was cancelled have been visited already.
+#[derive(Clone, Debug, Error)]
+#[error("{error}")]
+pub struct PartialPathsCancelled {
+    /// The cancellation error, which identifies the checkpoint at which the computation stopped.
+    pub error: CancellationError,
+    /// The statistics of the computation up to the cancellation, if they were collected.
+    pub stats: Stats,
+    /// How far the computation got.
+    pub progress: PartialPathsProgress,
+}
+
+impl From<PartialPathsCancelled> for CancellationError {
+    fn from(cancelled: PartialPathsCancelled) -> Self {
+        cancelled.error
+    }
+}
+
+/// The progress of computing the partial paths of a file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PartialPathsProgress {
+    /// The number of partial paths that stitching started from.
+    pub initial_paths: usize,
+    /// The number of stitching phases that were started.
+    pub phases: usize,
+    /// The number of partial paths that were found and visited.
+    pub accepted_paths: usize,
+    /// The number of partial paths that were found, but not extended yet.
+    pub pending_paths: usize,
+}
+
+impl Display for PartialPathsProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} paths found in {} phases, {} paths pending",
+            self.accepted_paths, self.phases, self.pending_paths
+        )
+    }
+}
+
+/// Configuration for computing the partial paths of a file, which determines the trade-off
+/// between the work done at index time and the work done at query time.
+#[derive(Clone, Debug)]
+pub struct PartialPathsConfig {
+    /// Only compute the shortest paths between endpoints.
+    minimal: bool,
+    /// Additional nodes at which partial paths start and end.
+    join_nodes: HashSet<Handle<Node>>,
+    /// The configuration of the stitcher used to compute the partial paths.
+    stitcher: StitcherConfig,
+    /// Pre-resolve references that can only resolve within the file.
+    local_resolution: bool,
+}
+
+impl PartialPathsConfig {
+    pub fn minimal(&self) -> bool {
+        self.minimal
+    }
+
+    /// Sets whether partial paths end at the first endpoint they reach.  If not, paths are also
+    /// extended beyond intermediate endpoints, which increases the number of partial paths, but
+    /// reduces the number of paths that must be stitched together at query time.
+    pub fn with_minimal(mut self, minimal: bool) -> Self {
+        self.minimal = minimal;
+        self
+    }
+
+    pub fn join_nodes(&self) -> impl Iterator<Item = Handle<Node>> + '_ {
+        self.join_nodes.iter().copied()
+    }
+
+    /// Adds nodes at which partial paths start and end, in addition to the regular endpoints.
+    /// Splitting paths at nodes that many paths go through reduces the number of partial paths
+    /// computed for large files, at the cost of more stitching at query time.
+    pub fn with_join_nodes<I>(mut self, join_nodes: I) -> Self
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+    {
+        self.join_nodes.extend(join_nodes);
+        self
+    }
+
+    pub fn local_resolution(&self) -> bool {
+        self.local_resolution
+    }
+
+    /// Sets whether references that can only resolve to definitions in the same file are
+    /// resolved when the partial paths of the file are computed, by
+    /// [`ForwardPartialPathStitcher::find_local_resolutions_in_file`][].  Storing their
+    /// resolutions lets queries for purely local names skip path stitching.  This is disabled by
+    /// default.
+    pub fn with_local_resolution(mut self, local_resolution: bool) -> Self {
+        self.local_resolution = local_resolution;
+        self
+    }
+
+    pub fn stitcher_config(&self) -> StitcherConfig {
+        self.stitcher
+    }
+
+    pub fn with_stitcher_config(mut self, stitcher: StitcherConfig) -> Self {
+        self.stitcher = stitcher;
+        self
+    }
+
+    /// Returns whether partial paths may start or end at the given node.
+    pub fn is_endpoint(&self, graph: &StackGraph, node: Handle<Node>) -> bool {
+        graph[node].is_endpoint() || self.join_nodes.contains(&node)
+    }
+}
+
+impl Default for PartialPathsConfig {
+    fn default() -> Self {
+        Self {
+            minimal: true,
+            join_nodes: HashSet::new(),
+            stitcher: StitcherConfig::default(),
+            local_resolution: false,
         }
     }
 }
@@ -24,13 +24,16 @@
 //!
 //! ### Database Schema
 //!
-//! The database has three main tables:
+//! The database has five main tables:
 //!
 //! - **`graphs`**: Stores serialized stack graph data per file
 //!   - `file`: File path (primary key)
 //!   - `tag`: Content hash or version identifier
 //!   - `error`: Error message if indexing failed
 //!   - `value`: Serialized graph data (bincode blob)
+//!   - `language`: Language of the file, if known
+//!   - `incomplete`: Why the stored partial paths are incomplete, if computing them was
+//!     cancelled
 //!
 //! - **`file_paths`**: Stores partial paths that start/end within a file
 //!   - `file`: The file this path belongs to
@@ -42,6 +45,36 @@
 //!   - `symbol_stack`: Serialized symbol stack state for matching
 //!   - `value`: Serialized partial path (bincode blob)
 //!
+//! - **`file_dependencies`**: Records which other files a file's resolutions depend on
+//!   - `file`: The file whose references were resolved
+//!   - `dependency`: A file whose partial paths participated in those resolutions
+//!
+//! - **`file_globals`**: Records the global variables a file's graph was built with
+//!   - `file`: The file that was indexed
+//!   - `name`: The name of the global variable
+//!   - `value`: The value of the global variable
+//!
+//! - **`root_symbols`**: Records the symbols a file exports and imports through the root node
+//!   - `file`: The file that was indexed
+//!   - `symbol`: The first symbol of the symbol stack at the root node
+//!   - `direction`: `export` for root paths into the file, `import` for paths from the file to
+//!     the root node
+//!
+//! - **`local_resolutions`**: Stores the definitions of references that can only resolve within
+//!   their own file, if they were pre-resolved when the file was indexed
+//!   - `file`: The file of the references and definitions
+//!   - `value`: Serialized local IDs of the references and their definitions (bincode blob)
+//!
+//! - **`parse_errors`**: Records the regions of a file that contained parse errors, if its graph
+//!   was built from a partially parsed file (see
+//!   [`StackGraph::add_parse_error`][crate::graph::StackGraph::add_parse_error])
+//!   - `file`: The file that was indexed
+//!   - `value`: Serialized source spans of the regions (bincode blob)
+//!
+//! - **`history`**, **`history_file_paths`**, **`history_root_paths`**: Record every version of
+//!   the graphs and partial paths of files, if the database is append-only (see
+//!   [History](#history))
+//!
 //! ### Two-Phase API
 //!
 //! The module provides separate reader and writer types:
@@ -56,6 +89,9 @@
 //!   - Check file status
 //!   - Efficient filtering
 //!
+//! For tests and short-lived sessions, [`memory::MemoryStorage`][] provides the operations of both
+//! types without requiring a database file.
+//!
 //! This separation ensures safe concurrent access (multiple readers, single writer).
 //!
 //! ## Basic Usage
@@ -167,6 +203,46 @@
 //! }
 //! ```
 //!
+//! ## Dependency Tracking
+//!
+//! Cached partial paths only depend on the file they belong to, but resolved references depend on
+//! all files whose partial paths were stitched together to produce them. After resolving the
+//! references of a file, record the files that participated in the resulting paths:
+//!
+//! ```rust,ignore
+//! writer.store_dependencies_for_file(&graph, file, &mut partials, &complete_paths)?;
+//! ```
+//!
+//! When a file changes, only the files that depended on it need to be re-resolved:
+//!
+//! ```rust,ignore
+//! for dependent in reader.dependents_of_file(Path::new("src/lib.rs"))? {
+//!     // re-resolve references in `dependent`
+//! }
+//! ```
+//!
+//! ## History
+//!
+//! A database can be made append-only with [`SQLiteWriter::set_append_only`][].  Every write that
+//! changes the graph or partial paths of a file then also records the new state of the file as a
+//! new version, and removing a file records its deletion.  The current state is still available
+//! as before, but a reader can query the index as it existed at an earlier point in time:
+//!
+//! ```rust,ignore
+//! writer.set_append_only(true)?;
+//! writer.set_commit(Some("4f2a9c1"))?;
+//! // ... index files ...
+//!
+//! let mut reader = SQLiteReader::open("graphs.db")?;
+//! reader.as_of(&PointInTime::Commit("4f2a9c1".to_string()))?;
+//! // ... query the index as it was after indexing commit 4f2a9c1 ...
+//! reader.latest()?;
+//! ```
+//!
+//! Only graphs and partial paths are versioned.  Dependencies, globals, root symbols, and
+//! resolutions of files, including local resolutions, are not, and a reader that queries an earlier point in time does
+//! not see them.
+//!
 //! ## Performance Considerations
 //!
 //! ### WAL Mode
@@ -183,12 +259,29 @@
 //! ```rust,ignore
 //! // Batch stores for better performance
 //! let mut batch = writer.batch_writer()?;
-//! for file in files {
-//!     batch.store_result_for_file(&graph, &file, &tag, Ok(()))?;
+//! for (graph, file, tag, partials, paths) in results {
+//!     batch.store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
 //! }
 //! batch.commit()?;  // Single transaction
 //! ```
 //!
+//! ### Blob Codecs
+//!
+//! Stored values are encoded by the [codec][codec::BlobCodec] that the database was created with.
+//! Graphs are passed to the codec as they are, while the other values are encoded with bincode,
+//! and the codec transforms their encoding.  Databases can be made considerably smaller with the
+//! zstd codec, which requires the `zstd` feature, at the cost of compressing and decompressing
+//! every value:
+//!
+//! ```rust,ignore
+//! let writer = SQLiteWriter::open_with_codec("graphs.db", Arc::new(ZstdCodec::default()))?;
+//! ```
+//!
+//! Graphs can be encoded with rkyv instead by the rkyv codec, which requires the `rkyv` feature.
+//!
+//! The codec is recorded in the database, and [`SQLiteReader::open`][] and
+//! [`SQLiteWriter::open`][] use it when they open the database again.
+//!
 //! ### Selective Loading
 //!
 //! Only load what you need:
@@ -225,6 +318,15 @@
 //! - Opening an old database returns `StorageError::IncorrectVersion`
 //! - You must recreate the database or migrate data
 //!
+//! Databases also record the version of this crate that created them, the time they were
+//! created, and the cargo features that were enabled, as properties in a **`properties`**
+//! key/value table.  Indexers can [record][SQLiteWriter::set_language_rules] the rules they
+//! indexed each language with, and other properties of their own.  All properties are returned by
+//! [`SQLiteReader::metadata`][], so that tools can refuse or warn about databases that were
+//! created by an incompatible version, or indexed with different rules.  When different rules are
+//! recorded for a language, all files of the language are
+//! [invalidated][SQLiteWriter::invalidate_language], and are indexed again.
+//!
 //! ## Cargo Features
 //!
 //! This module requires the `storage` cargo feature:
@@ -234,6 +336,14 @@
 //! stack-graphs = { version = "...", features = ["storage"] }
 //! ```
 //!
+//! Databases can be encrypted at rest with [SQLCipher][], by enabling the `encryption` feature
+//! and opening them with [`SQLiteWriter::open_encrypted`][] and
+//! [`SQLiteReader::open_encrypted`][].  A database is encrypted with the key it is created with,
+//! and can only be opened again with the same key.  The feature links against the system's
+//! crypto library.
+//!
+//! [SQLCipher]: https://www.zetetic.net/sqlcipher/
+//!
 //! ## See Also
 //!
 //! - [`serde`][crate::serde]: Serialization formats used by storage
@@ -243,21 +353,34 @@
 use bincode::error::DecodeError;
 use bincode::error::EncodeError;
 use itertools::Itertools;
+use lsp_positions::Span;
 use rusqlite::functions::FunctionFlags;
 use rusqlite::types::ValueRef;
 use rusqlite::Connection;
 use rusqlite::OptionalExtension;
 use rusqlite::Params;
 use rusqlite::Statement;
+use rusqlite::Transaction;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 use thiserror::Error;
 
 use crate::arena::Handle;
+use crate::arena::MemoryBudget;
+use crate::arena::OutOfBudget;
 use crate::graph::Degree;
 use crate::graph::File;
+use crate::graph::FileRoots;
+use crate::graph::Fingerprint;
 use crate::graph::Node;
+use crate::graph::NodeID;
 use crate::graph::StackGraph;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
@@ -269,18 +392,63 @@ use crate::stitching::ForwardCandidates;
 use crate::CancellationError;
 use crate::CancellationFlag;
 
-const VERSION: usize = 6;
+#[cfg(feature = "tokio")]
+mod async_reader;
+pub mod chunks;
+pub mod codec;
+mod encoding;
+mod legacy;
+pub mod memory;
+
+#[cfg(feature = "tokio")]
+pub use async_reader::SQLiteReaderAsync;
+
+use codec::builtin_codec;
+use codec::BincodeCodec;
+use codec::BlobCodec;
+use encoding::CompactPartialPath;
+use encoding::PathDictionary;
+use encoding::PathDictionaryBuilder;
+
+/// The version of the database schema.  Databases with a different version cannot be opened,
+/// except that databases of [`LEGACY_VERSION`][] can still be read.
+pub const VERSION: usize = 7;
+
+/// The version of the database schema of the last release, which stored every partial path in
+/// full, instead of in the [compact encoding][encoding].  Databases of this version can be opened
+/// for reading, but not for writing.  They are converted into an in-memory database of the current
+/// version when they are opened, which takes time and memory for large databases.
+pub const LEGACY_VERSION: usize = 6;
 
 const SCHEMA: &str = r#"
         CREATE TABLE metadata (
-            version INTEGER NOT NULL
+            version     INTEGER NOT NULL,
+            append_only INTEGER NOT NULL DEFAULT 0,
+            codec       TEXT NOT NULL DEFAULT 'bincode',
+            generation  INTEGER NOT NULL DEFAULT 0
+        ) STRICT;
+        CREATE TABLE properties (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
         ) STRICT;
         CREATE TABLE graphs (
-            file   TEXT PRIMARY KEY,
-            tag    TEXT NOT NULL,
-            error  TEXT,
-            value  BLOB NOT NULL
+            file        TEXT PRIMARY KEY,
+            tag         TEXT NOT NULL,
+            error       TEXT,
+            value       BLOB NOT NULL,
+            fingerprint TEXT,
+            language    TEXT,
+            incomplete  TEXT
         ) STRICT;
+        CREATE TRIGGER graphs_insert AFTER INSERT ON graphs BEGIN
+            UPDATE metadata SET generation = generation + 1;
+        END;
+        CREATE TRIGGER graphs_update AFTER UPDATE ON graphs BEGIN
+            UPDATE metadata SET generation = generation + 1;
+        END;
+        CREATE TRIGGER graphs_delete AFTER DELETE ON graphs BEGIN
+            UPDATE metadata SET generation = generation + 1;
+        END;
         CREATE TABLE file_paths (
             file     TEXT NOT NULL,
             local_id INTEGER NOT NULL,
@@ -293,14 +461,220 @@ const SCHEMA: &str = r#"
             value        BLOB NOT NULL,
             FOREIGN KEY(file) REFERENCES graphs(file)
         ) STRICT;
+        CREATE TABLE path_dictionaries (
+            file  TEXT PRIMARY KEY,
+            value BLOB NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE file_dependencies (
+            file       TEXT NOT NULL,
+            dependency TEXT NOT NULL,
+            PRIMARY KEY(file, dependency),
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE file_globals (
+            file  TEXT NOT NULL,
+            name  TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY(file, name),
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE local_resolutions (
+            file  TEXT PRIMARY KEY,
+            value BLOB NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE parse_errors (
+            file  TEXT PRIMARY KEY,
+            value BLOB NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE root_symbols (
+            file      TEXT NOT NULL,
+            symbol    TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            PRIMARY KEY(file, symbol, direction),
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE resolutions (
+            file                TEXT NOT NULL,
+            local_id            INTEGER NOT NULL,
+            definition_file     TEXT NOT NULL,
+            definition_local_id INTEGER NOT NULL,
+            definition_tag      TEXT NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE resolution_dependencies (
+            file           TEXT NOT NULL,
+            local_id       INTEGER NOT NULL,
+            dependency     TEXT NOT NULL,
+            dependency_tag TEXT NOT NULL,
+            PRIMARY KEY(file, local_id, dependency),
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE resolved_files (
+            file       TEXT PRIMARY KEY,
+            tag        TEXT NOT NULL,
+            generation INTEGER NOT NULL,
+            FOREIGN KEY(file) REFERENCES graphs(file)
+        ) STRICT;
+        CREATE TABLE history (
+            version     INTEGER PRIMARY KEY,
+            file        TEXT NOT NULL,
+            timestamp   INTEGER NOT NULL,
+            commit_id   TEXT,
+            deleted     INTEGER NOT NULL,
+            tag         TEXT,
+            error       TEXT,
+            value       BLOB,
+            fingerprint TEXT,
+            language    TEXT,
+            incomplete  TEXT
+        ) STRICT;
+        CREATE TABLE history_file_paths (
+            version  INTEGER NOT NULL,
+            local_id INTEGER NOT NULL,
+            value    BLOB NOT NULL,
+            FOREIGN KEY(version) REFERENCES history(version)
+        ) STRICT;
+        CREATE TABLE history_root_paths (
+            version      INTEGER NOT NULL,
+            symbol_stack TEXT NOT NULL,
+            value        BLOB NOT NULL,
+            FOREIGN KEY(version) REFERENCES history(version)
+        ) STRICT;
+        CREATE TABLE history_path_dictionaries (
+            version INTEGER PRIMARY KEY,
+            value   BLOB NOT NULL,
+            FOREIGN KEY(version) REFERENCES history(version)
+        ) STRICT;
+        CREATE TABLE history_commits (
+            commit_id TEXT PRIMARY KEY,
+            version   INTEGER NOT NULL
+        ) STRICT;
     "#;
 
 const INDEXES: &str = r#"
         CREATE INDEX IF NOT EXISTS idx_graphs_file ON graphs(file);
+        CREATE INDEX IF NOT EXISTS idx_graphs_language ON graphs(language);
         CREATE INDEX IF NOT EXISTS idx_file_paths_local_id ON file_paths(file, local_id);
         CREATE INDEX IF NOT EXISTS idx_root_paths_symbol_stack ON root_paths(symbol_stack);
+        CREATE INDEX IF NOT EXISTS idx_file_dependencies_dependency ON file_dependencies(dependency);
+        CREATE INDEX IF NOT EXISTS idx_root_symbols_symbol ON root_symbols(symbol, direction);
+        CREATE INDEX IF NOT EXISTS idx_resolutions_local_id ON resolutions(file, local_id);
+        CREATE INDEX IF NOT EXISTS idx_resolutions_definition_file ON resolutions(definition_file);
+        CREATE INDEX IF NOT EXISTS idx_resolution_dependencies_dependency ON resolution_dependencies(dependency);
+        CREATE INDEX IF NOT EXISTS idx_history_file ON history(file, version);
+        CREATE INDEX IF NOT EXISTS idx_history_timestamp ON history(timestamp);
+        CREATE INDEX IF NOT EXISTS idx_history_file_paths_local_id ON history_file_paths(version, local_id);
+        CREATE INDEX IF NOT EXISTS idx_history_root_paths_symbol_stack ON history_root_paths(symbol_stack);
+    "#;
+
+/// Temporary tables and views that shadow the tables of the database with their state at a given
+/// version of the history.  The version is substituted for `{version}`.  The tables for data that
+/// is not versioned are shadowed by empty temporary tables, so that stores into them succeed but
+/// do not affect the database.
+const HISTORY_SNAPSHOT: &str = r#"
+        CREATE TEMP TABLE history_snapshot AS
+            SELECT file, MAX(version) AS version FROM main.history
+            WHERE version <= {version} GROUP BY file;
+        CREATE INDEX temp.idx_history_snapshot_file ON history_snapshot(file);
+        CREATE TEMP VIEW graphs AS
+            SELECT h.file AS file, h.tag AS tag, h.error AS error, h.value AS value,
+                   h.fingerprint AS fingerprint, h.language AS language,
+                   h.incomplete AS incomplete
+            FROM temp.history_snapshot s JOIN main.history h ON h.version = s.version
+            WHERE NOT h.deleted;
+        CREATE TEMP VIEW file_paths AS
+            SELECT h.file AS file, p.local_id AS local_id, p.value AS value
+            FROM temp.history_snapshot s JOIN main.history h ON h.version = s.version
+            JOIN main.history_file_paths p ON p.version = s.version
+            WHERE NOT h.deleted;
+        CREATE TEMP VIEW root_paths AS
+            SELECT h.file AS file, p.symbol_stack AS symbol_stack, p.value AS value
+            FROM temp.history_snapshot s JOIN main.history h ON h.version = s.version
+            JOIN main.history_root_paths p ON p.version = s.version
+            WHERE NOT h.deleted;
+        CREATE TEMP TABLE file_dependencies (
+            file       TEXT NOT NULL,
+            dependency TEXT NOT NULL,
+            PRIMARY KEY(file, dependency)
+        );
+        CREATE TEMP TABLE file_globals (
+            file  TEXT NOT NULL,
+            name  TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY(file, name)
+        );
+        CREATE TEMP TABLE local_resolutions (
+            file  TEXT PRIMARY KEY,
+            value BLOB NOT NULL
+        );
+        CREATE TEMP TABLE parse_errors (
+            file  TEXT PRIMARY KEY,
+            value BLOB NOT NULL
+        );
+        CREATE TEMP TABLE root_symbols (
+            file      TEXT NOT NULL,
+            symbol    TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            PRIMARY KEY(file, symbol, direction)
+        );
+        CREATE TEMP TABLE resolutions (
+            file                TEXT NOT NULL,
+            local_id            INTEGER NOT NULL,
+            definition_file     TEXT NOT NULL,
+            definition_local_id INTEGER NOT NULL,
+            definition_tag      TEXT NOT NULL
+        );
+        CREATE TEMP TABLE resolution_dependencies (
+            file           TEXT NOT NULL,
+            local_id       INTEGER NOT NULL,
+            dependency     TEXT NOT NULL,
+            dependency_tag TEXT NOT NULL,
+            PRIMARY KEY(file, local_id, dependency)
+        );
+        CREATE TEMP TABLE resolved_files (
+            file       TEXT PRIMARY KEY,
+            tag        TEXT NOT NULL,
+            generation INTEGER NOT NULL
+        );
+    "#;
+
+/// Temporary view that shadows the path dictionaries with their state at a given version of the
+/// history, like [`HISTORY_SNAPSHOT`][].
+const HISTORY_SNAPSHOT_PATH_DICTIONARIES: &str = r#"
+        CREATE TEMP VIEW path_dictionaries AS
+            SELECT h.file AS file, d.value AS value
+            FROM temp.history_snapshot s JOIN main.history h ON h.version = s.version
+            JOIN main.history_path_dictionaries d ON d.version = s.version
+            WHERE NOT h.deleted;
+    "#;
+
+const DROP_HISTORY_SNAPSHOT: &str = r#"
+        DROP VIEW IF EXISTS temp.graphs;
+        DROP VIEW IF EXISTS temp.file_paths;
+        DROP VIEW IF EXISTS temp.root_paths;
+        DROP VIEW IF EXISTS temp.path_dictionaries;
+        DROP TABLE IF EXISTS temp.file_dependencies;
+        DROP TABLE IF EXISTS temp.file_globals;
+        DROP TABLE IF EXISTS temp.local_resolutions;
+        DROP TABLE IF EXISTS temp.parse_errors;
+        DROP TABLE IF EXISTS temp.root_symbols;
+        DROP TABLE IF EXISTS temp.resolutions;
+        DROP TABLE IF EXISTS temp.resolution_dependencies;
+        DROP TABLE IF EXISTS temp.resolved_files;
+        DROP TABLE IF EXISTS temp.history_snapshot;
     "#;
 
+/// The direction of [root symbols][SQLiteReader::exports_of] that a file exports.
+const EXPORT: &str = "export";
+/// The direction of [root symbols][SQLiteReader::imports_of] that a file imports.
+const IMPORT: &str = "import";
+
+/// The maximum number of symbols for which root paths are prefetched in a single query.
+const PREFETCH_BATCH_SIZE: usize = 256;
+
 const PRAGMAS: &str = r#"
         PRAGMA journal_mode = WAL;
         PRAGMA foreign_keys = false;
@@ -317,6 +691,26 @@ pub enum StorageError {
     IncorrectVersion(usize),
     #[error("database does not exist {0}")]
     MissingDatabase(String),
+    #[error("cannot decrypt database {0} with the given key")]
+    IncorrectKey(String),
+    #[error("unknown blob codec {0}")]
+    UnknownCodec(String),
+    #[error("database uses a different blob codec {0}")]
+    IncorrectCodec(String),
+    #[error("blob codec failed: {0}")]
+    Codec(#[from] codec::CodecError),
+    #[error("file does not exist {0}")]
+    MissingFile(String),
+    #[error("invalid fingerprint for file {0}")]
+    InvalidFingerprint(String),
+    #[error("commit does not exist {0}")]
+    MissingCommit(String),
+    #[error("invalid stored partial path for file {0}")]
+    InvalidPath(String),
+    #[error(transparent)]
+    OutOfBudget(#[from] OutOfBudget),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     #[error(transparent)]
     Rusqlite(#[from] rusqlite::Error),
     #[error(transparent)]
@@ -361,9 +755,123 @@ pub struct FileEntry {
     pub path: PathBuf,
     pub tag: String,
     pub status: FileStatus,
+    /// The [language][crate::graph::StackGraph::set_file_language] of the file, if known.
+    pub language: Option<String>,
+}
+
+/// A resolution of a reference to a definition, as stored in the database.  Nodes are identified
+/// by their file and local ID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StoredResolution {
+    pub file: PathBuf,
+    pub local_id: u32,
+    pub definition_file: PathBuf,
+    pub definition_local_id: u32,
+}
+
+/// A point in the history of an append-only database, which can be queried with
+/// [`SQLiteReader::as_of`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PointInTime {
+    /// The state of the index at the given time, in seconds since the Unix epoch.
+    Timestamp(u64),
+    /// The state of the index after storing the files of the given
+    /// [commit][SQLiteWriter::set_commit].
+    Commit(String),
+}
+
+impl From<SystemTime> for PointInTime {
+    fn from(time: SystemTime) -> Self {
+        Self::Timestamp(unix_timestamp(time))
+    }
+}
+
+/// A version of a file, as recorded in the history of an append-only database.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileVersion {
+    /// The version number, which increases with every version that is recorded for any file.
+    pub version: u64,
+    /// The time the version was recorded, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The [commit][SQLiteWriter::set_commit] the version was recorded for, if any.
+    pub commit: Option<String>,
+    /// The tag of the file, or `None` if the file was removed in this version.
+    pub tag: Option<String>,
+}
+
+/// The metadata that describes a database, as returned by [`SQLiteReader::metadata`][].  Tools can
+/// use it to refuse or warn about databases that were created by an incompatible version, or
+/// that were indexed with different rules than the ones they use.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DatabaseMetadata {
+    /// The version of the database schema.
+    pub schema_version: usize,
+    /// The version of this crate that created the database, if recorded.
+    pub crate_version: Option<String>,
+    /// The time the database was created, in seconds since the Unix epoch, if recorded.
+    pub created_at: Option<u64>,
+    /// The cargo features of this crate that were enabled when the database was created.
+    pub build_flags: Vec<String>,
+    /// The name of the [codec][BlobCodec] that blobs are stored with.
+    pub codec: String,
+    /// The [recorded][SQLiteWriter::set_language_rules] rules that the files of each
+    /// [language][crate::graph::StackGraph::set_file_language] were indexed with.
+    pub language_rules: BTreeMap<String, LanguageRules>,
+    /// All recorded properties, including the ones above and the ones that were
+    /// [set][SQLiteWriter::set_property] by tools.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl DatabaseMetadata {
+    /// Returns whether the database was created by this version of the crate.
+    pub fn is_created_by_this_version(&self) -> bool {
+        self.crate_version.as_deref() == Some(CRATE_VERSION)
+    }
+}
+
+/// The stack graph construction rules that the files of a language were indexed with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LanguageRules {
+    /// A hash of the rules, such as a hash of the source of the TSG file.
+    pub hash: String,
+    /// The version of the rules, if known.
+    pub version: Option<String>,
+}
+
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+const CRATE_VERSION_PROPERTY: &str = "crate_version";
+const SCHEMA_VERSION_PROPERTY: &str = "schema_version";
+const CREATED_AT_PROPERTY: &str = "created_at";
+const BUILD_FLAGS_PROPERTY: &str = "build_flags";
+/// The prefix of the properties that record language rules, which are named
+/// `rules.LANGUAGE.hash` and `rules.LANGUAGE.version`.
+const RULES_PROPERTY_PREFIX: &str = "rules.";
+
+/// Returns the cargo features of this crate that are enabled.
+fn build_flags() -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if cfg!(feature = "copious-debugging") {
+        flags.push("copious-debugging");
+    }
+    if cfg!(feature = "encryption") {
+        flags.push("encryption");
+    }
+    if cfg!(feature = "rkyv") {
+        flags.push("rkyv");
+    }
+    if cfg!(feature = "serde") {
+        flags.push("serde");
+    }
+    if cfg!(feature = "tokio") {
+        flags.push("tokio");
+    }
+    if cfg!(feature = "zstd") {
+        flags.push("zstd");
+    }
+    flags
 }
 
-/// An iterator over a query returning rows with (path,tag,error) tuples.
+/// An iterator over a query returning rows with (path,tag,error,language) tuples.
 pub struct Files<'a, P: Params>(Statement<'a>, P);
 
 impl<'a, P: Params + Clone> Files<'a, P> {
@@ -373,6 +881,7 @@ impl<'a, P: Params + Clone> Files<'a, P> {
                 path: PathBuf::from(r.get::<_, String>(0)?),
                 tag: r.get::<_, String>(1)?,
                 status: r.get_ref(2)?.into(),
+                language: r.get::<_, Option<String>>(3)?,
             })
         })?;
         let entries = entries.map(|r| -> Result<FileEntry> { Ok(r?) });
@@ -383,44 +892,391 @@ impl<'a, P: Params + Clone> Files<'a, P> {
 /// Writer to store stack graphs and partial paths in a SQLite database.
 pub struct SQLiteWriter {
     conn: Connection,
+    codec: Arc<dyn BlobCodec>,
+    history: History,
+    file_roots: FileRoots,
+}
+
+/// Determines whether and how writes are recorded in the history of the database.
+#[derive(Clone, Debug, Default)]
+struct History {
+    append_only: bool,
+    commit: Option<String>,
 }
 
 impl SQLiteWriter {
     /// Open an in-memory database.
     pub fn open_in_memory() -> Result<Self> {
+        Self::open_in_memory_with_codec(Arc::new(BincodeCodec))
+    }
+
+    /// Open an in-memory database, which stores blobs with the given [codec][BlobCodec].
+    pub fn open_in_memory_with_codec(codec: Arc<dyn BlobCodec>) -> Result<Self> {
         let mut conn = Connection::open_in_memory()?;
-        Self::init(&mut conn)?;
+        Self::init(&mut conn, codec.name())?;
+        set_codec(&conn, codec.clone())?;
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            codec,
+            history: History::default(),
+            file_roots: FileRoots::new(),
+        })
     }
 
     /// Open a file database.  If the file does not exist, it is automatically created.
     /// An error is returned if the database version is not supported.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let is_new = !path.as_ref().exists();
+        Self::open_with(path.as_ref(), None, None)
+    }
+
+    /// Open a file database, which stores blobs with the given [codec][BlobCodec].  If the file
+    /// does not exist, it is automatically created, and records the codec in its metadata.  An
+    /// error is returned if an existing database uses a different codec, or if the database
+    /// version is not supported.
+    pub fn open_with_codec<P: AsRef<Path>>(path: P, codec: Arc<dyn BlobCodec>) -> Result<Self> {
+        Self::open_with(path.as_ref(), None, Some(codec))
+    }
+
+    /// Open an encrypted file database with the given key.  If the file does not exist, it is
+    /// automatically created and encrypted with the key.  An error is returned if the database
+    /// cannot be decrypted with the key, or if the database version is not supported.
+    #[cfg(feature = "encryption")]
+    pub fn open_encrypted<P: AsRef<Path>>(path: P, key: &str) -> Result<Self> {
+        Self::open_with(path.as_ref(), Some(key), None)
+    }
+
+    fn open_with(
+        path: &Path,
+        key: Option<&str>,
+        codec: Option<Arc<dyn BlobCodec>>,
+    ) -> Result<Self> {
+        let is_new = !path.exists();
         let mut conn = Connection::open(path)?;
+        set_key(&conn, path, key)?;
         set_pragmas_and_functions(&conn)?;
         if is_new {
-            Self::init(&mut conn)?;
+            let name = codec
+                .as_ref()
+                .map_or(BincodeCodec::NAME, |codec| codec.name());
+            Self::init(&mut conn, name)?;
         } else {
             check_version(&conn)?;
         }
+        let codec = resolve_codec(stored_codec_name(&conn)?, codec)?;
+        set_codec(&conn, codec.clone())?;
         init_indexes(&mut conn)?;
-        Ok(Self { conn })
+        let append_only = conn.query_row("SELECT append_only FROM metadata", [], |r| r.get(0))?;
+        Ok(Self {
+            conn,
+            codec,
+            history: History {
+                append_only,
+                commit: None,
+            },
+            file_roots: FileRoots::new(),
+        })
     }
 
     /// Create database tables and write metadata.
-    fn init(conn: &mut Connection) -> Result<()> {
+    fn init(conn: &mut Connection, codec: &str) -> Result<()> {
+        // must be set before any tables are created, so that free pages can be released by
+        // incremental vacuums later on
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL")?;
         let tx = conn.transaction()?;
         tx.execute_batch(SCHEMA)?;
-        tx.execute("INSERT INTO metadata (version) VALUES (?)", [VERSION])?;
+        tx.execute(
+            "INSERT INTO metadata (version, codec) VALUES (?, ?)",
+            (VERSION, codec),
+        )?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO properties (key, value) VALUES (?, ?)")?;
+            stmt.execute((CRATE_VERSION_PROPERTY, CRATE_VERSION))?;
+            stmt.execute((SCHEMA_VERSION_PROPERTY, VERSION.to_string()))?;
+            stmt.execute((
+                CREATED_AT_PROPERTY,
+                unix_timestamp(SystemTime::now()).to_string(),
+            ))?;
+            stmt.execute((BUILD_FLAGS_PROPERTY, build_flags().join(",")))?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the metadata of the database.
+    pub fn metadata(&self) -> Result<DatabaseMetadata> {
+        metadata(&self.conn)
+    }
+
+    /// Set a metadata property of the database, which is returned in the
+    /// [properties][DatabaseMetadata::properties] of its metadata.  Tools can use properties to
+    /// record how they indexed the database.
+    pub fn set_property(&mut self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO properties (key, value) VALUES (?, ?)",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Record the rules that the files of the given language are indexed with.  If different rules
+    /// were recorded for the language before, the stored files of the language were indexed with
+    /// those rules, and are [invalidated][Self::invalidate_language].  Returns the number of files
+    /// that were invalidated.
+    pub fn set_language_rules(&mut self, language: &str, rules: &LanguageRules) -> Result<usize> {
+        let hash_key = format!("{}{}.hash", RULES_PROPERTY_PREFIX, language);
+        let version_key = format!("{}{}.version", RULES_PROPERTY_PREFIX, language);
+        let tx = self.conn.transaction()?;
+        let recorded_hash = tx
+            .query_row(
+                "SELECT value FROM properties WHERE key = ?",
+                [&hash_key],
+                |r| r.get::<_, String>(0),
+            )
+            .optional()?;
+        let count = match recorded_hash {
+            Some(hash) if hash != rules.hash => Self::invalidate_language_inner(&tx, language)?,
+            _ => 0,
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO properties (key, value) VALUES (?, ?)",
+            [&hash_key, &rules.hash],
+        )?;
+        match &rules.version {
+            Some(version) => tx.execute(
+                "INSERT OR REPLACE INTO properties (key, value) VALUES (?, ?)",
+                [&version_key, version],
+            )?,
+            None => tx.execute("DELETE FROM properties WHERE key = ?", [&version_key])?,
+        };
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Mark all files of the given [language][crate::graph::StackGraph::set_file_language] as
+    /// stale, so that they are indexed again, for example because the rules of the language have
+    /// changed.  The tags of the files are cleared, so that their status is reported as missing
+    /// for any tag, and cached query results that involve them are discarded.  Their graphs and
+    /// partial paths remain available until they are indexed again.  Returns the number of files
+    /// that were invalidated.
+    pub fn invalidate_language(&mut self, language: &str) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let count = Self::invalidate_language_inner(&tx, language)?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Mark all files of the given language as stale.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn invalidate_language_inner(conn: &Connection, language: &str) -> Result<usize> {
+        let mut stmt =
+            conn.prepare_cached("UPDATE graphs SET tag = '' WHERE language = ? AND tag != ''")?;
+        Ok(stmt.execute([language])?)
+    }
+
+    /// Make the database append-only, or stop it from being append-only.  While a database is
+    /// append-only, every store or removal of a file also records the new state of the file in
+    /// the history of the database, which can be queried with [`SQLiteReader::as_of`][].  The
+    /// setting is stored in the database, and applies to all writers that open it afterwards.
+    /// When the setting is enabled, the current state of every file in the database is recorded
+    /// as a new version, so that the history is complete from then on.  Versions that were
+    /// recorded before are kept when the setting is disabled.
+    pub fn set_append_only(&mut self, append_only: bool) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("UPDATE metadata SET append_only = ?", [append_only])?;
+        if append_only && !self.history.append_only {
+            let history = History {
+                append_only,
+                ..self.history.clone()
+            };
+            let files = {
+                let mut stmt = tx.prepare_cached("SELECT file FROM graphs ORDER BY file")?;
+                let files = stmt
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                files
+            };
+            for file in files {
+                Self::record_version_inner(&tx, &history, &file)?;
+            }
+        }
         tx.commit()?;
+        self.history.append_only = append_only;
+        Ok(())
+    }
+
+    /// Returns whether the database is append-only.
+    pub fn is_append_only(&self) -> bool {
+        self.history.append_only
+    }
+
+    /// Set the commit, or other revision identifier, that subsequent writes are recorded for in
+    /// the history of an append-only database.  The state of the index after the last write for
+    /// a commit can be queried with [`PointInTime::Commit`][], even if no files were changed for
+    /// the commit.
+    pub fn set_commit(&mut self, commit: Option<&str>) -> Result<()> {
+        self.history.commit = commit.map(str::to_string);
+        if let Some(commit) = commit {
+            if self.history.append_only {
+                self.conn.execute(
+                    "INSERT OR REPLACE INTO history_commits (commit_id, version)
+                     SELECT ?, IFNULL(MAX(version), 0) FROM history",
+                    [commit],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the current state of the given file as a new version in the history, if the
+    /// database is append-only.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn record_version_inner(conn: &Connection, history: &History, file: &str) -> Result<()> {
+        if !history.append_only {
+            return Ok(());
+        }
+        copious_debugging!("--> Record version of {}", file);
+        let timestamp = unix_timestamp(SystemTime::now());
+        let count = {
+            let mut stmt = conn.prepare_cached(
+                "INSERT INTO history
+                   (file, timestamp, commit_id, deleted, tag, error, value, fingerprint, language,
+                    incomplete)
+                 SELECT file, ?2, ?3, 0, tag, error, value, fingerprint, language, incomplete
+                 FROM graphs WHERE file = ?1",
+            )?;
+            stmt.execute((file, timestamp, &history.commit))?
+        };
+        if count == 0 {
+            return Ok(());
+        }
+        let version = conn.last_insert_rowid();
+        {
+            let mut stmt = conn.prepare_cached(
+                "INSERT INTO history_file_paths (version, local_id, value)
+                 SELECT ?1, local_id, value FROM file_paths WHERE file = ?2",
+            )?;
+            stmt.execute((version, file))?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "INSERT INTO history_root_paths (version, symbol_stack, value)
+                 SELECT ?1, symbol_stack, value FROM root_paths WHERE file = ?2",
+            )?;
+            stmt.execute((version, file))?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "INSERT INTO history_path_dictionaries (version, value)
+                 SELECT ?1, value FROM path_dictionaries WHERE file = ?2",
+            )?;
+            stmt.execute((version, file))?;
+        }
+        Self::record_commit_inner(conn, history)
+    }
+
+    /// Record the removal of the files matching the given condition as new versions in the
+    /// history, if the database is append-only.  The condition may refer to the parameter `?1`,
+    /// which is bound to the given value.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn record_removals_inner(
+        conn: &Connection,
+        history: &History,
+        condition: &str,
+        param: &str,
+    ) -> Result<()> {
+        if !history.append_only {
+            return Ok(());
+        }
+        copious_debugging!("--> Record removal of files matching {}", condition);
+        let timestamp = unix_timestamp(SystemTime::now());
+        let mut stmt = conn.prepare_cached(&format!(
+            "INSERT INTO history (file, timestamp, commit_id, deleted)
+             SELECT file, ?2, ?3, 1 FROM graphs WHERE {}",
+            condition
+        ))?;
+        stmt.execute((param, timestamp, &history.commit))?;
+        Self::record_commit_inner(conn, history)
+    }
+
+    /// Move the commit that writes are recorded for to the latest version.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn record_commit_inner(conn: &Connection, history: &History) -> Result<()> {
+        if let Some(commit) = &history.commit {
+            let mut stmt = conn.prepare_cached(
+                "INSERT OR REPLACE INTO history_commits (commit_id, version)
+                 SELECT ?, MAX(version) FROM history",
+            )?;
+            stmt.execute([commit])?;
+        }
         Ok(())
     }
 
+    /// Run the maintenance tasks whose thresholds in the given plan are reached, to release free
+    /// space and keep the statistics that SQLite uses for query planning up to date.  The number
+    /// of changed rows is estimated as the difference between the current row counts of the
+    /// tables and the row counts that were recorded by the last `ANALYZE`.
+    ///
+    /// Databases that were created without incremental vacuuming are converted by a full vacuum
+    /// the first time they are vacuumed, which rewrites the whole database.
+    pub fn maintain(&mut self, plan: &MaintenancePlan) -> Result<MaintenanceReport> {
+        let page_size = self
+            .conn
+            .query_row("PRAGMA page_size", [], |r| r.get::<_, u64>(0))?;
+        let free_pages = self
+            .conn
+            .query_row("PRAGMA freelist_count", [], |r| r.get::<_, u64>(0))?;
+        let free_bytes = free_pages * page_size;
+        let changed_rows = changed_rows_since_analyze(&self.conn)?;
+        let mut report = MaintenanceReport {
+            free_bytes,
+            changed_rows,
+            ..MaintenanceReport::default()
+        };
+
+        if plan.force || (free_bytes > 0 && free_bytes >= plan.min_free_bytes) {
+            copious_debugging!("--> Vacuum {} free bytes", free_bytes);
+            let page_count_before = self
+                .conn
+                .query_row("PRAGMA page_count", [], |r| r.get::<_, u64>(0))?;
+            let auto_vacuum = self
+                .conn
+                .query_row("PRAGMA auto_vacuum", [], |r| r.get::<_, u32>(0))?;
+            if auto_vacuum == INCREMENTAL_AUTO_VACUUM {
+                self.conn.execute_batch("PRAGMA incremental_vacuum")?;
+            } else {
+                self.conn.execute_batch(
+                    "PRAGMA auto_vacuum = INCREMENTAL;
+                     VACUUM;",
+                )?;
+            }
+            let page_count_after = self
+                .conn
+                .query_row("PRAGMA page_count", [], |r| r.get::<_, u64>(0))?;
+            report.vacuumed = true;
+            report.bytes_freed = page_count_before.saturating_sub(page_count_after) * page_size;
+        }
+        if plan.force || (changed_rows > 0 && changed_rows >= plan.min_changed_rows_for_analyze) {
+            copious_debugging!("--> Analyze after {} changed rows", changed_rows);
+            self.conn.execute_batch("ANALYZE")?;
+            report.analyzed = true;
+        }
+        if plan.force || (changed_rows > 0 && changed_rows >= plan.min_changed_rows_for_reindex) {
+            copious_debugging!("--> Reindex after {} changed rows", changed_rows);
+            self.conn.execute_batch("REINDEX")?;
+            report.reindexed = true;
+        }
+        Ok(report)
+    }
+
     /// Clean all data from the database.
     pub fn clean_all(&mut self) -> Result<usize> {
         let tx = self.conn.transaction()?;
+        Self::record_removals_inner(&tx, &self.history, "1", "")?;
         let count = Self::clean_all_inner(&tx)?;
         tx.commit()?;
         Ok(count)
@@ -438,6 +1294,42 @@ impl SQLiteWriter {
             let mut stmt = conn.prepare_cached("DELETE FROM root_paths")?;
             stmt.execute([])?;
         }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM path_dictionaries")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_dependencies")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_globals")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM local_resolutions")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM parse_errors")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM root_symbols")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM resolutions")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM resolution_dependencies")?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM resolved_files")?;
+            stmt.execute([])?;
+        }
         let count = {
             let mut stmt = conn.prepare_cached("DELETE FROM graphs")?;
             stmt.execute([])?
@@ -449,6 +1341,7 @@ impl SQLiteWriter {
     /// that file is cleaned.
     pub fn clean_file(&mut self, file: &Path) -> Result<usize> {
         let tx = self.conn.transaction()?;
+        Self::record_removals_inner(&tx, &self.history, "file = ?1", &file.to_string_lossy())?;
         let count = Self::clean_file_inner(&tx, file)?;
         tx.commit()?;
         Ok(count)
@@ -458,7 +1351,20 @@ impl SQLiteWriter {
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
     fn clean_file_inner(conn: &Connection, file: &Path) -> Result<usize> {
+        Self::clean_file_data_inner(conn, file)?;
+        let count = {
+            let mut stmt = conn.prepare_cached("DELETE FROM graphs WHERE file=?")?;
+            stmt.execute([&file.to_string_lossy()])?
+        };
+        Ok(count)
+    }
+
+    /// Clean all file data, except for the file graph, from the database.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn clean_file_data_inner(conn: &Connection, file: &Path) -> Result<()> {
         let file = file.to_string_lossy();
+        Self::clean_resolutions_inner(conn, &file, false)?;
         {
             let mut stmt = conn.prepare_cached("DELETE FROM file_paths WHERE file=?")?;
             stmt.execute([&file])?;
@@ -467,17 +1373,43 @@ impl SQLiteWriter {
             let mut stmt = conn.prepare_cached("DELETE FROM root_paths WHERE file=?")?;
             stmt.execute([&file])?;
         }
-        let count = {
-            let mut stmt = conn.prepare_cached("DELETE FROM graphs WHERE file=?")?;
-            stmt.execute([&file])?
-        };
-        Ok(count)
-    }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM path_dictionaries WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_dependencies WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_globals WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM local_resolutions WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM parse_errors WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM root_symbols WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        Ok(())
+    }
 
     /// Clean file or directory data from the database.  Data for all decendants of the given path
     /// is cleaned.
     pub fn clean_file_or_directory(&mut self, file_or_directory: &Path) -> Result<usize> {
         let tx = self.conn.transaction()?;
+        Self::record_removals_inner(
+            &tx,
+            &self.history,
+            "path_descendant_of(file, ?1)",
+            &file_or_directory.to_string_lossy(),
+        )?;
         let count = Self::clean_file_or_directory_inner(&tx, file_or_directory)?;
         tx.commit()?;
         Ok(count)
@@ -489,6 +1421,7 @@ impl SQLiteWriter {
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
     fn clean_file_or_directory_inner(conn: &Connection, file_or_directory: &Path) -> Result<usize> {
         let file_or_directory = file_or_directory.to_string_lossy();
+        Self::clean_resolutions_inner(conn, &file_or_directory, true)?;
         {
             let mut stmt =
                 conn.prepare_cached("DELETE FROM file_paths WHERE path_descendant_of(file, ?)")?;
@@ -499,6 +1432,39 @@ impl SQLiteWriter {
                 conn.prepare_cached("DELETE FROM root_paths WHERE path_descendant_of(file, ?)")?;
             stmt.execute([&file_or_directory])?;
         }
+        {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM path_dictionaries WHERE path_descendant_of(file, ?)",
+            )?;
+            stmt.execute([&file_or_directory])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM file_dependencies WHERE path_descendant_of(file, ?)",
+            )?;
+            stmt.execute([&file_or_directory])?;
+        }
+        {
+            let mut stmt =
+                conn.prepare_cached("DELETE FROM file_globals WHERE path_descendant_of(file, ?)")?;
+            stmt.execute([&file_or_directory])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM local_resolutions WHERE path_descendant_of(file, ?)",
+            )?;
+            stmt.execute([&file_or_directory])?;
+        }
+        {
+            let mut stmt =
+                conn.prepare_cached("DELETE FROM parse_errors WHERE path_descendant_of(file, ?)")?;
+            stmt.execute([&file_or_directory])?;
+        }
+        {
+            let mut stmt =
+                conn.prepare_cached("DELETE FROM root_symbols WHERE path_descendant_of(file, ?)")?;
+            stmt.execute([&file_or_directory])?;
+        }
         let count = {
             let mut stmt =
                 conn.prepare_cached("DELETE FROM graphs WHERE path_descendant_of(file, ?)")?;
@@ -507,10 +1473,212 @@ impl SQLiteWriter {
         Ok(count)
     }
 
+    /// Clean the stored resolutions of the given file, or of all descendants of the given path if
+    /// `descendants` is true, and of the files whose resolutions depend on them.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn clean_resolutions_inner(
+        conn: &Connection,
+        file_or_directory: &str,
+        descendants: bool,
+    ) -> Result<()> {
+        let matches = |column: &str| {
+            if descendants {
+                format!("path_descendant_of({}, ?1)", column)
+            } else {
+                format!("{} = ?1", column)
+            }
+        };
+        {
+            let mut stmt = conn.prepare_cached(&format!(
+                "DELETE FROM resolved_files WHERE {}
+                 OR file IN (SELECT file FROM file_dependencies WHERE {})
+                 OR file IN (SELECT file FROM resolutions WHERE {})
+                 OR file IN (SELECT file FROM resolution_dependencies WHERE {})",
+                matches("file"),
+                matches("dependency"),
+                matches("definition_file"),
+                matches("dependency"),
+            ))?;
+            stmt.execute([file_or_directory])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM resolutions WHERE file NOT IN (SELECT file FROM resolved_files)",
+            )?;
+            stmt.execute([])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "DELETE FROM resolution_dependencies
+                 WHERE file NOT IN (SELECT file FROM resolved_files)",
+            )?;
+            stmt.execute([])?;
+        }
+        Ok(())
+    }
+
+    /// Move the stored data of a file to a new path, as when the file was renamed or moved
+    /// without changing its content.  The stored graph and partial paths are rewritten to refer to
+    /// the new path instead of being recomputed, and any data that was stored for the new path is
+    /// replaced.  The resolutions that involve the file are invalidated.  Returns `false`, and
+    /// changes nothing, if no data is stored for the old path.
+    ///
+    /// The moved data is only correct if the graph of the file does not depend on its path.  This
+    /// can be checked by comparing the [stored fingerprint][Self::fingerprint_for_file] of the
+    /// moved graph with the fingerprint of a graph that is built for the new path.
+    pub fn rename_file(&mut self, old: &Path, new: &Path) -> Result<bool> {
+        let tx = self.conn.transaction()?;
+        let renamed = Self::rename_file_inner(&tx, &*self.codec, &self.history, old, new)?;
+        tx.commit()?;
+        Ok(renamed)
+    }
+
+    /// Move the stored data of a file to a new path.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn rename_file_inner(
+        conn: &Connection,
+        codec: &dyn BlobCodec,
+        history: &History,
+        old_path: &Path,
+        new_path: &Path,
+    ) -> Result<bool> {
+        let old = old_path.to_string_lossy();
+        let new = new_path.to_string_lossy();
+        let stored = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT tag, error, value, language, incomplete FROM graphs WHERE file = ?",
+            )?;
+            stmt.query_row([&old], |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, Option<String>>(1)?,
+                    r.get::<_, Vec<u8>>(2)?,
+                    r.get::<_, Option<String>>(3)?,
+                    r.get::<_, Option<String>>(4)?,
+                ))
+            })
+            .optional()?
+        };
+        let (tag, error, value, language, incomplete) = match stored {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        if old == new {
+            return Ok(true);
+        }
+        copious_debugging!("--> Rename {} to {}", old, new);
+        Self::record_removals_inner(conn, history, "file = ?1", &old)?;
+        Self::clean_file_inner(conn, new_path)?;
+        Self::clean_resolutions_inner(conn, &old, false)?;
+
+        // the fingerprint includes the file name, so it is computed again for the moved graph
+        let mut graph = codec.decode_graph(value)?;
+        graph.rename_file(&old, &new);
+        let fingerprint = match error {
+            Some(_) => None,
+            None => {
+                let mut loaded = StackGraph::new();
+                graph.load_into(&mut loaded)?;
+                let file = loaded.get_or_create_file(&new);
+                Some(loaded.fingerprint_for_file(file).to_string())
+            }
+        };
+        {
+            let serialized = codec.encode_graph(&graph)?;
+            let mut stmt = conn.prepare_cached(
+                "INSERT INTO graphs (file, tag, error, value, fingerprint, language, incomplete)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            stmt.execute((
+                &new,
+                &tag,
+                &error,
+                &serialized,
+                &fingerprint,
+                &language,
+                &incomplete,
+            ))?;
+        }
+
+        // stored paths refer to the file name through the dictionary of the file, so they are
+        // decoded, moved, and encoded again with a new dictionary
+        let old_dictionary = load_path_dictionary(conn, &old)?;
+        let mut dictionary = PathDictionaryBuilder::from_dictionary(PathDictionary::default());
+        let mut rename_path = |value: &[u8]| -> Result<Vec<u8>> {
+            let (path, _): (CompactPartialPath, usize) =
+                bincode::decode_from_slice(value, BINCODE_CONFIG)?;
+            let mut path = old_dictionary
+                .decode(&path)
+                .map_err(|_| StorageError::InvalidPath(old.to_string()))?;
+            path.rename_file(&old, &new);
+            Ok(bincode::encode_to_vec(
+                &dictionary.encode(&path),
+                BINCODE_CONFIG,
+            )?)
+        };
+        for table in ["file_paths", "root_paths"] {
+            let mut select = conn.prepare_cached(&format!(
+                "SELECT rowid, blob_decode(value) FROM {} WHERE file = ?",
+                table
+            ))?;
+            let paths = select
+                .query_map([&old], |r| {
+                    Ok((r.get::<_, i64>(0)?, r.get::<_, Vec<u8>>(1)?))
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let mut update = conn.prepare_cached(&format!(
+                "UPDATE {} SET file = ?, value = blob_encode(?) WHERE rowid = ?",
+                table
+            ))?;
+            for (rowid, value) in paths {
+                update.execute((&new, rename_path(&value)?, rowid))?;
+            }
+        }
+        {
+            let dictionary = bincode::encode_to_vec(&dictionary.build(), BINCODE_CONFIG)?;
+            let mut stmt = conn.prepare_cached(
+                "UPDATE path_dictionaries SET file = ?, value = blob_encode(?) WHERE file = ?",
+            )?;
+            stmt.execute((&new, dictionary, &old))?;
+        }
+
+        for table in [
+            "file_globals",
+            "local_resolutions",
+            "parse_errors",
+            "root_symbols",
+        ] {
+            let mut stmt =
+                conn.prepare_cached(&format!("UPDATE {} SET file = ?1 WHERE file = ?2", table))?;
+            stmt.execute([&new, &old])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "UPDATE OR REPLACE file_dependencies SET file = ?1 WHERE file = ?2",
+            )?;
+            stmt.execute([&new, &old])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached(
+                "UPDATE OR REPLACE file_dependencies SET dependency = ?1 WHERE dependency = ?2",
+            )?;
+            stmt.execute([&new, &old])?;
+        }
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM graphs WHERE file = ?")?;
+            stmt.execute([&old])?;
+        }
+        Self::record_version_inner(conn, history, &new)?;
+        Ok(true)
+    }
+
     /// Store an error, indicating that indexing this file failed.
     pub fn store_error_for_file(&mut self, file: &Path, tag: &str, error: &str) -> Result<()> {
         let tx = self.conn.transaction()?;
-        Self::store_error_for_file_inner(&tx, file, tag, error)?;
+        Self::store_error_for_file_inner(&tx, &*self.codec, file, tag, error)?;
+        Self::record_version_inner(&tx, &self.history, &file.to_string_lossy())?;
         tx.commit()?;
         Ok(())
     }
@@ -520,6 +1688,7 @@ impl SQLiteWriter {
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
     fn store_error_for_file_inner(
         conn: &Connection,
+        codec: &dyn BlobCodec,
         file: &Path,
         tag: &str,
         error: &str,
@@ -528,12 +1697,15 @@ impl SQLiteWriter {
         let mut stmt = conn
             .prepare_cached("INSERT INTO graphs (file, tag, error, value) VALUES (?, ?, ?, ?)")?;
         let graph = crate::serde::StackGraph::default();
-        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
+        let serialized = codec.encode_graph(&graph)?;
         stmt.execute((&file.to_string_lossy(), tag, error, serialized))?;
         Ok(())
     }
 
-    /// Store the result of a successful file index.
+    /// Store the result of a successful file index.  If the stored graph of the file has the same
+    /// [fingerprint][StackGraph::fingerprint_for_file], the graph is not rewritten, and only its
+    /// tag is updated.  All other data of the file is replaced, including the regions of the file
+    /// that contained [parse errors][StackGraph::parse_errors].
     pub fn store_result_for_file<'a, IP>(
         &mut self,
         graph: &StackGraph,
@@ -545,31 +1717,147 @@ impl SQLiteWriter {
     where
         IP: IntoIterator<Item = &'a PartialPath>,
     {
-        let path = Path::new(graph[file].name());
+        self.store_result_for_file_with(graph, file, tag, None, partials, paths)
+    }
+
+    /// Store the result of a file index whose partial paths are incomplete, because computing
+    /// them was cancelled.  The partial paths that were found are stored as for
+    /// [`store_result_for_file`][Self::store_result_for_file], and the given reason is returned
+    /// by [`SQLiteReader::incomplete_reason_for_file`][] until the file is stored again.
+    pub fn store_incomplete_result_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        reason: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        self.store_result_for_file_with(graph, file, tag, Some(reason), partials, paths)
+    }
+
+    fn store_result_for_file_with<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        incomplete: Option<&str>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
         let tx = self.conn.transaction()?;
-        Self::clean_file_inner(&tx, path)?;
-        Self::store_graph_for_file_inner(&tx, graph, file, tag)?;
+        Self::store_graph_if_changed_inner(&tx, &*self.codec, graph, file, tag, incomplete)?;
+        Self::store_parse_errors_for_file_inner(&tx, graph, file)?;
         Self::store_partial_paths_for_file_inner(&tx, graph, file, partials, paths)?;
+        Self::record_version_inner(&tx, &self.history, graph[file].name())?;
         tx.commit()?;
         Ok(())
     }
 
+    /// Store the file graph, unless a graph with the same fingerprint is already stored, in which
+    /// case only its tag and whether it is incomplete are updated.  All other file data is
+    /// removed.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_graph_if_changed_inner(
+        conn: &Connection,
+        codec: &dyn BlobCodec,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        incomplete: Option<&str>,
+    ) -> Result<()> {
+        let file_str = graph[file].name();
+        let path = Path::new(file_str);
+        let fingerprint = graph.fingerprint_for_file(file).to_string();
+        let unchanged = {
+            let mut stmt = conn.prepare_cached(
+                "SELECT 1 FROM graphs WHERE file = ? AND error IS NULL AND fingerprint = ?",
+            )?;
+            stmt.exists([file_str, fingerprint.as_str()])?
+        };
+        if unchanged {
+            copious_debugging!("--> Keep unchanged graph for {}", file_str);
+            Self::clean_file_data_inner(conn, path)?;
+            let mut stmt =
+                conn.prepare_cached("UPDATE graphs SET tag = ?, incomplete = ? WHERE file = ?")?;
+            stmt.execute((tag, incomplete, file_str))?;
+        } else {
+            Self::clean_file_inner(conn, path)?;
+            Self::store_graph_for_file_inner(
+                conn,
+                codec,
+                graph,
+                file,
+                tag,
+                &fingerprint,
+                incomplete,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Store the file graph.
     ///
     /// This is an inner method, which does not wrap individual SQL statements in a transaction.
     fn store_graph_for_file_inner(
         conn: &Connection,
+        codec: &dyn BlobCodec,
         graph: &StackGraph,
         file: Handle<File>,
         tag: &str,
+        fingerprint: &str,
+        incomplete: Option<&str>,
     ) -> Result<()> {
         let file_str = graph[file].name();
         copious_debugging!("--> Store graph for {}", file_str);
-        let mut stmt =
-            conn.prepare_cached("INSERT INTO graphs (file, tag, value) VALUES (?, ?, ?)")?;
+        let mut stmt = conn.prepare_cached(
+            "INSERT INTO graphs (file, tag, value, fingerprint, language, incomplete)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )?;
+        let language = graph.file_language(file);
         let graph = serde::StackGraph::from_graph_filter(graph, &FileFilter(file));
-        let serialized = bincode::encode_to_vec(&graph, BINCODE_CONFIG)?;
-        stmt.execute((file_str, tag, &serialized))?;
+        let serialized = codec.encode_graph(&graph)?;
+        stmt.execute((
+            file_str,
+            tag,
+            &serialized,
+            fingerprint,
+            language,
+            incomplete,
+        ))?;
+        Ok(())
+    }
+
+    /// Store the regions of the file that contained parse errors, if there are any.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_parse_errors_for_file_inner(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+    ) -> Result<()> {
+        let parse_errors = graph.parse_errors(file);
+        if parse_errors.is_empty() {
+            return Ok(());
+        }
+        let file_str = graph[file].name();
+        copious_debugging!(
+            "--> Store {} parse errors for {}",
+            parse_errors.len(),
+            file_str
+        );
+        let serialized = bincode::encode_to_vec(parse_errors.to_vec(), BINCODE_CONFIG)?;
+        let mut stmt = conn.prepare_cached(
+            "INSERT OR REPLACE INTO parse_errors (file, value) VALUES (?, blob_encode(?))",
+        )?;
+        stmt.execute((file_str, serialized))?;
         Ok(())
     }
 
@@ -587,10 +1875,16 @@ impl SQLiteWriter {
         IP: IntoIterator<Item = &'a PartialPath>,
     {
         let file_str = graph[file].name();
-        let mut node_stmt =
-            conn.prepare_cached("INSERT INTO file_paths (file, local_id, value) VALUES (?, ?, ?)")?;
+        let mut dictionary =
+            PathDictionaryBuilder::from_dictionary(load_path_dictionary(conn, file_str)?);
+        let mut node_stmt = conn.prepare_cached(
+            "INSERT INTO file_paths (file, local_id, value) VALUES (?, ?, blob_encode(?))",
+        )?;
         let mut root_stmt = conn.prepare_cached(
-            "INSERT INTO root_paths (file, symbol_stack, value) VALUES (?, ?, ?)",
+            "INSERT INTO root_paths (file, symbol_stack, value) VALUES (?, ?, blob_encode(?))",
+        )?;
+        let mut symbol_stmt = conn.prepare_cached(
+            "INSERT OR IGNORE INTO root_symbols (file, symbol, direction) VALUES (?, ?, ?)",
         )?;
         #[cfg_attr(not(feature = "copious-debugging"), allow(unused))]
         let mut node_path_count = 0usize;
@@ -609,8 +1903,11 @@ impl SQLiteWriter {
                     path.symbol_stack_precondition.display(graph, partials),
                 );
                 let symbol_stack = path.symbol_stack_precondition.storage_key(graph, partials);
+                if let Some(symbol) = path.symbol_stack_precondition.iter(partials).next() {
+                    symbol_stmt.execute((file_str, &graph[symbol.symbol], EXPORT))?;
+                }
                 let path = serde::PartialPath::from_partial_path(graph, partials, path);
-                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
+                let serialized = bincode::encode_to_vec(&dictionary.encode(&path), BINCODE_CONFIG)?;
                 root_stmt.execute((file_str, symbol_stack, serialized))?;
                 root_path_count += 1;
             } else if start_node.is_in_file(file) {
@@ -618,8 +1915,13 @@ impl SQLiteWriter {
                     " * Add as node path from node {}",
                     path.start_node.display(graph),
                 );
+                if graph[path.end_node].is_root() {
+                    if let Some(symbol) = path.symbol_stack_postcondition.iter(partials).next() {
+                        symbol_stmt.execute((file_str, &graph[symbol.symbol], IMPORT))?;
+                    }
+                }
                 let path = serde::PartialPath::from_partial_path(graph, partials, path);
-                let serialized = bincode::encode_to_vec(&path, BINCODE_CONFIG)?;
+                let serialized = bincode::encode_to_vec(&dictionary.encode(&path), BINCODE_CONFIG)?;
                 node_stmt.execute((file_str, path.start_node.local_id, serialized))?;
                 node_path_count += 1;
             } else {
@@ -635,6 +1937,157 @@ impl SQLiteWriter {
                 root_path_count,
             );
         }
+        let dictionary = bincode::encode_to_vec(&dictionary.build(), BINCODE_CONFIG)?;
+        let mut stmt = conn.prepare_cached(
+            "INSERT OR REPLACE INTO path_dictionaries (file, value) VALUES (?, blob_encode(?))",
+        )?;
+        stmt.execute((file_str, dictionary))?;
+        Ok(())
+    }
+
+    /// Store the pre-resolved references of the given file, as found by
+    /// [`ForwardPartialPathStitcher::find_local_resolutions_in_file`][], which pairs every
+    /// reference with its definitions in the same file.  Any previously stored local resolutions
+    /// of the file are replaced.  Storing the result of the file removes them, so they must be
+    /// stored afterwards.
+    ///
+    /// [`ForwardPartialPathStitcher::find_local_resolutions_in_file`]: crate::stitching::ForwardPartialPathStitcher::find_local_resolutions_in_file
+    pub fn store_local_resolutions_for_file(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        resolutions: &[(Handle<Node>, Vec<Handle<Node>>)],
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        Self::store_local_resolutions_for_file_inner(&tx, graph, file, resolutions)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store the pre-resolved references of the given file.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_local_resolutions_for_file_inner(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+        resolutions: &[(Handle<Node>, Vec<Handle<Node>>)],
+    ) -> Result<()> {
+        let file_str = graph[file].name();
+        copious_debugging!("--> Store local resolutions for {}", file_str);
+        let local_id = |node: Handle<Node>| {
+            let id = graph[node].id();
+            if !id.is_in_file(file) {
+                panic!(
+                    "local resolution {} must be in given file {}",
+                    node.display(graph),
+                    file_str
+                );
+            }
+            id.local_id()
+        };
+        let value = resolutions
+            .iter()
+            .map(|(reference, definitions)| {
+                let definitions = definitions.iter().map(|node| local_id(*node)).collect();
+                (local_id(*reference), definitions)
+            })
+            .collect::<Vec<(u32, Vec<u32>)>>();
+        let serialized = bincode::encode_to_vec(&value, BINCODE_CONFIG)?;
+        let mut stmt = conn.prepare_cached(
+            "INSERT OR REPLACE INTO local_resolutions (file, value) VALUES (?, blob_encode(?))",
+        )?;
+        stmt.execute((file_str, serialized))?;
+        Ok(())
+    }
+
+    /// Store the files that the resolutions of the given file depend on.  The dependencies are
+    /// the files whose nodes participate in any of the given paths, which are typically the
+    /// complete paths found when resolving the references in the file.  Any previously stored
+    /// dependencies of the file are replaced.
+    pub fn store_dependencies_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let tx = self.conn.transaction()?;
+        Self::store_dependencies_for_file_inner(&tx, graph, file, partials, paths)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store the files that the resolutions of the given file depend on.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_dependencies_for_file_inner<'a, IP>(
+        conn: &Connection,
+        graph: &StackGraph,
+        file: Handle<File>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let file_str = graph[file].name();
+        copious_debugging!("--> Store dependencies for {}", file_str);
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_dependencies WHERE file=?")?;
+            stmt.execute([file_str])?;
+        }
+        let mut stmt = conn.prepare_cached(
+            "INSERT OR IGNORE INTO file_dependencies (file, dependency) VALUES (?, ?)",
+        )?;
+        for path in paths {
+            for dependency in path.participating_files(graph, partials) {
+                if dependency == file {
+                    continue;
+                }
+                copious_debugging!(" * Add dependency on {}", graph[dependency].name());
+                stmt.execute((file_str, graph[dependency].name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the global variables that were used to build the graph of the given file.  Storing
+    /// them allows detecting that a file must be indexed again because its build options changed.
+    /// Any previously stored globals of the file are replaced.
+    pub fn store_globals_for_file(
+        &mut self,
+        file: &Path,
+        globals: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        Self::store_globals_for_file_inner(&tx, file, globals)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store the global variables that were used to build the graph of the given file.
+    ///
+    /// This is an inner method, which does not wrap individual SQL statements in a transaction.
+    fn store_globals_for_file_inner(
+        conn: &Connection,
+        file: &Path,
+        globals: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let file = file.to_string_lossy();
+        copious_debugging!("--> Store globals for {}", file);
+        {
+            let mut stmt = conn.prepare_cached("DELETE FROM file_globals WHERE file=?")?;
+            stmt.execute([&file])?;
+        }
+        let mut stmt =
+            conn.prepare_cached("INSERT INTO file_globals (file, name, value) VALUES (?, ?, ?)")?;
+        for (name, value) in globals {
+            stmt.execute((&file, name, value))?;
+        }
         Ok(())
     }
 
@@ -644,54 +2097,291 @@ impl SQLiteWriter {
         status_for_file(&self.conn, file, tag)
     }
 
+    /// Returns the global variables that the graph of the given file was built with.
+    pub fn globals_for_file(&mut self, file: &Path) -> Result<BTreeMap<String, String>> {
+        globals_for_file(&self.conn, file)
+    }
+
+    /// Returns the files whose resolutions depend on the given file, and must be invalidated
+    /// when the given file changes.
+    pub fn dependents_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        dependents_of_file(&self.conn, file)
+    }
+
+    /// Returns the files that the resolutions of the given file depend on.
+    pub fn dependencies_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        dependencies_of_file(&self.conn, file)
+    }
+
+    /// Returns the successfully indexed files that are stored with the given tag.  If tags are
+    /// content hashes, as they are for the indexer, these are the files with the same content,
+    /// which can be used to detect that a file was [renamed][Self::rename_file].
+    pub fn indexed_files_with_tag(&mut self, tag: &str) -> Result<Vec<PathBuf>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT file FROM graphs WHERE tag = ? AND error IS NULL ORDER BY file",
+        )?;
+        let files = stmt
+            .query_map([tag], |r| r.get::<_, String>(0).map(PathBuf::from))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
+    /// Returns the [fingerprint][StackGraph::fingerprint_for_file] of the stored graph of the
+    /// given file, or `None` if no graph is stored for it.
+    pub fn fingerprint_for_file(&mut self, file: &Path) -> Result<Option<Fingerprint>> {
+        let file = file.to_string_lossy();
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT fingerprint FROM graphs WHERE file = ? AND error IS NULL")?;
+        let fingerprint = stmt
+            .query_row([&file], |r| r.get::<_, Option<String>>(0))
+            .optional()?
+            .flatten();
+        fingerprint
+            .map(|fingerprint| {
+                fingerprint
+                    .parse::<Fingerprint>()
+                    .map_err(|_| StorageError::InvalidFingerprint(file.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Set the file roots of the files in the database.  The roots are kept when the writer is
+    /// [converted into a reader][Self::into_reader], which uses them to translate local paths to
+    /// file names.
+    pub fn set_file_roots(&mut self, file_roots: FileRoots) {
+        self.file_roots = file_roots;
+    }
+
+    /// Returns the file roots of the files in the database.
+    pub fn file_roots(&self) -> &FileRoots {
+        &self.file_roots
+    }
+
+    /// Start a batch of writes, which are committed together in a single transaction.  This is
+    /// considerably faster than committing every file separately when storing many files.  The
+    /// writes are discarded if the batch is dropped without calling [`SQLiteBatchWriter::commit`].
+    pub fn batch_writer(&mut self) -> Result<SQLiteBatchWriter<'_>> {
+        Ok(SQLiteBatchWriter {
+            tx: self.conn.transaction()?,
+            codec: &*self.codec,
+            history: &self.history,
+        })
+    }
+
     /// Convert this writer into a reader for the same database.
     pub fn into_reader(self) -> SQLiteReader {
         SQLiteReader {
             conn: self.conn,
+            codec: self.codec,
             loaded_graphs: HashSet::new(),
             loaded_node_paths: HashSet::new(),
             loaded_root_paths: HashSet::new(),
+            prefetched_root_symbols: HashSet::new(),
             graph: StackGraph::new(),
             partials: PartialPaths::new(),
             db: Database::new(),
             stats: Stats::default(),
+            file_roots: self.file_roots,
+            budget: None,
+            as_of: None,
+            path_dictionaries: HashMap::new(),
+            local_resolutions: HashMap::new(),
+            shadowed_files: HashSet::new(),
         }
     }
 }
 
+/// Writer that stores the results of many files in a single transaction.  Created by
+/// [`SQLiteWriter::batch_writer`].
+pub struct SQLiteBatchWriter<'a> {
+    tx: Transaction<'a>,
+    codec: &'a dyn BlobCodec,
+    history: &'a History,
+}
+
+impl SQLiteBatchWriter<'_> {
+    /// Store an error, indicating that indexing this file failed.  Any existing data for the
+    /// file is removed.
+    pub fn store_error_for_file(&mut self, file: &Path, tag: &str, error: &str) -> Result<()> {
+        SQLiteWriter::clean_file_inner(&self.tx, file)?;
+        SQLiteWriter::store_error_for_file_inner(&self.tx, self.codec, file, tag, error)?;
+        SQLiteWriter::record_version_inner(&self.tx, self.history, &file.to_string_lossy())
+    }
+
+    /// Store the result of a successful file index.  See
+    /// [`SQLiteWriter::store_result_for_file`][] for how unchanged graphs are handled.
+    pub fn store_result_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        self.store_result_for_file_with(graph, file, tag, None, partials, paths)
+    }
+
+    /// Store the result of a file index whose partial paths are incomplete.  See
+    /// [`SQLiteWriter::store_incomplete_result_for_file`][].
+    pub fn store_incomplete_result_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        reason: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        self.store_result_for_file_with(graph, file, tag, Some(reason), partials, paths)
+    }
+
+    fn store_result_for_file_with<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        incomplete: Option<&str>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        SQLiteWriter::store_graph_if_changed_inner(
+            &self.tx, self.codec, graph, file, tag, incomplete,
+        )?;
+        SQLiteWriter::store_parse_errors_for_file_inner(&self.tx, graph, file)?;
+        SQLiteWriter::store_partial_paths_for_file_inner(&self.tx, graph, file, partials, paths)?;
+        SQLiteWriter::record_version_inner(&self.tx, self.history, graph[file].name())
+    }
+
+    /// Store the pre-resolved references of the given file.  See
+    /// [`SQLiteWriter::store_local_resolutions_for_file`][].
+    pub fn store_local_resolutions_for_file(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        resolutions: &[(Handle<Node>, Vec<Handle<Node>>)],
+    ) -> Result<()> {
+        SQLiteWriter::store_local_resolutions_for_file_inner(&self.tx, graph, file, resolutions)
+    }
+
+    /// Store the global variables that were used to build the graph of the given file.  Any
+    /// previously stored globals of the file are replaced.
+    pub fn store_globals_for_file(
+        &mut self,
+        file: &Path,
+        globals: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        SQLiteWriter::store_globals_for_file_inner(&self.tx, file, globals)
+    }
+
+    /// Get the file's status in the database, including any uncommitted writes of this batch.
+    /// If a tag is provided, it must match or the file is reported missing.
+    pub fn status_for_file(&mut self, file: &str, tag: Option<&str>) -> Result<FileStatus> {
+        status_for_file(&self.tx, file, tag)
+    }
+
+    /// Returns the global variables that the graph of the given file was built with, including
+    /// any uncommitted writes of this batch.
+    pub fn globals_for_file(&mut self, file: &Path) -> Result<BTreeMap<String, String>> {
+        globals_for_file(&self.tx, file)
+    }
+
+    /// Commit all writes in this batch.
+    pub fn commit(self) -> Result<()> {
+        self.tx.commit()?;
+        Ok(())
+    }
+}
+
 /// Reader to load stack graphs and partial paths from a SQLite database.
 pub struct SQLiteReader {
     conn: Connection,
+    codec: Arc<dyn BlobCodec>,
     loaded_graphs: HashSet<String>,
     loaded_node_paths: HashSet<Handle<Node>>,
     loaded_root_paths: HashSet<String>,
+    prefetched_root_symbols: HashSet<String>,
     graph: StackGraph,
     partials: PartialPaths,
     db: Database,
     stats: Stats,
+    file_roots: FileRoots,
+    budget: Option<MemoryBudget>,
+    as_of: Option<PointInTime>,
+    /// The dictionaries of the files whose paths have been loaded.
+    path_dictionaries: HashMap<String, PathDictionary>,
+    /// The local resolutions of the files that have been looked up, which map the local IDs of
+    /// references to the local IDs of their definitions.
+    local_resolutions: HashMap<String, HashMap<u32, Vec<u32>>>,
+    /// The files whose stored data is replaced by [`Self::shadow_file`][].
+    shadowed_files: HashSet<String>,
 }
 
 impl SQLiteReader {
     /// Open a file database.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        if !path.as_ref().exists() {
+        Self::open_with(path.as_ref(), None, None)
+    }
+
+    /// Open a file database that stores blobs with the given [codec][BlobCodec], which is needed
+    /// for databases that were created with a codec that is not built in.  An error is returned
+    /// if the database uses a different codec.
+    pub fn open_with_codec<P: AsRef<Path>>(path: P, codec: Arc<dyn BlobCodec>) -> Result<Self> {
+        Self::open_with(path.as_ref(), None, Some(codec))
+    }
+
+    /// Open an encrypted file database with the given key.  An error is returned if the database
+    /// cannot be decrypted with the key.
+    #[cfg(feature = "encryption")]
+    pub fn open_encrypted<P: AsRef<Path>>(path: P, key: &str) -> Result<Self> {
+        Self::open_with(path.as_ref(), Some(key), None)
+    }
+
+    fn open_with(
+        path: &Path,
+        key: Option<&str>,
+        codec: Option<Arc<dyn BlobCodec>>,
+    ) -> Result<Self> {
+        if !path.exists() {
             return Err(StorageError::MissingDatabase(
-                path.as_ref().to_string_lossy().to_string(),
+                path.to_string_lossy().to_string(),
             ));
         }
         let mut conn = Connection::open(path)?;
+        set_key(&conn, path, key)?;
         set_pragmas_and_functions(&conn)?;
-        check_version(&conn)?;
+        if check_readable_version(&conn)? == LEGACY_VERSION {
+            return Ok(legacy::convert(&conn)?.into_reader());
+        }
+        let codec = resolve_codec(stored_codec_name(&conn)?, codec)?;
+        set_codec(&conn, codec.clone())?;
         init_indexes(&mut conn)?;
         Ok(Self {
             conn,
+            codec,
             loaded_graphs: HashSet::new(),
             loaded_node_paths: HashSet::new(),
             loaded_root_paths: HashSet::new(),
+            prefetched_root_symbols: HashSet::new(),
             graph: StackGraph::new(),
             partials: PartialPaths::new(),
             db: Database::new(),
             stats: Stats::default(),
+            file_roots: FileRoots::new(),
+            budget: None,
+            as_of: None,
+            path_dictionaries: HashMap::new(),
+            local_resolutions: HashMap::new(),
+            shadowed_files: HashSet::new(),
         })
     }
 
@@ -700,27 +2390,112 @@ impl SQLiteReader {
     pub fn clear(&mut self) {
         self.loaded_graphs.clear();
         self.graph = StackGraph::new();
+        self.graph.set_memory_budget(self.budget.clone());
 
         self.loaded_node_paths.clear();
         self.loaded_root_paths.clear();
+        self.prefetched_root_symbols.clear();
         self.partials.clear();
         self.db.clear();
+        self.path_dictionaries.clear();
+        self.local_resolutions.clear();
+        self.shadowed_files.clear();
 
         self.stats.clear();
     }
 
     /// Clear path data that has been loaded into this reader instance.
     /// After this call, all node handles remain valid, but all path data
-    /// is invalid.
+    /// is invalid.  The paths of [shadowed files][Self::shadow_file] are not loaded again.
     pub fn clear_paths(&mut self) {
         self.loaded_node_paths.clear();
         self.loaded_root_paths.clear();
+        self.prefetched_root_symbols.clear();
         self.partials.clear();
         self.db.clear();
+        self.path_dictionaries.clear();
 
         self.stats.clear_paths();
     }
 
+    /// Query the index as it existed at the given point in the history of an append-only
+    /// database.  All subsequent queries of this reader see the graphs and partial paths of files
+    /// as they were stored at that point, until [`Self::latest`][] or this method is called again.
+    /// Since data that is not versioned is not visible, stored resolutions are ignored, and
+    /// storing resolutions does not affect the database.  All data that has been loaded into this
+    /// reader is [cleared][Self::clear].
+    pub fn as_of(&mut self, point: &PointInTime) -> Result<()> {
+        let version = match point {
+            PointInTime::Timestamp(timestamp) => self.conn.query_row(
+                "SELECT IFNULL(MAX(version), 0) FROM main.history WHERE timestamp <= ?",
+                [timestamp],
+                |r| r.get::<_, i64>(0),
+            )?,
+            PointInTime::Commit(commit) => self
+                .conn
+                .query_row(
+                    "SELECT version FROM main.history_commits WHERE commit_id = ?",
+                    [commit],
+                    |r| r.get::<_, i64>(0),
+                )
+                .optional()?
+                .ok_or_else(|| StorageError::MissingCommit(commit.clone()))?,
+        };
+        copious_debugging!("--> Query as of version {}", version);
+        self.conn.execute_batch(DROP_HISTORY_SNAPSHOT)?;
+        self.conn
+            .execute_batch(&HISTORY_SNAPSHOT.replace("{version}", &version.to_string()))?;
+        self.conn
+            .execute_batch(HISTORY_SNAPSHOT_PATH_DICTIONARIES)?;
+        self.conn.flush_prepared_statement_cache();
+        self.as_of = Some(point.clone());
+        self.clear();
+        Ok(())
+    }
+
+    /// Query the current state of the index again, after [`Self::as_of`][] was used to query an
+    /// earlier point in time.  All data that has been loaded into this reader is
+    /// [cleared][Self::clear].
+    pub fn latest(&mut self) -> Result<()> {
+        self.conn.execute_batch(DROP_HISTORY_SNAPSHOT)?;
+        self.conn.flush_prepared_statement_cache();
+        self.as_of = None;
+        self.clear();
+        Ok(())
+    }
+
+    /// Returns the point in time that this reader queries, or `None` if it queries the current
+    /// state of the index.
+    pub fn point_in_time(&self) -> Option<&PointInTime> {
+        self.as_of.as_ref()
+    }
+
+    /// Returns the metadata of the database.  Databases of [`LEGACY_VERSION`][] are converted
+    /// when they are opened, and report the metadata of the converted database.
+    pub fn metadata(&self) -> Result<DatabaseMetadata> {
+        metadata(&self.conn)
+    }
+
+    /// Returns the versions of the given file that are recorded in the history of the database,
+    /// from oldest to newest.  This is empty if the database was never append-only.
+    pub fn file_history(&self, file: &str) -> Result<Vec<FileVersion>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT version, timestamp, commit_id, CASE WHEN deleted THEN NULL ELSE tag END
+             FROM main.history WHERE file = ? ORDER BY version",
+        )?;
+        let versions = stmt
+            .query_map([file], |r| {
+                Ok(FileVersion {
+                    version: r.get(0)?,
+                    timestamp: r.get(1)?,
+                    commit: r.get(2)?,
+                    tag: r.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(versions)
+    }
+
     /// Get the file's status in the database. If a tag is provided, it must match or the file
     /// is reported missing.
     pub fn status_for_file<T: AsRef<str>>(
@@ -731,14 +2506,364 @@ impl SQLiteReader {
         status_for_file(&self.conn, file, tag)
     }
 
+    /// Returns the global variables that the graph of the given file was built with.
+    pub fn globals_for_file(&mut self, file: &Path) -> Result<BTreeMap<String, String>> {
+        globals_for_file(&self.conn, file)
+    }
+
+    /// Returns the files whose resolutions depend on the given file, and must be invalidated
+    /// when the given file changes.
+    pub fn dependents_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        dependents_of_file(&self.conn, file)
+    }
+
+    /// Returns the files that the resolutions of the given file depend on.
+    pub fn dependencies_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        dependencies_of_file(&self.conn, file)
+    }
+
+    /// Returns the regions of the given file that contained parse errors when it was indexed.
+    /// Results that involve a file with parse errors may be incomplete.  The parse errors of a
+    /// file are also restored when its graph is [loaded][Self::load_graph_for_file].
+    pub fn parse_errors_for_file(&mut self, file: &str) -> Result<Vec<Span>> {
+        parse_errors_for_file(&self.conn, file)
+    }
+
+    /// Returns why the stored partial paths of the given file are incomplete, or `None` if they
+    /// are complete or the file is not in the database.  Partial paths are incomplete if
+    /// computing them was cancelled, and the paths found until then were stored with
+    /// [`SQLiteWriter::store_incomplete_result_for_file`][].  Results that involve such a file
+    /// may be incomplete.
+    pub fn incomplete_reason_for_file(&mut self, file: &str) -> Result<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT incomplete FROM graphs WHERE file = ?")?;
+        let reason = stmt
+            .query_row([file], |r| r.get::<_, Option<String>>(0))
+            .optional()?;
+        Ok(reason.flatten())
+    }
+
+    /// Returns the symbols that the given file exports through the root node, in sorted order.
+    /// These are the first symbols of the symbol stacks that the root paths of the file start
+    /// with, so the file may define them for other files.
+    pub fn exports_of(&mut self, file: &Path) -> Result<Vec<String>> {
+        self.root_symbols_of_file(file, EXPORT)
+    }
+
+    /// Returns the symbols that the given file imports through the root node, in sorted order.
+    /// These are the first symbols of the symbol stacks with which paths from the file arrive at
+    /// the root node, so other files may define them for the file.
+    pub fn imports_of(&mut self, file: &Path) -> Result<Vec<String>> {
+        self.root_symbols_of_file(file, IMPORT)
+    }
+
+    /// Returns the files that export the given symbol through the root node, in sorted order.
+    /// These are the files that could define the symbol for other files.
+    pub fn exporters_of(&mut self, symbol: &str) -> Result<Vec<PathBuf>> {
+        self.files_with_root_symbol(symbol, EXPORT)
+    }
+
+    /// Returns the files that import the given symbol through the root node, in sorted order.
+    /// These are the files whose references may resolve to a definition of the symbol in
+    /// another file.
+    pub fn importers_of(&mut self, symbol: &str) -> Result<Vec<PathBuf>> {
+        self.files_with_root_symbol(symbol, IMPORT)
+    }
+
+    fn root_symbols_of_file(&mut self, file: &Path, direction: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT symbol FROM root_symbols WHERE file = ? AND direction = ? ORDER BY symbol",
+        )?;
+        let symbols = stmt
+            .query_map((file.to_string_lossy(), direction), |r| {
+                r.get::<_, String>(0)
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(symbols)
+    }
+
+    fn files_with_root_symbol(&mut self, symbol: &str, direction: &str) -> Result<Vec<PathBuf>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT file FROM root_symbols WHERE symbol = ? AND direction = ? ORDER BY file",
+        )?;
+        let files = stmt
+            .query_map((symbol, direction), |r| {
+                r.get::<_, String>(0).map(PathBuf::from)
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
+    /// Returns the tag that the given file was stored with, or `None` if the file is not in the
+    /// database.  The tag changes whenever the file is indexed with different content.
+    pub fn tag_for_file(&mut self, file: &str) -> Result<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT tag FROM graphs WHERE file = ?")?;
+        let tag = stmt
+            .query_row([file], |r| r.get::<_, String>(0))
+            .optional()?;
+        Ok(tag)
+    }
+
+    /// Returns the generation of the database, which increases whenever a file is stored in or
+    /// removed from the database.  Unlike the [tags][Self::tag_for_file] of files, this also
+    /// changes when files are added, so it can be used to detect that results that depend on the
+    /// set of files in the database, such as the references to a definition, are out of date.
+    /// The generation refers to the latest state of the database, also when reading it at a
+    /// [point in time][Self::as_of].
+    pub fn generation(&self) -> Result<u64> {
+        let generation = self
+            .conn
+            .query_row("SELECT generation FROM main.metadata", [], |r| {
+                r.get::<_, u64>(0)
+            })?;
+        Ok(generation)
+    }
+
+    /// Returns the files whose resolutions are stored in the database.  Resolutions of a file are
+    /// removed when the file, or any file that its resolutions depend on, is cleaned or indexed
+    /// again.
+    pub fn list_resolved_files(&mut self) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT file FROM resolved_files")?;
+        let files = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<HashSet<_>, _>>()?;
+        Ok(files)
+    }
+
+    /// Returns the stored resolutions of the reference with the given local ID in the given file,
+    /// if they are fresh.  Resolutions are fresh if none of the files that participated in them,
+    /// which include the file of the reference and the files of the definitions, have changed
+    /// since the resolutions were stored, which is checked by comparing the tags of the files.
+    /// That the reference has no definitions is only fresh if no file has been stored in or
+    /// removed from the database since, because any new file can define the reference.  Returns
+    /// `None` if the file has no stored resolutions, or if they are not fresh, in which case the
+    /// reference must be resolved by stitching.  An empty result means that the reference was
+    /// resolved, but has no definitions.
+    pub fn fresh_resolutions_for_node(
+        &mut self,
+        file: &str,
+        local_id: u32,
+    ) -> Result<Option<Vec<StoredResolution>>> {
+        let resolved_generation = {
+            let mut stmt = self.conn.prepare_cached(
+                "SELECT resolved_files.generation FROM resolved_files JOIN graphs USING (file)
+                 WHERE file = ? AND resolved_files.tag = graphs.tag",
+            )?;
+            stmt.query_row([file], |row| row.get::<_, u64>(0))
+                .optional()?
+        };
+        let resolved_generation = match resolved_generation {
+            Some(resolved_generation) => resolved_generation,
+            None => return Ok(None),
+        };
+        let dependencies_changed = {
+            let mut stmt = self.conn.prepare_cached(
+                "SELECT 1 FROM resolution_dependencies d LEFT JOIN graphs g ON g.file = d.dependency
+                 WHERE d.file = ? AND d.local_id = ? AND g.tag IS NOT d.dependency_tag",
+            )?;
+            stmt.exists((file, local_id))?
+        };
+        if dependencies_changed {
+            return Ok(None);
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT r.file, r.local_id, r.definition_file, r.definition_local_id,
+                    g.tag IS r.definition_tag
+             FROM resolutions r LEFT JOIN graphs g ON g.file = r.definition_file
+             WHERE r.file = ? AND r.local_id = ?
+             ORDER BY r.definition_file, r.definition_local_id",
+        )?;
+        let mut rows = stmt.query((file, local_id))?;
+        let mut resolutions = Vec::new();
+        while let Some(row) = rows.next()? {
+            if !row.get::<_, bool>(4)? {
+                return Ok(None);
+            }
+            resolutions.push(StoredResolution {
+                file: PathBuf::from(row.get::<_, String>(0)?),
+                local_id: row.get(1)?,
+                definition_file: PathBuf::from(row.get::<_, String>(2)?),
+                definition_local_id: row.get(3)?,
+            });
+        }
+        if resolutions.is_empty() && resolved_generation != self.generation()? {
+            return Ok(None);
+        }
+        Ok(Some(resolutions))
+    }
+
+    /// Returns the definitions of the given reference, if it was pre-resolved when its file was
+    /// indexed, because it can only resolve to definitions in the same file.  Returns `None` if
+    /// the reference must be resolved by path stitching.  The node must be in the graph of this
+    /// reader.  Lookups are counted in the [stats][Self::stats] of this reader, which gives the
+    /// hit rate of local resolution.
+    pub fn local_resolutions_for_node(
+        &mut self,
+        reference: Handle<Node>,
+    ) -> Result<Option<Vec<Handle<Node>>>> {
+        let id = self.graph[reference].id();
+        let file = match id.file() {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let file_str = self.graph[file].name();
+        if !self.local_resolutions.contains_key(file_str) {
+            let mut resolutions = HashMap::new();
+            let mut stmt = self.conn.prepare_cached(
+                "SELECT blob_decode(value) FROM local_resolutions WHERE file = ?",
+            )?;
+            let value = stmt
+                .query_row([file_str], |row| row.get::<_, Vec<u8>>(0))
+                .optional()?;
+            if let Some(value) = value {
+                let (value, _): (Vec<(u32, Vec<u32>)>, usize) =
+                    bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+                resolutions.extend(value);
+            }
+            self.local_resolutions
+                .insert(file_str.to_string(), resolutions);
+        }
+        let definitions = match self.local_resolutions[file_str].get(&id.local_id()) {
+            Some(definitions) => definitions,
+            None => {
+                self.stats.local_resolution_misses += 1;
+                return Ok(None);
+            }
+        };
+        self.stats.local_resolution_hits += 1;
+        Ok(Some(
+            definitions
+                .iter()
+                .filter_map(|local_id| self.graph.node_for_id(NodeID::new_in_file(file, *local_id)))
+                .collect(),
+        ))
+    }
+
+    /// Returns the stored resolutions of the references in the given file.  The resolutions are
+    /// returned regardless of whether they are fresh.
+    pub fn resolutions_for_file(&mut self, file: &str) -> Result<Vec<StoredResolution>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT file, local_id, definition_file, definition_local_id FROM resolutions
+             WHERE file = ? ORDER BY local_id, definition_file, definition_local_id",
+        )?;
+        let resolutions = stmt
+            .query_map([file], |row| {
+                Ok(StoredResolution {
+                    file: PathBuf::from(row.get::<_, String>(0)?),
+                    local_id: row.get(1)?,
+                    definition_file: PathBuf::from(row.get::<_, String>(2)?),
+                    definition_local_id: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(resolutions)
+    }
+
+    /// Store the resolutions of the references in the given files, and mark the files as resolved.
+    /// The resolutions are pairs of reference and definition nodes in the graph of this reader.
+    /// The dependencies are pairs of reference nodes and the files that participated in the paths
+    /// to their definitions, as returned by [`PartialPath::participating_files`][].  Any
+    /// previously stored resolutions of the files are replaced.  All resolutions and files are
+    /// stored in a single transaction, together with the current tags of the files involved and
+    /// the current [generation][Self::generation] of the database, which are used to check
+    /// whether the resolutions are [fresh][Self::fresh_resolutions_for_node].
+    pub fn store_resolutions_for_files(
+        &mut self,
+        files: &[String],
+        resolutions: &[(Handle<Node>, Handle<Node>)],
+        dependencies: &[(Handle<Node>, Handle<File>)],
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM resolutions WHERE file = ?")?;
+            let mut delete_dependencies =
+                tx.prepare_cached("DELETE FROM resolution_dependencies WHERE file = ?")?;
+            let mut mark = tx.prepare_cached(
+                "INSERT OR REPLACE INTO resolved_files (file, tag, generation)
+                 SELECT file, tag, (SELECT generation FROM main.metadata) FROM graphs
+                 WHERE file = ?",
+            )?;
+            for file in files {
+                delete.execute([file])?;
+                delete_dependencies.execute([file])?;
+                mark.execute([file])?;
+            }
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO resolutions
+                   (file, local_id, definition_file, definition_local_id, definition_tag)
+                 SELECT ?1, ?2, ?3, ?4, tag FROM graphs WHERE file = ?3",
+            )?;
+            for (reference, definition) in resolutions {
+                let reference = self.graph[*reference].id();
+                let definition = self.graph[*definition].id();
+                let (reference_file, definition_file) = match (reference.file(), definition.file())
+                {
+                    (Some(reference_file), Some(definition_file)) => {
+                        (reference_file, definition_file)
+                    }
+                    _ => continue,
+                };
+                insert.execute((
+                    self.graph[reference_file].name(),
+                    reference.local_id(),
+                    self.graph[definition_file].name(),
+                    definition.local_id(),
+                ))?;
+            }
+            let mut insert_dependency = tx.prepare_cached(
+                "INSERT OR IGNORE INTO resolution_dependencies
+                   (file, local_id, dependency, dependency_tag)
+                 SELECT ?1, ?2, ?3, tag FROM graphs WHERE file = ?3",
+            )?;
+            for (reference, dependency) in dependencies {
+                let reference = self.graph[*reference].id();
+                let reference_file = match reference.file() {
+                    Some(reference_file) => reference_file,
+                    None => continue,
+                };
+                insert_dependency.execute((
+                    self.graph[reference_file].name(),
+                    reference.local_id(),
+                    self.graph[*dependency].name(),
+                ))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove all stored resolutions from the database.
+    pub fn clean_resolutions(&mut self) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM resolutions", [])?;
+        tx.execute("DELETE FROM resolution_dependencies", [])?;
+        tx.execute("DELETE FROM resolved_files", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Returns a [`Files`][] value that can be used to iterate over all files in the database.
     pub fn list_all<'a>(&'a mut self) -> Result<Files<'a, ()>> {
         self.conn
-            .prepare("SELECT file, tag, error FROM graphs")
+            .prepare("SELECT file, tag, error, language FROM graphs")
             .map(|stmt| Files(stmt, ()))
             .map_err(|e| e.into())
     }
 
+    /// Returns a [`Files`][] value that can be used to iterate over all files in the database that
+    /// are tagged with the given [language][crate::graph::StackGraph::set_file_language].
+    pub fn list_language<'a>(&'a self, language: &str) -> Result<Files<'a, [String; 1]>> {
+        self.conn
+            .prepare("SELECT file, tag, error, language FROM graphs WHERE language = ?")
+            .map(|stmt| Files(stmt, [language.to_string()]))
+            .map_err(|e| e.into())
+    }
+
     /// Returns a [`Files`][] value that can be used to iterate over all descendants of a
     /// file or directory in the database.
     pub fn list_file_or_directory<'a>(
@@ -753,20 +2878,61 @@ impl SQLiteReader {
         file_or_directory: &Path,
     ) -> Result<Files<'a, [String; 1]>> {
         let file_or_directory = file_or_directory.to_string_lossy().to_string();
-        conn.prepare("SELECT file, tag, error FROM graphs WHERE path_descendant_of(file, ?)")
-            .map(|stmt| Files(stmt, [file_or_directory]))
-            .map_err(|e| e.into())
+        conn.prepare(
+            "SELECT file, tag, error, language FROM graphs WHERE path_descendant_of(file, ?)",
+        )
+        .map(|stmt| Files(stmt, [file_or_directory]))
+        .map_err(|e| e.into())
+    }
+
+    /// Set the file roots that are used to translate local paths to file names in the database.
+    /// This allows using databases that were created with the roots checked out at different
+    /// locations.
+    pub fn set_file_roots(&mut self, file_roots: FileRoots) {
+        self.file_roots = file_roots;
+    }
+
+    /// Returns the file roots that are used to translate local paths to file names.
+    pub fn file_roots(&self) -> &FileRoots {
+        &self.file_roots
+    }
+
+    /// Assigns a memory budget to the stack graph, partial paths, and database of this reader.
+    /// Loading data fails with [`StorageError::OutOfBudget`][] once the budget is exceeded.  The
+    /// data that was loaded before that is kept, and can be removed with [`Self::clear`][].
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.graph.set_memory_budget(budget.clone());
+        self.partials.set_memory_budget(budget.clone());
+        self.db.set_memory_budget(budget.clone());
+        self.budget = budget;
+    }
+
+    fn check_memory_budget(&self) -> Result<()> {
+        if let Some(budget) = &self.budget {
+            budget.check_usage()?;
+        }
+        Ok(())
+    }
+
+    /// Ensure the graph for the file at the given local path is loaded.  The path is translated
+    /// to a file name using the reader's [file roots][Self::set_file_roots].
+    pub fn load_graph_for_path(&mut self, path: &Path) -> Result<Handle<File>> {
+        let file = self.file_roots.file_name(path);
+        self.load_graph_for_file(&file)
     }
 
     /// Ensure the graph for the given file is loaded.
     pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
-        Self::load_graph_for_file_inner(
+        let handle = Self::load_graph_for_file_inner(
             file,
             &mut self.graph,
             &mut self.loaded_graphs,
             &self.conn,
+            &*self.codec,
             &mut self.stats,
-        )
+        )?;
+        self.check_memory_budget()?;
+        Ok(handle)
     }
 
     fn load_graph_for_file_inner(
@@ -774,6 +2940,7 @@ impl SQLiteReader {
         graph: &mut StackGraph,
         loaded_graphs: &mut HashSet<String>,
         conn: &Connection,
+        codec: &dyn BlobCodec,
         stats: &mut Stats,
     ) -> Result<Handle<File>> {
         copious_debugging!("--> Load graph for {}", file);
@@ -782,14 +2949,70 @@ impl SQLiteReader {
             stats.file_cached += 1;
             return Ok(graph.get_file(file).expect("loaded file to exist"));
         }
-        copious_debugging!(" * Load from database");
-        stats.file_loads += 1;
-        let mut stmt = conn.prepare_cached("SELECT value FROM graphs WHERE file = ?")?;
-        let value = stmt.query_row([file], |row| row.get::<_, Vec<u8>>(0))?;
-        let (file_graph, _): (serde::StackGraph, usize) =
-            bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
-        file_graph.load_into(graph)?;
-        Ok(graph.get_file(file).expect("loaded file to exist"))
+        copious_debugging!(" * Load from database");
+        stats.file_loads += 1;
+        let mut stmt =
+            conn.prepare_cached("SELECT value, fingerprint FROM graphs WHERE file = ?")?;
+        let (value, fingerprint) = stmt.query_row([file], |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        let file_graph = codec.decode_graph(value)?;
+        match fingerprint {
+            Some(fingerprint) => {
+                let fingerprint = fingerprint
+                    .parse::<Fingerprint>()
+                    .map_err(|_| StorageError::InvalidFingerprint(file.to_string()))?;
+                file_graph.load_into_verified(graph, file, fingerprint)?;
+            }
+            None => file_graph.load_into(graph)?,
+        }
+        let handle = graph.get_file(file).expect("loaded file to exist");
+        for span in parse_errors_for_file(conn, file)? {
+            graph.add_parse_error(handle, span);
+        }
+        Ok(handle)
+    }
+
+    /// Replace the stored graph and partial paths of a file with the graph of the file in the given
+    /// stack graph, and the given partial paths of that file, without modifying the database.
+    /// Queries then see the file as it is in the given graph, such as the unsaved contents of an
+    /// editor buffer, while all other files are loaded from the database as usual.  If the stored
+    /// graph of the file was already loaded, all loaded data is [cleared][Self::clear] first.  The
+    /// file stays shadowed until this reader is cleared.  Returns the handle of the file in the
+    /// graph of this reader.
+    pub fn shadow_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<Handle<File>>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let name = graph[file].name().to_string();
+        copious_debugging!("--> Shadow file {}", name);
+        if self.loaded_graphs.contains(&name) {
+            self.clear();
+        }
+        serde::StackGraph::from_graph_filter(graph, &FileFilter(file))
+            .load_into(&mut self.graph)?;
+        let handle = self.graph.get_file(&name).expect("loaded file to exist");
+        for span in graph.parse_errors(file) {
+            self.graph.add_parse_error(handle, span.clone());
+        }
+        for path in paths {
+            let path = serde::PartialPath::from_partial_path(graph, partials, path)
+                .to_partial_path(&mut self.graph, &mut self.partials)?;
+            self.db
+                .add_partial_path(&self.graph, &mut self.partials, path);
+        }
+        self.loaded_node_paths
+            .extend(self.graph.nodes_for_file(handle));
+        self.loaded_graphs.insert(name.clone());
+        self.shadowed_files.insert(name);
+        self.check_memory_budget()?;
+        Ok(handle)
     }
 
     pub fn load_graphs_for_file_or_directory(
@@ -805,8 +3028,75 @@ impl SQLiteReader {
                 &mut self.graph,
                 &mut self.loaded_graphs,
                 &self.conn,
+                &*self.codec,
+                &mut self.stats,
+            )?;
+            self.check_memory_budget()?;
+        }
+        Ok(())
+    }
+
+    /// Load the graphs and all stored partial paths of the files in the given file or directory.
+    /// This is useful to inspect the stored data, for example to visualize it. The loaded paths are
+    /// not tracked, and may be loaded again by [`Self::load_partial_path_extensions`].
+    pub fn load_partial_paths_for_file_or_directory(
+        &mut self,
+        file_or_directory: &Path,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        self.load_partial_paths_inner(
+            "SELECT file, blob_decode(value) FROM file_paths WHERE path_descendant_of(file, ?1)
+             UNION ALL
+             SELECT file, blob_decode(value) FROM root_paths WHERE path_descendant_of(file, ?1)",
+            &file_or_directory.to_string_lossy(),
+            cancellation_flag,
+        )
+    }
+
+    /// Load the graph and all stored partial paths of the given file. The loaded paths are not
+    /// tracked, and may be loaded again by [`Self::load_partial_path_extensions`].
+    pub fn load_partial_paths_for_file(
+        &mut self,
+        file: &str,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        self.load_partial_paths_inner(
+            "SELECT file, blob_decode(value) FROM file_paths WHERE file = ?1
+             UNION ALL
+             SELECT file, blob_decode(value) FROM root_paths WHERE file = ?1",
+            file,
+            cancellation_flag,
+        )
+    }
+
+    fn load_partial_paths_inner(
+        &mut self,
+        query: &str,
+        param: &str,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(query)?;
+        let paths = stmt.query_map([param], |row| {
+            let file = row.get::<_, String>(0)?;
+            let value = row.get::<_, Vec<u8>>(1)?;
+            Ok((file, value))
+        })?;
+        for path in paths {
+            cancellation_flag.check("loading partial paths")?;
+            let (file, value) = path?;
+            Self::load_graph_for_file_inner(
+                &file,
+                &mut self.graph,
+                &mut self.loaded_graphs,
+                &self.conn,
+                &*self.codec,
                 &mut self.stats,
             )?;
+            let path = decode_stored_path(&self.conn, &mut self.path_dictionaries, &file, &value)?;
+            let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+            self.db
+                .add_partial_path(&self.graph, &mut self.partials, path);
+            self.check_memory_budget()?;
         }
         Ok(())
     }
@@ -827,9 +3117,13 @@ impl SQLiteReader {
         let id = self.graph[node].id();
         let file = id.file().expect("file node required");
         let file = self.graph[file].name();
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT file,value from file_paths WHERE file = ? AND local_id = ?")?;
+        if self.shadowed_files.contains(file) {
+            copious_debugging!("   > Shadowed");
+            return Ok(());
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT file, blob_decode(value) from file_paths WHERE file = ? AND local_id = ?",
+        )?;
         let paths = stmt.query_map((file, id.local_id()), |row| {
             let file = row.get::<_, String>(0)?;
             let value = row.get::<_, Vec<u8>>(1)?;
@@ -845,10 +3139,10 @@ impl SQLiteReader {
                 &mut self.graph,
                 &mut self.loaded_graphs,
                 &self.conn,
+                &*self.codec,
                 &mut self.stats,
             )?;
-            let (path, _): (serde::PartialPath, usize) =
-                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+            let path = decode_stored_path(&self.conn, &mut self.path_dictionaries, &file, &value)?;
             let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
             copious_debugging!(
                 "   > Loaded {}",
@@ -872,8 +3166,19 @@ impl SQLiteReader {
             " * Load extensions from root with symbol stack {}",
             symbol_stack.display(&self.graph, &mut self.partials)
         );
+        let first_symbol = symbol_stack.iter(&mut self.partials).next();
+        if let Some(first_symbol) = first_symbol {
+            if self
+                .prefetched_root_symbols
+                .contains(&self.graph[first_symbol.symbol])
+            {
+                copious_debugging!("   > Already prefetched");
+                self.stats.root_path_prefetch_hits += 1;
+                return Ok(());
+            }
+        }
         let mut stmt = self.conn.prepare_cached(
-            "SELECT file,value from root_paths WHERE symbol_stack LIKE ? ESCAPE ?",
+            "SELECT file, blob_decode(value) from root_paths WHERE symbol_stack LIKE ? ESCAPE ?",
         )?;
         let (symbol_stack_patterns, escape) =
             symbol_stack.storage_key_patterns(&self.graph, &mut self.partials);
@@ -898,15 +3203,19 @@ impl SQLiteReader {
             for path in paths {
                 cancellation_flag.check("loading root paths")?;
                 let (file, value) = path?;
+                if self.shadowed_files.contains(&file) {
+                    continue;
+                }
                 Self::load_graph_for_file_inner(
                     &file,
                     &mut self.graph,
                     &mut self.loaded_graphs,
                     &self.conn,
+                    &*self.codec,
                     &mut self.stats,
                 )?;
-                let (path, _): (serde::PartialPath, usize) =
-                    bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+                let path =
+                    decode_stored_path(&self.conn, &mut self.path_dictionaries, &file, &value)?;
                 let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
                 copious_debugging!(
                     "   > Loaded {}",
@@ -921,6 +3230,104 @@ impl SQLiteReader {
         Ok(())
     }
 
+    /// Ensure all paths starting at the root whose symbol stack precondition starts with any of
+    /// the given symbols are loaded.  The paths are loaded in bulk, using a single query for up
+    /// to a few hundred symbols, and subsequent loads of extensions from the root for
+    /// symbol stacks that start with any of these symbols are skipped.  This saves many round
+    /// trips if the symbols that a query will need are known up front, which can be measured
+    /// using the `root_path_prefetches` and `root_path_prefetch_hits` [statistics][Stats].
+    pub fn prefetch_root_paths<I, S>(
+        &mut self,
+        symbols: I,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let symbols = symbols
+            .into_iter()
+            .map(|s| s.as_ref().to_string())
+            .filter(|s| !self.prefetched_root_symbols.contains(s))
+            .sorted()
+            .dedup()
+            .collect::<Vec<_>>();
+        for batch in symbols.chunks(PREFETCH_BATCH_SIZE) {
+            copious_debugging!(" * Prefetch root paths for {} symbols", batch.len());
+            self.stats.root_path_prefetches += 1;
+            // a symbol stack key consists of a variable marker, followed by the symbols
+            let patterns = batch
+                .iter()
+                .flat_map(|symbol| {
+                    let symbol = symbol.replace("%", "\\%").replace("_", "\\_");
+                    [
+                        format!("_\u{241E}{}", symbol),
+                        format!("_\u{241E}{}\u{241F}%", symbol),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let query = format!(
+                "SELECT file, blob_decode(value) FROM root_paths WHERE {}",
+                std::iter::repeat("symbol_stack LIKE ? ESCAPE '\\'")
+                    .take(patterns.len())
+                    .join(" OR ")
+            );
+            let mut stmt = self.conn.prepare(&query)?;
+            let paths = stmt.query_map(rusqlite::params_from_iter(patterns), |row| {
+                let file = row.get::<_, String>(0)?;
+                let value = row.get::<_, Vec<u8>>(1)?;
+                Ok((file, value))
+            })?;
+            for path in paths {
+                cancellation_flag.check("prefetching root paths")?;
+                let (file, value) = path?;
+                if self.shadowed_files.contains(&file) {
+                    continue;
+                }
+                Self::load_graph_for_file_inner(
+                    &file,
+                    &mut self.graph,
+                    &mut self.loaded_graphs,
+                    &self.conn,
+                    &*self.codec,
+                    &mut self.stats,
+                )?;
+                let path =
+                    decode_stored_path(&self.conn, &mut self.path_dictionaries, &file, &value)?;
+                let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+                self.db
+                    .add_partial_path(&self.graph, &mut self.partials, path);
+            }
+            self.prefetched_root_symbols.extend(batch.iter().cloned());
+            self.check_memory_budget()?;
+        }
+        Ok(())
+    }
+
+    /// Ensure the graph of the given file is loaded, and [prefetch][Self::prefetch_root_paths]
+    /// the root paths for all symbols that are pushed in the file, which are the symbols that
+    /// queries for references in the file are most likely to need.
+    pub fn prefetch_root_paths_for_file(
+        &mut self,
+        file: &str,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        let file = self.load_graph_for_file(file)?;
+        let symbols = self
+            .graph
+            .nodes_for_file(file)
+            .filter(|n| {
+                matches!(
+                    self.graph[*n],
+                    Node::PushSymbol(_) | Node::PushScopedSymbol(_)
+                )
+            })
+            .filter_map(|n| self.graph[n].symbol())
+            .map(|s| self.graph[s].to_string())
+            .collect::<Vec<_>>();
+        self.prefetch_root_paths(symbols, cancellation_flag)
+    }
+
     /// Ensure all possible extensions for the given partial path are loaded.
     pub fn load_partial_path_extensions(
         &mut self,
@@ -934,10 +3341,13 @@ impl SQLiteReader {
         let end_node = self.graph[path.end_node].id();
         if self.graph[path.end_node].file().is_some() {
             self.load_paths_for_node(path.end_node, cancellation_flag)?;
+            for alias in self.graph.node_aliases(path.end_node).to_vec() {
+                self.load_paths_for_node(alias, cancellation_flag)?;
+            }
         } else if end_node.is_root() {
             self.load_paths_for_root(path.symbol_stack_postcondition, cancellation_flag)?;
         }
-        Ok(())
+        self.check_memory_budget()
     }
 
     /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
@@ -949,6 +3359,112 @@ impl SQLiteReader {
     pub fn stats(&self) -> Stats {
         self.stats.clone()
     }
+
+    /// Return statistics about the contents of the database. This decodes the graphs of all
+    /// files, and can be expensive for large databases.
+    pub fn database_stats(&mut self) -> Result<DatabaseStats> {
+        let mut node_paths = path_counts_per_file(&self.conn, "file_paths")?;
+        let mut root_paths = path_counts_per_file(&self.conn, "root_paths")?;
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT file, tag, error, value FROM graphs ORDER BY file")?;
+        let mut rows = stmt.query([])?;
+        let mut files = Vec::new();
+        while let Some(row) = rows.next()? {
+            let file = row.get::<_, String>(0)?;
+            let tag = row.get::<_, String>(1)?;
+            let status = row.get_ref(2)?.into();
+            let value = row.get::<_, Vec<u8>>(3)?;
+            let graph = self.codec.decode_graph(value)?;
+            let language = graph
+                .file_languages
+                .data
+                .iter()
+                .find(|l| l.file == file)
+                .map(|l| l.language.clone());
+            files.push(FileStats {
+                node_paths: node_paths.remove(&file).unwrap_or_default(),
+                root_paths: root_paths.remove(&file).unwrap_or_default(),
+                path: PathBuf::from(file),
+                tag,
+                status,
+                language,
+                nodes: graph.nodes.data.len(),
+                edges: graph.edges.data.len(),
+            });
+        }
+        let page_count = self
+            .conn
+            .query_row("PRAGMA page_count", [], |r| r.get::<_, u64>(0))?;
+        let page_size = self
+            .conn
+            .query_row("PRAGMA page_size", [], |r| r.get::<_, u64>(0))?;
+        Ok(DatabaseStats {
+            files,
+            size: page_count * page_size,
+        })
+    }
+}
+
+/// The value of the `auto_vacuum` pragma for incremental vacuuming.
+const INCREMENTAL_AUTO_VACUUM: u32 = 2;
+
+/// Estimates the number of rows that changed since the database was last analyzed, as the sum of
+/// the differences between the current row counts of the tables and the row counts recorded in
+/// the statistics of the last analysis.  If the database was never analyzed, all rows count as
+/// changed.
+fn changed_rows_since_analyze(conn: &Connection) -> Result<u64> {
+    let mut analyzed = HashMap::<String, u64>::new();
+    let has_stats = {
+        let mut stmt = conn.prepare(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sqlite_stat1'",
+        )?;
+        stmt.exists([])?
+    };
+    if has_stats {
+        let mut stmt = conn.prepare("SELECT tbl, stat FROM sqlite_stat1")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let table = row.get::<_, String>(0)?;
+            // the first number of the statistics is the number of rows in the table
+            let count = row
+                .get::<_, Option<String>>(1)?
+                .and_then(|stat| stat.split(' ').next().and_then(|n| n.parse::<u64>().ok()))
+                .unwrap_or(0);
+            let entry = analyzed.entry(table).or_default();
+            *entry = (*entry).max(count);
+        }
+    }
+    let tables = {
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?;
+        let tables = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        tables
+    };
+    let mut changed = 0;
+    for table in tables {
+        let count = conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| {
+            r.get::<_, u64>(0)
+        })?;
+        let analyzed = analyzed.get(&table).copied().unwrap_or(0);
+        changed += count.abs_diff(analyzed);
+    }
+    Ok(changed)
+}
+
+/// Count the stored paths per file in the given paths table.
+fn path_counts_per_file(conn: &Connection, table: &str) -> Result<HashMap<String, usize>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT file, COUNT(*) FROM {} GROUP BY file",
+        table
+    ))?;
+    let counts = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?)))?
+        .collect::<std::result::Result<_, rusqlite::Error>>()?;
+    Ok(counts)
 }
 
 // Methods for computing keys and patterns for a symbol stack. The format of a storage key is:
@@ -1026,6 +3542,374 @@ impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError>
     fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
         (&self.graph, &mut self.partials, &self.db)
     }
+
+    fn record_resolution(&mut self, candidates: &[Handle<PartialPath>]) {
+        self.db.record_resolution(candidates);
+    }
+}
+
+/// Statistics about the contents of a database, returned by [`SQLiteReader::database_stats`].
+pub struct DatabaseStats {
+    /// Statistics for every file in the database, ordered by path.
+    pub files: Vec<FileStats>,
+    /// The size of the database in bytes.
+    pub size: u64,
+}
+
+/// The thresholds that decide which tasks are run by [`SQLiteWriter::maintain`][].
+#[derive(Clone, Debug)]
+pub struct MaintenancePlan {
+    /// The minimum number of bytes in free pages for which the database is vacuumed.
+    pub min_free_bytes: u64,
+    /// The minimum number of rows changed since the last analysis for which the database is
+    /// analyzed again.
+    pub min_changed_rows_for_analyze: u64,
+    /// The minimum number of rows changed since the last analysis for which all indexes are
+    /// rebuilt.
+    pub min_changed_rows_for_reindex: u64,
+    /// Whether all tasks are run, regardless of the thresholds.
+    pub force: bool,
+}
+
+impl MaintenancePlan {
+    /// Returns a plan that runs all tasks, regardless of the thresholds.
+    pub fn all() -> Self {
+        Self {
+            force: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for MaintenancePlan {
+    fn default() -> Self {
+        Self {
+            min_free_bytes: 16 * 1024 * 1024,
+            min_changed_rows_for_analyze: 10_000,
+            min_changed_rows_for_reindex: 1_000_000,
+            force: false,
+        }
+    }
+}
+
+/// The tasks that were run by [`SQLiteWriter::maintain`][], and the measurements they were based
+/// on.
+#[derive(Clone, Debug, Default)]
+pub struct MaintenanceReport {
+    /// The number of bytes in free pages before maintenance.
+    pub free_bytes: u64,
+    /// The estimated number of rows changed since the last analysis, before maintenance.
+    pub changed_rows: u64,
+    /// Whether the database was vacuumed.
+    pub vacuumed: bool,
+    /// The number of bytes by which vacuuming reduced the size of the database.
+    pub bytes_freed: u64,
+    /// Whether the database was analyzed.
+    pub analyzed: bool,
+    /// Whether all indexes were rebuilt.
+    pub reindexed: bool,
+}
+
+/// Statistics about a single file in the database.
+pub struct FileStats {
+    pub path: PathBuf,
+    pub tag: String,
+    pub status: FileStatus,
+    /// The [language][crate::graph::StackGraph::set_file_language] of the file, if known.
+    pub language: Option<String>,
+    /// The number of nodes in the file's graph.
+    pub nodes: usize,
+    /// The number of edges in the file's graph.
+    pub edges: usize,
+    /// The number of partial paths starting at a node in the file.
+    pub node_paths: usize,
+    /// The number of partial paths starting at the root node.
+    pub root_paths: usize,
+}
+
+/// An integrity problem in a database, found by [`verify`][].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IntegrityIssue {
+    /// The stored graph of the file cannot be decoded or loaded, or does not have the stored
+    /// fingerprint.
+    CorruptGraph { file: String, error: String },
+    /// A stored partial path of the file cannot be decoded, or refers to nodes that do not exist
+    /// in the file's graph.
+    CorruptPath { file: String, error: String },
+    /// A stored partial path of the file is stored under a key that does not match the path, which
+    /// means it is not found when looking up paths by their start node or symbol stack.
+    MisindexedPath {
+        file: String,
+        key: String,
+        expected: String,
+    },
+    /// Partial paths are stored for a file that has no stored graph.
+    OrphanedPaths { file: String },
+    /// The source of the file changed since it was indexed.
+    ChangedSource { file: String },
+    /// The source of the file does not exist anymore.
+    MissingSource { file: String },
+}
+
+impl IntegrityIssue {
+    /// Returns the file that has this issue.
+    pub fn file(&self) -> &str {
+        match self {
+            Self::CorruptGraph { file, .. }
+            | Self::CorruptPath { file, .. }
+            | Self::MisindexedPath { file, .. }
+            | Self::OrphanedPaths { file }
+            | Self::ChangedSource { file }
+            | Self::MissingSource { file } => file,
+        }
+    }
+
+    /// Returns the action that repairs this issue.
+    pub fn repair_action(&self) -> RepairAction {
+        match self {
+            Self::OrphanedPaths { .. } | Self::MissingSource { .. } => RepairAction::Remove,
+            _ => RepairAction::Reindex,
+        }
+    }
+}
+
+impl std::fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::CorruptGraph { error, .. } => write!(f, "corrupt graph: {}", error),
+            Self::CorruptPath { error, .. } => write!(f, "corrupt partial path: {}", error),
+            Self::MisindexedPath { key, expected, .. } => write!(
+                f,
+                "partial path stored under key {:?}, expected {:?}",
+                key, expected
+            ),
+            Self::OrphanedPaths { .. } => write!(f, "partial paths stored without graph"),
+            Self::ChangedSource { .. } => write!(f, "source changed since indexing"),
+            Self::MissingSource { .. } => write!(f, "source does not exist"),
+        }
+    }
+}
+
+/// How to repair a file with integrity issues.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum RepairAction {
+    /// Index the file again, which replaces all of its stored data.
+    Reindex,
+    /// Remove all stored data of the file.
+    Remove,
+}
+
+/// The result of [verifying][verify] a database.
+#[derive(Clone, Debug, Default)]
+pub struct VerificationReport {
+    /// The number of files that were verified.
+    pub files: usize,
+    /// The issues that were found, ordered by file.
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl VerificationReport {
+    /// Returns whether no issues were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the plan to repair all issues, which contains one action for every file with
+    /// issues, ordered by file.  Files are removed if any of their issues requires it, and
+    /// reindexed otherwise.  Since the files are independent, the plan can be carried out
+    /// incrementally, for example by [cleaning][SQLiteWriter::clean_file] all files in the plan and
+    /// indexing the files that must be reindexed.
+    pub fn repair_plan(&self) -> Vec<(PathBuf, RepairAction)> {
+        let mut plan = BTreeMap::new();
+        for issue in &self.issues {
+            let action = plan
+                .entry(PathBuf::from(issue.file()))
+                .or_insert(RepairAction::Reindex);
+            *action = (*action).max(issue.repair_action());
+        }
+        plan.into_iter().collect()
+    }
+}
+
+/// Verifies the integrity of the database.  This checks that the graph of every file can be
+/// decoded and loaded, and has the stored fingerprint, and that every stored partial path can be
+/// decoded, refers to existing nodes, and is stored under the right key.
+///
+/// If a `source_tag` function is given, it is called with the name of every file, and must return
+/// the tag of the current source of the file, computed in the same way as the tags that were
+/// stored when indexing, or `None` if the source does not exist anymore.  Files whose tags do not
+/// match are reported as changed.
+///
+/// All graphs and paths are decoded, which can be expensive for large databases.  The data that is
+/// loaded into the reader is not affected.
+pub fn verify(
+    db: &SQLiteReader,
+    mut source_tag: Option<&mut dyn FnMut(&str) -> Option<String>>,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<VerificationReport> {
+    let conn = &db.conn;
+    let mut report = VerificationReport::default();
+    let mut stmt =
+        conn.prepare("SELECT file, tag, error, value, fingerprint FROM graphs ORDER BY file")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        cancellation_flag.check("verifying files")?;
+        let file = row.get::<_, String>(0)?;
+        let tag = row.get::<_, String>(1)?;
+        let failed = row.get::<_, Option<String>>(2)?.is_some();
+        let value = row.get::<_, Vec<u8>>(3)?;
+        let fingerprint = row.get::<_, Option<String>>(4)?;
+        report.files += 1;
+
+        if let Some(source_tag) = source_tag.as_mut() {
+            match source_tag(&file) {
+                Some(source_tag) if source_tag == tag => {}
+                Some(_) => report
+                    .issues
+                    .push(IntegrityIssue::ChangedSource { file: file.clone() }),
+                None => {
+                    report.issues.push(IntegrityIssue::MissingSource { file });
+                    continue;
+                }
+            }
+        }
+        if failed {
+            // the graphs of files that failed to index are empty
+            continue;
+        }
+
+        let mut graph = StackGraph::new();
+        if let Err(error) = verify_graph(&mut graph, &*db.codec, &file, value, fingerprint) {
+            report
+                .issues
+                .push(IntegrityIssue::CorruptGraph { file, error });
+            continue;
+        }
+        verify_paths(conn, &mut graph, &file, &mut report.issues)?;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT file FROM file_paths WHERE file NOT IN (SELECT file FROM graphs)
+         UNION
+         SELECT file FROM root_paths WHERE file NOT IN (SELECT file FROM graphs)
+         ORDER BY file",
+    )?;
+    let orphans = stmt
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    report.issues.extend(
+        orphans
+            .into_iter()
+            .map(|file| IntegrityIssue::OrphanedPaths { file }),
+    );
+    report.issues.sort_by(|a, b| a.file().cmp(b.file()));
+    Ok(report)
+}
+
+/// Decodes the stored graph of a file, and loads it into the given graph, verifying its
+/// fingerprint if one is stored.
+fn verify_graph(
+    graph: &mut StackGraph,
+    codec: &dyn BlobCodec,
+    file: &str,
+    value: Vec<u8>,
+    fingerprint: Option<String>,
+) -> std::result::Result<(), String> {
+    let file_graph = codec.decode_graph(value).map_err(|e| e.to_string())?;
+    match fingerprint {
+        Some(fingerprint) => {
+            let fingerprint = fingerprint
+                .parse::<Fingerprint>()
+                .map_err(|_| format!("invalid fingerprint {}", fingerprint))?;
+            file_graph.load_into_verified(graph, file, fingerprint)
+        }
+        None => file_graph.load_into(graph),
+    }
+    .map_err(|e| e.to_string())?;
+    if graph.get_file(file).is_none() {
+        return Err(format!("graph does not contain file {}", file));
+    }
+    Ok(())
+}
+
+/// Verifies the stored partial paths of a file, whose graph must be loaded in the given graph.
+fn verify_paths(
+    conn: &Connection,
+    graph: &mut StackGraph,
+    file: &str,
+    issues: &mut Vec<IntegrityIssue>,
+) -> Result<()> {
+    let mut partials = PartialPaths::new();
+    let mut dictionaries = HashMap::new();
+    let mut verify_path = |key: String, value: Vec<u8>, is_root: bool| {
+        let path = decode_stored_path(conn, &mut dictionaries, file, &value)
+            .map_err(|e| e.to_string())
+            .and_then(|path| verify_path_nodes(graph, &mut partials, path));
+        let path = match path {
+            Ok(path) => path,
+            Err(error) => {
+                issues.push(IntegrityIssue::CorruptPath {
+                    file: file.to_string(),
+                    error,
+                });
+                return;
+            }
+        };
+        let expected = if is_root {
+            path.symbol_stack_precondition
+                .storage_key(graph, &mut partials)
+        } else {
+            graph[path.start_node].id().local_id().to_string()
+        };
+        if key != expected {
+            issues.push(IntegrityIssue::MisindexedPath {
+                file: file.to_string(),
+                key,
+                expected,
+            });
+        }
+    };
+
+    let mut stmt =
+        conn.prepare_cached("SELECT local_id, blob_decode(value) FROM file_paths WHERE file = ?")?;
+    let paths = stmt
+        .query_map([file], |r| {
+            Ok((r.get::<_, u32>(0)?, r.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for (local_id, value) in paths {
+        verify_path(local_id.to_string(), value, false);
+    }
+
+    let mut stmt = conn
+        .prepare_cached("SELECT symbol_stack, blob_decode(value) FROM root_paths WHERE file = ?")?;
+    let paths = stmt
+        .query_map([file], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for (symbol_stack, value) in paths {
+        verify_path(symbol_stack, value, true);
+    }
+    Ok(())
+}
+
+/// Converts a decoded partial path, and checks that all of its nodes exist in the given graph.
+fn verify_path_nodes(
+    graph: &mut StackGraph,
+    partials: &mut PartialPaths,
+    path: serde::PartialPath,
+) -> std::result::Result<PartialPath, String> {
+    let path = path
+        .to_partial_path(graph, partials)
+        .map_err(|e| e.to_string())?;
+    let mut edges = path.edges;
+    while let Some(edge) = edges.pop_front(partials) {
+        if graph.node_for_id(edge.source_node_id).is_none() {
+            return Err("path has an edge from a node that does not exist".to_string());
+        }
+    }
+    Ok(path)
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1034,8 +3918,16 @@ pub struct Stats {
     pub file_cached: usize,
     pub root_path_loads: usize,
     pub root_path_cached: usize,
+    /// The number of bulk queries issued to prefetch root paths.
+    pub root_path_prefetches: usize,
+    /// The number of root path loads that were skipped because the paths had been prefetched.
+    pub root_path_prefetch_hits: usize,
     pub node_path_loads: usize,
     pub node_path_cached: usize,
+    /// The number of references that were resolved from their pre-computed local resolutions.
+    pub local_resolution_hits: usize,
+    /// The number of references that were looked up, but had no local resolutions.
+    pub local_resolution_misses: usize,
 }
 
 impl Stats {
@@ -1061,6 +3953,154 @@ fn check_version(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Check if the database has a version that this library version can read, and return the
+/// version.
+fn check_readable_version(conn: &Connection) -> Result<usize> {
+    let version = conn.query_row("SELECT version FROM metadata", [], |r| r.get::<_, usize>(0))?;
+    match version {
+        VERSION | LEGACY_VERSION => Ok(version),
+        _ => Err(StorageError::IncorrectVersion(version)),
+    }
+}
+
+/// Decodes a stored partial path of the given file.  The dictionary of the file is loaded when it
+/// is first needed, and kept in the given map for later paths of the same file.
+fn decode_stored_path(
+    conn: &Connection,
+    dictionaries: &mut HashMap<String, PathDictionary>,
+    file: &str,
+    value: &[u8],
+) -> Result<serde::PartialPath> {
+    if !dictionaries.contains_key(file) {
+        let dictionary = load_path_dictionary(conn, file)?;
+        dictionaries.insert(file.to_string(), dictionary);
+    }
+    let (path, _): (CompactPartialPath, usize) = bincode::decode_from_slice(value, BINCODE_CONFIG)?;
+    dictionaries[file]
+        .decode(&path)
+        .map_err(|_| StorageError::InvalidPath(file.to_string()))
+}
+
+/// Loads the path dictionary of the given file, which is empty if the file has no stored paths.
+fn load_path_dictionary(conn: &Connection, file: &str) -> Result<PathDictionary> {
+    let mut stmt =
+        conn.prepare_cached("SELECT blob_decode(value) FROM path_dictionaries WHERE file = ?")?;
+    let value = stmt
+        .query_row([file], |r| r.get::<_, Vec<u8>>(0))
+        .optional()?;
+    match value {
+        Some(value) => {
+            let (dictionary, _): (PathDictionary, usize) =
+                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+            Ok(dictionary)
+        }
+        None => Ok(PathDictionary::default()),
+    }
+}
+
+/// Sets the key of an encrypted database, which must be done before anything else is read from or
+/// written to the database.  Whether the key is correct is checked by reading the schema, which
+/// fails if the database cannot be decrypted.
+fn set_key(conn: &Connection, path: &Path, key: Option<&str>) -> Result<()> {
+    let key = match key {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    conn.pragma_update(None, "key", key)?;
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |r| {
+        r.get::<_, i64>(0)
+    })
+    .map_err(|_| StorageError::IncorrectKey(path.to_string_lossy().to_string()))?;
+    Ok(())
+}
+
+/// Reads the metadata of the database.
+fn metadata(conn: &Connection) -> Result<DatabaseMetadata> {
+    let schema_version =
+        conn.query_row("SELECT version FROM metadata", [], |r| r.get::<_, usize>(0))?;
+    let codec = stored_codec_name(conn)?;
+    let mut stmt = conn.prepare_cached("SELECT key, value FROM main.properties")?;
+    let properties = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+        .collect::<std::result::Result<BTreeMap<_, _>, _>>()?;
+
+    let mut language_rules = BTreeMap::new();
+    for (key, hash) in &properties {
+        let language = match key
+            .strip_prefix(RULES_PROPERTY_PREFIX)
+            .and_then(|key| key.strip_suffix(".hash"))
+        {
+            Some(language) => language,
+            None => continue,
+        };
+        let version_key = format!("{}{}.version", RULES_PROPERTY_PREFIX, language);
+        language_rules.insert(
+            language.to_string(),
+            LanguageRules {
+                hash: hash.clone(),
+                version: properties.get(&version_key).cloned(),
+            },
+        );
+    }
+
+    Ok(DatabaseMetadata {
+        schema_version,
+        crate_version: properties.get(CRATE_VERSION_PROPERTY).cloned(),
+        created_at: properties
+            .get(CREATED_AT_PROPERTY)
+            .and_then(|value| value.parse().ok()),
+        build_flags: properties
+            .get(BUILD_FLAGS_PROPERTY)
+            .map(|flags| {
+                flags
+                    .split(',')
+                    .filter(|flag| !flag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        codec,
+        language_rules,
+        properties,
+    })
+}
+
+/// Returns the name of the [codec][BlobCodec] that is recorded in the metadata of the database.
+fn stored_codec_name(conn: &Connection) -> Result<String> {
+    Ok(conn.query_row("SELECT codec FROM metadata", [], |r| r.get(0))?)
+}
+
+/// Returns the given codec if it has the given name that is recorded in a database, or the built-in
+/// codec with that name if no codec is given.
+fn resolve_codec(name: String, codec: Option<Arc<dyn BlobCodec>>) -> Result<Arc<dyn BlobCodec>> {
+    match codec {
+        Some(codec) if codec.name() == name => Ok(codec),
+        Some(_) => Err(StorageError::IncorrectCodec(name)),
+        None => builtin_codec(&name).ok_or(StorageError::UnknownCodec(name)),
+    }
+}
+
+/// Registers the SQL functions `blob_encode` and `blob_decode`, which pass blobs through the given
+/// codec.  All blobs except graphs are encoded when they are written, and decoded when they are
+/// read, by the SQL statements that write and read them.  Graphs are encoded and decoded by the
+/// codec directly, because it encodes them from the graph itself.
+fn set_codec(conn: &Connection, codec: Arc<dyn BlobCodec>) -> Result<()> {
+    let flags = FunctionFlags::SQLITE_DETERMINISTIC | FunctionFlags::SQLITE_UTF8;
+    let encoder = AssertUnwindSafe(codec.clone());
+    conn.create_scalar_function("blob_encode", 1, flags, move |ctx| {
+        encoder
+            .encode(ctx.get::<Vec<u8>>(0)?)
+            .map_err(rusqlite::Error::UserFunctionError)
+    })?;
+    let decoder = AssertUnwindSafe(codec);
+    conn.create_scalar_function("blob_decode", 1, flags, move |ctx| {
+        decoder
+            .decode(ctx.get::<Vec<u8>>(0)?)
+            .map_err(rusqlite::Error::UserFunctionError)
+    })?;
+    Ok(())
+}
+
 fn set_pragmas_and_functions(conn: &Connection) -> Result<()> {
     conn.execute_batch(PRAGMAS)?;
     conn.create_scalar_function(
@@ -1078,6 +4118,13 @@ fn set_pragmas_and_functions(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Returns the given time in seconds since the Unix epoch.
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 fn init_indexes(conn: &mut Connection) -> Result<()> {
     let tx = conn.transaction()?;
     tx.execute_batch(INDEXES)?;
@@ -1097,10 +4144,59 @@ fn status_for_file<T: AsRef<str>>(
             .optional()?
             .unwrap_or(FileStatus::Missing)
     } else {
-        let mut stmt = conn.prepare_cached("SELECT status FROM graphs WHERE file = ?")?;
+        let mut stmt = conn.prepare_cached("SELECT error FROM graphs WHERE file = ?")?;
         stmt.query_row([file], |r| r.get_ref(0).map(FileStatus::from))
             .optional()?
             .unwrap_or(FileStatus::Missing)
     };
     Ok(result)
 }
+
+fn globals_for_file(conn: &Connection, file: &Path) -> Result<BTreeMap<String, String>> {
+    let mut stmt = conn.prepare_cached("SELECT name, value FROM file_globals WHERE file = ?")?;
+    let globals = stmt
+        .query_map([file.to_string_lossy()], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })?
+        .collect::<std::result::Result<BTreeMap<_, _>, _>>()?;
+    Ok(globals)
+}
+
+fn parse_errors_for_file(conn: &Connection, file: &str) -> Result<Vec<Span>> {
+    let mut stmt =
+        conn.prepare_cached("SELECT blob_decode(value) FROM parse_errors WHERE file = ?")?;
+    let value = stmt
+        .query_row([file], |row| row.get::<_, Vec<u8>>(0))
+        .optional()?;
+    match value {
+        Some(value) => {
+            let (spans, _): (Vec<Span>, usize) =
+                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+            Ok(spans)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn dependents_of_file(conn: &Connection, file: &Path) -> Result<Vec<PathBuf>> {
+    let mut stmt = conn
+        .prepare_cached("SELECT file FROM file_dependencies WHERE dependency = ? ORDER BY file")?;
+    let files = stmt
+        .query_map([file.to_string_lossy()], |r| {
+            r.get::<_, String>(0).map(PathBuf::from)
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(files)
+}
+
+fn dependencies_of_file(conn: &Connection, file: &Path) -> Result<Vec<PathBuf>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT dependency FROM file_dependencies WHERE file = ? ORDER BY dependency",
+    )?;
+    let files = stmt
+        .query_map([file.to_string_lossy()], |r| {
+            r.get::<_, String>(0).map(PathBuf::from)
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(files)
+}
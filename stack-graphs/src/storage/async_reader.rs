@@ -0,0 +1,204 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines an async wrapper around [`SQLiteReader`][], for use from async servers.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use lsp_positions::Span;
+use tokio::runtime::Handle;
+
+use crate::storage::FileStatus;
+use crate::storage::Result;
+use crate::storage::SQLiteReader;
+use crate::storage::StorageError;
+use crate::CancellationError;
+use crate::CancellationFlag;
+
+/// An async wrapper around a [`SQLiteReader`][].
+///
+/// All database access and stitching is blocking, so every call runs on the blocking thread pool
+/// of a tokio runtime, which is the runtime that the wrapper was created in, unless another one
+/// is given with [`SQLiteReaderAsync::with_runtime`][].  Calls are serialized, because they share
+/// the graphs and partial paths that the reader has loaded.  The wrapper can be cloned cheaply,
+/// and all clones share the same reader.
+///
+/// Dropping the future of a call cancels it cooperatively: the work is not interrupted, but the
+/// [cancellation flag][CancellationFlag] that is passed to it is raised, so that loading and
+/// stitching stop at their next cancellation check.  This makes it possible to apply timeouts by
+/// wrapping calls in `tokio::time::timeout`.
+#[derive(Clone)]
+pub struct SQLiteReaderAsync {
+    reader: Arc<Mutex<SQLiteReader>>,
+    runtime: Handle,
+}
+
+impl SQLiteReaderAsync {
+    /// Opens a database for reading.  Must be called from within a tokio runtime.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = PathBuf::from(path.as_ref());
+        let runtime = Handle::current();
+        let reader = join(
+            runtime
+                .spawn_blocking(move || SQLiteReader::open(path))
+                .await,
+        )??;
+        Ok(Self::with_runtime(reader, runtime))
+    }
+
+    /// Opens an encrypted database for reading with the given key.  Must be called from within a
+    /// tokio runtime.
+    #[cfg(feature = "encryption")]
+    pub async fn open_encrypted<P: AsRef<Path>>(path: P, key: &str) -> Result<Self> {
+        let path = PathBuf::from(path.as_ref());
+        let key = key.to_string();
+        let runtime = Handle::current();
+        let reader = join(
+            runtime
+                .spawn_blocking(move || SQLiteReader::open_encrypted(path, &key))
+                .await,
+        )??;
+        Ok(Self::with_runtime(reader, runtime))
+    }
+
+    /// Wraps an open reader.  Must be called from within a tokio runtime.
+    pub fn new(reader: SQLiteReader) -> Self {
+        Self::with_runtime(reader, Handle::current())
+    }
+
+    /// Wraps an open reader, whose calls run on the blocking thread pool of the given runtime.
+    /// This can be used to keep blocking work off the runtime that serves requests.
+    pub fn with_runtime(reader: SQLiteReader, runtime: Handle) -> Self {
+        Self {
+            reader: Arc::new(Mutex::new(reader)),
+            runtime,
+        }
+    }
+
+    /// Runs a function with exclusive access to the reader on the blocking thread pool, and
+    /// returns its result.  The function should pass the given cancellation flag to any loading
+    /// or stitching it does, so that it stops when the returned future is dropped.
+    pub async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut SQLiteReader, &dyn CancellationFlag) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(cancelled.clone());
+        let reader = self.reader.clone();
+        let task = self.runtime.spawn_blocking(move || {
+            let cancellation_flag = FutureCancellationFlag(cancelled);
+            // A panic while the reader was in use does not leave it in a state that other calls
+            // cannot use, because all loaded data is consistent between database accesses.
+            let mut reader = reader.lock().unwrap_or_else(|e| e.into_inner());
+            cancellation_flag.check("waiting for reader")?;
+            f(&mut *reader, &cancellation_flag)
+        });
+        join(task.await)?
+    }
+
+    /// Ensure the graph for the given file is loaded.
+    pub async fn load_graph_for_file(&self, file: String) -> Result<()> {
+        self.run(move |reader, _| reader.load_graph_for_file(&file).map(|_| ()))
+            .await
+    }
+
+    /// Load the graph and all stored partial paths of the given file.
+    pub async fn load_partial_paths_for_file(&self, file: String) -> Result<()> {
+        self.run(move |reader, cancellation_flag| {
+            reader.load_partial_paths_for_file(&file, cancellation_flag)
+        })
+        .await
+    }
+
+    /// Get the file's status in the database. If a tag is provided, it must match or the file
+    /// is reported missing.
+    pub async fn status_for_file(&self, file: String, tag: Option<String>) -> Result<FileStatus> {
+        self.run(move |reader, _| reader.status_for_file(&file, tag))
+            .await
+    }
+
+    /// Returns the tag that the given file was stored with, or `None` if the file is not in the
+    /// database.
+    pub async fn tag_for_file(&self, file: String) -> Result<Option<String>> {
+        self.run(move |reader, _| reader.tag_for_file(&file)).await
+    }
+
+    /// Returns the generation of the database, which increases whenever a file is stored in or
+    /// removed from the database.
+    pub async fn generation(&self) -> Result<u64> {
+        self.run(move |reader, _| reader.generation()).await
+    }
+
+    /// Returns the files that the resolutions of the given file depend on.
+    pub async fn dependencies_of_file(&self, file: PathBuf) -> Result<Vec<PathBuf>> {
+        self.run(move |reader, _| reader.dependencies_of_file(&file))
+            .await
+    }
+
+    /// Returns the files whose resolutions depend on the given file.
+    pub async fn dependents_of_file(&self, file: PathBuf) -> Result<Vec<PathBuf>> {
+        self.run(move |reader, _| reader.dependents_of_file(&file))
+            .await
+    }
+
+    /// Returns the regions of the given file that contained parse errors when it was indexed.
+    pub async fn parse_errors_for_file(&self, file: String) -> Result<Vec<Span>> {
+        self.run(move |reader, _| reader.parse_errors_for_file(&file))
+            .await
+    }
+
+    /// Returns why the stored partial paths of the given file are incomplete, if they are.
+    pub async fn incomplete_reason_for_file(&self, file: String) -> Result<Option<String>> {
+        self.run(move |reader, _| reader.incomplete_reason_for_file(&file))
+            .await
+    }
+
+    /// Removes all loaded graphs and partial paths from the reader.
+    pub async fn clear(&self) -> Result<()> {
+        self.run(|reader, _| {
+            reader.clear();
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Returns the result of a blocking task, and propagates its panic, if it panicked.
+fn join<T>(result: std::result::Result<T, tokio::task::JoinError>) -> Result<T> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(_) => Err(StorageError::Cancelled("runtime shutdown")),
+    }
+}
+
+/// Raises a cancellation flag when dropped, which happens when the future that owns it is
+/// dropped before it completes.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+struct FutureCancellationFlag(Arc<AtomicBool>);
+
+impl CancellationFlag for FutureCancellationFlag {
+    fn check(&self, at: &'static str) -> std::result::Result<(), CancellationError> {
+        if self.0.load(Ordering::Relaxed) {
+            return Err(CancellationError(at));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Temporary on-disk storage for the graph of a file that is built in chunks.
+//!
+//! Graph construction for very large files, such as machine-generated sources with hundreds of
+//! thousands of nodes, can be done in chunks, which are serialized and written to a
+//! [`GraphChunks`][] spill file as soon as they are complete.  Only the chunk that is being built
+//! has to be kept in memory as a stack graph.  When all chunks are written, they are loaded into
+//! the stack graph of the file in one go.
+//!
+//! ```rust,ignore
+//! use stack_graphs::storage::chunks::GraphChunks;
+//!
+//! let mut chunks = GraphChunks::new()?;
+//! for chunk in serialized_chunks {
+//!     chunks.push(&chunk)?;
+//! }
+//! chunks.load_into(&mut graph)?;
+//! ```
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::graph::StackGraph;
+use crate::serde;
+
+use super::Result;
+use super::BINCODE_CONFIG;
+
+/// Counter to give the spill files of a process unique names.
+static NEXT_SPILL_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file that holds the serialized chunks of a graph.  The file is removed when the
+/// value is dropped.
+pub struct GraphChunks {
+    path: PathBuf,
+    file: BufWriter<File>,
+    len: usize,
+}
+
+impl GraphChunks {
+    /// Creates an empty spill file in the system's temporary directory.
+    pub fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "stack-graphs-chunks-{}-{}",
+            std::process::id(),
+            NEXT_SPILL_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            len: 0,
+        })
+    }
+
+    /// Writes a chunk to the spill file.
+    pub fn push(&mut self, chunk: &serde::StackGraph) -> Result<()> {
+        bincode::encode_into_std_write(chunk, &mut self.file, BINCODE_CONFIG)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns the number of chunks in the spill file.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Loads all chunks into the given graph.  Files of the chunks that do not exist in the graph
+    /// are added to it.  The nodes of all chunks are loaded before any of their edges, so that
+    /// edges can refer to nodes of other chunks.  Only a single chunk is decoded at a time.
+    pub fn load_into(mut self, graph: &mut StackGraph) -> Result<()> {
+        self.file.flush()?;
+        for chunk in self.chunks()? {
+            let chunk = chunk?;
+            for file in &chunk.files.data {
+                chunk.get_or_create_file(graph, file);
+            }
+            for language in &chunk.file_languages.data {
+                let file = chunk.get_or_create_file(graph, &language.file);
+                graph.set_file_language(file, &language.language);
+            }
+            chunk.load_nodes_into(graph)?;
+        }
+        for chunk in self.chunks()? {
+            chunk?.load_edges_into(graph)?;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator that decodes the chunks from the start of the spill file.
+    fn chunks(&mut self) -> Result<impl Iterator<Item = Result<serde::StackGraph>> + '_> {
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(file);
+        Ok((0..self.len).map(move |_| -> Result<serde::StackGraph> {
+            Ok(bincode::decode_from_std_read(&mut reader, BINCODE_CONFIG)?)
+        }))
+    }
+}
+
+impl Drop for GraphChunks {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
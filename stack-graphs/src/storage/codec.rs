@@ -0,0 +1,174 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Codecs for the blobs that are stored in a database.
+//!
+//! The [codec][BlobCodec] of a database encodes the values that are stored as blobs.  Graphs are
+//! passed to the codec as [serializable graphs][crate::serde::StackGraph], so that a codec can
+//! choose how to encode them.  The other values, such as partial paths, are stored in the compact
+//! encoding of this crate, which is internal to it, so they are encoded with bincode, and the
+//! codec only transforms the bincode encoding.  By default, the codec encodes graphs with bincode
+//! as well, and transforms their encoding in the same way.
+//!
+//! The codec is chosen when a database is created, and its name is recorded in the metadata of the
+//! database, so that the same codec is used whenever it is opened.  The [default
+//! codec][BincodeCodec] stores the bincode encoding as is.  The `ZstdCodec`, which requires the
+//! `zstd` feature, compresses it, which makes databases considerably smaller at the cost of
+//! compressing and decompressing every blob.  The `RkyvCodec`, which requires the `rkyv` feature,
+//! encodes graphs with rkyv instead of bincode, and stores the other values as is.
+
+use std::sync::Arc;
+
+use crate::serde;
+use crate::storage::BINCODE_CONFIG;
+
+/// The error type of codecs.
+pub type CodecError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A codec that encodes the values that are stored as blobs in a database.  Databases that were
+/// created with a codec that is not [built in][builtin_codec] can only be opened by passing an
+/// instance of the codec explicitly.
+pub trait BlobCodec: Send + Sync {
+    /// Returns the name that is recorded in the metadata of databases that use this codec.
+    fn name(&self) -> &str;
+
+    /// Encodes the graph of a file into the blob that is stored.  By default, the graph is
+    /// encoded with bincode, and the encoding is passed to [`encode`][Self::encode].
+    fn encode_graph(&self, graph: &serde::StackGraph) -> Result<Vec<u8>, CodecError> {
+        self.encode(bincode::encode_to_vec(graph, BINCODE_CONFIG)?)
+    }
+
+    /// Decodes a stored blob into the graph of a file.  This must be the inverse of
+    /// [`encode_graph`][Self::encode_graph].
+    fn decode_graph(&self, blob: Vec<u8>) -> Result<serde::StackGraph, CodecError> {
+        let (graph, _) = bincode::decode_from_slice(&self.decode(blob)?, BINCODE_CONFIG)?;
+        Ok(graph)
+    }
+
+    /// Encodes the bincode encoding of any other value into the blob that is stored.
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError>;
+
+    /// Decodes a stored blob into the bincode encoding of the value.  This must be the inverse of
+    /// [`encode`][Self::encode].
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError>;
+}
+
+/// The default codec, which stores the bincode encoding of values as is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+impl BincodeCodec {
+    pub const NAME: &'static str = "bincode";
+}
+
+impl BlobCodec for BincodeCodec {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(data)
+    }
+
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(blob)
+    }
+}
+
+/// A codec that compresses the bincode encoding of values with zstd.
+#[cfg(feature = "zstd")]
+#[derive(Clone, Copy, Debug)]
+pub struct ZstdCodec {
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCodec {
+    pub const NAME: &'static str = "bincode+zstd";
+
+    /// Creates a codec that compresses with the given zstd compression level.  The level does not
+    /// have to match when a database is opened again, since it is not needed for decompression.
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdCodec {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl BlobCodec for ZstdCodec {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(zstd::bulk::compress(&data, self.level)?)
+    }
+
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(zstd::stream::decode_all(blob.as_slice())?)
+    }
+}
+
+/// A codec that encodes graphs with rkyv, and stores the bincode encoding of other values as is.
+/// Stored graphs are validated before they are deserialized.
+#[cfg(feature = "rkyv")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RkyvCodec;
+
+#[cfg(feature = "rkyv")]
+impl RkyvCodec {
+    pub const NAME: &'static str = "rkyv";
+}
+
+#[cfg(feature = "rkyv")]
+impl BlobCodec for RkyvCodec {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn encode_graph(&self, graph: &serde::StackGraph) -> Result<Vec<u8>, CodecError> {
+        let bytes = rkyv::to_bytes::<_, 4096>(graph).map_err(|e| e.to_string())?;
+        Ok(bytes.into_vec())
+    }
+
+    fn decode_graph(&self, blob: Vec<u8>) -> Result<serde::StackGraph, CodecError> {
+        // The archived graph has to be aligned, which the blob is not guaranteed to be.
+        let mut bytes = rkyv::AlignedVec::with_capacity(blob.len());
+        bytes.extend_from_slice(&blob);
+        let archived =
+            rkyv::check_archived_root::<serde::StackGraph>(&bytes).map_err(|e| e.to_string())?;
+        let graph = rkyv::Deserialize::deserialize(archived, &mut rkyv::Infallible)?;
+        Ok(graph)
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(data)
+    }
+
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(blob)
+    }
+}
+
+/// Returns the built-in codec with the given name, or `None` if there is no such codec, or if it
+/// requires a feature that is not enabled.
+pub fn builtin_codec(name: &str) -> Option<Arc<dyn BlobCodec>> {
+    match name {
+        BincodeCodec::NAME => Some(Arc::new(BincodeCodec)),
+        #[cfg(feature = "zstd")]
+        ZstdCodec::NAME => Some(Arc::new(ZstdCodec::default())),
+        #[cfg(feature = "rkyv")]
+        RkyvCodec::NAME => Some(Arc::new(RkyvCodec)),
+        _ => None,
+    }
+}
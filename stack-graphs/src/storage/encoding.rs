@@ -0,0 +1,303 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines the compact encoding of the partial paths that are stored in a database.
+//!
+//! The partial paths of a file have a lot in common: the nodes of their edges are mostly in the
+//! same file, with nearby local IDs, and their symbol stacks mention the same symbols, often with
+//! the same prefixes.  The [serializable partial paths][crate::serde::PartialPath] repeat all of
+//! this for every path, which dominates the size of a database.
+//!
+//! In the compact encoding, the paths of a file share a [`PathDictionary`][], which is stored
+//! once per file.  It contains the file names and symbols that the paths mention, and a trie of
+//! the symbol stacks of the paths, so that stacks with a common prefix share the entries of the
+//! prefix.  Each [`CompactPartialPath`][] refers to the dictionary by index, and encodes the
+//! nodes of its edges as differences from the previous node, which are small numbers that take a
+//! single byte in the variable-length integer encoding of bincode.
+
+use std::collections::HashMap;
+
+use crate::serde;
+
+/// Error decoding a compact partial path, because it does not match the dictionary of its file.
+#[derive(Debug)]
+pub(crate) struct InvalidIndex;
+
+/// The file names, symbols, and symbol stack prefixes that the stored partial paths of a file
+/// refer to.
+#[derive(Clone, Debug, Default, bincode::Encode, bincode::Decode)]
+pub(crate) struct PathDictionary {
+    strings: Vec<String>,
+    /// A trie of symbol stack prefixes.  Prefix `i + 1` consists of prefix `prefixes[i].parent`
+    /// followed by `prefixes[i].symbol`.  Prefix `0` is the empty stack.
+    prefixes: Vec<PrefixEntry>,
+}
+
+#[derive(Clone, Debug, bincode::Encode, bincode::Decode)]
+struct PrefixEntry {
+    parent: u32,
+    symbol: CompactScopedSymbol,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, bincode::Encode, bincode::Decode)]
+struct CompactScopedSymbol {
+    symbol: u32,
+    scopes: Option<CompactScopeStack>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, bincode::Encode, bincode::Decode)]
+struct CompactScopeStack {
+    scopes: Vec<CompactNodeID>,
+    variable: Option<u32>,
+}
+
+/// A node ID.  The file is `0` for nodes that do not belong to a file, and otherwise the index of
+/// the file name in the dictionary plus one.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, bincode::Encode, bincode::Decode)]
+struct CompactNodeID {
+    file: u32,
+    local_id: u32,
+}
+
+#[derive(Clone, Debug, bincode::Encode, bincode::Decode)]
+struct CompactSymbolStack {
+    prefix: u32,
+    variable: Option<u32>,
+}
+
+/// An edge of a partial path.  The node is encoded relative to the node of the previous edge, or
+/// the start node of the path for the first edge.
+#[derive(Clone, Debug, bincode::Encode, bincode::Decode)]
+struct CompactEdge {
+    file: u32,
+    local_id_delta: i64,
+    precedence: i32,
+}
+
+/// A partial path that refers to the [`PathDictionary`][] of its file.
+#[derive(Clone, Debug, bincode::Encode, bincode::Decode)]
+pub(crate) struct CompactPartialPath {
+    start_node: CompactNodeID,
+    end_node: CompactNodeID,
+    symbol_stack_precondition: CompactSymbolStack,
+    symbol_stack_postcondition: CompactSymbolStack,
+    scope_stack_precondition: CompactScopeStack,
+    scope_stack_postcondition: CompactScopeStack,
+    edges: Vec<CompactEdge>,
+}
+
+/// Builds the dictionary of a file while encoding its partial paths.
+pub(crate) struct PathDictionaryBuilder {
+    dictionary: PathDictionary,
+    string_ids: HashMap<String, u32>,
+    prefix_ids: HashMap<(u32, CompactScopedSymbol), u32>,
+}
+
+impl PathDictionaryBuilder {
+    /// Creates a builder that extends the given dictionary.  Paths that were encoded with the
+    /// dictionary remain valid.
+    pub(crate) fn from_dictionary(dictionary: PathDictionary) -> Self {
+        let string_ids = dictionary
+            .strings
+            .iter()
+            .enumerate()
+            .map(|(id, string)| (string.clone(), id as u32))
+            .collect();
+        let prefix_ids = dictionary
+            .prefixes
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| ((entry.parent, entry.symbol.clone()), index as u32 + 1))
+            .collect();
+        Self {
+            dictionary,
+            string_ids,
+            prefix_ids,
+        }
+    }
+
+    /// Returns the dictionary that the encoded paths refer to.
+    pub(crate) fn build(self) -> PathDictionary {
+        self.dictionary
+    }
+
+    pub(crate) fn encode(&mut self, path: &serde::PartialPath) -> CompactPartialPath {
+        let start_node = self.node(&path.start_node);
+        let mut previous = start_node.local_id;
+        let edges = path
+            .edges
+            .edges
+            .iter()
+            .map(|edge| {
+                let source = self.node(&edge.source);
+                let local_id_delta = source.local_id as i64 - previous as i64;
+                previous = source.local_id;
+                CompactEdge {
+                    file: source.file,
+                    local_id_delta,
+                    precedence: edge.precedence,
+                }
+            })
+            .collect();
+        CompactPartialPath {
+            start_node,
+            end_node: self.node(&path.end_node),
+            symbol_stack_precondition: self.symbol_stack(&path.symbol_stack_precondition),
+            symbol_stack_postcondition: self.symbol_stack(&path.symbol_stack_postcondition),
+            scope_stack_precondition: self.scope_stack(&path.scope_stack_precondition),
+            scope_stack_postcondition: self.scope_stack(&path.scope_stack_postcondition),
+            edges,
+        }
+    }
+
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some(id) = self.string_ids.get(value) {
+            return *id;
+        }
+        let id = self.dictionary.strings.len() as u32;
+        self.dictionary.strings.push(value.to_string());
+        self.string_ids.insert(value.to_string(), id);
+        id
+    }
+
+    fn node(&mut self, node: &serde::NodeID) -> CompactNodeID {
+        CompactNodeID {
+            file: node.file.as_ref().map_or(0, |file| self.string(file) + 1),
+            local_id: node.local_id,
+        }
+    }
+
+    fn scope_stack(&mut self, stack: &serde::PartialScopeStack) -> CompactScopeStack {
+        CompactScopeStack {
+            scopes: stack.scopes.iter().map(|scope| self.node(scope)).collect(),
+            variable: stack.variable.as_ref().map(|variable| variable.0),
+        }
+    }
+
+    fn symbol_stack(&mut self, stack: &serde::PartialSymbolStack) -> CompactSymbolStack {
+        let mut prefix = 0;
+        for symbol in &stack.symbols {
+            let symbol = CompactScopedSymbol {
+                symbol: self.string(&symbol.symbol),
+                scopes: symbol
+                    .scopes
+                    .as_ref()
+                    .map(|scopes| self.scope_stack(scopes)),
+            };
+            let key = (prefix, symbol);
+            prefix = match self.prefix_ids.get(&key) {
+                Some(id) => *id,
+                None => {
+                    self.dictionary.prefixes.push(PrefixEntry {
+                        parent: key.0,
+                        symbol: key.1.clone(),
+                    });
+                    let id = self.dictionary.prefixes.len() as u32;
+                    self.prefix_ids.insert(key, id);
+                    id
+                }
+            };
+        }
+        CompactSymbolStack {
+            prefix,
+            variable: stack.variable.as_ref().map(|variable| variable.0),
+        }
+    }
+}
+
+impl PathDictionary {
+    pub(crate) fn decode(
+        &self,
+        path: &CompactPartialPath,
+    ) -> Result<serde::PartialPath, InvalidIndex> {
+        let start_node = self.node(&path.start_node)?;
+        let mut previous = path.start_node.local_id as i64;
+        let mut edges = Vec::with_capacity(path.edges.len());
+        for edge in &path.edges {
+            let local_id = previous + edge.local_id_delta;
+            if local_id < 0 || local_id > u32::MAX as i64 {
+                return Err(InvalidIndex);
+            }
+            previous = local_id;
+            edges.push(serde::PartialPathEdge {
+                source: self.node(&CompactNodeID {
+                    file: edge.file,
+                    local_id: local_id as u32,
+                })?,
+                precedence: edge.precedence,
+            });
+        }
+        Ok(serde::PartialPath {
+            start_node,
+            end_node: self.node(&path.end_node)?,
+            symbol_stack_precondition: self.symbol_stack(&path.symbol_stack_precondition)?,
+            symbol_stack_postcondition: self.symbol_stack(&path.symbol_stack_postcondition)?,
+            scope_stack_precondition: self.scope_stack(&path.scope_stack_precondition)?,
+            scope_stack_postcondition: self.scope_stack(&path.scope_stack_postcondition)?,
+            edges: serde::PartialPathEdgeList { edges },
+        })
+    }
+
+    fn string(&self, id: u32) -> Result<&String, InvalidIndex> {
+        self.strings.get(id as usize).ok_or(InvalidIndex)
+    }
+
+    fn node(&self, node: &CompactNodeID) -> Result<serde::NodeID, InvalidIndex> {
+        let file = match node.file {
+            0 => None,
+            file => Some(self.string(file - 1)?.clone()),
+        };
+        Ok(serde::NodeID {
+            file,
+            local_id: node.local_id,
+        })
+    }
+
+    fn scope_stack(
+        &self,
+        stack: &CompactScopeStack,
+    ) -> Result<serde::PartialScopeStack, InvalidIndex> {
+        Ok(serde::PartialScopeStack {
+            scopes: stack
+                .scopes
+                .iter()
+                .map(|scope| self.node(scope))
+                .collect::<Result<_, _>>()?,
+            variable: stack.variable.map(serde::ScopeStackVariable),
+        })
+    }
+
+    fn symbol_stack(
+        &self,
+        stack: &CompactSymbolStack,
+    ) -> Result<serde::PartialSymbolStack, InvalidIndex> {
+        let mut symbols = Vec::new();
+        let mut prefix = stack.prefix;
+        while prefix != 0 {
+            let entry = self.prefixes.get(prefix as usize - 1).ok_or(InvalidIndex)?;
+            // parents are always added before their children, which rules out cycles
+            if entry.parent >= prefix {
+                return Err(InvalidIndex);
+            }
+            symbols.push(serde::PartialScopedSymbol {
+                symbol: self.string(entry.symbol.symbol)?.clone(),
+                scopes: entry
+                    .symbol
+                    .scopes
+                    .as_ref()
+                    .map(|scopes| self.scope_stack(scopes))
+                    .transpose()?,
+            });
+            prefix = entry.parent;
+        }
+        symbols.reverse();
+        Ok(serde::PartialSymbolStack {
+            symbols,
+            variable: stack.variable.map(serde::SymbolStackVariable),
+        })
+    }
+}
@@ -0,0 +1,280 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Reading databases of [`LEGACY_VERSION`][super::LEGACY_VERSION].
+//!
+//! Legacy databases only have the `graphs`, `file_paths`, and `root_paths` tables.  Graphs and
+//! partial paths are stored in full, as plain bincode, and graphs use the layout of the
+//! serialized graphs of that version, which is kept here.  Legacy databases are read by converting
+//! them into an in-memory database of the current version.
+
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::graph::StackGraph;
+use crate::partial::PartialPaths;
+use crate::serde;
+
+use super::Result;
+use super::SQLiteWriter;
+use super::BINCODE_CONFIG;
+
+/// Copies the files of a legacy database into a new in-memory database of the current version.
+pub(super) fn convert(conn: &Connection) -> Result<SQLiteWriter> {
+    let mut writer = SQLiteWriter::open_in_memory()?;
+    let mut batch = writer.batch_writer()?;
+    let mut graphs = conn.prepare("SELECT file, tag, error, value FROM graphs ORDER BY file")?;
+    let mut rows = graphs.query([])?;
+    while let Some(row) = rows.next()? {
+        let file = row.get::<_, String>(0)?;
+        let tag = row.get::<_, String>(1)?;
+        if let Some(error) = row.get::<_, Option<String>>(2)? {
+            batch.store_error_for_file(Path::new(&file), &tag, &error)?;
+            continue;
+        }
+        let value = row.get::<_, Vec<u8>>(3)?;
+        let (stored, _): (StoredGraph, usize) = bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+        let mut graph = StackGraph::new();
+        serde::StackGraph::from(stored).load_into(&mut graph)?;
+        let handle = graph
+            .get_file(&file)
+            .ok_or_else(|| serde::Error::FileNotFound(file.clone()))?;
+
+        let mut partials = PartialPaths::new();
+        let mut paths = Vec::new();
+        let mut stmt = conn.prepare_cached(
+            "SELECT value FROM file_paths WHERE file = ?1
+             UNION ALL
+             SELECT value FROM root_paths WHERE file = ?1",
+        )?;
+        let mut path_rows = stmt.query([&file])?;
+        while let Some(path_row) = path_rows.next()? {
+            let value = path_row.get::<_, Vec<u8>>(0)?;
+            let (path, _): (serde::PartialPath, usize) =
+                bincode::decode_from_slice(&value, BINCODE_CONFIG)?;
+            paths.push(path.to_partial_path(&mut graph, &mut partials)?);
+        }
+        batch.store_result_for_file(&graph, handle, &tag, &mut partials, &paths)?;
+    }
+    batch.commit()?;
+    Ok(writer)
+}
+
+/// The stored graph of a file.  The layout of this struct and the types it contains must not
+/// change, since they have to match the graphs that were written by the legacy version.
+#[derive(bincode::Decode)]
+struct StoredGraph {
+    files: serde::Files,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(bincode::Decode)]
+enum Node {
+    DropScopes {
+        id: serde::NodeID,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    JumpToScope {
+        id: serde::NodeID,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_definition: bool,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PopSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_definition: bool,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushScopedSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        scope: serde::NodeID,
+        is_reference: bool,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    PushSymbol {
+        id: serde::NodeID,
+        symbol: String,
+        is_reference: bool,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Root {
+        id: serde::NodeID,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+    Scope {
+        id: serde::NodeID,
+        is_exported: bool,
+        source_info: Option<SourceInfo>,
+        debug_info: Option<serde::DebugInfo>,
+    },
+}
+
+#[derive(bincode::Decode)]
+struct SourceInfo {
+    span: lsp_positions::Span,
+    syntax_type: Option<String>,
+}
+
+#[derive(bincode::Decode)]
+struct Edge {
+    source: serde::NodeID,
+    sink: serde::NodeID,
+    precedence: i32,
+    debug_info: Option<serde::DebugInfo>,
+}
+
+impl From<StoredGraph> for serde::StackGraph {
+    fn from(graph: StoredGraph) -> Self {
+        Self {
+            files: graph.files,
+            nodes: serde::Nodes {
+                data: graph.nodes.into_iter().map(Into::into).collect(),
+            },
+            edges: serde::Edges {
+                data: graph.edges.into_iter().map(Into::into).collect(),
+            },
+            ..Self::default()
+        }
+    }
+}
+
+impl From<Node> for serde::Node {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::DropScopes {
+                id,
+                source_info,
+                debug_info,
+            } => Self::DropScopes {
+                id,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::JumpToScope {
+                id,
+                source_info,
+                debug_info,
+            } => Self::JumpToScope {
+                id,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::PopScopedSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopScopedSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::PopSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info,
+                debug_info,
+            } => Self::PopSymbol {
+                id,
+                symbol,
+                is_definition,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::PushScopedSymbol {
+                id,
+                symbol,
+                scope,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushScopedSymbol {
+                id,
+                symbol,
+                scope,
+                is_reference,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::PushSymbol {
+                id,
+                symbol,
+                is_reference,
+                source_info,
+                debug_info,
+            } => Self::PushSymbol {
+                id,
+                symbol,
+                is_reference,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::Root {
+                id,
+                source_info,
+                debug_info,
+            } => Self::Root {
+                id,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+            Node::Scope {
+                id,
+                is_exported,
+                source_info,
+                debug_info,
+            } => Self::Scope {
+                id,
+                is_exported,
+                source_info: source_info.map(Into::into),
+                debug_info,
+            },
+        }
+    }
+}
+
+impl From<SourceInfo> for serde::SourceInfo {
+    fn from(info: SourceInfo) -> Self {
+        Self {
+            span: info.span,
+            syntax_type: info.syntax_type,
+            definiens_span: None,
+        }
+    }
+}
+
+impl From<Edge> for serde::Edge {
+    fn from(edge: Edge) -> Self {
+        Self {
+            source: edge.source,
+            sink: edge.sink,
+            precedence: edge.precedence,
+            condition: None,
+            confidence: None,
+            debug_info: edge.debug_info,
+        }
+    }
+}
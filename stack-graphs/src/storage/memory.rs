@@ -0,0 +1,627 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! In-memory storage for stack graphs and partial paths.
+//!
+//! [`MemoryStorage`][] provides the same operations as [`SQLiteWriter`][super::SQLiteWriter] and
+//! [`SQLiteReader`][super::SQLiteReader], but keeps all data in memory.  This is useful for unit
+//! tests and short-lived analysis sessions, which do not need a database file.
+//!
+//! Stored data is kept in its serialized form, and is loaded lazily, exactly like the SQLite
+//! backend does.  This means that loading graphs and partial path extensions behaves the same,
+//! including the [statistics][super::Stats] that are collected.
+//!
+//! ```rust,ignore
+//! use stack_graphs::storage::memory::MemoryStorage;
+//!
+//! let mut storage = MemoryStorage::new();
+//! storage.store_result_for_file(&graph, file, "tag", &mut partials, &paths)?;
+//!
+//! storage.load_graph_for_file("src/main.rs")?;
+//! storage.load_partial_path_extensions(&path, &NoCancellation)?;
+//! let (graph, partials, db) = storage.get();
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use lsp_positions::Span;
+
+use crate::arena::Handle;
+use crate::graph::Degree;
+use crate::graph::File;
+use crate::graph::FileRoots;
+use crate::graph::Node;
+use crate::graph::StackGraph;
+use crate::partial::PartialPath;
+use crate::partial::PartialPaths;
+use crate::partial::PartialSymbolStack;
+use crate::serde;
+use crate::serde::FileFilter;
+use crate::stitching::Database;
+use crate::stitching::ForwardCandidates;
+use crate::CancellationFlag;
+
+use super::FileEntry;
+use super::FileStatus;
+use super::Result;
+use super::Stats;
+use super::StorageError;
+
+/// The stored data of a single file.
+struct FileData {
+    tag: String,
+    error: Option<String>,
+    graph: serde::StackGraph,
+    node_paths: Vec<(u32, serde::PartialPath)>,
+    root_paths: Vec<(String, serde::PartialPath)>,
+    dependencies: BTreeSet<String>,
+    globals: BTreeMap<String, String>,
+    parse_errors: Vec<Span>,
+}
+
+impl FileData {
+    fn status(&self) -> FileStatus {
+        match &self.error {
+            Some(error) => FileStatus::Error(error.clone()),
+            None => FileStatus::Indexed,
+        }
+    }
+
+    fn entry(&self, file: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(file),
+            tag: self.tag.clone(),
+            status: self.status(),
+            language: self.language(file).map(str::to_string),
+        }
+    }
+
+    fn language(&self, file: &str) -> Option<&str> {
+        self.graph
+            .file_languages
+            .data
+            .iter()
+            .find(|entry| entry.file == file)
+            .map(|entry| entry.language.as_str())
+    }
+}
+
+/// Storage that keeps stack graphs and partial paths in memory.  It combines the operations of
+/// [`SQLiteWriter`][super::SQLiteWriter] and [`SQLiteReader`][super::SQLiteReader].
+pub struct MemoryStorage {
+    files: BTreeMap<String, FileData>,
+    loaded_graphs: HashSet<String>,
+    loaded_node_paths: HashSet<Handle<Node>>,
+    loaded_root_paths: HashSet<String>,
+    graph: StackGraph,
+    partials: PartialPaths,
+    db: Database,
+    stats: Stats,
+    file_roots: FileRoots,
+}
+
+impl MemoryStorage {
+    /// Create a new, empty storage.
+    pub fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            loaded_graphs: HashSet::new(),
+            loaded_node_paths: HashSet::new(),
+            loaded_root_paths: HashSet::new(),
+            graph: StackGraph::new(),
+            partials: PartialPaths::new(),
+            db: Database::new(),
+            stats: Stats::default(),
+            file_roots: FileRoots::new(),
+        }
+    }
+
+    /// Clean all data from the storage.
+    pub fn clean_all(&mut self) -> Result<usize> {
+        let count = self.files.len();
+        self.files.clear();
+        Ok(count)
+    }
+
+    /// Clean file data from the storage.
+    pub fn clean_file(&mut self, file: &Path) -> Result<usize> {
+        let count = match self.files.remove(&*file.to_string_lossy()) {
+            Some(_) => 1,
+            None => 0,
+        };
+        Ok(count)
+    }
+
+    /// Clean file or directory data from the storage.  Data for all decendants of the given path
+    /// is cleaned.
+    pub fn clean_file_or_directory(&mut self, file_or_directory: &Path) -> Result<usize> {
+        let count = self.files.len();
+        self.files
+            .retain(|file, _| !Path::new(file).starts_with(file_or_directory));
+        Ok(count - self.files.len())
+    }
+
+    /// Store an error, indicating that indexing this file failed.
+    pub fn store_error_for_file(&mut self, file: &Path, tag: &str, error: &str) -> Result<()> {
+        copious_debugging!("--> Store error for {}", file.display());
+        self.files.insert(
+            file.to_string_lossy().to_string(),
+            FileData {
+                tag: tag.to_string(),
+                error: Some(error.to_string()),
+                graph: serde::StackGraph::default(),
+                node_paths: Vec::new(),
+                root_paths: Vec::new(),
+                dependencies: BTreeSet::new(),
+                globals: BTreeMap::new(),
+                parse_errors: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Store the result of a successful file index.
+    pub fn store_result_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        tag: &str,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let file_str = graph[file].name();
+        copious_debugging!("--> Store graph for {}", file_str);
+        let mut data = FileData {
+            tag: tag.to_string(),
+            error: None,
+            graph: serde::StackGraph::from_graph_filter(graph, &FileFilter(file)),
+            node_paths: Vec::new(),
+            root_paths: Vec::new(),
+            dependencies: BTreeSet::new(),
+            globals: BTreeMap::new(),
+            parse_errors: graph.parse_errors(file).to_vec(),
+        };
+        for path in paths {
+            copious_debugging!(
+                "--> Add {} partial path {}",
+                file_str,
+                path.display(graph, partials)
+            );
+            let start_node = graph[path.start_node].id();
+            if start_node.is_root() {
+                let symbol_stack = path.symbol_stack_precondition.storage_key(graph, partials);
+                let path = serde::PartialPath::from_partial_path(graph, partials, path);
+                data.root_paths.push((symbol_stack, path));
+            } else if start_node.is_in_file(file) {
+                let path = serde::PartialPath::from_partial_path(graph, partials, path);
+                data.node_paths.push((path.start_node.local_id, path));
+            } else {
+                panic!(
+                    "added path {} must start in given file {} or at root",
+                    path.display(graph, partials),
+                    graph[file].name()
+                );
+            }
+        }
+        self.files.insert(file_str.to_string(), data);
+        Ok(())
+    }
+
+    /// Store the files that the resolutions of the given file depend on.  See
+    /// [`SQLiteWriter::store_dependencies_for_file`][super::SQLiteWriter::store_dependencies_for_file].
+    /// Dependencies can only be stored for files that are present in the storage.
+    pub fn store_dependencies_for_file<'a, IP>(
+        &mut self,
+        graph: &StackGraph,
+        file: Handle<File>,
+        partials: &mut PartialPaths,
+        paths: IP,
+    ) -> Result<()>
+    where
+        IP: IntoIterator<Item = &'a PartialPath>,
+    {
+        let file_str = graph[file].name();
+        let data = match self.files.get_mut(file_str) {
+            Some(data) => data,
+            None => return Err(StorageError::MissingFile(file_str.to_string())),
+        };
+        data.dependencies.clear();
+        for path in paths {
+            for dependency in path.participating_files(graph, partials) {
+                if dependency != file {
+                    data.dependencies
+                        .insert(graph[dependency].name().to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the global variables that were used to build the graph of the given file.  See
+    /// [`SQLiteWriter::store_globals_for_file`][super::SQLiteWriter::store_globals_for_file].
+    /// Globals can only be stored for files that are present in the storage.
+    pub fn store_globals_for_file(
+        &mut self,
+        file: &Path,
+        globals: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let file = file.to_string_lossy();
+        let data = match self.files.get_mut(&*file) {
+            Some(data) => data,
+            None => return Err(StorageError::MissingFile(file.to_string())),
+        };
+        data.globals = globals.clone();
+        Ok(())
+    }
+
+    /// Get the file's status in the storage. If a tag is provided, it must match or the file
+    /// is reported missing.
+    pub fn status_for_file<T: AsRef<str>>(
+        &mut self,
+        file: &str,
+        tag: Option<T>,
+    ) -> Result<FileStatus> {
+        let status = match self.files.get(file) {
+            Some(data) => match tag {
+                Some(tag) if tag.as_ref() != data.tag => FileStatus::Missing,
+                _ => data.status(),
+            },
+            None => FileStatus::Missing,
+        };
+        Ok(status)
+    }
+
+    /// Returns the global variables that the graph of the given file was built with.
+    pub fn globals_for_file(&mut self, file: &Path) -> Result<BTreeMap<String, String>> {
+        let globals = self
+            .files
+            .get(&*file.to_string_lossy())
+            .map(|data| data.globals.clone())
+            .unwrap_or_default();
+        Ok(globals)
+    }
+
+    /// Returns the files whose resolutions depend on the given file, and must be invalidated
+    /// when the given file changes.
+    pub fn dependents_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        let file = file.to_string_lossy();
+        let dependents = self
+            .files
+            .iter()
+            .filter(|(_, data)| data.dependencies.contains(&*file))
+            .map(|(dependent, _)| PathBuf::from(dependent))
+            .collect();
+        Ok(dependents)
+    }
+
+    /// Returns the files that the resolutions of the given file depend on.
+    pub fn dependencies_of_file(&mut self, file: &Path) -> Result<Vec<PathBuf>> {
+        let dependencies = self
+            .files
+            .get(&*file.to_string_lossy())
+            .map(|data| data.dependencies.iter().map(PathBuf::from).collect())
+            .unwrap_or_default();
+        Ok(dependencies)
+    }
+
+    /// Returns the regions of the given file that contained parse errors when it was stored.  See
+    /// [`SQLiteReader::parse_errors_for_file`][super::SQLiteReader::parse_errors_for_file].
+    pub fn parse_errors_for_file(&mut self, file: &str) -> Result<Vec<Span>> {
+        let parse_errors = self
+            .files
+            .get(file)
+            .map(|data| data.parse_errors.clone())
+            .unwrap_or_default();
+        Ok(parse_errors)
+    }
+
+    /// Returns an iterator over all files in the storage.
+    pub fn list_all<'a>(&'a self) -> impl Iterator<Item = FileEntry> + 'a {
+        self.files.iter().map(|(file, data)| data.entry(file))
+    }
+
+    /// Returns an iterator over all descendants of a file or directory in the storage.
+    pub fn list_file_or_directory<'a>(
+        &'a self,
+        file_or_directory: &'a Path,
+    ) -> impl Iterator<Item = FileEntry> + 'a {
+        self.files
+            .iter()
+            .filter(move |(file, _)| Path::new(file).starts_with(file_or_directory))
+            .map(|(file, data)| data.entry(file))
+    }
+
+    /// Returns an iterator over all files in the storage that are tagged with the given
+    /// [language][crate::graph::StackGraph::set_file_language].
+    pub fn list_language<'a>(&'a self, language: &'a str) -> impl Iterator<Item = FileEntry> + 'a {
+        self.files
+            .iter()
+            .filter(move |(file, data)| data.language(file) == Some(language))
+            .map(|(file, data)| data.entry(file))
+    }
+
+    /// Clear all data that has been loaded from this storage.  The stored data is not affected.
+    /// After this call, all existing handles from this storage are invalid.
+    pub fn clear(&mut self) {
+        self.loaded_graphs.clear();
+        self.graph = StackGraph::new();
+
+        self.loaded_node_paths.clear();
+        self.loaded_root_paths.clear();
+        self.partials.clear();
+        self.db.clear();
+
+        self.stats.clear();
+    }
+
+    /// Clear path data that has been loaded from this storage.  After this call, all node
+    /// handles remain valid, but all path data is invalid.
+    pub fn clear_paths(&mut self) {
+        self.loaded_node_paths.clear();
+        self.loaded_root_paths.clear();
+        self.partials.clear();
+        self.db.clear();
+
+        self.stats.clear_paths();
+    }
+
+    /// Set the file roots that are used to translate local paths to file names in the storage.
+    pub fn set_file_roots(&mut self, file_roots: FileRoots) {
+        self.file_roots = file_roots;
+    }
+
+    /// Returns the file roots that are used to translate local paths to file names.
+    pub fn file_roots(&self) -> &FileRoots {
+        &self.file_roots
+    }
+
+    /// Ensure the graph for the file at the given local path is loaded.  The path is translated
+    /// to a file name using the storage's [file roots][Self::set_file_roots].
+    pub fn load_graph_for_path(&mut self, path: &Path) -> Result<Handle<File>> {
+        let file = self.file_roots.file_name(path);
+        self.load_graph_for_file(&file)
+    }
+
+    /// Ensure the graph for the given file is loaded.
+    pub fn load_graph_for_file(&mut self, file: &str) -> Result<Handle<File>> {
+        Self::load_graph_for_file_inner(
+            file,
+            &self.files,
+            &mut self.graph,
+            &mut self.loaded_graphs,
+            &mut self.stats,
+        )
+    }
+
+    fn load_graph_for_file_inner(
+        file: &str,
+        files: &BTreeMap<String, FileData>,
+        graph: &mut StackGraph,
+        loaded_graphs: &mut HashSet<String>,
+        stats: &mut Stats,
+    ) -> Result<Handle<File>> {
+        copious_debugging!("--> Load graph for {}", file);
+        if loaded_graphs.contains(file) {
+            copious_debugging!(" * Already loaded");
+            stats.file_cached += 1;
+            return Ok(graph.get_file(file).expect("loaded file to exist"));
+        }
+        copious_debugging!(" * Load from storage");
+        let data = files
+            .get(file)
+            .ok_or_else(|| StorageError::MissingFile(file.to_string()))?;
+        loaded_graphs.insert(file.to_string());
+        stats.file_loads += 1;
+        data.graph.load_into(graph)?;
+        let handle = graph.get_file(file).expect("loaded file to exist");
+        for span in &data.parse_errors {
+            graph.add_parse_error(handle, span.clone());
+        }
+        Ok(handle)
+    }
+
+    /// Ensure the graphs for all descendants of the given file or directory are loaded.
+    pub fn load_graphs_for_file_or_directory(
+        &mut self,
+        file_or_directory: &Path,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        for file in self.files.keys() {
+            if !Path::new(file).starts_with(file_or_directory) {
+                continue;
+            }
+            cancellation_flag.check("loading graphs")?;
+            Self::load_graph_for_file_inner(
+                file,
+                &self.files,
+                &mut self.graph,
+                &mut self.loaded_graphs,
+                &mut self.stats,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Ensure the paths starting a the given node are loaded.
+    fn load_paths_for_node(
+        &mut self,
+        node: Handle<Node>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(" * Load extensions from node {}", node.display(&self.graph));
+        if !self.loaded_node_paths.insert(node) {
+            copious_debugging!("   > Already loaded");
+            self.stats.node_path_cached += 1;
+            return Ok(());
+        }
+        self.stats.node_path_loads += 1;
+        let id = self.graph[node].id();
+        let file = id.file().expect("file node required");
+        let file = self.graph[file].name().to_string();
+        let data = match self.files.get(&file) {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        for (local_id, path) in &data.node_paths {
+            if *local_id != id.local_id() {
+                continue;
+            }
+            cancellation_flag.check("loading node paths")?;
+            let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+            copious_debugging!(
+                "   > Loaded {}",
+                path.display(&self.graph, &mut self.partials)
+            );
+            self.db
+                .add_partial_path(&self.graph, &mut self.partials, path);
+        }
+        Ok(())
+    }
+
+    /// Ensure the paths starting at the root and matching the given symbol stack are loaded.
+    fn load_paths_for_root(
+        &mut self,
+        symbol_stack: PartialSymbolStack,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(
+            " * Load extensions from root with symbol stack {}",
+            symbol_stack.display(&self.graph, &mut self.partials)
+        );
+        let (symbol_stack_patterns, escape) =
+            symbol_stack.storage_key_patterns(&self.graph, &mut self.partials);
+        let escape = escape.chars().next();
+        for symbol_stack in symbol_stack_patterns {
+            if !self.loaded_root_paths.insert(symbol_stack.clone()) {
+                copious_debugging!("   > Already loaded");
+                self.stats.root_path_cached += 1;
+                continue;
+            }
+            self.stats.root_path_loads += 1;
+            let pattern = symbol_stack.chars().collect::<Vec<_>>();
+            for (file, data) in &self.files {
+                for (key, path) in &data.root_paths {
+                    if !like(&pattern, escape, &key.chars().collect::<Vec<_>>()) {
+                        continue;
+                    }
+                    cancellation_flag.check("loading root paths")?;
+                    Self::load_graph_for_file_inner(
+                        file,
+                        &self.files,
+                        &mut self.graph,
+                        &mut self.loaded_graphs,
+                        &mut self.stats,
+                    )?;
+                    let path = path.to_partial_path(&mut self.graph, &mut self.partials)?;
+                    copious_debugging!(
+                        "   > Loaded {}",
+                        path.display(&self.graph, &mut self.partials)
+                    );
+                    self.db
+                        .add_partial_path(&self.graph, &mut self.partials, path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ensure all possible extensions for the given partial path are loaded.
+    pub fn load_partial_path_extensions(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        copious_debugging!(
+            "--> Load extensions for {}",
+            path.display(&self.graph, &mut self.partials)
+        );
+        let end_node = self.graph[path.end_node].id();
+        if self.graph[path.end_node].file().is_some() {
+            self.load_paths_for_node(path.end_node, cancellation_flag)?;
+        } else if end_node.is_root() {
+            self.load_paths_for_root(path.symbol_stack_postcondition, cancellation_flag)?;
+        }
+        Ok(())
+    }
+
+    /// Get the stack graph, partial paths arena, and path database for the currently loaded data.
+    pub fn get(&mut self) -> (&mut StackGraph, &mut PartialPaths, &mut Database) {
+        (&mut self.graph, &mut self.partials, &mut self.db)
+    }
+
+    /// Return stats about this storage.
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ForwardCandidates<Handle<PartialPath>, PartialPath, Database, StorageError> for MemoryStorage {
+    fn load_forward_candidates(
+        &mut self,
+        path: &PartialPath,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> std::result::Result<(), StorageError> {
+        self.load_partial_path_extensions(path, cancellation_flag)
+    }
+
+    fn get_forward_candidates<R>(&mut self, path: &PartialPath, result: &mut R)
+    where
+        R: std::iter::Extend<Handle<PartialPath>>,
+    {
+        self.db
+            .find_candidate_partial_paths(&self.graph, &mut self.partials, path, result);
+    }
+
+    fn get_joining_candidate_degree(&self, path: &PartialPath) -> Degree {
+        self.db.get_incoming_path_degree(path.end_node)
+    }
+
+    fn get_graph_partials_and_db(&mut self) -> (&StackGraph, &mut PartialPaths, &Database) {
+        (&self.graph, &mut self.partials, &self.db)
+    }
+
+    fn record_resolution(&mut self, candidates: &[Handle<PartialPath>]) {
+        self.db.record_resolution(candidates);
+    }
+}
+
+/// Matches a value against a pattern with the semantics of SQL's `LIKE` operator, as used by the
+/// SQLite backend to look up root paths.  Like SQLite, ASCII characters are matched
+/// case-insensitively.
+fn like(pattern: &[char], escape: Option<char>, value: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some((c, rest)) if Some(*c) == escape => match (rest.split_first(), value.split_first()) {
+            (Some((p, rest)), Some((v, value))) => {
+                p.eq_ignore_ascii_case(v) && like(rest, escape, value)
+            }
+            _ => false,
+        },
+        Some((&'%', rest)) => (0..=value.len()).any(|i| like(rest, escape, &value[i..])),
+        Some((&'_', rest)) => match value.split_first() {
+            Some((_, value)) => like(rest, escape, value),
+            None => false,
+        },
+        Some((p, rest)) => match value.split_first() {
+            Some((v, value)) => p.eq_ignore_ascii_case(v) && like(rest, escape, value),
+            None => false,
+        },
+    }
+}
@@ -84,6 +84,74 @@
 //! )?;
 //! ```
 //!
+//! ### Embed the Visualization in a Host Page
+//!
+//! Editors that show the visualization in a webview, such as a VS Code extension, usually cannot
+//! use a single HTML string, because the webview's content security policy requires scripts and
+//! styles to be loaded from files or nonced elements.  The [`Assets`][] API returns the pieces
+//! separately, so that the host can serve them however it needs to:
+//!
+//! ```rust,ignore
+//! use stack_graphs::visualization::Assets;
+//!
+//! // Static assets, which can be written to the extension's media directory once
+//! std::fs::write("media/visualization.css", Assets::css())?;
+//! for (name, script) in Assets::scripts() {
+//!     std::fs::write(format!("media/{}", name), script)?;
+//! }
+//!
+//! // The data of a graph, and the script that renders it into an element of the page
+//! let data = graph.to_visualization_data(&mut partials, &mut db, &NoFilter)?;
+//! let script = Assets::render_script(
+//!     &data,
+//!     "#container",
+//!     Some("(node) => vscode.postMessage({ command: 'nodeClicked', node })"),
+//! )?;
+//! ```
+//!
+//! The click handler is a JavaScript expression that evaluates to a function.  It is called with
+//! the ID, type, symbol, and source info of every node that is clicked, as a plain object that
+//! can be posted as a message to the editor.
+//!
+//! ### Jump to Source
+//!
+//! A visualization can ask the page or application that hosts it to jump to the source of a node
+//! when the node is clicked, using one of the [`SourceNavigation`][] contracts.  Nodes without a
+//! file or source location are ignored.
+//!
+//! With [`SourceNavigation::PostMessage`][], a message is posted to the parent window, which is
+//! the window of the visualization itself if it is not embedded.  Lines and columns are
+//! 0-indexed, and columns count UTF-16 code units, as in LSP:
+//!
+//! ```json
+//! {
+//!   "type": "stack-graphs.navigate",
+//!   "file": "src/main.py",
+//!   "span": {
+//!     "start": { "line": 2, "column": 4 },
+//!     "end": { "line": 2, "column": 7 }
+//!   }
+//! }
+//! ```
+//!
+//! With [`SourceNavigation::Url`][], a URL is built from a template and opened.  The placeholders
+//! `{file}`, `{line}`, `{column}`, `{end_line}`, and `{end_column}` are replaced by the file name
+//! and the 1-indexed lines and columns of the span of the node.  For example, if the files in the
+//! graph have absolute names, the template `vscode://file{file}:{line}:{column}` opens the node
+//! in VS Code:
+//!
+//! ```rust,ignore
+//! use stack_graphs::visualization::SourceNavigation;
+//!
+//! let html = graph.to_html_string_with_navigation(
+//!     "My Stack Graph",
+//!     &mut partials,
+//!     &mut db,
+//!     &NoFilter,
+//!     &SourceNavigation::Url("vscode://file{file}:{line}:{column}".to_string()),
+//! )?;
+//! ```
+//!
 //! ## Visualization Output
 //!
 //! The generated HTML is a single self-contained file that includes:
@@ -115,6 +183,20 @@
 //! // Open in browser to inspect
 //! ```
 //!
+//! ### Finding Nodes
+//!
+//! The generated page contains an index of the symbols and file names of the nodes in the graph.
+//! Typing in its search box lists the matching symbols and files, and choosing one highlights
+//! the matching nodes and centers the view on the first of them.
+//!
+//! ### Decluttering the View
+//!
+//! The filters panel of the generated page hides nodes by kind or file, edges to the root node,
+//! and path highlighting, without regenerating the page with a different [`Filter`][].  Rendered
+//! nodes carry their kind and file in `data-kind` and `data-file` attributes, and rendered edges
+//! carry those of their endpoints in `data-source-*` and `data-sink-*` attributes, so that a host
+//! page can style or select them as well.
+//!
 //! ### Inspecting Path Finding
 //!
 //! After computing partial paths, visualize them to understand how name
@@ -198,6 +280,9 @@
 //! - [`partial`][crate::partial]: Partial paths shown in visualization
 //! - [`serde`][crate::serde]: Serialization of graph data
 
+use std::collections::BTreeMap;
+
+use serde_json::json;
 use serde_json::Error;
 
 use crate::arena::Handle;
@@ -207,6 +292,7 @@ use crate::graph::StackGraph;
 use crate::partial::PartialPath;
 use crate::partial::PartialPaths;
 use crate::serde::Filter;
+use crate::serde::ImplicationFilter;
 use crate::stitching::Database;
 
 static CSS: &'static str = include_str!("visualization/visualization.css");
@@ -217,20 +303,206 @@ static JS: &'static str = include_str!("visualization/visualization.js");
 static PKG: &'static str = env!("CARGO_PKG_NAME");
 static VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+//-----------------------------------------------------------------------------
+// Assets
+
+/// The assets of the visualization, for hosting it in a page of one's own, such as an editor
+/// webview.  See the [module documentation][self] for an example.
+pub struct Assets;
+
+impl Assets {
+    /// Returns the stylesheet of the visualization.
+    pub fn css() -> &'static str {
+        CSS
+    }
+
+    /// Returns the scripts of the visualization, with suggested file names, in the order in which
+    /// they must be loaded.
+    pub fn scripts() -> [(&'static str, &'static str); 3] {
+        [
+            ("d3.min.js", D3),
+            ("d3-dag.min.js", D3_DAG),
+            ("visualization.js", JS),
+        ]
+    }
+
+    /// Returns a script that renders the given data into the element that matches the given CSS
+    /// selector.  It must be run after the [scripts][Assets::scripts] have been loaded.  If a
+    /// click handler is given, it must be a JavaScript expression that evaluates to a function,
+    /// which is called with every node that is clicked.
+    pub fn render_script(
+        data: &VisualizationData,
+        container: &str,
+        on_node_click: Option<&str>,
+    ) -> Result<String, Error> {
+        Self::render_script_with_navigation(data, container, on_node_click, &SourceNavigation::None)
+    }
+
+    /// Returns a script like [`render_script`][Assets::render_script] does, for a visualization
+    /// that asks its host to jump to the source of clicked nodes as specified.
+    pub fn render_script_with_navigation(
+        data: &VisualizationData,
+        container: &str,
+        on_node_click: Option<&str>,
+        navigation: &SourceNavigation,
+    ) -> Result<String, Error> {
+        let container = serde_json::to_string(container)?;
+        let version = serde_json::to_string(&format!("{PKG} {VERSION}"))?;
+        let on_node_click = on_node_click.unwrap_or("null");
+        let source_navigation = serde_json::to_string(&navigation.to_metadata())?;
+        Ok(format!(
+            r#"
+(() => {{
+  const graph = {graph};
+  const paths = {paths};
+  const index = {index};
+  const container = d3.select({container});
+  new StackGraph(container, graph, paths, {{
+    version: {version},
+    on_node_click: {on_node_click},
+    source_navigation: {source_navigation},
+    search_index: index,
+  }});
+}})();
+"#,
+            graph = data.graph,
+            paths = data.paths,
+            index = data.index,
+        ))
+    }
+}
+
+/// How a visualization reports clicks on nodes to the page or application that hosts it, so that
+/// it can jump to the source of the node.  See the [module documentation][self] for the details of
+/// each contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceNavigation {
+    /// Clicks are not reported.
+    None,
+    /// A navigation message is posted to the parent window.
+    PostMessage,
+    /// A URL built from the given template is opened.
+    Url(String),
+}
+
+impl SourceNavigation {
+    fn to_metadata(&self) -> serde_json::Value {
+        match self {
+            Self::None => serde_json::Value::Null,
+            Self::PostMessage => json!({ "kind": "post_message" }),
+            Self::Url(template) => json!({ "kind": "url", "template": template }),
+        }
+    }
+}
+
+impl Default for SourceNavigation {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// The data of a visualization, as JSON.
+#[derive(Clone, Debug)]
+pub struct VisualizationData {
+    /// The nodes and edges of the graph.
+    pub graph: String,
+    /// The partial paths of the graph.
+    pub paths: String,
+    /// The search index of the graph, which maps symbols and file names to the nodes that have
+    /// them.
+    pub index: String,
+}
+
+/// An entry of the search index of a visualization.
+#[derive(serde::Serialize)]
+struct SearchEntry {
+    text: String,
+    kind: &'static str,
+    nodes: Vec<crate::serde::NodeID>,
+}
+
+fn search_index(graph: &StackGraph, filter: &dyn Filter) -> Vec<SearchEntry> {
+    let filter = ImplicationFilter(filter);
+    let mut symbols = BTreeMap::<String, Vec<_>>::new();
+    let mut files = BTreeMap::<String, Vec<_>>::new();
+    for node in graph.iter_nodes() {
+        if !filter.include_node(graph, &node) {
+            continue;
+        }
+        let id = crate::serde::NodeID::from_node_id(graph, graph[node].id());
+        if let Some(symbol) = graph[node].symbol() {
+            symbols
+                .entry(graph[symbol].to_string())
+                .or_default()
+                .push(id.clone());
+        }
+        if let Some(file) = graph[node].file() {
+            files
+                .entry(graph[file].name().to_string())
+                .or_default()
+                .push(id);
+        }
+    }
+    let symbols = symbols.into_iter().map(|(text, nodes)| SearchEntry {
+        text,
+        kind: "symbol",
+        nodes,
+    });
+    let files = files.into_iter().map(|(text, nodes)| SearchEntry {
+        text,
+        kind: "file",
+        nodes,
+    });
+    symbols.chain(files).collect()
+}
+
 //-----------------------------------------------------------------------------
 // StackGraph
 
 impl StackGraph {
-    pub fn to_html_string(
+    /// Returns the data of a visualization of this graph and the paths in the given database, for
+    /// use with [`Assets::render_script`][].
+    pub fn to_visualization_data(
         &self,
-        title: &str,
         partials: &mut PartialPaths,
         db: &mut Database,
         filter: &dyn Filter,
-    ) -> Result<String, Error> {
+    ) -> Result<VisualizationData, Error> {
         let filter = VisualizationFilter(filter);
         let graph = serde_json::to_string(&self.to_serializable_filter(&filter))?;
         let paths = serde_json::to_string(&db.to_serializable_filter(self, partials, &filter))?;
+        let index = serde_json::to_string(&search_index(self, &filter))?;
+        Ok(VisualizationData {
+            graph,
+            paths,
+            index,
+        })
+    }
+
+    /// Returns a self-contained HTML page that visualizes this graph and the paths in the given
+    /// database.
+    pub fn to_html_string(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+    ) -> Result<String, Error> {
+        self.to_html_string_with_navigation(title, partials, db, filter, &SourceNavigation::None)
+    }
+
+    /// Returns a self-contained HTML page like [`to_html_string`][StackGraph::to_html_string]
+    /// does, which asks its host to jump to the source of clicked nodes as specified.
+    pub fn to_html_string_with_navigation(
+        &self,
+        title: &str,
+        partials: &mut PartialPaths,
+        db: &mut Database,
+        filter: &dyn Filter,
+        navigation: &SourceNavigation,
+    ) -> Result<String, Error> {
+        let data = self.to_visualization_data(partials, db, filter)?;
+        let render = Assets::render_script_with_navigation(&data, "#container", None, navigation)?;
         let html = format!(
             r#"
 <!DOCTYPE html>
@@ -261,11 +533,6 @@ impl StackGraph {
 {JS}
 </script>
 
-<script type="text/javascript">
-  let graph = {graph};
-  let paths = {paths};
-</script>
-
 <style>
   html, body, #container {{
     width: 100%;
@@ -281,8 +548,7 @@ impl StackGraph {
   <div id="container">
   </div>
   <script type="text/javascript">
-    const container = d3.select("\#container");
-    new StackGraph(container, graph, paths, {{ version: "{PKG} {VERSION}" }});
+{render}
   </script>
 </body>
 
@@ -19,7 +19,10 @@ class StackGraph {
 
     static number_of_file_colors = 8;
 
+    static max_search_results = 20;
+
     constructor(container, graph, paths, metadata) {
+        this.container = container;
         this.metadata = metadata;
 
         this.graph = graph;
@@ -31,6 +34,12 @@ class StackGraph {
         this.current_edge = null;
         this.current_orient = { y: "south", x: "east" };
         this.paths_lock = null;
+        this.node_positions = {};
+        this.search_matches = [];
+        this.hidden_kinds = new Set();
+        this.hidden_files = new Set();
+        this.show_edges_to_root = true;
+        this.show_paths = true;
         this.render();
     }
 
@@ -57,15 +66,15 @@ class StackGraph {
     }
 
     compute_file_data() {
-        for (let i in graph.files) {
-            const file = graph.files[i];
+        for (let i in this.graph.files) {
+            const file = this.graph.files[i];
             this.F[file] = i;
         }
     }
 
     compute_node_data() {
-        for (let i in graph.nodes) {
-            const node = graph.nodes[i];
+        for (let i in this.graph.nodes) {
+            const node = this.graph.nodes[i];
             node.paths = []
             this.ID[this.node_to_id_str(node)] = i;
             this.N.push(node);
@@ -166,7 +175,7 @@ class StackGraph {
 
     render() {
         // define svg
-        const svg = container.append('svg')
+        const svg = this.container.append('svg')
             .attr('width', '100%')
             .attr('height', '100%');
         const background = svg.append("rect")
@@ -177,6 +186,8 @@ class StackGraph {
         this.render_help();
         this.render_tooltip();
         this.render_legend();
+        this.render_search();
+        this.render_filters();
         this.render_graph();
 
         // pan & zoom
@@ -189,6 +200,8 @@ class StackGraph {
                 background.classed("engaged", false);
             });
         background.call(zoom);
+        this.zoom = zoom;
+        this.zoom_target = background;
 
         // global key events
         d3.select(window).on("keyup", (e) => {
@@ -237,6 +250,9 @@ class StackGraph {
                 return d === undefined ? [0, 0] : [d.width + 2 * StackGraph.distx, d.height + 2 * StackGraph.disty];
             });
         const { width, height } = layout(dag);
+        for (const d of dag.descendants()) {
+            this.node_positions[this.node_to_id_str(this.N[d.data.id])] = { x: d.x, y: d.y };
+        }
 
         // set viewport
         this.sg.attr("viewBox", [0, 0, width, height].join(" "));
@@ -252,7 +268,11 @@ class StackGraph {
             .enter()
             .append("g")
             .attr("class", (d) => `${d.data.is_jump ? "jump" : "edge"} ${this.edge_to_file_class(d.data)}`)
-            .attr("id", (d) => this.edge_to_id_str(d.data));
+            .attr("id", (d) => this.edge_to_id_str(d.data))
+            .attr("data-source-kind", (d) => this.node_for_id(d.data.source)?.type)
+            .attr("data-source-file", (d) => this.node_id_to_filter_file(d.data.source))
+            .attr("data-sink-kind", (d) => this.node_for_id(d.data.sink)?.type)
+            .attr("data-sink-file", (d) => this.node_id_to_filter_file(d.data.sink));
         edges.append("path")
             .attr("id", (d) => this.edge_to_id_str(d.data) + ":path")
             .attr("d", (d) => line(d.reversed ? d3.reverse(d.points) : d.points))
@@ -289,6 +309,8 @@ class StackGraph {
             .on("click", (e, d) => {
                 const node = this.N[d.data.id];
                 this.paths_click(e, node);
+                this.node_click_callback(node);
+                this.source_navigation_click(node);
             });
 
         // edge mouse events
@@ -308,11 +330,16 @@ class StackGraph {
                 this.tooltip_mouseout(e);
             });
 
+        // restore search highlights and filters
+        this.search_highlight();
+        this.filters_apply();
     }
 
     render_node(node, g) {
         g.attr('id', this.node_to_id_str(node));
         g.attr('class', `node ${node.type} ${this.node_to_file_class(node)}`);
+        g.attr('data-kind', node.type);
+        g.attr('data-file', this.node_id_to_filter_file(node.id));
 
         switch (node.type) {
             case "drop_scopes":
@@ -485,25 +512,100 @@ class StackGraph {
         }
     }
 
+    // ------------------------------------------------------------------------------------------------
+    // Node Click Callback
+    //
+
+    // Reports a clicked node to the host page, if it provided an `on_node_click` function in the
+    // metadata.  The node is passed as a plain object without any of the derived data, so that it
+    // can be posted as a message, such as from a webview to the editor that hosts it.
+    node_click_callback(node) {
+        const callback = this.metadata?.on_node_click;
+        if (typeof callback !== "function") {
+            return;
+        }
+        const data = {
+            id: node.id,
+            type: node.type,
+        };
+        if (node.hasOwnProperty("symbol")) {
+            data.symbol = node.symbol;
+        }
+        if (this.node_has_source_info(node)) {
+            data.source_info = node.source_info;
+        }
+        callback(data);
+    }
+
+    // ------------------------------------------------------------------------------------------------
+    // Source Navigation
+    //
+
+    // Asks the host to jump to the source of a clicked node, if source navigation is enabled in
+    // the metadata.  Nodes without a file or source location are ignored.
+    source_navigation_click(node) {
+        const navigation = this.metadata?.source_navigation;
+        if (!navigation || node.id.file === undefined || !this.node_has_source_info(node)) {
+            return;
+        }
+        const file = node.id.file;
+        const span = node.source_info.span;
+        switch (navigation.kind) {
+            case "post_message": {
+                // Lines and columns are 0-indexed, and columns count UTF-16 code units, as in LSP.
+                const message = {
+                    type: "stack-graphs.navigate",
+                    file,
+                    span: {
+                        start: { line: span.start.line, column: span.start.column.utf16_offset },
+                        end: { line: span.end.line, column: span.end.column.utf16_offset },
+                    },
+                };
+                window.parent.postMessage(message, "*");
+                break;
+            }
+            case "url": {
+                // Lines and columns are 1-indexed, as editors expect in URLs.
+                const values = {
+                    file: encodeURI(file),
+                    line: span.start.line + 1,
+                    column: span.start.column.utf16_offset + 1,
+                    end_line: span.end.line + 1,
+                    end_column: span.end.column.utf16_offset + 1,
+                };
+                const url = navigation.template.replace(/\{(\w+)\}/g,
+                    (placeholder, name) => values.hasOwnProperty(name) ? values[name] : placeholder);
+                window.open(url, "_blank");
+                break;
+            }
+            default:
+                console.log("Unknown source navigation kind ", navigation.kind);
+                break;
+        }
+    }
+
     // ------------------------------------------------------------------------------------------------
     // Path Highlighting
     //
 
     paths_mouseover(e, node) {
-        if (this.paths_lock !== null) {
+        if (this.paths_lock !== null || !this.show_paths) {
             return;
         }
         this.paths_highlight(node);
     }
 
     paths_mouseout(e, node) {
-        if (this.paths_lock !== null) {
+        if (this.paths_lock !== null || !this.show_paths) {
             return;
         }
         this.paths_nolight(node);
     }
 
     paths_click(e, node) {
+        if (!this.show_paths) {
+            return;
+        }
         if (this.paths_lock === null) {
             if (node.paths.length > 0) {
                 this.paths_nolight(node);
@@ -729,6 +831,10 @@ class StackGraph {
         if (edge.hasOwnProperty("precedence")) {
             tooltip.add_row("precedence", edge.precedence);
         }
+        const edge_rule = this.debug_info_value(edge, "tsg_rule");
+        if (edge_rule !== null) {
+            tooltip.add_row("rule", edge_rule);
+        }
 
         if (edge.hasOwnProperty("debug_info") && edge.debug_info.length > 0) {
             tooltip.add_header("debug info");
@@ -757,6 +863,10 @@ class StackGraph {
         if (this.node_has_source_info(node)) {
             tooltip.add_row("location", this.source_info_to_str(node.source_info));
         }
+        const node_rule = this.debug_info_value(node, "tsg_rule");
+        if (node_rule !== null) {
+            tooltip.add_row("rule", node_rule);
+        }
         if (node.paths.length > 0) {
             tooltip.add_row("outgoing paths", `${node.paths.length}`);
         }
@@ -769,6 +879,14 @@ class StackGraph {
         }
     }
 
+    debug_info_value(element, key) {
+        if (!element.hasOwnProperty("debug_info")) {
+            return null;
+        }
+        const entry = element.debug_info.find((entry) => entry.key == key);
+        return entry === undefined ? null : entry.value;
+    }
+
     tooltip_path_update(tooltip, paths_lock) {
         if (!this.tooltip_on_current_path(paths_lock)) {
             return;
@@ -819,6 +937,176 @@ class StackGraph {
         legend.style('visibility', this.show_file_legend() ? null : 'hidden');
     }
 
+    // ------------------------------------------------------------------------------------------------
+    // Search
+    //
+
+    render_search() {
+        const search = d3.select('body').append('div')
+            .attr('id', 'sg-search');
+        this.search_input = search.append('input')
+            .attr('type', 'search')
+            .attr('placeholder', "Search symbols and files");
+        this.search_results = search.append('ul');
+        this.search_input
+            .on("input", (e) => {
+                this.search_update();
+            })
+            .on("keyup", (e) => {
+                // keep typing from triggering the global key bindings
+                e.stopPropagation();
+                switch (e.keyCode) {
+                    case 13: { // enter
+                        const entries = this.search_entries();
+                        if (entries.length > 0) {
+                            this.search_select(entries[0]);
+                        }
+                        break;
+                    }
+                    case 27: // esc
+                        this.search_input.property("value", "");
+                        this.search_update();
+                        this.search_select(null);
+                        break;
+                }
+            });
+    }
+
+    search_entries() {
+        const query = this.search_input.property("value").trim().toLowerCase();
+        if (query.length === 0) {
+            return [];
+        }
+        return (this.metadata?.search_index ?? [])
+            .filter((entry) => entry.text.toLowerCase().includes(query))
+            .slice(0, StackGraph.max_search_results);
+    }
+
+    search_update() {
+        this.search_results.selectAll("*").remove();
+        for (const entry of this.search_entries()) {
+            this.search_results.append("li")
+                .attr("class", `sg-search-${entry.kind}`)
+                .text(`${entry.text} (${entry.nodes.length})`)
+                .on("click", (e) => {
+                    this.search_select(entry);
+                });
+        }
+    }
+
+    search_select(entry) {
+        this.search_nolight();
+        this.search_matches = entry === null ? [] : entry.nodes.map((id) => this.node_id_to_str(id));
+        this.search_highlight();
+        const position = this.search_matches
+            .map((id) => this.node_positions[id])
+            .find((position) => position !== undefined);
+        if (position !== undefined) {
+            this.zoom_target.transition()
+                .duration(500)
+                .call(this.zoom.translateTo, position.x, position.y);
+        }
+    }
+
+    search_highlight() {
+        for (const id of this.search_matches) {
+            d3.select(this.id_selector(id)).classed("search-match", true);
+        }
+    }
+
+    search_nolight() {
+        for (const id of this.search_matches) {
+            d3.select(this.id_selector(id)).classed("search-match", false);
+        }
+    }
+
+    // ------------------------------------------------------------------------------------------------
+    // Filters
+    //
+
+    render_filters() {
+        const filters = d3.select('body').append('details')
+            .attr('id', 'sg-filters');
+        filters.append('summary').text("Filters");
+
+        filters.append("h1").text("Node kinds");
+        const kinds = [...new Set(this.graph.nodes.map((node) => node.type))].sort();
+        for (const kind of kinds) {
+            this.new_filter(filters, kind, true, (checked) => {
+                this.filter_set(this.hidden_kinds, kind, !checked);
+            });
+        }
+
+        filters.append("h1").text("Files");
+        this.new_filter(filters, "[global]", true, (checked) => {
+            this.filter_set(this.hidden_files, "", !checked);
+        });
+        for (const file in this.F) {
+            this.new_filter(filters, file, true, (checked) => {
+                this.filter_set(this.hidden_files, file, !checked);
+            });
+        }
+
+        filters.append("h1").text("Edges & Paths");
+        this.new_filter(filters, "Edges to root", true, (checked) => {
+            this.show_edges_to_root = checked;
+            this.filters_apply();
+        });
+        this.new_filter(filters, "Paths", true, (checked) => {
+            this.paths_toggle(checked);
+        });
+    }
+
+    new_filter(element, text, initial, on_change) {
+        const label = element.append("label")
+            .attr("class", "sg-filter");
+        label.append("input")
+            .attr("type", "checkbox")
+            .property("checked", initial)
+            .on("change", (e) => {
+                on_change(e.target.checked);
+            });
+        label.append("span").text(text);
+    }
+
+    filter_set(hidden, value, is_hidden) {
+        if (is_hidden) {
+            hidden.add(value);
+        } else {
+            hidden.delete(value);
+        }
+        this.filters_apply();
+    }
+
+    filters_apply() {
+        const is_hidden = (kind, file) => this.hidden_kinds.has(kind) || this.hidden_files.has(file);
+        const show_edges_to_root = this.show_edges_to_root;
+        this.sg.selectAll("g.node")
+            .classed("filtered-out", function () {
+                return is_hidden(this.getAttribute("data-kind"), this.getAttribute("data-file"));
+            });
+        this.sg.selectAll("g.edge, g.jump")
+            .classed("filtered-out", function () {
+                const sink_kind = this.getAttribute("data-sink-kind");
+                return is_hidden(this.getAttribute("data-source-kind"), this.getAttribute("data-source-file"))
+                    || is_hidden(sink_kind, this.getAttribute("data-sink-file"))
+                    || (sink_kind === "root" && !show_edges_to_root);
+            });
+    }
+
+    paths_toggle(show) {
+        if (!show) {
+            if (this.paths_lock !== null) {
+                this.paths_nolight(this.paths_lock.node);
+                this.paths_lock = null;
+                this.tooltip_update();
+            } else if (this.current_node !== null) {
+                this.paths_nolight(this.current_node);
+            }
+        }
+        this.show_paths = show;
+    }
+
     // ------------------------------------------------------------------------------------------------
     // Help
     //
@@ -859,6 +1147,13 @@ class StackGraph {
             this.tooltip_update();
         }));
 
+        help_content.append("h1").text("Search");
+        help_content.append("p").html(`
+            Search for symbols and file names using the search box.
+            Choose a result, or press <kbd>enter</kbd> for the first one, to highlight the matching nodes and center the view on them.
+            Clear the search by pressing the <kbd>esc</kbd> key.
+        `);
+
         help_content.append("h1").text("Paths");
         help_content.append("p").html(`
             Cycle through individual paths by clicking on a node with outgoing paths.
@@ -966,6 +1261,16 @@ class StackGraph {
         }
     }
 
+    node_for_id(id) {
+        return this.N[this.ID[this.node_id_to_str(id)]];
+    }
+
+    // Returns the file of a node ID as it is used by the filters, which is the empty string for
+    // global nodes.
+    node_id_to_filter_file(id) {
+        return id.hasOwnProperty('file') ? id.file : "";
+    }
+
     id_selector(id) {
         const sel = "#" + id.replaceAll(/[^a-zA-Z0-9]/g, '\\$&');
         return sel;
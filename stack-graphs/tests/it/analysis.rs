@@ -0,0 +1,766 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use itertools::Itertools;
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use stack_graphs::analysis::binding_drift;
+use stack_graphs::analysis::callees_of;
+use stack_graphs::analysis::callers_of;
+use stack_graphs::analysis::find_dead_definitions;
+use stack_graphs::analysis::find_unresolved_references;
+use stack_graphs::analysis::group_references_by_definition;
+use stack_graphs::analysis::import_graph;
+use stack_graphs::analysis::rename;
+use stack_graphs::analysis::resolve_all_references;
+use stack_graphs::analysis::symbol_contexts;
+use stack_graphs::analysis::CallHierarchyConfig;
+use stack_graphs::analysis::DeadDefinitionsConfig;
+use stack_graphs::analysis::DriftKind;
+use stack_graphs::analysis::ResolveAllOptions;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::Precedence;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::StoredResolution;
+use stack_graphs::NoCancellation;
+use std::cell::Cell;
+use std::path::PathBuf;
+
+use crate::util::create_edge;
+use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
+use crate::util::edge;
+
+/// Builds the graph of a file in a test database, and returns the partial paths to store for it.
+type FileBuilder<'a> =
+    Box<dyn Fn(&mut StackGraph, &mut PartialPaths, Handle<File>) -> Vec<PartialPath> + 'a>;
+
+/// Pairs the name of a file with the closure that builds it.
+fn build_file<'a, F>(name: &'a str, build: F) -> (&'a str, FileBuilder<'a>)
+where
+    F: Fn(&mut StackGraph, &mut PartialPaths, Handle<File>) -> Vec<PartialPath> + 'a,
+{
+    (name, Box::new(build))
+}
+
+/// Creates a database with the given files, which are built and stored in order.
+fn create_database(files: Vec<(&str, FileBuilder)>) -> SQLiteReader {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    for (name, build) in files {
+        let file = graph.add_file(name).unwrap();
+        let paths = build(&mut graph, &mut partials, file);
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, &paths)
+            .unwrap();
+    }
+    writer.into_reader()
+}
+
+/// Returns a file builder that adds definitions of and references to the given symbols, which are
+/// all connected to the root node.
+fn symbols<'a>(
+    definitions: &'a [&'a str],
+    references: &'a [&'a str],
+) -> impl Fn(&mut StackGraph, &mut PartialPaths, Handle<File>) -> Vec<PartialPath> + 'a {
+    move |graph: &mut StackGraph, partials: &mut PartialPaths, file: Handle<File>| {
+        let r = StackGraph::root_node();
+        let mut paths = Vec::new();
+        for definition in definitions {
+            let node = create_pop_symbol_node(graph, file, definition, true);
+            paths.push(create_partial_path_and_edges(graph, partials, &[r, node]).unwrap());
+        }
+        for reference in references {
+            let node = create_push_symbol_node(graph, file, reference, true);
+            paths.push(create_partial_path_and_edges(graph, partials, &[node, r]).unwrap());
+        }
+        paths
+    }
+}
+
+fn create_dead_definitions_database() -> SQLiteReader {
+    // exported and used: foo, exported and unused: baz, local and unused: bar
+    create_database(vec![
+        build_file("lib", |graph, partials, file| {
+            let paths = symbols(&["foo", "baz"], &[])(graph, partials, file);
+            create_pop_symbol_node(graph, file, "bar", true);
+            paths
+        }),
+        build_file("main", symbols(&[], &["foo"])),
+    ])
+}
+
+#[test]
+fn can_find_dead_definitions() {
+    let mut reader = create_dead_definitions_database();
+    let results = find_dead_definitions(
+        &mut reader,
+        &DeadDefinitionsConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    let results = results
+        .iter()
+        .map(|d| {
+            (
+                d.file.to_string_lossy().to_string(),
+                d.symbol.clone(),
+                d.exported,
+            )
+        })
+        .collect_vec();
+    assert_eq!(vec![("lib".to_string(), "bar".to_string(), false)], results);
+}
+
+#[test]
+fn can_find_exported_dead_definitions() {
+    let mut reader = create_dead_definitions_database();
+    let results = find_dead_definitions(
+        &mut reader,
+        &DeadDefinitionsConfig::default().with_include_exported(true),
+        &NoCancellation,
+    )
+    .unwrap();
+    let results = results
+        .iter()
+        .map(|d| (d.symbol.clone(), d.exported))
+        .sorted()
+        .collect_vec();
+    assert_eq!(
+        vec![("bar".to_string(), false), ("baz".to_string(), true)],
+        results
+    );
+}
+
+#[test]
+fn can_find_unresolved_references() {
+    let mut reader = create_database(vec![
+        build_file("lib", symbols(&["foo"], &[])),
+        build_file("main", symbols(&[], &["foo", "qux"])),
+    ]);
+    let results =
+        find_unresolved_references(&mut reader, StitcherConfig::default(), &NoCancellation)
+            .unwrap();
+    let results = results
+        .iter()
+        .map(|u| (u.file.to_string_lossy().to_string(), u.symbol.clone()))
+        .collect_vec();
+    assert_eq!(vec![("main".to_string(), "qux".to_string())], results);
+}
+
+fn position(line: usize, column: usize) -> Position {
+    Position {
+        line,
+        column: Offset {
+            utf8_offset: column,
+            utf16_offset: column,
+            grapheme_offset: column,
+        },
+        ..Default::default()
+    }
+}
+
+fn set_span(graph: &mut StackGraph, node: Handle<Node>, line: usize, start: usize, end: usize) {
+    graph.source_info_mut(node).span = Span {
+        start: position(line, start),
+        end: position(line, end),
+    };
+}
+
+fn create_rename_database() -> SQLiteReader {
+    create_database(vec![
+        // lib:  def foo
+        build_file("lib", |graph, partials, file| {
+            let foo = create_pop_symbol_node(graph, file, "foo", true);
+            set_span(graph, foo, 0, 4, 7);
+            vec![
+                create_partial_path_and_edges(graph, partials, &[StackGraph::root_node(), foo])
+                    .unwrap(),
+            ]
+        }),
+        // main: def bar
+        //       foo
+        build_file("main", |graph, partials, file| {
+            let bar = create_pop_symbol_node(graph, file, "bar", true);
+            set_span(graph, bar, 0, 4, 7);
+            let scope = create_scope_node(graph, file, false);
+            create_edge(graph, (scope, bar));
+            let foo_ref = create_push_symbol_node(graph, file, "foo", true);
+            set_span(graph, foo_ref, 1, 0, 3);
+            vec![create_partial_path_and_edges(
+                graph,
+                partials,
+                &[foo_ref, scope, StackGraph::root_node()],
+            )
+            .unwrap()]
+        }),
+    ])
+}
+
+#[test]
+fn can_rename_definition_and_references() {
+    let mut reader = create_rename_database();
+    let result = rename(&mut reader, "lib", &position(0, 5), "baz", &NoCancellation).unwrap();
+    let edits = result
+        .edits
+        .iter()
+        .map(|e| {
+            (
+                e.file.to_string_lossy().to_string(),
+                e.span.start.line,
+                e.span.start.column.utf8_offset,
+                e.replacement.as_str(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![
+            ("lib".to_string(), 0, 4, "baz"),
+            ("main".to_string(), 1, 0, "baz"),
+        ],
+        edits
+    );
+    assert!(result.is_safe());
+}
+
+#[test]
+fn can_group_references_by_definition() {
+    let mut reader = create_rename_database();
+    let groups = group_references_by_definition(&mut reader, "main", &NoCancellation).unwrap();
+    let groups = groups
+        .iter()
+        .map(|g| {
+            (
+                g.file.to_string_lossy().to_string(),
+                g.symbol.as_str(),
+                g.external,
+                g.references.clone(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![(
+            "lib".to_string(),
+            "foo",
+            true,
+            vec![Span {
+                start: position(1, 0),
+                end: position(1, 3),
+            }]
+        )],
+        groups
+    );
+
+    let groups = group_references_by_definition(&mut reader, "lib", &NoCancellation).unwrap();
+    assert!(groups.is_empty());
+}
+
+#[test]
+fn can_detect_shadowed_rename() {
+    let mut reader = create_rename_database();
+    let result = rename(&mut reader, "main", &position(1, 1), "bar", &NoCancellation).unwrap();
+    assert_eq!(2, result.edits.len());
+    let conflicts = result
+        .conflicts
+        .iter()
+        .map(|c| {
+            (
+                c.file.to_string_lossy().to_string(),
+                c.span.start.line,
+                c.definition_file.to_string_lossy().to_string(),
+                c.definition_span.start.line,
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![("main".to_string(), 1, "main".to_string(), 0)],
+        conflicts
+    );
+}
+
+#[test]
+fn renamed_definitions_that_shadow_the_new_name_are_not_conflicts() {
+    let mut reader = create_database(vec![
+        // lib:  def foo
+        build_file("lib", |graph, partials, file| {
+            let foo = create_pop_symbol_node(graph, file, "foo", true);
+            set_span(graph, foo, 0, 4, 7);
+            vec![
+                create_partial_path_and_edges(graph, partials, &[StackGraph::root_node(), foo])
+                    .unwrap(),
+            ]
+        }),
+        // main: def bar, which shadows the definitions reachable from the root
+        //       bar
+        build_file("main", |graph, partials, file| {
+            let bar = create_pop_symbol_node(graph, file, "bar", true);
+            set_span(graph, bar, 0, 4, 7);
+            let scope = create_scope_node(graph, file, false);
+            graph.add_edge(scope, bar, Precedence::HIGH);
+            create_edge(graph, (scope, StackGraph::root_node()));
+            let bar_ref = create_push_symbol_node(graph, file, "bar", true);
+            set_span(graph, bar_ref, 1, 0, 3);
+            let mut ref_path =
+                create_partial_path_and_edges(graph, partials, &[bar_ref, scope]).unwrap();
+            ref_path
+                .append(graph, partials, edge(scope, bar, Precedence::HIGH))
+                .unwrap();
+            vec![ref_path]
+        }),
+    ]);
+    let result = rename(&mut reader, "main", &position(0, 5), "foo", &NoCancellation).unwrap();
+    assert_eq!(2, result.edits.len());
+    assert!(result.is_safe());
+}
+
+#[test]
+fn can_extract_import_graph_with_cycles() {
+    // a defines A and uses B, b defines B and uses A, c uses A
+    let mut reader = create_database(vec![
+        build_file("a", symbols(&["A"], &["B"])),
+        build_file("b", symbols(&["B"], &["A"])),
+        build_file("c", symbols(&[], &["A"])),
+    ]);
+
+    let imports = import_graph(&mut reader, &NoCancellation).unwrap();
+    let dependencies = imports
+        .dependencies
+        .iter()
+        .map(|d| {
+            (
+                d.file.to_string_lossy().to_string(),
+                d.dependency.to_string_lossy().to_string(),
+                d.references,
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![
+            ("a".to_string(), "b".to_string(), 1),
+            ("b".to_string(), "a".to_string(), 1),
+            ("c".to_string(), "a".to_string(), 1),
+        ],
+        dependencies
+    );
+    assert_eq!(
+        vec![
+            vec![PathBuf::from("a"), PathBuf::from("b")],
+            vec![PathBuf::from("c")],
+        ],
+        imports.strongly_connected_components()
+    );
+    assert_eq!(
+        vec![vec![PathBuf::from("a"), PathBuf::from("b")]],
+        imports.cycles()
+    );
+    assert!(imports
+        .to_dot()
+        .contains("  \"c\" -> \"a\" [label=\"1\"];\n"));
+}
+
+fn create_call_database() -> SQLiteReader {
+    // def f():
+    //   ...
+    //
+    // def g():
+    //   f()
+    //
+    // f()
+    create_database(vec![build_file("main", |graph, partials, file| {
+        let r = StackGraph::root_node();
+        let call = graph.add_string("call");
+        let f = create_pop_symbol_node(graph, file, "f", true);
+        set_span(graph, f, 0, 4, 5);
+        graph.source_info_mut(f).definiens_span = Span {
+            start: position(0, 0),
+            end: position(2, 0),
+        };
+        let g = create_pop_symbol_node(graph, file, "g", true);
+        set_span(graph, g, 3, 4, 5);
+        graph.source_info_mut(g).definiens_span = Span {
+            start: position(3, 0),
+            end: position(5, 0),
+        };
+        let mut paths = vec![
+            create_partial_path_and_edges(graph, partials, &[r, f]).unwrap(),
+            create_partial_path_and_edges(graph, partials, &[r, g]).unwrap(),
+        ];
+        for (line, column) in [(4, 2), (6, 0)] {
+            let f_ref = create_push_symbol_node(graph, file, "f", true);
+            set_span(graph, f_ref, line, column, column + 1);
+            graph.source_info_mut(f_ref).syntax_type = call.into();
+            paths.push(create_partial_path_and_edges(graph, partials, &[f_ref, r]).unwrap());
+        }
+        paths
+    })])
+}
+
+#[test]
+fn can_find_callers_of_definition() {
+    let mut reader = create_call_database();
+    let callers = callers_of(
+        &mut reader,
+        "main",
+        &position(0, 4),
+        &CallHierarchyConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    let callers = callers
+        .iter()
+        .map(|c| {
+            (
+                c.symbol.clone(),
+                c.call_sites
+                    .iter()
+                    .map(|s| (s.span.start.line, s.span.start.column.utf8_offset))
+                    .collect_vec(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(vec![("g".to_string(), vec![(4, 2)])], callers);
+}
+
+#[test]
+fn can_find_callees_of_definition() {
+    let mut reader = create_call_database();
+    let callees = callees_of(
+        &mut reader,
+        "main",
+        &position(3, 4),
+        &CallHierarchyConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    let callees = callees
+        .iter()
+        .map(|c| {
+            (
+                c.symbol.clone(),
+                c.call_sites
+                    .iter()
+                    .map(|s| (s.span.start.line, s.span.start.column.utf8_offset))
+                    .collect_vec(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(vec![("f".to_string(), vec![(4, 2)])], callees);
+}
+
+#[test]
+fn can_resolve_all_references_and_resume() {
+    let reference_id = Cell::new(0);
+    let definition_id = Cell::new(0);
+    let mut reader = create_database(vec![
+        build_file("a", |graph, partials, file| {
+            let paths = symbols(&["A"], &[])(graph, partials, file);
+            definition_id.set(graph[paths[0].end_node].id().local_id());
+            paths
+        }),
+        build_file("b", |graph, partials, file| {
+            let paths = symbols(&[], &["A"])(graph, partials, file);
+            reference_id.set(graph[paths[0].start_node].id().local_id());
+            paths
+        }),
+        build_file("c", symbols(&[], &["B"])),
+    ]);
+    let reference_id = reference_id.get();
+    let definition_id = definition_id.get();
+
+    let options = ResolveAllOptions::default().with_batch_size(1);
+    let mut reports = 0;
+    let result =
+        resolve_all_references(&mut reader, &options, &NoCancellation, |_| reports += 1).unwrap();
+    assert_eq!(4, reports);
+    assert!(result.is_complete());
+    assert_eq!(3, result.files_resolved);
+    assert_eq!(2, result.references);
+    assert_eq!(1, result.resolutions);
+    assert_eq!(
+        vec![StoredResolution {
+            file: PathBuf::from("b"),
+            local_id: reference_id,
+            definition_file: PathBuf::from("a"),
+            definition_local_id: definition_id,
+        }],
+        reader.resolutions_for_file("b").unwrap()
+    );
+    assert!(reader.resolutions_for_file("c").unwrap().is_empty());
+
+    // resuming skips all files that are resolved already
+    let result = resolve_all_references(&mut reader, &options, &NoCancellation, |_| {}).unwrap();
+    assert_eq!(3, result.files_skipped);
+    assert_eq!(0, result.files_resolved);
+
+    // not resuming resolves all files again
+    let result = resolve_all_references(
+        &mut reader,
+        &options.with_resume(false),
+        &NoCancellation,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(0, result.files_skipped);
+    assert_eq!(3, result.files_resolved);
+    assert_eq!(1, reader.resolutions_for_file("b").unwrap().len());
+
+    // fresh resolutions can be looked up by reference node
+    assert_eq!(
+        Some(vec![StoredResolution {
+            file: PathBuf::from("b"),
+            local_id: reference_id,
+            definition_file: PathBuf::from("a"),
+            definition_local_id: definition_id,
+        }]),
+        reader
+            .fresh_resolutions_for_node("b", reference_id)
+            .unwrap()
+    );
+    assert_eq!(
+        Some(vec![]),
+        reader
+            .fresh_resolutions_for_node("b", definition_id + 1000)
+            .unwrap()
+    );
+    reader.clean_resolutions().unwrap();
+    assert_eq!(
+        None,
+        reader
+            .fresh_resolutions_for_node("b", reference_id)
+            .unwrap()
+    );
+}
+
+#[test]
+fn resolutions_are_not_fresh_when_participating_files_change() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-fresh-resolutions-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    // b references B, which m re-exports as A, which a defines; c references C, which is not
+    // defined anywhere
+    let reference_id = Cell::new(0);
+    let unresolved_id = Cell::new(0);
+    {
+        let mut writer = SQLiteWriter::open(&db_path).unwrap();
+        let mut graph = StackGraph::new();
+        let mut partials = PartialPaths::new();
+        let r = StackGraph::root_node();
+        let mut store = |graph: &mut StackGraph,
+                         partials: &mut PartialPaths,
+                         file: Handle<File>,
+                         paths: Vec<PartialPath>| {
+            writer
+                .store_result_for_file(graph, file, "1", partials, &paths)
+                .unwrap()
+        };
+
+        let a = graph.add_file("a").unwrap();
+        let paths = symbols(&["A"], &[])(&mut graph, &mut partials, a);
+        store(&mut graph, &mut partials, a, paths);
+
+        let m = graph.add_file("m").unwrap();
+        let pop = create_pop_symbol_node(&mut graph, m, "B", false);
+        let push = create_push_symbol_node(&mut graph, m, "A", false);
+        let paths =
+            vec![
+                create_partial_path_and_edges(&mut graph, &mut partials, &[r, pop, push, r])
+                    .unwrap(),
+            ];
+        store(&mut graph, &mut partials, m, paths);
+
+        let b = graph.add_file("b").unwrap();
+        let paths = symbols(&[], &["B"])(&mut graph, &mut partials, b);
+        reference_id.set(graph[paths[0].start_node].id().local_id());
+        store(&mut graph, &mut partials, b, paths);
+
+        let c = graph.add_file("c").unwrap();
+        let paths = symbols(&[], &["C"])(&mut graph, &mut partials, c);
+        unresolved_id.set(graph[paths[0].start_node].id().local_id());
+        store(&mut graph, &mut partials, c, paths);
+    }
+    let reference_id = reference_id.get();
+    let unresolved_id = unresolved_id.get();
+
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+    let result = resolve_all_references(
+        &mut reader,
+        &ResolveAllOptions::default(),
+        &NoCancellation,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(1, result.resolutions);
+    assert_eq!(
+        1,
+        reader
+            .fresh_resolutions_for_node("b", reference_id)
+            .unwrap()
+            .map_or(0, |resolutions| resolutions.len())
+    );
+    assert_eq!(
+        Some(vec![]),
+        reader
+            .fresh_resolutions_for_node("c", unresolved_id)
+            .unwrap()
+    );
+
+    // a new file can define the unresolved reference, but does not participate in the resolution
+    {
+        let mut writer = SQLiteWriter::open(&db_path).unwrap();
+        let mut graph = StackGraph::new();
+        let mut partials = PartialPaths::new();
+        let d = graph.add_file("d").unwrap();
+        let paths = symbols(&["D"], &[])(&mut graph, &mut partials, d);
+        writer
+            .store_result_for_file(&graph, d, "1", &mut partials, &paths)
+            .unwrap();
+    }
+    assert_eq!(
+        None,
+        reader
+            .fresh_resolutions_for_node("c", unresolved_id)
+            .unwrap()
+    );
+    assert!(reader
+        .fresh_resolutions_for_node("b", reference_id)
+        .unwrap()
+        .is_some());
+
+    // m is neither the file of the reference nor of the definition, but participates in the
+    // resolution
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute("UPDATE graphs SET tag = '2' WHERE file = 'm'", [])
+            .unwrap();
+    }
+    assert_eq!(
+        None,
+        reader
+            .fresh_resolutions_for_node("b", reference_id)
+            .unwrap()
+    );
+
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn can_report_binding_drift() {
+    // foo moves from a to c, and bar is defined in c
+    let create_before_database = || {
+        create_database(vec![
+            build_file("a", symbols(&["foo"], &[])),
+            build_file("main", symbols(&[], &["bar", "foo"])),
+        ])
+    };
+    let mut before = create_before_database();
+    let mut after = create_database(vec![
+        build_file("c", symbols(&["bar", "foo"], &[])),
+        build_file("main", symbols(&[], &["bar", "foo"])),
+    ]);
+
+    let mut unchanged = create_before_database();
+    let drifts = binding_drift(&mut before, &mut unchanged, &NoCancellation).unwrap();
+    assert!(drifts.is_empty());
+
+    let drifts = binding_drift(&mut before, &mut after, &NoCancellation).unwrap();
+    let drifts = drifts
+        .iter()
+        .map(|d| {
+            (
+                d.kind,
+                d.reference.symbol.as_str(),
+                d.before
+                    .iter()
+                    .map(|l| l.file.to_string_lossy().to_string())
+                    .collect_vec(),
+                d.after
+                    .iter()
+                    .map(|l| l.file.to_string_lossy().to_string())
+                    .collect_vec(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![
+            (DriftKind::Appeared, "bar", vec![], vec!["c".to_string()]),
+            (
+                DriftKind::Changed,
+                "foo",
+                vec!["a".to_string()],
+                vec!["c".to_string()]
+            ),
+        ],
+        drifts
+    );
+}
+
+#[test]
+fn can_extract_symbol_contexts() {
+    let source = "/// A foo.\nclass Foo {\n    // Does bar.\n    bar() {}\n}\n";
+    let mut reader = create_database(vec![build_file("lib", |graph, _, lib| {
+        let foo = create_pop_symbol_node(graph, lib, "Foo", true);
+        set_span(graph, foo, 1, 6, 9);
+        let class = graph.add_string("class");
+        let foo_line = graph.add_string("class Foo {");
+        let source_info = graph.source_info_mut(foo);
+        source_info.syntax_type = class.into();
+        source_info.containing_line = foo_line.into();
+        source_info.definiens_span = Span {
+            start: position(1, 10),
+            end: position(4, 1),
+        };
+
+        let bar = create_pop_symbol_node(graph, lib, "bar", true);
+        set_span(graph, bar, 3, 4, 7);
+        let bar_line = graph.add_string("    bar() {}");
+        graph.source_info_mut(bar).containing_line = bar_line.into();
+        vec![]
+    })]);
+
+    let contexts = symbol_contexts(&mut reader, "lib", Some(source)).unwrap();
+    let contexts = contexts
+        .iter()
+        .map(|c| {
+            (
+                c.qualified_name.as_str(),
+                c.kind.as_ref().map(|kind| kind.to_string()),
+                c.signature.as_deref(),
+                c.doc.as_deref(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        vec![
+            (
+                "Foo",
+                Some("class".to_string()),
+                Some("class Foo {"),
+                Some("A foo.")
+            ),
+            ("Foo.bar", None, Some("bar() {}"), Some("Does bar.")),
+        ],
+        contexts
+    );
+
+    let contexts = symbol_contexts(&mut reader, "lib", None).unwrap();
+    assert!(contexts.iter().all(|c| c.doc.is_none()));
+}
@@ -10,9 +10,12 @@ use stack_graphs::arena::Deque;
 use stack_graphs::arena::DequeArena;
 use stack_graphs::arena::List;
 use stack_graphs::arena::ListArena;
+use stack_graphs::arena::MemoryBudget;
+use stack_graphs::arena::OutOfBudget;
 use stack_graphs::arena::ReversibleList;
 use stack_graphs::arena::ReversibleListArena;
 use stack_graphs::arena::SupplementalArena;
+use stack_graphs::CancellationFlag;
 
 #[test]
 fn can_allocate_in_arena() {
@@ -28,6 +31,48 @@ fn can_allocate_in_arena() {
     assert_ne!(arena.get(hello2), arena.get(there));
 }
 
+#[test]
+fn can_charge_arenas_to_memory_budget() {
+    let budget = MemoryBudget::new(16);
+    let mut first = Arena::<u32>::new();
+    first.add(1);
+    first.set_budget(Some(budget.clone()));
+    assert_eq!(budget.used(), 4);
+
+    let mut second = Arena::<u64>::new();
+    second.set_budget(Some(budget.clone()));
+    second.add(2);
+    assert_eq!(budget.used(), 12);
+    assert_eq!(budget.remaining(), 4);
+    assert!(budget.check_usage().is_ok());
+
+    assert_eq!(
+        second.try_add(3),
+        Err(OutOfBudget {
+            limit: 16,
+            used: 20
+        })
+    );
+    assert_eq!(second.len(), 2);
+    assert_eq!(budget.used(), 12);
+
+    second.add(3);
+    assert_eq!(
+        budget.check_usage(),
+        Err(OutOfBudget {
+            limit: 16,
+            used: 20
+        })
+    );
+    assert!(budget.check("test").is_err());
+
+    second.clear();
+    assert_eq!(budget.used(), 4);
+    drop(first);
+    assert_eq!(budget.used(), 0);
+    assert!(budget.check("test").is_ok());
+}
+
 #[test]
 fn can_allocate_in_supplemental_arena() {
     let mut arena = Arena::<u32>::new();
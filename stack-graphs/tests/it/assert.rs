@@ -0,0 +1,80 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::assert::parse;
+use stack_graphs::assert::Assertion;
+use stack_graphs::assert::AssertionParseError;
+use stack_graphs::assert::AssertionSyntax;
+use stack_graphs::graph::StackGraph;
+
+#[test]
+fn can_parse_assertions() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let source = r#"
+x = 1
+def foo():
+  pass
+foo(x)
+#   ^ defined: 2, 3
+"#;
+    let assertions = parse(&mut graph, file, source, &AssertionSyntax::default()).unwrap();
+    assert_eq!(1, assertions.len());
+    match &assertions[0] {
+        Assertion::Defined { source, targets } => {
+            assert_eq!(4, source.position.line);
+            assert_eq!(4, source.position.column.utf8_offset);
+            let lines = targets.iter().map(|t| t.line).collect::<Vec<_>>();
+            assert_eq!(vec![1, 2], lines);
+        }
+        _ => panic!("expected defined assertion"),
+    }
+
+    let source = "foo(x)\n# ^ defines: a, b\n";
+    let assertions = parse(&mut graph, file, source, &AssertionSyntax::default()).unwrap();
+    match &assertions[0] {
+        Assertion::Defines { source, symbols } => {
+            assert_eq!(0, source.position.line);
+            assert_eq!(2, source.position.column.utf8_offset);
+            let names = symbols.iter().map(|s| &graph[*s]).collect::<Vec<_>>();
+            assert_eq!(vec!["a", "b"], names);
+        }
+        _ => panic!("expected defines assertion"),
+    }
+}
+
+#[test]
+fn can_parse_assertions_with_comment_syntax() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.rs").unwrap();
+    let source = "value = flags ^ mask: 1\n//      ^ refers: flags\n";
+    let syntax = AssertionSyntax::new().with_comment_prefix("//");
+    let assertions = parse(&mut graph, file, source, &syntax).unwrap();
+    assert_eq!(1, assertions.len());
+    assert!(matches!(assertions[0], Assertion::Refers { .. }));
+
+    // without the comment syntax, the source line itself is not a valid assertion line
+    assert_eq!(
+        AssertionParseError::AssertionRefersToNonSourceLine(0),
+        parse(&mut graph, file, source, &AssertionSyntax::default()).unwrap_err()
+    );
+}
+
+#[test]
+fn cannot_parse_invalid_assertions() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let syntax = AssertionSyntax::default();
+    assert_eq!(
+        AssertionParseError::InvalidAssertion(1, "resolves".to_string()),
+        parse(&mut graph, file, "foo\n# ^ resolves: 1\n", &syntax).unwrap_err()
+    );
+    assert_eq!(
+        AssertionParseError::InvalidColumn(1, 6, 0),
+        parse(&mut graph, file, "foo\n#     ^ defined: 1\n", &syntax).unwrap_err()
+    );
+}
@@ -0,0 +1,504 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Checks that the C API is compatible with the ABI that is recorded in
+//! `include/stack-graphs.abi`.  The declarations of functions, types, and constants, and the
+//! variants of enums, are taken from the generated header.  The layouts of structs are taken from
+//! the Rust definitions, which the header does not describe.
+
+use std::mem::MaybeUninit;
+
+use stack_graphs::c::*;
+
+const HEADER: &str = include_str!("../../../include/stack-graphs.h");
+const BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/include/stack-graphs.abi");
+const UPDATE_VARIABLE: &str = "STACK_GRAPHS_UPDATE_ABI";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    /// A function, typedef, or constant, as its normalized declaration.
+    Declaration(String),
+    Enum(String, Vec<String>),
+    Struct(String),
+    Layout(Layout),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Layout {
+    name: String,
+    size: usize,
+    align: usize,
+    fields: Vec<(String, usize)>,
+}
+
+/// An item of the ABI, with the cargo feature it depends on, if any.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    feature: Option<String>,
+    item: Item,
+}
+
+macro_rules! layout {
+    ($name:ident { $($field:ident),* $(,)? }) => {{
+        let value = MaybeUninit::<$name>::uninit();
+        let base = value.as_ptr();
+        Layout {
+            name: stringify!($name).to_string(),
+            size: std::mem::size_of::<$name>(),
+            align: std::mem::align_of::<$name>(),
+            fields: vec![$((
+                stringify!($field).to_string(),
+                unsafe { std::ptr::addr_of!((*base).$field) } as usize - base as usize,
+            )),*],
+        }
+    }};
+}
+
+/// Returns the layouts of all structs of the C API.
+fn layouts() -> Vec<Layout> {
+    #[cfg_attr(not(feature = "visualization"), allow(unused_mut))]
+    let mut layouts = vec![
+        layout!(sg_symbol { symbol, symbol_len }),
+        layout!(sg_symbols { symbols, count }),
+        layout!(sg_string { content, length }),
+        layout!(sg_strings { strings, count }),
+        layout!(sg_file { name, name_len }),
+        layout!(sg_files { files, count }),
+        layout!(sg_node_id { file, local_id }),
+        layout!(sg_node {
+            kind,
+            id,
+            symbol,
+            scope,
+            is_endpoint,
+        }),
+        layout!(sg_nodes { nodes, count }),
+        layout!(sg_edge {
+            source,
+            sink,
+            precedence,
+        }),
+        layout!(sg_offset {
+            utf8_offset,
+            utf16_offset,
+            grapheme_offset,
+        }),
+        layout!(sg_utf8_bounds { start, end }),
+        layout!(sg_position {
+            line,
+            column,
+            containing_line,
+            trimmed_line,
+        }),
+        layout!(sg_span { start, end }),
+        layout!(sg_source_info {
+            span,
+            syntax_type,
+            containing_line,
+            definiens_span,
+            fully_qualified_name,
+        }),
+        layout!(sg_source_infos { infos, count }),
+        layout!(sg_node_source_info { node, source_info }),
+        layout!(sg_partial_scope_stack {
+            cells,
+            direction,
+            length,
+            variable,
+        }),
+        layout!(sg_partial_scoped_symbol { symbol, scopes }),
+        layout!(sg_partial_symbol_stack_cell {
+            head,
+            tail,
+            reversed,
+        }),
+        layout!(sg_partial_symbol_stack_cells { cells, count }),
+        layout!(sg_partial_symbol_stack {
+            cells,
+            direction,
+            length,
+            variable,
+        }),
+        layout!(sg_partial_scope_stack_cell {
+            head,
+            tail,
+            reversed,
+        }),
+        layout!(sg_partial_scope_stack_cells { cells, count }),
+        layout!(sg_partial_path_edge {
+            source_node_id,
+            precedence,
+        }),
+        layout!(sg_partial_path_edge_list_cell {
+            head,
+            tail,
+            reversed,
+        }),
+        layout!(sg_partial_path_edge_list_cells { cells, count }),
+        layout!(sg_partial_path_edge_list {
+            cells,
+            direction,
+            length,
+        }),
+        layout!(sg_partial_path {
+            start_node,
+            end_node,
+            symbol_stack_precondition,
+            symbol_stack_postcondition,
+            scope_stack_precondition,
+            scope_stack_postcondition,
+            edges,
+        }),
+        layout!(sg_stitcher_config {
+            detect_similar_paths
+        }),
+        layout!(sg_partial_paths { paths, count }),
+        layout!(sg_node_handle_set { elements, length }),
+        layout!(sg_forward_partial_path_stitcher {
+            previous_phase_partial_paths,
+            previous_phase_partial_paths_length,
+            is_complete,
+        }),
+    ];
+    #[cfg(feature = "visualization")]
+    layouts.push(layout!(sg_visualization_options {
+        files,
+        file_count,
+        source_navigation,
+        url_template,
+        url_template_length,
+    }));
+    layouts
+}
+
+fn is_feature_enabled(feature: &Option<String>) -> bool {
+    match feature.as_deref() {
+        None => true,
+        Some("visualization") => cfg!(feature = "visualization"),
+        Some(_) => false,
+    }
+}
+
+/// Parses the declarations, enums, and structs of the generated header.  Items that are guarded
+/// by `SG_FEATURE_*` defines depend on the corresponding cargo feature.
+fn parse_header(header: &str) -> Vec<Entry> {
+    // the features of the enclosing conditionals, where `Err` marks C++-only sections
+    let mut conditionals: Vec<Result<Option<String>, ()>> = Vec::new();
+    let mut entries = Vec::new();
+    let mut text = String::new();
+    let mut depth = 0i32;
+    for line in header.lines().map(str::trim) {
+        let feature = conditionals
+            .iter()
+            .rev()
+            .find_map(|conditional| conditional.clone().ok().flatten());
+        let in_cpp = conditionals.iter().any(Result::is_err);
+        if line.starts_with('#') {
+            if let Some(feature) = line.strip_prefix("#if defined(SG_FEATURE_") {
+                conditionals.push(Ok(Some(feature.trim_end_matches(')').to_lowercase())));
+            } else if line.starts_with("#ifdef __cplusplus") {
+                conditionals.push(Err(()));
+            } else if line.starts_with("#if") {
+                conditionals.push(Ok(None));
+            } else if line.starts_with("#endif") {
+                conditionals.pop();
+            } else if line.starts_with("#define")
+                && !in_cpp
+                && !conditionals.is_empty()
+                && !line.starts_with("#define STACK_GRAPHS_H_")
+            {
+                entries.push(Entry {
+                    feature,
+                    item: Item::Declaration(normalize(line)),
+                });
+            }
+            continue;
+        }
+        if in_cpp || line.is_empty() || line.starts_with("//") || line.starts_with("/*") {
+            continue;
+        }
+        text.push(' ');
+        text.push_str(line);
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+        if depth != 0 || !line.ends_with(';') {
+            continue;
+        }
+        let declaration = normalize(&text);
+        text.clear();
+        let item = if declaration.starts_with("enum sg_") && declaration.contains('{') {
+            let open = declaration.find('{').unwrap();
+            let close = declaration.rfind('}').unwrap();
+            let variants = declaration[open + 1..close]
+                .split(',')
+                .map(str::trim)
+                .filter(|variant| !variant.is_empty())
+                .map(str::to_string)
+                .collect();
+            Item::Enum(
+                declaration["enum ".len()..open].trim().to_string(),
+                variants,
+            )
+        } else if declaration.starts_with("struct sg_") && declaration.contains('{') {
+            let open = declaration.find('{').unwrap();
+            Item::Struct(declaration["struct ".len()..open].trim().to_string())
+        } else if declaration.starts_with("struct sg_") && !declaration.contains('(') {
+            // opaque structs have no layout that embedders can depend on
+            continue;
+        } else {
+            Item::Declaration(declaration)
+        };
+        entries.push(Entry { feature, item });
+    }
+    entries
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+/// Returns the current ABI: the declarations and enums of the header, and the layouts of its
+/// structs.
+fn current_abi() -> Vec<Entry> {
+    let layouts = layouts();
+    let header = parse_header(HEADER);
+    let structs = header.iter().filter_map(|entry| match &entry.item {
+        Item::Struct(name) => Some(Entry {
+            feature: entry.feature.clone(),
+            item: match layouts.iter().find(|layout| &layout.name == name) {
+                Some(layout) => Item::Layout(layout.clone()),
+                None => Item::Struct(name.clone()),
+            },
+        }),
+        _ => None,
+    });
+    let others = header
+        .iter()
+        .filter(|entry| !matches!(entry.item, Item::Struct(_)))
+        .cloned();
+    structs.chain(others).collect()
+}
+
+fn render(version: &str, entries: &[Entry]) -> String {
+    let mut result = String::new();
+    result.push_str(&format!("version {}\n", version));
+    for entry in entries {
+        if let Some(feature) = &entry.feature {
+            result.push_str(&format!("[{}] ", feature));
+        }
+        match &entry.item {
+            Item::Declaration(declaration) => result.push_str(&format!("decl {}", declaration)),
+            Item::Enum(name, variants) => {
+                result.push_str(&format!("enum {} {}", name, variants.join(" ")))
+            }
+            Item::Layout(layout) => {
+                result.push_str(&format!(
+                    "layout {} {} {}",
+                    layout.name, layout.size, layout.align
+                ));
+                for (field, offset) in &layout.fields {
+                    result.push_str(&format!(" {}:{}", field, offset));
+                }
+            }
+            Item::Struct(name) => panic!("struct {} has no layout", name),
+        }
+        result.push('\n');
+    }
+    result
+}
+
+fn parse_baseline(baseline: &str) -> (String, Vec<Entry>) {
+    let mut version = None;
+    let mut entries = Vec::new();
+    for line in baseline.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (feature, line) = match line.strip_prefix('[') {
+            Some(rest) => {
+                let (feature, line) = rest.split_once("] ").expect("feature to be closed");
+                (Some(feature.to_string()), line)
+            }
+            None => (None, line),
+        };
+        let (kind, rest) = line.split_once(' ').expect("line to have a kind");
+        let item = match kind {
+            "version" => {
+                version = Some(rest.to_string());
+                continue;
+            }
+            "decl" => Item::Declaration(rest.to_string()),
+            "enum" => {
+                let mut words = rest.split(' ').map(str::to_string);
+                let name = words.next().unwrap();
+                Item::Enum(name, words.collect())
+            }
+            "layout" => {
+                let mut words = rest.split(' ');
+                let name = words.next().unwrap().to_string();
+                let size = words.next().unwrap().parse().unwrap();
+                let align = words.next().unwrap().parse().unwrap();
+                let fields = words
+                    .map(|field| {
+                        let (field, offset) = field.split_once(':').unwrap();
+                        (field.to_string(), offset.parse().unwrap())
+                    })
+                    .collect();
+                Item::Layout(Layout {
+                    name,
+                    size,
+                    align,
+                    fields,
+                })
+            }
+            _ => panic!("unknown kind of ABI entry {}", kind),
+        };
+        entries.push(Entry { feature, item });
+    }
+    (version.expect("baseline to have a version"), entries)
+}
+
+/// Returns whether a crate with the current version can be used by embedders that were built
+/// against the baseline version, according to semantic versioning.
+fn is_semver_compatible(baseline: &str, current: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split(&['.', '-'][..])
+            .take(3)
+            .map(|part| part.parse::<u64>().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let baseline = parse(baseline);
+    let current = parse(current);
+    if baseline[0] != 0 {
+        baseline[0] == current[0]
+    } else if baseline[1] != 0 {
+        current[0] == 0 && baseline[1] == current[1]
+    } else {
+        baseline == current
+    }
+}
+
+/// Returns the changes that break compatibility with the baseline.
+fn incompatibilities(baseline: &[Entry], current: &[Entry]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for entry in current
+        .iter()
+        .filter(|entry| is_feature_enabled(&entry.feature))
+    {
+        if let Item::Struct(name) = &entry.item {
+            problems.push(format!(
+                "struct {} is missing from the layouts of the ABI test",
+                name
+            ));
+        }
+    }
+    for entry in baseline
+        .iter()
+        .filter(|entry| is_feature_enabled(&entry.feature))
+    {
+        let compatible = match &entry.item {
+            Item::Declaration(_) => current.iter().any(|other| other.item == entry.item),
+            Item::Enum(name, variants) => current.iter().any(|other| match &other.item {
+                Item::Enum(other_name, other_variants) => {
+                    other_name == name && other_variants.starts_with(variants)
+                }
+                _ => false,
+            }),
+            Item::Layout(_) => current.iter().any(|other| other.item == entry.item),
+            Item::Struct(_) => true,
+        };
+        if !compatible {
+            problems.push(match &entry.item {
+                Item::Declaration(declaration) => {
+                    format!("`{}` was removed or changed", declaration)
+                }
+                Item::Enum(name, _) => {
+                    format!("variants of enum {} were removed or reordered", name)
+                }
+                Item::Layout(layout) => {
+                    format!("layout of struct {} was removed or changed", layout.name)
+                }
+                Item::Struct(_) => unreachable!(),
+            });
+        }
+    }
+    problems
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn c_api_is_compatible_with_recorded_abi() {
+    let current = current_abi();
+    let version = env!("CARGO_PKG_VERSION");
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        assert!(
+            cfg!(feature = "visualization"),
+            "the ABI must be recorded with all features enabled"
+        );
+        let comments = std::fs::read_to_string(BASELINE_PATH)
+            .unwrap_or_default()
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        std::fs::write(BASELINE_PATH, comments + &render(version, &current))
+            .expect("cannot write ABI");
+        return;
+    }
+
+    let baseline = std::fs::read_to_string(BASELINE_PATH).expect("cannot read ABI");
+    let (baseline_version, baseline) = parse_baseline(&baseline);
+    if !is_semver_compatible(&baseline_version, version) {
+        // a release that may break the ABI; the recorded ABI is updated along with it
+        return;
+    }
+    let problems = incompatibilities(&baseline, &current);
+    assert!(
+        problems.is_empty(),
+        "The C API is not compatible with the ABI of version {}:\n  {}\n\
+         Restore compatibility, or release a new major version and set {}=1 to record its ABI.",
+        baseline_version,
+        problems.join("\n  "),
+        UPDATE_VARIABLE,
+    );
+}
+
+#[test]
+fn header_is_parsed() {
+    let entries = parse_header(HEADER);
+    assert!(entries.contains(&Entry {
+        feature: None,
+        item: Item::Declaration("struct sg_stack_graph *sg_stack_graph_new(void);".to_string()),
+    }));
+    assert!(entries.contains(&Entry {
+        feature: None,
+        item: Item::Enum(
+            "sg_result".to_string(),
+            vec![
+                "SG_RESULT_SUCCESS".to_string(),
+                "SG_RESULT_CANCELLED".to_string()
+            ]
+        ),
+    }));
+    assert!(entries.contains(&Entry {
+        feature: Some("visualization".to_string()),
+        item: Item::Struct("sg_visualization_options".to_string()),
+    }));
+}
+
+#[test]
+fn semver_compatibility_is_checked() {
+    assert!(is_semver_compatible("0.14.1", "0.14.2"));
+    assert!(!is_semver_compatible("0.14.1", "0.15.0"));
+    assert!(is_semver_compatible("1.2.0", "1.3.0"));
+    assert!(!is_semver_compatible("1.2.0", "2.0.0"));
+    assert!(!is_semver_compatible("0.0.1", "0.0.2"));
+}
@@ -0,0 +1,137 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use stack_graphs::c::sg_partial_path_arena_find_all_complete_paths;
+use stack_graphs::c::sg_partial_path_arena_free;
+use stack_graphs::c::sg_partial_path_arena_new;
+use stack_graphs::c::sg_partial_path_list_count;
+use stack_graphs::c::sg_partial_path_list_free;
+use stack_graphs::c::sg_partial_path_list_new;
+use stack_graphs::c::sg_result;
+use stack_graphs::c::sg_stack_graph;
+use stack_graphs::c::sg_stitcher_config;
+
+use crate::c::test_graph::TestGraph;
+use crate::test_graphs;
+
+const THREADS: usize = 8;
+const ITERATIONS: usize = 20;
+
+/// A graph that is shared by several threads, which only read it.
+struct SharedGraph(TestGraph);
+
+unsafe impl Send for SharedGraph {}
+unsafe impl Sync for SharedGraph {}
+
+/// Finds all complete paths from the references in the graph, using a partial path arena and path
+/// list of its own, and returns the number of paths that were found.
+fn find_complete_paths(
+    graph: *const sg_stack_graph,
+    cancellation_flag: Option<&AtomicUsize>,
+) -> (sg_result, usize) {
+    let rust_graph = unsafe { &(*graph).inner };
+    let references = rust_graph
+        .iter_nodes()
+        .filter(|handle| rust_graph[*handle].is_reference())
+        .collect::<Vec<_>>();
+    let paths = sg_partial_path_arena_new();
+    let path_list = sg_partial_path_list_new();
+    let stitcher_config = sg_stitcher_config {
+        detect_similar_paths: false,
+    };
+    let result = sg_partial_path_arena_find_all_complete_paths(
+        graph,
+        paths,
+        references.len(),
+        references.as_ptr() as *const _,
+        path_list,
+        &stitcher_config,
+        cancellation_flag.map_or(std::ptr::null(), |flag| {
+            flag as *const AtomicUsize as *const usize
+        }),
+    );
+    let count = sg_partial_path_list_count(path_list);
+    sg_partial_path_list_free(path_list);
+    sg_partial_path_arena_free(paths);
+    (result, count)
+}
+
+#[test]
+fn independent_instances_can_be_used_concurrently() {
+    let graph: TestGraph = test_graphs::class_field_through_function_parameter::new();
+    let (result, expected) = find_complete_paths(graph.graph, None);
+    assert_eq!(sg_result::SG_RESULT_SUCCESS, result);
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            std::thread::spawn(move || {
+                for _ in 0..ITERATIONS {
+                    let graph: TestGraph =
+                        test_graphs::class_field_through_function_parameter::new();
+                    assert_eq!(
+                        (sg_result::SG_RESULT_SUCCESS, expected),
+                        find_complete_paths(graph.graph, None)
+                    );
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("thread should not panic");
+    }
+}
+
+#[test]
+fn graph_can_be_read_concurrently() {
+    let graph = Arc::new(SharedGraph(
+        test_graphs::class_field_through_function_parameter::new(),
+    ));
+    let (result, expected) = find_complete_paths(graph.0.graph, None);
+    assert_eq!(sg_result::SG_RESULT_SUCCESS, result);
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            let graph = graph.clone();
+            std::thread::spawn(move || {
+                for _ in 0..ITERATIONS {
+                    assert_eq!(
+                        (sg_result::SG_RESULT_SUCCESS, expected),
+                        find_complete_paths(graph.0.graph, None)
+                    );
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("thread should not panic");
+    }
+}
+
+#[test]
+fn shared_cancellation_flag_cancels_all_computations() {
+    let graph = Arc::new(SharedGraph(test_graphs::cyclic_imports_python::new()));
+    let cancellation_flag = Arc::new(AtomicUsize::new(1));
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            let graph = graph.clone();
+            let cancellation_flag = cancellation_flag.clone();
+            std::thread::spawn(move || {
+                let (result, _) = find_complete_paths(graph.0.graph, Some(&cancellation_flag));
+                assert_eq!(sg_result::SG_RESULT_CANCELLED, result);
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("thread should not panic");
+    }
+    assert_eq!(1, cancellation_flag.load(Ordering::Relaxed));
+}
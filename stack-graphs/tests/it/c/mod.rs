@@ -5,14 +5,18 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+mod abi;
 mod can_create_graph;
 mod can_find_local_nodes;
 mod can_find_partial_paths_in_file;
 mod can_find_qualified_definitions_with_phased_partial_path_stitching;
 mod can_jump_to_definition;
 mod can_jump_to_definition_with_phased_partial_path_stitching;
+mod can_use_instances_concurrently;
 mod files;
 mod nodes;
 mod partial;
 mod symbols;
 mod test_graph;
+#[cfg(feature = "visualization")]
+mod visualization;
@@ -0,0 +1,84 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::ffi::CStr;
+
+use libc::c_char;
+use stack_graphs::c::sg_file_handle;
+use stack_graphs::c::sg_html_free;
+use stack_graphs::c::sg_source_navigation;
+use stack_graphs::c::sg_stack_graph_to_html;
+use stack_graphs::c::sg_visualization_options;
+
+use crate::c::test_graph::TestGraph;
+use crate::test_graphs;
+
+fn to_html(graph: &TestGraph, title: &str, options: Option<&sg_visualization_options>) -> String {
+    let html = sg_stack_graph_to_html(
+        graph.graph,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        title.as_ptr() as *const c_char,
+        title.len(),
+        options.map_or(std::ptr::null(), |options| options as *const _),
+    );
+    assert!(!html.is_null());
+    let result = unsafe { CStr::from_ptr(html) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    sg_html_free(html);
+    result
+}
+
+#[test]
+fn can_create_html_visualization() {
+    let graph: TestGraph = test_graphs::simple::new();
+    let html = to_html(&graph, "simple graph", None);
+    assert!(html.contains("<title>simple graph</title>"));
+    assert!(html.contains("\"test.py\""));
+}
+
+#[test]
+fn can_filter_files_in_html_visualization() {
+    let graph: TestGraph = test_graphs::sequenced_import_star::new();
+    let rust_graph = unsafe { &(*graph.graph).inner };
+    let file = rust_graph.get_file("a.py").unwrap();
+    let files = [file.as_u32() as sg_file_handle];
+    let template = "vscode://file/{path}:{line}";
+    let options = sg_visualization_options {
+        files: files.as_ptr(),
+        file_count: files.len(),
+        source_navigation: sg_source_navigation::SG_SOURCE_NAVIGATION_URL,
+        url_template: template.as_ptr() as *const c_char,
+        url_template_length: template.len(),
+    };
+    let html = to_html(&graph, "a.py", Some(&options));
+    assert!(html.contains("\"a.py\""));
+    assert!(!html.contains("\"main.py\""));
+    assert!(html.contains(template));
+}
+
+#[test]
+fn invalid_title_is_rejected() {
+    let graph: TestGraph = test_graphs::simple::new();
+    let title = [0xffu8, 0xfe];
+    let html = sg_stack_graph_to_html(
+        graph.graph,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        title.as_ptr() as *const c_char,
+        title.len(),
+        std::ptr::null(),
+    );
+    assert!(html.is_null());
+}
+
+#[test]
+fn freeing_null_html_is_a_no_op() {
+    sg_html_free(std::ptr::null_mut());
+}
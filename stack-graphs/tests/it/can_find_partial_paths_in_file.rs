@@ -5,12 +5,16 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::cell::Cell;
 use std::collections::BTreeSet;
 
 use pretty_assertions::assert_eq;
+use stack_graphs::graph::NodeID;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
-use stack_graphs::stitching::{ForwardPartialPathStitcher, StitcherConfig};
+use stack_graphs::stitching::{ForwardPartialPathStitcher, PartialPathsConfig, StitcherConfig};
+use stack_graphs::CancellationError;
+use stack_graphs::CancellationFlag;
 use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
@@ -209,3 +213,139 @@ fn sequenced_import_star() {
     check_partial_paths_in_file(&graph, "a.py", SEQUENCED_IMPORT_STAR_A_PATHS);
     check_partial_paths_in_file(&graph, "b.py", SEQUENCED_IMPORT_STAR_B_PATHS);
 }
+
+fn find_partial_paths_in_file(
+    graph: &StackGraph,
+    file: &str,
+    config: &PartialPathsConfig,
+) -> BTreeSet<String> {
+    let file = graph.get_file(file).expect("Missing file");
+    let mut partials = PartialPaths::new();
+    let mut results = BTreeSet::new();
+    ForwardPartialPathStitcher::find_partial_paths_in_file(
+        graph,
+        &mut partials,
+        file,
+        config,
+        &NoCancellation,
+        |graph, partials, path| {
+            results.insert(path.display(graph, partials).to_string());
+        },
+    )
+    .expect("should never be cancelled");
+    results
+}
+
+#[test]
+fn partial_paths_are_split_at_join_nodes() {
+    let graph: StackGraph = test_graphs::sequenced_import_star::new();
+    let file = graph.get_file("main.py").expect("Missing file");
+    let join_node = graph
+        .node_for_id(NodeID::new_in_file(file, 3))
+        .expect("Missing node");
+    let results = find_partial_paths_in_file(
+        &graph,
+        "main.py",
+        &PartialPathsConfig::default().with_join_nodes(std::iter::once(join_node)),
+    );
+    let expected_paths = [
+        "<__main__,%1> ($1) [root] -> [main.py(0) definition __main__] <%1> ($1)",
+        "<%1> ($1) [main.py(8) reference a] -> [root] <a,%1> ($1)",
+        "<__main__.,%1> ($1) [main.py(0) definition __main__] -> [main.py(3) scope] <%1> ($1)",
+        "<%1> ($1) [main.py(6) reference foo] -> [main.py(3) scope] <foo,%1> ($1)",
+        "<%1> ($1) [main.py(3) scope] -> [main.py(8) reference a] <a.,%1> ($1)",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect::<BTreeSet<_>>();
+    assert_eq!(expected_paths, results);
+}
+
+#[test]
+fn non_minimal_partial_paths_extend_beyond_endpoints() {
+    let graph: StackGraph = test_graphs::sequenced_import_star::new();
+    let minimal = find_partial_paths_in_file(&graph, "main.py", &PartialPathsConfig::default());
+    let all = find_partial_paths_in_file(
+        &graph,
+        "main.py",
+        &PartialPathsConfig::default().with_minimal(false),
+    );
+    assert!(minimal.is_subset(&all));
+    assert!(all.contains("<%1> ($1) [main.py(6) reference foo] -> [root] <a.foo,%1> ($1)"));
+    assert!(!minimal.contains("<%1> ($1) [main.py(6) reference foo] -> [root] <a.foo,%1> ($1)"));
+}
+
+struct CancelAfterChecks(Cell<usize>);
+
+struct CountChecks(Cell<usize>);
+
+impl CancellationFlag for CountChecks {
+    fn check(&self, _at: &'static str) -> Result<(), CancellationError> {
+        self.0.set(self.0.get() + 1);
+        Ok(())
+    }
+}
+
+impl CancellationFlag for CancelAfterChecks {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.0.get() == 0 {
+            return Err(CancellationError(at));
+        }
+        self.0.set(self.0.get() - 1);
+        Ok(())
+    }
+}
+
+#[test]
+fn cancelled_partial_path_computation_keeps_found_paths() {
+    let graph = test_graphs::class_field_through_function_parameter::new();
+    let file = graph.get_file("main.py").expect("Missing file");
+    let mut partials = PartialPaths::new();
+
+    let cancelled = ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &CancelAfterChecks(Cell::new(0)),
+        |_, _, _| panic!("no paths should be found"),
+    )
+    .expect_err("should be cancelled");
+    assert_eq!("collecting initial partial paths", cancelled.error.0);
+    assert_eq!(0, cancelled.progress.initial_paths);
+
+    // cancelling at the last check of the complete computation stops it while extending the last
+    // partial path, after all other paths were extended
+    let total_checks = CountChecks(Cell::new(0));
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &total_checks,
+        |_, _, _| {},
+    )
+    .expect("should not be cancelled");
+    let checks = total_checks.0.get() - 1;
+    assert!(checks > graph.nodes_for_file(file).count() + 1);
+    let mut results = BTreeSet::new();
+    let cancelled = ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        &graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        &CancelAfterChecks(Cell::new(checks)),
+        |graph, partials, path| {
+            results.insert(path.display(graph, partials).to_string());
+        },
+    )
+    .expect_err("should be cancelled");
+    assert_eq!("extending partial paths", cancelled.error.0);
+    assert_eq!(results.len(), cancelled.progress.accepted_paths);
+    assert!(cancelled.progress.pending_paths > 0);
+    let expected_paths = CLASS_FIELD_THROUGH_FUNCTION_PARAMETER_MAIN_PATHS
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<BTreeSet<_>>();
+    assert!(results.is_subset(&expected_paths));
+}
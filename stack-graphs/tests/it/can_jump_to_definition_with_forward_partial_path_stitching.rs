@@ -14,11 +14,26 @@ use stack_graphs::stitching::Database;
 use stack_graphs::stitching::DatabaseCandidates;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::stitching::StitchingStrategy;
 use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
 
 fn check_jump_to_definition(graph: &StackGraph, expected_partial_paths: &[&str]) {
+    for strategy in [
+        StitchingStrategy::BreadthFirst,
+        StitchingStrategy::DepthFirst,
+        StitchingStrategy::BestFirst,
+    ] {
+        check_jump_to_definition_with_strategy(graph, strategy, expected_partial_paths);
+    }
+}
+
+fn check_jump_to_definition_with_strategy(
+    graph: &StackGraph,
+    strategy: StitchingStrategy,
+    expected_partial_paths: &[&str],
+) {
     let mut partials = PartialPaths::new();
     let mut db = Database::new();
 
@@ -44,7 +59,7 @@ fn check_jump_to_definition(graph: &StackGraph, expected_partial_paths: &[&str])
     ForwardPartialPathStitcher::find_all_complete_partial_paths(
         &mut DatabaseCandidates::new(graph, &mut partials, &mut db),
         references,
-        StitcherConfig::default(),
+        StitcherConfig::default().with_strategy(strategy),
         &NoCancellation,
         |_, _, p| {
             complete_partial_paths.push(p.clone());
@@ -60,7 +75,7 @@ fn check_jump_to_definition(graph: &StackGraph, expected_partial_paths: &[&str])
         .iter()
         .map(|s| s.to_string())
         .collect::<BTreeSet<_>>();
-    assert_eq!(expected_partial_paths, results);
+    assert_eq!(expected_partial_paths, results, "using {:?}", strategy);
 }
 
 #[test]
@@ -9,6 +9,7 @@ use enumset::enum_set;
 use stack_graphs::arena::Handle;
 use stack_graphs::cycles::Appendables;
 use stack_graphs::cycles::AppendingCycleDetector;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::Cyclicity;
 use stack_graphs::partial::PartialPath;
@@ -175,9 +176,9 @@ fn finding_simple_identity_cycle_is_detected() {
         let db = &GraphEdges;
 
         for edge in &[
-            edge(r, foo_ref, 0),
-            edge(foo_ref, s, 0),
-            edge(s, foo_def, 0),
+            edge(r, foo_ref, Precedence::DEFAULT),
+            edge(foo_ref, s, Precedence::DEFAULT),
+            edge(s, foo_def, Precedence::DEFAULT),
         ] {
             cd.append(&mut edges, *edge);
             assert!(cd
@@ -185,7 +186,7 @@ fn finding_simple_identity_cycle_is_detected() {
                 .unwrap()
                 .is_empty());
         }
-        cd.append(&mut edges, edge(foo_def, r, 0));
+        cd.append(&mut edges, edge(foo_def, r, Precedence::DEFAULT));
         assert_eq!(
             enum_set![Cyclicity::StrengthensPostcondition],
             cd.is_cyclic(&graph, &mut partials, db, &mut edges).unwrap()
@@ -265,13 +266,13 @@ fn finding_composite_identity_cycle_is_detected() {
         let mut cd = AppendingCycleDetector::new();
         let db = &GraphEdges;
         for edge in &[
-            edge(r, s, 0),
-            edge(r, s, 0),
-            edge(s, bar_def, 0),
-            edge(bar_def, foo_ref, 0),
-            edge(foo_ref, s, 0),
-            edge(s, foo_def, 0),
-            edge(foo_def, bar_ref, 0),
+            edge(r, s, Precedence::DEFAULT),
+            edge(r, s, Precedence::DEFAULT),
+            edge(s, bar_def, Precedence::DEFAULT),
+            edge(bar_def, foo_ref, Precedence::DEFAULT),
+            edge(foo_ref, s, Precedence::DEFAULT),
+            edge(s, foo_def, Precedence::DEFAULT),
+            edge(foo_def, bar_ref, Precedence::DEFAULT),
         ] {
             cd.append(&mut edges, *edge);
             assert!(cd
@@ -279,7 +280,7 @@ fn finding_composite_identity_cycle_is_detected() {
                 .unwrap()
                 .is_empty());
         }
-        cd.append(&mut edges, edge(bar_ref, s, 0));
+        cd.append(&mut edges, edge(bar_ref, s, Precedence::DEFAULT));
         assert_eq!(
             enum_set![Cyclicity::StrengthensPostcondition],
             cd.is_cyclic(&graph, &mut partials, db, &mut edges).unwrap()
@@ -6,12 +6,34 @@
 // ------------------------------------------------------------------------------------------------
 
 use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
 
 use maplit::hashset;
-use stack_graphs::graph::{Degree, StackGraph};
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::diff;
+use stack_graphs::graph::DiffNode;
+use stack_graphs::graph::{
+    DefinitionKind, Degree, File, FileRoots, Fingerprint, IterationOrder, Node, Precedence,
+    StackGraph,
+};
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::paths::PathResolutionError;
+use stack_graphs::serde::FileFilter;
+use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
 
 use crate::test_graphs;
 use crate::test_graphs::CreateStackGraph;
+use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
 
 #[test]
 fn can_create_symbols() {
@@ -145,20 +167,36 @@ fn can_add_and_remove_edges() {
     let h2 = graph.internal_scope(file, 1);
     let h3 = graph.internal_scope(file, 2);
     let h4 = graph.internal_scope(file, 3);
-    graph.add_edge(h1, h2, 0);
-    graph.add_edge(h1, h3, 0);
-    graph.add_edge(h1, h4, 0);
+    graph.add_edge(h1, h2, Precedence::DEFAULT);
+    graph.add_edge(h1, h3, Precedence::DEFAULT);
+    graph.add_edge(h1, h4, Precedence::DEFAULT);
     // If you try to overwrite an edge, the original edge takes precedence.
-    graph.add_edge(h1, h3, 1);
+    graph.add_edge(h1, h3, Precedence::HIGH);
     assert_eq!(
         graph
             .outgoing_edges(h1)
             .map(|edge| (edge.sink, edge.precedence))
             .collect::<HashSet<_>>(),
-        hashset! { (h2, 0), (h3, 0), (h4, 0) }
+        hashset! {
+            (h2, Precedence::DEFAULT),
+            (h3, Precedence::DEFAULT),
+            (h4, Precedence::DEFAULT),
+        }
     );
 }
 
+#[test]
+fn higher_precedences_shadow_lower_ones() {
+    assert!(Precedence::HIGH.shadows(Precedence::DEFAULT));
+    assert!(Precedence::DEFAULT.shadows(Precedence::LOW));
+    assert!(!Precedence::DEFAULT.shadows(Precedence::DEFAULT));
+    assert!(!Precedence::LOW.shadows(Precedence::HIGH));
+    assert_eq!(Precedence::DEFAULT, Precedence::DEFAULT);
+    assert_eq!(Precedence::from(1), Precedence::HIGH);
+    assert_eq!(i32::from(Precedence::LOW), -1);
+    assert_eq!(Precedence::new(7).to_string(), "7");
+}
+
 #[test]
 fn singleton_nodes_have_correct_ids() {
     let graph = StackGraph::new();
@@ -207,11 +245,702 @@ fn can_get_incoming_edges() {
     assert_eq!(Degree::Zero, graph.incoming_edge_degree(h1));
     assert_eq!(Degree::Zero, graph.incoming_edge_degree(h2));
     assert_eq!(Degree::Zero, graph.incoming_edge_degree(h3));
-    graph.add_edge(h1, h2, 0);
-    graph.add_edge(h3, h2, 0);
+    graph.add_edge(h1, h2, Precedence::DEFAULT);
+    graph.add_edge(h3, h2, Precedence::DEFAULT);
     assert_eq!(Degree::Zero, graph.incoming_edge_degree(h1));
     assert_eq!(Degree::Multiple, graph.incoming_edge_degree(h2));
     assert_eq!(Degree::Zero, graph.incoming_edge_degree(h3));
-    graph.add_edge(h3, h1, 0);
+    graph.add_edge(h3, h1, Precedence::DEFAULT);
     assert_eq!(Degree::One, graph.incoming_edge_degree(h1));
 }
+
+#[test]
+fn can_create_files_in_roots() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file_in_root("repo", "src/main.py");
+    assert_eq!(graph[file].name(), "repo://src/main.py");
+    assert_eq!(graph[file].root(), Some("repo"));
+    assert_eq!(graph[file].relative_path(), "src/main.py");
+    assert_eq!(graph.get_file_in_root("repo", "src/main.py"), Some(file));
+    assert_eq!(graph.get_file_in_root("other", "src/main.py"), None);
+
+    let plain = graph.get_or_create_file("main.py");
+    assert_eq!(graph[plain].root(), None);
+    assert_eq!(graph[plain].relative_path(), "main.py");
+
+    let uri = graph.get_or_create_file("file:///tmp/main.py");
+    assert_eq!(graph[uri].root(), None);
+    assert_eq!(graph[uri].relative_path(), "file:///tmp/main.py");
+    assert_eq!(graph.get_file_in_root("file", "/tmp/main.py"), None);
+    assert_eq!(graph.add_file_in_root("file", "/tmp/main.py"), Err(uri));
+}
+
+#[test]
+fn can_map_file_roots_to_local_paths() {
+    let mut roots = FileRoots::new();
+    roots.add_root("repo", "/home/dev/repo");
+    roots.add_root("vendor", "/home/dev/repo/vendor");
+
+    assert_eq!(
+        roots.file_name(Path::new("/home/dev/repo/src/main.py")),
+        "repo://src/main.py"
+    );
+    assert_eq!(
+        roots.file_name(Path::new("/home/dev/repo/vendor/lib.py")),
+        "vendor://lib.py"
+    );
+    assert_eq!(roots.file_name(Path::new("/tmp/x.py")), "/tmp/x.py");
+
+    let mut graph = StackGraph::new();
+    let main = graph.get_or_create_file_in_root("repo", "src/main.py");
+    let unknown = graph.get_or_create_file_in_root("unknown", "a.py");
+    let uri = graph.get_or_create_file("repo://b.py");
+    assert_eq!(
+        roots.local_path(&graph[main]),
+        PathBuf::from("/home/dev/repo/src/main.py")
+    );
+    assert_eq!(
+        roots.local_path(&graph[unknown]),
+        PathBuf::from("unknown://a.py")
+    );
+    assert_eq!(roots.local_path(&graph[uri]), PathBuf::from("repo://b.py"));
+
+    roots.add_root("repo", "/ci/checkout");
+    assert_eq!(
+        roots.local_path(&graph[main]),
+        PathBuf::from("/ci/checkout/src/main.py")
+    );
+}
+
+#[test]
+fn can_get_definition_kind_from_syntax_type() {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let symbol = graph.add_symbol("foo");
+    let id = graph.new_node_id(file);
+    let function = graph.add_pop_symbol_node(id, symbol, true).unwrap();
+    let id = graph.new_node_id(file);
+    let local = graph.add_pop_symbol_node(id, symbol, true).unwrap();
+    let id = graph.new_node_id(file);
+    let untyped = graph.add_pop_symbol_node(id, symbol, true).unwrap();
+    let function_type = graph.add_string("function");
+    graph.source_info_mut(function).syntax_type = function_type.into();
+    let local_type = graph.add_string("local");
+    graph.source_info_mut(local).syntax_type = local_type.into();
+
+    assert_eq!(
+        Some(DefinitionKind::Function),
+        graph.definition_kind(function)
+    );
+    assert_eq!(
+        Some(DefinitionKind::Other("local".to_string())),
+        graph.definition_kind(local)
+    );
+    assert_eq!(None, graph.definition_kind(untyped));
+    assert_eq!(
+        "local",
+        DefinitionKind::Other("local".to_string()).to_string()
+    );
+    assert_eq!(Ok(DefinitionKind::Class), "class".parse::<DefinitionKind>());
+}
+
+#[test]
+fn can_fingerprint_files() {
+    fn create_file(graph: &mut StackGraph, precedence: Precedence) -> Handle<File> {
+        let file = graph.add_file("test.py").unwrap();
+        let root = StackGraph::root_node();
+        let reference = create_push_symbol_node(graph, file, "foo", true);
+        let definition = create_pop_symbol_node(graph, file, "foo", true);
+        graph.add_edge(reference, root, Precedence::DEFAULT);
+        graph.add_edge(root, definition, precedence);
+        file
+    }
+
+    let mut graph = StackGraph::new();
+    let file = create_file(&mut graph, Precedence::DEFAULT);
+    let fingerprint = graph.fingerprint_for_file(file);
+    assert_eq!(
+        Ok(fingerprint),
+        fingerprint.to_string().parse::<Fingerprint>()
+    );
+
+    // The fingerprint does not depend on handles, or on the other files in the graph.
+    let mut other_graph = StackGraph::new();
+    let other_file = other_graph.add_file("other.py").unwrap();
+    let bar = create_pop_symbol_node(&mut other_graph, other_file, "bar", true);
+    other_graph.add_edge(StackGraph::root_node(), bar, Precedence::DEFAULT);
+    let file_in_other_graph = create_file(&mut other_graph, Precedence::DEFAULT);
+    assert_eq!(
+        fingerprint,
+        other_graph.fingerprint_for_file(file_in_other_graph)
+    );
+
+    let mut changed_graph = StackGraph::new();
+    let changed_file = create_file(&mut changed_graph, Precedence::HIGH);
+    let changed_fingerprint = changed_graph.fingerprint_for_file(changed_file);
+    assert_ne!(fingerprint, changed_fingerprint);
+
+    let serialized = stack_graphs::serde::StackGraph::from_graph_filter(&graph, &FileFilter(file));
+    serialized
+        .load_into_verified(&mut StackGraph::new(), "test.py", fingerprint)
+        .unwrap();
+    assert!(serialized
+        .load_into_verified(&mut StackGraph::new(), "test.py", changed_fingerprint)
+        .is_err());
+}
+
+#[test]
+fn can_stitch_paths_in_frozen_graph_on_many_threads() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "foo", true);
+    graph.add_edge(reference, definition, Precedence::DEFAULT);
+    let frozen = graph.freeze();
+
+    let threads = (0..4)
+        .map(|_| {
+            let frozen = frozen.clone();
+            std::thread::spawn(move || {
+                let mut partials = PartialPaths::new();
+                let mut count = 0;
+                ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                    &mut GraphEdgeCandidates::new(&frozen, &mut partials, None),
+                    vec![reference],
+                    StitcherConfig::default(),
+                    &NoCancellation,
+                    |_, _, _| count += 1,
+                )
+                .expect("should never be cancelled");
+                count
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        assert_eq!(1, thread.join().unwrap());
+    }
+
+    assert!(frozen.is_unique());
+    let mut graph = frozen.thaw().ok().expect("frozen graph to be unique");
+    graph.add_file("other.py").unwrap();
+}
+
+#[test]
+fn cannot_thaw_shared_frozen_graph() {
+    let frozen = StackGraph::new().freeze();
+    let clone = frozen.clone();
+    let frozen = frozen.thaw().err().expect("frozen graph to be shared");
+    drop(clone);
+    assert!(frozen.thaw().is_ok());
+}
+
+#[test]
+fn can_declare_node_aliases() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let a = create_scope_node(&mut graph, file, false);
+    let b = create_scope_node(&mut graph, file, false);
+    let c = create_scope_node(&mut graph, file, false);
+    let d = create_scope_node(&mut graph, file, false);
+    graph.add_node_alias(a, b);
+    graph.add_node_alias(c, b);
+    assert!(graph.are_aliases(a, c));
+    assert!(!graph.are_aliases(a, d));
+    assert_eq!(2, graph.node_aliases(b).len());
+    graph.add_node_alias(StackGraph::root_node(), d);
+    assert!(graph.node_aliases(d).is_empty());
+}
+
+#[test]
+fn can_stitch_paths_through_node_aliases() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let exported = create_scope_node(&mut graph, file, false);
+    let original = create_scope_node(&mut graph, file, false);
+    let definition = create_pop_symbol_node(&mut graph, file, "foo", true);
+    graph.add_edge(reference, exported, Precedence::DEFAULT);
+    graph.add_edge(original, definition, Precedence::DEFAULT);
+
+    let count_complete_paths = |graph: &StackGraph| {
+        let mut partials = PartialPaths::new();
+        let mut count = 0;
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| {
+                assert_eq!(definition, path.end_node);
+                count += 1;
+            },
+        )
+        .expect("should never be cancelled");
+        count
+    };
+    assert_eq!(0, count_complete_paths(&graph));
+    graph.add_node_alias(exported, original);
+    assert_eq!(1, count_complete_paths(&graph));
+}
+
+#[test]
+fn can_diff_file_subgraphs() {
+    let build = |definitions: &[&str], references: &[&str]| {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test.py").unwrap();
+        let root = StackGraph::root_node();
+        for symbol in definitions {
+            let node = create_pop_symbol_node(&mut graph, file, symbol, true);
+            graph.add_edge(root, node, Precedence::DEFAULT);
+        }
+        for symbol in references {
+            let node = create_push_symbol_node(&mut graph, file, symbol, true);
+            graph.add_edge(node, root, Precedence::DEFAULT);
+        }
+        graph
+    };
+    let old = build(&["foo", "bar"], &["baz"]);
+    let new = build(&["foo", "qux", "quux"], &["baz"]);
+
+    assert!(diff(&old, &old, "test.py").is_empty());
+
+    let result = diff(&old, &new, "test.py");
+    let symbols = |nodes: &[DiffNode]| nodes.iter().map(|n| n.symbol.clone()).collect::<Vec<_>>();
+    assert_eq!(vec!["qux", "quux"], symbols(&result.added_definitions));
+    assert_eq!(vec!["bar"], symbols(&result.removed_definitions));
+    assert!(result.added_references.is_empty());
+    assert!(result.removed_references.is_empty());
+    // edges are identified by node IDs, which shift when nodes are added
+    assert_eq!(2, result.added_edges.len());
+    assert_eq!(1, result.removed_edges.len());
+    assert_eq!("[root]", result.removed_edges[0].sink);
+
+    let result = diff(&old, &StackGraph::new(), "test.py");
+    assert_eq!(2, result.removed_definitions.len());
+    assert_eq!(1, result.removed_references.len());
+    assert!(result.added_definitions.is_empty());
+}
+
+#[test]
+fn can_stitch_paths_through_conditional_root_edges() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let main_file = graph.add_file("Main.java").unwrap();
+    let conditional = create_push_symbol_node(&mut graph, main_file, "foo", true);
+    let unconditional = create_push_symbol_node(&mut graph, main_file, "foo", true);
+    graph.add_edge(conditional, root, Precedence::DEFAULT);
+    graph.add_edge(unconditional, root, Precedence::DEFAULT);
+    let a_file = graph.add_file("a/Foo.java").unwrap();
+    let a_definition = create_pop_symbol_node(&mut graph, a_file, "foo", true);
+    graph.add_edge(root, a_definition, Precedence::DEFAULT);
+    let b_file = graph.add_file("b/Foo.java").unwrap();
+    let b_definition = create_pop_symbol_node(&mut graph, b_file, "foo", true);
+    graph.add_edge(root, b_definition, Precedence::DEFAULT);
+    let builtins_file = graph.add_file("builtins.java").unwrap();
+    let builtin_definition = create_pop_symbol_node(&mut graph, builtins_file, "foo", true);
+    graph.add_edge(root, builtin_definition, Precedence::DEFAULT);
+
+    let a = graph.add_string("a");
+    let b = graph.add_string("b");
+    graph.set_edge_condition(conditional, root, a);
+    graph.set_edge_condition(root, a_definition, a);
+    graph.set_edge_condition(root, b_definition, b);
+    assert_eq!(Some(a), graph.edge_condition(root, a_definition));
+    assert_eq!(None, graph.edge_condition(root, builtin_definition));
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut find_definitions = |reference: Handle<Node>, use_database: bool| {
+        let mut definitions = Vec::new();
+        let visit = |_: &StackGraph, _: &mut PartialPaths, path: &PartialPath| {
+            definitions.push(path.end_node);
+        };
+        let result = if use_database {
+            ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+                vec![reference],
+                StitcherConfig::default(),
+                &NoCancellation,
+                visit,
+            )
+        } else {
+            ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+                vec![reference],
+                StitcherConfig::default(),
+                &NoCancellation,
+                visit,
+            )
+        };
+        result.expect("should never be cancelled");
+        definitions.sort();
+        definitions
+    };
+    for use_database in [false, true] {
+        let mut expected = vec![a_definition, builtin_definition];
+        expected.sort();
+        assert_eq!(expected, find_definitions(conditional, use_database));
+        assert_eq!(
+            vec![builtin_definition],
+            find_definitions(unconditional, use_database)
+        );
+    }
+
+    // Conditions can only be attached to edges to or from the root node.
+    graph.add_edge(conditional, unconditional, Precedence::DEFAULT);
+    graph.set_edge_condition(conditional, unconditional, a);
+    assert_eq!(None, graph.edge_condition(conditional, unconditional));
+}
+
+#[test]
+fn can_compute_confidence_of_stitched_paths() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let main_file = graph.add_file("main.py").unwrap();
+    let guessed = create_push_symbol_node(&mut graph, main_file, "foo", true);
+    let certain = create_push_symbol_node(&mut graph, main_file, "bar", true);
+    graph.add_edge(guessed, root, Precedence::DEFAULT);
+    graph.add_edge(certain, root, Precedence::DEFAULT);
+    let lib_file = graph.add_file("lib.py").unwrap();
+    let foo_definition = create_pop_symbol_node(&mut graph, lib_file, "foo", true);
+    let bar_definition = create_pop_symbol_node(&mut graph, lib_file, "bar", true);
+    graph.add_edge(root, foo_definition, Precedence::DEFAULT);
+    graph.add_edge(root, bar_definition, Precedence::DEFAULT);
+
+    graph.set_edge_confidence(guessed, root, 50);
+    graph.set_edge_confidence(root, foo_definition, 80);
+    assert_eq!(Some(50), graph.edge_confidence(guessed, root));
+    assert_eq!(Some(100), graph.edge_confidence(certain, root));
+    assert_eq!(None, graph.edge_confidence(root, guessed));
+    graph.set_edge_confidence(root, bar_definition, 200);
+    assert_eq!(Some(100), graph.edge_confidence(root, bar_definition));
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut confidences = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![guessed, certain],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            confidences.push((path.end_node, path.confidence(graph, partials).unwrap()));
+        },
+    )
+    .expect("should never be cancelled");
+    confidences.sort_by_key(|(node, _)| *node);
+    assert_eq!(
+        vec![(foo_definition, Some(0.4)), (bar_definition, None)],
+        confidences
+    );
+}
+
+#[test]
+fn confidence_of_paths_through_unloaded_nodes_is_unknown() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("lib.py").unwrap();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+    let mut partials = PartialPaths::new();
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo, bar]).unwrap();
+    assert_eq!(None, path.confidence(&graph, &mut partials).unwrap());
+
+    // the nodes of the path are not loaded in an empty graph
+    let other = StackGraph::new();
+    assert!(matches!(
+        path.confidence(&other, &mut partials),
+        Err(PathResolutionError::UnknownNode)
+    ));
+}
+
+#[test]
+fn can_mark_stitched_paths_as_speculative() {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let main_file = graph.add_file("main.py").unwrap();
+    let guessed = create_push_symbol_node(&mut graph, main_file, "foo", true);
+    let certain = create_push_symbol_node(&mut graph, main_file, "bar", true);
+    graph.add_edge(guessed, root, Precedence::DEFAULT);
+    graph.add_edge(certain, root, Precedence::DEFAULT);
+    let lib_file = graph.add_file("lib.py").unwrap();
+    let dynamic_exports = create_scope_node(&mut graph, lib_file, false);
+    let foo_definition = create_pop_symbol_node(&mut graph, lib_file, "foo", true);
+    let bar_definition = create_pop_symbol_node(&mut graph, lib_file, "bar", true);
+    graph.add_edge(root, dynamic_exports, Precedence::DEFAULT);
+    graph.add_edge(dynamic_exports, foo_definition, Precedence::DEFAULT);
+    graph.add_edge(root, bar_definition, Precedence::DEFAULT);
+
+    graph.mark_speculative(dynamic_exports);
+    graph.mark_speculative(root);
+    assert!(graph.is_speculative(dynamic_exports));
+    assert!(!graph.is_speculative(foo_definition));
+    assert!(!graph.is_speculative(root));
+
+    // the flag is preserved when the graph is serialized
+    let mut loaded = StackGraph::new();
+    stack_graphs::serde::StackGraph::from_graph(&graph)
+        .load_into(&mut loaded)
+        .unwrap();
+    let loaded_lib_file = loaded.get_file("lib.py").unwrap();
+    assert_eq!(
+        1,
+        loaded
+            .nodes_for_file(loaded_lib_file)
+            .filter(|node| loaded.is_speculative(*node))
+            .count()
+    );
+    assert_eq!(
+        graph.fingerprint_for_file(lib_file),
+        loaded.fingerprint_for_file(loaded_lib_file)
+    );
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+
+    let mut speculative = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![guessed, certain],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            speculative.push((path.end_node, path.is_speculative(graph, partials)));
+        },
+    )
+    .expect("should never be cancelled");
+    speculative.sort_by_key(|(node, _)| *node);
+    assert_eq!(
+        vec![(foo_definition, true), (bar_definition, false)],
+        speculative
+    );
+}
+
+#[test]
+fn can_iterate_in_sorted_order() {
+    let create_graph = |file_names: &[&str]| {
+        let mut graph = StackGraph::new();
+        for file_name in file_names {
+            let file = graph.add_file(file_name).unwrap();
+            let definition = create_pop_symbol_node(&mut graph, file, file_name, true);
+            graph.add_edge(StackGraph::root_node(), definition, Precedence::DEFAULT);
+        }
+        graph
+    };
+    let file_names = |graph: &StackGraph| {
+        graph
+            .iter_files()
+            .map(|file| graph[file].name().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let mut graph = create_graph(&["b.py", "a.py"]);
+    assert_eq!(IterationOrder::Insertion, graph.iteration_order());
+    assert_eq!(vec!["b.py", "a.py"], file_names(&graph));
+    graph.set_iteration_order(IterationOrder::Sorted);
+    assert_eq!(vec!["a.py", "b.py"], file_names(&graph));
+    let nodes = graph
+        .iter_nodes()
+        .map(|node| graph[node].id().display(&graph).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["[root]", "[jump]", "a.py(0)", "b.py(0)"], nodes);
+
+    let mut other = create_graph(&["a.py", "b.py"]);
+    other.set_iteration_order(IterationOrder::Sorted);
+    assert_eq!(graph.to_serializable(), other.to_serializable());
+}
+
+#[test]
+fn can_map_handles_to_stable_ids() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+    let serialized = graph.to_serializable().with_node_handles(&graph);
+
+    let id = serialized.node_handles.stable_id(bar.as_u32()).unwrap();
+    assert_eq!(Some("test.py"), id.file.as_deref());
+    assert_eq!(graph[bar].id().local_id(), id.local_id);
+    let id = serialized
+        .node_handles
+        .stable_id(StackGraph::root_node().as_u32())
+        .unwrap();
+    assert_eq!(None, id.file);
+
+    // Loading the graph after another file renumbers the handles, but not the stable IDs.
+    let mut other = StackGraph::new();
+    let other_file = other.add_file("other.py").unwrap();
+    create_pop_symbol_node(&mut other, other_file, "baz", true);
+    serialized.load_into(&mut other).unwrap();
+    for (handle, symbol) in [(foo, "foo"), (bar, "bar")] {
+        let id = serialized.node_handles.stable_id(handle.as_u32()).unwrap();
+        let other_handle = other
+            .node_handle_for_stable_id(id.file.as_deref(), id.local_id)
+            .unwrap();
+        assert_ne!(handle, other_handle);
+        assert_eq!(symbol, &other[other[other_handle].symbol().unwrap()]);
+    }
+    assert_eq!(None, other.node_handle_for_stable_id(Some("missing.py"), 0));
+    assert_eq!(
+        Some(StackGraph::root_node()),
+        other.node_handle_for_stable_id(None, 1)
+    );
+}
+
+#[test]
+fn can_compact_graph_and_database() {
+    let mut graph = StackGraph::new();
+    let kept = graph.add_file("kept.py").unwrap();
+    let removed = graph.add_file("removed.py").unwrap();
+    let root = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, kept, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, removed, "bar", true);
+    let reference = create_push_symbol_node(&mut graph, removed, "foo", true);
+    graph.add_edge(root, foo, Precedence::DEFAULT);
+    graph.add_edge(root, bar, Precedence::DEFAULT);
+    graph.add_edge(reference, root, Precedence::DEFAULT);
+
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in [kept, removed] {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |graph, partials, path| {
+                db.add_partial_path(graph, partials, path.clone());
+            },
+        )
+        .expect("should never be cancelled");
+    }
+    let kept_paths = db
+        .iter_partial_paths()
+        .filter(|path| {
+            graph[db[*path].start_node].is_in_file(kept)
+                || graph[db[*path].end_node].is_in_file(kept)
+        })
+        .count();
+
+    let remap = db.compact_with_graph(&mut graph, &mut partials, |graph, file| {
+        graph[*file].name() == "kept.py"
+    });
+    assert_eq!(
+        vec!["kept.py"],
+        graph
+            .iter_files()
+            .map(|f| graph[f].name())
+            .collect::<Vec<_>>()
+    );
+    assert!(remap.file(removed).is_none());
+    assert!(remap.node(bar).is_none());
+    assert!(remap.node(reference).is_none());
+    let foo = remap.node(foo).unwrap();
+    assert_eq!("foo", &graph[graph[foo].symbol().unwrap()]);
+    assert_eq!(
+        vec!["foo"],
+        graph.iter_symbols().map(|s| &graph[s]).collect::<Vec<_>>()
+    );
+    assert_eq!(kept_paths, db.iter_partial_paths().count());
+}
+
+#[test]
+fn can_presize_graph_and_report_interner_stats() {
+    let mut graph = StackGraph::with_capacity(10, 1000, 100);
+    let file = graph.add_file("test.py").unwrap();
+    for i in 0..100 {
+        create_pop_symbol_node(&mut graph, file, &format!("symbol{}", i), true);
+    }
+    graph.add_symbol("symbol0");
+
+    let stats = graph.interner_stats();
+    assert_eq!(100, stats.symbols);
+    assert_eq!(1, stats.files);
+    assert_eq!(
+        "test.py".len()
+            + (0..100)
+                .map(|i| format!("symbol{}", i).len())
+                .sum::<usize>(),
+        stats.bytes
+    );
+    assert!(stats.bytes <= stats.allocated_bytes);
+    assert_eq!(1, stats.buffers);
+    assert!(stats.symbol_load_factor > 0.0 && stats.symbol_load_factor <= 1.0);
+}
+
+#[test]
+fn can_remove_synthetic_nodes_after_query() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test.py").unwrap();
+    let scope = create_scope_node(&mut graph, file, false);
+    let next_id = graph.new_node_id(file);
+    let node_count = graph.iter_nodes().count();
+
+    {
+        let mut graph = graph.synthetic_scope();
+        let id = graph.new_synthetic_node_id(file);
+        assert!(id.is_synthetic());
+        assert!(!graph[scope].id().is_synthetic());
+        let symbol = graph.add_symbol("x");
+        let reference = graph.add_push_symbol_node(id, symbol, true).unwrap();
+        graph.add_edge(reference, scope, Precedence::DEFAULT);
+        graph.add_edge(scope, reference, Precedence::DEFAULT);
+        graph.add_node_alias(scope, reference);
+        assert_eq!(Some(reference), graph.node_for_id(id));
+        assert_eq!(2, graph.nodes_for_file(file).count());
+        assert_eq!(Degree::One, graph.incoming_edge_degree(scope));
+        // Builder-assigned IDs are not affected by synthetic nodes.
+        assert_eq!(next_id, graph.new_node_id(file));
+    }
+
+    assert_eq!(node_count, graph.iter_nodes().count());
+    assert_eq!(1, graph.nodes_for_file(file).count());
+    assert_eq!(0, graph.outgoing_edges(scope).count());
+    assert_eq!(Degree::Zero, graph.incoming_edge_degree(scope));
+    assert!(graph.node_aliases(scope).is_empty());
+    let id = graph.new_synthetic_node_id(file);
+    assert_eq!(None, graph.node_for_id(id));
+    assert_eq!(next_id, graph.new_node_id(file));
+}
@@ -0,0 +1,124 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeSet;
+
+use maplit::btreeset;
+use pretty_assertions::assert_eq;
+use stack_graphs::graph::DefinitionKind;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::lsif::LsifError;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+fn resolve_all_references(graph: &StackGraph) -> BTreeSet<String> {
+    let mut partials = PartialPaths::new();
+    let mut results = BTreeSet::new();
+    let references = graph
+        .iter_nodes()
+        .filter(|handle| graph[*handle].is_reference());
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        references,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |graph, partials, path| {
+            results.insert(path.display(graph, partials).to_string());
+        },
+    )
+    .expect("should never be cancelled");
+    results
+}
+
+#[test]
+fn can_import_lsif_definitions_and_references() {
+    let dump = r#"
+{"id":1,"type":"vertex","label":"metaData","version":"0.6.0","positionEncoding":"utf-16"}
+{"id":2,"type":"vertex","label":"document","uri":"file:///src/a.ts","languageId":"typescript"}
+{"id":3,"type":"vertex","label":"document","uri":"file:///src/b%20c.ts","languageId":"typescript"}
+{"id":4,"type":"vertex","label":"range","start":{"line":0,"character":9},"end":{"line":0,"character":12},"tag":{"type":"definition","text":"foo","kind":12,"fullRange":{"start":{"line":0,"character":0},"end":{"line":2,"character":1}}}}
+{"id":5,"type":"vertex","label":"resultSet"}
+{"id":6,"type":"edge","label":"next","outV":4,"inV":5}
+{"id":7,"type":"vertex","label":"definitionResult"}
+{"id":8,"type":"edge","label":"textDocument/definition","outV":5,"inV":7}
+{"id":9,"type":"edge","label":"item","outV":7,"inVs":[4],"document":2}
+{"id":10,"type":"vertex","label":"range","start":{"line":1,"character":0},"end":{"line":1,"character":3}}
+{"id":11,"type":"edge","label":"next","outV":10,"inV":5}
+{"id":12,"type":"vertex","label":"range","start":{"line":2,"character":0},"end":{"line":2,"character":3}}
+{"id":13,"type":"edge","label":"contains","outV":2,"inVs":[4]}
+{"id":14,"type":"edge","label":"contains","outV":3,"inVs":[10,12]}
+"#;
+    let mut graph = StackGraph::new();
+    let import = graph.add_from_lsif(dump.as_bytes()).unwrap();
+    assert_eq!(2, import.files.len());
+    assert_eq!(1, import.definitions);
+    assert_eq!(1, import.references);
+    assert_eq!(1, import.skipped_ranges);
+    assert_eq!("/src/b c.ts", graph[import.files[1]].name());
+
+    let definition = graph
+        .iter_nodes()
+        .find(|handle| graph[*handle].is_definition())
+        .unwrap();
+    let source_info = graph.source_info(definition).unwrap();
+    assert_eq!(9, source_info.span.start.column.utf16_offset);
+    assert_eq!(2, source_info.definiens_span.end.line);
+    assert_eq!(
+        Some(DefinitionKind::Function),
+        graph.definition_kind(definition)
+    );
+
+    assert_eq!(
+        btreeset! {
+            "<> () [/src/b c.ts(0) reference /src/a.ts:1:10] -> [/src/a.ts(0) definition /src/a.ts:1:10] <> ()"
+                .to_string()
+        },
+        resolve_all_references(&graph)
+    );
+}
+
+#[test]
+fn lsif_monikers_resolve_across_dumps() {
+    let exporting_dump = r#"[
+{"id":"d","type":"vertex","label":"document","uri":"file:///lib/index.ts"},
+{"id":"r","type":"vertex","label":"range","start":{"line":0,"character":0},"end":{"line":0,"character":3},"tag":{"type":"definition"}},
+{"id":"m","type":"vertex","label":"moniker","scheme":"npm","identifier":"lib:foo","kind":"export"},
+{"id":"e1","type":"edge","label":"moniker","outV":"r","inV":"m"},
+{"id":"e2","type":"edge","label":"contains","outV":"d","inVs":["r"]}
+]"#;
+    let importing_dump = r#"
+{"id":1,"type":"vertex","label":"document","uri":"file:///app/main.ts"}
+{"id":2,"type":"vertex","label":"range","start":{"line":4,"character":2},"end":{"line":4,"character":5}}
+{"id":3,"type":"vertex","label":"resultSet"}
+{"id":4,"type":"edge","label":"next","outV":2,"inV":3}
+{"id":5,"type":"vertex","label":"moniker","scheme":"npm","identifier":"lib:foo","kind":"import"}
+{"id":6,"type":"edge","label":"moniker","outV":3,"inV":5}
+{"id":7,"type":"edge","label":"contains","outV":1,"inVs":[2]}
+"#;
+    let mut graph = StackGraph::new();
+    graph.add_from_lsif(exporting_dump.as_bytes()).unwrap();
+    graph.add_from_lsif(importing_dump.as_bytes()).unwrap();
+    assert_eq!(
+        btreeset! {
+            "<> () [/app/main.ts(0) reference lib:foo] -> [/lib/index.ts(1) definition lib:foo] <> ()"
+                .to_string()
+        },
+        resolve_all_references(&graph)
+    );
+
+    assert!(matches!(
+        graph.add_from_lsif(importing_dump.as_bytes()),
+        Err(LsifError::FileAlreadyPresent(_))
+    ));
+    assert!(matches!(
+        graph.add_from_lsif(r#"{"id":1,"type":"vertex"}"#.as_bytes()),
+        Err(LsifError::InvalidElement(_))
+    ));
+}
@@ -10,7 +10,10 @@
 
 pub mod test_graphs;
 
+#[cfg(feature = "storage")]
+mod analysis;
 mod arena;
+mod assert;
 mod c;
 mod can_create_graph;
 mod can_find_local_nodes;
@@ -21,7 +24,15 @@ mod can_jump_to_definition;
 mod can_jump_to_definition_with_forward_partial_path_stitching;
 mod cycles;
 mod graph;
+#[cfg(feature = "lsif")]
+mod lsif;
+mod neo4j;
+mod overlay;
+mod parallel;
 mod partial;
+mod query;
+#[cfg(feature = "storage")]
+mod resolve;
 #[cfg(feature = "serde")]
 mod serde;
 mod stats;
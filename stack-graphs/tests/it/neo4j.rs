@@ -0,0 +1,71 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::serde::NoFilter;
+
+use crate::util::create_edge;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn create_graph() -> (StackGraph, Handle<Node>, Handle<Node>) {
+    let mut graph = StackGraph::new();
+    let root = StackGraph::root_node();
+    let file = graph.add_file("a \"quoted\" file").unwrap();
+    let definition = create_pop_symbol_node(&mut graph, file, "x", true);
+    let reference = create_push_symbol_node(&mut graph, file, "x", true);
+    create_edge(&mut graph, (root, definition));
+    create_edge(&mut graph, (reference, root));
+    (graph, reference, definition)
+}
+
+#[test]
+fn can_export_cypher() {
+    let (graph, reference, definition) = create_graph();
+    let cypher = graph.to_cypher_string(&[(reference, definition)], &NoFilter);
+    let lines = cypher.lines().collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "CREATE INDEX stack_graph_node_id IF NOT EXISTS FOR (n:StackGraphNode) ON (n.id);",
+            "CREATE (:StackGraphNode:Root {id: \"[root]\"});",
+            "CREATE (:StackGraphNode:JumpToScope {id: \"[jump]\"});",
+            "CREATE (:StackGraphNode:PopSymbol {id: \"a \\\"quoted\\\" file(0)\", file: \"a \\\"quoted\\\" file\", local_id: 0, symbol: \"x\", is_definition: true, is_reference: false});",
+            "CREATE (:StackGraphNode:PushSymbol {id: \"a \\\"quoted\\\" file(1)\", file: \"a \\\"quoted\\\" file\", local_id: 1, symbol: \"x\", is_definition: false, is_reference: true});",
+            "MATCH (source:StackGraphNode {id: \"[root]\"}), (sink:StackGraphNode {id: \"a \\\"quoted\\\" file(0)\"}) CREATE (source)-[:EDGE {precedence: 0}]->(sink);",
+            "MATCH (source:StackGraphNode {id: \"a \\\"quoted\\\" file(1)\"}), (sink:StackGraphNode {id: \"[root]\"}) CREATE (source)-[:EDGE {precedence: 0}]->(sink);",
+            "MATCH (reference:StackGraphNode {id: \"a \\\"quoted\\\" file(1)\"}), (definition:StackGraphNode {id: \"a \\\"quoted\\\" file(0)\"}) CREATE (reference)-[:RESOLVES_TO]->(definition);",
+        ],
+        lines
+    );
+}
+
+#[test]
+fn can_export_neo4j_csv() {
+    let (graph, reference, definition) = create_graph();
+    let csv = graph.to_neo4j_csv(&[(reference, definition)], &NoFilter);
+    assert_eq!(
+        vec![
+            "id:ID,:LABEL,file:string,local_id:int,symbol:string,scope:string,is_definition:boolean,is_reference:boolean,is_exported:boolean,syntax_type:string,start_line:int,start_column:int,end_line:int,end_column:int",
+            "\"[root]\",StackGraphNode;Root,,,,,,,,,,,,",
+            "\"[jump]\",StackGraphNode;JumpToScope,,,,,,,,,,,,",
+            "\"a \"\"quoted\"\" file(0)\",StackGraphNode;PopSymbol,\"a \"\"quoted\"\" file\",0,\"x\",,true,false,,,,,,",
+            "\"a \"\"quoted\"\" file(1)\",StackGraphNode;PushSymbol,\"a \"\"quoted\"\" file\",1,\"x\",,false,true,,,,,,",
+        ],
+        csv.nodes.lines().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            ":START_ID,:END_ID,:TYPE,precedence:int",
+            "\"[root]\",\"a \"\"quoted\"\" file(0)\",EDGE,0",
+            "\"a \"\"quoted\"\" file(1)\",\"[root]\",EDGE,0",
+            "\"a \"\"quoted\"\" file(1)\",\"a \"\"quoted\"\" file(0)\",RESOLVES_TO,",
+        ],
+        csv.relationships.lines().collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,58 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::graph::Precedence;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::overlay::GraphOverlay;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+#[test]
+fn can_stitch_paths_from_overlay_into_base_graph() {
+    let mut base = StackGraph::new();
+    let lib = base.add_file("lib.py").unwrap();
+    let foo = create_pop_symbol_node(&mut base, lib, "foo", true);
+    base.add_edge(StackGraph::root_node(), foo, Precedence::DEFAULT);
+    let other = base.add_file("other.py").unwrap();
+    let bar = create_pop_symbol_node(&mut base, other, "bar", true);
+    base.add_edge(StackGraph::root_node(), bar, Precedence::DEFAULT);
+    let main = base.add_file("main.py").unwrap();
+    let old_reference = create_push_symbol_node(&mut base, main, "bar", true);
+    base.add_edge(old_reference, StackGraph::root_node(), Precedence::DEFAULT);
+    let base_node_count = base.iter_nodes().count();
+
+    // The unsaved buffer of main.py refers to foo instead of bar.
+    let mut overlay = GraphOverlay::new(&base);
+    let file = overlay.graph_mut().add_file("main.py").unwrap();
+    let reference = create_push_symbol_node(overlay.graph_mut(), file, "foo", true);
+    overlay
+        .graph_mut()
+        .add_edge(reference, StackGraph::root_node(), Precedence::DEFAULT);
+    assert!(overlay.is_shadowed(main));
+
+    let mut definitions = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut overlay,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| definitions.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+
+    assert_eq!(1, definitions.len());
+    assert_eq!(Some(foo), overlay.base_node(definitions[0]));
+    assert_eq!(None, overlay.base_node(reference));
+    // Only the files that the query visits are copied, and the base graph is not modified.
+    assert!(overlay.graph().get_file("lib.py").is_some());
+    assert!(overlay.graph().get_file("other.py").is_none());
+    assert_eq!(base_node_count, base.iter_nodes().count());
+}
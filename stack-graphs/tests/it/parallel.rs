@@ -0,0 +1,175 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use std::collections::BTreeMap;
+
+use pretty_assertions::assert_eq;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::NodeID;
+use stack_graphs::graph::Precedence;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::parallel::BuildError;
+use stack_graphs::parallel::ParallelBuilder;
+use stack_graphs::parallel::WorkQueue;
+use stack_graphs::CancellationError;
+use stack_graphs::CancellationFlag;
+use stack_graphs::NoCancellation;
+
+fn build_definition(name: &String, graph: &mut StackGraph) -> Result<Handle<File>, String> {
+    if name.is_empty() {
+        return Err("missing name".to_string());
+    }
+    let file = graph.get_or_create_file(&format!("{}.py", name));
+    let symbol = graph.add_symbol(name);
+    let definition = graph
+        .add_pop_symbol_node(NodeID::new_in_file(file, 0), symbol, true)
+        .unwrap();
+    graph.add_edge(StackGraph::root_node(), definition, Precedence::DEFAULT);
+    Ok(file)
+}
+
+#[test]
+fn every_input_gets_one_result() {
+    let names = (0..20)
+        .map(|i| {
+            if i == 7 {
+                String::new()
+            } else {
+                format!("x{}", i)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut results = BTreeMap::new();
+    ParallelBuilder::new()
+        .with_jobs(4)
+        .with_queue_size(1)
+        .run(
+            &names,
+            build_definition,
+            &NoCancellation,
+            |index, result| {
+                let summary = match result {
+                    Ok(built) => {
+                        assert_eq!(
+                            format!("{}.py", names[index]),
+                            built.graph[built.file].name()
+                        );
+                        Ok(built.paths.len())
+                    }
+                    Err(BuildError::Build(err)) => Err(err),
+                    Err(BuildError::Cancelled(err)) => panic!("unexpected cancellation: {}", err),
+                };
+                assert!(results.insert(index, summary).is_none());
+                Ok::<_, ()>(())
+            },
+        )
+        .unwrap();
+    assert_eq!(names.len(), results.len());
+    for (index, summary) in results {
+        if index == 7 {
+            assert_eq!(Err("missing name".to_string()), summary);
+        } else {
+            assert_eq!(Ok(1), summary);
+        }
+    }
+}
+
+#[test]
+fn consumer_errors_stop_the_workers() {
+    let names = (0..100).map(|i| format!("x{}", i)).collect::<Vec<_>>();
+    let mut consumed = 0;
+    let result = ParallelBuilder::new().with_jobs(4).with_queue_size(0).run(
+        &names,
+        build_definition,
+        &NoCancellation,
+        |_, _| {
+            consumed += 1;
+            if consumed == 3 {
+                return Err("stop");
+            }
+            Ok(())
+        },
+    );
+    assert_eq!(Err("stop"), result);
+    assert_eq!(3, consumed);
+}
+
+struct Cancelled;
+
+impl CancellationFlag for Cancelled {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        Err(CancellationError(at))
+    }
+}
+
+#[test]
+fn cancelled_inputs_are_not_built() {
+    let names = (0..10).map(|i| format!("x{}", i)).collect::<Vec<_>>();
+    let mut cancelled = 0;
+    ParallelBuilder::new()
+        .with_jobs(2)
+        .run(
+            &names,
+            |_: &String, _: &mut StackGraph| -> Result<Handle<File>, String> {
+                panic!("cancelled inputs should not be built")
+            },
+            &Cancelled,
+            |_, result| {
+                assert!(matches!(result, Err(BuildError::Cancelled(_))));
+                cancelled += 1;
+                Ok::<_, ()>(())
+            },
+        )
+        .unwrap();
+    assert_eq!(names.len(), cancelled);
+}
+
+#[test]
+fn prioritized_inputs_jump_the_queue() {
+    let queue = ["a", "b", "c"].iter().copied().collect::<WorkQueue<_>>();
+    queue.prioritize("c");
+    queue.prioritize("d");
+    queue.push("e");
+    assert_eq!(5, queue.len());
+    let order = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+    assert_eq!(vec!["d", "c", "a", "b", "e"], order);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn prioritized_inputs_replace_matching_queued_inputs() {
+    let queue = [("a", 1), ("b", 1), ("c", 1), ("b", 2)]
+        .iter()
+        .copied()
+        .collect::<WorkQueue<_>>();
+    queue.prioritize_matching(("b", 3), |x, y| x.0 == y.0);
+    queue.prioritize_matching(("d", 3), |x, y| x.0 == y.0);
+    let order = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+    assert_eq!(vec![("d", 3), ("b", 1), ("a", 1), ("c", 1)], order);
+}
+
+#[test]
+fn inputs_prioritized_while_running_are_built_next() {
+    let queue = (0..10).map(|i| format!("x{}", i)).collect::<WorkQueue<_>>();
+    let mut order = Vec::new();
+    ParallelBuilder::new()
+        .with_jobs(1)
+        .run_queue(&queue, build_definition, &NoCancellation, |name, result| {
+            assert!(result.is_ok());
+            if name == "x1" {
+                queue.prioritize("x8".to_string());
+            }
+            order.push(name);
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+    assert_eq!(
+        vec!["x0", "x1", "x8", "x2", "x3", "x4", "x5", "x6", "x7", "x9"],
+        order
+    );
+}
@@ -902,6 +902,50 @@ fn can_append_partial_paths_without_precondition_variables() -> Result<(), PathR
     Ok(())
 }
 
+#[test]
+fn can_inspect_bindings_of_concatenated_partial_paths() -> Result<(), PathResolutionError> {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").expect("");
+    let scope0 = create_scope_node(&mut graph, file, false);
+    let foo_ref = create_push_symbol_node(&mut graph, file, "foo", false);
+    let foo_def = create_pop_symbol_node(&mut graph, file, "foo", false);
+    let bar_ref = create_push_symbol_node(&mut graph, file, "bar", false);
+
+    let mut ps = PartialPaths::new();
+    let l = create_partial_path_and_edges(&mut graph, &mut ps, &[foo_ref, scope0]).expect("");
+    let mut r =
+        create_partial_path_and_edges(&mut graph, &mut ps, &[scope0, foo_def, bar_ref]).expect("");
+    r.ensure_no_overlapping_variables(&mut ps, &l);
+
+    let left = l.display(&graph, &mut ps).to_string();
+    let result = l.concatenate_with_bindings(&graph, &mut ps, &r)?;
+    assert_eq!(left, l.display(&graph, &mut ps).to_string());
+    assert_eq!(
+        "<%1> ($1) [test(1) push foo] -> [test(3) push bar] <bar,%1> ($1)",
+        result.path.display(&graph, &mut ps).to_string()
+    );
+
+    let mut concatenated = l.clone();
+    concatenated.concatenate(&graph, &mut ps, &r)?;
+    assert!(concatenated.equals(&mut ps, &result.path));
+
+    assert!(!result.symbol_bindings.is_empty());
+    assert_eq!(1, result.symbol_bindings.iter().count());
+    assert!(!result.scope_bindings.is_empty());
+    assert_eq!(1, result.scope_bindings.iter().count());
+
+    let mut substituted = r.clone();
+    substituted.apply_partial_bindings(&mut ps, &result.symbol_bindings, &result.scope_bindings)?;
+    assert!(substituted
+        .symbol_stack_postcondition
+        .equals(&mut ps, result.path.symbol_stack_postcondition));
+    assert!(substituted
+        .scope_stack_postcondition
+        .equals(&mut ps, result.path.scope_stack_postcondition));
+
+    Ok(())
+}
+
 #[test]
 fn can_resolve_to_node() -> Result<(), PathResolutionError> {
     let mut graph = StackGraph::new();
@@ -964,3 +1008,24 @@ fn can_resolve_to_node() -> Result<(), PathResolutionError> {
 
     Ok(())
 }
+
+#[test]
+fn can_explain_partial_paths() -> Result<(), PathResolutionError> {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let import = create_pop_symbol_node(&mut graph, file, "foo", false);
+    let alias = create_push_symbol_node(&mut graph, file, "bar", false);
+    let definition = create_pop_symbol_node(&mut graph, file, "bar", true);
+    let path = create_partial_path_and_edges(
+        &mut graph,
+        &mut partials,
+        &[reference, scope, import, alias, definition],
+    )?;
+
+    assert_eq!(vec![import, alias], path.explain(&graph, &mut partials));
+    Ok(())
+}
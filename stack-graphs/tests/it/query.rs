@@ -0,0 +1,145 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::query::DefinitionMatch;
+use stack_graphs::query::Query;
+use stack_graphs::query::QueryError;
+use stack_graphs::query::QueryResults;
+use stack_graphs::query::ReferenceMatch;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
+
+use crate::util::create_edge;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+
+fn create_definition(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    symbol: &str,
+    syntax_type: &str,
+) -> Handle<Node> {
+    let root = StackGraph::root_node();
+    let definition = create_pop_symbol_node(graph, file, symbol, true);
+    let syntax_type = graph.add_string(syntax_type);
+    graph.source_info_mut(definition).syntax_type = syntax_type.into();
+    create_edge(graph, (root, definition));
+    definition
+}
+
+fn create_reference(graph: &mut StackGraph, file: Handle<File>, symbol: &str) -> Handle<Node> {
+    let root = StackGraph::root_node();
+    let reference = create_push_symbol_node(graph, file, symbol, true);
+    create_edge(graph, (reference, root));
+    reference
+}
+
+fn execute(graph: &StackGraph, query: &str) -> QueryResults {
+    let mut partials = PartialPaths::new();
+    Query::parse(query)
+        .unwrap()
+        .execute(
+            graph,
+            &mut partials,
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap()
+}
+
+#[test]
+fn can_query_definitions_and_references() {
+    let mut graph = StackGraph::new();
+    let lib = graph.add_file("src/lib.py").unwrap();
+    let get_a = create_definition(&mut graph, lib, "get_a", "function");
+    let get_b = create_definition(&mut graph, lib, "get_b", "function");
+    let get_c = create_definition(&mut graph, lib, "get_c", "variable");
+    let test = graph.add_file("tests/test.py").unwrap();
+    let ref_a1 = create_reference(&mut graph, test, "get_a");
+    let ref_a2 = create_reference(&mut graph, test, "get_a");
+    let ref_b = create_reference(&mut graph, test, "get_b");
+    let missing = create_reference(&mut graph, test, "missing");
+
+    assert_eq!(
+        QueryResults::Definitions(vec![DefinitionMatch {
+            definition: get_a,
+            references: vec![ref_a1, ref_a2],
+        }]),
+        execute(
+            &graph,
+            r#"definitions named "get_*" kind function in "src/**" references > 1"#
+        )
+    );
+    assert_eq!(
+        QueryResults::Definitions(vec![
+            DefinitionMatch {
+                definition: get_a,
+                references: vec![ref_a1, ref_a2],
+            },
+            DefinitionMatch {
+                definition: get_b,
+                references: vec![ref_b],
+            },
+        ]),
+        execute(&graph, "definitions kind function references>=1")
+    );
+    assert!(execute(&graph, "definitions in tests/*").is_empty());
+    assert_eq!(
+        QueryResults::References(vec![ReferenceMatch {
+            reference: missing,
+            definitions: vec![],
+        }]),
+        execute(&graph, r#"references in "tests/*" definitions = 0"#)
+    );
+
+    graph.set_file_language(lib, "python");
+    assert_eq!(
+        QueryResults::Definitions(vec![DefinitionMatch {
+            definition: get_c,
+            references: vec![],
+        }]),
+        execute(&graph, "definitions language python kind variable")
+    );
+    assert!(execute(&graph, "references language python").is_empty());
+}
+
+#[test]
+fn can_report_query_errors() {
+    assert_eq!(
+        Err(QueryError::UnexpectedEnd("comparison operator")),
+        Query::parse("definitions references")
+    );
+    assert_eq!(
+        Err(QueryError::InvalidFilter("references", "references")),
+        Query::parse("references references > 1")
+    );
+    assert_eq!(
+        Err(QueryError::InvalidFilter("kind", "references")),
+        Query::parse("references kind function")
+    );
+    assert_eq!(
+        Err(QueryError::UnexpectedToken {
+            found: "nodes".to_string(),
+            offset: 0,
+            expected: "`definitions` or `references`",
+        }),
+        Query::parse("nodes")
+    );
+    assert_eq!(
+        Err(QueryError::InvalidNumber("many".to_string())),
+        Query::parse("definitions references > many")
+    );
+    assert_eq!(
+        Err(QueryError::UnterminatedString(18)),
+        Query::parse(r#"definitions named "foo"#)
+    );
+}
@@ -0,0 +1,414 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use lsp_positions::Offset;
+use lsp_positions::Position;
+use lsp_positions::Span;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::DefinitionKind;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::Precedence;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::resolve::find_definitions;
+use stack_graphs::resolve::QueryPosition;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::PartialPathsConfig;
+use stack_graphs::stitching::ShadowingPolicy;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::NoCancellation;
+
+use crate::util::create_partial_path_and_edges;
+use crate::util::create_pop_symbol_node;
+use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
+
+fn position(line: usize, column: usize) -> Position {
+    Position {
+        line,
+        column: Offset {
+            utf8_offset: column,
+            utf16_offset: column,
+            grapheme_offset: column,
+        },
+        ..Default::default()
+    }
+}
+
+fn set_span(graph: &mut StackGraph, node: Handle<Node>, line: usize, start: usize, end: usize) {
+    graph.source_info_mut(node).span = Span {
+        start: position(line, start),
+        end: position(line, end),
+    };
+}
+
+fn create_database() -> SQLiteReader {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    // lib:  def foo
+    let lib = graph.add_file("lib").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    set_span(&mut graph, foo, 0, 4, 7);
+    let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    writer
+        .store_result_for_file(&graph, lib, "lib-1", &mut partials, vec![&foo_path])
+        .unwrap();
+
+    // main: foo
+    let main = graph.add_file("main").unwrap();
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo_ref, 0, 0, 3);
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+    writer
+        .store_result_for_file(&graph, main, "main-1", &mut partials, vec![&ref_path])
+        .unwrap();
+
+    writer.into_reader()
+}
+
+#[test]
+fn can_find_definitions_at_position() {
+    let mut reader = create_database();
+    let definitions = find_definitions(
+        &mut reader,
+        &QueryPosition::new("main", 0, 2),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![(
+            "lib".to_string(),
+            "foo".to_string(),
+            Some(Span {
+                start: position(0, 4),
+                end: position(0, 7),
+            })
+        )],
+        definitions
+            .into_iter()
+            .map(|d| (d.file, d.symbol, d.span))
+            .collect::<Vec<_>>()
+    );
+
+    let definitions = find_definitions(
+        &mut reader,
+        &QueryPosition::new("main", 0, 3),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert!(definitions.is_empty());
+}
+
+#[test]
+fn definitions_report_their_kind_and_confidence() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    // lib:  def foo, which references only find by guessing; class bar
+    let lib = graph.add_file("lib").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    set_span(&mut graph, foo, 0, 4, 7);
+    let function = graph.add_string("function");
+    graph.source_info_mut(foo).syntax_type = function.into();
+    let bar = create_pop_symbol_node(&mut graph, lib, "bar", true);
+    set_span(&mut graph, bar, 1, 6, 9);
+    let class = graph.add_string("class");
+    graph.source_info_mut(bar).syntax_type = class.into();
+    let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    graph.set_edge_confidence(r, foo, 50);
+    let bar_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, bar]).unwrap();
+    writer
+        .store_result_for_file(
+            &graph,
+            lib,
+            "lib-1",
+            &mut partials,
+            vec![&foo_path, &bar_path],
+        )
+        .unwrap();
+
+    // main: foo bar
+    let main = graph.add_file("main").unwrap();
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo_ref, 0, 0, 3);
+    let bar_ref = create_push_symbol_node(&mut graph, main, "bar", true);
+    set_span(&mut graph, bar_ref, 0, 4, 7);
+    let foo_ref_path =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+    let bar_ref_path =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[bar_ref, r]).unwrap();
+    writer
+        .store_result_for_file(
+            &graph,
+            main,
+            "main-1",
+            &mut partials,
+            vec![&foo_ref_path, &bar_ref_path],
+        )
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    let mut find = |column| {
+        find_definitions(
+            &mut reader,
+            &QueryPosition::new("main", 0, column),
+            StitcherConfig::default(),
+            &NoCancellation,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.symbol, d.kind, d.confidence))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![("foo".to_string(), Some(DefinitionKind::Function), Some(0.5))],
+        find(1)
+    );
+    assert_eq!(
+        vec![("bar".to_string(), Some(DefinitionKind::Class), None)],
+        find(5)
+    );
+}
+
+#[test]
+fn definitions_in_files_with_parse_errors_are_flagged() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    // lib:  def foo; followed by a line that could not be parsed
+    let lib = graph.add_file("lib").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    set_span(&mut graph, foo, 0, 4, 7);
+    graph.add_parse_error(
+        lib,
+        Span {
+            start: position(1, 0),
+            end: position(1, 1),
+        },
+    );
+    let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    writer
+        .store_result_for_file(&graph, lib, "lib-1", &mut partials, vec![&foo_path])
+        .unwrap();
+
+    // main: foo
+    let main = graph.add_file("main").unwrap();
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo_ref, 0, 0, 3);
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+    writer
+        .store_result_for_file(&graph, main, "main-1", &mut partials, vec![&ref_path])
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    assert_eq!(1, reader.parse_errors_for_file("lib").unwrap().len());
+    assert!(reader.parse_errors_for_file("main").unwrap().is_empty());
+
+    let definitions = find_definitions(
+        &mut reader,
+        &QueryPosition::new("main", 0, 1),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![("lib".to_string(), true)],
+        definitions
+            .into_iter()
+            .map(|d| (d.file, d.possibly_incomplete))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn shadowed_definitions_depend_on_shadowing_policy() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    // lib:  def foo
+    let lib = graph.add_file("lib").unwrap();
+    let lib_foo = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    set_span(&mut graph, lib_foo, 0, 4, 7);
+    let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, lib_foo]).unwrap();
+    writer
+        .store_result_for_file(&graph, lib, "lib-1", &mut partials, vec![&foo_path])
+        .unwrap();
+
+    // main: def foo; foo, where the import of lib takes precedence over the local definition
+    let main = graph.add_file("main").unwrap();
+    let main_foo = create_pop_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, main_foo, 0, 4, 7);
+    let scope = create_scope_node(&mut graph, main, false);
+    graph.add_edge(scope, main_foo, Precedence::DEFAULT);
+    graph.add_edge(scope, r, Precedence::HIGH);
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo_ref, 1, 0, 3);
+    graph.add_edge(foo_ref, scope, Precedence::DEFAULT);
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_partial_paths_in_file(
+        &graph,
+        &mut partials,
+        main,
+        &PartialPathsConfig::default(),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .unwrap();
+    writer
+        .store_result_for_file(&graph, main, "main-1", &mut partials, &paths)
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    let mut find = |shadowing_policy| {
+        find_definitions(
+            &mut reader,
+            &QueryPosition::new("main", 1, 1),
+            StitcherConfig::default().with_shadowing_policy(shadowing_policy),
+            &NoCancellation,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|d| d.file)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec!["lib".to_string()],
+        find(ShadowingPolicy::StrictPrecedence)
+    );
+    assert_eq!(
+        vec!["main".to_string()],
+        find(ShadowingPolicy::FileLocalFirst)
+    );
+    assert_eq!(
+        vec!["lib".to_string(), "main".to_string()],
+        find(ShadowingPolicy::None)
+    );
+}
+
+#[test]
+fn local_references_are_resolved_without_stitching() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    // main: def foo; foo; bar
+    let main = graph.add_file("main").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo, 0, 4, 7);
+    let scope = create_scope_node(&mut graph, main, false);
+    graph.add_edge(scope, foo, Precedence::DEFAULT);
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    set_span(&mut graph, foo_ref, 1, 0, 3);
+    graph.add_edge(foo_ref, scope, Precedence::DEFAULT);
+    let bar_ref = create_push_symbol_node(&mut graph, main, "bar", true);
+    set_span(&mut graph, bar_ref, 2, 0, 3);
+    graph.add_edge(bar_ref, r, Precedence::DEFAULT);
+
+    let config = PartialPathsConfig::default().with_local_resolution(true);
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_partial_paths_in_file(
+        &graph,
+        &mut partials,
+        main,
+        &config,
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .unwrap();
+    let mut local_resolutions = Vec::new();
+    ForwardPartialPathStitcher::find_local_resolutions_in_file(
+        &graph,
+        &mut partials,
+        main,
+        &config,
+        &NoCancellation,
+        |_, _, reference, paths| {
+            local_resolutions.push((reference, paths.iter().map(|p| p.end_node).collect()))
+        },
+    )
+    .unwrap();
+    assert_eq!(vec![(foo_ref, vec![foo])], local_resolutions);
+
+    writer
+        .store_result_for_file(&graph, main, "main-1", &mut partials, &paths)
+        .unwrap();
+    writer
+        .store_local_resolutions_for_file(&graph, main, &local_resolutions)
+        .unwrap();
+    let mut reader = writer.into_reader();
+
+    let definitions = find_definitions(
+        &mut reader,
+        &QueryPosition::new("main", 1, 1),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![("main".to_string(), "foo".to_string())],
+        definitions
+            .into_iter()
+            .map(|d| (d.file, d.symbol))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(1, reader.stats().local_resolution_hits);
+    assert_eq!(0, reader.stats().node_path_loads);
+
+    let definitions = find_definitions(
+        &mut reader,
+        &QueryPosition::new("main", 2, 1),
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .unwrap();
+    assert!(definitions.is_empty());
+    assert_eq!(1, reader.stats().local_resolution_misses);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_resolver_finds_and_caches_definitions() {
+    use stack_graphs::resolve::AsyncResolver;
+    use stack_graphs::resolve::QueryCache;
+    use stack_graphs::storage::SQLiteReaderAsync;
+    use std::sync::Arc;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let reader = SQLiteReaderAsync::new(create_database());
+        let cache = Arc::new(QueryCache::new());
+        let resolver = AsyncResolver::new(reader.clone()).with_cache(cache.clone());
+
+        let position = QueryPosition::new("main", 0, 0);
+        let first = resolver.find_definitions(position.clone()).await.unwrap();
+        assert_eq!(1, first.len());
+        let second = resolver.find_definitions(position).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(1, cache.stats().hits);
+
+        assert_eq!(
+            Some("lib-1".to_string()),
+            reader.tag_for_file("lib".to_string()).await.unwrap()
+        );
+    });
+}
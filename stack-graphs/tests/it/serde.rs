@@ -23,6 +23,8 @@ fn serde_json_stack_graph() {
         files: serde::Files {
             data: vec!["index.ts".to_owned()],
         },
+        file_languages: serde::FileLanguages::default(),
+        rooted_files: serde::RootedFiles::default(),
         nodes: serde::Nodes {
             data: vec![serde::Node::Root {
                 id: serde::NodeID {
@@ -53,6 +55,7 @@ fn serde_json_stack_graph() {
                         },
                     },
                     syntax_type: None,
+                    definiens_span: None,
                 }),
                 debug_info: Some(serde::DebugInfo { data: vec![] }),
             }],
@@ -68,9 +71,14 @@ fn serde_json_stack_graph() {
                     local_id: 0,
                 },
                 precedence: 0,
+                condition: None,
+                confidence: None,
                 debug_info: Some(serde::DebugInfo { data: vec![] }),
             }],
         },
+        aliases: serde::Aliases::default(),
+        speculative_nodes: serde::SpeculativeNodes::default(),
+        node_handles: serde::NodeHandles::default(),
     };
 
     // formatted using: json_pp -json_opt utf8,canonical,pretty,indent_length=4
@@ -1276,3 +1284,83 @@ fn can_serialize_partial_paths() {
     );
     assert_json_eq!(expected, actual);
 }
+
+#[test]
+fn can_load_standalone_partial_path_into_empty_graph() {
+    let graph: StackGraph = test_graphs::simple::new();
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, p| paths.push(p.clone()),
+        )
+        .expect("Expect path finding to work");
+    }
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let expected = path.display(&graph, &mut partials).to_string();
+        let json = serde_json::to_value(&path.to_standalone(&graph, &mut partials))
+            .expect("Cannot serialize path");
+        let standalone = serde_json::from_value::<serde::StandalonePartialPath>(json)
+            .expect("Cannot deserialize path");
+
+        let mut loaded_graph = StackGraph::new();
+        let mut loaded_partials = PartialPaths::new();
+        let loaded = standalone
+            .load_into(&mut loaded_graph, &mut loaded_partials)
+            .expect("Cannot load path");
+        assert_eq!(
+            expected,
+            loaded
+                .display(&loaded_graph, &mut loaded_partials)
+                .to_string()
+        );
+    }
+}
+
+#[test]
+fn can_round_trip_partial_paths_through_text() {
+    let mut graph: StackGraph = test_graphs::simple::new();
+    let mut partials = PartialPaths::new();
+    let mut db = Database::new();
+    for file in graph.iter_files() {
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            &graph,
+            &mut partials,
+            file,
+            StitcherConfig::default(),
+            &NoCancellation,
+            |g, ps, p| {
+                db.add_partial_path(g, ps, p.clone());
+            },
+        )
+        .expect("Expect path finding to work");
+    }
+    let text = db.to_text(&graph, &mut partials);
+    assert_eq!(db.iter_partial_paths().count(), text.lines().count());
+
+    let mut loaded_partials = PartialPaths::new();
+    let loaded = Database::from_text(
+        &format!("# expected paths\n\n{}", text),
+        &mut graph,
+        &mut loaded_partials,
+    )
+    .expect("Cannot parse paths");
+    assert_eq!(text, loaded.to_text(&graph, &mut loaded_partials));
+
+    let result = Database::from_text(
+        "# comment\n\"test.py\":1 -> 1 : <> ($1) =>",
+        &mut graph,
+        &mut loaded_partials,
+    );
+    assert!(matches!(
+        result,
+        Err(serde::TextError::Syntax { line: 2, .. })
+    ));
+}
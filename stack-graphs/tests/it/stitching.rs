@@ -5,14 +5,37 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::cmp::Ordering;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+
 use itertools::Itertools;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::NodeKind;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::Appendable;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::DatabaseCandidates;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::GraphEdgeCandidates;
+use stack_graphs::stitching::HotPathStats;
+use stack_graphs::stitching::Instrumentation;
+use stack_graphs::stitching::NoResolutionExtension;
+use stack_graphs::stitching::PathComparison;
+use stack_graphs::stitching::ResolutionExtension;
+use stack_graphs::stitching::ShadowingPolicy;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::NoCancellation;
 
 use crate::util::create_partial_path_and_edges;
 use crate::util::create_pop_symbol_node;
 use crate::util::create_push_symbol_node;
+use crate::util::create_scope_node;
 
 fn test_foo_bar_root_candidate_paths(symbols: &[&str], variable: bool) -> usize {
     let mut graph = StackGraph::new();
@@ -102,3 +125,606 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn identical_partial_paths_are_added_once() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    let mut other_path = path.clone();
+    other_path.eliminate_precondition_stack_variables(&mut partials);
+
+    let mut db = Database::new();
+    let first = db.add_partial_path(&graph, &mut partials, path.clone());
+    let second = db.add_partial_path(&graph, &mut partials, path);
+    let third = db.add_partial_path(&graph, &mut partials, other_path);
+    assert_eq!(first, second);
+    assert_ne!(first, third);
+    assert_eq!(2, db.iter_partial_paths().count());
+}
+
+#[test]
+fn can_dedup_partial_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    let mut db = Database::new();
+    db.set_deduplicate_paths(false);
+    db.add_partial_path(&graph, &mut partials, path.clone());
+    db.add_partial_path(&graph, &mut partials, path);
+    assert_eq!(2, db.iter_partial_paths().count());
+
+    assert_eq!(1, db.dedup(&graph, &mut partials));
+    assert_eq!(1, db.iter_partial_paths().count());
+    let mut results = Vec::new();
+    db.find_candidate_partial_paths_from_root(&graph, &mut partials, None, &mut results);
+    assert_eq!(1, results.len());
+}
+
+#[test]
+fn can_find_definitions_of_symbol_chain() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let dot = create_pop_symbol_node(&mut graph, file, ".", false);
+    let bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+    graph.add_edge(r, foo, Precedence::DEFAULT);
+    graph.add_edge(foo, dot, Precedence::DEFAULT);
+    graph.add_edge(dot, bar, Precedence::DEFAULT);
+
+    let symbols = ["foo", ".", "bar"]
+        .iter()
+        .map(|s| graph.add_symbol(s))
+        .collect_vec();
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_definitions_of_symbol_chain(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        r,
+        &symbols,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, segment, path| results.push((segment, path.end_node)),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![(0, foo), (2, bar)], results);
+}
+
+fn unshadowed_definitions(
+    graph: &StackGraph,
+    reference: Handle<Node>,
+    policy: ShadowingPolicy,
+) -> Vec<Handle<Node>> {
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default().with_shadowing_policy(policy),
+        &NoCancellation,
+        |_, _, path| paths.push(path.clone()),
+    )
+    .expect("should never be cancelled");
+    policy.remove_shadowed_paths(graph, &mut partials, &mut paths);
+    paths.iter().map(|path| path.end_node).sorted().collect()
+}
+
+#[test]
+fn shadowing_policy_decides_on_precedence() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let preferred = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let other = create_pop_symbol_node(&mut graph, file, "foo", true);
+    graph.add_edge(reference, scope, Precedence::DEFAULT);
+    graph.add_edge(scope, preferred, Precedence::HIGH);
+    graph.add_edge(scope, other, Precedence::DEFAULT);
+
+    assert_eq!(
+        vec![preferred],
+        unshadowed_definitions(&graph, reference, ShadowingPolicy::StrictPrecedence)
+    );
+    assert_eq!(
+        vec![preferred],
+        unshadowed_definitions(&graph, reference, ShadowingPolicy::FileLocalFirst)
+    );
+    assert_eq!(
+        vec![preferred, other],
+        unshadowed_definitions(&graph, reference, ShadowingPolicy::None)
+    );
+}
+
+#[test]
+fn shadowing_policy_can_prefer_file_local_definitions() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let local = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let remote = create_pop_symbol_node(&mut graph, other_file, "foo", true);
+    graph.add_edge(reference, r, Precedence::DEFAULT);
+    graph.add_edge(r, local, Precedence::DEFAULT);
+    graph.add_edge(r, remote, Precedence::DEFAULT);
+
+    assert_eq!(
+        vec![local, remote],
+        unshadowed_definitions(&graph, reference, ShadowingPolicy::StrictPrecedence)
+    );
+    assert_eq!(
+        vec![local],
+        unshadowed_definitions(&graph, reference, ShadowingPolicy::FileLocalFirst)
+    );
+}
+
+#[test]
+fn path_comparison_decides_which_similar_path_is_kept() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let file = graph.add_file("test").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let definition = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let first = create_scope_node(&mut graph, file, false);
+    let second = create_scope_node(&mut graph, file, false);
+    let via_root =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[reference, r, definition])
+            .unwrap();
+    let local = create_partial_path_and_edges(
+        &mut graph,
+        &mut partials,
+        &[reference, first, second, definition],
+    )
+    .unwrap();
+
+    let compare = |comparison: PathComparison,
+                   partials: &mut PartialPaths,
+                   path: &PartialPath,
+                   other: &PartialPath| {
+        comparison.compare(
+            &graph,
+            partials,
+            ShadowingPolicy::StrictPrecedence,
+            path,
+            other,
+        )
+    };
+    assert_eq!(
+        Some(Ordering::Less),
+        compare(
+            PathComparison::PreferShorter,
+            &mut partials,
+            &via_root,
+            &local
+        )
+    );
+    assert_eq!(
+        Some(Ordering::Greater),
+        compare(
+            PathComparison::PreferFewerRootCrossings,
+            &mut partials,
+            &via_root,
+            &local
+        )
+    );
+    assert_eq!(
+        Some(Ordering::Equal),
+        compare(
+            PathComparison::PreferHigherPrecedence,
+            &mut partials,
+            &via_root,
+            &local
+        )
+    );
+
+    let unrelated =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, definition]).unwrap();
+    assert_eq!(
+        None,
+        PathComparison::PreferShorter.compare(
+            &graph,
+            &mut partials,
+            ShadowingPolicy::StrictPrecedence,
+            &via_root,
+            &unrelated,
+        )
+    );
+}
+
+#[test]
+fn can_find_first_unshadowed_definition() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let scope = create_scope_node(&mut graph, file, false);
+    let preferred = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let other = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let unresolved = create_push_symbol_node(&mut graph, file, "bar", true);
+    graph.add_edge(reference, scope, Precedence::DEFAULT);
+    graph.add_edge(scope, preferred, Precedence::HIGH);
+    graph.add_edge(scope, other, Precedence::DEFAULT);
+
+    let mut partials = PartialPaths::new();
+    let path = ForwardPartialPathStitcher::find_first_complete_partial_path(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert_eq!(Some(preferred), path.map(|path| path.end_node));
+
+    let path = ForwardPartialPathStitcher::find_first_complete_partial_path(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![unresolved],
+        StitcherConfig::default(),
+        &NoCancellation,
+    )
+    .expect("should never be cancelled");
+    assert!(path.is_none());
+}
+
+/// Prefers definitions in the same file as the reference, and records how often it was asked
+/// about extensions.
+#[derive(Default)]
+struct PreferSameFile {
+    extensions: usize,
+}
+
+impl ResolutionExtension for PreferSameFile {
+    fn accept_extension(
+        &mut self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _extension: &PartialPath,
+    ) -> bool {
+        self.extensions += 1;
+        true
+    }
+
+    fn accept_complete_path(
+        &mut self,
+        graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        path: &PartialPath,
+    ) -> bool {
+        graph[path.start_node].file() == graph[path.end_node].file()
+    }
+}
+
+#[test]
+fn resolution_extension_can_reject_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let local = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let remote = create_pop_symbol_node(&mut graph, other_file, "foo", true);
+    graph.add_edge(reference, r, Precedence::DEFAULT);
+    graph.add_edge(r, local, Precedence::DEFAULT);
+    graph.add_edge(r, remote, Precedence::DEFAULT);
+
+    let mut partials = PartialPaths::new();
+    let mut extension = PreferSameFile::default();
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths_with_extension(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &mut extension,
+        &NoCancellation,
+        |_, _, path| results.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![local], results);
+    assert_eq!(3, extension.extensions);
+}
+
+/// Counts the events that are reported to it.
+#[derive(Default)]
+struct CountingInstrumentation {
+    candidates: AtomicUsize,
+    extensions: AtomicUsize,
+    results: AtomicUsize,
+}
+
+impl Instrumentation for CountingInstrumentation {
+    fn candidate_loaded(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _candidate: &dyn Appendable,
+    ) {
+        self.candidates.fetch_add(1, Relaxed);
+    }
+
+    fn path_extended(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+        _extension: &PartialPath,
+    ) {
+        self.extensions.fetch_add(1, Relaxed);
+    }
+
+    fn result_emitted(
+        &self,
+        _graph: &StackGraph,
+        _partials: &mut PartialPaths,
+        _path: &PartialPath,
+    ) {
+        self.results.fetch_add(1, Relaxed);
+    }
+}
+
+#[test]
+fn instrumentation_observes_stitching() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let local = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let remote = create_pop_symbol_node(&mut graph, other_file, "foo", true);
+    graph.add_edge(reference, r, Precedence::DEFAULT);
+    graph.add_edge(r, local, Precedence::DEFAULT);
+    graph.add_edge(r, remote, Precedence::DEFAULT);
+
+    let mut partials = PartialPaths::new();
+    let instrumentation = Arc::new(CountingInstrumentation::default());
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths_with_instrumentation(
+        &mut GraphEdgeCandidates::new(&graph, &mut partials, None),
+        vec![reference],
+        StitcherConfig::default(),
+        &mut NoResolutionExtension,
+        instrumentation.clone(),
+        &NoCancellation,
+        |_, _, path| results.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(2, results.len());
+    assert_eq!(3, instrumentation.candidates.load(Relaxed));
+    assert_eq!(3, instrumentation.extensions.load(Relaxed));
+    assert_eq!(results.len(), instrumentation.results.load(Relaxed));
+}
+
+fn definitions_with_config(
+    graph: &StackGraph,
+    reference: Handle<Node>,
+    config: StitcherConfig,
+) -> Vec<Handle<Node>> {
+    let mut partials = PartialPaths::new();
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        vec![reference],
+        config,
+        &NoCancellation,
+        |_, _, path| results.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    results.into_iter().sorted().collect()
+}
+
+#[test]
+fn traversal_guards_constrain_paths() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let third_file = graph.add_file("third").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let local = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let remote = create_pop_symbol_node(&mut graph, other_file, "foo", true);
+    let reexport = create_pop_symbol_node(&mut graph, other_file, "foo", false);
+    let forward = create_push_symbol_node(&mut graph, other_file, "bar", false);
+    let transitive = create_pop_symbol_node(&mut graph, third_file, "bar", true);
+    graph.add_edge(reference, local, Precedence::DEFAULT);
+    graph.add_edge(reference, r, Precedence::DEFAULT);
+    graph.add_edge(r, remote, Precedence::DEFAULT);
+    graph.add_edge(r, reexport, Precedence::DEFAULT);
+    graph.add_edge(reexport, forward, Precedence::DEFAULT);
+    graph.add_edge(forward, r, Precedence::DEFAULT);
+    graph.add_edge(r, transitive, Precedence::DEFAULT);
+
+    assert_eq!(
+        vec![local, remote, transitive],
+        definitions_with_config(&graph, reference, StitcherConfig::default())
+    );
+    assert_eq!(
+        vec![local],
+        definitions_with_config(
+            &graph,
+            reference,
+            StitcherConfig::default().forbid_node_kinds(vec![NodeKind::Root])
+        )
+    );
+    assert_eq!(
+        vec![local],
+        definitions_with_config(
+            &graph,
+            reference,
+            StitcherConfig::default().with_max_root_crossings(Some(0))
+        )
+    );
+    assert_eq!(
+        vec![local, remote],
+        definitions_with_config(
+            &graph,
+            reference,
+            StitcherConfig::default().with_max_root_crossings(Some(1))
+        )
+    );
+}
+
+#[test]
+fn hot_paths_are_remembered() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(&mut graph, file, "foo", true);
+    let foo = create_pop_symbol_node(&mut graph, other_file, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, other_file, "bar", true);
+
+    let mut db = Database::new();
+    db.set_hot_path_capacity(2);
+    let mut add_path = |nodes: &[Handle<Node>]| {
+        let path = create_partial_path_and_edges(&mut graph, &mut partials, nodes).unwrap();
+        db.add_partial_path(&graph, &mut partials, path)
+    };
+    let reference_path = add_path(&[reference, r]);
+    let foo_path = add_path(&[r, foo]);
+    add_path(&[r, bar]);
+
+    let mut resolve = |db: &mut Database| {
+        let mut results = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut DatabaseCandidates::new(&graph, &mut partials, db),
+            vec![reference],
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_, _, path| results.push(path.end_node),
+        )
+        .expect("should never be cancelled");
+        results
+    };
+    assert_eq!(vec![foo], resolve(&mut db));
+    assert_eq!(
+        vec![reference_path, foo_path],
+        db.hot_partial_paths().sorted().collect_vec()
+    );
+    // the first lookup after the paths became hot is remembered, and answers the next one
+    assert_eq!(vec![foo], resolve(&mut db));
+    assert_eq!(vec![foo], resolve(&mut db));
+    assert_eq!(
+        HotPathStats {
+            lookups: 3,
+            hits: 1
+        },
+        db.hot_path_stats()
+    );
+
+    // adding a root path forgets remembered lookups
+    let baz = create_pop_symbol_node(&mut graph, other_file, "baz", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, baz]).unwrap();
+    db.add_partial_path(&graph, &mut partials, path);
+    let mut results = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut DatabaseCandidates::new(&graph, &mut partials, &mut db),
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_, _, path| results.push(path.end_node),
+    )
+    .expect("should never be cancelled");
+    assert_eq!(vec![foo], results);
+    assert_eq!(1, db.hot_path_stats().hits);
+}
+
+#[test]
+fn hot_path_use_counts_decay() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let mut db = Database::new();
+    db.set_hot_path_capacity(1);
+    let mut paths = (0..10)
+        .map(|i| {
+            let node = create_pop_symbol_node(&mut graph, file, &format!("p{}", i), true);
+            let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, node]);
+            db.add_partial_path(&graph, &mut partials, path.unwrap())
+        })
+        .collect_vec();
+    let old = paths.remove(0);
+    let new = paths.remove(0);
+
+    for _ in 0..3 {
+        db.record_resolution(&[old]);
+    }
+    assert_eq!(vec![old], db.hot_partial_paths().collect_vec());
+
+    // once more paths are counted than are kept, the counts are halved, and the paths that were
+    // only used once are forgotten
+    for path in paths {
+        db.record_resolution(&[path]);
+    }
+    assert_eq!(vec![old], db.hot_partial_paths().collect_vec());
+
+    // without decay, the new path would have to be used more than three times to become hot
+    db.record_resolution(&[new]);
+    assert_eq!(vec![old], db.hot_partial_paths().collect_vec());
+    db.record_resolution(&[new]);
+    assert_eq!(vec![new], db.hot_partial_paths().collect_vec());
+}
+
+#[test]
+fn root_partitions_constrain_paths() {
+    let mut graph = StackGraph::new();
+    let app_file = graph.add_file_in_root("app", "main").unwrap();
+    let lib_file = graph.add_file_in_root("lib", "api").unwrap();
+    let internal_file = graph.add_file("internal").unwrap();
+    let r = StackGraph::root_node();
+    let foo_reference = create_push_symbol_node(&mut graph, app_file, "foo", true);
+    let bar_reference = create_push_symbol_node(&mut graph, app_file, "bar", true);
+    let baz_reference = create_push_symbol_node(&mut graph, app_file, "baz", true);
+    let foo = create_pop_symbol_node(&mut graph, lib_file, "foo", true);
+    let reexport = create_pop_symbol_node(&mut graph, lib_file, "baz", false);
+    let forward = create_push_symbol_node(&mut graph, lib_file, "bar", false);
+    let bar = create_pop_symbol_node(&mut graph, internal_file, "bar", true);
+    graph.add_edge(foo_reference, r, Precedence::DEFAULT);
+    graph.add_edge(bar_reference, r, Precedence::DEFAULT);
+    graph.add_edge(baz_reference, r, Precedence::DEFAULT);
+    graph.add_edge(r, foo, Precedence::DEFAULT);
+    graph.add_edge(r, reexport, Precedence::DEFAULT);
+    graph.add_edge(reexport, forward, Precedence::DEFAULT);
+    graph.add_edge(forward, r, Precedence::DEFAULT);
+    graph.add_edge(r, bar, Precedence::DEFAULT);
+
+    let config = StitcherConfig::default();
+    assert_eq!(
+        vec![bar],
+        definitions_with_config(&graph, bar_reference, config)
+    );
+
+    graph.set_root_partition("app", "app");
+    graph.set_root_partition("lib", "lib");
+    graph.set_file_partition(internal_file, "internal");
+    graph.add_partition_visibility("app", "lib");
+    graph.add_partition_visibility("lib", "internal");
+    assert_eq!(Some("lib"), graph.file_partition(lib_file));
+    assert_eq!(
+        vec![foo],
+        definitions_with_config(&graph, foo_reference, config)
+    );
+    assert_eq!(
+        Vec::<Handle<Node>>::new(),
+        definitions_with_config(&graph, bar_reference, config)
+    );
+    // the re-exporting package can see the internal one
+    assert_eq!(
+        vec![bar],
+        definitions_with_config(&graph, baz_reference, config)
+    );
+}
@@ -6,10 +6,38 @@
 // ------------------------------------------------------------------------------------------------
 
 use itertools::Itertools;
+use stack_graphs::graph::FileRoots;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
+use stack_graphs::resolve::QueryCache;
+use stack_graphs::resolve::QueryCacheStats;
+use stack_graphs::resolve::QueryKind;
+use stack_graphs::resolve::QueryPosition;
+use stack_graphs::serde;
+use stack_graphs::storage::chunks::GraphChunks;
+use stack_graphs::storage::codec::BincodeCodec;
+use stack_graphs::storage::codec::BlobCodec;
+use stack_graphs::storage::codec::CodecError;
+use stack_graphs::storage::memory::MemoryStorage;
+use stack_graphs::storage::verify;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::IntegrityIssue;
+use stack_graphs::storage::LanguageRules;
+use stack_graphs::storage::MaintenancePlan;
+use stack_graphs::storage::PointInTime;
+use stack_graphs::storage::RepairAction;
+use stack_graphs::storage::SQLiteReader;
 use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::StorageError;
+use stack_graphs::storage::BINCODE_CONFIG;
+use stack_graphs::storage::LEGACY_VERSION;
+use stack_graphs::storage::VERSION;
 use stack_graphs::NoCancellation;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::util::create_partial_path_and_edges;
 use crate::util::create_pop_symbol_node;
@@ -125,3 +153,1503 @@ fn find_candidates_for_shorter_symbol_stack_without_variable() {
     let results = test_foo_bar_root_candidate_paths(&["foo"], false);
     assert_eq!(0, results);
 }
+
+#[test]
+fn prefetched_root_paths_are_not_loaded_again() {
+    let mut reader = {
+        let mut writer = SQLiteWriter::open_in_memory().unwrap();
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test1").unwrap();
+        let mut partials = PartialPaths::new();
+        let r = StackGraph::root_node();
+        let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+        let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+        let bar_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, bar]).unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, vec![&foo_path, &bar_path])
+            .unwrap();
+        writer.into_reader()
+    };
+
+    reader
+        .prefetch_root_paths(vec!["foo", "baz"], &NoCancellation)
+        .unwrap();
+    assert_eq!(1, reader.stats().root_path_prefetches);
+    assert_eq!(1, reader.get().2.iter_partial_paths().count());
+
+    let (graph, partials, _) = reader.get();
+    let file = graph.add_file("test2").unwrap();
+    let r = StackGraph::root_node();
+    let reference = create_push_symbol_node(graph, file, "foo", true);
+    let mut path = create_partial_path_and_edges(graph, partials, &[reference, r]).unwrap();
+    path.eliminate_precondition_stack_variables(partials);
+    reader
+        .load_partial_path_extensions(&path, &NoCancellation)
+        .unwrap();
+    assert_eq!(0, reader.stats().root_path_loads);
+    assert_eq!(1, reader.stats().root_path_prefetch_hits);
+    assert_eq!(1, reader.get().2.iter_partial_paths().count());
+}
+
+#[test]
+fn shadowed_files_replace_their_stored_paths() {
+    let mut reader = {
+        let mut writer = SQLiteWriter::open_in_memory().unwrap();
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test1").unwrap();
+        let mut partials = PartialPaths::new();
+        let r = StackGraph::root_node();
+        let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+        writer
+            .store_result_for_file(&graph, file, "", &mut partials, vec![&foo_path])
+            .unwrap();
+        writer.into_reader()
+    };
+
+    // the unsaved contents of test1 define bar instead of foo
+    let mut buffer = StackGraph::new();
+    let buffer_file = buffer.add_file("test1").unwrap();
+    let mut buffer_partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let bar = create_pop_symbol_node(&mut buffer, buffer_file, "bar", true);
+    let bar_path =
+        create_partial_path_and_edges(&mut buffer, &mut buffer_partials, &[r, bar]).unwrap();
+    reader
+        .shadow_file(&buffer, buffer_file, &mut buffer_partials, vec![&bar_path])
+        .unwrap();
+
+    let (graph, partials, _) = reader.get();
+    let file = graph.add_file("test2").unwrap();
+    let references = ["foo", "bar"]
+        .iter()
+        .map(|symbol| {
+            let reference = create_push_symbol_node(graph, file, *symbol, true);
+            let mut path = create_partial_path_and_edges(graph, partials, &[reference, r]).unwrap();
+            path.eliminate_precondition_stack_variables(partials);
+            path
+        })
+        .collect_vec();
+    let mut candidates = Vec::new();
+    for path in &references {
+        reader
+            .load_partial_path_extensions(path, &NoCancellation)
+            .unwrap();
+        let (graph, partials, db) = reader.get();
+        let mut results = Vec::new();
+        db.find_candidate_partial_paths_from_root(
+            graph,
+            partials,
+            Some(path.symbol_stack_postcondition),
+            &mut results,
+        );
+        candidates.push(results.len());
+    }
+    assert_eq!(vec![0, 1], candidates);
+}
+
+#[test]
+fn graph_chunks_are_loaded_into_a_single_graph() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let bar = create_push_symbol_node(&mut graph, file, "bar", true);
+    graph.add_edge(r, foo, Precedence::DEFAULT);
+    graph.add_edge(foo, bar, Precedence::DEFAULT);
+    graph.add_edge(bar, r, Precedence::DEFAULT);
+    let expected = serde::StackGraph::from_graph(&graph);
+
+    // the first chunk contains foo and its edges, including the one to bar in the second chunk
+    let mut chunks = GraphChunks::new().unwrap();
+    for (nodes, sources) in [(vec![foo], vec![r, foo]), (vec![bar], vec![bar])] {
+        let mut chunk = expected.clone();
+        let ids = nodes
+            .iter()
+            .map(|node| serde::NodeID::from_node(&graph, *node))
+            .collect_vec();
+        let sources = sources
+            .iter()
+            .map(|node| serde::NodeID::from_node(&graph, *node))
+            .collect_vec();
+        chunk.nodes.data.retain(|node| ids.contains(node.id()));
+        chunk
+            .edges
+            .data
+            .retain(|edge| sources.contains(&edge.source));
+        chunks.push(&chunk).unwrap();
+    }
+    assert_eq!(2, chunks.len());
+
+    let mut loaded = StackGraph::new();
+    chunks.load_into(&mut loaded).unwrap();
+    assert_eq!(expected, serde::StackGraph::from_graph(&loaded));
+}
+
+#[test]
+fn dependencies_are_recorded_and_invalidated() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+    let complete_path =
+        create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r, foo_def]).unwrap();
+
+    writer
+        .store_result_for_file(&graph, def_file, "", &mut partials, vec![&def_path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "", &mut partials, vec![&ref_path])
+        .unwrap();
+    writer
+        .store_dependencies_for_file(&graph, ref_file, &mut partials, vec![&complete_path])
+        .unwrap();
+
+    assert_eq!(
+        vec![PathBuf::from("ref")],
+        writer.dependents_of_file(Path::new("def")).unwrap()
+    );
+    assert_eq!(
+        vec![PathBuf::from("def")],
+        writer.dependencies_of_file(Path::new("ref")).unwrap()
+    );
+    assert!(writer
+        .dependents_of_file(Path::new("ref"))
+        .unwrap()
+        .is_empty());
+
+    // storing new results for a file invalidates its recorded dependencies
+    writer
+        .store_result_for_file(&graph, ref_file, "", &mut partials, vec![&ref_path])
+        .unwrap();
+    let mut reader = writer.into_reader();
+    assert!(reader
+        .dependents_of_file(Path::new("def"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn memory_storage_loads_graphs_and_extensions() {
+    let mut storage = MemoryStorage::new();
+
+    {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test1").unwrap();
+        let mut partials = PartialPaths::new();
+
+        let r = StackGraph::root_node();
+        let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+        storage
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+            .unwrap();
+        storage
+            .store_error_for_file(Path::new("test3"), "tag", "parse error")
+            .unwrap();
+    }
+
+    assert!(matches!(
+        storage.status_for_file("test1", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+    assert!(matches!(
+        storage.status_for_file("test1", Some("other")).unwrap(),
+        FileStatus::Missing
+    ));
+    assert!(matches!(
+        storage.status_for_file::<&str>("test3", None).unwrap(),
+        FileStatus::Error(_)
+    ));
+    assert_eq!(2, storage.list_all().count());
+
+    let (graph, partials, _) = storage.get();
+    let file = graph.add_file("test2").unwrap();
+    let r = StackGraph::root_node();
+    let foo = create_push_symbol_node(graph, file, "foo", true);
+    let path = create_partial_path_and_edges(graph, partials, &[foo, r]).unwrap();
+
+    storage
+        .load_partial_path_extensions(&path, &NoCancellation)
+        .unwrap();
+    let (graph, partials, db) = storage.get();
+    let mut results = Vec::new();
+    db.find_candidate_partial_paths_from_root(
+        graph,
+        partials,
+        Some(path.symbol_stack_postcondition),
+        &mut results,
+    );
+    assert_eq!(1, results.len());
+    assert!(graph.get_file("test1").is_some());
+    assert_eq!(1, storage.stats().file_loads);
+
+    assert_eq!(1, storage.clean_file(Path::new("test1")).unwrap());
+    assert!(matches!(
+        storage.status_for_file::<&str>("test1", None).unwrap(),
+        FileStatus::Missing
+    ));
+}
+
+#[test]
+fn batch_writer_stores_files_on_commit() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    {
+        let mut batch = writer.batch_writer().unwrap();
+        batch
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+            .unwrap();
+        assert!(matches!(
+            batch.status_for_file("test", Some("tag")).unwrap(),
+            FileStatus::Indexed
+        ));
+        // dropped without commit
+    }
+    assert!(matches!(
+        writer.status_for_file("test", None).unwrap(),
+        FileStatus::Missing
+    ));
+
+    let mut batch = writer.batch_writer().unwrap();
+    batch
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+        .unwrap();
+    batch
+        .store_error_for_file(Path::new("other"), "tag", "parse error")
+        .unwrap();
+    batch.commit().unwrap();
+    assert!(matches!(
+        writer.status_for_file("test", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+    assert!(matches!(
+        writer.status_for_file("other", None).unwrap(),
+        FileStatus::Error(_)
+    ));
+}
+
+#[test]
+fn incomplete_results_are_stored_with_their_reason() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-incomplete-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    let mut writer = SQLiteWriter::open(&db_path).unwrap();
+    let mut batch = writer.batch_writer().unwrap();
+    batch
+        .store_incomplete_result_for_file(
+            &graph,
+            file,
+            "tag-1",
+            "timed out",
+            &mut partials,
+            vec![&path],
+        )
+        .unwrap();
+    batch.commit().unwrap();
+
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+    assert!(matches!(
+        reader.status_for_file("test", Some("tag-1")).unwrap(),
+        FileStatus::Indexed
+    ));
+    assert_eq!(
+        Some("timed out".to_string()),
+        reader.incomplete_reason_for_file("test").unwrap()
+    );
+    assert_eq!(None, reader.incomplete_reason_for_file("missing").unwrap());
+
+    // storing the complete result clears the reason, also if the graph is unchanged
+    writer
+        .store_result_for_file(&graph, file, "tag-2", &mut partials, vec![&path])
+        .unwrap();
+    assert_eq!(None, reader.incomplete_reason_for_file("test").unwrap());
+
+    drop(reader);
+    drop(writer);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn file_roots_are_stored_with_graphs_and_kept_by_readers() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut roots = FileRoots::new();
+    roots.add_root("repo", "/home/dev/repo");
+    writer.set_file_roots(roots);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file_in_root("repo", "src/main.py").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    let file = reader
+        .load_graph_for_path(Path::new("/home/dev/repo/src/main.py"))
+        .unwrap();
+    let (graph, _, _) = reader.get();
+    assert_eq!(Some("repo"), graph[file].root());
+    assert_eq!("src/main.py", graph[file].relative_path());
+}
+
+#[test]
+fn database_stats_describe_stored_files() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    graph.set_file_language(file, "python");
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+        .unwrap();
+    writer
+        .store_error_for_file(Path::new("other"), "tag", "parse error")
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    let stats = reader.database_stats().unwrap();
+    assert!(stats.size > 0);
+    assert_eq!(
+        vec![PathBuf::from("other"), PathBuf::from("test")],
+        stats.files.iter().map(|f| f.path.clone()).collect_vec()
+    );
+
+    let other = &stats.files[0];
+    assert!(matches!(other.status, FileStatus::Error(_)));
+    assert_eq!(None, other.language);
+    assert_eq!(0, other.nodes);
+    assert_eq!(0, other.root_paths);
+
+    let test = &stats.files[1];
+    assert!(matches!(test.status, FileStatus::Indexed));
+    assert_eq!("tag", test.tag);
+    assert_eq!(Some("python"), test.language.as_deref());
+    assert!(test.nodes > 0);
+    assert_eq!(1, test.root_paths);
+    assert_eq!(0, test.node_paths);
+}
+
+#[test]
+fn globals_are_stored_with_file() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    let globals = BTreeMap::from([
+        ("LANGUAGE_VERSION".to_string(), "3".to_string()),
+        ("PROJECT_ROOT".to_string(), "/src".to_string()),
+    ]);
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+        .unwrap();
+    writer
+        .store_globals_for_file(Path::new("test"), &globals)
+        .unwrap();
+    assert_eq!(globals, writer.globals_for_file(Path::new("test")).unwrap());
+
+    // storing new results for a file removes its recorded globals
+    writer
+        .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+        .unwrap();
+    let mut reader = writer.into_reader();
+    assert!(reader
+        .globals_for_file(Path::new("test"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn unchanged_graphs_are_kept_and_verified_on_load() {
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    writer
+        .store_result_for_file(&graph, file, "old", &mut partials, vec![&path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, file, "new", &mut partials, vec![&path])
+        .unwrap();
+    assert!(matches!(
+        writer.status_for_file("test", Some("new")).unwrap(),
+        FileStatus::Indexed
+    ));
+    assert!(matches!(
+        writer.status_for_file("test", Some("old")).unwrap(),
+        FileStatus::Missing
+    ));
+
+    let mut reader = writer.into_reader();
+    let loaded_file = reader.load_graph_for_file("test").unwrap();
+    let (loaded_graph, _, _) = reader.get();
+    assert_eq!(
+        graph.fingerprint_for_file(file),
+        loaded_graph.fingerprint_for_file(loaded_file)
+    );
+}
+
+#[test]
+fn renamed_files_keep_their_stored_data() {
+    let build = |name: &str| {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file(name).unwrap();
+        let mut partials = PartialPaths::new();
+        let r = StackGraph::root_node();
+        let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+        (graph, file, partials, path)
+    };
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    {
+        let (graph, file, mut partials, path) = build("old");
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+            .unwrap();
+    }
+    let globals = BTreeMap::from([("PROJECT_ROOT".to_string(), "/src".to_string())]);
+    writer
+        .store_globals_for_file(Path::new("old"), &globals)
+        .unwrap();
+
+    assert!(writer
+        .rename_file(Path::new("old"), Path::new("new"))
+        .unwrap());
+    assert!(!writer
+        .rename_file(Path::new("old"), Path::new("other"))
+        .unwrap());
+    assert_eq!(
+        vec![PathBuf::from("new")],
+        writer.indexed_files_with_tag("tag").unwrap()
+    );
+    assert!(matches!(
+        writer.status_for_file("old", None).unwrap(),
+        FileStatus::Missing
+    ));
+    assert!(matches!(
+        writer.status_for_file("new", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+    assert_eq!(globals, writer.globals_for_file(Path::new("new")).unwrap());
+
+    // the moved graph has the fingerprint of the same graph built for the new path
+    let (graph, file, _, _) = build("new");
+    assert_eq!(
+        Some(graph.fingerprint_for_file(file)),
+        writer.fingerprint_for_file(Path::new("new")).unwrap()
+    );
+
+    let mut reader = writer.into_reader();
+    reader.load_graph_for_file("new").unwrap();
+    reader
+        .prefetch_root_paths(vec!["foo"], &NoCancellation)
+        .unwrap();
+    let (graph, _, db) = reader.get();
+    let paths = db.iter_partial_paths().collect::<Vec<_>>();
+    assert_eq!(1, paths.len());
+    let end_node = db[paths[0]].end_node;
+    assert_eq!(graph.get_file("new"), graph[end_node].file());
+    assert!(graph.get_file("old").is_none());
+}
+
+#[test]
+fn maintenance_runs_when_thresholds_are_reached() {
+    let mut graph = StackGraph::new();
+    let file1 = graph.add_file("test1").unwrap();
+    let file2 = graph.add_file("test2").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file1, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, file2, "bar", true);
+    let foo_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    let bar_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, bar]).unwrap();
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    writer
+        .store_result_for_file(&graph, file1, "", &mut partials, vec![&foo_path])
+        .unwrap();
+
+    let report = writer.maintain(&MaintenancePlan::all()).unwrap();
+    assert!(report.changed_rows > 0);
+    assert!(report.vacuumed && report.analyzed && report.reindexed);
+
+    let plan = MaintenancePlan {
+        min_changed_rows_for_analyze: 1,
+        ..MaintenancePlan::default()
+    };
+    let report = writer.maintain(&plan).unwrap();
+    assert_eq!(0, report.changed_rows);
+    assert!(!report.vacuumed && !report.analyzed && !report.reindexed);
+
+    writer
+        .store_result_for_file(&graph, file2, "", &mut partials, vec![&bar_path])
+        .unwrap();
+    let report = writer.maintain(&plan).unwrap();
+    assert!(report.changed_rows > 0);
+    assert!(report.analyzed && !report.reindexed);
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypted_databases_require_their_key() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-encrypted-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    {
+        let mut graph = StackGraph::new();
+        let file = graph.add_file("test").unwrap();
+        let mut partials = PartialPaths::new();
+        let r = StackGraph::root_node();
+        let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+        let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+        let mut writer = SQLiteWriter::open_encrypted(&db_path, "secret").unwrap();
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, vec![&path])
+            .unwrap();
+    }
+
+    assert!(matches!(
+        SQLiteReader::open_encrypted(&db_path, "wrong"),
+        Err(StorageError::IncorrectKey(_))
+    ));
+    assert!(SQLiteReader::open(&db_path).is_err());
+    let mut reader = SQLiteReader::open_encrypted(&db_path, "secret").unwrap();
+    assert!(matches!(
+        reader.status_for_file("test", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+/// A codec that flips all bits, so that blobs are only readable if they are decoded.
+struct InvertingCodec;
+
+impl BlobCodec for InvertingCodec {
+    fn name(&self) -> &str {
+        "inverting"
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(data.into_iter().map(|b| !b).collect())
+    }
+
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(blob.into_iter().map(|b| !b).collect())
+    }
+}
+
+#[test]
+fn databases_use_the_codec_they_were_created_with() {
+    let db_path =
+        std::env::temp_dir().join(format!("stack-graphs-codec-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    SQLiteWriter::open_with_codec(&db_path, Arc::new(InvertingCodec))
+        .unwrap()
+        .store_result_for_file(&graph, file, "", &mut partials, vec![&path])
+        .unwrap();
+
+    assert!(matches!(
+        SQLiteReader::open(&db_path),
+        Err(StorageError::UnknownCodec(name)) if name == "inverting"
+    ));
+    assert!(matches!(
+        SQLiteWriter::open_with_codec(&db_path, Arc::new(BincodeCodec)),
+        Err(StorageError::IncorrectCodec(_))
+    ));
+
+    let mut reader = SQLiteReader::open_with_codec(&db_path, Arc::new(InvertingCodec)).unwrap();
+    let loaded_file = reader.load_graph_for_file("test").unwrap();
+    reader
+        .load_partial_paths_for_file("test", &NoCancellation)
+        .unwrap();
+    let (loaded_graph, _, db) = reader.get();
+    assert_eq!(
+        graph.fingerprint_for_file(file),
+        loaded_graph.fingerprint_for_file(loaded_file)
+    );
+    assert_eq!(1, db.iter_partial_paths().count());
+
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+/// A codec that stores graphs in its own format, which is their bincode encoding behind a marker,
+/// and all other values as they are.
+struct MarkedGraphCodec;
+
+impl MarkedGraphCodec {
+    const MARKER: &'static [u8] = b"graph:";
+}
+
+impl BlobCodec for MarkedGraphCodec {
+    fn name(&self) -> &str {
+        "marked-graph"
+    }
+
+    fn encode_graph(&self, graph: &serde::StackGraph) -> Result<Vec<u8>, CodecError> {
+        let mut blob = Self::MARKER.to_vec();
+        blob.extend(bincode::encode_to_vec(graph, BINCODE_CONFIG)?);
+        Ok(blob)
+    }
+
+    fn decode_graph(&self, blob: Vec<u8>) -> Result<serde::StackGraph, CodecError> {
+        let data = blob
+            .strip_prefix(Self::MARKER)
+            .ok_or("graph blob without marker")?;
+        let (graph, _) = bincode::decode_from_slice(data, BINCODE_CONFIG)?;
+        Ok(graph)
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(data)
+    }
+
+    fn decode(&self, blob: Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(blob)
+    }
+}
+
+#[test]
+fn codecs_encode_graphs_themselves() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-graph-codec-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    SQLiteWriter::open_with_codec(&db_path, Arc::new(MarkedGraphCodec))
+        .unwrap()
+        .store_result_for_file(&graph, file, "", &mut partials, vec![&path])
+        .unwrap();
+
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        let value = conn
+            .query_row("SELECT value FROM graphs WHERE file = 'test'", [], |r| {
+                r.get::<_, Vec<u8>>(0)
+            })
+            .unwrap();
+        assert!(value.starts_with(MarkedGraphCodec::MARKER));
+    }
+
+    let mut reader = SQLiteReader::open_with_codec(&db_path, Arc::new(MarkedGraphCodec)).unwrap();
+    let loaded_file = reader.load_graph_for_file("test").unwrap();
+    reader
+        .load_partial_paths_for_file("test", &NoCancellation)
+        .unwrap();
+    let (loaded_graph, _, db) = reader.get();
+    assert_eq!(
+        graph.fingerprint_for_file(file),
+        loaded_graph.fingerprint_for_file(loaded_file)
+    );
+    assert_eq!(1, db.iter_partial_paths().count());
+    assert!(verify(&reader, None, &NoCancellation).unwrap().is_ok());
+
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn graphs_can_be_stored_with_rkyv() {
+    let db_path =
+        std::env::temp_dir().join(format!("stack-graphs-rkyv-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap();
+    SQLiteWriter::open_with_codec(&db_path, Arc::new(stack_graphs::storage::codec::RkyvCodec))
+        .unwrap()
+        .store_result_for_file(&graph, file, "", &mut partials, vec![&path])
+        .unwrap();
+
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+    let loaded_file = reader.load_graph_for_file("test").unwrap();
+    reader
+        .load_partial_paths_for_file("test", &NoCancellation)
+        .unwrap();
+    let (loaded_graph, _, db) = reader.get();
+    assert_eq!(
+        graph.fingerprint_for_file(file),
+        loaded_graph.fingerprint_for_file(loaded_file)
+    );
+    assert_eq!(1, db.iter_partial_paths().count());
+
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn file_languages_are_stored_and_listed() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let python_file = graph.add_file("main.py").unwrap();
+    graph.set_file_language(python_file, "python");
+    let typescript_file = graph.add_file("index.ts").unwrap();
+    graph.set_file_language(typescript_file, "typescript");
+    let untagged_file = graph.add_file("README").unwrap();
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut storage = MemoryStorage::new();
+    for file in [python_file, typescript_file, untagged_file] {
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, Vec::new())
+            .unwrap();
+        storage
+            .store_result_for_file(&graph, file, "tag", &mut partials, Vec::new())
+            .unwrap();
+    }
+
+    let mut reader = writer.into_reader();
+    let python_files = reader
+        .list_language("python")
+        .unwrap()
+        .try_iter()
+        .unwrap()
+        .map(|entry| entry.unwrap().path)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![PathBuf::from("main.py")], python_files);
+    let languages = reader
+        .list_all()
+        .unwrap()
+        .try_iter()
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .map(|entry| (entry.path, entry.language))
+        .sorted()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (PathBuf::from("README"), None),
+            (PathBuf::from("index.ts"), Some("typescript".to_string())),
+            (PathBuf::from("main.py"), Some("python".to_string())),
+        ],
+        languages
+    );
+    assert_eq!(
+        vec![PathBuf::from("index.ts")],
+        storage
+            .list_language("typescript")
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>()
+    );
+
+    let loaded_file = reader.load_graph_for_file("main.py").unwrap();
+    let (loaded_graph, _, _) = reader.get();
+    assert_eq!(Some("python"), loaded_graph.file_language(loaded_file));
+    assert_eq!(
+        vec![loaded_file],
+        loaded_graph
+            .iter_files_in_language("python")
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn database_metadata_records_versions_and_rules() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    writer
+        .set_language_rules(
+            "python",
+            &LanguageRules {
+                hash: "abc".to_string(),
+                version: Some("1.0".to_string()),
+            },
+        )
+        .unwrap();
+    writer
+        .set_language_rules(
+            "d.ts",
+            &LanguageRules {
+                hash: "def".to_string(),
+                version: None,
+            },
+        )
+        .unwrap();
+    writer.set_property("indexer", "test").unwrap();
+
+    let reader = writer.into_reader();
+    let metadata = reader.metadata().unwrap();
+    assert_eq!(VERSION, metadata.schema_version);
+    assert!(metadata.is_created_by_this_version());
+    assert!(metadata.created_at.is_some());
+    assert_eq!("bincode", metadata.codec);
+    assert_eq!(
+        vec![
+            (
+                "d.ts".to_string(),
+                LanguageRules {
+                    hash: "def".to_string(),
+                    version: None,
+                },
+            ),
+            (
+                "python".to_string(),
+                LanguageRules {
+                    hash: "abc".to_string(),
+                    version: Some("1.0".to_string()),
+                },
+            ),
+        ],
+        metadata.language_rules.into_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some("test"),
+        metadata.properties.get("indexer").map(String::as_str)
+    );
+}
+
+#[test]
+fn changed_language_rules_invalidate_files() {
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let python_file = graph.add_file("main.py").unwrap();
+    graph.set_file_language(python_file, "python");
+    let typescript_file = graph.add_file("index.ts").unwrap();
+    graph.set_file_language(typescript_file, "typescript");
+
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    for file in [python_file, typescript_file] {
+        writer
+            .store_result_for_file(&graph, file, "tag", &mut partials, Vec::new())
+            .unwrap();
+    }
+    let rules = |hash: &str| LanguageRules {
+        hash: hash.to_string(),
+        version: None,
+    };
+    assert_eq!(0, writer.set_language_rules("python", &rules("a")).unwrap());
+    assert_eq!(0, writer.set_language_rules("python", &rules("a")).unwrap());
+    assert!(matches!(
+        writer.status_for_file("main.py", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+
+    assert_eq!(1, writer.set_language_rules("python", &rules("b")).unwrap());
+    assert!(matches!(
+        writer.status_for_file("main.py", Some("tag")).unwrap(),
+        FileStatus::Missing
+    ));
+    assert!(matches!(
+        writer.status_for_file("index.ts", Some("tag")).unwrap(),
+        FileStatus::Indexed
+    ));
+
+    assert_eq!(1, writer.invalidate_language("typescript").unwrap());
+    assert!(matches!(
+        writer.status_for_file("index.ts", Some("tag")).unwrap(),
+        FileStatus::Missing
+    ));
+    assert_eq!(0, writer.invalidate_language("typescript").unwrap());
+}
+
+#[test]
+fn verification_reports_changed_and_missing_sources() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let other_file = graph.add_file("other").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+
+    writer
+        .store_result_for_file(&graph, def_file, "def-tag", &mut partials, vec![&def_path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "ref-tag", &mut partials, vec![&ref_path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, other_file, "other-tag", &mut partials, vec![])
+        .unwrap();
+    let reader = writer.into_reader();
+
+    let report = verify(&reader, None, &NoCancellation).unwrap();
+    assert_eq!(3, report.files);
+    assert!(report.is_ok());
+
+    let mut source_tag = |file: &str| match file {
+        "def" => Some("def-tag".to_string()),
+        "ref" => Some("new-ref-tag".to_string()),
+        _ => None,
+    };
+    let report = verify(&reader, Some(&mut source_tag), &NoCancellation).unwrap();
+    assert_eq!(
+        vec![
+            IntegrityIssue::MissingSource {
+                file: "other".to_string()
+            },
+            IntegrityIssue::ChangedSource {
+                file: "ref".to_string()
+            },
+        ],
+        report.issues
+    );
+    assert_eq!(
+        vec![
+            (PathBuf::from("other"), RepairAction::Remove),
+            (PathBuf::from("ref"), RepairAction::Reindex),
+        ],
+        report.repair_plan()
+    );
+}
+
+#[test]
+fn enabling_append_only_records_the_files_in_the_database() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+
+    // the files are stored before the database is append-only
+    writer
+        .store_result_for_file(&graph, def_file, "def-tag", &mut partials, vec![&def_path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "ref-tag", &mut partials, vec![&ref_path])
+        .unwrap();
+    writer.set_commit(Some("before")).unwrap();
+    writer.set_append_only(true).unwrap();
+    writer.set_commit(Some("after")).unwrap();
+    writer.clean_file(Path::new("def")).unwrap();
+    let mut reader = writer.into_reader();
+
+    let list_files = |reader: &mut SQLiteReader| {
+        reader
+            .list_all()
+            .unwrap()
+            .try_iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+
+    reader
+        .as_of(&PointInTime::Commit("before".to_string()))
+        .unwrap();
+    assert_eq!(
+        vec![PathBuf::from("def"), PathBuf::from("ref")],
+        list_files(&mut reader)
+    );
+    reader
+        .load_partial_paths_for_file("def", &NoCancellation)
+        .unwrap();
+    let (_, _, db) = reader.get();
+    assert_eq!(1, db.iter_partial_paths().count());
+
+    reader
+        .as_of(&PointInTime::Commit("after".to_string()))
+        .unwrap();
+    assert_eq!(vec![PathBuf::from("ref")], list_files(&mut reader));
+
+    let history = reader.file_history("def").unwrap();
+    assert_eq!(
+        vec![
+            (Some("before".to_string()), Some("def-tag".to_string())),
+            (Some("after".to_string()), None),
+        ],
+        history
+            .into_iter()
+            .map(|version| (version.commit, version.tag))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn append_only_database_can_be_queried_as_of_commit() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    writer.set_append_only(true).unwrap();
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+
+    writer.set_commit(Some("first")).unwrap();
+    writer
+        .store_result_for_file(&graph, def_file, "def-tag", &mut partials, vec![&def_path])
+        .unwrap();
+    writer.set_commit(Some("second")).unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "ref-tag", &mut partials, vec![&ref_path])
+        .unwrap();
+    writer.clean_file(Path::new("def")).unwrap();
+    writer.set_commit(Some("empty")).unwrap();
+    let mut reader = writer.into_reader();
+
+    let list_files = |reader: &mut SQLiteReader| {
+        reader
+            .list_all()
+            .unwrap()
+            .try_iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![PathBuf::from("ref")], list_files(&mut reader));
+
+    reader
+        .as_of(&PointInTime::Commit("first".to_string()))
+        .unwrap();
+    assert_eq!(vec![PathBuf::from("def")], list_files(&mut reader));
+    reader
+        .load_partial_paths_for_file("def", &NoCancellation)
+        .unwrap();
+
+    reader
+        .as_of(&PointInTime::Commit("empty".to_string()))
+        .unwrap();
+    assert_eq!(vec![PathBuf::from("ref")], list_files(&mut reader));
+    assert!(reader
+        .as_of(&PointInTime::Commit("missing".to_string()))
+        .is_err());
+
+    reader.latest().unwrap();
+    assert_eq!(None, reader.point_in_time());
+    assert_eq!(vec![PathBuf::from("ref")], list_files(&mut reader));
+
+    let history = reader.file_history("def").unwrap();
+    assert_eq!(
+        vec![
+            (Some("first".to_string()), Some("def-tag".to_string())),
+            (Some("second".to_string()), None),
+        ],
+        history
+            .into_iter()
+            .map(|version| (version.commit, version.tag))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn query_cache_is_invalidated_when_involved_files_change() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-query-cache-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+
+    let mut writer = SQLiteWriter::open(&db_path).unwrap();
+    writer
+        .store_result_for_file(&graph, def_file, "def-1", &mut partials, vec![&def_path])
+        .unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "ref-1", &mut partials, vec![&ref_path])
+        .unwrap();
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+    assert_eq!(
+        Some("def-1".to_string()),
+        reader.tag_for_file("def").unwrap()
+    );
+    assert_eq!(None, reader.tag_for_file("missing").unwrap());
+
+    let cache = QueryCache::new();
+    let position = QueryPosition::new("ref", 0, 0);
+    assert_eq!(
+        None,
+        cache
+            .get(&mut reader, QueryKind::Definitions, &position)
+            .unwrap()
+    );
+    cache
+        .insert(
+            &mut reader,
+            QueryKind::Definitions,
+            position.clone(),
+            vec!["def"],
+            vec!["def:0:0".to_string()],
+        )
+        .unwrap();
+    assert_eq!(
+        Some(vec!["def:0:0".to_string()]),
+        cache
+            .get(&mut reader, QueryKind::Definitions, &position)
+            .unwrap()
+    );
+    assert_eq!(
+        None,
+        cache
+            .get(&mut reader, QueryKind::References, &position)
+            .unwrap()
+    );
+
+    // indexing a file involved in the query again invalidates the cached result
+    writer
+        .store_result_for_file(&graph, def_file, "def-2", &mut partials, vec![&def_path])
+        .unwrap();
+    assert_eq!(
+        None,
+        cache
+            .get(&mut reader, QueryKind::Definitions, &position)
+            .unwrap()
+    );
+    assert!(cache.is_empty());
+    assert_eq!(
+        QueryCacheStats {
+            hits: 1,
+            misses: 3,
+            invalidations: 1,
+            evictions: 0,
+        },
+        cache.stats()
+    );
+
+    drop(reader);
+    drop(writer);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn query_cache_is_invalidated_when_files_are_added() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-query-cache-added-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let def_file = graph.add_file("def").unwrap();
+    let ref_file = graph.add_file("ref").unwrap();
+    let mut partials = PartialPaths::new();
+
+    let r = StackGraph::root_node();
+    let foo_def = create_pop_symbol_node(&mut graph, def_file, "foo", true);
+    let foo_ref = create_push_symbol_node(&mut graph, ref_file, "foo", true);
+
+    let def_path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo_def]).unwrap();
+    let ref_path = create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap();
+
+    let mut writer = SQLiteWriter::open(&db_path).unwrap();
+    writer
+        .store_result_for_file(&graph, def_file, "def-1", &mut partials, vec![&def_path])
+        .unwrap();
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+
+    // no file references the definition yet
+    let cache = QueryCache::new();
+    let position = QueryPosition::new("def", 0, 0);
+    cache
+        .insert(
+            &mut reader,
+            QueryKind::References,
+            position.clone(),
+            Vec::<String>::new(),
+            Vec::<String>::new(),
+        )
+        .unwrap();
+    assert_eq!(
+        Some(vec![]),
+        cache
+            .get(&mut reader, QueryKind::References, &position)
+            .unwrap()
+    );
+
+    // indexing a new file that references the definition invalidates the cached result, even
+    // though none of the files involved in the query changed
+    let generation = reader.generation().unwrap();
+    writer
+        .store_result_for_file(&graph, ref_file, "ref-1", &mut partials, vec![&ref_path])
+        .unwrap();
+    assert!(reader.generation().unwrap() > generation);
+    assert_eq!(
+        Some("def-1".to_string()),
+        reader.tag_for_file("def").unwrap()
+    );
+    assert_eq!(
+        None,
+        cache
+            .get(&mut reader, QueryKind::References, &position)
+            .unwrap()
+    );
+    assert!(cache.is_empty());
+    assert_eq!(1, cache.stats().invalidations);
+
+    // removing a file invalidates cached results as well
+    cache
+        .insert(
+            &mut reader,
+            QueryKind::References,
+            position.clone(),
+            vec!["ref"],
+            vec!["ref:0:0".to_string()],
+        )
+        .unwrap();
+    writer.clean_file(Path::new("ref")).unwrap();
+    assert_eq!(
+        None,
+        cache
+            .get(&mut reader, QueryKind::References, &position)
+            .unwrap()
+    );
+
+    drop(reader);
+    drop(writer);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn root_symbols_are_recorded_per_file() {
+    let mut writer = SQLiteWriter::open_in_memory().unwrap();
+    let mut graph = StackGraph::new();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+
+    let lib = graph.add_file("lib").unwrap();
+    let foo = create_pop_symbol_node(&mut graph, lib, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, lib, "bar", true);
+    let lib_paths = vec![
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo]).unwrap(),
+        create_partial_path_and_edges(&mut graph, &mut partials, &[r, bar]).unwrap(),
+    ];
+    writer
+        .store_result_for_file(&graph, lib, "", &mut partials, &lib_paths)
+        .unwrap();
+
+    let main = graph.add_file("main").unwrap();
+    let foo_ref = create_push_symbol_node(&mut graph, main, "foo", true);
+    let main_paths =
+        vec![create_partial_path_and_edges(&mut graph, &mut partials, &[foo_ref, r]).unwrap()];
+    writer
+        .store_result_for_file(&graph, main, "", &mut partials, &main_paths)
+        .unwrap();
+
+    let mut reader = writer.into_reader();
+    assert_eq!(
+        vec!["bar".to_string(), "foo".to_string()],
+        reader.exports_of(Path::new("lib")).unwrap()
+    );
+    assert!(reader.imports_of(Path::new("lib")).unwrap().is_empty());
+    assert_eq!(
+        vec!["foo".to_string()],
+        reader.imports_of(Path::new("main")).unwrap()
+    );
+    assert_eq!(
+        vec![PathBuf::from("lib")],
+        reader.exporters_of("foo").unwrap()
+    );
+    assert_eq!(
+        vec![PathBuf::from("main")],
+        reader.importers_of("foo").unwrap()
+    );
+    assert!(reader.importers_of("bar").unwrap().is_empty());
+}
+
+#[test]
+fn legacy_databases_can_be_read_but_not_written() {
+    let db_path = std::env::temp_dir().join(format!(
+        "stack-graphs-legacy-database-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&db_path);
+
+    let mut graph = StackGraph::new();
+    let file = graph.add_file("test").unwrap();
+    let mut partials = PartialPaths::new();
+    let r = StackGraph::root_node();
+    let foo = create_pop_symbol_node(&mut graph, file, "foo", true);
+    let bar = create_pop_symbol_node(&mut graph, file, "bar", true);
+    let path = create_partial_path_and_edges(&mut graph, &mut partials, &[r, foo, bar]).unwrap();
+    let expected = path.display(&graph, &mut partials).to_string();
+
+    // write the database as the legacy version did, which stored graphs and paths as plain
+    // bincode, and whose edges had no conditions or confidences
+    {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            r#"
+                CREATE TABLE metadata (
+                    version INTEGER NOT NULL
+                ) STRICT;
+                CREATE TABLE graphs (
+                    file   TEXT PRIMARY KEY,
+                    tag    TEXT NOT NULL,
+                    error  TEXT,
+                    value  BLOB NOT NULL
+                ) STRICT;
+                CREATE TABLE file_paths (
+                    file     TEXT NOT NULL,
+                    local_id INTEGER NOT NULL,
+                    value    BLOB NOT NULL,
+                    FOREIGN KEY(file) REFERENCES graphs(file)
+                ) STRICT;
+                CREATE TABLE root_paths (
+                    file         TEXT NOT NULL,
+                    symbol_stack TEXT NOT NULL,
+                    value        BLOB NOT NULL,
+                    FOREIGN KEY(file) REFERENCES graphs(file)
+                ) STRICT;
+            "#,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO metadata (version) VALUES (?)",
+            [LEGACY_VERSION],
+        )
+        .unwrap();
+
+        let stored = graph.to_serializable();
+        // bincode encodes a struct as the tuple of its fields
+        let legacy_edges = stored
+            .edges
+            .data
+            .iter()
+            .map(|edge| {
+                (
+                    edge.source.clone(),
+                    edge.sink.clone(),
+                    edge.precedence,
+                    edge.debug_info.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let legacy_graph = (stored.files, stored.nodes, legacy_edges);
+        let graph_value = bincode::encode_to_vec(&legacy_graph, BINCODE_CONFIG).unwrap();
+        conn.execute(
+            "INSERT INTO graphs (file, tag, error, value) VALUES ('test', 'tag', NULL, ?)",
+            [graph_value],
+        )
+        .unwrap();
+
+        let legacy_path = serde::PartialPath::from_partial_path(&graph, &mut partials, &path);
+        let path_value = bincode::encode_to_vec(&legacy_path, BINCODE_CONFIG).unwrap();
+        conn.execute(
+            "INSERT INTO root_paths (file, symbol_stack, value) VALUES ('test', 'foo.bar', ?)",
+            [path_value],
+        )
+        .unwrap();
+    }
+
+    assert!(matches!(
+        SQLiteWriter::open(&db_path),
+        Err(StorageError::IncorrectVersion(LEGACY_VERSION))
+    ));
+    let mut reader = SQLiteReader::open(&db_path).unwrap();
+    assert_eq!(
+        vec![(PathBuf::from("test"), "tag".to_string())],
+        reader
+            .list_all()
+            .unwrap()
+            .try_iter()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .map(|entry| (entry.path, entry.tag))
+            .collect::<Vec<_>>()
+    );
+    reader
+        .load_partial_paths_for_file("test", &NoCancellation)
+        .unwrap();
+    let (graph, partials, db) = reader.get();
+    let paths = db
+        .iter_partial_paths()
+        .map(|handle| db[handle].display(graph, partials).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec![expected], paths);
+
+    drop(reader);
+    let _ = std::fs::remove_file(&db_path);
+}
@@ -84,7 +84,7 @@ impl CreateStackGraph for StackGraph {
     }
 
     fn edge(&mut self, source: Handle<Node>, sink: Handle<Node>) {
-        self.add_edge(source, sink, 0);
+        self.add_edge(source, sink, Precedence::DEFAULT);
     }
 
     fn exported_scope(&mut self, file: Handle<File>, local_id: u32) -> Handle<Node> {
@@ -9,6 +9,7 @@ use controlled_option::ControlledOption;
 use lsp_positions::Offset;
 use lsp_positions::Position;
 use lsp_positions::Span;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::SourceInfo;
 use stack_graphs::graph::StackGraph;
 use std::ops::Range;
@@ -44,8 +45,8 @@ pub fn new() -> StackGraph {
     graph.edge(push_call, scope);
     graph.edge(scope, pop_call);
     graph.edge(scope, root);
-    graph.add_edge(pop_call, jump_to, 1);
-    graph.add_edge(pop_call, drop, 0);
+    graph.add_edge(pop_call, jump_to, Precedence::HIGH);
+    graph.add_edge(pop_call, drop, Precedence::DEFAULT);
     graph.edge(drop, pop_dot);
     graph.edge(pop_dot, def_x);
 
@@ -11,6 +11,7 @@ use stack_graphs::graph::Edge;
 use stack_graphs::graph::File;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::NodeID;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
@@ -149,14 +150,14 @@ pub(crate) fn create_partial_path_and_edges(
     let mut prev = nodes.next().unwrap();
     let mut path = PartialPath::from_node(graph, partials, *prev);
     for next in nodes {
-        graph.add_edge(*prev, *next, 0);
+        graph.add_edge(*prev, *next, Precedence::DEFAULT);
         path.append(
             graph,
             partials,
             Edge {
                 source: *prev,
                 sink: *next,
-                precedence: 0,
+                precedence: Precedence::DEFAULT,
             },
         )?;
         prev = next;
@@ -166,12 +167,12 @@ pub(crate) fn create_partial_path_and_edges(
 }
 
 pub(crate) fn create_edge(graph: &mut StackGraph, contents: NiceEdge) -> Edge {
-    let edge = edge(contents.0, contents.1, 0);
+    let edge = edge(contents.0, contents.1, Precedence::DEFAULT);
     graph.add_edge(edge.source, edge.sink, edge.precedence);
     edge
 }
 
-pub(crate) fn edge(source: Handle<Node>, sink: Handle<Node>, precedence: i32) -> Edge {
+pub(crate) fn edge(source: Handle<Node>, sink: Handle<Node>, precedence: Precedence) -> Edge {
     Edge {
         source,
         sink,
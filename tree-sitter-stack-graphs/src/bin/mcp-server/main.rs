@@ -9,24 +9,94 @@
 //!
 //! This server implements the Model Context Protocol (MCP) to provide definition lookup
 //! capabilities using stack graphs. It accepts requests to find all symbol definitions
-//! referenced within a specific line range of a source file.
+//! referenced within a specific line range of a source file.  Files with unsaved changes are
+//! looked up by passing their contents to `lookup_definitions_in_buffer`, which parses them with
+//! the grammars given with `--grammar`, or those of the tree-sitter configuration.
+//!
+//! Requests and responses are exchanged on standard input and output, and log messages are
+//! written to standard error.  The log level is set with `--log-level`, or with the
+//! `STACK_GRAPHS_MCP_LOG` environment variable, which accepts the same filters as `RUST_LOG`.
+//! Use `--quiet` to only log errors.  Payloads are logged at the `trace` level, with source code
+//! redacted unless `--log-source` is given.
+//!
+//! Errors that clients can recover from have their own JSON-RPC error codes, and a `data` payload
+//! whose `kind` names the error and whose `remediation` tells clients what to do about it:
+//!
+//! | code   | kind               | remediation                                        |
+//! |--------|--------------------|----------------------------------------------------|
+//! | -32001 | `database_missing` | `index` the project, which creates the database    |
+//! | -32002 | `file_not_indexed` | `index` the file                                   |
+//! | -32003 | `stale_index`      | `index` the file, or the project for an old schema |
+//! | -32800 | `cancelled`        | `retry` the request                                |
+//! | -32603 | `internal_error`   | none                                               |
+//!
+//! Files and directories passed to the `index` tool are queued, and indexed in the background by
+//! a single worker thread.  Files that a request fails on because they are not indexed or changed
+//! since they were indexed are queued before all other files, so that the request can be retried
+//! as soon as possible.
 
 use anyhow::{anyhow, Result};
+use base64::Engine;
+use clap::Parser;
+use log::{debug, error, info, trace, warn};
+use lsp_positions::LineIndex;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use stack_graphs::analysis::{
+    callees_of, callers_of, group_references_by_definition, CallHierarchyConfig, CallHierarchyItem,
+};
 use stack_graphs::arena::Handle;
-use stack_graphs::graph::{Node, StackGraph};
+use stack_graphs::graph::{DefinitionKind, Node, StackGraph};
+use stack_graphs::parallel::WorkQueue;
+use stack_graphs::partial::{PartialPath, PartialPaths};
 use stack_graphs::stitching::{DatabaseCandidates, ForwardPartialPathStitcher, StitcherConfig};
-use stack_graphs::storage::SQLiteReader;
-use stack_graphs::NoCancellation;
-use std::collections::HashSet;
+use stack_graphs::storage::{FileStatus, SQLiteReader, SQLiteWriter, StorageError};
+use stack_graphs::{CancellationError, NoCancellation};
+use std::collections::{BTreeMap, HashSet};
 use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
-use tree_sitter_stack_graphs::loader::FileReader;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::cli::index::Indexer;
+use tree_sitter_stack_graphs::cli::util::iter_files_and_directories;
+use tree_sitter_stack_graphs::cli::util::reporter::Reporter;
+use tree_sitter_stack_graphs::loader::{
+    FileReader, Loader, DEFAULT_BUILTINS_PATHS, DEFAULT_TSG_PATHS,
+};
+use tree_sitter_stack_graphs::{FILE_PATH_VAR, ROOT_PATH_VAR};
 
 /// MCP protocol message types
 const JSONRPC_VERSION: &str = "2.0";
 
+/// Environment variable that configures logging, overriding the `--log-level` flag
+const LOG_ENV_VAR: &str = "STACK_GRAPHS_MCP_LOG";
+
+/// Keys of request and response fields that can contain source code
+const SOURCE_KEYS: [&str; 4] = ["content", "definitions", "header", "text"];
+
+/// Command line arguments
+#[derive(Parser)]
+#[clap(about, version)]
+struct Cli {
+    /// Maximum level of log messages: error, warn, info, debug, or trace
+    #[clap(long, default_value = "info")]
+    log_level: log::LevelFilter,
+    /// Only log errors
+    #[clap(short, long, conflicts_with = "log_level")]
+    quiet: bool,
+    /// Include source code in logged payloads, instead of redacting it
+    #[clap(long)]
+    log_source: bool,
+    /// The path to look for the tree-sitter grammars that parse unsaved buffers.
+    /// Can be specified multiple times.  Defaults to the tree-sitter configuration.
+    #[clap(long, value_name = "GRAMMAR_PATH")]
+    grammar: Vec<PathBuf>,
+}
+
 /// Returns the default database path in the current user's local data directory for the
 /// given crate name. Distinct crate names will have distinct database paths.
 fn default_user_database_path_for_crate(crate_name: &str) -> Result<PathBuf> {
@@ -67,6 +137,124 @@ struct JsonRpcError {
     data: Option<Value>,
 }
 
+/// JSON-RPC error code of errors that clients cannot recover from
+const INTERNAL_ERROR: i32 = -32603;
+/// JSON-RPC error code of requests that need a database that does not exist
+const DATABASE_MISSING: i32 = -32001;
+/// JSON-RPC error code of requests for files that are not in the index
+const FILE_NOT_INDEXED: i32 = -32002;
+/// JSON-RPC error code of requests for files whose index is outdated
+const STALE_INDEX: i32 = -32003;
+/// JSON-RPC error code of cancelled requests, as used by the Language Server Protocol
+const REQUEST_CANCELLED: i32 = -32800;
+
+/// Errors that clients can recover from, which are reported with their own error codes and
+/// remediations.  All other errors are reported as internal errors.
+#[derive(Debug, Error)]
+enum McpError {
+    #[error("Database {0} does not exist")]
+    DatabaseMissing(String),
+    #[error("File {0} is not indexed")]
+    FileNotIndexed(String),
+    #[error("File {0} failed to index: {1}")]
+    FileFailedToIndex(String, String),
+    #[error("File {0} changed since it was indexed")]
+    FileChanged(String),
+    #[error("Database has unsupported schema version {0}")]
+    UnsupportedSchema(usize),
+    #[error("Request was cancelled at {0}")]
+    Cancelled(&'static str),
+}
+
+impl McpError {
+    fn to_json_rpc_error(&self) -> JsonRpcError {
+        let (code, data) = match self {
+            Self::DatabaseMissing(database_path) => (
+                DATABASE_MISSING,
+                json!({
+                    "kind": "database_missing",
+                    "database_path": database_path,
+                    "remediation": "index",
+                }),
+            ),
+            Self::FileNotIndexed(file_path) => (
+                FILE_NOT_INDEXED,
+                json!({
+                    "kind": "file_not_indexed",
+                    "file_path": file_path,
+                    "remediation": "index",
+                }),
+            ),
+            Self::FileFailedToIndex(file_path, reason) => (
+                FILE_NOT_INDEXED,
+                json!({
+                    "kind": "file_not_indexed",
+                    "file_path": file_path,
+                    "reason": reason,
+                    "remediation": "index",
+                }),
+            ),
+            Self::FileChanged(file_path) => (
+                STALE_INDEX,
+                json!({
+                    "kind": "stale_index",
+                    "file_path": file_path,
+                    "remediation": "index",
+                }),
+            ),
+            Self::UnsupportedSchema(version) => (
+                STALE_INDEX,
+                json!({
+                    "kind": "stale_index",
+                    "schema_version": version,
+                    "expected_schema_version": stack_graphs::storage::VERSION,
+                    "remediation": "index",
+                }),
+            ),
+            Self::Cancelled(_) => (
+                REQUEST_CANCELLED,
+                json!({
+                    "kind": "cancelled",
+                    "remediation": "retry",
+                }),
+            ),
+        };
+        JsonRpcError {
+            code,
+            message: self.to_string(),
+            data: Some(data),
+        }
+    }
+}
+
+impl From<CancellationError> for McpError {
+    fn from(error: CancellationError) -> Self {
+        Self::Cancelled(error.0)
+    }
+}
+
+/// Converts an error to a JSON-RPC error, with the code and remediation of its kind
+fn json_rpc_error(error: &anyhow::Error) -> JsonRpcError {
+    match error.downcast_ref::<McpError>() {
+        Some(error) => error.to_json_rpc_error(),
+        None => JsonRpcError {
+            code: INTERNAL_ERROR,
+            message: error.to_string(),
+            data: Some(json!({ "kind": "internal_error" })),
+        },
+    }
+}
+
+/// Converts a storage error to an error of the right kind, described with the given context if it
+/// is an internal error
+fn storage_error(error: StorageError, context: &str) -> anyhow::Error {
+    match error {
+        StorageError::Cancelled(at) => McpError::Cancelled(at).into(),
+        StorageError::IncorrectVersion(version) => McpError::UnsupportedSchema(version).into(),
+        error => anyhow!("{}: {}", context, error),
+    }
+}
+
 /// Parameters for the lookup_definitions tool
 #[derive(Debug, Deserialize)]
 struct LookupDefinitionsParams {
@@ -76,6 +264,32 @@ struct LookupDefinitionsParams {
     line_start: usize,
     /// Ending line (1-indexed, inclusive)
     line_end: usize,
+    /// Definition kinds to include (e.g. `function`, `class`); all kinds if empty
+    #[serde(default)]
+    kinds: Vec<String>,
+    /// Whether to explain how each definition was resolved
+    #[serde(default)]
+    explain: bool,
+    /// Whether to include the span, declaration header, and language of each definition
+    #[serde(default)]
+    include_spans: bool,
+    /// Whether to leave out definitions that are only found by speculative paths
+    #[serde(default)]
+    exclude_speculative: bool,
+}
+
+/// Parameters for the lookup_definitions_in_buffer tool
+#[derive(Debug, Deserialize)]
+struct LookupDefinitionsInBufferParams {
+    /// Full contents of the buffer, which replace the contents of the file on disk
+    content: String,
+    /// Root directory of the project, which some languages use to derive module names from file
+    /// paths
+    #[serde(default)]
+    root_path: Option<String>,
+    /// Path of the buffer, line range, and options, as for lookup_definitions
+    #[serde(flatten)]
+    lookup: LookupDefinitionsParams,
 }
 
 /// Response from lookup_definitions
@@ -89,21 +303,282 @@ struct LookupDefinitionsResult {
     definitions_found: usize,
     /// Number of references with no definition
     unresolved_references: usize,
+    /// Files involved in the lookup that contained parse errors when they were indexed, whose
+    /// graphs may be incomplete, so that definitions may be wrong or missing
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    incomplete_files: Vec<String>,
+    /// Unique definitions, with the resolution chain if an explanation was requested, and the
+    /// span, declaration header, and language if spans were requested
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    results: Vec<DefinitionResult>,
+}
+
+/// A definition in the response from lookup_definitions
+#[derive(Debug, Serialize)]
+struct DefinitionResult {
+    /// Symbol of the definition
+    symbol: String,
+    /// Kind of the definition, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    /// Location of the definition, formatted as PATH:LINE:COLUMN
+    location: String,
+    /// Exact span of the definition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<SpanResult>,
+    /// Line of the declaration that contains the definition, without surrounding whitespace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    header: Option<String>,
+    /// Language of the file containing the definition, if it can be determined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// Confidence between 0 and 1 that the reference resolves to the definition, if the rules of
+    /// the language mark some of the edges that were followed as uncertain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f32>,
+    /// Whether the definition was found by a path that visits nodes whose edges the rules of the
+    /// language mark as guesses
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    speculative: bool,
+    /// Intermediate files and symbols traversed to reach the definition, such as the imports
+    /// and re-exports that were followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolution_chain: Option<Vec<ResolutionStep>>,
+}
+
+/// A span in the response from lookup_definitions.  Lines and columns are 1-indexed, and the end
+/// is exclusive.  Columns count graphemes.
+#[derive(Debug, Serialize)]
+struct SpanResult {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// A step in the resolution chain of a definition
+#[derive(Debug, Serialize)]
+struct ResolutionStep {
+    /// Symbol that is pushed or popped in this step
+    symbol: String,
+    /// Location of the step, formatted as PATH:LINE:COLUMN, or PATH if the step has no source
+    location: String,
+}
+
+/// Parameters for the call_hierarchy_incoming and call_hierarchy_outgoing tools
+#[derive(Debug, Deserialize)]
+struct CallHierarchyParams {
+    /// Path to the source file
+    file_path: String,
+    /// Line of the definition or reference (1-indexed)
+    line: usize,
+    /// Column of the definition or reference (1-indexed)
+    column: usize,
+}
+
+/// A caller or callee in the response from the call_hierarchy tools
+#[derive(Debug, Serialize)]
+struct CallHierarchyCall {
+    /// Symbol of the caller or callee
+    symbol: String,
+    /// Kind of the caller or callee, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    /// Location of the caller or callee, formatted as PATH:LINE:COLUMN
+    location: String,
+    /// Locations of the calls, formatted as PATH:LINE:COLUMN
+    call_sites: Vec<String>,
+}
+
+/// Response from the call_hierarchy tools
+#[derive(Debug, Serialize)]
+struct CallHierarchyResult {
+    /// Callers or callees of the definition
+    calls: Vec<CallHierarchyCall>,
+}
+
+/// Parameters for the list_unresolved tool
+#[derive(Debug, Deserialize)]
+struct ListUnresolvedParams {
+    /// Path to the source file
+    file_path: String,
+}
+
+/// An unresolved reference in the response from the list_unresolved tool
+#[derive(Debug, Serialize)]
+struct UnresolvedReference {
+    /// Symbol of the reference
+    symbol: String,
+    /// Location of the reference, formatted as PATH:LINE:COLUMN
+    location: String,
+}
+
+/// Response from the list_unresolved tool
+#[derive(Debug, Serialize)]
+struct ListUnresolvedResult {
+    /// Number of references in the file
+    references_found: usize,
+    /// References with no definition, in source order
+    unresolved: Vec<UnresolvedReference>,
+}
+
+/// Parameters for the list_dependencies tool
+#[derive(Debug, Deserialize)]
+struct ListDependenciesParams {
+    /// Path to the source file
+    file_path: String,
+    /// Whether to include definitions in the source file itself
+    #[serde(default)]
+    include_local: bool,
+}
+
+/// A referenced definition in the response from the list_dependencies tool
+#[derive(Debug, Serialize)]
+struct DependencySymbol {
+    /// Symbol of the definition
+    symbol: String,
+    /// Kind of the definition, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    /// Location of the definition, formatted as PATH:LINE:COLUMN, or PATH if it has no source
+    location: String,
+    /// Locations of the references to the definition, formatted as PATH:LINE:COLUMN
+    references: Vec<String>,
+}
+
+/// A file containing referenced definitions in the response from the list_dependencies tool
+#[derive(Debug, Serialize)]
+struct FileDependency {
+    /// Path of the file
+    file_path: String,
+    /// Referenced definitions in the file, in source order
+    symbols: Vec<DependencySymbol>,
+}
+
+/// Response from the list_dependencies tool
+#[derive(Debug, Serialize)]
+struct ListDependenciesResult {
+    /// Files containing definitions that the references in the source file resolve to, ordered
+    /// by path
+    dependencies: Vec<FileDependency>,
+}
+
+/// Response from the status tool
+#[derive(Debug, Serialize)]
+struct StatusResult {
+    /// Path of the database
+    database_path: String,
+    /// Whether the database exists
+    database_exists: bool,
+    /// Version of the database schema supported by the server
+    schema_version: usize,
+    /// Number of files that were indexed successfully
+    indexed_files: usize,
+    /// Number of indexed files that no longer exist or changed since they were indexed
+    stale_files: usize,
+    /// Number of files that failed to index
+    failed_files: usize,
+    /// Time the database was last modified, in seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_index_time: Option<u64>,
+    /// Resident memory of the server process, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_usage: Option<u64>,
+}
+
+/// Response from the reload tool
+#[derive(Debug, Serialize)]
+struct ReloadResult {
+    /// Path of the database that will be reopened by the next request
+    database_path: String,
+}
+
+/// Parameters for the index tool
+#[derive(Debug, Deserialize)]
+struct IndexParams {
+    /// Paths of the files and directories to index
+    paths: Vec<String>,
+}
+
+/// Response from the index tool
+#[derive(Debug, Serialize)]
+struct IndexResult {
+    /// Number of files that were queued for indexing
+    queued_files: usize,
+    /// Number of files that are waiting to be indexed, including the queued files
+    pending_files: usize,
+}
+
+/// A file that is waiting to be indexed by the index worker
+#[derive(Debug)]
+struct IndexJob {
+    source_root: PathBuf,
+    source_path: PathBuf,
+    strict: bool,
 }
 
 struct McpServer {
     db_path: PathBuf,
     file_reader: FileReader,
+    /// Database reader that is reused between requests, as long as the database is not modified
+    reader: Option<SQLiteReader>,
+    /// Modification time of the database when the reader was opened
+    reader_modified: Option<SystemTime>,
+    /// Whether source code is included in logged payloads
+    log_source: bool,
+    /// Paths of the grammars that parse unsaved buffers, or empty to use the tree-sitter
+    /// configuration
+    grammar_paths: Vec<PathBuf>,
+    /// Loader for the languages of unsaved buffers, which is created when it is first needed
+    loader: Option<Loader>,
+    /// Files that are waiting to be indexed in the background
+    index_queue: Arc<WorkQueue<IndexJob>>,
+    /// Whether the thread that indexes the files in the queue was started
+    index_worker_started: bool,
 }
 
 impl McpServer {
-    fn new(db_path: PathBuf) -> Self {
+    fn new(db_path: PathBuf, log_source: bool, grammar_paths: Vec<PathBuf>) -> Self {
         Self {
             db_path,
             file_reader: FileReader::new(),
+            reader: None,
+            reader_modified: None,
+            log_source,
+            grammar_paths,
+            loader: None,
+            index_queue: Arc::new(WorkQueue::new()),
+            index_worker_started: false,
         }
     }
 
+    /// Returns a database reader.  The reader of a previous request is reused, unless the
+    /// database was modified since it was opened, so that lookups observe newly indexed data.
+    /// Return the reader with [`McpServer::keep_reader`] to reuse it for later requests.
+    fn open_reader(&mut self) -> Result<SQLiteReader> {
+        let modified = database_modified_time(&self.db_path);
+        if let Some(reader) = self.reader.take() {
+            if modified == self.reader_modified {
+                return Ok(reader);
+            }
+            debug!("Database was modified, reopening database={}", self.db_path.display());
+            self.file_reader = FileReader::new();
+        }
+        let reader = SQLiteReader::open(&self.db_path).map_err(|e| match e {
+            StorageError::MissingDatabase(_) => {
+                McpError::DatabaseMissing(self.db_path.display().to_string()).into()
+            }
+            e => storage_error(e, "Failed to open database"),
+        })?;
+        self.reader_modified = modified;
+        Ok(reader)
+    }
+
+    /// Keeps a reader returned by [`McpServer::open_reader`] for later requests
+    fn keep_reader(&mut self, reader: SQLiteReader) {
+        self.reader = Some(reader);
+    }
+
     fn handle_request(&mut self, request: JsonRpcRequest) -> JsonRpcResponse {
         let id = request.id.clone();
 
@@ -126,11 +601,7 @@ impl McpServer {
                 jsonrpc: JSONRPC_VERSION.to_string(),
                 id,
                 result: None,
-                error: Some(JsonRpcError {
-                    code: -32603,
-                    message: e.to_string(),
-                    data: None,
-                }),
+                error: Some(json_rpc_error(&e)),
             },
         }
     }
@@ -169,10 +640,141 @@ impl McpServer {
                             "type": "integer",
                             "description": "Ending line number (1-indexed, inclusive)",
                             "minimum": 1
+                        },
+                        "kinds": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Only include definitions of these kinds, such as module, class, interface, type, function, method, field, or variable"
+                        },
+                        "explain": {
+                            "type": "boolean",
+                            "description": "Include a resolution chain for each definition, listing the intermediate files and symbols, such as imports and re-exports, that were followed to reach it"
+                        },
+                        "include_spans": {
+                            "type": "boolean",
+                            "description": "Include the exact span, the declaration header, and the language of each definition, so that snippets can be highlighted without parsing the files"
+                        },
+                        "exclude_speculative": {
+                            "type": "boolean",
+                            "description": "Leave out definitions that are only found by speculative guesses of the language rules, such as dynamic imports of modules named by strings"
                         }
                     },
                     "required": ["file_path", "line_start", "line_end"]
                 }
+            }, {
+                "name": "lookup_definitions_in_buffer",
+                "description": "Find definitions for all symbols referenced in a line range of an unsaved buffer, whose contents may differ from the indexed source file",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "Path of the source file that the buffer belongs to, which does not need to exist yet"
+                        },
+                        "content": {
+                            "type": "string",
+                            "description": "Full contents of the buffer"
+                        },
+                        "root_path": {
+                            "type": "string",
+                            "description": "Root directory of the project, which some languages use to derive module names from file paths; defaults to the current directory if it contains the file, or the directory of the file otherwise"
+                        },
+                        "line_start": {
+                            "type": "integer",
+                            "description": "Starting line number in the buffer (1-indexed, inclusive)",
+                            "minimum": 1
+                        },
+                        "line_end": {
+                            "type": "integer",
+                            "description": "Ending line number in the buffer (1-indexed, inclusive)",
+                            "minimum": 1
+                        },
+                        "kinds": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Only include definitions of these kinds, such as module, class, interface, type, function, method, field, or variable"
+                        },
+                        "explain": {
+                            "type": "boolean",
+                            "description": "Include a resolution chain for each definition, listing the intermediate files and symbols, such as imports and re-exports, that were followed to reach it"
+                        },
+                        "include_spans": {
+                            "type": "boolean",
+                            "description": "Include the exact span, the declaration header, and the language of each definition, so that snippets can be highlighted without parsing the files"
+                        },
+                        "exclude_speculative": {
+                            "type": "boolean",
+                            "description": "Leave out definitions that are only found by speculative guesses of the language rules, such as dynamic imports of modules named by strings"
+                        }
+                    },
+                    "required": ["file_path", "content", "line_start", "line_end"]
+                }
+            }, {
+                "name": "call_hierarchy_incoming",
+                "description": "Find the functions and methods that call the definition at a position in a source file",
+                "inputSchema": call_hierarchy_schema()
+            }, {
+                "name": "call_hierarchy_outgoing",
+                "description": "Find the functions and methods that are called by the definition at a position in a source file",
+                "inputSchema": call_hierarchy_schema()
+            }, {
+                "name": "status",
+                "description": "Report the status of the index, such as the number of indexed, stale, and failed files, to decide whether re-indexing is needed before relying on lookups",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {}
+                }
+            }, {
+                "name": "reload",
+                "description": "Reopen the database and discard cached data, so that later lookups observe the latest index",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {}
+                }
+            }, {
+                "name": "index",
+                "description": "Queue files or directories for indexing in the background, and report how many files are waiting to be indexed",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Paths to the source files or directories"
+                        }
+                    },
+                    "required": ["paths"]
+                }
+            }, {
+                "name": "list_unresolved",
+                "description": "List the references in a source file that do not resolve to any definition in the index",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "Path to the source file"
+                        }
+                    },
+                    "required": ["file_path"]
+                }
+            }, {
+                "name": "list_dependencies",
+                "description": "List the definitions that the references in a source file resolve to, grouped by the files that contain them, to find exactly the upstream files needed to understand the file",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "Path to the source file"
+                        },
+                        "include_local": {
+                            "type": "boolean",
+                            "description": "Include definitions in the source file itself"
+                        }
+                    },
+                    "required": ["file_path"]
+                }
             }]
         }))
     }
@@ -187,7 +789,8 @@ impl McpServer {
 
         let arguments = params
             .get("arguments")
-            .ok_or_else(|| anyhow!("Missing arguments"))?;
+            .cloned()
+            .unwrap_or_else(|| json!({}));
 
         match tool_name {
             "lookup_definitions" => {
@@ -200,40 +803,261 @@ impl McpServer {
                     }]
                 }))
             }
+            "lookup_definitions_in_buffer" => {
+                let args: LookupDefinitionsInBufferParams =
+                    serde_json::from_value(arguments.clone())?;
+                let result = self.lookup_definitions_in_buffer(args)?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "call_hierarchy_incoming" | "call_hierarchy_outgoing" => {
+                let args: CallHierarchyParams = serde_json::from_value(arguments.clone())?;
+                let result = self.call_hierarchy(args, tool_name == "call_hierarchy_incoming")?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "list_unresolved" => {
+                let args: ListUnresolvedParams = serde_json::from_value(arguments.clone())?;
+                let result = self.list_unresolved(args)?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "list_dependencies" => {
+                let args: ListDependenciesParams = serde_json::from_value(arguments.clone())?;
+                let result = self.list_dependencies(args)?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "status" => {
+                let result = self.status()?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "reload" => {
+                let result = self.reload();
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
+            "index" => {
+                let args: IndexParams = serde_json::from_value(arguments.clone())?;
+                let result = self.index(args)?;
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&result)?
+                    }]
+                }))
+            }
             _ => Err(anyhow!("Unknown tool: {}", tool_name)),
         }
     }
 
     fn lookup_definitions(&mut self, params: LookupDefinitionsParams) -> Result<LookupDefinitionsResult> {
-        // Validate line range
-        if params.line_start > params.line_end {
-            return Err(anyhow!(
-                "Invalid line range: start ({}) > end ({})",
-                params.line_start,
-                params.line_end
-            ));
-        }
+        check_line_range(&params)?;
 
         // Canonicalize the file path
         let file_path = std::fs::canonicalize(&params.file_path)
             .map_err(|e| anyhow!("Failed to resolve file path '{}': {}", params.file_path, e))?;
 
         // Open the database
-        let mut db_reader = SQLiteReader::open(&self.db_path)
-            .map_err(|e| anyhow!("Failed to open database: {}", e))?;
+        let mut db_reader = self.open_reader()?;
+        self.check_indexed(&mut db_reader, &file_path)?;
 
         // Load the graph for this file
         let file_path_str = file_path.to_string_lossy();
         db_reader.load_graph_for_file(&file_path_str)
-            .map_err(|e| anyhow!("Failed to load graph for file: {}", e))?;
+            .map_err(|e| storage_error(e, "Failed to load graph for file"))?;
+
+        let result =
+            self.find_definitions_in_range(&mut db_reader, &file_path_str, &params, None)?;
+        self.keep_reader(db_reader);
+        Ok(result)
+    }
+
+    fn lookup_definitions_in_buffer(
+        &mut self,
+        params: LookupDefinitionsInBufferParams,
+    ) -> Result<LookupDefinitionsResult> {
+        check_line_range(&params.lookup)?;
+
+        // Canonicalize the file path, which may not exist yet for new files
+        let file_path = buffer_path(Path::new(&params.lookup.file_path))?;
+        let file_path_str = file_path.to_string_lossy();
+        let root_path = match &params.root_path {
+            Some(root_path) => std::fs::canonicalize(root_path)
+                .map_err(|e| anyhow!("Failed to resolve root path '{}': {}", root_path, e))?,
+            None => default_root_path(&file_path),
+        };
+
+        // Open the database, and use the globals that the file was indexed with, if any
+        let mut db_reader = self.open_reader()?;
+        let globals = db_reader
+            .globals_for_file(&file_path)
+            .map_err(|e| storage_error(e, "Failed to read globals for file"))?;
+
+        // Build the graph and partial paths of the buffer, which replace the stored ones
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(&file_path_str);
+        let mut partials = PartialPaths::new();
+        let paths = self.build_buffer_graph(
+            &mut graph,
+            file,
+            &mut partials,
+            &file_path,
+            &root_path,
+            &params.content,
+            &globals,
+        )?;
+        db_reader
+            .shadow_file(&graph, file, &mut partials, &paths)
+            .map_err(|e| storage_error(e, "Failed to load graph for buffer"))?;
+
+        let result = self.find_definitions_in_range(
+            &mut db_reader,
+            &file_path_str,
+            &params.lookup,
+            Some(&params.content),
+        )?;
+        // The buffer must not be visible to later requests
+        db_reader.clear();
+        self.keep_reader(db_reader);
+        Ok(result)
+    }
+
+    /// Builds the graph of an unsaved buffer into the given file, and returns the minimal set of
+    /// partial paths of the file.  Parse errors are recovered from, since buffers are often
+    /// incomplete while they are edited.
+    fn build_buffer_graph(
+        &mut self,
+        graph: &mut StackGraph,
+        file: Handle<stack_graphs::graph::File>,
+        partials: &mut PartialPaths,
+        file_path: &Path,
+        root_path: &Path,
+        content: &str,
+        file_globals: &BTreeMap<String, String>,
+    ) -> Result<Vec<PartialPath>> {
+        let mut globals = Variables::new();
+        globals
+            .add(
+                FILE_PATH_VAR.into(),
+                file_path.to_string_lossy().as_ref().into(),
+            )
+            .expect("failed to add file path variable");
+        globals
+            .add(
+                ROOT_PATH_VAR.into(),
+                root_path.to_string_lossy().as_ref().into(),
+            )
+            .expect("failed to add root path variable");
+        for (name, value) in file_globals {
+            // the file and root path set above take precedence
+            globals
+                .add(name.as_str().into(), value.as_str().into())
+                .unwrap_or_default();
+        }
+
+        let loader = self.loader()?;
+        let (injections, detect_similar_paths) = {
+            let lcs = loader
+                .load_for_file(file_path, &mut Some(content), &NoCancellation)
+                .map_err(|e| {
+                    anyhow!(
+                        "Failed to load language for '{}': {}",
+                        file_path.display(),
+                        e
+                    )
+                })?;
+            let lc = lcs
+                .primary
+                .ok_or_else(|| anyhow!("No language found for '{}'", file_path.display()))?;
+            if let Some(language) = lc.name() {
+                graph.set_file_language(file, language);
+            }
+            let mut builder = lc.sgl.builder_into_stack_graph(graph, file, content);
+            builder.set_error_recovery(true);
+            let injections = builder
+                .build_with_injections(&globals, &NoCancellation)
+                .map_err(|e| {
+                    anyhow!("Failed to build graph for '{}': {}", file_path.display(), e)
+                })?;
+            (injections, !lcs.no_similar_paths_in_file())
+        };
+        // injected languages are loaded when they are first needed
+        loader
+            .build_injections_into(
+                graph,
+                file,
+                content,
+                &globals,
+                injections,
+                true,
+                &NoCancellation,
+            )
+            .map_err(|e| anyhow!("Failed to build graph for '{}': {}", file_path.display(), e))?;
+
+        let mut paths = Vec::new();
+        ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+            graph,
+            partials,
+            file,
+            StitcherConfig::default().with_detect_similar_paths(detect_similar_paths),
+            &NoCancellation,
+            |_, _, path| paths.push(path.clone()),
+        )
+        .map_err(|e| anyhow!("Failed to find paths for '{}': {}", file_path.display(), e))?;
+        Ok(paths)
+    }
 
+    /// Returns the loader for the languages of unsaved buffers, which is created on first use
+    fn loader(&mut self) -> Result<&mut Loader> {
+        if self.loader.is_none() {
+            self.loader = Some(create_loader(&self.grammar_paths)?);
+        }
+        Ok(self.loader.as_mut().expect("loader was created"))
+    }
+
+    /// Finds the definitions of the references in the line range of the given file, which must
+    /// be loaded in the reader.  The source of definitions in the file is read from the given
+    /// buffer contents instead of from disk, if any.
+    fn find_definitions_in_range(
+        &mut self,
+        db_reader: &mut SQLiteReader,
+        file_path_str: &str,
+        params: &LookupDefinitionsParams,
+        buffer: Option<&str>,
+    ) -> Result<LookupDefinitionsResult> {
         // Get mutable references to graph, partials, and database
         let (graph, partials, db) = db_reader.get();
 
         // Find the file handle
         let file_handle = graph
             .iter_files()
-            .find(|f| graph[*f].name() == file_path_str.as_ref())
+            .find(|f| graph[*f].name() == file_path_str)
             .ok_or_else(|| anyhow!("File not found in graph: {}", file_path_str))?;
 
         // Find all reference nodes in the line range (convert to 0-indexed)
@@ -247,10 +1071,18 @@ impl McpServer {
             line_end_0,
         );
 
-        eprintln!("Found {} references in range", references.len());
+        debug!("references_found={} file={}", references.len(), file_path_str);
+
+        // Only include definitions of the requested kinds, if any
+        let kinds = params
+            .kinds
+            .iter()
+            .map(|kind| DefinitionKind::from_syntax_type(kind))
+            .collect::<HashSet<_>>();
 
         // Find definitions for each reference
         let mut definition_sources = Vec::new();
+        let mut definition_results = Vec::new();
         let mut unresolved_count = 0;
         let mut seen_definitions = HashSet::new();
 
@@ -263,10 +1095,31 @@ impl McpServer {
                 vec![*reference],
                 StitcherConfig::default(),
                 &NoCancellation,
-                |g, _p, path| {
+                |g, p, path| {
                     // path.end_node is the definition
                     let definition_node = path.end_node;
 
+                    // Skip definitions that are not of the requested kinds
+                    let kind = g.definition_kind(definition_node);
+                    if !kinds.is_empty() && !kind.as_ref().map_or(false, |k| kinds.contains(k)) {
+                        found_definition = true;
+                        return;
+                    }
+
+                    // Skip speculative definitions if only reliable ones are requested
+                    let speculative = path.is_speculative(g, p);
+                    if speculative && params.exclude_speculative {
+                        found_definition = true;
+                        return;
+                    }
+
+                    // Skip definitions whose confidence is unknown, because the path visits
+                    // nodes that are not loaded
+                    let confidence = match path.confidence(g, p) {
+                        Ok(confidence) => confidence,
+                        Err(_) => return,
+                    };
+
                     // Get source info for the definition
                     if let Some(source_info) = g.source_info(definition_node) {
                         // Get the file from the node ID
@@ -296,10 +1149,12 @@ impl McpServer {
                             .map(|s| g[s].to_string())
                             .unwrap_or_else(|| "<unknown>".to_string());
 
-                        // Read the definition source code
+                        // Read the definition source code, from the buffer if it is in the file
+                        let def_buffer = buffer.filter(|_| def_file_path == file_path_str);
                         let def_source = self.extract_definition_source(
                             Path::new(def_file_path),
                             &source_info.span,
+                            def_buffer,
                         ).unwrap_or_else(|e| {
                             format!("// Error reading source: {}", e)
                         });
@@ -312,21 +1167,75 @@ impl McpServer {
                             source_info.span.start.column.grapheme_offset + 1
                         );
 
-                        let formatted_definition = format!(
-                            "// Symbol: {}\n// Location: {}\n{}",
-                            symbol_name,
-                            location,
-                            def_source
-                        );
+                        let mut formatted_definition = format!("// Symbol: {}\n", symbol_name);
+                        if let Some(kind) = &kind {
+                            formatted_definition += &format!("// Kind: {}\n", kind);
+                        }
+                        if let Some(confidence) = confidence {
+                            formatted_definition +=
+                                &format!("// Confidence: {:.0}%\n", confidence * 100.0);
+                        }
+                        if speculative {
+                            formatted_definition += "// Speculative: true\n";
+                        }
+                        formatted_definition += &format!("// Location: {}\n{}", location, def_source);
 
                         definition_sources.push(formatted_definition);
+                        if params.explain || params.include_spans {
+                            let (span, header, language) = if params.include_spans {
+                                let span = &source_info.span;
+                                let header = match source_info.containing_line.into_option() {
+                                    Some(line) => Some(g[line].trim().to_string()),
+                                    None => self
+                                        .extract_definition_source(
+                                            Path::new(def_file_path),
+                                            &lsp_positions::Span {
+                                                start: span.start.clone(),
+                                                end: span.start.clone(),
+                                            },
+                                            def_buffer,
+                                        )
+                                        .ok()
+                                        .map(|line| line.trim().to_string()),
+                                };
+                                (
+                                    Some(SpanResult {
+                                        start_line: span.start.line + 1,
+                                        start_column: span.start.column.grapheme_offset + 1,
+                                        end_line: span.end.line + 1,
+                                        end_column: span.end.column.grapheme_offset + 1,
+                                    }),
+                                    header,
+                                    g.file_language(def_file_handle)
+                                        .map(str::to_string)
+                                        .or_else(|| language_for_path(Path::new(def_file_path))),
+                                )
+                            } else {
+                                (None, None, None)
+                            };
+                            definition_results.push(DefinitionResult {
+                                symbol: symbol_name,
+                                kind: kind.as_ref().map(|kind| kind.to_string()),
+                                location,
+                                span,
+                                header,
+                                language,
+                                confidence,
+                                speculative,
+                                resolution_chain: if params.explain {
+                                    Some(resolution_chain(g, p, path))
+                                } else {
+                                    None
+                                },
+                            });
+                        }
                         found_definition = true;
                     }
                 },
             );
 
             if let Err(e) = result {
-                eprintln!("Error finding definition for reference: {}", e);
+                warn!("Error finding definition for reference: {}", e);
             }
 
             if !found_definition {
@@ -334,6 +1243,19 @@ impl McpServer {
             }
         }
 
+        // Files with parse errors were indexed in error recovery mode, and have incomplete graphs
+        let mut incomplete_files = std::iter::once(file_path_str.to_string())
+            .chain(seen_definitions.iter().map(|(file, _, _)| file.clone()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|file| {
+                graph
+                    .get_file(file)
+                    .map_or(false, |file| !graph.parse_errors(file).is_empty())
+            })
+            .collect::<Vec<_>>();
+        incomplete_files.sort();
+
         // Concatenate all definitions with separators
         let concatenated = if definition_sources.is_empty() {
             String::new()
@@ -346,9 +1268,294 @@ impl McpServer {
             references_found: references.len(),
             definitions_found: seen_definitions.len(),
             unresolved_references: unresolved_count,
+            incomplete_files,
+            results: definition_results,
+        })
+    }
+
+    fn call_hierarchy(&mut self, params: CallHierarchyParams, incoming: bool) -> Result<CallHierarchyResult> {
+        if params.line == 0 || params.column == 0 {
+            return Err(anyhow!(
+                "Invalid position: line and column are 1-indexed, got {}:{}",
+                params.line,
+                params.column
+            ));
+        }
+
+        // Canonicalize the file path
+        let file_path = std::fs::canonicalize(&params.file_path)
+            .map_err(|e| anyhow!("Failed to resolve file path '{}': {}", params.file_path, e))?;
+
+        // Convert the grapheme column to a position with all offsets
+        let content = self.file_reader.get(&file_path)?;
+        let position = LineIndex::new(content)
+            .position_for_line_and_grapheme(params.line - 1, params.column - 1)
+            .ok_or_else(|| anyhow!("Position {}:{} out of range", params.line, params.column))?;
+
+        // Open the database
+        let mut db_reader = self.open_reader()?;
+        self.check_indexed(&mut db_reader, &file_path)?;
+
+        let file_path_str = file_path.to_string_lossy();
+        let config = CallHierarchyConfig::default();
+        let items = if incoming {
+            callers_of(&mut db_reader, &file_path_str, &position, &config, &NoCancellation)
+        } else {
+            callees_of(&mut db_reader, &file_path_str, &position, &config, &NoCancellation)
+        }
+        .map_err(|e| storage_error(e, "Failed to find call hierarchy"))?;
+        self.keep_reader(db_reader);
+
+        Ok(CallHierarchyResult {
+            calls: items.iter().map(call_hierarchy_call).collect(),
+        })
+    }
+
+    fn status(&mut self) -> Result<StatusResult> {
+        let mut result = StatusResult {
+            database_path: self.db_path.display().to_string(),
+            database_exists: self.db_path.exists(),
+            schema_version: stack_graphs::storage::VERSION,
+            indexed_files: 0,
+            stale_files: 0,
+            failed_files: 0,
+            last_index_time: None,
+            memory_usage: resident_memory(),
+        };
+        if !result.database_exists {
+            return Ok(result);
+        }
+
+        result.last_index_time = database_modified_time(&self.db_path)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        let mut db_reader = self.open_reader()?;
+        let mut files = db_reader.list_all()?;
+        for entry in files.try_iter()? {
+            let entry = entry?;
+            match entry.status {
+                FileStatus::Missing => {}
+                FileStatus::Indexed => {
+                    result.indexed_files += 1;
+                    let is_stale = match std::fs::read_to_string(&entry.path) {
+                        Ok(content) => sha1(&content) != entry.tag,
+                        Err(_) => true,
+                    };
+                    if is_stale {
+                        result.stale_files += 1;
+                    }
+                }
+                FileStatus::Error(_) => result.failed_files += 1,
+            }
+        }
+        drop(files);
+        self.keep_reader(db_reader);
+
+        Ok(result)
+    }
+
+    fn reload(&mut self) -> ReloadResult {
+        info!("Reloading database={}", self.db_path.display());
+        self.reader = None;
+        self.file_reader = FileReader::new();
+        ReloadResult {
+            database_path: self.db_path.display().to_string(),
+        }
+    }
+
+    fn index(&mut self, params: IndexParams) -> Result<IndexResult> {
+        let paths = params
+            .paths
+            .iter()
+            .map(|path| {
+                std::fs::canonicalize(path)
+                    .map_err(|e| anyhow!("Failed to resolve path '{}': {}", path, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut queued_files = 0;
+        for (source_root, source_path, strict) in iter_files_and_directories(paths) {
+            self.index_queue.push(IndexJob {
+                source_root,
+                source_path,
+                strict,
+            });
+            queued_files += 1;
+        }
+        self.start_index_worker();
+        info!("Queued {} files for indexing", queued_files);
+        Ok(IndexResult {
+            queued_files,
+            pending_files: self.index_queue.len(),
+        })
+    }
+
+    /// Checks that a file is indexed, and that it did not change since it was indexed.  If it is
+    /// not, the file is queued for indexing before all other files that are waiting.
+    fn check_indexed(&mut self, db_reader: &mut SQLiteReader, file_path: &Path) -> Result<()> {
+        let result = check_indexed(db_reader, file_path);
+        if let Err(e) = &result {
+            if matches!(
+                e.downcast_ref::<McpError>(),
+                Some(McpError::FileNotIndexed(_)) | Some(McpError::FileChanged(_))
+            ) {
+                let job = IndexJob {
+                    source_root: default_root_path(file_path),
+                    source_path: file_path.to_path_buf(),
+                    strict: true,
+                };
+                self.index_queue
+                    .prioritize_matching(job, |a, b| a.source_path == b.source_path);
+                self.start_index_worker();
+                info!("Prioritized indexing file={}", file_path.display());
+            }
+        }
+        result
+    }
+
+    /// Starts the thread that indexes the files in the queue, unless it was already started
+    fn start_index_worker(&mut self) {
+        if self.index_worker_started {
+            return;
+        }
+        self.index_worker_started = true;
+        let db_path = self.db_path.clone();
+        let grammar_paths = self.grammar_paths.clone();
+        let queue = self.index_queue.clone();
+        thread::spawn(move || {
+            if let Err(e) = run_index_worker(&db_path, &grammar_paths, &queue) {
+                error!("Index worker stopped: {}", e);
+            }
+        });
+    }
+
+    fn list_unresolved(&mut self, params: ListUnresolvedParams) -> Result<ListUnresolvedResult> {
+        // Canonicalize the file path
+        let file_path = std::fs::canonicalize(&params.file_path)
+            .map_err(|e| anyhow!("Failed to resolve file path '{}': {}", params.file_path, e))?;
+
+        // Open the database
+        let mut db_reader = self.open_reader()?;
+        self.check_indexed(&mut db_reader, &file_path)?;
+
+        // Load the graph for this file
+        let file_path_str = file_path.to_string_lossy();
+        db_reader.load_graph_for_file(&file_path_str)
+            .map_err(|e| storage_error(e, "Failed to load graph for file"))?;
+
+        let (graph, partials, db) = db_reader.get();
+
+        let file_handle = graph
+            .iter_files()
+            .find(|f| graph[*f].name() == file_path_str.as_ref())
+            .ok_or_else(|| anyhow!("File not found in graph: {}", file_path_str))?;
+
+        // Collect all references in the file, in source order
+        let mut references = self.find_references_in_range(&graph, file_handle, 0, usize::MAX);
+        references.sort_by_key(|reference| {
+            graph
+                .source_info(*reference)
+                .map(|source_info| {
+                    (
+                        source_info.span.start.line,
+                        source_info.span.start.column.utf8_offset,
+                    )
+                })
+        });
+
+        let mut unresolved = Vec::new();
+        for reference in &references {
+            let mut found_definition = false;
+            ForwardPartialPathStitcher::find_all_complete_partial_paths(
+                &mut DatabaseCandidates::new(graph, partials, db),
+                vec![*reference],
+                StitcherConfig::default(),
+                &NoCancellation,
+                |_, _, _| found_definition = true,
+            )
+            .map_err(McpError::from)?;
+            if found_definition {
+                continue;
+            }
+
+            let symbol = graph[*reference]
+                .symbol()
+                .map(|s| graph[s].to_string())
+                .unwrap_or_else(|| "<unknown>".to_string());
+            let location = match graph.source_info(*reference) {
+                Some(source_info) => format!(
+                    "{}:{}:{}",
+                    file_path_str,
+                    source_info.span.start.line + 1,
+                    source_info.span.start.column.grapheme_offset + 1
+                ),
+                None => file_path_str.to_string(),
+            };
+            unresolved.push(UnresolvedReference { symbol, location });
+        }
+
+        self.keep_reader(db_reader);
+
+        Ok(ListUnresolvedResult {
+            references_found: references.len(),
+            unresolved,
         })
     }
 
+    fn list_dependencies(&mut self, params: ListDependenciesParams) -> Result<ListDependenciesResult> {
+        // Canonicalize the file path
+        let file_path = std::fs::canonicalize(&params.file_path)
+            .map_err(|e| anyhow!("Failed to resolve file path '{}': {}", params.file_path, e))?;
+
+        // Open the database
+        let mut db_reader = self.open_reader()?;
+        self.check_indexed(&mut db_reader, &file_path)?;
+
+        let file_path_str = file_path.to_string_lossy();
+        let groups = group_references_by_definition(&mut db_reader, &file_path_str, &NoCancellation)
+            .map_err(|e| storage_error(e, "Failed to resolve references"))?;
+        self.keep_reader(db_reader);
+
+        let format_location = |file: &Path, span: &lsp_positions::Span| {
+            format!(
+                "{}:{}:{}",
+                file.display(),
+                span.start.line + 1,
+                span.start.column.grapheme_offset + 1
+            )
+        };
+        let mut dependencies: Vec<FileDependency> = Vec::new();
+        for group in groups {
+            if !group.external && !params.include_local {
+                continue;
+            }
+            let symbol = DependencySymbol {
+                symbol: group.symbol,
+                kind: group.kind.as_ref().map(|kind| kind.to_string()),
+                location: match &group.span {
+                    Some(span) => format_location(&group.file, span),
+                    None => group.file.display().to_string(),
+                },
+                references: group
+                    .references
+                    .iter()
+                    .map(|span| format_location(&file_path, span))
+                    .collect(),
+            };
+            // groups are ordered by file, so all symbols of a file are adjacent
+            let file = group.file.display().to_string();
+            match dependencies.last_mut() {
+                Some(dependency) if dependency.file_path == file => dependency.symbols.push(symbol),
+                _ => dependencies.push(FileDependency {
+                    file_path: file,
+                    symbols: vec![symbol],
+                }),
+            }
+        }
+
+        Ok(ListDependenciesResult { dependencies })
+    }
+
     fn find_references_in_range(
         &self,
         graph: &StackGraph,
@@ -384,8 +1591,12 @@ impl McpServer {
         &mut self,
         file_path: &Path,
         span: &lsp_positions::Span,
+        buffer: Option<&str>,
     ) -> Result<String> {
-        let content = self.file_reader.get(file_path)?;
+        let content = match buffer {
+            Some(buffer) => buffer,
+            None => self.file_reader.get(file_path)?,
+        };
         let lines: Vec<&str> = content.lines().collect();
 
         let start_line = span.start.line;
@@ -406,10 +1617,8 @@ impl McpServer {
     fn run(&mut self) -> Result<()> {
         let stdin = io::stdin();
         let mut stdout = io::stdout();
-        let mut stderr = io::stderr();
 
-        writeln!(stderr, "Stack Graphs MCP Server starting...")?;
-        writeln!(stderr, "Database: {}", self.db_path.display())?;
+        info!("Stack Graphs MCP Server starting database={}", self.db_path.display());
 
         for line in stdin.lock().lines() {
             let line = line?;
@@ -418,12 +1627,11 @@ impl McpServer {
                 continue;
             }
 
-            writeln!(stderr, "Received: {}", line)?;
-
             // Parse the request
             let request: JsonRpcRequest = match serde_json::from_str(&line) {
                 Ok(req) => req,
                 Err(e) => {
+                    warn!("Failed to parse request: {}", e);
                     let error_response = JsonRpcResponse {
                         jsonrpc: JSONRPC_VERSION.to_string(),
                         id: None,
@@ -441,12 +1649,28 @@ impl McpServer {
                 }
             };
 
+            debug!("request method={} id={}", request.method, display_id(&request.id));
+            if log::log_enabled!(log::Level::Trace) {
+                let params = request.params.clone().unwrap_or(Value::Null);
+                trace!("request params={}", self.loggable(params));
+            }
+
             // Handle the request
             let response = self.handle_request(request);
 
             // Send the response
             let response_json = serde_json::to_string(&response)?;
-            writeln!(stderr, "Sending: {}", response_json)?;
+            match &response.error {
+                Some(error) => warn!(
+                    "response id={} error={}",
+                    display_id(&response.id),
+                    error.message
+                ),
+                None => debug!("response id={}", display_id(&response.id)),
+            }
+            if log::log_enabled!(log::Level::Trace) {
+                trace!("response payload={}", self.loggable(serde_json::to_value(&response)?));
+            }
             writeln!(stdout, "{}", response_json)?;
             stdout.flush()?;
         }
@@ -455,9 +1679,341 @@ impl McpServer {
     }
 }
 
+/// Returns the input schema of the call_hierarchy tools
+fn call_hierarchy_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "file_path": {
+                "type": "string",
+                "description": "Path to the source file"
+            },
+            "line": {
+                "type": "integer",
+                "description": "Line number of the definition or a reference to it (1-indexed)",
+                "minimum": 1
+            },
+            "column": {
+                "type": "integer",
+                "description": "Column number of the definition or a reference to it (1-indexed)",
+                "minimum": 1
+            }
+        },
+        "required": ["file_path", "line", "column"]
+    })
+}
+
+/// Creates a loader for the grammars at the given paths, or those of the tree-sitter
+/// configuration if no paths are given
+fn create_loader(grammar_paths: &[PathBuf]) -> Result<Loader> {
+    if !grammar_paths.is_empty() {
+        Loader::from_paths(
+            grammar_paths.to_vec(),
+            None,
+            DEFAULT_TSG_PATHS.clone(),
+            DEFAULT_BUILTINS_PATHS.clone(),
+        )
+    } else {
+        let loader_config = tree_sitter_config::Config::load(None)
+            .and_then(|v| v.get())
+            .map_err(|e| anyhow!("Failed to load tree-sitter configuration: {}", e))?;
+        Loader::from_tree_sitter_configuration(
+            &loader_config,
+            None,
+            DEFAULT_TSG_PATHS.clone(),
+            DEFAULT_BUILTINS_PATHS.clone(),
+        )
+    }
+    .map_err(|e| anyhow!("Failed to create language loader: {}", e))
+}
+
+/// Indexes the files in the queue for as long as the server runs.  The database, loader, and
+/// indexer are created once, and used for all files.
+fn run_index_worker(
+    db_path: &Path,
+    grammar_paths: &[PathBuf],
+    queue: &WorkQueue<IndexJob>,
+) -> Result<()> {
+    let mut db =
+        SQLiteWriter::open(db_path).map_err(|e| anyhow!("Failed to open database: {}", e))?;
+    let mut loader = create_loader(grammar_paths)?;
+    let reporter = LogReporter;
+    let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
+    loop {
+        match queue.pop() {
+            Some(job) => {
+                if let Err(e) = indexer.index_file(
+                    &job.source_root,
+                    &job.source_path,
+                    job.strict,
+                    &NoCancellation,
+                ) {
+                    warn!("Failed to index file={}: {}", job.source_path.display(), e);
+                }
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+/// Reports the files indexed by the index worker in the log
+struct LogReporter;
+
+impl Reporter for LogReporter {
+    fn skipped(&self, path: &Path, summary: &str, _details: Option<&dyn std::fmt::Display>) {
+        debug!("Skipped indexing file={}: {}", path.display(), summary);
+    }
+
+    fn started(&self, path: &Path) {
+        debug!("Indexing file={}", path.display());
+    }
+
+    fn succeeded(&self, path: &Path, summary: &str, _details: Option<&dyn std::fmt::Display>) {
+        info!("Indexed file={}: {}", path.display(), summary);
+    }
+
+    fn failed(&self, path: &Path, summary: &str, _details: Option<&dyn std::fmt::Display>) {
+        warn!("Failed to index file={}: {}", path.display(), summary);
+    }
+
+    fn cancelled(&self, path: &Path, summary: &str, _details: Option<&dyn std::fmt::Display>) {
+        warn!("Cancelled indexing file={}: {}", path.display(), summary);
+    }
+}
+
+/// Returns the time the database was last modified.  The database is written in WAL mode, so
+/// recent changes may only be in the WAL file.
+fn database_modified_time(db_path: &Path) -> Option<SystemTime> {
+    let mut wal_path = db_path.to_path_buf().into_os_string();
+    wal_path.push("-wal");
+    [db_path.to_path_buf(), PathBuf::from(wal_path)]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+/// Checks that the line range of a lookup is not empty
+fn check_line_range(params: &LookupDefinitionsParams) -> Result<()> {
+    if params.line_start > params.line_end {
+        return Err(anyhow!(
+            "Invalid line range: start ({}) > end ({})",
+            params.line_start,
+            params.line_end
+        ));
+    }
+    Ok(())
+}
+
+/// Canonicalizes the path of a buffer.  The file of the buffer may not exist yet, in which case
+/// only its directory is canonicalized.
+fn buffer_path(file_path: &Path) -> Result<PathBuf> {
+    if let Ok(file_path) = std::fs::canonicalize(file_path) {
+        return Ok(file_path);
+    }
+    let (dir, name) = match (file_path.parent(), file_path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return Err(anyhow!("Invalid file path '{}'", file_path.display())),
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let dir = std::fs::canonicalize(dir)
+        .map_err(|e| anyhow!("Failed to resolve file path '{}': {}", file_path.display(), e))?;
+    Ok(dir.join(name))
+}
+
+/// Returns the root directory of the project of a buffer, when none is given: the current
+/// directory if it contains the file, or the directory of the file otherwise
+fn default_root_path(file_path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .and_then(std::fs::canonicalize)
+        .ok()
+        .filter(|dir| file_path.starts_with(dir))
+        .or_else(|| file_path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// Checks that a file is indexed, and that it did not change since it was indexed, so that the
+/// positions in its graph match its content
+fn check_indexed(db_reader: &mut SQLiteReader, file_path: &Path) -> Result<()> {
+    let file = file_path.to_string_lossy();
+    match db_reader
+        .status_for_file(&file, None::<&str>)
+        .map_err(|e| storage_error(e, "Failed to get file status"))?
+    {
+        FileStatus::Indexed => {}
+        FileStatus::Missing => return Err(McpError::FileNotIndexed(file.to_string()).into()),
+        FileStatus::Error(error) => {
+            return Err(McpError::FileFailedToIndex(file.to_string(), error).into())
+        }
+    }
+    let content = std::fs::read_to_string(file_path)?;
+    match db_reader
+        .status_for_file(&file, Some(sha1(&content)))
+        .map_err(|e| storage_error(e, "Failed to get file status"))?
+    {
+        FileStatus::Indexed => Ok(()),
+        _ => Err(McpError::FileChanged(file.to_string()).into()),
+    }
+}
+
+/// Returns the hash of a file's content, as used by the indexer to tag files in the database
+fn sha1(value: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(value);
+    base64::prelude::BASE64_STANDARD_NO_PAD.encode(hasher.finalize())
+}
+
+/// Returns the resident memory of the current process in bytes, if it can be determined
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Returns the name of the language of a source file, based on its extension
+fn language_for_path(path: &Path) -> Option<String> {
+    let language = match path.extension()?.to_str()? {
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" => "cpp",
+        "cs" => "csharp",
+        "go" => "go",
+        "java" => "java",
+        "js" | "jsx" | "mjs" | "cjs" => "javascript",
+        "py" | "pyi" => "python",
+        "rb" => "ruby",
+        "rs" => "rust",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        _ => return None,
+    };
+    Some(language.to_string())
+}
+
+/// Returns the steps that explain how a complete path resolves its reference, collapsing
+/// consecutive steps for the same symbol in the same file
+fn resolution_chain(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    path: &PartialPath,
+) -> Vec<ResolutionStep> {
+    let mut steps = Vec::new();
+    let mut previous: Option<(String, String)> = None;
+    for node in path.explain(graph, partials) {
+        let symbol = match graph[node].symbol() {
+            Some(symbol) => graph[symbol].to_string(),
+            None => continue,
+        };
+        let file = match graph[node].file() {
+            Some(file) => graph[file].name().to_string(),
+            None => continue,
+        };
+        if previous.as_ref().map_or(false, |(s, f)| *s == symbol && *f == file) {
+            continue;
+        }
+        let location = match graph.source_info(node) {
+            Some(source_info) if source_info.span != lsp_positions::Span::default() => format!(
+                "{}:{}:{}",
+                file,
+                source_info.span.start.line + 1,
+                source_info.span.start.column.grapheme_offset + 1
+            ),
+            _ => file.clone(),
+        };
+        steps.push(ResolutionStep {
+            symbol: symbol.clone(),
+            location,
+        });
+        previous = Some((symbol, file));
+    }
+    steps
+}
+
+fn call_hierarchy_call(item: &CallHierarchyItem) -> CallHierarchyCall {
+    let format_location = |file: &Path, span: &lsp_positions::Span| {
+        format!(
+            "{}:{}:{}",
+            file.display(),
+            span.start.line + 1,
+            span.start.column.grapheme_offset + 1
+        )
+    };
+    CallHierarchyCall {
+        symbol: item.symbol.clone(),
+        kind: item.kind.as_ref().map(|kind| kind.to_string()),
+        location: match &item.span {
+            Some(span) => format_location(&item.file, span),
+            None => item.file.display().to_string(),
+        },
+        call_sites: item
+            .call_sites
+            .iter()
+            .map(|call_site| format_location(&call_site.file, &call_site.span))
+            .collect(),
+    }
+}
+
+impl McpServer {
+    /// Returns a payload for logging, with source code redacted unless it should be logged
+    fn loggable(&self, mut payload: Value) -> Value {
+        if !self.log_source {
+            redact_source(&mut payload);
+        }
+        payload
+    }
+}
+
+/// Replaces strings that can contain source code with a placeholder
+fn redact_source(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                match field {
+                    Value::String(text) if SOURCE_KEYS.contains(&key.as_str()) => {
+                        *text = format!("<redacted {} bytes>", text.len());
+                    }
+                    _ => redact_source(field),
+                }
+            }
+        }
+        Value::Array(elements) => elements.iter_mut().for_each(redact_source),
+        _ => {}
+    }
+}
+
+/// Formats a request ID for logging
+fn display_id(id: &Option<Value>) -> String {
+    match id {
+        Some(id) => id.to_string(),
+        None => "null".to_string(),
+    }
+}
+
 fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let default_level = if cli.quiet {
+        log::LevelFilter::Error
+    } else {
+        cli.log_level
+    };
+    env_logger::Builder::new()
+        .filter_level(default_level)
+        .parse_env(LOG_ENV_VAR)
+        .target(env_logger::Target::Stderr)
+        .init();
+
     let db_path = default_user_database_path_for_crate(env!("CARGO_PKG_NAME"))?;
 
-    let mut server = McpServer::new(db_path);
+    let mut server = McpServer::new(db_path, cli.log_source, cli.grammar);
     server.run()
 }
@@ -57,19 +57,30 @@
 //! }
 //! ```
 
+pub mod bench;
 pub mod clean;
+pub mod coverage;
 pub mod database;
+pub mod export;
+pub mod extract;
 pub mod index;
 pub mod init;
 pub mod load;
+pub mod lsif;
 #[cfg(feature = "lsp")]
 pub mod lsp;
+pub mod maintain;
 pub mod r#match;
 pub mod parse;
+pub mod profile;
 pub mod query;
+pub mod sarif;
+pub mod stats;
 pub mod status;
+pub mod tags;
 pub mod test;
 pub mod util;
+pub mod verify;
 pub mod visualize;
 
 pub mod path_loading {
@@ -77,53 +88,105 @@ pub mod path_loading {
 
     use clap::Subcommand;
 
+    use crate::cli::bench::BenchArgs;
     use crate::cli::clean::CleanArgs;
+    use crate::cli::coverage::CoverageArgs;
     use crate::cli::database::DatabaseArgs;
+    use crate::cli::export::ExportArgs;
+    use crate::cli::extract::ExtractArgs;
     use crate::cli::index::IndexArgs;
     use crate::cli::init::InitArgs;
     use crate::cli::load::PathLoaderArgs;
+    use crate::cli::lsif::ImportLsifArgs;
     #[cfg(feature = "lsp")]
     use crate::cli::lsp::LspArgs;
+    use crate::cli::maintain::MaintainArgs;
     use crate::cli::parse::ParseArgs;
+    use crate::cli::profile::ProfileArgs;
     use crate::cli::query::QueryArgs;
     use crate::cli::r#match::MatchArgs;
+    use crate::cli::sarif::SarifArgs;
+    use crate::cli::stats::StatsArgs;
     use crate::cli::status::StatusArgs;
+    use crate::cli::tags::TagsArgs;
     use crate::cli::test::TestArgs;
+    use crate::cli::verify::VerifyArgs;
     use crate::cli::visualize::VisualizeArgs;
 
     #[derive(Subcommand)]
     pub enum Subcommands {
+        Bench(Bench),
         Clean(Clean),
+        Coverage(Coverage),
+        Export(Export),
+        Extract(Extract),
+        ImportLsif(ImportLsif),
         Index(Index),
         Init(Init),
         #[cfg(feature = "lsp")]
         Lsp(Lsp),
+        Maintain(Maintain),
         Match(Match),
         Parse(Parse),
+        Profile(Profile),
         Query(Query),
+        Sarif(Sarif),
+        Stats(Stats),
         Status(Status),
+        Tags(Tags),
         Test(Test),
+        Verify(Verify),
         Visualize(Visualize),
     }
 
     impl Subcommands {
         pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
             match self {
+                Self::Bench(cmd) => cmd.run(default_db_path),
                 Self::Clean(cmd) => cmd.run(default_db_path),
+                Self::Coverage(cmd) => cmd.run(),
+                Self::Export(cmd) => cmd.run(default_db_path),
+                Self::Extract(cmd) => cmd.run(default_db_path),
+                Self::ImportLsif(cmd) => cmd.run(default_db_path),
                 Self::Index(cmd) => cmd.run(default_db_path),
                 Self::Init(cmd) => cmd.run(),
                 #[cfg(feature = "lsp")]
                 Self::Lsp(cmd) => cmd.run(default_db_path),
+                Self::Maintain(cmd) => cmd.run(default_db_path),
                 Self::Match(cmd) => cmd.run(),
                 Self::Parse(cmd) => cmd.run(),
+                Self::Profile(cmd) => cmd.run(),
                 Self::Query(cmd) => cmd.run(default_db_path),
+                Self::Sarif(cmd) => cmd.run(default_db_path),
+                Self::Stats(cmd) => cmd.run(default_db_path),
                 Self::Status(cmd) => cmd.run(default_db_path),
+                Self::Tags(cmd) => cmd.run(default_db_path),
                 Self::Test(cmd) => cmd.run(),
+                Self::Verify(cmd) => cmd.run(default_db_path),
                 Self::Visualize(cmd) => cmd.run(default_db_path),
             }
         }
     }
 
+    /// Benchmark indexing and resolution on a corpus.
+    #[derive(clap::Parser)]
+    pub struct Bench {
+        #[clap(flatten)]
+        load_args: PathLoaderArgs,
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        bench_args: BenchArgs,
+    }
+
+    impl Bench {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let loader = self.load_args.get()?;
+            let db_path = self.db_args.get_or(default_db_path);
+            self.bench_args.run(&db_path, loader)
+        }
+    }
+
     /// Clean the indexing database.
     #[derive(clap::Parser)]
     pub struct Clean {
@@ -140,6 +203,70 @@ pub mod path_loading {
         }
     }
 
+    /// Measure how many identifiers the stack graph rules cover.
+    #[derive(clap::Parser)]
+    pub struct Coverage {
+        #[clap(flatten)]
+        load_args: PathLoaderArgs,
+        #[clap(flatten)]
+        coverage_args: CoverageArgs,
+    }
+
+    impl Coverage {
+        pub fn run(self) -> anyhow::Result<()> {
+            let loader = self.load_args.get()?;
+            self.coverage_args.run(loader)
+        }
+    }
+
+    /// Export the database to Neo4j.
+    #[derive(clap::Parser)]
+    pub struct Export {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        export_args: ExportArgs,
+    }
+
+    impl Export {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.export_args.run(&db_path)
+        }
+    }
+
+    /// Extract the contexts of definitions as JSON lines, for code search or embedding.
+    #[derive(clap::Parser)]
+    pub struct Extract {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        extract_args: ExtractArgs,
+    }
+
+    impl Extract {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.extract_args.run(&db_path)
+        }
+    }
+
+    /// Import an LSIF dump into the database, as an approximate stack graph.
+    #[derive(clap::Parser)]
+    pub struct ImportLsif {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        import_lsif_args: ImportLsifArgs,
+    }
+
+    impl ImportLsif {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.import_lsif_args.run(&db_path)
+        }
+    }
+
     /// Index source files into the database.
     #[derive(clap::Parser)]
     pub struct Index {
@@ -193,6 +320,22 @@ pub mod path_loading {
         }
     }
 
+    /// Vacuum, analyze, and reindex the database when needed.
+    #[derive(clap::Parser)]
+    pub struct Maintain {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        maintain_args: MaintainArgs,
+    }
+
+    impl Maintain {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.maintain_args.run(&db_path)
+        }
+    }
+
     /// Match stanza queries against a source file.
     #[derive(clap::Parser)]
     pub struct Match {
@@ -225,6 +368,22 @@ pub mod path_loading {
         }
     }
 
+    /// Attribute graph construction cost to the stanzas of the stack graph rules.
+    #[derive(clap::Parser)]
+    pub struct Profile {
+        #[clap(flatten)]
+        load_args: PathLoaderArgs,
+        #[clap(flatten)]
+        profile_args: ProfileArgs,
+    }
+
+    impl Profile {
+        pub fn run(self) -> anyhow::Result<()> {
+            let loader = self.load_args.get()?;
+            self.profile_args.run(loader)
+        }
+    }
+
     /// Query the database to resolve references.
     #[derive(clap::Parser)]
     pub struct Query {
@@ -241,6 +400,38 @@ pub mod path_loading {
         }
     }
 
+    /// Write a SARIF log of unresolved references and files that could not be indexed.
+    #[derive(clap::Parser)]
+    pub struct Sarif {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        sarif_args: SarifArgs,
+    }
+
+    impl Sarif {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.sarif_args.run(&db_path)
+        }
+    }
+
+    /// Show statistics about the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Stats {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        stats_args: StatsArgs,
+    }
+
+    impl Stats {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.stats_args.run(&db_path)
+        }
+    }
+
     /// Show indexing status for source files.
     #[derive(clap::Parser)]
     pub struct Status {
@@ -257,6 +448,22 @@ pub mod path_loading {
         }
     }
 
+    /// Write a ctags or etags file for the definitions in the database.
+    #[derive(clap::Parser)]
+    pub struct Tags {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        tags_args: TagsArgs,
+    }
+
+    impl Tags {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.tags_args.run(&db_path)
+        }
+    }
+
     /// Run test files and show results.
     #[derive(clap::Parser)]
     pub struct Test {
@@ -273,6 +480,22 @@ pub mod path_loading {
         }
     }
 
+    /// Verify the integrity of the database, and plan repairs.
+    #[derive(clap::Parser)]
+    pub struct Verify {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        verify_args: VerifyArgs,
+    }
+
+    impl Verify {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.verify_args.run(&db_path)
+        }
+    }
+
     /// Visualize command
     #[derive(clap::Parser)]
     pub struct Visualize {
@@ -295,33 +518,55 @@ pub mod provided_languages {
 
     use clap::Subcommand;
 
+    use crate::cli::bench::BenchArgs;
     use crate::cli::clean::CleanArgs;
+    use crate::cli::coverage::CoverageArgs;
     use crate::cli::database::DatabaseArgs;
+    use crate::cli::export::ExportArgs;
+    use crate::cli::extract::ExtractArgs;
     use crate::cli::index::IndexArgs;
     use crate::cli::init::InitArgs;
     use crate::cli::load::LanguageConfigurationsLoaderArgs;
+    use crate::cli::lsif::ImportLsifArgs;
     #[cfg(feature = "lsp")]
     use crate::cli::lsp::LspArgs;
+    use crate::cli::maintain::MaintainArgs;
     use crate::cli::parse::ParseArgs;
+    use crate::cli::profile::ProfileArgs;
     use crate::cli::query::QueryArgs;
     use crate::cli::r#match::MatchArgs;
+    use crate::cli::sarif::SarifArgs;
+    use crate::cli::stats::StatsArgs;
     use crate::cli::status::StatusArgs;
+    use crate::cli::tags::TagsArgs;
     use crate::cli::test::TestArgs;
+    use crate::cli::verify::VerifyArgs;
     use crate::cli::visualize::VisualizeArgs;
     use crate::loader::LanguageConfiguration;
 
     #[derive(Subcommand)]
     pub enum Subcommands {
+        Bench(Bench),
         Clean(Clean),
+        Coverage(Coverage),
+        Export(Export),
+        Extract(Extract),
+        ImportLsif(ImportLsif),
         Index(Index),
         Init(Init),
         #[cfg(feature = "lsp")]
         Lsp(Lsp),
+        Maintain(Maintain),
         Match(Match),
         Parse(Parse),
+        Profile(Profile),
         Query(Query),
+        Sarif(Sarif),
+        Stats(Stats),
         Status(Status),
+        Tags(Tags),
         Test(Test),
+        Verify(Verify),
         Visualize(Visualize),
     }
 
@@ -332,21 +577,55 @@ pub mod provided_languages {
             configurations: Vec<LanguageConfiguration>,
         ) -> anyhow::Result<()> {
             match self {
+                Self::Bench(cmd) => cmd.run(default_db_path, configurations),
                 Self::Clean(cmd) => cmd.run(default_db_path),
+                Self::Coverage(cmd) => cmd.run(configurations),
+                Self::Export(cmd) => cmd.run(default_db_path),
+                Self::Extract(cmd) => cmd.run(default_db_path),
+                Self::ImportLsif(cmd) => cmd.run(default_db_path),
                 Self::Index(cmd) => cmd.run(default_db_path, configurations),
                 Self::Init(cmd) => cmd.run(),
                 #[cfg(feature = "lsp")]
                 Self::Lsp(cmd) => cmd.run(default_db_path, configurations),
+                Self::Maintain(cmd) => cmd.run(default_db_path),
                 Self::Match(cmd) => cmd.run(configurations),
                 Self::Parse(cmd) => cmd.run(configurations),
+                Self::Profile(cmd) => cmd.run(configurations),
                 Self::Query(cmd) => cmd.run(default_db_path),
+                Self::Sarif(cmd) => cmd.run(default_db_path),
+                Self::Stats(cmd) => cmd.run(default_db_path),
                 Self::Status(cmd) => cmd.run(default_db_path),
+                Self::Tags(cmd) => cmd.run(default_db_path),
                 Self::Test(cmd) => cmd.run(configurations),
+                Self::Verify(cmd) => cmd.run(default_db_path),
                 Self::Visualize(cmd) => cmd.run(default_db_path),
             }
         }
     }
 
+    /// Benchmark indexing and resolution on a corpus.
+    #[derive(clap::Parser)]
+    pub struct Bench {
+        #[clap(flatten)]
+        load_args: LanguageConfigurationsLoaderArgs,
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        bench_args: BenchArgs,
+    }
+
+    impl Bench {
+        pub fn run(
+            self,
+            default_db_path: PathBuf,
+            configurations: Vec<LanguageConfiguration>,
+        ) -> anyhow::Result<()> {
+            let loader = self.load_args.get(configurations)?;
+            let db_path = self.db_args.get_or(default_db_path);
+            self.bench_args.run(&db_path, loader)
+        }
+    }
+
     /// Clean the indexing database.
     #[derive(clap::Parser)]
     pub struct Clean {
@@ -363,6 +642,70 @@ pub mod provided_languages {
         }
     }
 
+    /// Measure how many identifiers the stack graph rules cover.
+    #[derive(clap::Parser)]
+    pub struct Coverage {
+        #[clap(flatten)]
+        load_args: LanguageConfigurationsLoaderArgs,
+        #[clap(flatten)]
+        coverage_args: CoverageArgs,
+    }
+
+    impl Coverage {
+        pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
+            let loader = self.load_args.get(configurations)?;
+            self.coverage_args.run(loader)
+        }
+    }
+
+    /// Export the database to Neo4j.
+    #[derive(clap::Parser)]
+    pub struct Export {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        export_args: ExportArgs,
+    }
+
+    impl Export {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.export_args.run(&db_path)
+        }
+    }
+
+    /// Extract the contexts of definitions as JSON lines, for code search or embedding.
+    #[derive(clap::Parser)]
+    pub struct Extract {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        extract_args: ExtractArgs,
+    }
+
+    impl Extract {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.extract_args.run(&db_path)
+        }
+    }
+
+    /// Import an LSIF dump into the database, as an approximate stack graph.
+    #[derive(clap::Parser)]
+    pub struct ImportLsif {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        import_lsif_args: ImportLsifArgs,
+    }
+
+    impl ImportLsif {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.import_lsif_args.run(&db_path)
+        }
+    }
+
     /// Index source files into the database.
     #[derive(clap::Parser)]
     pub struct Index {
@@ -424,6 +767,22 @@ pub mod provided_languages {
         }
     }
 
+    /// Vacuum, analyze, and reindex the database when needed.
+    #[derive(clap::Parser)]
+    pub struct Maintain {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        maintain_args: MaintainArgs,
+    }
+
+    impl Maintain {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.maintain_args.run(&db_path)
+        }
+    }
+
     /// Match stanza queries against a source file.
     #[derive(clap::Parser)]
     pub struct Match {
@@ -456,6 +815,22 @@ pub mod provided_languages {
         }
     }
 
+    /// Attribute graph construction cost to the stanzas of the stack graph rules.
+    #[derive(clap::Parser)]
+    pub struct Profile {
+        #[clap(flatten)]
+        load_args: LanguageConfigurationsLoaderArgs,
+        #[clap(flatten)]
+        profile_args: ProfileArgs,
+    }
+
+    impl Profile {
+        pub fn run(self, configurations: Vec<LanguageConfiguration>) -> anyhow::Result<()> {
+            let loader = self.load_args.get(configurations)?;
+            self.profile_args.run(loader)
+        }
+    }
+
     /// Query the database to resolve references.
     #[derive(clap::Parser)]
     pub struct Query {
@@ -472,6 +847,38 @@ pub mod provided_languages {
         }
     }
 
+    /// Write a SARIF log of unresolved references and files that could not be indexed.
+    #[derive(clap::Parser)]
+    pub struct Sarif {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        sarif_args: SarifArgs,
+    }
+
+    impl Sarif {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.sarif_args.run(&db_path)
+        }
+    }
+
+    /// Show statistics about the indexing database.
+    #[derive(clap::Parser)]
+    pub struct Stats {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        stats_args: StatsArgs,
+    }
+
+    impl Stats {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.stats_args.run(&db_path)
+        }
+    }
+
     /// Show indexing status for source files.
     #[derive(clap::Parser)]
     pub struct Status {
@@ -488,6 +895,22 @@ pub mod provided_languages {
         }
     }
 
+    /// Write a ctags or etags file for the definitions in the database.
+    #[derive(clap::Parser)]
+    pub struct Tags {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        tags_args: TagsArgs,
+    }
+
+    impl Tags {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.tags_args.run(&db_path)
+        }
+    }
+
     /// Run test files and show results.
     #[derive(clap::Parser)]
     pub struct Test {
@@ -504,6 +927,22 @@ pub mod provided_languages {
         }
     }
 
+    /// Verify the integrity of the database, and plan repairs.
+    #[derive(clap::Parser)]
+    pub struct Verify {
+        #[clap(flatten)]
+        db_args: DatabaseArgs,
+        #[clap(flatten)]
+        verify_args: VerifyArgs,
+    }
+
+    impl Verify {
+        pub fn run(self, default_db_path: PathBuf) -> anyhow::Result<()> {
+            let db_path = self.db_args.get_or(default_db_path);
+            self.verify_args.run(&db_path)
+        }
+    }
+
     /// Visualize command
     #[derive(clap::Parser)]
     pub struct Visualize {
@@ -0,0 +1,270 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueHint;
+use serde_json::json;
+use stack_graphs::stats::FrequencyDistribution;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::SQLiteWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::cli::index::Indexer;
+use crate::cli::index::IndexingSummary;
+use crate::cli::query::Querier;
+use crate::cli::util::reporter::ConsoleReporter;
+use crate::cli::util::ExistingPathBufValueParser;
+use crate::cli::util::SourcePosition;
+use crate::loader::Loader;
+use crate::CancellationFlag;
+use crate::NoCancellation;
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Source file or directory paths of the corpus to index.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+        value_parser = ExistingPathBufValueParser,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Reference source positions to resolve, formatted as PATH:LINE:COLUMN. If none are given,
+    /// references are sampled from the indexed corpus.
+    #[clap(long = "query", value_name = "SOURCE_POSITION", value_parser)]
+    pub queries: Vec<SourcePosition>,
+
+    /// Maximum number of references that are sampled from the corpus.
+    #[clap(long, value_name = "COUNT", default_value_t = 100)]
+    pub max_queries: usize,
+
+    /// Number of times every query is run.
+    #[clap(long, value_name = "COUNT", default_value_t = 1)]
+    pub iterations: usize,
+
+    /// Reuse files that are already indexed, instead of indexing the whole corpus again.
+    #[clap(long)]
+    pub cached: bool,
+
+    /// Number of files to index in parallel. Defaults to the number of available CPUs.
+    #[clap(long, short = 'j', value_name = "JOBS")]
+    pub jobs: Option<usize>,
+
+    /// Write the JSON report to the given file, instead of printing it.
+    #[clap(long, short = 'o', value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub output: Option<PathBuf>,
+}
+
+impl BenchArgs {
+    pub fn new(source_paths: Vec<PathBuf>) -> Self {
+        Self {
+            source_paths,
+            queries: Vec::new(),
+            max_queries: 100,
+            iterations: 1,
+            cached: false,
+            jobs: None,
+            output: None,
+        }
+    }
+
+    pub fn run(self, db_path: &Path, mut loader: Loader) -> anyhow::Result<()> {
+        let source_paths = self
+            .source_paths
+            .iter()
+            .map(|p| p.canonicalize())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let reporter = ConsoleReporter::none();
+        let mut report = BenchmarkReport::default();
+
+        {
+            let mut db = SQLiteWriter::open(&db_path)?;
+            let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
+            indexer.force = !self.cached;
+            indexer.jobs = self.jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+            });
+            report.index(&mut indexer, &source_paths, &NoCancellation)?;
+        }
+
+        let mut db = SQLiteReader::open(&db_path)?;
+        let queries = if self.queries.is_empty() {
+            sample_references(&mut db, &source_paths, self.max_queries)?
+        } else {
+            let mut queries = self.queries;
+            for query in &mut queries {
+                query.canonicalize()?;
+            }
+            queries
+        };
+        let mut querier = Querier::new(&mut db, &reporter);
+        for _ in 0..self.iterations {
+            report.query(&mut querier, &queries, &NoCancellation)?;
+        }
+
+        let json = serde_json::to_string_pretty(&report.to_json())?;
+        match self.output {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&path, json)?;
+            }
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+}
+
+/// Returns the positions of up to `max_references` references in the indexed files under the
+/// given source paths.  The references are spread evenly over the corpus, and are returned in
+/// a deterministic order, so that runs on the same corpus are comparable.
+pub fn sample_references(
+    db: &mut SQLiteReader,
+    source_paths: &[PathBuf],
+    max_references: usize,
+) -> anyhow::Result<Vec<SourcePosition>> {
+    let mut files = Vec::new();
+    for source_path in source_paths {
+        let mut entries = db.list_file_or_directory(source_path)?;
+        for entry in entries.try_iter()? {
+            files.push(entry?.path);
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut references = Vec::new();
+    for path in files {
+        db.load_graph_for_file(&path.to_string_lossy())?;
+        let (graph, _, _) = db.get();
+        let file = match graph.get_file(&path.to_string_lossy()) {
+            Some(file) => file,
+            None => continue,
+        };
+        for node in graph.nodes_for_file(file) {
+            if !graph[node].is_reference() {
+                continue;
+            }
+            if let Some(source_info) = graph.source_info(node) {
+                references.push(SourcePosition {
+                    path: path.clone(),
+                    line: source_info.span.start.line,
+                    column: source_info.span.start.column.grapheme_offset,
+                });
+            }
+        }
+    }
+    references.sort_by(|a, b| (&a.path, a.line, a.column).cmp(&(&b.path, b.line, b.column)));
+
+    if references.len() <= max_references {
+        return Ok(references);
+    }
+    let count = references.len();
+    Ok((0..max_references)
+        .map(|i| references[i * count / max_references].clone())
+        .collect())
+}
+
+/// The results of a benchmark run.
+#[derive(Clone, Debug, Default)]
+pub struct BenchmarkReport {
+    /// The summary of indexing the corpus.
+    pub indexing: IndexingSummary,
+    /// The distribution of the time in milliseconds it took to index a file.
+    pub file_indexing_times: FrequencyDistribution<u128>,
+    /// The number of queries that were run.
+    pub queries: usize,
+    /// The total number of definitions found by the queries.
+    pub definitions: usize,
+    /// Total time spent running queries.
+    pub query_elapsed: Duration,
+    /// The distribution of the time in microseconds it took to run a query.
+    pub query_latencies: FrequencyDistribution<u128>,
+}
+
+impl BenchmarkReport {
+    /// Indexes the given source paths with the indexer, and records the indexing times.
+    pub fn index(
+        &mut self,
+        indexer: &mut Indexer,
+        source_paths: &[PathBuf],
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> anyhow::Result<()> {
+        indexer.set_collect_stats(true);
+        indexer.index_all(source_paths, None::<&Path>, cancellation_flag)?;
+        self.indexing = indexer.summary().clone();
+        self.file_indexing_times += indexer.stats().file_indexing_times;
+        Ok(())
+    }
+
+    /// Resolves the references at the given positions with the querier, and records the query
+    /// latencies.
+    pub fn query(
+        &mut self,
+        querier: &mut Querier,
+        references: &[SourcePosition],
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> anyhow::Result<()> {
+        for reference in references {
+            let start = Instant::now();
+            let results = querier.definitions(reference.clone(), cancellation_flag)?;
+            let elapsed = start.elapsed();
+            self.queries += 1;
+            self.definitions += results.iter().map(|r| r.targets.len()).sum::<usize>();
+            self.query_elapsed += elapsed;
+            self.query_latencies.record(elapsed.as_micros());
+        }
+        Ok(())
+    }
+
+    /// Returns the report as JSON, which can be stored to track performance across versions.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "indexing": {
+                "files": self.indexing.indexed,
+                "skipped": self.indexing.skipped,
+                "failed": self.indexing.failed.len(),
+                "elapsed_ms": self.indexing.elapsed.as_millis() as u64,
+                "files_per_second": per_second(self.indexing.indexed, self.indexing.elapsed),
+                "file_time_ms": percentiles(&self.file_indexing_times),
+            },
+            "queries": {
+                "count": self.queries,
+                "definitions": self.definitions,
+                "elapsed_ms": self.query_elapsed.as_millis() as u64,
+                "queries_per_second": per_second(self.queries, self.query_elapsed),
+                "latency_us": percentiles(&self.query_latencies),
+            },
+        })
+    }
+}
+
+fn per_second(count: usize, elapsed: Duration) -> Option<f64> {
+    if elapsed.is_zero() {
+        return None;
+    }
+    Some(count as f64 / elapsed.as_secs_f64())
+}
+
+fn percentiles(distribution: &FrequencyDistribution<u128>) -> serde_json::Value {
+    let quantiles = distribution.quantiles(100);
+    if quantiles.is_empty() {
+        return serde_json::Value::Null;
+    }
+    json!({
+        "p50": *quantiles[50] as u64,
+        "p90": *quantiles[90] as u64,
+        "p99": *quantiles[99] as u64,
+        "max": *quantiles[100] as u64,
+    })
+}
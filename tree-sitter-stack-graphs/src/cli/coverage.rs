@@ -0,0 +1,185 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueHint;
+use serde_json::json;
+use stack_graphs::graph::StackGraph;
+use std::path::Path;
+use std::path::PathBuf;
+use tree_sitter::Parser;
+use tree_sitter_graph::Variables;
+
+use crate::cli::util::iter_files_and_directories;
+use crate::cli::util::ExistingPathBufValueParser;
+use crate::coverage::FileCoverage;
+use crate::loader::FileReader;
+use crate::loader::Loader;
+use crate::NoCancellation;
+use crate::FILE_PATH_VAR;
+use crate::ROOT_PATH_VAR;
+
+/// The number of uncovered constructs that are listed in the plain output.
+const MAX_UNCOVERED_CONSTRUCTS: usize = 20;
+
+#[derive(Args)]
+pub struct CoverageArgs {
+    /// Source file or directory paths.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+        value_parser = ExistingPathBufValueParser,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Print the coverage as JSON, including the location of every uncovered identifier.
+    #[clap(long)]
+    pub json: bool,
+}
+
+impl CoverageArgs {
+    pub fn run(self, mut loader: Loader) -> anyhow::Result<()> {
+        let mut file_reader = FileReader::new();
+        let mut files = Vec::new();
+        for (source_root, source_path, strict) in iter_files_and_directories(&self.source_paths) {
+            match Self::file_coverage(&mut loader, &mut file_reader, &source_root, &source_path) {
+                Ok(Some(coverage)) => files.push((source_path, coverage)),
+                Ok(None) if strict => {
+                    return Err(anyhow!(
+                        "No stack graph language found for {}",
+                        source_path.display()
+                    ))
+                }
+                Ok(None) => {}
+                Err(err) => eprintln!("Skipped {}: {}", source_path.display(), err),
+            }
+        }
+
+        let mut total = FileCoverage::default();
+        for (_, coverage) in &files {
+            total.add(coverage);
+        }
+        if self.json {
+            let value = json!({
+                "files": files
+                    .iter()
+                    .map(|(path, coverage)| {
+                        let mut value = coverage_to_json(coverage);
+                        value["path"] = json!(path.to_string_lossy());
+                        value["uncovered"] = coverage
+                            .uncovered
+                            .iter()
+                            .map(|identifier| {
+                                json!({
+                                    "text": identifier.text,
+                                    "kind": identifier.kind,
+                                    "parent_kind": identifier.parent_kind,
+                                    "line": identifier.start.row + 1,
+                                    "column": identifier.start.column + 1,
+                                })
+                            })
+                            .collect();
+                        value
+                    })
+                    .collect::<Vec<_>>(),
+                "total": coverage_to_json(&total),
+            });
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        } else {
+            println!(
+                "| {:^40} | {:^11} | {:^11} | {:^11} | {:^11} | {:^8} |",
+                "file", "identifiers", "covered", "references", "definitions", "coverage",
+            );
+            println!(
+                "|------------------------------------------|-------------|-------------|-------------|-------------|----------|"
+            );
+            for (path, coverage) in &files {
+                print_coverage_row(&path.display().to_string(), coverage);
+            }
+            print_coverage_row("total", &total);
+
+            let mut constructs = total.uncovered_constructs().into_iter().collect::<Vec<_>>();
+            constructs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if !constructs.is_empty() {
+                println!();
+                println!("Constructs with the most uncovered identifiers:");
+                for (construct, count) in constructs.into_iter().take(MAX_UNCOVERED_CONSTRUCTS) {
+                    println!("  {:>8}  {}", count, construct);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the coverage of a file, or returns `None` if there is no language for it.
+    fn file_coverage(
+        loader: &mut Loader,
+        file_reader: &mut FileReader,
+        source_root: &Path,
+        source_path: &Path,
+    ) -> anyhow::Result<Option<FileCoverage>> {
+        let lc = match loader
+            .load_for_file(source_path, file_reader, &NoCancellation)?
+            .primary
+        {
+            Some(lc) => lc,
+            None => return Ok(None),
+        };
+        let source = file_reader.get(source_path)?;
+
+        let mut parser = Parser::new();
+        parser.set_language(&lc.language)?;
+        let tree = parser
+            .parse(source, None)
+            .ok_or_else(|| anyhow!("failed to parse"))?;
+
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(&source_path.to_string_lossy());
+        let mut globals = Variables::new();
+        globals
+            .add(
+                FILE_PATH_VAR.into(),
+                source_path.to_string_lossy().into_owned().into(),
+            )
+            .expect("failed to add file path variable");
+        globals
+            .add(
+                ROOT_PATH_VAR.into(),
+                source_root.to_string_lossy().into_owned().into(),
+            )
+            .expect("failed to add root path variable");
+        lc.sgl
+            .build_stack_graph_into(&mut graph, file, source, &globals, &NoCancellation)?;
+
+        Ok(Some(FileCoverage::compute(&tree, source, &graph, file)))
+    }
+}
+
+fn coverage_to_json(coverage: &FileCoverage) -> serde_json::Value {
+    json!({
+        "identifiers": coverage.identifiers,
+        "covered": coverage.covered(),
+        "references": coverage.references,
+        "definitions": coverage.definitions,
+        "ratio": coverage.ratio(),
+        "uncovered_constructs": coverage.uncovered_constructs(),
+    })
+}
+
+fn print_coverage_row(title: &str, coverage: &FileCoverage) {
+    println!(
+        "| {:>40} | {:>11} | {:>11} | {:>11} | {:>11} | {:>7.1}% |",
+        title,
+        coverage.identifiers,
+        coverage.covered(),
+        coverage.references,
+        coverage.definitions,
+        coverage.ratio() * 100.0,
+    );
+}
@@ -16,6 +16,7 @@ pub struct DatabaseArgs {
     #[clap(
         long,
         short = 'D',
+        visible_alias = "db",
         value_name = "DATABASE_PATH",
         value_hint = ValueHint::AnyPath,
     )]
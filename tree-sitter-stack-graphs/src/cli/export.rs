@@ -0,0 +1,116 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::Node;
+use stack_graphs::serde::NoFilter;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::NoCancellation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Export database to Neo4j
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Source file or directory paths.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Output path.  For the CSV format, this is a directory where `nodes.csv` and
+    /// `relationships.csv` are written.
+    #[clap(
+        long,
+        short = 'o',
+        value_name = "OUTPUT_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub output: PathBuf,
+
+    /// Export format.
+    #[clap(long, value_enum, default_value_t = ExportFormat::Cypher)]
+    pub format: ExportFormat,
+
+    /// Resolve all references in the selected files, and export the resolutions.
+    #[clap(long)]
+    pub resolutions: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Script of Cypher statements.
+    Cypher,
+    /// CSV files for the Neo4j bulk importer.
+    Csv,
+}
+
+impl ExportArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let cancellation_flag = &NoCancellation;
+        let mut db = SQLiteReader::open(&db_path)?;
+        for source_path in &self.source_paths {
+            let source_path = source_path.canonicalize()?;
+            db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?;
+        }
+        let resolutions = if self.resolutions {
+            Self::resolve_all_references(&mut db)?
+        } else {
+            Vec::new()
+        };
+        let (graph, _, _) = db.get();
+        match self.format {
+            ExportFormat::Cypher => {
+                if let Some(dir) = self.output.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(
+                    &self.output,
+                    graph.to_cypher_string(&resolutions, &NoFilter),
+                )?;
+            }
+            ExportFormat::Csv => {
+                let csv = graph.to_neo4j_csv(&resolutions, &NoFilter);
+                std::fs::create_dir_all(&self.output)?;
+                std::fs::write(self.output.join("nodes.csv"), csv.nodes)?;
+                std::fs::write(self.output.join("relationships.csv"), csv.relationships)?;
+            }
+        }
+        println!("Export at {}", self.output.display());
+        Ok(())
+    }
+
+    fn resolve_all_references(
+        db: &mut SQLiteReader,
+    ) -> anyhow::Result<Vec<(Handle<Node>, Handle<Node>)>> {
+        let (graph, _, _) = db.get();
+        let starting_nodes = graph
+            .iter_nodes()
+            .filter(|n| graph[*n].is_reference())
+            .collect::<Vec<_>>();
+        let stitcher_config = StitcherConfig::default()
+            // always detect similar paths, we don't know the language configurations for the data in the database
+            .with_detect_similar_paths(true);
+        let mut resolutions = Vec::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            db,
+            starting_nodes,
+            stitcher_config,
+            &NoCancellation,
+            |_, _, p| {
+                resolutions.push((p.start_node, p.end_node));
+            },
+        )?;
+        Ok(resolutions)
+    }
+}
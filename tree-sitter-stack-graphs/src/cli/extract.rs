@@ -0,0 +1,98 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::ArgGroup;
+use clap::Args;
+use clap::ValueHint;
+use serde_json::json;
+use stack_graphs::analysis::symbol_contexts;
+use stack_graphs::analysis::SymbolContext;
+use stack_graphs::storage::SQLiteReader;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::util::indexed_files;
+use crate::loader::FileReader;
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("paths")
+        .required(true)
+        .args(&["source_paths", "all"]),
+))]
+pub struct ExtractArgs {
+    /// Source file or directory paths to extract definitions from.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Extract definitions from all indexed source paths.
+    #[clap(long, short = 'a')]
+    pub all: bool,
+
+    /// Do not read the sources of files to extract doc comments.
+    #[clap(long)]
+    pub no_docs: bool,
+
+    /// Write the JSON lines to the given file, instead of printing them.
+    #[clap(long, short = 'o', value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub output: Option<PathBuf>,
+}
+
+impl ExtractArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let mut db = SQLiteReader::open(&db_path)?;
+        let files = indexed_files(&mut db, &self.source_paths, self.all)?;
+
+        let mut output: Box<dyn Write> = match &self.output {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            }
+            None => Box::new(std::io::stdout().lock()),
+        };
+        let mut file_reader = FileReader::new();
+        for file in files {
+            let source = if self.no_docs {
+                None
+            } else {
+                file_reader.get(Path::new(&file)).ok()
+            };
+            for context in symbol_contexts(&mut db, &file, source)? {
+                writeln!(output, "{}", context_to_json(&context))?;
+            }
+            db.clear();
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts a symbol context to JSON, using 1-based lines and (grapheme) columns.
+fn context_to_json(context: &SymbolContext) -> serde_json::Value {
+    json!({
+        "qualified_name": context.qualified_name,
+        "symbol": context.symbol,
+        "kind": context.kind.as_ref().map(|kind| kind.to_string()),
+        "signature": context.signature,
+        "doc": context.doc,
+        "path": context.file.to_string_lossy(),
+        "start": {
+            "line": context.span.start.line + 1,
+            "column": context.span.start.column.grapheme_offset + 1,
+        },
+        "end": {
+            "line": context.span.end.line + 1,
+            "column": context.span.end.column.grapheme_offset + 1,
+        },
+    })
+}
@@ -9,24 +9,44 @@ use clap::Args;
 use clap::ValueHint;
 use stack_graphs::arena::Handle;
 use stack_graphs::graph::File;
+use stack_graphs::graph::Fingerprint;
+use stack_graphs::graph::Node;
 use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::stats::FrequencyDistribution;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::PartialPathsConfig;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::chunks::GraphChunks;
 use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::LanguageRules;
+use stack_graphs::storage::SQLiteBatchWriter;
 use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::storage::StorageError;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::IsTerminal;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 use thiserror::Error;
 use tree_sitter_graph::Variables;
 
+use crate::build_injections_into;
 use crate::cli::util::duration_from_seconds_str;
+use crate::cli::util::global_from_str;
 use crate::cli::util::iter_files_and_directories;
 use crate::cli::util::print_indexing_stats;
+use crate::cli::util::print_indexing_summary;
 use crate::cli::util::reporter::ConsoleReporter;
 use crate::cli::util::reporter::Level;
 use crate::cli::util::reporter::Reporter;
@@ -35,13 +55,19 @@ use crate::cli::util::wait_for_input;
 use crate::cli::util::BuildErrorWithSource;
 use crate::cli::util::CLIFileReporter;
 use crate::cli::util::ExistingPathBufValueParser;
+use crate::cli::util::ProgressBar;
 use crate::loader::FileLanguageConfigurations;
+use crate::loader::FileLanguages;
 use crate::loader::FileReader;
+use crate::loader::LoadError;
 use crate::loader::Loader;
+use crate::loader::SkipReason;
 use crate::BuildError;
 use crate::CancelAfterDuration;
 use crate::CancellationFlag;
+use crate::GraphChunkSink;
 use crate::NoCancellation;
+use crate::StackGraphLanguage;
 use crate::{FILE_PATH_VAR, ROOT_PATH_VAR};
 
 #[derive(Args)]
@@ -75,6 +101,16 @@ pub struct IndexArgs {
     #[clap(long)]
     pub hide_error_details: bool,
 
+    /// Maximum size of files to index in bytes. Larger files are skipped.
+    #[clap(long, value_name = "BYTES")]
+    pub max_file_size: Option<u64>,
+
+    /// Build the graphs of files larger than this size in bytes in chunks, which are written to
+    /// a temporary file while the graph is built. This bounds the memory needed for the stack
+    /// graphs of huge, e.g. machine-generated, files.
+    #[clap(long, value_name = "BYTES")]
+    pub streaming_threshold: Option<u64>,
+
     /// Maximum runtime per file in seconds.
     #[clap(
         long,
@@ -83,12 +119,53 @@ pub struct IndexArgs {
     )]
     pub max_file_time: Option<Duration>,
 
+    /// Global variable passed to the stack graph construction rules of all files. Files are
+    /// indexed again if the variables they were indexed with change.
+    #[clap(
+        long = "global",
+        value_name = "NAME=VALUE",
+        value_parser = global_from_str,
+    )]
+    pub globals: Vec<(String, String)>,
+
+    /// Number of files to index in parallel. Defaults to the number of available CPUs.
+    #[clap(long, short = 'j', value_name = "JOBS")]
+    pub jobs: Option<usize>,
+
+    /// Do not show a progress bar while indexing.
+    #[clap(long)]
+    pub no_progress: bool,
+
+    /// Pre-resolve references that can only resolve to definitions in their own file, so that
+    /// queries for them do not need any path stitching.
+    #[clap(long)]
+    pub local_resolution: bool,
+
+    /// Index files with parse errors instead of failing them. The graphs of such files are
+    /// incomplete, and the regions with parse errors are recorded in the database, so that
+    /// queries can flag results that involve them.
+    #[clap(long)]
+    pub error_recovery: bool,
+
     #[clap(long)]
     pub stats: bool,
 
     /// Wait for user input before starting analysis. Useful for profiling.
     #[clap(long)]
     pub wait_at_start: bool,
+
+    /// Keep running after indexing, and index files again when they change.
+    #[clap(long)]
+    pub watch: bool,
+
+    /// Interval in seconds at which files are checked for changes in watch mode.
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        value_parser = duration_from_seconds_str,
+        default_value = "1",
+    )]
+    pub watch_interval: Duration,
 }
 
 impl IndexArgs {
@@ -100,7 +177,16 @@ impl IndexArgs {
             verbose: false,
             hide_error_details: false,
             max_file_time: None,
+            max_file_size: None,
+            streaming_threshold: None,
             wait_at_start: false,
+            watch: false,
+            watch_interval: Duration::from_secs(1),
+            globals: Vec::new(),
+            jobs: None,
+            no_progress: false,
+            local_resolution: false,
+            error_recovery: false,
             stats: false,
         }
     }
@@ -114,18 +200,48 @@ impl IndexArgs {
         let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
         indexer.force = self.force;
         indexer.max_file_time = self.max_file_time;
+        indexer.max_file_size = self.max_file_size;
+        indexer.streaming_threshold = self.streaming_threshold;
+        indexer.jobs = self
+            .jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+        indexer.show_progress = !self.no_progress && std::io::stderr().is_terminal();
+        indexer.local_resolution = self.local_resolution;
+        indexer.error_recovery = self.error_recovery;
         indexer.set_collect_stats(self.stats);
+        indexer.set_globals(self.globals.into_iter().collect());
 
         let source_paths = self
             .source_paths
             .into_iter()
             .map(|p| p.canonicalize())
             .collect::<std::result::Result<Vec<_>, _>>()?;
-        indexer.index_all(source_paths, self.continue_from, &NoCancellation)?;
+        // take the snapshot before indexing, so that changes made during indexing are picked up
+        let watched_files = if self.watch {
+            Some(WatchedFiles::scan(&source_paths))
+        } else {
+            None
+        };
+        let result = indexer.index_all(&source_paths, self.continue_from, &NoCancellation);
+
+        println!();
+        print_indexing_summary(indexer.summary());
+        result?;
 
         if self.stats {
             println!();
-            print_indexing_stats(indexer.into_stats());
+            print_indexing_stats(indexer.stats());
+        }
+
+        if let Some(watched_files) = watched_files {
+            println!();
+            println!("Watching for changes...");
+            indexer.watch(
+                &source_paths,
+                watched_files,
+                self.watch_interval,
+                &NoCancellation,
+            )?;
         }
         Ok(())
     }
@@ -161,10 +277,27 @@ pub struct Indexer<'a> {
     loader: &'a mut Loader,
     reporter: &'a dyn Reporter,
     stats: Option<IndexingStats>,
+    summary: IndexingSummary,
+    globals: BTreeMap<String, String>,
+    file_globals: Option<Box<dyn Fn(&Path) -> BTreeMap<String, String> + 'a>>,
+    /// Languages whose rules have been recorded in the database.
+    recorded_rules: HashSet<String>,
     /// Index files, even if they already exist in the database.
     pub force: bool,
     /// Maximum time per file.
     pub max_file_time: Option<Duration>,
+    /// Maximum size of files in bytes. Larger files are skipped.
+    pub max_file_size: Option<u64>,
+    /// Size of files in bytes above which their graphs are built in chunks.
+    pub streaming_threshold: Option<u64>,
+    /// Number of threads used to index files.
+    pub jobs: usize,
+    /// Show a progress bar while indexing.
+    pub show_progress: bool,
+    /// Pre-resolve references that can only resolve within their own file.
+    pub local_resolution: bool,
+    /// Build partial graphs for files with parse errors, instead of failing them.
+    pub error_recovery: bool,
 }
 
 impl<'a> Indexer<'a> {
@@ -179,10 +312,46 @@ impl<'a> Indexer<'a> {
             reporter,
             force: false,
             max_file_time: None,
+            max_file_size: None,
+            streaming_threshold: None,
+            jobs: 1,
+            show_progress: false,
+            local_resolution: false,
+            error_recovery: false,
             stats: None,
+            summary: IndexingSummary::default(),
+            globals: BTreeMap::new(),
+            file_globals: None,
+            recorded_rules: HashSet::new(),
         }
     }
 
+    /// Set the global variables that are passed to the stack graph construction rules of all
+    /// files. The file and root path variables are always set by the indexer. The variables are
+    /// stored with every file, and files are indexed again if their variables change.
+    pub fn set_globals(&mut self, globals: BTreeMap<String, String>) {
+        self.globals = globals;
+    }
+
+    /// Set a function that computes additional global variables for a file, such as a language
+    /// version that depends on the project the file is part of. These take precedence over the
+    /// variables set with [`Self::set_globals`].
+    pub fn set_file_globals<F>(&mut self, file_globals: F)
+    where
+        F: Fn(&Path) -> BTreeMap<String, String> + 'a,
+    {
+        self.file_globals = Some(Box::new(file_globals));
+    }
+
+    /// Returns the global variables for the given file.
+    fn globals_for_file(&self, source_path: &Path) -> BTreeMap<String, String> {
+        let mut globals = self.globals.clone();
+        if let Some(file_globals) = &self.file_globals {
+            globals.extend(file_globals(source_path));
+        }
+        globals
+    }
+
     pub fn set_collect_stats(&mut self, collect_stats: bool) {
         if !collect_stats {
             self.stats = None;
@@ -202,20 +371,13 @@ impl<'a> Indexer<'a> {
         IP: IntoIterator<Item = P>,
         Q: AsRef<Path>,
     {
-        for (source_root, source_path, strict) in iter_files_and_directories(source_paths) {
-            let mut file_status = CLIFileReporter::new(self.reporter, &source_path);
-            cancellation_flag.check("indexing all files")?;
-            self.index_file(
-                &source_root,
-                &source_path,
-                strict,
-                &mut continue_from,
-                cancellation_flag,
-                &mut file_status,
-            )?;
-            file_status.assert_reported();
-        }
-        Ok(())
+        let start = Instant::now();
+        let files = iter_files_and_directories(source_paths).collect::<Vec<_>>();
+        let mut progress = ProgressBar::new(files.len(), self.show_progress);
+        let result = self.index_files(files, &mut continue_from, cancellation_flag, &mut progress);
+        progress.clear();
+        self.summary.elapsed += start.elapsed();
+        result
     }
 
     pub fn index(
@@ -224,104 +386,225 @@ impl<'a> Indexer<'a> {
         source_path: &Path,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<()> {
-        let mut file_status = CLIFileReporter::new(self.reporter, source_path);
-        self.index_file(
-            &source_root,
-            &source_path,
-            true,
-            &mut None::<&Path>,
-            cancellation_flag,
-            &mut file_status,
-        )?;
-        file_status.assert_reported();
-        Ok(())
+        self.index_file(source_root, source_path, true, cancellation_flag)
     }
 
-    /// Analyze file and add error context to any failures that are returned.
-    fn index_file<P>(
+    /// Index a single file. If the indexing is not strict, files for which no language is
+    /// available are skipped instead of reported as failures, as happens for files that are found
+    /// in directories.
+    pub fn index_file(
         &mut self,
         source_root: &Path,
         source_path: &Path,
-        missing_is_error: bool,
+        strict: bool,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        let files = vec![(source_root.to_path_buf(), source_path.to_path_buf(), strict)];
+        let mut progress = ProgressBar::new(1, false);
+        self.index_files(files, &mut None::<&Path>, cancellation_flag, &mut progress)
+    }
+
+    /// Index the given files. Files are processed in chunks. The files in a chunk are first
+    /// prepared sequentially, which requires the loader and the database. They are then processed
+    /// concurrently by the configured number of jobs, and the results are stored in a single
+    /// transaction as they come in.
+    fn index_files<P>(
+        &mut self,
+        files: Vec<(PathBuf, PathBuf, bool)>,
         continue_from: &mut Option<P>,
         cancellation_flag: &dyn CancellationFlag,
-        file_status: &mut CLIFileReporter,
+        progress: &mut ProgressBar,
     ) -> Result<()>
     where
         P: AsRef<Path>,
     {
-        match self.index_file_inner(
-            source_root,
-            source_path,
-            missing_is_error,
-            continue_from,
-            cancellation_flag,
-            file_status,
-        ) {
-            ok @ Ok(_) => {
-                file_status.assert_reported();
-                ok
+        let jobs = self.jobs.max(1);
+        let chunk_size = if jobs > 1 {
+            jobs * FILES_PER_JOB_IN_CHUNK
+        } else {
+            1
+        };
+        for chunk in files.chunks(chunk_size) {
+            let mut prepared = Vec::with_capacity(chunk.len());
+            for (source_root, source_path, strict) in chunk {
+                cancellation_flag.check("indexing all files")?;
+                progress.clear();
+                let mut file_status = CLIFileReporter::new(self.reporter, source_path);
+                if let Some(file) = self.prepare_file(
+                    source_root,
+                    source_path,
+                    *strict,
+                    continue_from,
+                    &mut file_status,
+                )? {
+                    prepared.push(file);
+                } else {
+                    file_status.assert_reported();
+                    progress.inc();
+                }
+            }
+            while !prepared.is_empty() {
+                let missing =
+                    self.process_and_store_files(&prepared, jobs, cancellation_flag, progress)?;
+                prepared = self.load_injected_languages(prepared, missing)?;
             }
-            err @ Err(_) => {
-                file_status.failure_if_processing("error", Some(&format!("Error analyzing file {}. To continue analysis from this file later, add: --continue-from {}", source_path.display(), source_path.display())));
-                err
+        }
+        Ok(())
+    }
+
+    /// Load the injected languages that processed files were missing, and return those files with
+    /// the loaded languages added, so that they can be processed again. Languages that cannot be
+    /// found are not looked up again for the file.
+    fn load_injected_languages(
+        &mut self,
+        files: Vec<PreparedFile>,
+        missing: Vec<(usize, BTreeSet<String>)>,
+    ) -> Result<Vec<PreparedFile>> {
+        let mut files = files.into_iter().map(Some).collect::<Vec<_>>();
+        let mut retry = Vec::with_capacity(missing.len());
+        for (index, names) in missing {
+            let mut file = files[index]
+                .take()
+                .expect("file to be missing languages once");
+            for name in names {
+                let language = self
+                    .loader
+                    .load_for_injection(&name, &NoCancellation)
+                    .map_err(IndexError::LoadError)?
+                    .map(|lc| lc.language.clone())
+                    .filter(|language| !file.languages.injected.contains(language));
+                match language {
+                    Some(language) => file.languages.injected.push(language),
+                    None => {
+                        file.unavailable_injections.insert(name);
+                    }
+                }
             }
+            retry.push(file);
         }
+        Ok(retry)
     }
 
-    fn index_file_inner<P>(
+    /// Prepare a file for indexing. Returns `None` if the file does not need to be indexed, in which
+    /// case the reason has been reported already.
+    fn prepare_file<P>(
         &mut self,
         source_root: &Path,
         source_path: &Path,
         missing_is_error: bool,
         continue_from: &mut Option<P>,
-        cancellation_flag: &dyn CancellationFlag,
         file_status: &mut CLIFileReporter<'_>,
-    ) -> Result<()>
+    ) -> Result<Option<PreparedFile>>
     where
         P: AsRef<Path>,
     {
         if self.should_skip(source_path, continue_from) {
             file_status.skipped("skipped", None);
-            return Ok(());
+            self.summary.skipped += 1;
+            return Ok(None);
         }
 
-        let mut file_reader = FileReader::new();
-        let lcs = match self
+        let mut file_reader = FileReader::new().with_max_size(self.max_file_size);
+        let recorded_rules = &self.recorded_rules;
+        let loaded = self
             .loader
             .load_for_file(source_path, &mut file_reader, &NoCancellation)
-        {
-            Ok(lcs) if !lcs.has_some() => {
+            .map(|lcs| {
+                if lcs.has_some() {
+                    // the rules of the primary language, if they are not recorded yet
+                    let rules = lcs.primary.and_then(|lc| {
+                        lc.name()
+                            .filter(|name| !recorded_rules.contains(*name))
+                            .map(|name| (name.to_string(), sha1(lc.sgl.tsg_source())))
+                    });
+                    Some((lcs.languages(), !lcs.no_similar_paths_in_file(), rules))
+                } else {
+                    None
+                }
+            });
+        let (languages, detect_similar_paths, rules) = match loaded {
+            Ok(None) => {
                 if missing_is_error {
                     file_status.failure("not supported", None);
+                    self.summary
+                        .failed
+                        .push((source_path.to_path_buf(), "not supported".to_string()));
                 }
-                return Ok(());
+                return Ok(None);
             }
-            Ok(lcs) => lcs,
-            Err(crate::loader::LoadError::Cancelled(_)) => {
+            Ok(Some(languages)) => languages,
+            Err(LoadError::Cancelled(_)) => {
                 file_status.warning("language loading timed out", None);
-                return Ok(());
+                self.summary.failed.push((
+                    source_path.to_path_buf(),
+                    "language loading timed out".to_string(),
+                ));
+                return Ok(None);
+            }
+            Err(LoadError::Io(err)) if SkipReason::from_io_error(&err).is_some() => {
+                let reason = SkipReason::from_io_error(&err).unwrap().clone();
+                return self.skip_file(source_path, &reason, file_status);
             }
             Err(e) => return Err(IndexError::LoadError(e)),
         };
-        let stitcher_config = StitcherConfig::default()
-            .with_detect_similar_paths(!lcs.no_similar_paths_in_file())
-            .with_collect_stats(self.stats.is_some());
 
-        let source = file_reader.get(source_path)?;
-        let tag = sha1(source);
+        let source = match file_reader.get(source_path) {
+            Ok(source) => source.to_string(),
+            Err(err) => match SkipReason::from_io_error(&err) {
+                Some(reason) => {
+                    let reason = reason.clone();
+                    return self.skip_file(source_path, &reason, file_status);
+                }
+                None => return Err(err.into()),
+            },
+        };
+        let tag = sha1(&source);
+        let globals = self.globals_for_file(source_path);
+
+        if let Some((language, hash)) = rules {
+            // if the rules changed since the database was indexed, this invalidates all files of
+            // the language, so that they are not skipped as cached below
+            self.db.set_language_rules(
+                &language,
+                &LanguageRules {
+                    hash,
+                    version: None,
+                },
+            )?;
+            self.recorded_rules.insert(language);
+        }
 
-        let success_status = match self
+        let mut status = self
             .db
-            .status_for_file(&source_path.to_string_lossy(), Some(&tag))?
-        {
+            .status_for_file(&source_path.to_string_lossy(), Some(&tag))?;
+        let mut renamed_fingerprint = None;
+        if matches!(status, FileStatus::Missing) && !self.force {
+            if let Some(old_path) = self.renamed_from(source_path, &tag)? {
+                // move the stored data of the old path, which is kept if building the graph for
+                // the new path gives the same graph
+                self.db.rename_file(&old_path, source_path)?;
+                status = self
+                    .db
+                    .status_for_file(&source_path.to_string_lossy(), Some(&tag))?;
+                renamed_fingerprint = self.db.fingerprint_for_file(source_path)?;
+            }
+        }
+        let globals_changed = match status {
+            FileStatus::Missing => false,
+            _ => self.db.globals_for_file(source_path)? != globals,
+        };
+        let renamed_fingerprint = renamed_fingerprint.filter(|_| !globals_changed);
+        let success_status = match status {
             FileStatus::Missing => "indexed",
+            _ if globals_changed => "reindexed",
+            _ if renamed_fingerprint.is_some() => "indexed",
             FileStatus::Indexed => {
                 if self.force {
                     "reindexed"
                 } else {
                     file_status.skipped("cached index", None);
-                    return Ok(());
+                    self.summary.skipped += 1;
+                    return Ok(None);
                 }
             }
             FileStatus::Error(error) => {
@@ -329,46 +612,226 @@ impl<'a> Indexer<'a> {
                     "reindexed"
                 } else {
                     file_status.skipped(&format!("cached error ({})", error), None);
-                    return Ok(());
+                    self.summary.skipped += 1;
+                    return Ok(None);
                 }
             }
         };
 
-        let file_cancellation_flag = CancelAfterDuration::from_option(self.max_file_time);
-        let cancellation_flag = cancellation_flag | file_cancellation_flag.as_ref();
+        Ok(Some(PreparedFile {
+            source_root: source_root.to_path_buf(),
+            source_path: source_path.to_path_buf(),
+            source,
+            tag,
+            globals,
+            success_status,
+            languages,
+            detect_similar_paths,
+            renamed_fingerprint,
+            unavailable_injections: BTreeSet::new(),
+        }))
+    }
+
+    /// Returns the previous path of a file that was renamed or moved.  This is a stored file with
+    /// the same content hash as the given file, which no longer exists.
+    fn renamed_from(&mut self, source_path: &Path, tag: &str) -> Result<Option<PathBuf>> {
+        Ok(self
+            .db
+            .indexed_files_with_tag(tag)?
+            .into_iter()
+            .find(|file| file != source_path && !file.exists()))
+    }
+
+    /// Skip a file that was not read, and store the reason as the error of the file, so that it
+    /// shows up in the status of the file.
+    fn skip_file(
+        &mut self,
+        source_path: &Path,
+        reason: &SkipReason,
+        file_status: &mut CLIFileReporter<'_>,
+    ) -> Result<Option<PreparedFile>> {
+        let reason = reason.to_string();
+        file_status.skipped(&reason, None);
+        self.summary.skipped += 1;
+        self.db.clean_file(source_path)?;
+        self.db.store_error_for_file(source_path, "", &reason)?;
+        Ok(None)
+    }
+
+    /// Process the prepared files, using the given number of threads, and store the results.
+    /// Returns the indices of the files that inject languages which are not loaded yet, with the
+    /// names of those languages. These files are not stored.
+    fn process_and_store_files(
+        &mut self,
+        files: &[PreparedFile],
+        jobs: usize,
+        cancellation_flag: &dyn CancellationFlag,
+        progress: &mut ProgressBar,
+    ) -> Result<Vec<(usize, BTreeSet<String>)>> {
+        let mut missing = Vec::new();
+        if files.is_empty() {
+            return Ok(missing);
+        }
+        let loader: &Loader = self.loader;
+        let lcs = files
+            .iter()
+            .map(|file| loader.configurations_for_languages(&file.languages))
+            .collect::<Vec<_>>();
+        let max_file_time = self.max_file_time;
+        let collect_stats = self.stats.is_some();
+        let local_resolution = self.local_resolution;
+        let error_recovery = self.error_recovery;
+        let streaming_threshold = self.streaming_threshold;
+        let mut store = FileResultStore {
+            db: self.db.batch_writer()?,
+            reporter: self.reporter,
+            stats: &mut self.stats,
+            summary: &mut self.summary,
+            progress,
+            resume_from: files[0].source_path.clone(),
+            stored: Vec::new(),
+        };
+
+        if jobs <= 1 || files.len() <= 1 {
+            for (index, (file, lcs)) in files.iter().zip(lcs.iter()).enumerate() {
+                let start = Instant::now();
+                let result = Self::process_file(
+                    file,
+                    lcs,
+                    max_file_time,
+                    collect_stats,
+                    local_resolution,
+                    error_recovery,
+                    streaming_threshold,
+                    cancellation_flag,
+                );
+                match result {
+                    ProcessedFile::MissingInjections(names) => missing.push((index, names)),
+                    result => store.store(file, result, start.elapsed())?,
+                }
+            }
+        } else {
+            let queue = Mutex::new(files.iter().zip(lcs.iter()).enumerate());
+            std::thread::scope(|scope| -> Result<()> {
+                let (sender, receiver) = mpsc::channel();
+                for _ in 0..jobs.min(files.len()) {
+                    let sender = sender.clone();
+                    let queue = &queue;
+                    scope.spawn(move || loop {
+                        let (index, file, lcs) = match queue.lock().unwrap().next() {
+                            Some((index, (file, lcs))) => (index, file, lcs),
+                            None => break,
+                        };
+                        let start = Instant::now();
+                        let result = Self::process_file(
+                            file,
+                            lcs,
+                            max_file_time,
+                            collect_stats,
+                            local_resolution,
+                            error_recovery,
+                            streaming_threshold,
+                            cancellation_flag,
+                        );
+                        if sender.send((index, result, start.elapsed())).is_err() {
+                            // the receiver stopped because storing failed
+                            break;
+                        }
+                    });
+                }
+                drop(sender);
+                for (index, result, elapsed) in receiver {
+                    match result {
+                        ProcessedFile::MissingInjections(names) => missing.push((index, names)),
+                        result => store.store(&files[index], result, elapsed)?,
+                    }
+                }
+                Ok(())
+            })?;
+        }
 
-        file_status.processing();
+        store.commit()?;
+        Ok(missing)
+    }
+
+    /// Build the stack graph and compute the partial paths for a prepared file. This does not
+    /// require access to the indexer, and can be run concurrently for several files.
+    fn process_file(
+        prepared: &PreparedFile,
+        lcs: &FileLanguageConfigurations,
+        max_file_time: Option<Duration>,
+        collect_stats: bool,
+        local_resolution: bool,
+        error_recovery: bool,
+        streaming_threshold: Option<u64>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> ProcessedFile {
+        let file_cancellation_flag = CancelAfterDuration::from_option(max_file_time);
+        let cancellation_flag = cancellation_flag | file_cancellation_flag.as_ref();
 
         let mut graph = StackGraph::new();
         let file = graph
-            .add_file(&source_path.to_string_lossy())
+            .add_file(&prepared.source_path.to_string_lossy())
             .expect("file not present in empty graph");
+        if let Some(language) = lcs.primary.and_then(|lc| lc.name()) {
+            graph.set_file_language(file, language);
+        }
 
+        let chunk_size = streaming_threshold
+            .filter(|threshold| prepared.source.len() as u64 > *threshold)
+            .map(|_| STREAMING_CHUNK_SIZE);
+        // injected languages that are not loaded yet are loaded by the indexer, which processes
+        // the file again
+        let missing_injections = RefCell::new(BTreeSet::new());
+        let injected_language = |name: &str| {
+            let sgl = lcs.injected_language(name);
+            if sgl.is_none() && !prepared.unavailable_injections.contains(name) {
+                missing_injections.borrow_mut().insert(name.to_string());
+            }
+            sgl
+        };
         let result = Self::build_stack_graph(
             &mut graph,
             file,
-            source_root,
-            source_path,
-            &source,
+            &prepared.source_root,
+            &prepared.source_path,
+            &prepared.source,
+            &prepared.globals,
             lcs,
+            &injected_language,
+            error_recovery,
+            chunk_size,
             &cancellation_flag,
         );
+        let missing_injections = missing_injections.into_inner();
+        if !missing_injections.is_empty() {
+            return ProcessedFile::MissingInjections(missing_injections);
+        }
         if let Err(err) = result {
-            match err.inner {
-                BuildError::Cancelled(_) => {
-                    file_status.warning("timed out", None);
-                    self.db
-                        .store_error_for_file(source_path, &tag, "timed out")?;
-                    return Ok(());
-                }
-                _ => {
-                    file_status.failure("failed", Some(&err.display_pretty()));
-                    self.db.store_error_for_file(source_path, &tag, "failed")?;
-                    return Ok(());
-                }
-            }
+            return match err.inner {
+                BuildError::Cancelled(_) => ProcessedFile::Failed {
+                    status: "timed out",
+                    details: None,
+                    warning: true,
+                },
+                _ => ProcessedFile::Failed {
+                    status: "failed",
+                    details: Some(err.display_pretty().to_string()),
+                    warning: false,
+                },
+            };
         };
-        if let Some(stats) = &mut self.stats {
+
+        if prepared.renamed_fingerprint == Some(graph.fingerprint_for_file(file)) {
+            return ProcessedFile::Renamed;
+        }
+
+        let mut stats = if collect_stats {
+            Some(IndexingStats::default())
+        } else {
+            None
+        };
+        if let Some(stats) = &mut stats {
             stats.total_graph_nodes.record(graph.iter_nodes().count());
             let mut total_edges = 0;
             for n in graph.iter_nodes() {
@@ -383,49 +846,98 @@ impl<'a> Indexer<'a> {
             stats.total_graph_edges.record(total_edges);
         }
 
+        let stitcher_config = StitcherConfig::default()
+            .with_detect_similar_paths(prepared.detect_similar_paths)
+            .with_collect_stats(collect_stats);
+        let paths_config = PartialPathsConfig::default()
+            .with_stitcher_config(stitcher_config)
+            .with_local_resolution(local_resolution);
         let mut partials = PartialPaths::new();
         let mut paths = Vec::new();
-        match ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        let incomplete = match ForwardPartialPathStitcher::find_partial_paths_in_file(
             &graph,
             &mut partials,
             file,
-            stitcher_config,
+            &paths_config,
             &(&cancellation_flag as &dyn CancellationFlag),
             |_g, _ps, p| {
                 paths.push(p.clone());
             },
         ) {
             Ok(stitching_stats) => {
-                if let Some(stats) = &mut self.stats {
+                if let Some(stats) = &mut stats {
+                    stats.stitching_stats += stitching_stats;
+                }
+                None
+            }
+            Err(cancelled) => {
+                if let Some(stats) = &mut stats {
+                    stats.stitching_stats += cancelled.stats;
+                }
+                Some(cancelled.progress.to_string())
+            }
+        };
+
+        // the paths that were found before the computation timed out are stored, but there is no
+        // time left to pre-resolve references
+        let mut local_resolutions = Vec::new();
+        if incomplete.is_some() {
+            return ProcessedFile::Indexed {
+                graph,
+                file,
+                partials,
+                paths,
+                local_resolutions,
+                incomplete,
+                stats,
+            };
+        }
+        match ForwardPartialPathStitcher::find_local_resolutions_in_file(
+            &graph,
+            &mut partials,
+            file,
+            &paths_config,
+            &(&cancellation_flag as &dyn CancellationFlag),
+            |_g, _ps, reference, paths| {
+                local_resolutions.push((reference, paths.iter().map(|p| p.end_node).collect()));
+            },
+        ) {
+            Ok(stitching_stats) => {
+                if let Some(stats) = &mut stats {
                     stats.stitching_stats += stitching_stats;
                 }
             }
             Err(_) => {
-                file_status.warning("path computation timed out", None);
-                self.db.store_error_for_file(
-                    source_path,
-                    &tag,
-                    &format!("path computation timed out"),
-                )?;
-                return Ok(());
+                return ProcessedFile::Failed {
+                    status: "local resolution timed out",
+                    details: None,
+                    warning: true,
+                };
             }
         }
 
-        self.db
-            .store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
-
-        file_status.success(success_status, None);
-
-        Ok(())
+        ProcessedFile::Indexed {
+            graph,
+            file,
+            partials,
+            paths,
+            local_resolutions,
+            incomplete,
+            stats,
+        }
     }
 
-    fn build_stack_graph<'b>(
+    fn build_stack_graph<'b, 'l>(
         graph: &mut StackGraph,
         file: Handle<File>,
         source_root: &Path,
         source_path: &Path,
         source: &'b str,
-        lcs: FileLanguageConfigurations<'b>,
+        file_globals: &BTreeMap<String, String>,
+        lcs: &'l FileLanguageConfigurations<'b>,
+        injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+        error_recovery: bool,
+        chunk_size: Option<usize>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> std::result::Result<(), BuildErrorWithSource<'b>> {
         let relative_source_path = source_path.strip_prefix(source_root).unwrap();
@@ -440,24 +952,63 @@ impl<'a> Indexer<'a> {
                 .add(ROOT_PATH_VAR.into(), source_root.to_str().unwrap().into())
                 .expect("failed to add root path variable");
 
-            lc.sgl
-                .build_stack_graph_into(graph, file, source, &globals, cancellation_flag)
-                .map_err(|inner| BuildErrorWithSource {
-                    inner,
-                    source_path: source_path.to_path_buf(),
-                    source_str: source,
-                    tsg_path: lc.sgl.tsg_path().to_path_buf(),
-                    tsg_str: &lc.sgl.tsg_source(),
-                })?;
+            for (name, value) in file_globals {
+                // the file and root path set above take precedence
+                globals
+                    .add(name.as_str().into(), value.as_str().into())
+                    .unwrap_or_default();
+            }
+
+            let result = if let Some(chunk_size) = chunk_size {
+                Self::build_stack_graph_in_chunks(
+                    graph,
+                    file,
+                    source,
+                    &globals,
+                    lcs,
+                    injected_language,
+                    error_recovery,
+                    chunk_size,
+                    cancellation_flag,
+                )
+            } else if error_recovery {
+                lc.sgl.build_stack_graph_with_error_recovery_into(
+                    graph,
+                    file,
+                    source,
+                    &globals,
+                    injected_language,
+                    cancellation_flag,
+                )
+            } else {
+                lc.sgl.build_stack_graph_with_injections_into(
+                    graph,
+                    file,
+                    source,
+                    &globals,
+                    injected_language,
+                    cancellation_flag,
+                )
+            };
+            result.map_err(|inner| BuildErrorWithSource {
+                inner,
+                source_path: source_path.to_path_buf(),
+                source_str: source,
+                tsg_path: lc.sgl.tsg_path().to_path_buf(),
+                tsg_str: &lc.sgl.tsg_source(),
+            })?;
         }
-        for (_, fa) in lcs.secondary {
+        for (_, fa) in &lcs.secondary {
             fa.build_stack_graph_into(
                 graph,
                 file,
                 &relative_source_path,
                 &source,
                 &mut std::iter::empty(),
-                &HashMap::new(),
+                &file_globals
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect::<HashMap<_, _>>(),
                 cancellation_flag,
             )
             .map_err(|inner| BuildErrorWithSource {
@@ -471,6 +1022,39 @@ impl<'a> Indexer<'a> {
         Ok(())
     }
 
+    /// Build the primary language graph of a file in chunks, which are spilled to a temporary
+    /// file, and loaded into the graph once the graph construction rules have been executed and
+    /// their intermediate graph has been dropped. Injections are built after that.
+    fn build_stack_graph_in_chunks<'b, 'l>(
+        graph: &mut StackGraph,
+        file: Handle<File>,
+        source: &'b str,
+        globals: &Variables,
+        lcs: &'l FileLanguageConfigurations<'b>,
+        injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+        error_recovery: bool,
+        chunk_size: usize,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> std::result::Result<(), BuildError> {
+        let lc = lcs.primary.expect("primary language");
+        let mut chunks = GraphChunks::new().map_err(chunk_error)?;
+        let mut builder = lc.sgl.builder_into_stack_graph(graph, file, source);
+        builder.set_error_recovery(error_recovery);
+        builder.set_chunk_sink(chunk_size, &mut chunks);
+        let injections = builder.build_with_injections(globals, cancellation_flag)?;
+        chunks.load_into(graph).map_err(chunk_error)?;
+        build_injections_into(
+            graph,
+            file,
+            source,
+            globals,
+            injections,
+            injected_language,
+            error_recovery,
+            cancellation_flag,
+        )
+    }
+
     /// Determines if a path should be skipped because we have not seen the
     /// continue_from mark yet. If the mark is seen, it is cleared, after which
     /// all paths are accepted.
@@ -487,11 +1071,390 @@ impl<'a> Indexer<'a> {
         false
     }
 
+    /// Poll the source paths for changes, starting from the given snapshot, and index files
+    /// again when they change. Files that no longer exist are removed from the database, after
+    /// the changed files are indexed, so that the data of renamed files can be moved to their
+    /// new path. Any invalidated files are logged. This only returns if it is cancelled or an
+    /// error occurs.
+    fn watch(
+        &mut self,
+        source_paths: &[PathBuf],
+        mut watched_files: WatchedFiles,
+        interval: Duration,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<()> {
+        loop {
+            std::thread::sleep(interval);
+            cancellation_flag.check("watching files")?;
+
+            let current_files = WatchedFiles::scan(source_paths);
+            let removed = watched_files.removed(&current_files);
+            for source_path in &removed {
+                self.log_invalidation(source_path, "removed")?;
+            }
+            let changed = current_files.changed(&watched_files);
+            for (_, source_path, _) in &changed {
+                self.log_invalidation(source_path, "changed")?;
+            }
+            watched_files = current_files;
+
+            if !changed.is_empty() {
+                let mut progress = ProgressBar::new(changed.len(), false);
+                self.index_files(
+                    changed,
+                    &mut None::<&Path>,
+                    cancellation_flag,
+                    &mut progress,
+                )?;
+            }
+            // the data of renamed files was moved while indexing, and is not removed here
+            for source_path in &removed {
+                self.db.clean_file(source_path)?;
+            }
+        }
+    }
+
+    /// Log that a file is invalidated, together with any files that depend on it.
+    fn log_invalidation(&mut self, source_path: &Path, reason: &str) -> Result<()> {
+        println!("{} {}", reason, source_path.display());
+        for dependent in self.db.dependents_of_file(source_path)? {
+            println!("  invalidates {}", dependent.display());
+        }
+        Ok(())
+    }
+
+    /// Returns the database that files are indexed into.
+    pub fn db(&mut self) -> &mut SQLiteWriter {
+        self.db
+    }
+
+    /// Returns a summary of the files indexed so far.
+    pub fn summary(&self) -> &IndexingSummary {
+        &self.summary
+    }
+
+    /// Returns the statistics collected so far.
+    pub fn stats(&self) -> IndexingStats {
+        self.stats.clone().unwrap_or_default()
+    }
+
     pub fn into_stats(self) -> IndexingStats {
         self.stats.unwrap_or_default()
     }
 }
 
+/// Number of files per job that are prepared before they are processed concurrently.
+const FILES_PER_JOB_IN_CHUNK: usize = 16;
+
+/// Maximum number of slowest files that are kept in the summary.
+const SLOWEST_FILES_IN_SUMMARY: usize = 5;
+
+/// Maximum number of graph nodes per chunk when the graph of a file is built in chunks.
+const STREAMING_CHUNK_SIZE: usize = 10_000;
+
+impl GraphChunkSink for GraphChunks {
+    fn add_chunk(
+        &mut self,
+        chunk: stack_graphs::serde::StackGraph,
+    ) -> std::result::Result<(), BuildError> {
+        self.push(&chunk).map_err(chunk_error)
+    }
+}
+
+fn chunk_error(error: StorageError) -> BuildError {
+    BuildError::GraphChunkError(error.to_string())
+}
+
+/// Snapshot of the modification times of the files in a set of source paths, used to detect
+/// changes in watch mode.
+struct WatchedFiles {
+    files: BTreeMap<PathBuf, WatchedFile>,
+}
+
+struct WatchedFile {
+    source_root: PathBuf,
+    strict: bool,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFiles {
+    fn scan(source_paths: &[PathBuf]) -> Self {
+        let files = iter_files_and_directories(source_paths)
+            .map(|(source_root, source_path, strict)| {
+                let modified = std::fs::metadata(&source_path)
+                    .and_then(|m| m.modified())
+                    .ok();
+                let file = WatchedFile {
+                    source_root,
+                    strict,
+                    modified,
+                };
+                (source_path, file)
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Returns the files that are new or modified compared to the given earlier snapshot.
+    fn changed(&self, earlier: &WatchedFiles) -> Vec<(PathBuf, PathBuf, bool)> {
+        self.files
+            .iter()
+            .filter(|(source_path, file)| {
+                earlier
+                    .files
+                    .get(*source_path)
+                    .map_or(true, |earlier_file| earlier_file.modified != file.modified)
+            })
+            .map(|(source_path, file)| (file.source_root.clone(), source_path.clone(), file.strict))
+            .collect()
+    }
+
+    /// Returns the files that are missing from the given later snapshot.
+    fn removed(&self, later: &WatchedFiles) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .filter(|source_path| !later.files.contains_key(*source_path))
+            .cloned()
+            .collect()
+    }
+}
+
+/// A file that is ready to be processed.
+struct PreparedFile {
+    source_root: PathBuf,
+    source_path: PathBuf,
+    source: String,
+    tag: String,
+    globals: BTreeMap<String, String>,
+    success_status: &'static str,
+    languages: FileLanguages,
+    detect_similar_paths: bool,
+    /// The fingerprint of the stored graph, if the stored data of the file was moved from the
+    /// path of a renamed file.
+    renamed_fingerprint: Option<Fingerprint>,
+    /// Injected languages that were looked up for the file, but could not be found.
+    unavailable_injections: BTreeSet<String>,
+}
+
+/// The result of processing a file.
+enum ProcessedFile {
+    Indexed {
+        graph: StackGraph,
+        file: Handle<File>,
+        partials: PartialPaths,
+        paths: Vec<PartialPath>,
+        /// The pre-resolved references of the file, with their definitions.
+        local_resolutions: Vec<(Handle<Node>, Vec<Handle<Node>>)>,
+        /// The progress of the path computation, if it timed out before all paths were found.
+        incomplete: Option<String>,
+        stats: Option<IndexingStats>,
+    },
+    Failed {
+        status: &'static str,
+        details: Option<String>,
+        /// Whether the failure is reported as a warning, such as time-outs.
+        warning: bool,
+    },
+    /// The file was renamed, and its graph is the same as the stored graph that was moved from
+    /// its old path, so the stored data is kept.
+    Renamed,
+    /// The file injects languages that are not loaded yet. It has to be processed again once they
+    /// are loaded.
+    MissingInjections(BTreeSet<String>),
+}
+
+/// Stores the results of processed files in a single batch, and reports them once the batch is
+/// committed, so that files are only reported as done when their results are in the database.
+struct FileResultStore<'a, 'b> {
+    db: SQLiteBatchWriter<'a>,
+    reporter: &'b dyn Reporter,
+    stats: &'b mut Option<IndexingStats>,
+    summary: &'b mut IndexingSummary,
+    progress: &'b mut ProgressBar,
+    /// The first file of the batch, from which indexing has to continue if the batch is not
+    /// committed.
+    resume_from: PathBuf,
+    /// The stored files that are reported when the batch is committed.
+    stored: Vec<StoredFile>,
+}
+
+/// A file whose result is stored in an uncommitted batch.
+struct StoredFile {
+    source_path: PathBuf,
+    status: &'static str,
+    details: Option<String>,
+    indexed: bool,
+    warning: bool,
+    elapsed: Duration,
+}
+
+impl FileResultStore<'_, '_> {
+    /// Store the result of the file. The file is reported when the batch is committed.
+    fn store(
+        &mut self,
+        prepared: &PreparedFile,
+        result: ProcessedFile,
+        elapsed: Duration,
+    ) -> Result<()> {
+        match self.store_inner(prepared, result, elapsed) {
+            Ok(stored) => {
+                self.stored.push(stored);
+                Ok(())
+            }
+            Err(err) => {
+                self.progress.clear();
+                report_error(self.reporter, &prepared.source_path, &self.resume_from);
+                Err(err)
+            }
+        }
+    }
+
+    fn store_inner(
+        &mut self,
+        prepared: &PreparedFile,
+        result: ProcessedFile,
+        elapsed: Duration,
+    ) -> Result<StoredFile> {
+        let (status, details, indexed, warning) = match result {
+            ProcessedFile::Indexed {
+                graph,
+                file,
+                mut partials,
+                paths,
+                local_resolutions,
+                incomplete,
+                stats,
+            } => {
+                match &incomplete {
+                    Some(progress) => self.db.store_incomplete_result_for_file(
+                        &graph,
+                        file,
+                        &prepared.tag,
+                        &format!("path computation timed out ({})", progress),
+                        &mut partials,
+                        &paths,
+                    )?,
+                    None => self.db.store_result_for_file(
+                        &graph,
+                        file,
+                        &prepared.tag,
+                        &mut partials,
+                        &paths,
+                    )?,
+                }
+                if !local_resolutions.is_empty() {
+                    self.db
+                        .store_local_resolutions_for_file(&graph, file, &local_resolutions)?;
+                }
+                self.db
+                    .store_globals_for_file(&prepared.source_path, &prepared.globals)?;
+                if let (Some(total), Some(stats)) = (self.stats.as_mut(), stats) {
+                    *total += stats;
+                    total.file_indexing_times.record(elapsed.as_millis());
+                }
+                let parse_errors = graph.parse_errors(file).len();
+                if let Some(progress) = incomplete {
+                    (
+                        "indexed incompletely, path computation timed out",
+                        Some(progress),
+                        true,
+                        true,
+                    )
+                } else if parse_errors > 0 {
+                    (
+                        "indexed with parse errors",
+                        Some(format!("{} regions could not be parsed", parse_errors)),
+                        true,
+                        true,
+                    )
+                } else {
+                    (prepared.success_status, None, true, false)
+                }
+            }
+            ProcessedFile::Failed {
+                status,
+                details,
+                warning,
+            } => {
+                self.db
+                    .store_error_for_file(&prepared.source_path, &prepared.tag, status)?;
+                self.db
+                    .store_globals_for_file(&prepared.source_path, &prepared.globals)?;
+                (status, details, false, warning)
+            }
+            ProcessedFile::Renamed => ("renamed", None, true, false),
+            ProcessedFile::MissingInjections(_) => {
+                unreachable!("files with missing injections are processed again")
+            }
+        };
+        Ok(StoredFile {
+            source_path: prepared.source_path.clone(),
+            status,
+            details,
+            indexed,
+            warning,
+            elapsed,
+        })
+    }
+
+    /// Commit the batch, and report the stored files.
+    fn commit(self) -> Result<()> {
+        let FileResultStore {
+            db,
+            reporter,
+            summary,
+            progress,
+            resume_from,
+            stored,
+            ..
+        } = self;
+        if let Err(err) = db.commit() {
+            progress.clear();
+            report_error(reporter, &resume_from, &resume_from);
+            return Err(err.into());
+        }
+        for file in stored {
+            progress.clear();
+            let mut file_status = CLIFileReporter::new(reporter, &file.source_path);
+            file_status.processing();
+            let details = file.details.as_ref().map(|d| d as &dyn std::fmt::Display);
+            if file.warning {
+                file_status.warning(file.status, details);
+            } else if file.indexed {
+                file_status.success(file.status, details);
+            } else {
+                file_status.failure(file.status, details);
+            }
+            file_status.assert_reported();
+            if file.indexed {
+                summary.indexed += 1;
+            } else {
+                summary
+                    .failed
+                    .push((file.source_path.clone(), file.status.to_string()));
+            }
+            summary.record_time(&file.source_path, file.elapsed);
+            progress.inc();
+        }
+        Ok(())
+    }
+}
+
+/// Report an error for the given file. None of the files of the uncommitted batch are stored, so
+/// the hint to continue points at the first file of the batch.
+fn report_error(reporter: &dyn Reporter, source_path: &Path, resume_from: &Path) {
+    let mut file_status = CLIFileReporter::new(reporter, source_path);
+    file_status.processing();
+    file_status.failure_if_processing(
+        "error",
+        Some(&format!(
+            "Error analyzing file {}. To continue analysis from this file later, add: --continue-from {}",
+            source_path.display(),
+            resume_from.display(),
+        )),
+    );
+}
+
 #[derive(Debug, Error)]
 pub enum IndexError {
     #[error("cancelled at {0}")]
@@ -522,8 +1485,49 @@ pub struct IndexingStats {
     pub total_graph_edges: FrequencyDistribution<usize>,
     // The distribution of the out-degrees of non-root nodes.
     pub node_out_degrees: FrequencyDistribution<usize>,
-    // The root node's out-degree.
+    // The largest out-degree of the root node in any file graph.
     pub root_out_degree: usize,
+    // The distribution of the time in milliseconds it took to index a file.
+    pub file_indexing_times: FrequencyDistribution<u128>,
     // The stitching statistics.
     pub stitching_stats: StitchingStats,
 }
+
+impl std::ops::AddAssign for IndexingStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.total_graph_nodes += rhs.total_graph_nodes;
+        self.total_graph_edges += rhs.total_graph_edges;
+        self.node_out_degrees += rhs.node_out_degrees;
+        self.root_out_degree = self.root_out_degree.max(rhs.root_out_degree);
+        self.file_indexing_times += rhs.file_indexing_times;
+        self.stitching_stats += rhs.stitching_stats;
+    }
+}
+
+/// Summary of an indexing run.
+#[derive(Clone, Debug, Default)]
+pub struct IndexingSummary {
+    /// Number of files that were indexed.
+    pub indexed: usize,
+    /// Number of files that were skipped, because they were cached or not selected.
+    pub skipped: usize,
+    /// Files that failed to index, with the reason.
+    pub failed: Vec<(PathBuf, String)>,
+    /// The slowest files, with the time it took to process them, slowest first.
+    pub slowest: Vec<(PathBuf, Duration)>,
+    /// Total time spent indexing.
+    pub elapsed: Duration,
+}
+
+impl IndexingSummary {
+    fn record_time(&mut self, path: &Path, time: Duration) {
+        if self.slowest.len() == SLOWEST_FILES_IN_SUMMARY
+            && self.slowest.last().map_or(false, |(_, t)| *t >= time)
+        {
+            return;
+        }
+        let index = self.slowest.partition_point(|(_, t)| *t >= time);
+        self.slowest.insert(index, (path.to_path_buf(), time));
+        self.slowest.truncate(SLOWEST_FILES_IN_SUMMARY);
+    }
+}
@@ -9,6 +9,8 @@ use clap::Args;
 use std::path::PathBuf;
 use tree_sitter_config::Config as TsConfig;
 
+use crate::loader::remote::GrammarCache;
+use crate::loader::remote::RemoteGrammar;
 use crate::loader::LanguageConfiguration;
 use crate::loader::LoadError;
 use crate::loader::LoadPath;
@@ -33,6 +35,17 @@ pub struct PathLoaderArgs {
     #[clap(long, value_name = "GRAMMAR_PATH")]
     pub grammar: Vec<PathBuf>,
 
+    /// The git URL of a tree-sitter grammar to fetch, optionally followed by a revision and
+    /// the expected git tree hash of that revision, as in `URL#REVISION:TREE_HASH`.
+    /// Fetched grammars are cached locally. Can be specified multiple times.
+    #[clap(long, value_name = "GRAMMAR_URL")]
+    pub grammar_url: Vec<RemoteGrammar>,
+
+    /// The directory where fetched grammars are cached.
+    /// Defaults to a directory in the user's cache directory.
+    #[clap(long, value_name = "CACHE_PATH")]
+    pub grammar_cache: Option<PathBuf>,
+
     /// The scope of the tree-sitter grammar.
     /// See https://tree-sitter.github.io/tree-sitter/syntax-highlighting#basics for details.
     #[clap(long, value_name = "SCOPE")]
@@ -45,6 +58,8 @@ impl PathLoaderArgs {
             tsg: None,
             builtins: None,
             grammar: Vec::new(),
+            grammar_url: Vec::new(),
+            grammar_cache: None,
             scope: None,
         }
     }
@@ -59,13 +74,16 @@ impl PathLoaderArgs {
             None => DEFAULT_BUILTINS_PATHS.clone(),
         };
 
-        let loader = if !self.grammar.is_empty() {
-            Loader::from_paths(
-                self.grammar.clone(),
-                self.scope.clone(),
-                tsg_paths,
-                builtins_paths,
-            )?
+        let mut grammar_paths = self.grammar.clone();
+        if !self.grammar_url.is_empty() {
+            let cache = GrammarCache::new(self.grammar_cache_path()?);
+            for grammar in &self.grammar_url {
+                grammar_paths.push(cache.fetch(grammar)?);
+            }
+        }
+
+        let loader = if !grammar_paths.is_empty() {
+            Loader::from_paths(grammar_paths, self.scope.clone(), tsg_paths, builtins_paths)?
         } else {
             let loader_config = TsConfig::load(None)
                 .and_then(|v| v.get())
@@ -79,6 +97,18 @@ impl PathLoaderArgs {
         };
         Ok(loader)
     }
+
+    fn grammar_cache_path(&self) -> Result<PathBuf, LoadError<'static>> {
+        if let Some(grammar_cache) = &self.grammar_cache {
+            return Ok(grammar_cache.clone());
+        }
+        match dirs::cache_dir() {
+            Some(dir) => Ok(dir.join("tree-sitter-stack-graphs").join("grammars")),
+            None => Err(LoadError::Reader(
+                "unable to determine cache directory for grammars".into(),
+            )),
+        }
+    }
 }
 
 /// CLI arguments for creating a path based loader.
@@ -0,0 +1,68 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueHint;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::SQLiteWriter;
+use stack_graphs::NoCancellation;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::util::sha1;
+
+/// The tag of imported files whose sources cannot be read.
+const LSIF_TAG: &str = "lsif";
+
+#[derive(Args)]
+pub struct ImportLsifArgs {
+    /// LSIF dump, in the JSON lines format or as a single JSON array.
+    #[clap(value_name = "LSIF_PATH", value_hint = ValueHint::FilePath)]
+    pub lsif_path: PathBuf,
+}
+
+impl ImportLsifArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let mut graph = StackGraph::new();
+        let reader = BufReader::new(std::fs::File::open(&self.lsif_path)?);
+        let import = graph.add_from_lsif(reader)?;
+
+        let mut db = SQLiteWriter::open(&db_path)?;
+        for file in &import.files {
+            let file = *file;
+            let mut partials = PartialPaths::new();
+            let mut paths = Vec::new();
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                &graph,
+                &mut partials,
+                file,
+                StitcherConfig::default(),
+                &NoCancellation,
+                |_g, _ps, p| {
+                    paths.push(p.clone());
+                },
+            )?;
+            // Use the same tags as indexing, so that the files are not reported as changed.
+            let tag = std::fs::read_to_string(graph[file].name())
+                .map(|source| sha1(&source))
+                .unwrap_or_else(|_| LSIF_TAG.to_string());
+            db.store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
+        }
+        println!(
+            "imported {} files with {} definitions and {} references, skipped {} ranges",
+            import.files.len(),
+            import.definitions,
+            import.references,
+            import.skipped_ranges,
+        );
+        Ok(())
+    }
+}
@@ -7,8 +7,7 @@
 
 use capture_it::capture;
 use clap::Args;
-use crossbeam_channel::RecvTimeoutError;
-use crossbeam_channel::Sender;
+use stack_graphs::parallel::WorkQueue;
 use stack_graphs::storage::SQLiteReader;
 use stack_graphs::storage::SQLiteWriter;
 use stack_graphs::storage::StorageError;
@@ -17,6 +16,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 use tokio::runtime::Handle;
 use tower_lsp::jsonrpc::Error;
 use tower_lsp::jsonrpc::ErrorCode;
@@ -33,6 +33,7 @@ use crate::cli::query::Querier;
 use crate::cli::query::QueryError;
 use crate::cli::util::duration_from_milliseconds_str;
 use crate::cli::util::duration_from_seconds_str;
+use crate::cli::util::iter_files_and_directories;
 use crate::cli::util::reporter::Reporter;
 use crate::cli::util::SourcePosition;
 use crate::cli::util::SourceSpan;
@@ -118,37 +119,82 @@ struct Backend {
     db_path: PathBuf,
     loader: Arc<std::sync::Mutex<Loader>>,
     args: LspArgs,
-    jobs: Arc<tokio::sync::Mutex<Option<(Sender<Job>, AtomicCancellationFlag)>>>,
+    jobs: Arc<tokio::sync::Mutex<Option<(Arc<WorkQueue<Job>>, AtomicCancellationFlag)>>>,
     logger: BackendLogger,
 }
 
 impl Backend {
-    async fn start_job_handler(&self) -> (Sender<Job>, AtomicCancellationFlag) {
+    /// Starts the thread that runs the jobs in the returned queue.  Jobs that are prioritized, such
+    /// as indexing files that are opened or saved, are run before the files of workspace folders
+    /// that are waiting to be indexed.  The thread opens the database and creates the indexer
+    /// once, and uses them for all jobs.  It holds the loader for as long as it runs.
+    async fn start_job_handler(&self) -> (Arc<WorkQueue<Job>>, AtomicCancellationFlag) {
         let handle = Handle::current();
         let backend = self.clone();
-        let (sender, receiver) = crossbeam_channel::unbounded::<Job>();
+        let queue = Arc::new(WorkQueue::new());
+        let thread_queue = queue.clone();
         let cancellation_flag = AtomicCancellationFlag::new();
         let thread_cancellation_flag = cancellation_flag.clone();
         thread::spawn(move || {
+            let mut db = match SQLiteWriter::open(&backend.db_path) {
+                Ok(db) => db,
+                Err(err) => {
+                    handle.block_on(capture!(
+                        [logger = &backend.logger, db_path = &backend.db_path],
+                        async move {
+                            logger
+                                .error(format!(
+                                    "failed to open database {}: {}",
+                                    db_path.display(),
+                                    err
+                                ))
+                                .await;
+                        }
+                    ));
+                    return;
+                }
+            };
+            let mut loader = match backend.loader.lock() {
+                Ok(l) => l,
+                Err(e) => {
+                    handle.block_on(capture!([logger = &backend.logger], async move {
+                        logger.error(format!("failed to lock loader: {}", e)).await;
+                    }));
+                    return;
+                }
+            };
+            let reporter = LspReporter {
+                handle: handle.clone(),
+                logger: backend.logger.clone(),
+            };
+            let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
+            indexer.max_file_time = backend.args.max_file_index_time;
+
             handle.block_on(capture!([logger = &backend.logger], async move {
                 logger.info("started job handler").await;
             }));
             loop {
-                match receiver.recv_timeout(Duration::from_millis(10)) {
-                    Ok(job) => job.run(&backend, handle.clone(), &thread_cancellation_flag),
-                    Err(RecvTimeoutError::Timeout) => {
+                match thread_queue.pop() {
+                    Some(job) => job.run(
+                        &backend,
+                        &mut indexer,
+                        &thread_queue,
+                        handle.clone(),
+                        &thread_cancellation_flag,
+                    ),
+                    None => {
                         if thread_cancellation_flag.check("").is_err() {
                             break;
                         }
+                        thread::sleep(Duration::from_millis(10));
                     }
-                    Err(RecvTimeoutError::Disconnected) => break,
                 }
             }
             handle.block_on(capture!([logger = &backend.logger], async move {
                 logger.info("stopped job handler").await;
             }));
         });
-        (sender, cancellation_flag)
+        (queue, cancellation_flag)
     }
 
     /// Opens or creates the database. If the database exists with an incompatible
@@ -172,50 +218,46 @@ impl Backend {
         Ok(())
     }
 
-    fn index(&self, path: &Path, handle: Handle, cancellation_flag: &dyn CancellationFlag) {
+    /// Schedules the files in a folder for indexing, as separate jobs, so that prioritized jobs
+    /// can run before the remaining files of the folder.  All files share the index time budget of
+    /// the folder.
+    fn schedule_folder(&self, path: &Path, queue: &WorkQueue<Job>, handle: Handle) {
+        let budget = self
+            .args
+            .max_folder_index_time
+            .map(|limit| Arc::new(IndexTimeBudget::new(limit)));
+        let mut count = 0;
+        for (source_root, source_path, strict) in iter_files_and_directories(vec![path]) {
+            queue.push(Job::IndexFile {
+                source_root,
+                source_path,
+                strict,
+                budget: budget.clone(),
+            });
+            count += 1;
+        }
+
         handle.block_on(capture!([logger = &self.logger, path], async move {
-            logger.info(format!("indexing {}", path.display())).await;
+            logger
+                .info(format!("scheduled {} files in {}", count, path.display()))
+                .await;
         }));
+    }
 
-        let mut db = match SQLiteWriter::open(&self.db_path) {
-            Ok(db) => db,
-            Err(err) => {
-                handle.block_on(capture!(
-                    [logger = &self.logger, db_path = &self.db_path],
-                    async move {
-                        logger
-                            .error(format!(
-                                "failed to open database {}: {}",
-                                db_path.display(),
-                                err
-                            ))
-                            .await;
-                    }
-                ));
-                return;
-            }
-        };
-
-        let mut loader = match self.loader.lock() {
-            Ok(l) => l,
-            Err(e) => {
-                handle.block_on(capture!([logger = &self.logger], async move {
-                    logger.error(format!("failed to lock loader: {}", e)).await;
-                }));
-                return;
-            }
-        };
+    fn index(
+        &self,
+        indexer: &mut Indexer,
+        source_root: &Path,
+        path: &Path,
+        strict: bool,
+        handle: Handle,
+        cancellation_flag: &dyn CancellationFlag,
+    ) {
+        handle.block_on(capture!([logger = &self.logger, path], async move {
+            logger.info(format!("indexing {}", path.display())).await;
+        }));
 
-        let reporter = LspReporter {
-            handle: handle.clone(),
-            logger: self.logger.clone(),
-        };
-        let folder_cancellation_flag =
-            CancelAfterDuration::from_option(self.args.max_folder_index_time);
-        let cancellation_flag = cancellation_flag | folder_cancellation_flag.as_ref();
-        let mut indexer = Indexer::new(&mut db, &mut loader, &reporter);
-        indexer.max_file_time = self.args.max_file_index_time;
-        let result = indexer.index_all(vec![path], None::<&Path>, &cancellation_flag);
+        let result = indexer.index_file(source_root, path, strict, cancellation_flag);
 
         handle.block_on(capture!([logger = &self.logger, path], async move {
             match result {
@@ -229,31 +271,18 @@ impl Backend {
         }));
     }
 
-    fn clean(&self, path: &Path, handle: Handle, _cancellation_flag: &dyn CancellationFlag) {
+    fn clean(
+        &self,
+        indexer: &mut Indexer,
+        path: &Path,
+        handle: Handle,
+        _cancellation_flag: &dyn CancellationFlag,
+    ) {
         handle.block_on(capture!([logger = &self.logger, path], async move {
             logger.info(format!("cleaning {}", path.display())).await;
         }));
 
-        let mut db = match SQLiteWriter::open(&self.db_path) {
-            Ok(db) => db,
-            Err(err) => {
-                handle.block_on(capture!(
-                    [logger = &self.logger, db_path = &self.db_path],
-                    async move {
-                        logger
-                            .error(format!(
-                                "failed to open database {}: {}",
-                                db_path.display(),
-                                err
-                            ))
-                            .await;
-                    }
-                ));
-                return;
-            }
-        };
-
-        match db.clean_file_or_directory(path) {
+        match indexer.db().clean_file_or_directory(path) {
             Ok(_) => handle.block_on(capture!([logger = &self.logger, path], async move {
                 logger.info(format!("cleaned {}", path.display())).await;
             })),
@@ -265,6 +294,24 @@ impl Backend {
         }
     }
 
+    /// Schedules indexing a document before any files that are waiting to be indexed.  If the
+    /// document is already waiting to be indexed as part of its folder, that job is moved to the
+    /// front instead.
+    async fn prioritize_document(&self, uri: &Url) {
+        let jobs = self.jobs.lock().await;
+        if let Ok(path) = uri.to_file_path() {
+            jobs.as_ref()
+                .unwrap()
+                .0
+                .prioritize_matching(Job::IndexPath(path), Job::is_same);
+        } else {
+            self.logger
+                .error(format!("No local path for document {}", uri))
+                .await;
+        }
+        drop(jobs);
+    }
+
     async fn definitions(&self, reference: SourcePosition) -> Vec<SourceSpan> {
         let mut db = match SQLiteReader::open(&self.db_path) {
             Ok(db) => db,
@@ -321,11 +368,7 @@ impl LanguageServer for Backend {
                     .info(format!("Initial workspace folder {}", folder.uri))
                     .await;
                 if let Ok(path) = folder.uri.to_file_path() {
-                    jobs.as_ref()
-                        .unwrap()
-                        .0
-                        .send(Job::IndexPath(path))
-                        .from_error()?;
+                    jobs.as_ref().unwrap().0.push(Job::IndexPath(path));
                 } else {
                     self.logger
                         .error(format!("No local path for workspace folder {}", folder.uri))
@@ -344,6 +387,7 @@ impl LanguageServer for Backend {
                 })),
                 text_document_sync: Some(
                     TextDocumentSyncOptions {
+                        open_close: Some(true),
                         save: Some(true.into()),
                         ..Default::default()
                     }
@@ -372,26 +416,18 @@ impl LanguageServer for Backend {
             .await;
     }
 
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        self.logger
+            .info(format!("Opened document {}", params.text_document.uri))
+            .await;
+        self.prioritize_document(&params.text_document.uri).await;
+    }
+
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        let jobs = self.jobs.lock().await;
         self.logger
             .info(format!("Saved document {}", params.text_document.uri))
             .await;
-        if let Ok(path) = params.text_document.uri.to_file_path() {
-            if let Err(e) = jobs.as_ref().unwrap().0.send(Job::IndexPath(path)) {
-                self.logger
-                    .error(format!("Scheduling index job failed: {}", e))
-                    .await;
-            }
-        } else {
-            self.logger
-                .error(format!(
-                    "No local path for document {}",
-                    params.text_document.uri
-                ))
-                .await;
-        }
-        drop(jobs);
+        self.prioritize_document(&params.text_document.uri).await;
     }
 
     async fn goto_definition(
@@ -483,11 +519,7 @@ impl LanguageServer for Backend {
                 .info(format!("Removed workspace folder {}", folder.uri))
                 .await;
             if let Ok(path) = folder.uri.to_file_path() {
-                if let Err(e) = jobs.as_ref().unwrap().0.send(Job::CleanPath(path)) {
-                    self.logger
-                        .error(format!("Scheduling clean job failed: {}", e))
-                        .await;
-                }
+                jobs.as_ref().unwrap().0.push(Job::CleanPath(path));
             } else {
                 self.logger
                     .error(format!("No local path for workspace folder {}", folder.uri))
@@ -499,11 +531,7 @@ impl LanguageServer for Backend {
                 .info(format!("Added workspace folder {}", folder.uri))
                 .await;
             if let Ok(path) = folder.uri.to_file_path() {
-                if let Err(e) = jobs.as_ref().unwrap().0.send(Job::IndexPath(path)) {
-                    self.logger
-                        .error(format!("Scheduling index job failed: {}", e))
-                        .await;
-                }
+                jobs.as_ref().unwrap().0.push(Job::IndexPath(path));
             } else {
                 self.logger
                     .error(format!("No local path for workspace folder {}", folder.uri))
@@ -563,18 +591,105 @@ impl<T, E: std::error::Error> FromStdError<T> for std::result::Result<T, E> {
 #[derive(Debug)]
 pub enum Job {
     IndexPath(PathBuf),
+    IndexFile {
+        source_root: PathBuf,
+        source_path: PathBuf,
+        strict: bool,
+        budget: Option<Arc<IndexTimeBudget>>,
+    },
     CleanPath(PathBuf),
 }
 
 impl Job {
-    fn run(self, backend: &Backend, handle: Handle, cancellation_flag: &dyn CancellationFlag) {
+    /// Returns whether two jobs do the same work, which is the case if they both clean the same
+    /// path, or both index the same path, whether it was scheduled by itself or for its folder.
+    fn is_same(&self, other: &Job) -> bool {
+        match (self, other) {
+            (Self::CleanPath(a), Self::CleanPath(b)) => a == b,
+            (Self::CleanPath(_), _) | (_, Self::CleanPath(_)) => false,
+            _ => self.path() == other.path(),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Self::IndexPath(path) | Self::CleanPath(path) => path,
+            Self::IndexFile { source_path, .. } => source_path,
+        }
+    }
+
+    fn run(
+        self,
+        backend: &Backend,
+        indexer: &mut Indexer,
+        queue: &WorkQueue<Job>,
+        handle: Handle,
+        cancellation_flag: &dyn CancellationFlag,
+    ) {
         match self {
-            Self::IndexPath(path) => backend.index(&path, handle, cancellation_flag),
-            Self::CleanPath(path) => backend.clean(&path, handle, cancellation_flag),
+            Self::IndexPath(path) if path.is_dir() => backend.schedule_folder(&path, queue, handle),
+            Self::IndexPath(path) => {
+                let source_root = path.parent().expect("expect file to have parent");
+                // the time limit starts when the job starts, not when it was scheduled
+                let limit_cancellation_flag =
+                    CancelAfterDuration::from_option(backend.args.max_folder_index_time);
+                backend.index(
+                    indexer,
+                    source_root,
+                    &path,
+                    true,
+                    handle,
+                    &(cancellation_flag | limit_cancellation_flag.as_ref()),
+                )
+            }
+            Self::IndexFile {
+                source_root,
+                source_path,
+                strict,
+                budget,
+            } => {
+                let start = Instant::now();
+                let limit_cancellation_flag = CancelAfterDuration::from_option(
+                    budget.as_ref().map(|budget| budget.remaining()),
+                );
+                backend.index(
+                    indexer,
+                    &source_root,
+                    &source_path,
+                    strict,
+                    handle,
+                    &(cancellation_flag | limit_cancellation_flag.as_ref()),
+                );
+                if let Some(budget) = budget {
+                    budget.spend(start.elapsed());
+                }
+            }
+            Self::CleanPath(path) => backend.clean(indexer, &path, handle, cancellation_flag),
         }
     }
 }
 
+/// The index time that is left for the files of a workspace folder.  Only the time spent indexing
+/// the files of the folder is counted, so that prioritized jobs that run in between do not use up
+/// the time of the folder.
+#[derive(Debug)]
+pub struct IndexTimeBudget(std::sync::Mutex<Duration>);
+
+impl IndexTimeBudget {
+    fn new(limit: Duration) -> Self {
+        Self(std::sync::Mutex::new(limit))
+    }
+
+    fn remaining(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+
+    fn spend(&self, elapsed: Duration) {
+        let mut remaining = self.0.lock().unwrap();
+        *remaining = remaining.saturating_sub(elapsed);
+    }
+}
+
 struct LspReporter {
     handle: Handle,
     logger: BackendLogger,
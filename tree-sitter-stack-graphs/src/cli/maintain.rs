@@ -0,0 +1,65 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use stack_graphs::storage::MaintenancePlan;
+use stack_graphs::storage::SQLiteWriter;
+use std::path::Path;
+
+#[derive(Args)]
+pub struct MaintainArgs {
+    /// Vacuum the database if its free pages take up at least this many bytes.
+    #[clap(long, value_name = "BYTES", default_value = "16777216")]
+    pub min_free_bytes: u64,
+
+    /// Analyze the database if at least this many rows changed since it was last analyzed.
+    #[clap(long, value_name = "ROWS", default_value = "10000")]
+    pub analyze_after: u64,
+
+    /// Rebuild all indexes if at least this many rows changed since the database was last
+    /// analyzed.
+    #[clap(long, value_name = "ROWS", default_value = "1000000")]
+    pub reindex_after: u64,
+
+    /// Run all maintenance tasks, regardless of the thresholds.
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl MaintainArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        if !db_path.exists() {
+            return Err(anyhow!("Database {} does not exist", db_path.display()));
+        }
+        let mut db = SQLiteWriter::open(db_path)?;
+        let plan = MaintenancePlan {
+            min_free_bytes: self.min_free_bytes,
+            min_changed_rows_for_analyze: self.analyze_after,
+            min_changed_rows_for_reindex: self.reindex_after,
+            force: self.force,
+        };
+        let report = db.maintain(&plan)?;
+        println!(
+            "{} free bytes, about {} rows changed since last analysis",
+            report.free_bytes, report.changed_rows
+        );
+        if report.vacuumed {
+            println!("vacuumed, freed {} bytes", report.bytes_freed);
+        }
+        if report.analyzed {
+            println!("analyzed");
+        }
+        if report.reindexed {
+            println!("rebuilt indexes");
+        }
+        if !(report.vacuumed || report.analyzed || report.reindexed) {
+            println!("no maintenance needed");
+        }
+        Ok(())
+    }
+}
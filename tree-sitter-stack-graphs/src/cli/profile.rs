@@ -0,0 +1,226 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use anyhow::anyhow;
+use clap::Args;
+use clap::ValueHint;
+use serde_json::json;
+use stack_graphs::graph::StackGraph;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use tree_sitter::Parser;
+use tree_sitter_graph::Variables;
+
+use crate::cli::util::iter_files_and_directories;
+use crate::cli::util::ExistingPathBufValueParser;
+use crate::loader::FileReader;
+use crate::loader::Loader;
+use crate::profile::RuleProfile;
+use crate::profile::RuleStats;
+use crate::NoCancellation;
+use crate::FILE_PATH_VAR;
+use crate::ROOT_PATH_VAR;
+
+#[derive(Args)]
+pub struct ProfileArgs {
+    /// Source file or directory paths.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+        value_parser = ExistingPathBufValueParser,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Number of most expensive stanzas to report per file and in total.
+    #[clap(long, value_name = "COUNT", default_value = "20")]
+    pub max_rules: usize,
+
+    /// Only report the aggregated profile of all files.
+    #[clap(long)]
+    pub total_only: bool,
+
+    /// Print the profile as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+/// The profile of a single file, and the time it took to build its graph.
+struct FileProfile {
+    path: PathBuf,
+    build_time: Duration,
+    profile: RuleProfile,
+}
+
+impl ProfileArgs {
+    pub fn run(self, mut loader: Loader) -> anyhow::Result<()> {
+        let mut file_reader = FileReader::new();
+        let mut files = Vec::new();
+        for (source_root, source_path, strict) in iter_files_and_directories(&self.source_paths) {
+            match Self::file_profile(&mut loader, &mut file_reader, &source_root, &source_path) {
+                Ok(Some(file)) => files.push(file),
+                Ok(None) if strict => {
+                    return Err(anyhow!(
+                        "No stack graph language found for {}",
+                        source_path.display()
+                    ))
+                }
+                Ok(None) => {}
+                Err(err) => eprintln!("Skipped {}: {}", source_path.display(), err),
+            }
+        }
+
+        let mut total = RuleProfile::default();
+        let mut total_build_time = Duration::ZERO;
+        for file in &files {
+            total.add(&file.profile);
+            total_build_time += file.build_time;
+        }
+        if self.json {
+            let mut value = json!({
+                "total": self.profile_to_json(total_build_time, &total),
+            });
+            if !self.total_only {
+                value["files"] = files
+                    .iter()
+                    .map(|file| {
+                        let mut value = self.profile_to_json(file.build_time, &file.profile);
+                        value["path"] = json!(file.path.to_string_lossy());
+                        value
+                    })
+                    .collect();
+            }
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        } else {
+            if !self.total_only {
+                for file in &files {
+                    self.print_profile(
+                        &file.path.display().to_string(),
+                        file.build_time,
+                        &file.profile,
+                    );
+                }
+            }
+            self.print_profile(
+                &format!("total of {} files", files.len()),
+                total_build_time,
+                &total,
+            );
+        }
+        Ok(())
+    }
+
+    /// Builds the graph of a file and computes its profile, or returns `None` if there is no
+    /// language for it.
+    fn file_profile(
+        loader: &mut Loader,
+        file_reader: &mut FileReader,
+        source_root: &Path,
+        source_path: &Path,
+    ) -> anyhow::Result<Option<FileProfile>> {
+        let lc = match loader
+            .load_for_file(source_path, file_reader, &NoCancellation)?
+            .primary
+        {
+            Some(lc) => lc,
+            None => return Ok(None),
+        };
+        let source = file_reader.get(source_path)?;
+
+        let mut parser = Parser::new();
+        parser.set_language(&lc.language)?;
+        let tree = parser
+            .parse(source, None)
+            .ok_or_else(|| anyhow!("failed to parse"))?;
+
+        let mut graph = StackGraph::new();
+        let file = graph.get_or_create_file(&source_path.to_string_lossy());
+        let mut globals = Variables::new();
+        globals
+            .add(
+                FILE_PATH_VAR.into(),
+                source_path.to_string_lossy().into_owned().into(),
+            )
+            .expect("failed to add file path variable");
+        globals
+            .add(
+                ROOT_PATH_VAR.into(),
+                source_root.to_string_lossy().into_owned().into(),
+            )
+            .expect("failed to add root path variable");
+        let start = Instant::now();
+        lc.sgl
+            .build_stack_graph_into(&mut graph, file, source, &globals, &NoCancellation)?;
+        let build_time = start.elapsed();
+
+        Ok(Some(FileProfile {
+            path: source_path.to_path_buf(),
+            build_time,
+            profile: RuleProfile::compute(&lc.sgl, &tree, source, &graph, file),
+        }))
+    }
+
+    fn profile_to_json(&self, build_time: Duration, profile: &RuleProfile) -> serde_json::Value {
+        json!({
+            "build_time_ms": build_time.as_secs_f64() * 1000.0,
+            "total": stats_to_json(&profile.total()),
+            "unattributed_nodes": profile.unattributed_nodes,
+            "unattributed_edges": profile.unattributed_edges,
+            "rules": profile
+                .most_expensive()
+                .into_iter()
+                .take(self.max_rules)
+                .map(|(rule, stats)| {
+                    let mut value = stats_to_json(stats);
+                    value["rule"] = json!(rule);
+                    value
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn print_profile(&self, title: &str, build_time: Duration, profile: &RuleProfile) {
+        let total = profile.total();
+        println!(
+            "{}: built in {:.1} ms, {} nodes, {} edges, {} unattributed",
+            title,
+            build_time.as_secs_f64() * 1000.0,
+            total.nodes + profile.unattributed_nodes,
+            total.edges + profile.unattributed_edges,
+            profile.unattributed_nodes + profile.unattributed_edges,
+        );
+        println!(
+            "| {:^9} | {:^10} | {:^9} | {:^9} | {:<50} |",
+            "matches", "match (ms)", "nodes", "edges", "stanza",
+        );
+        println!(
+            "|-----------|------------|-----------|-----------|----------------------------------------------------|"
+        );
+        for (rule, stats) in profile.most_expensive().into_iter().take(self.max_rules) {
+            println!(
+                "| {:>9} | {:>10.3} | {:>9} | {:>9} | {:<50} |",
+                stats.matches,
+                stats.match_time.as_secs_f64() * 1000.0,
+                stats.nodes,
+                stats.edges,
+                rule,
+            );
+        }
+        println!();
+    }
+}
+
+fn stats_to_json(stats: &RuleStats) -> serde_json::Value {
+    json!({
+        "matches": stats.matches,
+        "match_time_ms": stats.match_time.as_secs_f64() * 1000.0,
+        "nodes": stats.nodes,
+        "edges": stats.edges,
+    })
+}
@@ -5,13 +5,26 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
 use clap::ValueHint;
+use lsp_positions::Span;
+use serde_json::json;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::Node;
+use stack_graphs::graph::StackGraph;
+use stack_graphs::partial::PartialPath;
+use stack_graphs::partial::PartialPaths;
+use stack_graphs::serde::Filter;
+use stack_graphs::stitching::Database;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::Stats as StitchingStats;
 use stack_graphs::stitching::StitcherConfig;
@@ -41,17 +54,36 @@ pub struct QueryArgs {
     #[clap(long)]
     pub stats: bool,
 
+    /// Output format of the query results.
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Plain,
+    )]
+    pub format: OutputFormat,
+
     #[clap(subcommand)]
     target: Target,
 }
 
+/// Output format of query results
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable source excerpts
+    Plain,
+    /// A JSON array of query results
+    Json,
+    /// A SARIF log with a result per query result
+    Sarif,
+}
+
 impl QueryArgs {
     pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
         if self.wait_at_start {
             wait_for_input()?;
         }
         let mut db = SQLiteReader::open(&db_path)?;
-        let stitching_stats = self.target.run(&mut db, self.stats)?;
+        let stitching_stats = self.target.run(&mut db, self.stats, self.format)?;
         if self.stats {
             println!();
             print_stitching_stats(stitching_stats);
@@ -64,16 +96,29 @@ impl QueryArgs {
 
 #[derive(Subcommand)]
 pub enum Target {
+    /// Find the definitions of references.
     Definition(Definition),
+    /// Find the references to definitions.
+    Reference(Reference),
 }
 
 impl Target {
-    fn run(self, db: &mut SQLiteReader, collect_stats: bool) -> anyhow::Result<StitchingStats> {
-        let reporter = ConsoleReporter::details();
+    fn run(
+        self,
+        db: &mut SQLiteReader,
+        collect_stats: bool,
+        format: OutputFormat,
+    ) -> anyhow::Result<StitchingStats> {
+        // structured output must not be interleaved with progress reporting
+        let reporter = match format {
+            OutputFormat::Plain => ConsoleReporter::details(),
+            OutputFormat::Json | OutputFormat::Sarif => ConsoleReporter::none(),
+        };
         let mut querier = Querier::new(db, &reporter);
         querier.set_collect_stats(collect_stats);
         match self {
-            Self::Definition(cmd) => cmd.run(&mut querier)?,
+            Self::Definition(cmd) => cmd.run(&mut querier, format)?,
+            Self::Reference(cmd) => cmd.run(&mut querier, format)?,
         }
         Ok(querier.into_stats())
     }
@@ -89,71 +134,289 @@ pub struct Definition {
         value_parser,
     )]
     pub references: Vec<SourcePosition>,
+
+    /// Write a visualization of only the nodes, edges, and paths that resolve the references to
+    /// the given HTML file.
+    #[clap(long, value_name = "OUTPUT_PATH", value_hint = ValueHint::AnyPath)]
+    pub visualize: Option<PathBuf>,
 }
 
 impl Definition {
-    pub fn run(self, querier: &mut Querier) -> anyhow::Result<()> {
+    pub fn run(self, querier: &mut Querier, format: OutputFormat) -> anyhow::Result<()> {
         let cancellation_flag = NoCancellation;
-        let mut file_reader = FileReader::new();
+        let mut printer = ResultPrinter::new(QueryKind::Definition, format);
+        querier.set_collect_paths(self.visualize.is_some());
         for mut reference in self.references {
             reference.canonicalize()?;
+            let results = querier.definitions(reference, &cancellation_flag)?;
+            printer.add(results);
+        }
+        printer.finish()?;
+        if let Some(output) = &self.visualize {
+            let html = querier.visualize_paths("definitions")?;
+            if let Some(dir) = output.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(output, html)?;
+            // structured output must not be followed by anything else
+            if format == OutputFormat::Plain {
+                println!("Visualization at {}", output.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+pub struct Reference {
+    /// Definition source positions, formatted as PATH:LINE:COLUMN.
+    #[clap(
+        value_name = "SOURCE_POSITION",
+        required = true,
+        value_hint = ValueHint::AnyPath,
+        value_parser,
+    )]
+    pub definitions: Vec<SourcePosition>,
+}
+
+impl Reference {
+    pub fn run(self, querier: &mut Querier, format: OutputFormat) -> anyhow::Result<()> {
+        let cancellation_flag = NoCancellation;
+        let mut printer = ResultPrinter::new(QueryKind::Reference, format);
+        for mut definition in self.definitions {
+            definition.canonicalize()?;
+            let results = querier.references(definition, &cancellation_flag)?;
+            printer.add(results);
+        }
+        printer.finish()?;
+        Ok(())
+    }
+}
+
+/// The kind of query that produced a result.
+#[derive(Clone, Copy)]
+enum QueryKind {
+    Definition,
+    Reference,
+}
+
+impl QueryKind {
+    /// The name of the queried source.
+    fn source(&self) -> &'static str {
+        match self {
+            Self::Definition => "reference",
+            Self::Reference => "definition",
+        }
+    }
+
+    /// The name of the found targets.
+    fn target(&self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Reference => "reference",
+        }
+    }
+}
+
+/// Prints query results in the requested format.  Plain results are printed as soon as they are
+/// added, structured results are collected and printed when the printer is finished.
+struct ResultPrinter {
+    kind: QueryKind,
+    format: OutputFormat,
+    file_reader: FileReader,
+    results: Vec<QueryResult>,
+}
+
+impl ResultPrinter {
+    fn new(kind: QueryKind, format: OutputFormat) -> Self {
+        Self {
+            kind,
+            format,
+            file_reader: FileReader::new(),
+            results: Vec::new(),
+        }
+    }
 
-            let results = querier.definitions(reference.clone(), &cancellation_flag)?;
-            let numbered = results.len() > 1;
-            let indent = if numbered { 6 } else { 0 };
+    fn add(&mut self, results: Vec<QueryResult>) {
+        match self.format {
+            OutputFormat::Plain => self.print_plain(results),
+            OutputFormat::Json | OutputFormat::Sarif => self.results.extend(results),
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        let value = match self.format {
+            OutputFormat::Plain => return Ok(()),
+            OutputFormat::Json => self.to_json(),
+            OutputFormat::Sarif => self.to_sarif(),
+        };
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        Ok(())
+    }
+
+    fn print_plain(&mut self, results: Vec<QueryResult>) {
+        let numbered = results.len() > 1;
+        let indent = if numbered { 6 } else { 0 };
+        if numbered {
+            println!(
+                "found {} {}s at position",
+                results.len(),
+                self.kind.source()
+            );
+        }
+        for (idx, QueryResult { source, targets }) in results.into_iter().enumerate() {
             if numbered {
-                println!("found {} references at position", results.len());
+                println!("{:4}: queried {}", idx, self.kind.source());
+            } else {
+                println!("queried {}", self.kind.source());
             }
-            for (
-                idx,
-                QueryResult {
-                    source: reference,
-                    targets: definitions,
-                },
-            ) in results.into_iter().enumerate()
-            {
-                if numbered {
-                    println!("{:4}: queried reference", idx);
-                } else {
-                    println!("queried reference");
-                }
-                println!(
+            println!(
+                "{}",
+                Excerpt::from_source(
+                    &source.path,
+                    self.file_reader.get(&source.path).unwrap_or_default(),
+                    source.first_line(),
+                    source.first_line_column_range(),
+                    indent
+                )
+            );
+            match targets.len() {
+                0 => println!("{}has no {}s", " ".repeat(indent), self.kind.target()),
+                1 => println!("{}has {}", " ".repeat(indent), self.kind.target()),
+                n => println!("{}has {} {}s", " ".repeat(indent), n, self.kind.target()),
+            }
+            for target in targets.into_iter() {
+                print!(
                     "{}",
                     Excerpt::from_source(
-                        &reference.path,
-                        file_reader.get(&reference.path).unwrap_or_default(),
-                        reference.first_line(),
-                        reference.first_line_column_range(),
+                        &target.path,
+                        self.file_reader.get(&target.path).unwrap_or_default(),
+                        target.first_line(),
+                        target.first_line_column_range(),
                         indent
                     )
                 );
-                match definitions.len() {
-                    0 => println!("{}has no definitions", " ".repeat(indent)),
-                    1 => println!("{}has definition", " ".repeat(indent)),
-                    n => println!("{}has {} definitions", " ".repeat(indent), n),
-                }
-                for definition in definitions.into_iter() {
-                    print!(
-                        "{}",
-                        Excerpt::from_source(
-                            &definition.path,
-                            file_reader.get(&definition.path).unwrap_or_default(),
-                            definition.first_line(),
-                            definition.first_line_column_range(),
-                            indent
-                        )
+                if let Some(confidence) = target.confidence {
+                    println!(
+                        "{}with {:.0}% confidence",
+                        " ".repeat(indent),
+                        confidence * 100.0
                     );
                 }
+                if target.speculative {
+                    println!("{}speculative", " ".repeat(indent));
+                }
             }
         }
-        Ok(())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let results = self
+            .results
+            .iter()
+            .map(|result| {
+                json!({
+                    self.kind.source(): source_span_to_json(&result.source),
+                    format!("{}s", self.kind.target()): result
+                        .targets
+                        .iter()
+                        .map(source_span_to_json)
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::Value::Array(results)
+    }
+
+    fn to_sarif(&self) -> serde_json::Value {
+        let results = self
+            .results
+            .iter()
+            .map(|result| {
+                json!({
+                    "ruleId": self.kind.target(),
+                    "kind": "informational",
+                    "level": "none",
+                    "message": {
+                        "text": format!(
+                            "{} has {} {}(s)",
+                            self.kind.source(),
+                            result.targets.len(),
+                            self.kind.target(),
+                        ),
+                    },
+                    "locations": [source_span_to_sarif(&result.source)],
+                    "relatedLocations": result
+                        .targets
+                        .iter()
+                        .map(source_span_to_sarif)
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                },
+                "results": results,
+            }],
+        })
     }
 }
 
+/// Converts a source span to JSON, using 1-based lines and (grapheme) columns.
+fn source_span_to_json(span: &SourceSpan) -> serde_json::Value {
+    let mut value = json!({
+        "path": span.path.to_string_lossy(),
+        "start": {
+            "line": span.span.start.line + 1,
+            "column": span.span.start.column.grapheme_offset + 1,
+        },
+        "end": {
+            "line": span.span.end.line + 1,
+            "column": span.span.end.column.grapheme_offset + 1,
+        },
+    });
+    if let Some(kind) = &span.kind {
+        value["kind"] = json!(kind.to_string());
+    }
+    if let Some(confidence) = span.confidence {
+        value["confidence"] = json!(confidence);
+    }
+    if span.speculative {
+        value["speculative"] = json!(true);
+    }
+    value
+}
+
+/// Converts a source span to a SARIF location.
+fn source_span_to_sarif(span: &SourceSpan) -> serde_json::Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": {
+                "uri": span.path.to_string_lossy(),
+            },
+            "region": {
+                "startLine": span.span.start.line + 1,
+                "startColumn": span.span.start.column.grapheme_offset + 1,
+                "endLine": span.span.end.line + 1,
+                "endColumn": span.span.end.column.grapheme_offset + 1,
+            },
+        },
+    })
+}
+
 pub struct Querier<'a> {
     db: &'a mut SQLiteReader,
     reporter: &'a dyn Reporter,
     stats: Option<StitchingStats>,
+    paths: Option<Vec<PartialPath>>,
 }
 
 impl<'a> Querier<'a> {
@@ -162,6 +425,7 @@ impl<'a> Querier<'a> {
             db,
             reporter,
             stats: None,
+            paths: None,
         }
     }
 
@@ -173,6 +437,42 @@ impl<'a> Querier<'a> {
         }
     }
 
+    /// Sets whether the paths that resolve the references of definition queries are collected,
+    /// so that they can be [visualized][Querier::visualize_paths].
+    pub fn set_collect_paths(&mut self, collect_paths: bool) {
+        if !collect_paths {
+            self.paths = None;
+        } else if self.paths.is_none() {
+            self.paths = Some(Vec::new());
+        }
+    }
+
+    /// Returns a visualization of the collected paths, which only includes the nodes and edges
+    /// that the paths visit.
+    pub fn visualize_paths(
+        &mut self,
+        title: &str,
+    ) -> std::result::Result<String, serde_json::Error> {
+        let paths = self.paths.as_deref().unwrap_or_default();
+        let (graph, partials, _) = self.db.get();
+        let mut filter = PathsFilter::default();
+        let mut paths_db = Database::new();
+        for path in paths {
+            let nodes = path
+                .edges
+                .iter(partials)
+                .filter_map(|edge| graph.node_for_id(edge.source_node_id))
+                .chain(std::iter::once(path.end_node))
+                .collect::<Vec<_>>();
+            filter
+                .edges
+                .extend(nodes.windows(2).map(|pair| (pair[0], pair[1])));
+            filter.nodes.extend(nodes);
+            paths_db.add_partial_path(graph, partials, path.clone());
+        }
+        graph.to_html_string(title, partials, &mut paths_db, &filter)
+    }
+
     pub fn definitions(
         &mut self,
         reference: SourcePosition,
@@ -180,24 +480,15 @@ impl<'a> Querier<'a> {
     ) -> Result<Vec<QueryResult>> {
         let log_path = PathBuf::from(reference.to_string());
 
-        let mut file_reader = FileReader::new();
-        let tag = file_reader.get(&reference.path).ok().map(sha1);
-        match self
-            .db
-            .status_for_file(&reference.path.to_string_lossy(), tag.as_ref())?
-        {
-            FileStatus::Indexed => {}
-            _ => {
-                self.reporter.started(&log_path);
-                self.reporter.failed(&log_path, "file not indexed", None);
-                return Ok(Default::default());
-            }
+        if !self.is_indexed(&reference, &log_path)? {
+            return Ok(Default::default());
         }
 
         self.reporter.started(&log_path);
 
+        // load the root paths for all symbols in the file at once, instead of in every phase
         self.db
-            .load_graph_for_file(&reference.path.to_string_lossy())?;
+            .prefetch_root_paths_for_file(&reference.path.to_string_lossy(), cancellation_flag)?;
         let (graph, _, _) = self.db.get();
 
         let starting_nodes = reference.iter_references(graph).collect::<Vec<_>>();
@@ -212,48 +503,22 @@ impl<'a> Querier<'a> {
             let reference_span = SourceSpan {
                 path: reference.path.clone(),
                 span,
+                kind: None,
+                confidence: None,
+                speculative: false,
             };
 
             let mut reference_paths = Vec::new();
-            let stitcher_config = StitcherConfig::default()
-                // always detect similar paths, we don't know the language configurations for the data in the database
-                .with_detect_similar_paths(true)
-                .with_collect_stats(self.stats.is_some());
-            let ref_result = ForwardPartialPathStitcher::find_all_complete_partial_paths(
-                self.db,
-                std::iter::once(node),
-                stitcher_config,
-                &cancellation_flag,
-                |_g, _ps, p| {
-                    reference_paths.push(p.clone());
-                },
-            );
-            match ref_result {
-                Ok(ref_stats) => {
-                    if let Some(stats) = &mut self.stats {
-                        *stats += ref_stats
-                    }
-                }
-                Err(err) => {
-                    self.reporter.failed(&log_path, "query timed out", None);
-                    return Err(err.into());
-                }
+            self.find_complete_paths(std::iter::once(node), &log_path, cancellation_flag, |p| {
+                reference_paths.push(p.clone())
+            })?;
+
+            let actual_paths =
+                self.remove_shadowed_paths(reference_paths, &log_path, cancellation_flag)?;
+            if let Some(paths) = &mut self.paths {
+                paths.extend(actual_paths.iter().cloned());
             }
-
             let (graph, partials, _) = self.db.get();
-            let mut actual_paths = Vec::new();
-            for reference_path in &reference_paths {
-                if let Err(err) = cancellation_flag.check("shadowing") {
-                    self.reporter.failed(&log_path, "query timed out", None);
-                    return Err(err.into());
-                }
-                if reference_paths
-                    .iter()
-                    .all(|other| !other.shadows(partials, reference_path))
-                {
-                    actual_paths.push(reference_path.clone());
-                }
-            }
 
             let definitions = actual_paths
                 .into_iter()
@@ -262,11 +527,21 @@ impl<'a> Querier<'a> {
                         Some(p) => p.span.clone(),
                         None => return None,
                     };
+                    let kind = graph.definition_kind(path.end_node);
+                    // skip definitions whose confidence is unknown
+                    let confidence = path.confidence(graph, partials).ok()?;
+                    let speculative = path.is_speculative(graph, partials);
                     let path = match graph[path.end_node].id().file() {
                         Some(f) => PathBuf::from(graph[f].name()),
                         None => return None,
                     };
-                    Some(SourceSpan { path, span })
+                    Some(SourceSpan {
+                        path,
+                        span,
+                        kind,
+                        confidence,
+                        speculative,
+                    })
                 })
                 .collect::<Vec<_>>();
 
@@ -290,11 +565,268 @@ impl<'a> Querier<'a> {
         Ok(result)
     }
 
+    pub fn references(
+        &mut self,
+        definition: SourcePosition,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<QueryResult>> {
+        let log_path = PathBuf::from(definition.to_string());
+
+        if !self.is_indexed(&definition, &log_path)? {
+            return Ok(Default::default());
+        }
+
+        self.reporter.started(&log_path);
+
+        self.db
+            .load_graph_for_file(&definition.path.to_string_lossy())?;
+        let (graph, _, _) = self.db.get();
+
+        let definition_nodes = definition.iter_definitions(graph).collect::<Vec<_>>();
+        if definition_nodes.is_empty() {
+            self.reporter
+                .cancelled(&log_path, "no definitions at location", None);
+            return Ok(Default::default());
+        }
+
+        // references to the definitions can be in any file, so load all indexed graphs
+        let files = {
+            let mut files = self.db.list_all()?;
+            let files = files
+                .try_iter()?
+                .filter_map(|entry| match entry {
+                    Ok(entry) => match entry.status {
+                        FileStatus::Indexed => Some(Ok(entry.path)),
+                        _ => None,
+                    },
+                    Err(err) => Some(Err(err)),
+                })
+                .collect::<stack_graphs::storage::Result<Vec<_>>>()?;
+            files
+        };
+        for file in &files {
+            cancellation_flag.check("loading graphs")?;
+            self.db.load_graph_for_file(&file.to_string_lossy())?;
+        }
+
+        let (graph, _, _) = self.db.get();
+        let reference_nodes = graph
+            .iter_nodes()
+            .filter(|node| graph[*node].is_reference())
+            .collect::<Vec<_>>();
+        let definition_handles = definition_nodes
+            .iter()
+            .map(|(node, _)| *node)
+            .collect::<Vec<_>>();
+
+        let mut paths_per_reference = HashMap::<Handle<Node>, Vec<PartialPath>>::new();
+        self.find_complete_paths(
+            reference_nodes.into_iter(),
+            &log_path,
+            cancellation_flag,
+            |p| {
+                paths_per_reference
+                    .entry(p.start_node)
+                    .or_default()
+                    .push(p.clone())
+            },
+        )?;
+
+        let mut references_per_definition = HashMap::<Handle<Node>, Vec<SourceSpan>>::new();
+        for (_, paths) in paths_per_reference {
+            let actual_paths = self.remove_shadowed_paths(paths, &log_path, cancellation_flag)?;
+            let (graph, partials, _) = self.db.get();
+            for path in actual_paths {
+                if !definition_handles.contains(&path.end_node) {
+                    continue;
+                }
+                let span = match graph.source_info(path.start_node) {
+                    Some(p) => p.span.clone(),
+                    None => continue,
+                };
+                let file = match graph[path.start_node].id().file() {
+                    Some(f) => PathBuf::from(graph[f].name()),
+                    None => continue,
+                };
+                let confidence = match path.confidence(graph, partials) {
+                    Ok(confidence) => confidence,
+                    Err(_) => continue,
+                };
+                references_per_definition
+                    .entry(path.end_node)
+                    .or_default()
+                    .push(SourceSpan {
+                        path: file,
+                        span,
+                        kind: None,
+                        confidence,
+                        speculative: path.is_speculative(graph, partials),
+                    });
+            }
+        }
+
+        let (graph, _, _) = self.db.get();
+        let result = definition_nodes
+            .into_iter()
+            .map(|(node, span)| {
+                let mut references = references_per_definition.remove(&node).unwrap_or_default();
+                references.sort_by(compare_source_spans);
+                QueryResult {
+                    source: SourceSpan {
+                        path: definition.path.clone(),
+                        span,
+                        kind: graph.definition_kind(node),
+                        confidence: None,
+                        speculative: false,
+                    },
+                    targets: references,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let count: usize = result.iter().map(|r| r.targets.len()).sum();
+        self.reporter.succeeded(
+            &log_path,
+            &format!(
+                "found {} references for {} definitions",
+                count,
+                result.len()
+            ),
+            None,
+        );
+
+        Ok(result)
+    }
+
+    /// Checks if the file of the given position is indexed and up-to-date, and reports a failure
+    /// if it is not.
+    fn is_indexed(&mut self, position: &SourcePosition, log_path: &Path) -> Result<bool> {
+        let mut file_reader = FileReader::new();
+        let tag = file_reader.get(&position.path).ok().map(sha1);
+        match self
+            .db
+            .status_for_file(&position.path.to_string_lossy(), tag.as_ref())?
+        {
+            FileStatus::Indexed => Ok(true),
+            _ => {
+                self.reporter.started(log_path);
+                self.reporter.failed(log_path, "file not indexed", None);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Finds all complete paths starting at the given nodes.
+    fn find_complete_paths<I, F>(
+        &mut self,
+        starting_nodes: I,
+        log_path: &Path,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = Handle<Node>>,
+        F: FnMut(&PartialPath),
+    {
+        let stitcher_config = StitcherConfig::default()
+            // always detect similar paths, we don't know the language configurations for the data in the database
+            .with_detect_similar_paths(true)
+            .with_collect_stats(self.stats.is_some());
+        let result = ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            self.db,
+            starting_nodes,
+            stitcher_config,
+            &cancellation_flag,
+            |_g, _ps, p| visit(p),
+        );
+        match result {
+            Ok(stats) => {
+                if let Some(total_stats) = &mut self.stats {
+                    *total_stats += stats
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.reporter.failed(log_path, "query timed out", None);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Removes paths that are shadowed by other paths in the given set.
+    fn remove_shadowed_paths(
+        &mut self,
+        paths: Vec<PartialPath>,
+        log_path: &Path,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<PartialPath>> {
+        let (_, partials, _) = self.db.get();
+        let mut actual_paths = Vec::new();
+        for path in &paths {
+            if let Err(err) = cancellation_flag.check("shadowing") {
+                self.reporter.failed(log_path, "query timed out", None);
+                return Err(err.into());
+            }
+            if paths.iter().all(|other| !other.shadows(partials, path)) {
+                actual_paths.push(path.clone());
+            }
+        }
+        Ok(actual_paths)
+    }
+
     pub fn into_stats(self) -> StitchingStats {
         self.stats.unwrap_or_default()
     }
 }
 
+/// Includes only the nodes and edges that are visited by a set of paths.
+#[derive(Default)]
+struct PathsFilter {
+    nodes: HashSet<Handle<Node>>,
+    edges: HashSet<(Handle<Node>, Handle<Node>)>,
+}
+
+impl Filter for PathsFilter {
+    fn include_file(&self, _graph: &StackGraph, _file: &Handle<File>) -> bool {
+        true
+    }
+
+    fn include_node(&self, _graph: &StackGraph, node: &Handle<Node>) -> bool {
+        self.nodes.contains(node)
+    }
+
+    fn include_edge(
+        &self,
+        _graph: &StackGraph,
+        source: &Handle<Node>,
+        sink: &Handle<Node>,
+    ) -> bool {
+        self.edges.contains(&(*source, *sink))
+    }
+
+    fn include_partial_path(
+        &self,
+        _graph: &StackGraph,
+        _paths: &PartialPaths,
+        _path: &PartialPath,
+    ) -> bool {
+        true
+    }
+}
+
+fn compare_source_spans(a: &SourceSpan, b: &SourceSpan) -> std::cmp::Ordering {
+    a.path
+        .cmp(&b.path)
+        .then_with(|| compare_spans(&a.span, &b.span))
+}
+
+fn compare_spans(a: &Span, b: &Span) -> std::cmp::Ordering {
+    a.start
+        .line
+        .cmp(&b.start.line)
+        .then_with(|| a.start.column.utf8_offset.cmp(&b.start.column.utf8_offset))
+}
+
 #[derive(Debug, Error)]
 pub enum QueryError {
     #[error("cancelled at {0}")]
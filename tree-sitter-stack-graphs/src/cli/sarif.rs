@@ -0,0 +1,170 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use clap::ValueHint;
+use serde_json::json;
+use stack_graphs::analysis::find_unresolved_references;
+use stack_graphs::stitching::StitcherConfig;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::NoCancellation;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The rule of results for references that do not resolve to any definition.
+const UNRESOLVED_REFERENCE_RULE: &str = "unresolved-reference";
+/// The rule of results for files that could not be indexed.
+const INDEXING_ERROR_RULE: &str = "indexing-error";
+
+#[derive(Args)]
+pub struct SarifArgs {
+    /// Do not report unresolved references, which requires resolving all references in the
+    /// database.
+    #[clap(long)]
+    pub skip_unresolved: bool,
+
+    /// Directory that file locations are relative to.  Defaults to the current directory.
+    /// Files outside of it are reported with absolute URIs.
+    #[clap(long, value_name = "PATH", value_hint = ValueHint::DirPath)]
+    pub root: Option<PathBuf>,
+
+    /// Write the SARIF log to the given file, instead of printing it.
+    #[clap(long, short = 'o', value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub output: Option<PathBuf>,
+}
+
+impl SarifArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let root = match &self.root {
+            Some(root) => root.canonicalize()?,
+            None => std::env::current_dir()?,
+        };
+        let mut db = SQLiteReader::open(&db_path)?;
+
+        let mut results = Vec::new();
+        for entry in db.list_all()?.try_iter()? {
+            let entry = entry?;
+            if let FileStatus::Error(error) = &entry.status {
+                results.push(json!({
+                    "ruleId": INDEXING_ERROR_RULE,
+                    "ruleIndex": 1,
+                    "level": "error",
+                    "message": { "text": format!("File could not be indexed: {}", error) },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": artifact_location(&entry.path, &root),
+                        },
+                    }],
+                }));
+            }
+        }
+        if !self.skip_unresolved {
+            let unresolved =
+                find_unresolved_references(&mut db, StitcherConfig::default(), &NoCancellation)?;
+            for reference in unresolved {
+                let mut physical_location = json!({
+                    "artifactLocation": artifact_location(&reference.file, &root),
+                });
+                if let Some(span) = &reference.span {
+                    physical_location["region"] = json!({
+                        "startLine": span.start.line + 1,
+                        "startColumn": span.start.column.utf16_offset + 1,
+                        "endLine": span.end.line + 1,
+                        "endColumn": span.end.column.utf16_offset + 1,
+                    });
+                }
+                results.push(json!({
+                    "ruleId": UNRESOLVED_REFERENCE_RULE,
+                    "ruleIndex": 0,
+                    "level": "warning",
+                    "message": {
+                        "text": format!("Reference to `{}` does not resolve to any definition", reference.symbol),
+                    },
+                    "locations": [{ "physicalLocation": physical_location }],
+                }));
+            }
+        }
+
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                        "rules": [
+                            {
+                                "id": UNRESOLVED_REFERENCE_RULE,
+                                "shortDescription": { "text": "Reference does not resolve to any definition" },
+                                "defaultConfiguration": { "level": "warning" },
+                            },
+                            {
+                                "id": INDEXING_ERROR_RULE,
+                                "shortDescription": { "text": "File could not be indexed" },
+                                "defaultConfiguration": { "level": "error" },
+                            },
+                        ],
+                    },
+                },
+                "originalUriBaseIds": {
+                    "SRCROOT": { "uri": directory_uri(&root) },
+                },
+                "columnKind": "utf16CodeUnits",
+                "results": results,
+            }],
+        });
+
+        let mut output: Box<dyn Write> = match &self.output {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            }
+            None => Box::new(std::io::stdout().lock()),
+        };
+        serde_json::to_writer_pretty(&mut output, &log)?;
+        writeln!(output)?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns the SARIF artifact location of a file, which is relative to the root directory if the
+/// file is inside it, and an absolute URI otherwise.
+fn artifact_location(path: &Path, root: &Path) -> serde_json::Value {
+    match path.strip_prefix(root) {
+        Ok(relative) => json!({
+            "uri": uri_path(relative),
+            "uriBaseId": "SRCROOT",
+        }),
+        Err(_) => json!({ "uri": format!("file://{}", uri_path(path)) }),
+    }
+}
+
+fn directory_uri(path: &Path) -> String {
+    format!("file://{}/", uri_path(path).trim_end_matches('/'))
+}
+
+/// Returns a path as a URI path, with forward slashes and percent-encoded special characters.
+fn uri_path(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
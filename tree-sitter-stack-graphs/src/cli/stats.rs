@@ -0,0 +1,159 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use serde_json::json;
+use stack_graphs::storage::FileStats;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::SQLiteReader;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::cli::util::sha1;
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Print statistics as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+impl StatsArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let mut db = SQLiteReader::open(&db_path)?;
+        let db_stats = db.database_stats()?;
+
+        let mut total = Totals::default();
+        let mut languages = BTreeMap::new();
+        for file in &db_stats.files {
+            let language = file
+                .language
+                .clone()
+                .unwrap_or_else(|| "<unknown>".to_string());
+            let stale = is_stale(file);
+            total.add(file, stale);
+            languages
+                .entry(language)
+                .or_insert_with(Totals::default)
+                .add(file, stale);
+        }
+
+        if self.json {
+            let value = json!({
+                "database": db_path.to_string_lossy(),
+                "size": db_stats.size,
+                "total": total.to_json(),
+                "languages": languages
+                    .iter()
+                    .map(|(language, totals)| (language.clone(), totals.to_json()))
+                    .collect::<serde_json::Map<_, _>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        } else {
+            println!(
+                "Database {} ({})",
+                db_path.display(),
+                display_size(db_stats.size)
+            );
+            println!();
+            print_totals_header("language");
+            for (language, totals) in &languages {
+                print_totals_row(language, totals);
+            }
+            print_totals_row("total", &total);
+        }
+        Ok(())
+    }
+}
+
+/// Aggregated statistics for a set of files.
+#[derive(Default)]
+struct Totals {
+    files: usize,
+    indexed: usize,
+    failed: usize,
+    stale: usize,
+    nodes: usize,
+    edges: usize,
+    node_paths: usize,
+    root_paths: usize,
+}
+
+impl Totals {
+    fn add(&mut self, file: &FileStats, stale: bool) {
+        self.files += 1;
+        match file.status {
+            FileStatus::Missing => {}
+            FileStatus::Indexed => self.indexed += 1,
+            FileStatus::Error(_) => self.failed += 1,
+        }
+        if stale {
+            self.stale += 1;
+        }
+        self.nodes += file.nodes;
+        self.edges += file.edges;
+        self.node_paths += file.node_paths;
+        self.root_paths += file.root_paths;
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "files": self.files,
+            "indexed": self.indexed,
+            "failed": self.failed,
+            "stale": self.stale,
+            "nodes": self.nodes,
+            "edges": self.edges,
+            "node_paths": self.node_paths,
+            "root_paths": self.root_paths,
+        })
+    }
+}
+
+/// A file is stale if it no longer exists, or its content has changed since it was indexed.
+fn is_stale(file: &FileStats) -> bool {
+    match std::fs::read_to_string(&file.path) {
+        Ok(content) => sha1(&content) != file.tag,
+        Err(_) => true,
+    }
+}
+
+fn display_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn print_totals_header(title: &str) {
+    println!(
+        "| {:^15} | {:^9} | {:^9} | {:^9} | {:^9} | {:^11} | {:^11} | {:^11} | {:^11} |",
+        title, "files", "indexed", "failed", "stale", "nodes", "edges", "node paths", "root paths",
+    );
+    println!(
+        "|-----------------|-----------|-----------|-----------|-----------|-------------|-------------|-------------|-------------|"
+    );
+}
+
+fn print_totals_row(title: &str, totals: &Totals) {
+    println!(
+        "| {:>15} | {:>9} | {:>9} | {:>9} | {:>9} | {:>11} | {:>11} | {:>11} | {:>11} |",
+        title,
+        totals.files,
+        totals.indexed,
+        totals.failed,
+        totals.stale,
+        totals.nodes,
+        totals.edges,
+        totals.node_paths,
+        totals.root_paths,
+    );
+}
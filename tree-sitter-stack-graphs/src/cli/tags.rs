@@ -0,0 +1,202 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::ArgGroup;
+use clap::Args;
+use clap::ValueEnum;
+use clap::ValueHint;
+use itertools::Itertools;
+use stack_graphs::analysis::symbol_contexts;
+use stack_graphs::analysis::SymbolContext;
+use stack_graphs::storage::SQLiteReader;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::util::indexed_files;
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("paths")
+        .required(true)
+        .args(&["source_paths", "all"]),
+))]
+pub struct TagsArgs {
+    /// Source file or directory paths to write tags for.
+    #[clap(
+        value_name = "SOURCE_PATH",
+        value_hint = ValueHint::AnyPath,
+    )]
+    pub source_paths: Vec<PathBuf>,
+
+    /// Write tags for all indexed source paths.
+    #[clap(long, short = 'a')]
+    pub all: bool,
+
+    /// Tags file format.
+    #[clap(long, value_enum, default_value_t = TagsFormat::Ctags)]
+    pub format: TagsFormat,
+
+    /// Tags file path.  Defaults to `tags` for ctags, and `TAGS` for etags, in the current
+    /// directory.  File names in the tags file are relative to its directory.
+    #[clap(long, short = 'o', value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TagsFormat {
+    /// Universal-ctags extended format, as used by Vim.
+    Ctags,
+    /// Etags format, as used by Emacs.
+    Etags,
+}
+
+impl TagsArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let mut db = SQLiteReader::open(&db_path)?;
+        let files = indexed_files(&mut db, &self.source_paths, self.all)?;
+
+        let output = match (&self.output, self.format) {
+            (Some(path), _) => path.clone(),
+            (None, TagsFormat::Ctags) => PathBuf::from("tags"),
+            (None, TagsFormat::Etags) => PathBuf::from("TAGS"),
+        };
+        let output_dir = match output.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                std::fs::create_dir_all(dir)?;
+                dir.canonicalize()?
+            }
+            _ => std::env::current_dir()?,
+        };
+
+        let mut tags = Vec::new();
+        for file in files {
+            let contexts = symbol_contexts(&mut db, &file, None)?;
+            let scope_kinds = contexts
+                .iter()
+                .filter_map(|c| Some((c.qualified_name.clone(), c.kind.as_ref()?.to_string())))
+                .collect::<HashMap<_, _>>();
+            let file_name = pathdiff::diff_paths(&file, &output_dir)
+                .unwrap_or_else(|| PathBuf::from(&file))
+                .to_string_lossy()
+                .to_string();
+            for context in contexts {
+                if context.symbol.contains(&['\t', '\n'][..]) {
+                    continue;
+                }
+                tags.push(Tag::new(&file_name, context, &scope_kinds));
+            }
+            db.clear();
+        }
+
+        let mut output = std::io::BufWriter::new(std::fs::File::create(&output)?);
+        match self.format {
+            TagsFormat::Ctags => write_ctags(&mut output, tags)?,
+            TagsFormat::Etags => write_etags(&mut output, tags)?,
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+struct Tag {
+    name: String,
+    file: String,
+    /// The 1-based line of the definition.
+    line: usize,
+    /// The UTF-8 byte offset of the start of the line of the definition.
+    line_offset: usize,
+    signature: Option<String>,
+    kind: Option<String>,
+    /// The kind and name of the enclosing definition, if any.
+    scope: Option<(String, String)>,
+}
+
+impl Tag {
+    fn new(file: &str, context: SymbolContext, scope_kinds: &HashMap<String, String>) -> Tag {
+        let scope = scope_name(&context.qualified_name, &context.symbol).and_then(|scope| {
+            let kind = scope_kinds.get(scope)?;
+            Some((kind.clone(), scope.to_string()))
+        });
+        Tag {
+            file: file.to_string(),
+            line: context.span.start.line + 1,
+            line_offset: context.span.start.containing_line.start,
+            signature: context.signature,
+            kind: context.kind.map(|kind| kind.to_string()),
+            scope,
+            name: context.symbol,
+        }
+    }
+}
+
+/// Returns the qualified name of the definition that encloses a definition, which is its
+/// qualified name without its symbol and the separator before it.
+fn scope_name<'a>(qualified_name: &'a str, symbol: &str) -> Option<&'a str> {
+    let scope = qualified_name
+        .strip_suffix(symbol)?
+        .trim_end_matches(|c| matches!(c, '.' | ':' | '#' | '/'));
+    if scope.is_empty() {
+        None
+    } else {
+        Some(scope)
+    }
+}
+
+/// Writes tags in the universal-ctags extended format, sorted by name, with line number
+/// addresses and `kind`, `line`, and `scope` fields.
+fn write_ctags<W: Write>(output: &mut W, mut tags: Vec<Tag>) -> std::io::Result<()> {
+    tags.sort_by(|a, b| (&a.name, &a.file, a.line).cmp(&(&b.name, &b.file, b.line)));
+    writeln!(
+        output,
+        "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/"
+    )?;
+    writeln!(
+        output,
+        "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/"
+    )?;
+    writeln!(output, "!_TAG_PROGRAM_NAME\t{}\t//", env!("CARGO_PKG_NAME"))?;
+    writeln!(
+        output,
+        "!_TAG_PROGRAM_VERSION\t{}\t//",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    for tag in &tags {
+        write!(output, "{}\t{}\t{};\"", tag.name, tag.file, tag.line)?;
+        if let Some(kind) = &tag.kind {
+            write!(output, "\tkind:{}", kind)?;
+        }
+        write!(output, "\tline:{}", tag.line)?;
+        if let Some((kind, name)) = &tag.scope {
+            write!(output, "\tscope:{}:{}", kind, name)?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Writes tags in the etags format, grouped by file.  The tag text is the signature of the
+/// definition, or its name if it has no signature.
+fn write_etags<W: Write>(output: &mut W, mut tags: Vec<Tag>) -> std::io::Result<()> {
+    tags.sort_by(|a, b| (&a.file, a.line, &a.name).cmp(&(&b.file, b.line, &b.name)));
+    for (file, file_tags) in &tags.iter().group_by(|tag| &tag.file) {
+        let mut section = String::new();
+        for tag in file_tags {
+            let text = tag.signature.as_deref().unwrap_or(&tag.name);
+            section.push_str(&format!(
+                "{}\x7f{}\x01{},{}\n",
+                text.replace('\x7f', " "),
+                tag.name,
+                tag.line,
+                tag.line_offset
+            ));
+        }
+        write!(output, "\x0c\n{},{}\n{}", file, section.len(), section)?;
+    }
+    Ok(())
+}
@@ -37,6 +37,7 @@ use crate::cli::util::ExistingPathBufValueParser;
 use crate::cli::util::PathSpec;
 use crate::loader::ContentProvider;
 use crate::loader::FileReader;
+use crate::loader::InjectionError;
 use crate::loader::LanguageConfiguration;
 use crate::loader::Loader;
 use crate::test::Test;
@@ -125,6 +126,23 @@ pub struct TestArgs {
     )]
     pub save_visualization: Option<PathSpec>,
 
+    /// Compare the stack graph and minimal partial paths of tests to golden snapshot files.
+    /// Missing snapshot files are created.
+    /// Takes an optional path specification argument for the snapshot file.
+    /// [default: %r/%d/%n.snapshot]
+    #[clap(
+        long,
+        value_name = "PATH_SPEC",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "%r/%d/%n.snapshot"
+    )]
+    pub snapshot: Option<PathSpec>,
+
+    /// Overwrite snapshot files with the current snapshots, instead of comparing them.
+    #[clap(long, requires = "snapshot")]
+    pub update_snapshots: bool,
+
     /// Controls when graphs, paths, or visualization are saved.
     #[clap(
         long,
@@ -172,6 +190,8 @@ impl TestArgs {
             save_graph: None,
             save_paths: None,
             save_visualization: None,
+            snapshot: None,
+            update_snapshots: false,
             output_mode: OutputMode::OnFailure,
             no_builtins: false,
             max_test_time: None,
@@ -256,10 +276,10 @@ impl TestArgs {
             test_path.to_path_buf()
         };
         let mut file_reader = MappingFileReader::new(&load_path, test_path);
-        let lc = match loader
+        let languages = loader
             .load_for_file(&load_path, &mut file_reader, cancellation_flag.as_ref())?
-            .primary
-        {
+            .languages();
+        let lc = match loader.configurations_for_languages(&languages).primary {
             Some(lc) => lc,
             None => return Ok(TestResult::new()),
         };
@@ -297,6 +317,12 @@ impl TestArgs {
         }
         let mut globals = Variables::new();
         for test_fragment in &test.fragments {
+            // the loader is borrowed mutably to load injected languages, so the language
+            // configuration is looked up again for every fragment
+            let lc = loader
+                .configurations_for_languages(&languages)
+                .primary
+                .expect("primary language to be loaded");
             let result = if let Some(fa) = test_fragment
                 .path
                 .file_name()
@@ -327,13 +353,29 @@ impl TestArgs {
                     )
                     .unwrap_or_default();
 
-                lc.sgl.build_stack_graph_into(
-                    &mut test.graph,
-                    test_fragment.file,
-                    &test_fragment.source,
-                    &globals,
-                    cancellation_flag.as_ref(),
-                )
+                let injections = lc
+                    .sgl
+                    .builder_into_stack_graph(
+                        &mut test.graph,
+                        test_fragment.file,
+                        &test_fragment.source,
+                    )
+                    .build_with_injections(&globals, cancellation_flag.as_ref());
+                match injections.map(|injections| {
+                    loader.build_injections_into(
+                        &mut test.graph,
+                        test_fragment.file,
+                        &test_fragment.source,
+                        &globals,
+                        injections,
+                        false,
+                        cancellation_flag.as_ref(),
+                    )
+                }) {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(InjectionError::Build(err))) | Err(err) => Err(err),
+                    Ok(Err(InjectionError::Load(err))) => return Err(err.into()),
+                }
             } else {
                 return Err(anyhow!(
                     "Test fragment {} not supported by language of test file {}",
@@ -343,6 +385,10 @@ impl TestArgs {
             };
             match result {
                 Err(err) => {
+                    let lc = loader
+                        .configurations_for_languages(&languages)
+                        .primary
+                        .expect("primary language to be loaded");
                     file_status.failure(
                         "failed to build stack graph",
                         Some(&format!(
@@ -372,12 +418,32 @@ impl TestArgs {
                 },
             )?;
         }
-        let result = test.run(
+        let mut result = test.run(
             &mut partials,
             &mut db,
             stitcher_config,
             cancellation_flag.as_ref(),
         )?;
+        let mut snapshot_outputs = Vec::new();
+        if let Some(spec) = &self.snapshot {
+            let snapshot_path = spec.format(test_root, test_path);
+            let snapshot =
+                test.snapshot(&mut partials, stitcher_config, cancellation_flag.as_ref())?;
+            if self.update_snapshots || !snapshot_path.exists() {
+                if let Some(dir) = snapshot_path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&snapshot_path, snapshot)?;
+                snapshot_outputs.push(format!(
+                    "{}: snapshot at {}",
+                    test_path.display(),
+                    snapshot_path.display()
+                ));
+            } else {
+                let expected = std::fs::read_to_string(&snapshot_path)?;
+                result.absorb(test.check_snapshot(&snapshot, &expected, &snapshot_path));
+            }
+        }
         let success = result.failure_count() == 0;
         let outputs = if self.output_mode.test(!success) {
             let files = test.fragments.iter().map(|f| f.file).collect::<Vec<_>>();
@@ -395,6 +461,10 @@ impl TestArgs {
         } else {
             Vec::default()
         };
+        let outputs = snapshot_outputs
+            .into_iter()
+            .chain(outputs)
+            .collect::<Vec<_>>();
 
         if success {
             let details = outputs.join("\n");
@@ -16,10 +16,13 @@ use lsp_positions::Span;
 use sha1::Digest;
 use sha1::Sha1;
 use stack_graphs::arena::Handle;
+use stack_graphs::graph::DefinitionKind;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::stats::FrequencyDistribution;
 use stack_graphs::stitching::Stats as StitchingStats;
+use stack_graphs::storage::FileStatus;
+use stack_graphs::storage::SQLiteReader;
 use stack_graphs::storage::Stats as StorageStats;
 use std::ffi::OsStr;
 use std::ffi::OsString;
@@ -30,9 +33,11 @@ use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
+use std::time::Instant;
 use walkdir::WalkDir;
 
 use crate::cli::index::IndexingStats;
+use crate::cli::index::IndexingSummary;
 use crate::cli::util::reporter::Reporter;
 
 pub mod reporter;
@@ -193,12 +198,30 @@ impl SourcePosition {
         &'a self,
         graph: &'a StackGraph,
     ) -> impl Iterator<Item = (Handle<Node>, Span)> + 'a {
+        self.iter_nodes(graph, |node| node.is_reference())
+    }
+
+    pub fn iter_definitions<'a>(
+        &'a self,
+        graph: &'a StackGraph,
+    ) -> impl Iterator<Item = (Handle<Node>, Span)> + 'a {
+        self.iter_nodes(graph, |node| node.is_definition())
+    }
+
+    fn iter_nodes<'a, F>(
+        &'a self,
+        graph: &'a StackGraph,
+        include: F,
+    ) -> impl Iterator<Item = (Handle<Node>, Span)> + 'a
+    where
+        F: Fn(&Node) -> bool + Copy + 'a,
+    {
         graph
             .get_file(&self.path.to_string_lossy())
             .into_iter()
             .flat_map(move |file| {
                 graph.nodes_for_file(file).filter_map(move |node| {
-                    if !graph[node].is_reference() {
+                    if !include(&graph[node]) {
                         return None;
                     }
                     let source_info = match graph.source_info(node) {
@@ -301,6 +324,13 @@ pub struct SourceSpan {
     pub path: PathBuf,
     /// Span
     pub span: Span,
+    /// Kind of the definition at this span, if it is a definition with a syntax type
+    pub kind: Option<DefinitionKind>,
+    /// Confidence between 0 and 1 of the resolution that found this span, if the rules of the
+    /// language mark some of the edges it followed as uncertain
+    pub confidence: Option<f32>,
+    /// Whether the resolution that found this span visited speculative nodes
+    pub speculative: bool,
 }
 
 impl SourceSpan {
@@ -326,6 +356,21 @@ pub(crate) fn duration_from_seconds_str(s: &str) -> Result<Duration, anyhow::Err
     Ok(Duration::new(seconds, 0))
 }
 
+/// Parses a global variable of the form `NAME=VALUE`. The variables that are set by the indexer
+/// itself cannot be given.
+pub(crate) fn global_from_str(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected NAME=VALUE, got {}", s))?;
+    if name.is_empty() {
+        return Err(anyhow!("missing variable name in {}", s));
+    }
+    if name == crate::FILE_PATH_VAR || name == crate::ROOT_PATH_VAR {
+        return Err(anyhow!("variable {} is set by the indexer", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
 #[cfg(feature = "lsp")]
 pub(crate) fn duration_from_milliseconds_str(s: &str) -> Result<Duration, anyhow::Error> {
     let milliseconds = s.parse::<u64>()?;
@@ -456,6 +501,104 @@ impl<'a> CLIFileReporter<'a> {
     }
 }
 
+/// A simple progress bar, drawn on a single line of the console's standard error. It must be
+/// cleared before other output is printed, and is redrawn on the next update.
+pub(super) struct ProgressBar {
+    total: usize,
+    done: usize,
+    enabled: bool,
+    visible: bool,
+    last_drawn: Instant,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 40;
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub(super) fn new(total: usize, enabled: bool) -> Self {
+        Self {
+            total,
+            done: 0,
+            enabled,
+            visible: false,
+            last_drawn: Instant::now(),
+        }
+    }
+
+    /// Record that an item is done, and redraw the progress bar if necessary.
+    pub(super) fn inc(&mut self) {
+        self.done += 1;
+        if !self.enabled {
+            return;
+        }
+        if !self.visible
+            || self.done == self.total
+            || self.last_drawn.elapsed() >= Self::REDRAW_INTERVAL
+        {
+            self.draw();
+        }
+    }
+
+    /// Remove the progress bar from the console, so that other output can be printed.
+    pub(super) fn clear(&mut self) {
+        if !self.visible {
+            return;
+        }
+        eprint!("\r{:width$}\r", "", width = Self::WIDTH + 32);
+        let _ = std::io::stderr().flush();
+        self.visible = false;
+    }
+
+    fn draw(&mut self) {
+        let filled = if self.total == 0 {
+            Self::WIDTH
+        } else {
+            Self::WIDTH * self.done.min(self.total) / self.total
+        };
+        eprint!(
+            "\r[{}{}] {}/{} files",
+            "=".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            self.done,
+            self.total,
+        );
+        let _ = std::io::stderr().flush();
+        self.visible = true;
+        self.last_drawn = Instant::now();
+    }
+}
+
+/// Returns the names of the indexed files in the given source paths, or of all indexed files, in
+/// order.
+pub(crate) fn indexed_files(
+    db: &mut SQLiteReader,
+    source_paths: &[PathBuf],
+    all: bool,
+) -> anyhow::Result<Vec<String>> {
+    let mut files = Vec::new();
+    if all {
+        for entry in db.list_all()?.try_iter()? {
+            let entry = entry?;
+            if let FileStatus::Indexed = entry.status {
+                files.push(entry.path.to_string_lossy().to_string());
+            }
+        }
+    } else {
+        for source_path in source_paths {
+            let source_path = source_path.canonicalize()?;
+            for entry in db.list_file_or_directory(&source_path)?.try_iter()? {
+                let entry = entry?;
+                if let FileStatus::Indexed = entry.status {
+                    files.push(entry.path.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
 pub(crate) fn sha1(value: &str) -> String {
     let mut hasher = Sha1::new();
     hasher.update(value);
@@ -508,10 +651,33 @@ pub(super) fn print_indexing_stats(stats: IndexingStats) {
     print_quartiles_row("total graph edges", stats.total_graph_edges);
     print_quartiles_row("node out degrees", stats.node_out_degrees);
     print_value_row("root out degree", stats.root_out_degree);
+    print_quartiles_row("file indexing time (ms)", stats.file_indexing_times);
     println!();
     print_stitching_stats(stats.stitching_stats);
 }
 
+pub(super) fn print_indexing_summary(summary: &IndexingSummary) {
+    println!(
+        "Indexed {} files, skipped {} files, {} failed in {:.2?}.",
+        summary.indexed,
+        summary.skipped,
+        summary.failed.len(),
+        summary.elapsed,
+    );
+    if !summary.slowest.is_empty() {
+        println!("Slowest files:");
+        for (path, time) in &summary.slowest {
+            println!("  {:>10.2?}  {}", time, path.display());
+        }
+    }
+    if !summary.failed.is_empty() {
+        println!("Failed files:");
+        for (path, status) in &summary.failed {
+            println!("  {}: {}", path.display(), status);
+        }
+    }
+}
+
 pub(super) fn print_stitching_stats(stats: StitchingStats) {
     print_quartiles_header("stitching stats");
     print_quartiles_row("initial paths", stats.initial_paths);
@@ -553,6 +719,14 @@ pub(super) fn print_database_stats(stats: StorageStats) {
         "| {:>29} | {:>9} | {:>9} |",
         "rootpaths", stats.root_path_loads, stats.root_path_cached
     );
+    println!(
+        "| {:>29} | {:>9} | {:>9} |",
+        "rootpath prefetches", stats.root_path_prefetches, stats.root_path_prefetch_hits
+    );
+    println!(
+        "| {:>29} | {:>9} | {:>9} |",
+        "local resolutions", stats.local_resolution_misses, stats.local_resolution_hits
+    );
 }
 
 fn print_quartiles_header(title: &str) {
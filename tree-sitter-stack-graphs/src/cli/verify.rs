@@ -0,0 +1,77 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use clap::Args;
+use stack_graphs::storage::verify;
+use stack_graphs::storage::RepairAction;
+use stack_graphs::storage::SQLiteReader;
+use stack_graphs::storage::SQLiteWriter;
+use std::path::Path;
+
+use crate::cli::util::sha1;
+use crate::loader::FileReader;
+use crate::NoCancellation;
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Do not compare the tags of indexed files with their current sources.
+    #[clap(long)]
+    pub skip_sources: bool,
+
+    /// Remove the data of all files with issues, so that they are indexed again by the next
+    /// index run.
+    #[clap(long)]
+    pub clean: bool,
+
+    #[clap(long, short = 'v')]
+    pub verbose: bool,
+}
+
+impl VerifyArgs {
+    pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
+        let report = {
+            let db = SQLiteReader::open(&db_path)?;
+            let mut file_reader = FileReader::new();
+            let mut source_tag = |file: &str| file_reader.get(Path::new(file)).ok().map(sha1);
+            let source_tag: Option<&mut dyn FnMut(&str) -> Option<String>> = if self.skip_sources {
+                None
+            } else {
+                Some(&mut source_tag)
+            };
+            verify(&db, source_tag, &NoCancellation)?
+        };
+
+        if self.verbose {
+            for issue in &report.issues {
+                println!("{}: {}", issue.file(), issue);
+            }
+        }
+        let plan = report.repair_plan();
+        println!(
+            "verified {} files, found {} issues in {} files",
+            report.files,
+            report.issues.len(),
+            plan.len(),
+        );
+        for (path, action) in &plan {
+            let action = match action {
+                RepairAction::Reindex => "reindex",
+                RepairAction::Remove => "remove",
+            };
+            println!("{}: {}", path.display(), action);
+        }
+
+        if self.clean && !plan.is_empty() {
+            let mut db = SQLiteWriter::open(&db_path)?;
+            for (path, _) in &plan {
+                db.clean_file(path)?;
+            }
+            println!("removed data for {} files", plan.len());
+        }
+        Ok(())
+    }
+}
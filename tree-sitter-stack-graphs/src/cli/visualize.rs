@@ -6,12 +6,14 @@
 // ------------------------------------------------------------------------------------------------
 
 use clap::Args;
+use clap::ValueEnum;
 use clap::ValueHint;
 use stack_graphs::serde::NoFilter;
 use stack_graphs::stitching::Database;
 use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
 use stack_graphs::storage::SQLiteReader;
+use stack_graphs::visualization::SourceNavigation;
 use stack_graphs::NoCancellation;
 use std::path::Path;
 use std::path::PathBuf;
@@ -39,16 +41,77 @@ pub struct VisualizeArgs {
         default_value = "stack-graph.html",
     )]
     pub output: PathBuf,
+
+    /// Paths to include in the visualization.
+    #[clap(long, value_enum, default_value_t = PathsMode::Complete)]
+    pub paths: PathsMode,
+
+    /// Post a navigation message to the parent window when a node is clicked, so that the page
+    /// that embeds the visualization can jump to the source of the node.
+    #[clap(long, conflicts_with("navigate_url"))]
+    pub post_navigation_message: bool,
+
+    /// Open a URL built from the given template when a node is clicked.  The placeholders
+    /// {file}, {line}, {column}, {end_line}, and {end_column} are replaced by the location of
+    /// the node, e.g. `vscode://file{file}:{line}:{column}`.
+    #[clap(long, value_name = "TEMPLATE")]
+    pub navigate_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PathsMode {
+    /// Complete paths found by resolving all references in the selected files.
+    Complete,
+    /// Partial paths as they are stored in the database for the selected files.
+    Stored,
 }
 
 impl VisualizeArgs {
     pub fn run(self, db_path: &Path) -> anyhow::Result<()> {
         let cancellation_flag = &NoCancellation;
+        let navigation = match (&self.navigate_url, self.post_navigation_message) {
+            (Some(template), _) => SourceNavigation::Url(template.clone()),
+            (None, true) => SourceNavigation::PostMessage,
+            (None, false) => SourceNavigation::None,
+        };
         let mut db = SQLiteReader::open(&db_path)?;
         for source_path in &self.source_paths {
             let source_path = source_path.canonicalize()?;
-            db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?;
+            match self.paths {
+                PathsMode::Complete => {
+                    db.load_graphs_for_file_or_directory(&source_path, cancellation_flag)?
+                }
+                PathsMode::Stored => {
+                    db.load_partial_paths_for_file_or_directory(&source_path, cancellation_flag)?
+                }
+            }
         }
+        let html = match self.paths {
+            PathsMode::Complete => Self::visualize_complete_paths(&mut db, &navigation)?,
+            PathsMode::Stored => {
+                let (graph, partials, stored_paths_db) = db.get();
+                graph.to_html_string_with_navigation(
+                    "stack-graph",
+                    partials,
+                    stored_paths_db,
+                    &NoFilter,
+                    &navigation,
+                )?
+            }
+        };
+        if let Some(dir) = self.output.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.output, html)?;
+        println!("Visualization at {}", self.output.display());
+        Ok(())
+    }
+
+    fn visualize_complete_paths(
+        db: &mut SQLiteReader,
+        navigation: &SourceNavigation,
+    ) -> anyhow::Result<String> {
+        let cancellation_flag = &NoCancellation;
         let (graph, _, _) = db.get();
         let starting_nodes = graph
             .iter_nodes()
@@ -59,7 +122,7 @@ impl VisualizeArgs {
             // always detect similar paths, we don't know the language configurations for the data in the database
             .with_detect_similar_paths(true);
         ForwardPartialPathStitcher::find_all_complete_partial_paths(
-            &mut db,
+            db,
             starting_nodes,
             stitcher_config,
             cancellation_flag,
@@ -68,13 +131,13 @@ impl VisualizeArgs {
             },
         )?;
         let (graph, partials, _) = db.get();
-        let html =
-            graph.to_html_string("stack-graph", partials, &mut complete_paths_db, &NoFilter)?;
-        if let Some(dir) = self.output.parent() {
-            std::fs::create_dir_all(dir)?;
-        }
-        std::fs::write(&self.output, html)?;
-        println!("Visualization at {}", self.output.display());
-        Ok(())
+        let html = graph.to_html_string_with_navigation(
+            "stack-graph",
+            partials,
+            &mut complete_paths_db,
+            &NoFilter,
+            navigation,
+        )?;
+        Ok(html)
     }
 }
@@ -0,0 +1,194 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Measures how much of a source file the stack graph rules of a language cover.
+//!
+//! The [coverage][FileCoverage] of a file is the fraction of its identifier tokens that became
+//! references or definitions in its stack graph.  Identifier tokens are the named leaves of the
+//! parse tree whose kind is `identifier`, or ends in `_identifier`, such as `type_identifier` or
+//! `property_identifier`.  A token is covered by a reference or definition node if the source
+//! span of the node contains the token.
+//!
+//! Uncovered tokens are reported with the kind of their parent in the parse tree, which is the
+//! syntactic construct that the rules do not handle yet.
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::StackGraph;
+use tree_sitter::Point;
+use tree_sitter::Tree;
+
+/// The coverage of a source file by its stack graph.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The number of identifier tokens in the file.
+    pub identifiers: usize,
+    /// The number of identifier tokens that are covered by a reference.
+    pub references: usize,
+    /// The number of identifier tokens that are covered by a definition.
+    pub definitions: usize,
+    /// The identifier tokens that are covered by neither a reference nor a definition, in source
+    /// order.
+    pub uncovered: Vec<UncoveredIdentifier>,
+}
+
+/// An identifier token that is not covered by a reference or definition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UncoveredIdentifier {
+    /// The source text of the token.
+    pub text: String,
+    /// The kind of the token in the parse tree.
+    pub kind: String,
+    /// The kind of the parent of the token in the parse tree, if it has one.
+    pub parent_kind: Option<String>,
+    /// The start position of the token.  Rows and columns are 0-indexed, and columns are UTF-8
+    /// byte offsets.
+    pub start: Point,
+    /// The end position of the token.
+    pub end: Point,
+}
+
+impl FileCoverage {
+    /// Computes the coverage of a file by the nodes of the given graph that belong to it.  The
+    /// tree must be the parse tree of the given source, which the graph was built from.
+    pub fn compute(tree: &Tree, source: &str, graph: &StackGraph, file: Handle<File>) -> Self {
+        let mut reference_spans = Spans::default();
+        let mut definition_spans = Spans::default();
+        for node in graph.nodes_for_file(file) {
+            let spans = if graph[node].is_reference() {
+                &mut reference_spans
+            } else if graph[node].is_definition() {
+                &mut definition_spans
+            } else {
+                continue;
+            };
+            if let Some(source_info) = graph.source_info(node) {
+                let span = &source_info.span;
+                spans.add(
+                    (span.start.line, span.start.column.utf8_offset),
+                    (span.end.line, span.end.column.utf8_offset),
+                );
+            }
+        }
+
+        let mut coverage = Self::default();
+        let mut cursor = tree.walk();
+        let mut done = false;
+        while !done {
+            let node = cursor.node();
+            if node.is_named() && node.child_count() == 0 && is_identifier_kind(node.kind()) {
+                let start = node.start_position();
+                let end = node.end_position();
+                let start = (start.row, start.column);
+                let end = (end.row, end.column);
+                let is_reference = reference_spans.contains(start, end);
+                let is_definition = definition_spans.contains(start, end);
+                coverage.identifiers += 1;
+                if is_reference {
+                    coverage.references += 1;
+                }
+                if is_definition {
+                    coverage.definitions += 1;
+                }
+                if !is_reference && !is_definition {
+                    coverage.uncovered.push(UncoveredIdentifier {
+                        text: source[node.byte_range()].to_string(),
+                        kind: node.kind().to_string(),
+                        parent_kind: node.parent().map(|parent| parent.kind().to_string()),
+                        start: node.start_position(),
+                        end: node.end_position(),
+                    });
+                }
+            }
+            // visit the tree in pre-order
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+            loop {
+                if !cursor.goto_parent() {
+                    done = true;
+                    break;
+                }
+                if cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+        coverage
+    }
+
+    /// Returns the number of identifier tokens that are covered by a reference or a definition.
+    pub fn covered(&self) -> usize {
+        self.identifiers - self.uncovered.len()
+    }
+
+    /// Returns the fraction of identifier tokens that are covered.  A file without identifiers
+    /// is fully covered.
+    pub fn ratio(&self) -> f64 {
+        if self.identifiers == 0 {
+            return 1.0;
+        }
+        self.covered() as f64 / self.identifiers as f64
+    }
+
+    /// Returns the number of uncovered identifier tokens per kind of parent, which are the
+    /// constructs that the rules do not cover.
+    pub fn uncovered_constructs(&self) -> BTreeMap<String, usize> {
+        let mut constructs = BTreeMap::new();
+        for identifier in &self.uncovered {
+            let construct = identifier
+                .parent_kind
+                .clone()
+                .unwrap_or_else(|| identifier.kind.clone());
+            *constructs.entry(construct).or_default() += 1;
+        }
+        constructs
+    }
+
+    /// Adds the counts and uncovered tokens of another file to this one, to aggregate the
+    /// coverage of several files.
+    pub fn add(&mut self, other: &FileCoverage) {
+        self.identifiers += other.identifiers;
+        self.references += other.references;
+        self.definitions += other.definitions;
+        self.uncovered.extend(other.uncovered.iter().cloned());
+    }
+}
+
+/// Returns whether a parse tree node of the given kind is an identifier token.
+pub fn is_identifier_kind(kind: &str) -> bool {
+    kind == "identifier" || kind.ends_with("_identifier")
+}
+
+type Position = (usize, usize);
+
+/// The source spans of a set of nodes.  Most nodes span exactly their identifier token, so exact
+/// matches are looked up first, before spans that contain a token are searched.
+#[derive(Default)]
+struct Spans {
+    exact: HashSet<(Position, Position)>,
+    all: Vec<(Position, Position)>,
+}
+
+impl Spans {
+    fn add(&mut self, start: Position, end: Position) {
+        if self.exact.insert((start, end)) {
+            self.all.push((start, end));
+        }
+    }
+
+    fn contains(&self, start: Position, end: Position) -> bool {
+        self.exact.contains(&(start, end))
+            || self
+                .all
+                .iter()
+                .any(|(span_start, span_end)| *span_start <= start && end <= *span_end)
+    }
+}
@@ -173,6 +173,10 @@
 //! }
 //! ```
 //!
+//! The syntax type of a definition determines its [`DefinitionKind`][stack_graphs::graph::DefinitionKind],
+//! which query results report so that they can be filtered by kind. Use one of `module`, `class`, `interface`,
+//! `type`, `function`, `method`, `field`, or `variable` where applicable. Any other value is reported as is.
+//!
 //! ### Annotating definitions with definiens information
 //!
 //! You cannot annotate definitions with a definiens, which is the thing the definition covers. For example, for
@@ -217,6 +221,46 @@
 //!
 //! (If you don't specify a `precedence`, the default is 0.)
 //!
+//! Edges to and from the root node can have a `condition` attribute, such as the path of the
+//! module that a definition belongs to, or that a reference is imported from.  A path that arrives
+//! at the root node along an edge with a condition can only leave it along edges with the same
+//! condition, or without a condition:
+//!
+//! ``` skip
+//! (package_declaration (scoped_identifier) @name) @package {
+//!   edge ROOT_NODE -> @package.defs
+//!   attr (ROOT_NODE -> @package.defs) condition = (source-text @name)
+//! }
+//! ```
+//!
+//! Conditions on edges that do not start or end at the root node are ignored.
+//!
+//! Edges that do not always hold, such as edges that guess the members of an object via duck
+//! typing, can have a `confidence` attribute, whose value is a percentage between 0 and 100.  The
+//! confidence of a resolved path is the product of the confidences of its edges, and can be used to
+//! rank the definitions that a reference resolves to.  (If you don't specify a `confidence`, the
+//! edge is certain.)
+//!
+//! ``` skip
+//! (attribute object: (_) @object attribute: (identifier) @name) @attr {
+//!   edge @attr.ref -> @object.any_member
+//!   attr (@attr.ref -> @object.any_member) confidence = 50
+//! }
+//! ```
+//!
+//! Nodes whose edges are guesses that are often wrong, such as imports of modules whose names are
+//! computed from strings at runtime, can have a `speculative` attribute.  Any path that visits a
+//! speculative node is speculative itself, and the definitions that are only found by speculative
+//! paths are marked as such in query results, so that they can be hidden:
+//!
+//! ``` skip
+//! (call function: (identifier) @_fn arguments: (argument_list (string) @name)) @call {
+//!   node @call.import
+//!   attr (@call.import) type = "push_symbol", symbol = (source-text @name), speculative
+//!   edge @call.import -> ROOT_NODE
+//! }
+//! ```
+//!
 //! ### Referring to the singleton nodes
 //!
 //! The _root node_ and _jump to scope node_ are singleton nodes that always exist for all stack
@@ -248,6 +292,44 @@
 //! }
 //! ```
 //!
+//! Some debug entries are added to all nodes and edges automatically.  The `tsg_location` entry
+//! contains the location of the statement that created the node or edge, and the `tsg_rule` entry
+//! contains the location of the stanza that statement is part of, as `PATH:LINE:COLUMN`.  The
+//! latter can be retrieved with [`StackGraph::node_rule`][] and [`StackGraph::edge_rule`][], and
+//! helps to trace misbehaving graph elements back to the rules that created them.  The
+//! [`profile`][] module uses it to find the rules that are responsible for large graphs.
+//!
+//! ### Injecting other languages
+//!
+//! Files can contain content in other languages, such as SQL queries in string literals.  Such
+//! content is declared by adding the `injection_language` and `injection_content` attributes to a
+//! scope node.  The `injection_language` attribute names the injected language, and the
+//! `injection_content` attribute is the syntax node, or list of syntax nodes, containing the
+//! injected content:
+//!
+//! ``` skip
+//! (call function:(identifier)@fun arguments:(argument_list (string (string_content)@sql)))@call {
+//!   node @call.sql_scope
+//!   attr (@call.sql_scope) injection_language = "sql", injection_content = @sql
+//!   edge @call.sql_scope -> @call.lexical_scope
+//! }
+//! ```
+//!
+//! When using [`StackGraphLanguage::build_stack_graph_with_injections_into`][], the injected
+//! content is parsed with the injected language, and its rules are executed to add nodes to the
+//! same file.  Source spans of those nodes refer to the original file.  The injected rules can
+//! refer to the scope node that declared the injection using the `INJECTION_SCOPE_NODE` global
+//! variable, to correctly nest the injected scopes:
+//!
+//! ``` skip
+//! global INJECTION_SCOPE_NODE
+//!
+//! (program)@prog {
+//!   node @prog.lexical_scope
+//!   edge @prog.lexical_scope -> INJECTION_SCOPE_NODE
+//! }
+//! ```
+//!
 //! ### Working with paths
 //!
 //! Built-in path functions are available to compute symbols that depend on path information, such as
@@ -339,9 +421,11 @@ use controlled_option::ControlledOption;
 use lsp_positions::SpanCalculator;
 use once_cell::sync::Lazy;
 use stack_graphs::arena::Handle;
+use stack_graphs::graph::DebugInfo;
 use stack_graphs::graph::File;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::NodeID;
+use stack_graphs::graph::Precedence;
 use stack_graphs::graph::StackGraph;
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -373,8 +457,10 @@ use util::TreeSitterCancellationFlag;
 pub mod ci;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod coverage;
 pub mod functions;
 pub mod loader;
+pub mod profile;
 pub mod test;
 mod util;
 
@@ -395,12 +481,15 @@ static SCOPE_TYPE: &'static str = "scope";
 static DEBUG_ATTR_PREFIX: &'static str = "debug_";
 static DEFINIENS_NODE_ATTR: &'static str = "definiens_node";
 static EMPTY_SOURCE_SPAN_ATTR: &'static str = "empty_source_span";
+static INJECTION_CONTENT_ATTR: &'static str = "injection_content";
+static INJECTION_LANGUAGE_ATTR: &'static str = "injection_language";
 static IS_DEFINITION_ATTR: &'static str = "is_definition";
 static IS_ENDPOINT_ATTR: &'static str = "is_endpoint";
 static IS_EXPORTED_ATTR: &'static str = "is_exported";
 static IS_REFERENCE_ATTR: &'static str = "is_reference";
 static SCOPE_ATTR: &'static str = "scope";
 static SOURCE_NODE_ATTR: &'static str = "source_node";
+static SPECULATIVE_ATTR: &'static str = "speculative";
 static SYMBOL_ATTR: &'static str = "symbol";
 static SYNTAX_TYPE_ATTR: &'static str = "syntax_type";
 static TYPE_ATTR: &'static str = "type";
@@ -428,11 +517,20 @@ static PUSH_SCOPED_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> =
     Lazy::new(|| HashSet::from([TYPE_ATTR, SYMBOL_ATTR, SCOPE_ATTR, IS_REFERENCE_ATTR]));
 static PUSH_SYMBOL_ATTRS: Lazy<HashSet<&'static str>> =
     Lazy::new(|| HashSet::from([TYPE_ATTR, SYMBOL_ATTR, IS_REFERENCE_ATTR]));
-static SCOPE_ATTRS: Lazy<HashSet<&'static str>> =
-    Lazy::new(|| HashSet::from([TYPE_ATTR, IS_EXPORTED_ATTR, IS_ENDPOINT_ATTR]));
+static SCOPE_ATTRS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        TYPE_ATTR,
+        IS_EXPORTED_ATTR,
+        IS_ENDPOINT_ATTR,
+        INJECTION_CONTENT_ATTR,
+        INJECTION_LANGUAGE_ATTR,
+    ])
+});
 
 // Edge attribute names
 static PRECEDENCE_ATTR: &'static str = "precedence";
+static CONDITION_ATTR: &'static str = "condition";
+static CONFIDENCE_ATTR: &'static str = "confidence";
 
 // Global variables
 /// Name of the variable used to pass the root node.
@@ -445,6 +543,9 @@ pub const FILE_PATH_VAR: &'static str = "FILE_PATH";
 /// Name of the variable used to pass the root path.
 /// If given, should be an ancestor of the file path.
 pub const ROOT_PATH_VAR: &'static str = "ROOT_PATH";
+/// Name of the variable used to pass the scope node of an injection to the rules of the
+/// injected language.
+pub const INJECTION_SCOPE_NODE_VAR: &'static str = "INJECTION_SCOPE_NODE";
 
 /// Holds information about how to construct stack graphs for a particular language.
 pub struct StackGraphLanguage {
@@ -533,6 +634,17 @@ impl StackGraphLanguage {
     pub fn tsg_source(&self) -> &Cow<'static, str> {
         &self.tsg_source
     }
+
+    /// Returns the location of the given stanza, formatted as `PATH:LINE:COLUMN`, which is the
+    /// value of the `tsg_rule` debug entry of the nodes and edges it creates.
+    pub(crate) fn stanza_rule(&self, stanza: &tree_sitter_graph::ast::Stanza) -> String {
+        format!(
+            "{}:{}:{}",
+            self.tsg_path.display(),
+            stanza.range.start.row + 1,
+            stanza.range.start.column + 1
+        )
+    }
 }
 
 /// An error that can occur while loading in the TSG stack graph construction rules for a language
@@ -583,6 +695,155 @@ impl StackGraphLanguage {
     ) -> Builder<'a> {
         Builder::new(self, stack_graph, file, source)
     }
+
+    /// Executes the graph construction rules for this language against a source file, like
+    /// [`build_stack_graph_into`][Self::build_stack_graph_into], and builds any content injected
+    /// in other languages into the same file. The injected languages are looked up by name using
+    /// `injected_language`, and injections in unknown languages are ignored. Injected content is
+    /// built with the [`INJECTION_SCOPE_NODE_VAR`][] variable set to the scope node that declared
+    /// the injection, and may itself contain injections.
+    pub fn build_stack_graph_with_injections_into<'l>(
+        &'l self,
+        stack_graph: &mut StackGraph,
+        file: Handle<File>,
+        source: &str,
+        globals: &Variables,
+        injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), BuildError> {
+        self.build_with_injections_into(
+            stack_graph,
+            file,
+            source,
+            globals,
+            injected_language,
+            false,
+            cancellation_flag,
+        )
+    }
+
+    /// Executes the graph construction rules for this language against a source file, and builds
+    /// any injected content, like
+    /// [`build_stack_graph_with_injections_into`][Self::build_stack_graph_with_injections_into],
+    /// but in [error recovery mode][Builder::set_error_recovery].  Files with parse errors produce
+    /// a partial graph, and the regions that contained parse errors are recorded in the stack
+    /// graph, where they can be retrieved with [`StackGraph::parse_errors`][].
+    pub fn build_stack_graph_with_error_recovery_into<'l>(
+        &'l self,
+        stack_graph: &mut StackGraph,
+        file: Handle<File>,
+        source: &str,
+        globals: &Variables,
+        injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), BuildError> {
+        self.build_with_injections_into(
+            stack_graph,
+            file,
+            source,
+            globals,
+            injected_language,
+            true,
+            cancellation_flag,
+        )
+    }
+
+    fn build_with_injections_into<'l>(
+        &'l self,
+        stack_graph: &mut StackGraph,
+        file: Handle<File>,
+        source: &str,
+        globals: &Variables,
+        injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+        error_recovery: bool,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), BuildError> {
+        let mut builder = self.builder_into_stack_graph(stack_graph, file, source);
+        builder.set_error_recovery(error_recovery);
+        let injections = builder.build_with_injections(globals, cancellation_flag)?;
+        build_injections_into(
+            stack_graph,
+            file,
+            source,
+            globals,
+            injections,
+            injected_language,
+            error_recovery,
+            cancellation_flag,
+        )
+    }
+}
+
+/// Builds content injected in other languages into a file, for the injections that were returned
+/// by [`Builder::build_with_injections`][].  The injected languages are looked up by name using
+/// `injected_language`, and injections in unknown languages are ignored.  Injected content may
+/// itself contain injections, which are built as well.  This is only needed when using a builder
+/// directly; [`StackGraphLanguage::build_stack_graph_with_injections_into`][] builds injections
+/// itself.
+pub fn build_injections_into<'l>(
+    stack_graph: &mut StackGraph,
+    file: Handle<File>,
+    source: &str,
+    globals: &Variables,
+    mut injections: Vec<Injection>,
+    injected_language: &dyn Fn(&str) -> Option<&'l StackGraphLanguage>,
+    error_recovery: bool,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<(), BuildError> {
+    while let Some(injection) = injections.pop() {
+        let sgl = match injected_language(&injection.language) {
+            Some(sgl) => sgl,
+            None => continue,
+        };
+        injections.extend(build_injection_into(
+            sgl,
+            stack_graph,
+            file,
+            source,
+            globals,
+            injection,
+            error_recovery,
+            cancellation_flag,
+        )?);
+    }
+    Ok(())
+}
+
+/// Builds the content of a single injection into a file, using the given injected language, and
+/// returns the injections that the injected content declares itself.
+pub(crate) fn build_injection_into(
+    sgl: &StackGraphLanguage,
+    stack_graph: &mut StackGraph,
+    file: Handle<File>,
+    source: &str,
+    globals: &Variables,
+    injection: Injection,
+    error_recovery: bool,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<Injection>, BuildError> {
+    let mut builder = sgl.builder_into_stack_graph(stack_graph, file, source);
+    builder.set_included_ranges(injection.ranges);
+    builder.set_error_recovery(error_recovery);
+    let mut injection_globals = Variables::nested(globals);
+    injection_globals
+        .add(
+            INJECTION_SCOPE_NODE_VAR.into(),
+            builder.inject_node(injection.scope).into(),
+        )
+        .expect("Failed to set INJECTION_SCOPE_NODE");
+    builder.build_with_injections(&injection_globals, cancellation_flag)
+}
+
+/// Source content in another language that is embedded in a file. Injections are declared by
+/// setting the `injection_language` and `injection_content` attributes on a scope node.
+#[derive(Clone, Debug)]
+pub struct Injection {
+    /// The name of the injected language.
+    pub language: String,
+    /// The ranges of the source that contain the injected content.
+    pub ranges: Vec<tree_sitter::Range>,
+    /// The scope node that declared the injection, which the injected content is nested in.
+    pub scope: NodeID,
 }
 
 pub struct Builder<'a> {
@@ -594,6 +855,17 @@ pub struct Builder<'a> {
     remapped_nodes: HashMap<usize, NodeID>,
     injected_node_count: usize,
     span_calculator: SpanCalculator<'a>,
+    included_ranges: Vec<tree_sitter::Range>,
+    injections: Vec<Injection>,
+    error_recovery: bool,
+    chunk_sink: Option<(usize, &'a mut dyn GraphChunkSink)>,
+}
+
+/// Receives the chunks of a graph that is built in chunks.  See [`Builder::set_chunk_sink`][].
+pub trait GraphChunkSink {
+    /// Receives the next chunk, which contains some of the nodes of the file, and all edges that
+    /// start at those nodes.
+    fn add_chunk(&mut self, chunk: stack_graphs::serde::StackGraph) -> Result<(), BuildError>;
 }
 
 impl<'a> Builder<'a> {
@@ -613,18 +885,68 @@ impl<'a> Builder<'a> {
             remapped_nodes: HashMap::new(),
             injected_node_count: 0,
             span_calculator,
+            included_ranges: Vec::new(),
+            injections: Vec::new(),
+            error_recovery: false,
+            chunk_sink: None,
         }
     }
 
+    /// Restrict parsing to the given ranges of the source. This is used to build injected content,
+    /// which is parsed in the context of the whole source, so that source spans of the resulting
+    /// nodes are correct.
+    pub fn set_included_ranges(&mut self, ranges: Vec<tree_sitter::Range>) {
+        self.included_ranges = ranges;
+    }
+
+    /// Build a partial graph for source that contains parse errors, instead of failing with
+    /// [`BuildError::ParseErrors`][].  The graph construction rules are executed against the
+    /// parse tree with its `ERROR` and `MISSING` nodes, which rules typically do not match, so
+    /// the graph is missing nodes and edges for those regions.  The regions are recorded in the
+    /// stack graph with [`StackGraph::add_parse_error`][], so that they can be stored with the
+    /// graph, and results that involve the file can be flagged as possibly incomplete.
+    pub fn set_error_recovery(&mut self, error_recovery: bool) {
+        self.error_recovery = error_recovery;
+    }
+
+    /// Build the graph in chunks of at most `chunk_size` graph nodes, which are passed to `sink`
+    /// in serialized form, instead of adding them to the stack graph.  Only the stack graph nodes
+    /// of a single chunk are kept in memory, which bounds the memory needed for the stack graph of
+    /// very large files.  Parse errors are still recorded in the stack graph.
+    ///
+    /// The chunks of a file must all be loaded, nodes before edges, to get the graph of the file.
+    /// Any injections have to be built after the chunks are loaded, with
+    /// [`build_injections_into`][].
+    pub fn set_chunk_sink(&mut self, chunk_size: usize, sink: &'a mut dyn GraphChunkSink) {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        self.chunk_sink = Some((chunk_size, sink));
+    }
+
     /// Executes this builder.
     pub fn build(
-        mut self,
+        self,
         globals: &'a Variables<'a>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), BuildError> {
+        self.build_with_injections(globals, cancellation_flag)
+            .map(|_| ())
+    }
+
+    /// Executes this builder, and returns the injections that were declared by the graph
+    /// construction rules. It is the callers responsibility to build the injected content.
+    pub fn build_with_injections(
+        mut self,
+        globals: &'a Variables<'a>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<Injection>, BuildError> {
         let tree = {
             let mut parser = Parser::new();
             parser.set_language(&self.sgl.language)?;
+            if !self.included_ranges.is_empty() {
+                parser
+                    .set_included_ranges(&self.included_ranges)
+                    .map_err(|_| BuildError::InvalidIncludedRanges)?;
+            }
             let ts_cancellation_flag = TreeSitterCancellationFlag::from(cancellation_flag);
             // The parser.set_cancellation_flag` is unsafe, because it does not tie the
             // lifetime of the parser to the lifetime of the cancellation flag in any way.
@@ -635,11 +957,19 @@ impl<'a> Builder<'a> {
                 .parse(self.source, None)
                 .ok_or(BuildError::ParseError)?
         };
-        let parse_errors = ParseError::into_all(tree);
-        if parse_errors.errors().len() > 0 {
-            return Err(BuildError::ParseErrors(parse_errors));
-        }
-        let tree = parse_errors.into_tree();
+        let tree = if self.error_recovery {
+            for node in parse_error_nodes(&tree) {
+                let span = self.span_calculator.for_node(&node);
+                self.stack_graph.add_parse_error(self.file, span);
+            }
+            tree
+        } else {
+            let parse_errors = ParseError::into_all(tree);
+            if parse_errors.errors().len() > 0 {
+                return Err(BuildError::ParseErrors(parse_errors));
+            }
+            parse_errors.into_tree()
+        };
 
         let mut globals = Variables::nested(globals);
 
@@ -702,6 +1032,23 @@ impl<'a> Builder<'a> {
     }
 }
 
+/// Returns the `ERROR` and `MISSING` nodes of the tree, which are the regions of the source that
+/// could not be parsed, in source order.  Nested error nodes are not returned separately.
+fn parse_error_nodes(tree: &tree_sitter::Tree) -> Vec<tree_sitter::Node> {
+    let mut nodes = Vec::new();
+    let mut cursor = tree.walk();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.is_error() || node.is_missing() {
+            nodes.push(node);
+        } else if node.has_error() {
+            stack.extend(node.children(&mut cursor));
+        }
+    }
+    nodes.sort_by_key(|node| node.start_byte());
+    nodes
+}
+
 /// Trait to signal that the execution is cancelled
 pub trait CancellationFlag: Sync {
     fn check(&self, at: &'static str) -> Result<(), CancellationError>;
@@ -816,6 +1163,8 @@ pub enum BuildError {
     MissingSymbol(GraphNodeRef),
     #[error("Missing ‘scope’ attribute on graph node")]
     MissingScope(GraphNodeRef),
+    #[error("Missing ‘injection_language’ attribute on graph node")]
+    MissingInjectionLanguage(GraphNodeRef),
     #[error("Unknown ‘{0}’ flag type {1}")]
     UnknownFlagType(String, String),
     #[error("Unknown node type {0}")]
@@ -826,6 +1175,8 @@ pub enum BuildError {
     ExecutionError(tree_sitter_graph::ExecutionError),
     #[error("Error parsing source")]
     ParseError,
+    #[error("Included ranges are not ordered or overlap")]
+    InvalidIncludedRanges,
     #[error("Error parsing source")]
     ParseErrors(TreeWithParseErrorVec),
     #[error("Error converting shorthand ‘{0}’ on {1} with value {2}")]
@@ -834,6 +1185,8 @@ pub enum BuildError {
     LanguageError(#[from] tree_sitter::LanguageError),
     #[error("Expected exported symbol scope in {0}, got {1}")]
     SymbolScopeError(String, String),
+    #[error("Failed to store graph chunk: {0}")]
+    GraphChunkError(String),
 }
 
 impl From<stack_graphs::CancellationError> for BuildError {
@@ -901,7 +1254,10 @@ impl std::fmt::Display for DisplayBuildErrorPretty<'_> {
 }
 
 impl<'a> Builder<'a> {
-    fn load(mut self, cancellation_flag: &dyn CancellationFlag) -> Result<(), BuildError> {
+    fn load(
+        mut self,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<Injection>, BuildError> {
         let cancellation_flag: &dyn stack_graphs::CancellationFlag = &cancellation_flag;
 
         // By default graph ids are used for stack graph local_ids. A remapping is computed
@@ -925,21 +1281,20 @@ impl<'a> Builder<'a> {
                 .map(|_| panic!("index already remapped"));
         }
 
+        if let Some((chunk_size, sink)) = self.chunk_sink.take() {
+            // Build the chunks in a graph of their own, and leave the target graph untouched.
+            let target = std::mem::take(&mut *self.stack_graph);
+            let result = self.load_chunks(&target, chunk_size, sink, cancellation_flag);
+            *self.stack_graph = target;
+            result?;
+            return Ok(self.injections);
+        }
+
         // First create a stack graph node for each TSG node.  (The skip(...) is because the first
         // DSL nodes that we create are the proxies for the injected stack graph nodes.)
         for node_ref in self.graph.iter_nodes().skip(self.injected_node_count) {
             cancellation_flag.check("loading graph nodes")?;
-            let node_type = self.get_node_type(node_ref)?;
-            let handle = match node_type {
-                NodeType::DropScopes => self.load_drop_scopes(node_ref),
-                NodeType::PopScopedSymbol => self.load_pop_scoped_symbol(node_ref)?,
-                NodeType::PopSymbol => self.load_pop_symbol(node_ref)?,
-                NodeType::PushScopedSymbol => self.load_push_scoped_symbol(node_ref)?,
-                NodeType::PushSymbol => self.load_push_symbol(node_ref)?,
-                NodeType::Scope => self.load_scope(node_ref)?,
-            };
-            self.load_source_info(node_ref, handle)?;
-            self.load_node_debug_info(node_ref, handle)?;
+            self.load_node(node_ref)?;
         }
 
         for node in self.stack_graph.nodes_for_file(self.file) {
@@ -951,28 +1306,201 @@ impl<'a> Builder<'a> {
         // (Technically the caller could add outgoing nodes from “jump to scope” as well, but those
         // are invalid according to the stack graph semantics and will never be followed.
         for source_ref in self.graph.iter_nodes() {
-            let source = &self.graph[source_ref];
-            let source_node_id = self.node_id_for_graph_node(source_ref);
-            let source_handle = self.stack_graph.node_for_id(source_node_id).unwrap();
-            for (sink_ref, edge) in source.iter_edges() {
-                cancellation_flag.check("loading graph edges")?;
-                let precedence = match edge.attributes.get(PRECEDENCE_ATTR) {
-                    Some(precedence) => precedence.as_integer()? as i32,
-                    None => 0,
-                };
-                let sink_node_id = self.node_id_for_graph_node(sink_ref);
-                let sink_handle = self.stack_graph.node_for_id(sink_node_id).unwrap();
+            self.load_edges(source_ref, cancellation_flag)?;
+        }
+
+        Ok(self.injections)
+    }
+
+    /// Loads the graph in chunks of at most `chunk_size` graph nodes, and passes them to the sink.
+    /// Every chunk is built in an empty stack graph that has the same files as the target graph.
+    /// Nodes of other chunks that are referred to by the edges or scoped symbols of a chunk are
+    /// added to its graph as placeholder scope nodes, which are not part of the chunk.
+    fn load_chunks(
+        &mut self,
+        target: &StackGraph,
+        chunk_size: usize,
+        sink: &mut dyn GraphChunkSink,
+        cancellation_flag: &dyn stack_graphs::CancellationFlag,
+    ) -> Result<(), BuildError> {
+        let file_name = target[self.file].name().to_string();
+        // files are added in handle order, so that their handles are the same in both graphs
+        let mut files = target.iter_files().collect::<Vec<_>>();
+        files.sort_by_key(|file| file.as_u32());
+        let node_refs = self.graph.iter_nodes().collect::<Vec<_>>();
+        // The proxies for the injected stack graph nodes are part of the chunks, so that their
+        // edges are loaded, but no nodes are created for them.
+        for chunk in node_refs.chunks(chunk_size) {
+            *self.stack_graph = StackGraph::new();
+            for file in &files {
                 self.stack_graph
-                    .add_edge(source_handle, sink_handle, precedence);
-                Self::load_edge_debug_info(
-                    &mut self.stack_graph,
-                    source_handle,
-                    sink_handle,
-                    edge,
-                )?;
+                    .add_file_of(target, *file)
+                    .expect("file not present in empty graph");
+            }
+            if let Some(language) = target.file_language(self.file) {
+                self.stack_graph.set_file_language(self.file, language);
+            }
+
+            let mut chunk_nodes = Vec::new();
+            for node_ref in chunk {
+                if node_ref.index() < self.injected_node_count {
+                    continue;
+                }
+                cancellation_flag.check("loading graph nodes")?;
+                chunk_nodes.push(self.load_node(*node_ref)?);
+            }
+            for node in &chunk_nodes {
+                if let Node::PushScopedSymbol(node) = &self.stack_graph[*node] {
+                    let scope = node.scope;
+                    self.load_placeholder(scope, target, &node_refs)?;
+                }
+                self.verify_node(*node)?;
+            }
+            for source_ref in chunk {
+                let mut ids = vec![self.node_id_for_graph_node(*source_ref)];
+                ids.extend(
+                    self.graph[*source_ref]
+                        .iter_edges()
+                        .map(|(sink_ref, _)| self.node_id_for_graph_node(sink_ref)),
+                );
+                for id in ids {
+                    self.load_placeholder(id, target, &node_refs)?;
+                }
+                self.load_edges(*source_ref, cancellation_flag)?;
+            }
+
+            // Serialize the nodes of the chunk, and all edges, which may refer to nodes of other
+            // files that are not serialized.
+            let chunk_ids = chunk_nodes
+                .iter()
+                .map(|node| self.stack_graph[*node].id().local_id())
+                .collect::<HashSet<_>>();
+            let mut graph = stack_graphs::serde::StackGraph::from_graph(self.stack_graph);
+            graph.files.data.retain(|file| *file == file_name);
+            graph
+                .file_languages
+                .data
+                .retain(|language| language.file == file_name);
+            graph.nodes.data.retain(|node| {
+                let id = node.id();
+                id.file.as_deref() == Some(file_name.as_str()) && chunk_ids.contains(&id.local_id)
+            });
+            sink.add_chunk(graph)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a placeholder for a node that is not part of the chunk that is being built to the
+    /// chunk graph, if it does not exist yet.  Placeholders are scope nodes, which are exported if
+    /// the node they stand for is an exported scope, so that scoped symbols can be verified.
+    fn load_placeholder(
+        &mut self,
+        id: NodeID,
+        target: &StackGraph,
+        node_refs: &[GraphNodeRef],
+    ) -> Result<(), BuildError> {
+        if self.stack_graph.node_for_id(id).is_some() {
+            return Ok(());
+        }
+        let is_exported = if let Some(node) = target.node_for_id(id) {
+            target[node].is_exported_scope()
+        } else if let Some(node_ref) = self.graph_node_for_node_id(id, node_refs) {
+            let node = &self.graph[node_ref];
+            matches!(self.get_node_type(node_ref)?, NodeType::Scope)
+                && (self.load_flag(node, IS_EXPORTED_ATTR)?
+                    || self.load_flag(node, IS_ENDPOINT_ATTR)?)
+        } else {
+            false
+        };
+        self.stack_graph
+            .add_scope_node(id, is_exported)
+            .expect("placeholder node to be new");
+        Ok(())
+    }
+
+    /// Returns the graph node that is loaded as the given stack graph node of the file, which is
+    /// the inverse of [`Self::node_id_for_graph_node`].
+    fn graph_node_for_node_id(
+        &self,
+        id: NodeID,
+        node_refs: &[GraphNodeRef],
+    ) -> Option<GraphNodeRef> {
+        if !id.is_in_file(self.file) {
+            return None;
+        }
+        let index = match self
+            .remapped_nodes
+            .iter()
+            .find(|(_, remapped)| **remapped == id)
+        {
+            Some((index, _)) => *index,
+            None => {
+                let index = id.local_id() as usize + self.injected_node_count;
+                if self.remapped_nodes.contains_key(&index) {
+                    return None;
+                }
+                index
             }
+        };
+        node_refs.get(index).copied()
+    }
+
+    /// Creates the stack graph node for a TSG node.
+    fn load_node(&mut self, node_ref: GraphNodeRef) -> Result<Handle<Node>, BuildError> {
+        let node_type = self.get_node_type(node_ref)?;
+        let handle = match node_type {
+            NodeType::DropScopes => self.load_drop_scopes(node_ref),
+            NodeType::PopScopedSymbol => self.load_pop_scoped_symbol(node_ref)?,
+            NodeType::PopSymbol => self.load_pop_symbol(node_ref)?,
+            NodeType::PushScopedSymbol => self.load_push_scoped_symbol(node_ref)?,
+            NodeType::PushSymbol => self.load_push_symbol(node_ref)?,
+            NodeType::Scope => self.load_scope(node_ref)?,
+        };
+        self.load_source_info(node_ref, handle)?;
+        self.load_node_debug_info(node_ref, handle)?;
+        if self.load_flag(&self.graph[node_ref], SPECULATIVE_ATTR)? {
+            self.stack_graph.mark_speculative(handle);
         }
+        Ok(handle)
+    }
 
+    /// Creates the stack graph edges for the outgoing edges of a TSG node.
+    fn load_edges(
+        &mut self,
+        source_ref: GraphNodeRef,
+        cancellation_flag: &dyn stack_graphs::CancellationFlag,
+    ) -> Result<(), BuildError> {
+        let source = &self.graph[source_ref];
+        let source_node_id = self.node_id_for_graph_node(source_ref);
+        let source_handle = self.stack_graph.node_for_id(source_node_id).unwrap();
+        for (sink_ref, edge) in source.iter_edges() {
+            cancellation_flag.check("loading graph edges")?;
+            let precedence = match edge.attributes.get(PRECEDENCE_ATTR) {
+                Some(precedence) => Precedence::new(precedence.as_integer()? as i32),
+                None => Precedence::DEFAULT,
+            };
+            let sink_node_id = self.node_id_for_graph_node(sink_ref);
+            let sink_handle = self.stack_graph.node_for_id(sink_node_id).unwrap();
+            self.stack_graph
+                .add_edge(source_handle, sink_handle, precedence);
+            if let Some(condition) = edge.attributes.get(CONDITION_ATTR) {
+                let condition = self.stack_graph.add_string(condition.as_str()?);
+                self.stack_graph
+                    .set_edge_condition(source_handle, sink_handle, condition);
+            }
+            if let Some(confidence) = edge.attributes.get(CONFIDENCE_ATTR) {
+                let confidence = confidence.as_integer()?.min(100) as u8;
+                self.stack_graph
+                    .set_edge_confidence(source_handle, sink_handle, confidence);
+            }
+            Self::load_edge_debug_info(
+                &mut self.stack_graph,
+                self.sgl,
+                source_handle,
+                sink_handle,
+                edge,
+            )?;
+        }
         Ok(())
     }
 
@@ -1132,7 +1660,36 @@ impl<'a> Builder<'a> {
         let is_exported =
             self.load_flag(node, IS_EXPORTED_ATTR)? || self.load_flag(node, IS_ENDPOINT_ATTR)?;
         self.verify_attributes(node, SCOPE_TYPE, &SCOPE_ATTRS);
-        Ok(self.stack_graph.add_scope_node(id, is_exported).unwrap())
+        let node_handle = self.stack_graph.add_scope_node(id, is_exported).unwrap();
+        self.load_injection(node_ref, id)?;
+        Ok(node_handle)
+    }
+
+    fn load_injection(&mut self, node_ref: GraphNodeRef, scope: NodeID) -> Result<(), BuildError> {
+        let node = &self.graph[node_ref];
+        let ranges = match node.attributes.get(INJECTION_CONTENT_ATTR) {
+            Some(Value::Null) | None => return Ok(()),
+            Some(Value::List(contents)) => contents
+                .iter()
+                .map(|content| -> Result<tree_sitter::Range, BuildError> {
+                    Ok(self.graph[content.as_syntax_node_ref()?].range())
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(content) => vec![self.graph[content.as_syntax_node_ref()?].range()],
+        };
+        if ranges.is_empty() {
+            return Ok(());
+        }
+        let language = match node.attributes.get(INJECTION_LANGUAGE_ATTR) {
+            Some(language) => language.as_str()?.to_string(),
+            None => return Err(BuildError::MissingInjectionLanguage(node_ref)),
+        };
+        self.injections.push(Injection {
+            language,
+            ranges,
+            scope,
+        });
+        Ok(())
     }
 
     fn load_symbol(&self, value: &Value) -> Result<String, BuildError> {
@@ -1208,6 +1765,7 @@ impl<'a> Builder<'a> {
         node_handle: Handle<Node>,
     ) -> Result<(), BuildError> {
         let node = &self.graph[node_ref];
+        let mut rule = None;
         for (name, value) in node.attributes.iter() {
             let name = name.to_string();
             if name.starts_with(DEBUG_ATTR_PREFIX) {
@@ -1215,24 +1773,35 @@ impl<'a> Builder<'a> {
                     Value::String(value) => value.clone(),
                     value => value.to_string(),
                 };
-                let key = self
-                    .stack_graph
-                    .add_string(&name[DEBUG_ATTR_PREFIX.len()..]);
+                let name = &name[DEBUG_ATTR_PREFIX.len()..];
+                if name == DebugInfo::TSG_LOCATION {
+                    rule = Self::rule_for_location(self.sgl, &value);
+                }
+                let key = self.stack_graph.add_string(name);
                 let value = self.stack_graph.add_string(&value);
                 self.stack_graph
                     .node_debug_info_mut(node_handle)
                     .add(key, value);
             }
         }
+        if let Some(rule) = rule {
+            let key = self.stack_graph.add_string(DebugInfo::TSG_RULE);
+            let value = self.stack_graph.add_string(&rule);
+            self.stack_graph
+                .node_debug_info_mut(node_handle)
+                .add(key, value);
+        }
         Ok(())
     }
 
     fn load_edge_debug_info(
         stack_graph: &mut StackGraph,
+        sgl: &StackGraphLanguage,
         source_handle: Handle<Node>,
         sink_handle: Handle<Node>,
         edge: &Edge,
     ) -> Result<(), BuildError> {
+        let mut rule = None;
         for (name, value) in edge.attributes.iter() {
             let name = name.to_string();
             if name.starts_with(DEBUG_ATTR_PREFIX) {
@@ -1240,16 +1809,45 @@ impl<'a> Builder<'a> {
                     Value::String(value) => value.clone(),
                     value => value.to_string(),
                 };
-                let key = stack_graph.add_string(&name[DEBUG_ATTR_PREFIX.len()..]);
+                let name = &name[DEBUG_ATTR_PREFIX.len()..];
+                if name == DebugInfo::TSG_LOCATION {
+                    rule = Self::rule_for_location(sgl, &value);
+                }
+                let key = stack_graph.add_string(name);
                 let value = stack_graph.add_string(&value);
                 stack_graph
                     .edge_debug_info_mut(source_handle, sink_handle)
                     .add(key, value);
             }
         }
+        if let Some(rule) = rule {
+            let key = stack_graph.add_string(DebugInfo::TSG_RULE);
+            let value = stack_graph.add_string(&rule);
+            stack_graph
+                .edge_debug_info_mut(source_handle, sink_handle)
+                .add(key, value);
+        }
         Ok(())
     }
 
+    /// Returns the location of the stanza containing the statement at the given location, which
+    /// is formatted as `line LINE column COLUMN`.  The result is formatted as `PATH:LINE:COLUMN`.
+    fn rule_for_location(sgl: &StackGraphLanguage, location: &str) -> Option<String> {
+        let line = location
+            .split_whitespace()
+            .skip_while(|word| *word != "line")
+            .nth(1)?
+            .parse::<usize>()
+            .ok()?;
+        let row = line.checked_sub(1)?;
+        let stanza = sgl
+            .tsg
+            .stanzas
+            .iter()
+            .find(|s| s.range.start.row <= row && row <= s.range.end.row)?;
+        Some(sgl.stanza_rule(stanza))
+    }
+
     fn verify_attributes(
         &self,
         node: &GraphNode,
@@ -1261,6 +1859,7 @@ impl<'a> Builder<'a> {
             if !allowed_attributes.contains(id)
                 && id != SOURCE_NODE_ATTR
                 && id != EMPTY_SOURCE_SPAN_ATTR
+                && id != SPECULATIVE_ATTR
                 && !id.starts_with(DEBUG_ATTR_PREFIX)
             {
                 eprintln!("Unexpected attribute {} on node of type {}", id, node_type);
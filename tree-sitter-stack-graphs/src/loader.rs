@@ -11,6 +11,8 @@ use ini::Ini;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
 use stack_graphs::graph::StackGraph;
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -26,11 +28,16 @@ use tree_sitter_loader::Config as TsConfig;
 use tree_sitter_loader::LanguageConfiguration as TSLanguageConfiguration;
 use tree_sitter_loader::Loader as TsLoader;
 
+use crate::build_injection_into;
+use crate::BuildError;
 use crate::CancellationFlag;
 use crate::FileAnalyzer;
+use crate::Injection;
 use crate::StackGraphLanguage;
 use crate::FILE_PATH_VAR;
 
+pub mod remote;
+
 const BUILTINS_FILENAME: &str = "<builtins>";
 
 pub static DEFAULT_TSG_PATHS: Lazy<Vec<LoadPath>> =
@@ -142,6 +149,22 @@ impl LanguageConfiguration {
     ) -> std::io::Result<bool> {
         matches_file(&self.file_types, &self.content_regex, path, content).map(|l| l.is_some())
     }
+
+    /// Determines if this language is the injected language with the given name.
+    pub fn matches_injection(&self, name: &str) -> bool {
+        matches_injection(&self.scope, &self.file_types, name)
+    }
+
+    /// Returns the name of this language, which is the last component of its scope, such as
+    /// `python` for `source.python`, or its first file type if it has no scope.  Files are
+    /// [tagged][stack_graphs::graph::StackGraph::set_file_language] with this name when they are
+    /// indexed.
+    pub fn name(&self) -> Option<&str> {
+        match &self.scope {
+            Some(scope) => scope.rsplit('.').next(),
+            None => self.file_types.first().map(String::as_str),
+        }
+    }
 }
 
 #[derive(Clone, Default)]
@@ -294,6 +317,83 @@ impl Loader {
         }
     }
 
+    /// Load a stack graph language for injected content in the language with the given name. The name is
+    /// matched against the last component of the language scopes, and the file types of the languages.
+    /// Languages that have been loaded are available for injection in files loaded afterwards.
+    pub fn load_for_injection<'a>(
+        &'a mut self,
+        name: &str,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Option<&'a LanguageConfiguration>, LoadError<'static>> {
+        match &mut self.0 {
+            LoaderImpl::Paths(loader) => loader.load_for_injection(name, cancellation_flag),
+            LoaderImpl::Provided(loader) => Ok(loader.load_for_injection(name)),
+        }
+    }
+
+    /// Builds content injected in other languages into a file, for the injections that were returned
+    /// by [`Builder::build_with_injections`][crate::Builder::build_with_injections], like
+    /// [`build_injections_into`][crate::build_injections_into].  The injected languages are loaded
+    /// with [`Self::load_for_injection`][] when an injection names them, so they do not have to be
+    /// loaded before.  Injections in languages that cannot be found are ignored.
+    pub fn build_injections_into(
+        &mut self,
+        stack_graph: &mut StackGraph,
+        file: Handle<File>,
+        source: &str,
+        globals: &Variables,
+        mut injections: Vec<Injection>,
+        error_recovery: bool,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), InjectionError> {
+        while let Some(injection) = injections.pop() {
+            let lc = match self.load_for_injection(&injection.language, cancellation_flag)? {
+                Some(lc) => lc,
+                None => continue,
+            };
+            injections.extend(build_injection_into(
+                &lc.sgl,
+                stack_graph,
+                file,
+                source,
+                globals,
+                injection,
+                error_recovery,
+                cancellation_flag,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Look up the language configurations for the given file languages. The languages must have been returned
+    /// from an earlier call to [`Self::load_for_file`], otherwise they may not be found. Unlike [`Self::load_for_file`],
+    /// this does not require mutable access to the loader, which allows configurations to be shared between threads.
+    pub fn configurations_for_languages<'a>(
+        &'a self,
+        languages: &FileLanguages,
+    ) -> FileLanguageConfigurations<'a> {
+        let find = |language: &Language| match &self.0 {
+            LoaderImpl::Paths(loader) => loader
+                .cache
+                .iter()
+                .find(|(l, _)| l == language)
+                .map(|(_, lc)| lc),
+            LoaderImpl::Provided(loader) => loader
+                .configurations
+                .iter()
+                .find(|lc| &lc.language == language),
+        };
+        FileLanguageConfigurations {
+            primary: languages.primary.as_ref().and_then(|l| find(l)),
+            secondary: languages
+                .secondary
+                .iter()
+                .filter_map(|(l, fa)| find(l).map(|lc| (lc, fa.clone())))
+                .collect(),
+            injected: languages.injected.iter().filter_map(|l| find(l)).collect(),
+        }
+    }
+
     pub fn load_globals_from_config_path(
         path: &Path,
         globals: &mut Variables,
@@ -381,6 +481,10 @@ pub struct FileLanguageConfigurations<'a> {
         &'a LanguageConfiguration,
         Arc<dyn FileAnalyzer + Send + Sync>,
     )>,
+    /// Languages that can be injected into the file's content, see [`Injection`][crate::Injection].
+    /// These are only the languages that were loaded when the file was loaded.  Use
+    /// [`Loader::build_injections_into`][] to load injected languages when they are needed.
+    pub injected: Vec<&'a LanguageConfiguration>,
 }
 
 impl FileLanguageConfigurations<'_> {
@@ -388,6 +492,28 @@ impl FileLanguageConfigurations<'_> {
         self.primary.is_some() || !self.secondary.is_empty()
     }
 
+    /// Returns the languages of the configurations, which can be used to look up the configurations again
+    /// using [`Loader::configurations_for_languages`].
+    pub fn languages(&self) -> FileLanguages {
+        FileLanguages {
+            primary: self.primary.map(|lc| lc.language.clone()),
+            secondary: self
+                .secondary
+                .iter()
+                .map(|(lc, fa)| (lc.language.clone(), fa.clone()))
+                .collect(),
+            injected: self.injected.iter().map(|lc| lc.language.clone()).collect(),
+        }
+    }
+
+    /// Returns the stack graph language for injected content in the language with the given name.
+    pub fn injected_language(&self, name: &str) -> Option<&StackGraphLanguage> {
+        self.injected
+            .iter()
+            .find(|lc| lc.matches_injection(name))
+            .map(|lc| &lc.sgl)
+    }
+
     pub fn no_similar_paths_in_file(&self) -> bool {
         let mut no_similar_paths_in_file = true;
         if let Some(lc) = &self.primary {
@@ -400,6 +526,17 @@ impl FileLanguageConfigurations<'_> {
     }
 }
 
+/// Struct holding the languages for a file, without borrowing the loader.
+#[derive(Clone, Default)]
+pub struct FileLanguages {
+    /// The file's primary language.
+    pub primary: Option<Language>,
+    /// Any secondary languages, with their special file analyzers for the file.
+    pub secondary: Vec<(Language, Arc<dyn FileAnalyzer + Send + Sync>)>,
+    /// Languages that can be injected into the file's content.
+    pub injected: Vec<Language>,
+}
+
 #[derive(Debug, Error)]
 pub enum LoadError<'a> {
     #[error("{0}")]
@@ -438,6 +575,24 @@ pub enum LoadError<'a> {
     },
     #[error(transparent)]
     TreeSitter(anyhow::Error),
+    #[error("Failed to fetch grammar from {url}: {message}")]
+    GrammarFetch { url: String, message: String },
+    #[error("Checksum mismatch for grammar from {url}: expected {expected}, found {actual}")]
+    GrammarChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// An error that occurred while building injected content with
+/// [`Loader::build_injections_into`][].
+#[derive(Debug, Error)]
+pub enum InjectionError {
+    #[error(transparent)]
+    Load(#[from] LoadError<'static>),
+    #[error(transparent)]
+    Build(#[from] BuildError),
 }
 
 impl LoadError<'_> {
@@ -520,7 +675,18 @@ impl LanguageConfigurationsLoader {
                 secondary.push((language, fa));
             }
         }
-        Ok(FileLanguageConfigurations { primary, secondary })
+        Ok(FileLanguageConfigurations {
+            primary,
+            secondary,
+            injected: self.configurations.iter().collect(),
+        })
+    }
+
+    /// Load a stack graph language for injected content in the language with the given name.
+    pub fn load_for_injection(&self, name: &str) -> Option<&LanguageConfiguration> {
+        self.configurations
+            .iter()
+            .find(|lc| lc.matches_injection(name))
     }
 }
 
@@ -584,6 +750,43 @@ impl PathLoader {
             Some(selected_language) => selected_language.clone(),
             None => return Ok(FileLanguageConfigurations::default()),
         };
+        let index = self.load_language(language, cancellation_flag)?;
+        let lc = &self.cache[index].1;
+        Ok(FileLanguageConfigurations {
+            primary: Some(lc),
+            secondary: Vec::default(),
+            injected: self.cache.iter().map(|(_, lc)| lc).collect(),
+        })
+    }
+
+    pub fn load_for_injection<'a>(
+        &'a mut self,
+        name: &str,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Option<&'a LanguageConfiguration>, LoadError<'static>> {
+        let mut found_language = None;
+        for path in &self.paths {
+            let languages = self.loader.languages_at_path(path, self.scope.as_deref())?;
+            if let Some(language) = languages.into_iter().find(|l| l.matches_injection(name)) {
+                found_language = Some(language.clone());
+                break;
+            }
+        }
+        let language = match found_language {
+            Some(language) => language,
+            None => return Ok(None),
+        };
+        let index = self.load_language(language, cancellation_flag)?;
+        Ok(Some(&self.cache[index].1))
+    }
+
+    // Load the stack graph language for the given language, if it is not cached yet, and return its
+    // index in the cache
+    fn load_language(
+        &mut self,
+        language: SupplementedLanguage,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<usize, LoadError<'static>> {
         // the borrow checker is a hard master...
         let index = self.cache.iter().position(|e| &e.0 == &language.language);
         let index = match index {
@@ -616,11 +819,7 @@ impl PathLoader {
                 self.cache.len() - 1
             }
         };
-        let lc = &self.cache[index].1;
-        Ok(FileLanguageConfigurations {
-            primary: Some(lc),
-            secondary: Vec::default(),
-        })
+        Ok(index)
     }
 
     // Select language for the given file, considering paths and scope fields
@@ -777,7 +976,7 @@ impl SupplementedTsLoader {
         &mut self,
         path: &Path,
         scope: Option<&str>,
-    ) -> Result<Vec<&SupplementedLanguage>, LoadError> {
+    ) -> Result<Vec<&SupplementedLanguage>, LoadError<'static>> {
         if !self.1.contains_key(path) {
             let languages = self
                 .0
@@ -823,6 +1022,10 @@ impl SupplementedLanguage {
         matches_file(&self.file_types, &self.content_regex, path, content)
     }
 
+    pub fn matches_injection(&self, name: &str) -> bool {
+        matches_injection(&self.scope, &self.file_types, name)
+    }
+
     // Extracted from tree_sitter_loader::Loader::language_configuration_for_file_name
     pub fn best_for_file<'a>(
         languages: Vec<&'a SupplementedLanguage>,
@@ -887,27 +1090,136 @@ pub fn matches_file(
     Ok(Some(0isize))
 }
 
+/// Determines if a language with the given scope and file types is the injected language with the
+/// given name. Injected languages are matched by the last component of their scope, e.g., `python`
+/// for `source.python`, or by one of their file types, e.g., `py`.
+pub fn matches_injection(scope: &Option<String>, file_types: &Vec<String>, name: &str) -> bool {
+    scope.as_ref().map_or(false, |scope| {
+        scope
+            .rsplit('.')
+            .next()
+            .map_or(false, |s| s.eq_ignore_ascii_case(name))
+    }) || file_types.iter().any(|ft| ft.eq_ignore_ascii_case(name))
+}
+
 pub trait ContentProvider {
     fn get(&mut self, path: &Path) -> std::io::Result<Option<&str>>;
 }
 
 /// FileReader reads files from the filesystem and caches the most recently read file.
+///
+/// Files are decoded as UTF-8, or as UTF-16 if they start with a byte order mark or look like
+/// UTF-16 encoded text. Invalid sequences are replaced by the Unicode replacement character.
+/// A UTF-8 byte order mark is kept, so that byte offsets in the content match the file on disk.
+/// Tree-sitter skips it when parsing.
+/// Files that are too large or contain binary content are not read, and result in an error with
+/// a [`SkipReason`][], which can be retrieved using [`SkipReason::from_io_error`][].
 pub struct FileReader {
     cache: Option<(PathBuf, String)>,
+    max_size: Option<u64>,
 }
 
 impl FileReader {
     pub fn new() -> Self {
-        Self { cache: None }
+        Self {
+            cache: None,
+            max_size: None,
+        }
+    }
+
+    /// Set the maximum size in bytes of files that are read, or `None` for no limit.
+    pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
     }
 
     pub fn get(&mut self, path: &Path) -> std::io::Result<&str> {
         if self.cache.as_ref().map_or(true, |(p, _)| p != path) {
-            let content = std::fs::read_to_string(path)?;
+            if let Some(max_size) = self.max_size {
+                let size = std::fs::metadata(path)?.len();
+                if size > max_size {
+                    return Err(SkipReason::TooLarge { size, max_size }.into());
+                }
+            }
+            let content = Self::decode(&std::fs::read(path)?)?;
             self.cache = Some((path.to_path_buf(), content));
         }
         Ok(&self.cache.as_ref().unwrap().1)
     }
+
+    /// Decode file content, detecting UTF-16 encoded text and binary content.
+    pub fn decode(bytes: &[u8]) -> Result<String, SkipReason> {
+        if let Some(bytes) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+            return Ok(decode_utf16(bytes, u16::from_le_bytes));
+        }
+        if let Some(bytes) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+            return Ok(decode_utf16(bytes, u16::from_be_bytes));
+        }
+
+        let prefix = &bytes[..bytes.len().min(BINARY_DETECTION_PREFIX_LEN)];
+        if prefix.contains(&0) {
+            // Text without byte order mark that is mostly ASCII, encoded as UTF-16, has a zero
+            // byte in every code unit, either in the even or in the odd positions.
+            let units = prefix.len() / 2;
+            let zeros_at = |offset: usize| {
+                prefix
+                    .iter()
+                    .skip(offset)
+                    .step_by(2)
+                    .filter(|b| **b == 0)
+                    .count()
+            };
+            let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+            if bytes.len() % 2 == 0 && even_zeros == 0 && odd_zeros * 10 >= units * 9 {
+                return Ok(decode_utf16(bytes, u16::from_le_bytes));
+            }
+            if bytes.len() % 2 == 0 && odd_zeros == 0 && even_zeros * 10 >= units * 9 {
+                return Ok(decode_utf16(bytes, u16::from_be_bytes));
+            }
+            return Err(SkipReason::Binary);
+        }
+
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(content) => Ok(content),
+            Err(err) => Ok(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        }
+    }
+}
+
+/// Number of bytes at the start of a file that are inspected to detect binary content.
+const BINARY_DETECTION_PREFIX_LEN: usize = 8000;
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks(2)
+        .map(|c| from_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// The reason a file was not read by a [`FileReader`][]. The reason is returned as the inner
+/// error of an [`std::io::Error`][], so that it can pass through the [`ContentProvider`][]
+/// interface, and its message is suitable to be stored as the error of a file in the database.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SkipReason {
+    #[error("file too large ({size} bytes, maximum is {max_size} bytes)")]
+    TooLarge { size: u64, max_size: u64 },
+    #[error("binary file")]
+    Binary,
+}
+
+impl SkipReason {
+    /// Returns the skip reason if the given error was caused by a file that was skipped.
+    pub fn from_io_error(err: &std::io::Error) -> Option<&SkipReason> {
+        err.get_ref()
+            .and_then(|err| err.downcast_ref::<SkipReason>())
+    }
+}
+
+impl From<SkipReason> for std::io::Error {
+    fn from(value: SkipReason) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, value)
+    }
 }
 
 impl ContentProvider for FileReader {
@@ -0,0 +1,227 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Defines fetching of grammars from remote git repositories.
+//!
+//! Grammars are fetched into a local [`GrammarCache`], and the resulting directories can be passed
+//! to [`Loader::from_paths`][super::Loader::from_paths] like any other grammar path. A grammar
+//! can be pinned to a revision, and its content can be verified against an expected git tree hash.
+//! Fetching requires the `git` executable to be available.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::loader::LoadError;
+
+/// A grammar that is fetched from a git repository.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteGrammar {
+    /// The URL of the git repository.
+    pub url: String,
+    /// The revision to fetch, such as a tag, branch, or commit hash. If omitted, the default branch
+    /// is fetched.
+    pub revision: Option<String>,
+    /// The expected git tree hash of the fetched revision. If given, the grammar is rejected if its
+    /// content does not match.
+    pub checksum: Option<String>,
+    /// The directory of the grammar inside the repository, for repositories containing several
+    /// grammars.
+    pub subdirectory: Option<PathBuf>,
+}
+
+impl RemoteGrammar {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            revision: None,
+            checksum: None,
+            subdirectory: None,
+        }
+    }
+
+    pub fn with_revision(mut self, revision: impl Into<String>) -> Self {
+        self.revision = Some(revision.into());
+        self
+    }
+
+    pub fn with_checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.checksum = Some(checksum.into());
+        self
+    }
+
+    pub fn with_subdirectory(mut self, subdirectory: impl Into<PathBuf>) -> Self {
+        self.subdirectory = Some(subdirectory.into());
+        self
+    }
+}
+
+/// Parses grammars of the form `URL[#[REVISION][:CHECKSUM]]`. Git does not allow colons in
+/// revision names, which makes the format unambiguous.
+impl FromStr for RemoteGrammar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (url, fragment) = match s.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment)),
+            None => (s, None),
+        };
+        if url.is_empty() {
+            return Err(format!("missing grammar URL in {}", s));
+        }
+        let mut grammar = RemoteGrammar::new(url);
+        if let Some(fragment) = fragment {
+            let (revision, checksum) = match fragment.split_once(':') {
+                Some((revision, checksum)) => (revision, Some(checksum)),
+                None => (fragment, None),
+            };
+            if !revision.is_empty() {
+                grammar.revision = Some(revision.to_string());
+            }
+            if let Some(checksum) = checksum {
+                if checksum.is_empty() {
+                    return Err(format!("empty grammar checksum in {}", s));
+                }
+                grammar.checksum = Some(checksum.to_string());
+            }
+        }
+        Ok(grammar)
+    }
+}
+
+/// A local cache of grammars fetched from git repositories.
+#[derive(Clone, Debug)]
+pub struct GrammarCache {
+    root: PathBuf,
+}
+
+impl GrammarCache {
+    /// Creates a cache that stores grammars in the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the directory where the given grammar is cached. Every combination of URL and
+    /// revision is cached separately.
+    pub fn path_for(&self, grammar: &RemoteGrammar) -> PathBuf {
+        let mut name = sanitize(
+            grammar
+                .url
+                .split_once("://")
+                .map_or(grammar.url.as_str(), |(_, url)| url)
+                .trim_end_matches(".git"),
+        );
+        name.push('@');
+        name.push_str(&sanitize(grammar.revision.as_deref().unwrap_or("HEAD")));
+        self.root.join(name)
+    }
+
+    /// Returns the local directory of the given grammar, fetching it if it is not in the cache yet.
+    /// If the grammar has a checksum, the cached content is verified against it.
+    pub fn fetch(&self, grammar: &RemoteGrammar) -> Result<PathBuf, LoadError<'static>> {
+        let path = self.path_for(grammar);
+        if !path.join(".git").exists() {
+            self.fetch_into(grammar, &path)?;
+        }
+        if let Some(checksum) = &grammar.checksum {
+            Self::verify(grammar, &path, checksum)?;
+        }
+        Ok(match &grammar.subdirectory {
+            Some(subdirectory) => path.join(subdirectory),
+            None => path,
+        })
+    }
+
+    /// Fetch the grammar into a temporary directory, which is moved into place when complete, so
+    /// that interrupted fetches do not leave a partial grammar in the cache.
+    fn fetch_into(&self, grammar: &RemoteGrammar, path: &Path) -> Result<(), LoadError<'static>> {
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        if tmp_path.exists() {
+            std::fs::remove_dir_all(&tmp_path)?;
+        }
+        std::fs::create_dir_all(&tmp_path)?;
+
+        let revision = grammar.revision.as_deref().unwrap_or("HEAD");
+        git(grammar, &tmp_path, &["init", "--quiet"])?;
+        git(
+            grammar,
+            &tmp_path,
+            &[
+                "fetch",
+                "--quiet",
+                "--depth",
+                "1",
+                grammar.url.as_str(),
+                revision,
+            ],
+        )?;
+        git(
+            grammar,
+            &tmp_path,
+            &["checkout", "--quiet", "--detach", "FETCH_HEAD"],
+        )?;
+
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Verify that the checked out content of the grammar matches the given tree hash.
+    fn verify(
+        grammar: &RemoteGrammar,
+        path: &Path,
+        checksum: &str,
+    ) -> Result<(), LoadError<'static>> {
+        let actual = git(grammar, path, &["rev-parse", "HEAD^{tree}"])?;
+        if actual != checksum {
+            return Err(LoadError::GrammarChecksumMismatch {
+                url: grammar.url.clone(),
+                expected: checksum.to_string(),
+                actual,
+            });
+        }
+        let modified = git(grammar, path, &["status", "--porcelain"])?;
+        if !modified.is_empty() {
+            return Err(LoadError::GrammarFetch {
+                url: grammar.url.clone(),
+                message: format!("cached grammar at {} was modified", path.display()),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Run a git command in the given directory, and return its trimmed output.
+fn git<S: AsRef<OsStr>>(
+    grammar: &RemoteGrammar,
+    dir: &Path,
+    args: &[S],
+) -> Result<String, LoadError<'static>> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(LoadError::GrammarFetch {
+            url: grammar.url.clone(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,151 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Attributes the cost of stack graph construction to the stanzas of the TSG rules.
+//!
+//! A [rule profile][RuleProfile] records for every stanza of a language how often its query
+//! matched, how long matching its query took, and how many nodes and edges its statements
+//! created.  Nodes and edges are attributed to stanzas using the `tsg_rule` debug entry that is
+//! recorded during construction, see [`StackGraph::node_rule`][].
+//!
+//! The statements of all stanzas are executed lazily and interleaved, so execution time cannot be
+//! attributed to individual stanzas.  Instead, the query of each stanza is matched and timed
+//! separately, and the number of nodes and edges that a stanza creates stands in for the cost of
+//! executing it.  Stanzas with many matches or graph elements on large or generated files are the
+//! ones to look at first.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use std::time::Instant;
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::File;
+use stack_graphs::graph::StackGraph;
+use tree_sitter::Tree;
+
+use crate::StackGraphLanguage;
+
+/// The cost of the stanzas of a language on one or more source files.
+#[derive(Clone, Debug, Default)]
+pub struct RuleProfile {
+    /// The statistics per stanza, keyed by the location of the stanza as `PATH:LINE:COLUMN`.
+    pub rules: BTreeMap<String, RuleStats>,
+    /// The number of nodes that are not attributed to any stanza.
+    pub unattributed_nodes: usize,
+    /// The number of edges that are not attributed to any stanza.
+    pub unattributed_edges: usize,
+}
+
+/// The cost of a single stanza.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    /// The number of times the query of the stanza matched.
+    pub matches: usize,
+    /// The time it took to find all matches of the query of the stanza.
+    pub match_time: Duration,
+    /// The number of nodes created by the stanza.
+    pub nodes: usize,
+    /// The number of edges created by the stanza.
+    pub edges: usize,
+}
+
+impl RuleProfile {
+    /// Computes the profile of the rules of a language on a file.  The tree must be the parse tree
+    /// of the given source, and the graph must have been built from it with the rules of the
+    /// language.  All stanzas of the language are included, even if they did not match.
+    pub fn compute(
+        sgl: &StackGraphLanguage,
+        tree: &Tree,
+        source: &str,
+        graph: &StackGraph,
+        file: Handle<File>,
+    ) -> Self {
+        let mut profile = Self::default();
+        for stanza in &sgl.tsg.stanzas {
+            let mut stats = RuleStats::default();
+            let start = Instant::now();
+            let _: Result<(), Infallible> = stanza.try_visit_matches(tree, source, |_| {
+                stats.matches += 1;
+                Ok(())
+            });
+            stats.match_time = start.elapsed();
+            profile.rules.insert(sgl.stanza_rule(stanza), stats);
+        }
+
+        // edges from the root node are stored with the root node, which does not belong to the
+        // file, so they are included separately
+        let root = StackGraph::root_node();
+        let root_edges = graph
+            .outgoing_edges(root)
+            .filter(|edge| graph[edge.sink].file() == Some(file));
+        for node in graph.nodes_for_file(file) {
+            match graph.node_rule(node) {
+                Some(rule) => profile.rules.entry(rule.to_string()).or_default().nodes += 1,
+                None => profile.unattributed_nodes += 1,
+            }
+        }
+        for edge in root_edges.chain(
+            graph
+                .nodes_for_file(file)
+                .flat_map(|node| graph.outgoing_edges(node)),
+        ) {
+            match graph.edge_rule(edge.source, edge.sink) {
+                Some(rule) => profile.rules.entry(rule.to_string()).or_default().edges += 1,
+                None => profile.unattributed_edges += 1,
+            }
+        }
+        profile
+    }
+
+    /// Returns the sum of the statistics of all stanzas.
+    pub fn total(&self) -> RuleStats {
+        let mut total = RuleStats::default();
+        for stats in self.rules.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    /// Returns the stanzas ordered by decreasing cost, where the number of graph elements a stanza
+    /// created weighs more than its match count and match time.
+    pub fn most_expensive(&self) -> Vec<(&str, &RuleStats)> {
+        let mut rules = self
+            .rules
+            .iter()
+            .map(|(rule, stats)| (rule.as_str(), stats))
+            .collect::<Vec<_>>();
+        rules.sort_by(|a, b| {
+            (b.1.nodes + b.1.edges)
+                .cmp(&(a.1.nodes + a.1.edges))
+                .then_with(|| b.1.matches.cmp(&a.1.matches))
+                .then_with(|| b.1.match_time.cmp(&a.1.match_time))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        rules
+    }
+
+    /// Adds the statistics of another profile to this one, to aggregate the profiles of several
+    /// files.
+    pub fn add(&mut self, other: &RuleProfile) {
+        for (rule, stats) in &other.rules {
+            self.rules.entry(rule.clone()).or_default().add(stats);
+        }
+        self.unattributed_nodes += other.unattributed_nodes;
+        self.unattributed_edges += other.unattributed_edges;
+    }
+}
+
+impl RuleStats {
+    /// Adds the statistics of another stanza, or of the same stanza on another file, to these.
+    pub fn add(&mut self, other: &RuleStats) {
+        self.matches += other.matches;
+        self.match_time += other.match_time;
+        self.nodes += other.nodes;
+        self.edges += other.edges;
+    }
+}
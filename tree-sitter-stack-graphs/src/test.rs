@@ -56,26 +56,34 @@
 //! to a fragment.
 //!
 //! Any content before the first fragment header of the file is ignored, and will not be part of the test.
+//!
+//! ## Snapshots
+//!
+//! Besides assertions, the stack graph of a test can be compared to a golden snapshot.  A
+//! snapshot, as returned by [`Test::snapshot`][], lists the nodes, edges, and minimal partial
+//! paths of every fragment in a canonical text format, so that any change to the constructed
+//! graph shows up as a readable diff when checking it with [`Test::check_snapshot`][].
 
 use itertools::Itertools;
 use lsp_positions::Position;
 use lsp_positions::PositionedSubstring;
-use lsp_positions::SpanCalculator;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use stack_graphs::arena::Handle;
 use stack_graphs::assert::Assertion;
 use stack_graphs::assert::AssertionError;
-use stack_graphs::assert::AssertionSource;
-use stack_graphs::assert::AssertionTarget;
+use stack_graphs::assert::AssertionParseError;
+use stack_graphs::assert::AssertionSyntax;
 use stack_graphs::graph::File;
 use stack_graphs::graph::Node;
 use stack_graphs::graph::SourceInfo;
 use stack_graphs::graph::StackGraph;
 use stack_graphs::partial::PartialPaths;
 use stack_graphs::stitching::Database;
+use stack_graphs::stitching::ForwardPartialPathStitcher;
 use stack_graphs::stitching::StitcherConfig;
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -83,18 +91,10 @@ use tree_sitter_graph::Variables;
 
 use crate::CancellationFlag;
 
-const DEFINED: &'static str = "defined";
-const DEFINES: &'static str = "defines";
-const REFERS: &'static str = "refers";
-
 static PATH_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r#"---\s*path:\s*([^\s]+)\s*---"#).unwrap());
 static GLOBAL_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r#"---\s*global:\s*([^\s]+)=([^\s]+)\s*---"#).unwrap());
-static ASSERTION_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r#"(\^)\s*(\w+):\s*([^\s,]+(?:\s*,\s*[^\s,]+)*)?"#).unwrap());
-static LINE_NUMBER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\d+"#).unwrap());
-static NAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[^\s,]+"#).unwrap());
 
 /// An error that can occur while parsing tests
 #[derive(Debug, Error)]
@@ -145,6 +145,22 @@ impl std::fmt::Display for TestError {
     }
 }
 
+impl From<AssertionParseError> for TestError {
+    fn from(err: AssertionParseError) -> Self {
+        match err {
+            AssertionParseError::AssertionRefersToNonSourceLine(line) => {
+                Self::AssertionRefersToNonSourceLine(line)
+            }
+            AssertionParseError::InvalidAssertion(line, assertion) => {
+                Self::InvalidAssertion(line, assertion)
+            }
+            AssertionParseError::InvalidColumn(line, column, regular_line) => {
+                Self::InvalidColumn(line, column, regular_line)
+            }
+        }
+    }
+}
+
 /// A stack graph test
 pub struct Test {
     pub path: PathBuf,
@@ -297,100 +313,13 @@ impl TestFragment {
     where
         F: Fn(usize) -> Option<Handle<File>>,
     {
-        self.assertions.clear();
-
-        let mut current_line_span_calculator = SpanCalculator::new(&self.source);
-        let mut last_regular_line: Option<PositionedSubstring> = None;
-        let mut last_regular_line_number = None;
-        let mut last_regular_line_span_calculator = SpanCalculator::new(&self.source);
-        for (current_line_number, current_line) in
-            PositionedSubstring::lines_iter(&self.source).enumerate()
-        {
-            if let Some(m) = ASSERTION_REGEX.captures_iter(current_line.content).next() {
-                // assertion line
-                let last_regular_line = last_regular_line.as_ref().ok_or_else(|| {
-                    TestError::AssertionRefersToNonSourceLine(current_line_number)
-                })?;
-                let last_regular_line_number = last_regular_line_number.unwrap();
-
-                let carret_match = m.get(1).unwrap();
-                let assertion_match = m.get(2).unwrap();
-                let values_match = m.get(3);
-
-                let column_utf8_offset = carret_match.start();
-                let column_grapheme_offset = current_line_span_calculator
-                    .for_line_and_column(
-                        current_line_number,
-                        current_line.utf8_bounds.start,
-                        column_utf8_offset,
-                    )
-                    .column
-                    .grapheme_offset;
-                if column_grapheme_offset >= last_regular_line.grapheme_length {
-                    return Err(TestError::InvalidColumn(
-                        current_line_number,
-                        column_grapheme_offset,
-                        last_regular_line_number,
-                    ));
-                }
-                let position = last_regular_line_span_calculator.for_line_and_grapheme(
-                    last_regular_line_number,
-                    last_regular_line.utf8_bounds.start,
-                    column_grapheme_offset,
-                );
-                let source = AssertionSource {
-                    file: self.file,
-                    position,
-                };
-
-                match assertion_match.as_str() {
-                    DEFINED => {
-                        let mut targets = Vec::new();
-                        for line in LINE_NUMBER_REGEX
-                            .find_iter(values_match.map(|m| m.as_str()).unwrap_or(""))
-                        {
-                            let line = line.as_str().parse::<usize>().unwrap() - 1;
-                            let file = line_file(line).ok_or(
-                                TestError::AssertionRefersToNonSourceLine(current_line_number),
-                            )?;
-                            targets.push(AssertionTarget { file, line });
-                        }
-                        self.assertions.push(Assertion::Defined { source, targets });
-                    }
-                    DEFINES => {
-                        let mut symbols = Vec::new();
-                        for name in
-                            NAME_REGEX.find_iter(values_match.map(|m| m.as_str()).unwrap_or(""))
-                        {
-                            let symbol = graph.add_symbol(name.as_str());
-                            symbols.push(symbol);
-                        }
-                        self.assertions.push(Assertion::Defines { source, symbols });
-                    }
-                    REFERS => {
-                        let mut symbols = Vec::new();
-                        for name in
-                            NAME_REGEX.find_iter(values_match.map(|m| m.as_str()).unwrap_or(""))
-                        {
-                            let symbol = graph.add_symbol(name.as_str());
-                            symbols.push(symbol);
-                        }
-                        self.assertions.push(Assertion::Refers { source, symbols });
-                    }
-                    _ => {
-                        return Err(TestError::InvalidAssertion(
-                            current_line_number,
-                            assertion_match.as_str().to_string(),
-                        ));
-                    }
-                }
-            } else {
-                // regular source line
-                last_regular_line = Some(current_line);
-                last_regular_line_number = Some(current_line_number);
-            }
-        }
-
+        self.assertions = stack_graphs::assert::parse_with_line_files(
+            graph,
+            self.file,
+            &self.source,
+            &AssertionSyntax::default(),
+            line_file,
+        )?;
         Ok(())
     }
 }
@@ -491,6 +420,11 @@ pub enum TestFailure {
         missing_symbols: Vec<String>,
         unexpected_symbols: Vec<String>,
     },
+    SnapshotMismatch {
+        path: PathBuf,
+        snapshot_path: PathBuf,
+        diff: String,
+    },
     Cancelled(stack_graphs::CancellationError),
 }
 
@@ -611,6 +545,19 @@ impl std::fmt::Display for TestFailure {
                 }
                 Ok(())
             }
+            Self::SnapshotMismatch {
+                path,
+                snapshot_path,
+                diff,
+            } => {
+                writeln!(
+                    f,
+                    "{}: stack graph does not match snapshot {}",
+                    path.display(),
+                    snapshot_path.display()
+                )?;
+                write!(f, "{}", diff.trim_end())
+            }
             Self::Cancelled(err) => write!(f, "{}", err),
         }
     }
@@ -648,6 +595,92 @@ impl Test {
         Ok(result)
     }
 
+    /// Returns a snapshot of the nodes, edges, and minimal partial paths of the test fragments.
+    /// The snapshot uses a canonical text format, which does not depend on the order in which
+    /// paths are found, and is suitable to be stored as a golden file.
+    pub fn snapshot(
+        &self,
+        partials: &mut PartialPaths,
+        stitcher_config: StitcherConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<String, stack_graphs::CancellationError> {
+        let graph = &self.graph;
+        let mut snapshot = String::new();
+        for fragment in &self.fragments {
+            writeln!(snapshot, "--- path: {} ---", fragment.path.display()).unwrap();
+            let nodes = graph
+                .nodes_for_file(fragment.file)
+                .sorted_by_key(|n| graph[*n].id().local_id())
+                .collect::<Vec<_>>();
+            for node in &nodes {
+                write!(snapshot, "node {}", graph[*node].display(graph)).unwrap();
+                if let Some(source_info) = self.get_source_info(*node) {
+                    let span = &source_info.span;
+                    write!(
+                        snapshot,
+                        " at {}:{}-{}:{}",
+                        span.start.line + 1,
+                        span.start.column.grapheme_offset + 1,
+                        span.end.line + 1,
+                        span.end.column.grapheme_offset + 1
+                    )
+                    .unwrap();
+                }
+                writeln!(snapshot).unwrap();
+            }
+            for node in &nodes {
+                let edges = graph.outgoing_edges(*node).sorted_by_key(|e| {
+                    let id = graph[e.sink].id();
+                    (id.file().map(|f| graph[f].name()), id.local_id())
+                });
+                for edge in edges {
+                    writeln!(
+                        snapshot,
+                        "edge {} -> {} precedence {}",
+                        graph[edge.source].display(graph),
+                        graph[edge.sink].display(graph),
+                        edge.precedence
+                    )
+                    .unwrap();
+                }
+            }
+            let mut paths = Vec::new();
+            ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+                graph,
+                partials,
+                fragment.file,
+                stitcher_config,
+                &cancellation_flag,
+                |g, ps, p| paths.push(p.display(g, ps).to_string()),
+            )?;
+            for path in paths.into_iter().sorted() {
+                writeln!(snapshot, "path {}", path).unwrap();
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Checks a snapshot of this test, as returned by [`Test::snapshot`][], against the expected
+    /// snapshot that was read from `snapshot_path`.  The result contains a single success, or a
+    /// single failure that describes the differences.
+    pub fn check_snapshot(
+        &self,
+        snapshot: &str,
+        expected: &str,
+        snapshot_path: &Path,
+    ) -> TestResult {
+        let mut result = TestResult::new();
+        match snapshot_diff(expected, snapshot) {
+            None => result.add_success(),
+            Some(diff) => result.add_failure(TestFailure::SnapshotMismatch {
+                path: self.path.clone(),
+                snapshot_path: snapshot_path.to_path_buf(),
+                diff,
+            }),
+        }
+        result
+    }
+
     /// Construct a TestFailure from an AssertionError.
     fn from_error(&self, err: AssertionError) -> Result<(), TestFailure> {
         match err {
@@ -767,3 +800,85 @@ impl TestFragment {
         }
     }
 }
+
+/// The number of unchanged lines that are shown around changes in a snapshot diff.
+const SNAPSHOT_DIFF_CONTEXT: usize = 2;
+
+/// Returns a line-based diff between an expected and an actual snapshot, or `None` if they have
+/// the same lines.  Removed lines are prefixed with `-`, added lines with `+`, and unchanged lines
+/// around changes with a space.  Omitted unchanged lines are indicated by `...`.
+pub fn snapshot_diff(expected: &str, actual: &str) -> Option<String> {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    if expected == actual {
+        return None;
+    }
+
+    // compute the longest common subsequences of all suffixes, after stripping the common prefix
+    // and suffix, which are usually most of the snapshot
+    let prefix = expected
+        .iter()
+        .zip(actual.iter())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let removed = &expected[prefix..expected.len() - suffix];
+    let added = &actual[prefix..actual.len() - suffix];
+    let mut lcs = vec![vec![0usize; added.len() + 1]; removed.len() + 1];
+    for i in (0..removed.len()).rev() {
+        for j in (0..added.len()).rev() {
+            lcs[i][j] = if removed[i] == added[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(expected.len() + added.len());
+    lines.extend(expected[..prefix].iter().map(|l| (' ', *l)));
+    let (mut i, mut j) = (0, 0);
+    while i < removed.len() || j < added.len() {
+        if i < removed.len() && j < added.len() && removed[i] == added[j] {
+            lines.push((' ', removed[i]));
+            i += 1;
+            j += 1;
+        } else if i < removed.len() && (j == added.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', removed[i]));
+            i += 1;
+        } else {
+            lines.push(('+', added[j]));
+            j += 1;
+        }
+    }
+    lines.extend(
+        expected[expected.len() - suffix..]
+            .iter()
+            .map(|l| (' ', *l)),
+    );
+
+    let mut diff = String::new();
+    let mut omitted = false;
+    for (index, (tag, line)) in lines.iter().enumerate() {
+        let context = &lines[index.saturating_sub(SNAPSHOT_DIFF_CONTEXT)
+            ..(index + SNAPSHOT_DIFF_CONTEXT + 1).min(lines.len())];
+        if context.iter().all(|(tag, _)| *tag == ' ') {
+            omitted = true;
+            continue;
+        }
+        if omitted {
+            diff.push_str("...\n");
+            omitted = false;
+        }
+        writeln!(diff, "{}{}", tag, line).unwrap();
+    }
+    if omitted {
+        diff.push_str("...\n");
+    }
+    Some(diff)
+}
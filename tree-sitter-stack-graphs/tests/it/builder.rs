@@ -6,7 +6,11 @@
 // ------------------------------------------------------------------------------------------------
 
 use stack_graphs::graph::StackGraph;
+use std::collections::HashSet;
 use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::loader::FileReader;
+use tree_sitter_stack_graphs::BuildError;
+use tree_sitter_stack_graphs::GraphChunkSink;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
 use tree_sitter_stack_graphs::FILE_PATH_VAR;
@@ -85,3 +89,229 @@ fn can_support_injected_nodes() {
         &["[test.py(1) scope] -0-> [test.py(0) exported scope]"],
     );
 }
+
+#[test]
+fn can_build_injected_languages() {
+    let host_tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    (module (expression_statement (assignment left:(identifier)@name)))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      edge @mod.lexical_scope -> def
+    }
+    (module (expression_statement (string (string_content)@content)))@mod {
+      node injection_scope
+      attr (injection_scope) injection_language = "py", injection_content = @content
+      edge injection_scope -> @mod.lexical_scope
+    }
+    "#;
+    let injected_tsg = r#"
+    global INJECTION_SCOPE_NODE
+    (module)@mod {
+      node @mod.lexical_scope
+      edge @mod.lexical_scope -> INJECTION_SCOPE_NODE
+    }
+    (module (expression_statement (identifier)@name))@mod {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+      edge ref -> @mod.lexical_scope
+    }
+    "#;
+    let python = "x = 1\n\"x\"\n";
+
+    let file_name = "test.py";
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let host = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), host_tsg).unwrap();
+    let injected =
+        StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), injected_tsg).unwrap();
+    host.build_stack_graph_with_injections_into(
+        &mut graph,
+        file,
+        python,
+        &globals,
+        &|name| if name == "py" { Some(&injected) } else { None },
+        &NoCancellation,
+    )
+    .expect("Failed to build graph");
+
+    let reference = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_reference())
+        .expect("missing injected reference");
+    let definition = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_definition())
+        .expect("missing definition");
+
+    // the injected reference has a source span in the original file
+    let span = &graph.source_info(reference).unwrap().span;
+    assert_eq!(1, span.start.line);
+    assert_eq!(1, span.start.column.utf8_offset);
+
+    // the injected reference reaches the definition through the injection scope
+    let mut seen = HashSet::new();
+    let mut queue = vec![reference];
+    while let Some(node) = queue.pop() {
+        if seen.insert(node) {
+            queue.extend(graph.outgoing_edges(node).map(|e| e.sink));
+        }
+    }
+    assert!(seen.contains(&definition));
+}
+
+#[test]
+fn spans_in_files_with_byte_order_mark_match_the_file() {
+    let tsg = r#"
+    (module (expression_statement (assignment left:(identifier)@name)))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+    }
+    "#;
+    let python = FileReader::decode(b"\xEF\xBB\xBFx = 1\n").unwrap();
+
+    let file_name = "test.py";
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    language
+        .build_stack_graph_into(&mut graph, file, &python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    let definition = graph
+        .nodes_for_file(file)
+        .find(|n| graph[*n].is_definition())
+        .expect("missing definition");
+    assert_eq!("x", &graph[graph[definition].symbol().unwrap()]);
+    let span = &graph.source_info(definition).unwrap().span;
+    assert_eq!(0, span.start.line);
+    assert_eq!(3, span.start.column.utf8_offset);
+}
+
+#[test]
+fn can_build_partial_graphs_for_files_with_parse_errors() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    (module (expression_statement (assignment left:(identifier)@name)))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      edge @mod.lexical_scope -> def
+    }
+    "#;
+    let python = "x = 1\n)\n";
+
+    let file_name = "test.py";
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    assert!(matches!(
+        language.build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation),
+        Err(BuildError::ParseErrors(_))
+    ));
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    language
+        .build_stack_graph_with_error_recovery_into(
+            &mut graph,
+            file,
+            python,
+            &globals,
+            &|_| None,
+            &NoCancellation,
+        )
+        .expect("Failed to build graph");
+    assert!(!graph.parse_errors(file).is_empty());
+    assert!(graph
+        .nodes_for_file(file)
+        .any(|node| graph[node].is_definition()
+            && graph[node].symbol().map(|symbol| graph[symbol].as_str()) == Some("x")));
+}
+
+struct ChunkCollector(Vec<stack_graphs::serde::StackGraph>);
+
+impl GraphChunkSink for ChunkCollector {
+    fn add_chunk(&mut self, chunk: stack_graphs::serde::StackGraph) -> Result<(), BuildError> {
+        self.0.push(chunk);
+        Ok(())
+    }
+}
+
+#[test]
+fn can_build_graphs_in_chunks() {
+    let tsg = r#"
+    global ROOT_NODE
+    (module)@mod {
+      node @mod.defs
+      attr (@mod.defs) is_exported
+      edge ROOT_NODE -> @mod.defs
+    }
+    (module (function_definition name:(identifier)@name))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      node ref
+      attr (ref) type = "push_scoped_symbol", symbol = (source-text @name), scope = @mod.defs, is_reference
+      edge @mod.defs -> def
+      edge ref -> ROOT_NODE
+    }
+    "#;
+    let python = "def a(): pass\ndef b(): pass\ndef c(): pass\n";
+
+    let file_name = "test.py";
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), file_name.into())
+        .expect("failed to add file path variable");
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+
+    let mut expected = StackGraph::new();
+    let file = expected.get_or_create_file(file_name);
+    language
+        .build_stack_graph_into(&mut expected, file, python, &globals, &NoCancellation)
+        .expect("Failed to build graph");
+
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file(file_name);
+    let mut chunks = ChunkCollector(Vec::new());
+    let mut builder = language.builder_into_stack_graph(&mut graph, file, python);
+    builder.set_chunk_sink(2, &mut chunks);
+    builder
+        .build(&globals, &NoCancellation)
+        .expect("Failed to build graph");
+    assert_eq!(0, graph.nodes_for_file(file).count());
+    assert_eq!(5, chunks.0.len());
+
+    for chunk in &chunks.0 {
+        chunk.load_nodes_into(&mut graph).unwrap();
+    }
+    for chunk in &chunks.0 {
+        chunk.load_edges_into(&mut graph).unwrap();
+    }
+    assert_eq!(
+        stack_graphs::serde::StackGraph::from_graph(&expected),
+        stack_graphs::serde::StackGraph::from_graph(&graph)
+    );
+}
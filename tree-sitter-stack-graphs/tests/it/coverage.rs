@@ -0,0 +1,58 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use tree_sitter::Parser;
+use tree_sitter_stack_graphs::coverage::FileCoverage;
+
+use crate::build_stack_graph;
+
+#[test]
+fn can_compute_identifier_coverage() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    (module (expression_statement (assignment left:(identifier)@name)))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      edge @mod.lexical_scope -> def
+    }
+    (module (expression_statement (call arguments:(argument_list (identifier)@name))))@mod {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+      edge ref -> @mod.lexical_scope
+    }
+    "#;
+    let python = "x = 1\nprint(x)\n";
+
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(python, None).unwrap();
+    let coverage = FileCoverage::compute(&tree, python, &graph, file);
+
+    assert_eq!(3, coverage.identifiers);
+    assert_eq!(1, coverage.definitions);
+    assert_eq!(1, coverage.references);
+    assert_eq!(2, coverage.covered());
+    assert_eq!(
+        vec![("print", "identifier", Some("call"), 1)],
+        coverage
+            .uncovered
+            .iter()
+            .map(|i| (
+                i.text.as_str(),
+                i.kind.as_str(),
+                i.parent_kind.as_deref(),
+                i.start.row
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(&1), coverage.uncovered_constructs().get("call"),);
+}
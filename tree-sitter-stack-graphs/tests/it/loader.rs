@@ -10,11 +10,17 @@ use pretty_assertions::assert_eq;
 use stack_graphs::graph::StackGraph;
 use std::path::PathBuf;
 use tree_sitter::Language;
+use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::loader::remote::GrammarCache;
+use tree_sitter_stack_graphs::loader::remote::RemoteGrammar;
 use tree_sitter_stack_graphs::loader::FileAnalyzers;
+use tree_sitter_stack_graphs::loader::FileReader;
 use tree_sitter_stack_graphs::loader::LanguageConfiguration;
 use tree_sitter_stack_graphs::loader::Loader;
+use tree_sitter_stack_graphs::loader::SkipReason;
 use tree_sitter_stack_graphs::NoCancellation;
 use tree_sitter_stack_graphs::StackGraphLanguage;
+use tree_sitter_stack_graphs::FILE_PATH_VAR;
 
 static PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("test.py"));
 static TSG: Lazy<String> = Lazy::new(|| {
@@ -51,3 +57,156 @@ fn can_load_from_provided_language_configuration() {
         .expect("Expected loading stack graph language to succeed");
     assert_eq!(lc.primary.map(|lc| &lc.language), Some(&language));
 }
+
+#[test]
+fn injected_languages_are_loaded_when_they_are_needed() {
+    let host_tsg = r#"
+    (module (expression_statement (string (string_content)@content)))@mod {
+      node injection_scope
+      attr (injection_scope) injection_language = "injected", injection_content = @content
+    }
+    "#;
+    let injected_tsg = r#"
+    global INJECTION_SCOPE_NODE
+    (module (expression_statement (identifier)@name)) {
+      node ref
+      attr (ref) type = "push_symbol", symbol = (source-text @name), source_node = @name, is_reference
+      edge ref -> INJECTION_SCOPE_NODE
+    }
+    "#;
+    let language: Language = tree_sitter_python::LANGUAGE.into();
+    let configuration = |scope: &str, file_type: &str, tsg: &str| LanguageConfiguration {
+        language: language.clone(),
+        scope: Some(scope.into()),
+        content_regex: None,
+        file_types: vec![file_type.into()],
+        sgl: StackGraphLanguage::from_str(language.clone(), tsg).unwrap(),
+        builtins: StackGraph::new(),
+        special_files: FileAnalyzers::new(),
+        no_similar_paths_in_file: false,
+    };
+    let mut loader = Loader::from_language_configurations(
+        vec![
+            configuration("source.py", "py", host_tsg),
+            configuration("source.injected", "inj", injected_tsg),
+        ],
+        None,
+    )
+    .expect("Expected loader to succeed");
+
+    let python = "\"x\"\n";
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), "test.py".into())
+        .expect("failed to add file path variable");
+
+    // only the language of the file is loaded here, no file of the injected language was loaded
+    let injections = {
+        let lcs = loader
+            .load_for_file(&PATH, &mut Some(python), &NoCancellation)
+            .expect("Expected loading stack graph language to succeed");
+        let host = &lcs.primary.expect("Expected host language").sgl;
+        host.builder_into_stack_graph(&mut graph, file, python)
+            .build_with_injections(&globals, &NoCancellation)
+            .expect("Failed to build graph")
+    };
+    assert_eq!(1, injections.len());
+    assert!(!graph.nodes_for_file(file).any(|n| graph[n].is_reference()));
+
+    loader
+        .build_injections_into(
+            &mut graph,
+            file,
+            python,
+            &globals,
+            injections,
+            false,
+            &NoCancellation,
+        )
+        .expect("Failed to build injections");
+    assert!(graph.nodes_for_file(file).any(|n| graph[n].is_reference()));
+}
+
+#[test]
+fn can_parse_remote_grammars() {
+    assert_eq!(
+        Ok(RemoteGrammar::new(
+            "https://example.com/tree-sitter-foo.git"
+        )),
+        "https://example.com/tree-sitter-foo.git".parse()
+    );
+    assert_eq!(
+        Ok(RemoteGrammar::new("https://example.com/tree-sitter-foo.git").with_revision("v1.0")),
+        "https://example.com/tree-sitter-foo.git#v1.0".parse()
+    );
+    assert_eq!(
+        Ok(RemoteGrammar::new("git@example.com:tree-sitter-foo.git")
+            .with_revision("v1.0")
+            .with_checksum("abc123")),
+        "git@example.com:tree-sitter-foo.git#v1.0:abc123".parse()
+    );
+    assert_eq!(
+        Ok(RemoteGrammar::new("https://example.com/tree-sitter-foo.git").with_checksum("abc123")),
+        "https://example.com/tree-sitter-foo.git#:abc123".parse()
+    );
+    assert!("#v1.0".parse::<RemoteGrammar>().is_err());
+}
+
+#[test]
+fn remote_grammars_are_cached_per_revision() {
+    let cache = GrammarCache::new("cache");
+    let grammar = RemoteGrammar::new("https://example.com/tree-sitter-foo.git");
+    assert_eq!(
+        PathBuf::from("cache/example.com_tree-sitter-foo@HEAD"),
+        cache.path_for(&grammar)
+    );
+    assert_eq!(
+        PathBuf::from("cache/example.com_tree-sitter-foo@v1.0"),
+        cache.path_for(&grammar.with_revision("v1.0"))
+    );
+}
+
+#[test]
+fn file_reader_detects_encodings() {
+    assert_eq!(Ok("pass".to_string()), FileReader::decode(b"pass"));
+    assert_eq!(
+        Ok("\u{FEFF}pass".to_string()),
+        FileReader::decode(b"\xEF\xBB\xBFpass")
+    );
+    assert_eq!(
+        Ok("pass".to_string()),
+        FileReader::decode(b"\xFF\xFEp\0a\0s\0s\0")
+    );
+    assert_eq!(
+        Ok("pass".to_string()),
+        FileReader::decode(b"\xFE\xFF\0p\0a\0s\0s")
+    );
+    assert_eq!(Ok("pass".to_string()), FileReader::decode(b"p\0a\0s\0s\0"));
+    assert_eq!(
+        Ok("pa\u{FFFD}ss".to_string()),
+        FileReader::decode(b"pa\xFFss")
+    );
+}
+
+#[test]
+fn file_reader_skips_binary_and_large_files() {
+    assert_eq!(
+        Err(SkipReason::Binary),
+        FileReader::decode(b"\x7FELF\x02\x01\x01\0\0\0")
+    );
+
+    let path = std::env::temp_dir().join(format!("file-reader-{}.py", std::process::id()));
+    std::fs::write(&path, "x = 1\n").unwrap();
+    let mut reader = FileReader::new().with_max_size(Some(4));
+    let err = reader.get(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        Some(&SkipReason::TooLarge {
+            size: 6,
+            max_size: 4
+        }),
+        SkipReason::from_io_error(&err)
+    );
+}
@@ -17,9 +17,11 @@ use tree_sitter_stack_graphs::StackGraphLanguage;
 use tree_sitter_stack_graphs::FILE_PATH_VAR;
 
 mod builder;
+mod coverage;
 mod edges;
 mod loader;
 mod nodes;
+mod profile;
 mod test;
 
 pub(self) fn build_stack_graph(
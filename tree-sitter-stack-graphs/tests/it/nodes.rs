@@ -285,6 +285,27 @@ fn can_calculate_spans() {
     assert_eq!(trimmed_line, "a");
 }
 
+#[test]
+fn can_record_rules() {
+    let tsg = r#"
+      global ROOT_NODE
+
+      (identifier) @id {
+         node result
+         attr (result) type = "pop_symbol", symbol = (source-text @id), is_definition
+         edge ROOT_NODE -> result
+      }
+    "#;
+    let python = "a";
+    let (graph, file) = build_stack_graph(python, tsg).unwrap();
+    let node_handle = graph.nodes_for_file(file).next().unwrap();
+    assert_eq!(Some("<tsg>:4:7"), graph.node_rule(node_handle));
+    assert_eq!(
+        Some("<tsg>:4:7"),
+        graph.edge_rule(StackGraph::root_node(), node_handle)
+    );
+}
+
 #[test]
 fn can_set_definiens() {
     let tsg = r#"
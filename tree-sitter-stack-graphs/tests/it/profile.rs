@@ -0,0 +1,70 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2024, stack-graphs authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+use stack_graphs::graph::StackGraph;
+use tree_sitter::Parser;
+use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::profile::RuleProfile;
+use tree_sitter_stack_graphs::NoCancellation;
+use tree_sitter_stack_graphs::StackGraphLanguage;
+use tree_sitter_stack_graphs::FILE_PATH_VAR;
+
+#[test]
+fn can_attribute_nodes_and_edges_to_stanzas() {
+    let tsg = r#"
+    (module)@mod {
+      node @mod.lexical_scope
+    }
+    (module (expression_statement (assignment left:(identifier)@name)))@mod {
+      node def
+      attr (def) type = "pop_symbol", symbol = (source-text @name), source_node = @name, is_definition
+      edge @mod.lexical_scope -> def
+    }
+    (module (function_definition))@mod {
+      node @mod.unused
+    }
+    "#;
+    let python = "x = 1\ny = 2\n";
+
+    let language = StackGraphLanguage::from_str(tree_sitter_python::LANGUAGE.into(), tsg).unwrap();
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("test.py");
+    let mut globals = Variables::new();
+    globals
+        .add(FILE_PATH_VAR.into(), "test.py".into())
+        .expect("failed to add file path variable");
+    language
+        .build_stack_graph_into(&mut graph, file, python, &globals, &NoCancellation)
+        .unwrap();
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(python, None).unwrap();
+    let profile = RuleProfile::compute(&language, &tree, python, &graph, file);
+
+    assert_eq!(
+        vec![
+            ("<missing tsg path>:5:5", 2, 2, 2),
+            ("<missing tsg path>:2:5", 1, 1, 0),
+            ("<missing tsg path>:10:5", 0, 0, 0),
+        ],
+        profile
+            .most_expensive()
+            .into_iter()
+            .map(|(rule, stats)| (rule, stats.matches, stats.nodes, stats.edges))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(0, profile.unattributed_nodes);
+    assert_eq!(0, profile.unattributed_edges);
+
+    let mut total = RuleProfile::default();
+    total.add(&profile);
+    total.add(&profile);
+    assert_eq!(6, total.total().nodes);
+    assert_eq!(4, total.total().edges);
+}
@@ -18,6 +18,7 @@ use stack_graphs::stitching::StitcherConfig;
 use std::path::Path;
 use std::path::PathBuf;
 use tree_sitter_graph::Variables;
+use tree_sitter_stack_graphs::test::snapshot_diff;
 use tree_sitter_stack_graphs::test::Test;
 use tree_sitter_stack_graphs::BuildError;
 use tree_sitter_stack_graphs::NoCancellation;
@@ -345,3 +346,43 @@ fn test_cannot_set_global_before_first_fragment() {
         panic!("Parsing test unexpectedly succeeded.");
     }
 }
+
+#[test]
+fn test_can_check_snapshot() {
+    let python = r#"
+      x = 1;
+      x;
+    "#;
+    let mut test = Test::from_source(&PATH, python, &PATH).expect("Could not parse test");
+    let fragment = &test.fragments[0];
+    let (file, source) = (fragment.file, fragment.source.clone());
+    build_stack_graph_into(&mut test.graph, file, &source, &TSG, &Variables::new())
+        .expect("Could not load stack graph");
+    let mut partials = PartialPaths::new();
+    let snapshot = test
+        .snapshot(&mut partials, StitcherConfig::default(), &NoCancellation)
+        .expect("should never be cancelled");
+    assert!(snapshot.starts_with("--- path: test.py ---\n"));
+    assert!(snapshot.contains("\npath "));
+
+    let result = test.check_snapshot(&snapshot, &snapshot, Path::new("test.snapshot"));
+    assert_eq!(1, result.success_count());
+
+    let expected = snapshot.replacen("\npath ", "\npath removed\npath ", 1);
+    let result = test.check_snapshot(&snapshot, &expected, Path::new("test.snapshot"));
+    assert_eq!(1, result.failure_count());
+    let failure = result.failures_iter().next().unwrap().to_string();
+    assert!(failure.contains("does not match snapshot test.snapshot"));
+    assert!(failure.contains("\n-path removed\n"));
+}
+
+#[test]
+fn test_snapshot_diff_shows_context() {
+    let expected = "a\nb\nc\nd\ne\nf\ng\n";
+    let actual = "a\nb\nc\nd\nE\nf\ng\n";
+    assert_eq!(None, snapshot_diff(expected, expected));
+    assert_eq!(
+        Some("...\n c\n d\n-e\n+E\n f\n g\n".to_string()),
+        snapshot_diff(expected, actual)
+    );
+}